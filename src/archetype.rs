@@ -0,0 +1,352 @@
+use std::collections::HashMap;
+
+use wasm_bindgen::prelude::*;
+
+use crate::curves::Curve;
+use crate::error::Error;
+use crate::json::Json;
+use crate::math::Vec2;
+use crate::perception::Perception;
+use crate::utility::UtilityBrain;
+use crate::world::{AiWorld, Behavior};
+
+const FIELDS: [&str; 8] = [
+    "extends",
+    "max_speed",
+    "max_force",
+    "radius",
+    "behavior",
+    "sensors",
+    "behavior_tree",
+    "utility",
+];
+
+#[derive(Clone, Debug)]
+struct Sensors {
+    view_distance: f32,
+    fov: f32,
+    hearing: f32,
+}
+
+#[derive(Clone, Debug)]
+struct Consideration {
+    key: String,
+    min: f32,
+    max: f32,
+    curve: Curve,
+}
+
+#[derive(Clone, Debug)]
+struct Action {
+    name: String,
+    weight: f32,
+    considerations: Vec<Consideration>,
+}
+
+#[derive(Clone, Debug)]
+struct Archetype {
+    max_speed: f32,
+    max_force: f32,
+    radius: f32,
+    behavior: Behavior,
+    sensors: Option<Sensors>,
+    behavior_tree: Option<String>,
+    utility: Vec<Action>,
+}
+
+impl Default for Archetype {
+    fn default() -> Archetype {
+        Archetype {
+            max_speed: 1.0,
+            max_force: 1.0,
+            radius: 0.5,
+            behavior: Behavior::Idle,
+            sensors: None,
+            behavior_tree: None,
+            utility: Vec::new(),
+        }
+    }
+}
+
+/// Agent templates read from JSON, so speeds, senses and decision making can be
+/// tuned in data and agents spawned by name.
+#[wasm_bindgen]
+#[derive(Default)]
+pub struct Archetypes {
+    archetypes: HashMap<String, Archetype>,
+}
+
+#[wasm_bindgen]
+impl Archetypes {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Archetypes {
+        Archetypes::default()
+    }
+
+    /// Registers the archetypes of an object such as
+    ///
+    /// ```json
+    /// {
+    ///   "grunt": {
+    ///     "max_speed": 3, "max_force": 6, "radius": 0.5, "behavior": "wander",
+    ///     "sensors": {"view_distance": 12, "fov": 2.1, "hearing": 1},
+    ///     "behavior_tree": "grunt",
+    ///     "utility": [{"action": "flee", "weight": 1, "considerations": [
+    ///       {"key": "health", "min": 0, "max": 100,
+    ///        "curve": {"type": "linear", "slope": -1, "intercept": 1}}
+    ///     ]}]
+    ///   },
+    ///   "captain": {"extends": "grunt", "max_speed": 3.5}
+    /// }
+    /// ```
+    ///
+    /// and returns how many it read. Every field is optional; `extends` starts
+    /// from an archetype registered earlier or above in the same object, and
+    /// names already taken are replaced, so designers can reload tuned files.
+    /// `behavior` is one of `idle`, `seek`, `arrive`, `flee` and `wander`; `fov`
+    /// is in radians; curves take the parameters of the `Curve` constructors.
+    /// Unknown fields are errors, to catch typos, and nothing is registered
+    /// unless the whole object reads.
+    pub fn load_archetypes(&mut self, json: &str) -> Result<u32, Error> {
+        let Json::Object(entries) = Json::parse(json)? else {
+            return Err(Error::InvalidInput(
+                "archetypes must be an object of names to archetypes".into(),
+            ));
+        };
+        let mut loaded: Vec<(String, Archetype)> = Vec::with_capacity(entries.len());
+        for (name, definition) in &entries {
+            let archetype =
+                self.read(&loaded, definition)
+                    .map_err(|Error::InvalidInput(message)| {
+                        Error::InvalidInput(format!("archetype '{}': {}", name, message))
+                    })?;
+            match loaded.iter_mut().find(|(known, _)| known == name) {
+                Some((_, replaced)) => *replaced = archetype,
+                None => loaded.push((name.clone(), archetype)),
+            }
+        }
+        let count = loaded.len() as u32;
+        self.archetypes.extend(loaded);
+        Ok(count)
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.archetypes.contains_key(name)
+    }
+
+    pub fn remove(&mut self, name: &str) -> bool {
+        self.archetypes.remove(name).is_some()
+    }
+
+    /// Names of every archetype, sorted.
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.archetypes.keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn len(&self) -> u32 {
+        self.archetypes.len() as u32
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.archetypes.is_empty()
+    }
+
+    /// Adds an agent of archetype `name` to `world` at `(x, y)`, with its
+    /// speed, force and behavior, and returns its handle.
+    pub fn spawn(&self, world: &mut AiWorld, name: &str, x: f32, y: f32) -> Result<u32, Error> {
+        let archetype = self.get(name)?;
+        let handle = world.add_agent(x, y, archetype.max_speed, archetype.max_force)?;
+        world.set_behavior(handle, archetype.behavior);
+        Ok(handle)
+    }
+
+    pub fn max_speed(&self, name: &str) -> Option<f32> {
+        self.archetypes
+            .get(name)
+            .map(|archetype| archetype.max_speed)
+    }
+
+    pub fn max_force(&self, name: &str) -> Option<f32> {
+        self.archetypes
+            .get(name)
+            .map(|archetype| archetype.max_force)
+    }
+
+    pub fn radius(&self, name: &str) -> Option<f32> {
+        self.archetypes.get(name).map(|archetype| archetype.radius)
+    }
+
+    pub fn behavior(&self, name: &str) -> Option<Behavior> {
+        self.archetypes
+            .get(name)
+            .map(|archetype| archetype.behavior)
+    }
+
+    /// The behavior tree the archetype names, for the game to look up among the
+    /// trees it builds.
+    pub fn behavior_tree(&self, name: &str) -> Option<String> {
+        self.archetypes
+            .get(name)
+            .and_then(|archetype| archetype.behavior_tree.clone())
+    }
+
+    /// Adds an observer with the archetype's sensors to `perception` and returns
+    /// its id. Fails when the archetype has no `sensors`.
+    pub fn add_observer(
+        &self,
+        perception: &mut Perception,
+        name: &str,
+        position: Vec2,
+        facing: Vec2,
+    ) -> Result<u32, Error> {
+        let sensors =
+            self.get(name)?.sensors.as_ref().ok_or_else(|| {
+                Error::InvalidInput(format!("archetype '{}' has no sensors", name))
+            })?;
+        let id = perception.add_observer(position, facing, sensors.view_distance, sensors.fov)?;
+        perception.set_hearing(id, sensors.hearing);
+        Ok(id)
+    }
+
+    /// A fresh utility brain with the archetype's actions, in the order listed.
+    pub fn brain(&self, name: &str) -> Result<UtilityBrain, Error> {
+        let mut brain = UtilityBrain::new();
+        for action in &self.get(name)?.utility {
+            let id = brain.add_action(&action.name, action.weight);
+            for consideration in &action.considerations {
+                brain.add_consideration(
+                    id,
+                    &consideration.key,
+                    consideration.min,
+                    consideration.max,
+                    &consideration.curve,
+                )?;
+            }
+        }
+        Ok(brain)
+    }
+}
+
+impl Archetypes {
+    fn get(&self, name: &str) -> Result<&Archetype, Error> {
+        self.archetypes
+            .get(name)
+            .ok_or_else(|| Error::InvalidInput(format!("unknown archetype '{}'", name)))
+    }
+
+    /// Reads one definition on top of the archetype it extends, looked up in
+    /// `loaded` before the registered ones.
+    fn read(&self, loaded: &[(String, Archetype)], json: &Json) -> Result<Archetype, Error> {
+        check_fields(json, &FIELDS)?;
+        let mut archetype = match json.get("extends") {
+            Some(parent) => {
+                let parent = parent
+                    .as_str()
+                    .ok_or_else(|| Error::InvalidInput("'extends' must be a name".into()))?;
+                loaded
+                    .iter()
+                    .find(|(name, _)| name == parent)
+                    .map(|(_, archetype)| archetype)
+                    .map_or_else(|| self.get(parent), Ok)?
+                    .clone()
+            }
+            None => Archetype::default(),
+        };
+        if let Some(value) = positive(json, "max_speed")? {
+            archetype.max_speed = value;
+        }
+        if let Some(value) = positive(json, "max_force")? {
+            archetype.max_force = value;
+        }
+        if let Some(value) = positive(json, "radius")? {
+            archetype.radius = value;
+        }
+        if json.get("behavior").is_some() {
+            let behavior = json.field("behavior", Json::as_str)?;
+            archetype.behavior = Behavior::from_name(behavior)
+                .ok_or_else(|| Error::InvalidInput(format!("unknown behavior '{}'", behavior)))?;
+        }
+        if let Some(sensors) = json.get("sensors") {
+            check_fields(sensors, &["view_distance", "fov", "hearing"])?;
+            archetype.sensors = Some(Sensors {
+                view_distance: positive(sensors, "view_distance")?
+                    .ok_or_else(|| Error::InvalidInput("sensors need a 'view_distance'".into()))?,
+                fov: positive(sensors, "fov")?.unwrap_or(std::f32::consts::TAU),
+                hearing: number(sensors, "hearing")?.unwrap_or(1.0),
+            });
+        }
+        if json.get("behavior_tree").is_some() {
+            archetype.behavior_tree = Some(json.field("behavior_tree", Json::as_str)?.to_string());
+        }
+        if json.get("utility").is_some() {
+            archetype.utility = json
+                .field("utility", Json::as_array)?
+                .iter()
+                .map(read_action)
+                .collect::<Result<_, _>>()?;
+        }
+        Ok(archetype)
+    }
+}
+
+fn read_action(json: &Json) -> Result<Action, Error> {
+    check_fields(json, &["action", "weight", "considerations"])?;
+    let considerations = match json.get("considerations") {
+        Some(_) => json
+            .field("considerations", Json::as_array)?
+            .iter()
+            .map(|consideration| {
+                check_fields(consideration, &["key", "min", "max", "curve"])?;
+                Ok(Consideration {
+                    key: consideration.field("key", Json::as_str)?.to_string(),
+                    min: number(consideration, "min")?.unwrap_or(0.0),
+                    max: number(consideration, "max")?.unwrap_or(1.0),
+                    curve: match consideration.get("curve") {
+                        Some(curve) => Curve::read(curve)?,
+                        None => Curve::linear(1.0, 0.0),
+                    },
+                })
+            })
+            .collect::<Result<_, Error>>()?,
+        None => Vec::new(),
+    };
+    Ok(Action {
+        name: json.field("action", Json::as_str)?.to_string(),
+        weight: number(json, "weight")?.unwrap_or(1.0),
+        considerations,
+    })
+}
+
+/// Fails on a key of `json` that is not in `known`, or when it is not an object.
+fn check_fields(json: &Json, known: &[&str]) -> Result<(), Error> {
+    let Json::Object(entries) = json else {
+        return Err(Error::InvalidInput("expected an object".into()));
+    };
+    match entries
+        .iter()
+        .find(|(key, _)| !known.contains(&key.as_str()))
+    {
+        Some((key, _)) => Err(Error::InvalidInput(format!("unknown field '{}'", key))),
+        None => Ok(()),
+    }
+}
+
+fn number(json: &Json, key: &str) -> Result<Option<f32>, Error> {
+    match json.get(key) {
+        Some(_) => Ok(Some(json.field(key, Json::as_f64)? as f32)),
+        None => Ok(None),
+    }
+}
+
+fn positive(json: &Json, key: &str) -> Result<Option<f32>, Error> {
+    match number(json, key)? {
+        Some(value) if value <= 0.0 || !value.is_finite() => Err(Error::InvalidInput(format!(
+            "'{}' must be positive, got {}",
+            key, value
+        ))),
+        value => Ok(value),
+    }
+}
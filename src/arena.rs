@@ -0,0 +1,132 @@
+use std::cell::RefCell;
+use std::collections::BinaryHeap;
+use std::mem;
+use std::ops::{Deref, DerefMut};
+
+use wasm_bindgen::prelude::*;
+
+use crate::search::OpenNode;
+
+/// Buffers kept between searches on this thread, so pathfinding stops
+/// allocating once it has seen the largest graph and the busiest frame.
+#[derive(Default)]
+struct Pool {
+    free: Vec<SearchBuffers>,
+    leased: usize,
+    /// Most buffers leased at once since the last `reset_frame`.
+    peak: usize,
+}
+
+thread_local! {
+    static POOL: RefCell<Pool> = RefCell::new(Pool::default());
+}
+
+/// Per-node state and the open list of one graph search.
+#[derive(Default)]
+pub(crate) struct SearchBuffers {
+    pub g: Vec<f32>,
+    pub parent: Vec<usize>,
+    pub closed: Vec<bool>,
+    pub open: BinaryHeap<OpenNode>,
+    pub edges: Vec<(usize, f32)>,
+}
+
+impl SearchBuffers {
+    fn reset(&mut self, node_count: usize) {
+        self.g.clear();
+        self.g.resize(node_count, f32::INFINITY);
+        self.parent.clear();
+        self.parent.resize(node_count, usize::MAX);
+        self.closed.clear();
+        self.closed.resize(node_count, false);
+        self.open.clear();
+        self.edges.clear();
+    }
+
+    fn bytes(&self) -> usize {
+        self.g.capacity() * mem::size_of::<f32>()
+            + self.parent.capacity() * mem::size_of::<usize>()
+            + self.closed.capacity()
+            + self.open.capacity() * mem::size_of::<OpenNode>()
+            + self.edges.capacity() * mem::size_of::<(usize, f32)>()
+    }
+}
+
+/// Search buffers borrowed from this thread's pool, handed back on drop.
+#[derive(Default)]
+pub(crate) struct Lease {
+    buffers: SearchBuffers,
+}
+
+impl Deref for Lease {
+    type Target = SearchBuffers;
+
+    fn deref(&self) -> &SearchBuffers {
+        &self.buffers
+    }
+}
+
+impl DerefMut for Lease {
+    fn deref_mut(&mut self) -> &mut SearchBuffers {
+        &mut self.buffers
+    }
+}
+
+impl Drop for Lease {
+    fn drop(&mut self) {
+        let buffers = mem::take(&mut self.buffers);
+        // Ignored while the thread shuts down and its pool is already gone.
+        let _ = POOL.try_with(|pool| {
+            let mut pool = pool.borrow_mut();
+            pool.leased = pool.leased.saturating_sub(1);
+            pool.free.push(buffers);
+        });
+    }
+}
+
+/// Buffers for a search over `node_count` nodes with every cost infinite,
+/// no parents, nothing closed and an empty open list.
+pub(crate) fn search(node_count: usize) -> Lease {
+    let mut buffers = POOL
+        .try_with(|pool| {
+            let mut pool = pool.borrow_mut();
+            pool.leased += 1;
+            pool.peak = pool.peak.max(pool.leased);
+            pool.free.pop()
+        })
+        .ok()
+        .flatten()
+        .unwrap_or_default();
+    buffers.reset(node_count);
+    Lease { buffers }
+}
+
+/// Marks the end of a frame: frees the pooled search buffers this thread
+/// did not need since the previous call, so a burst of simultaneous searches
+/// does not hold on to its memory for the rest of the session. Call it once
+/// per rendered frame, or after loading a level.
+///
+/// Pathfinding reuses its open lists and per-node costs instead of
+/// allocating them for every search, so the wasm heap stops growing once it
+/// has served the largest graph. Worker threads keep pools of their own.
+#[wasm_bindgen]
+pub fn reset_frame() {
+    POOL.with(|pool| {
+        let mut pool = pool.borrow_mut();
+        let keep = pool.peak.saturating_sub(pool.leased);
+        pool.free.truncate(keep);
+        pool.peak = pool.leased;
+    });
+}
+
+/// Bytes held by this thread's pooled search buffers, for a memory HUD.
+#[wasm_bindgen]
+pub fn arena_bytes() -> u32 {
+    POOL.with(|pool| {
+        pool.borrow()
+            .free
+            .iter()
+            .map(SearchBuffers::bytes)
+            .sum::<usize>() as u32
+    })
+}
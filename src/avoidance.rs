@@ -0,0 +1,206 @@
+use std::collections::HashMap;
+
+use wasm_bindgen::prelude::*;
+
+use crate::error::Error;
+use crate::math::Vec2;
+use crate::steering::Agent;
+
+/// Angle between the center feeler and each side feeler, in radians.
+const WHISKER_ANGLE: f32 = 0.6;
+
+/// Side feelers are this fraction of the center feeler's length.
+const WHISKER_LENGTH: f32 = 0.7;
+
+#[derive(Clone, Copy)]
+enum Shape {
+    Circle { center: Vec2, radius: f32 },
+    Segment { start: Vec2, end: Vec2 },
+}
+
+/// Static circles and line segments for `Agent::avoid_obstacles`, such as props
+/// and walls that the navigation data does not cover.
+#[wasm_bindgen]
+#[derive(Clone, Default)]
+pub struct Obstacles {
+    shapes: HashMap<u32, Shape>,
+    next_id: u32,
+}
+
+#[wasm_bindgen]
+impl Obstacles {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Obstacles {
+        Obstacles::default()
+    }
+
+    /// Adds a circle and returns its id. Inflate the radius by the agents' own
+    /// radius to keep their bodies, not just their centers, clear of it.
+    pub fn add_circle(&mut self, x: f32, y: f32, radius: f32) -> Result<u32, Error> {
+        if radius > 0.0 {
+            Ok(self.insert(Shape::Circle {
+                center: Vec2::new(x, y),
+                radius,
+            }))
+        } else {
+            Err(Error::InvalidInput(format!(
+                "obstacle radius must be positive, got {}",
+                radius
+            )))
+        }
+    }
+
+    /// Adds a wall segment, avoided from either side, and returns its id.
+    pub fn add_segment(&mut self, x0: f32, y0: f32, x1: f32, y1: f32) -> u32 {
+        self.insert(Shape::Segment {
+            start: Vec2::new(x0, y0),
+            end: Vec2::new(x1, y1),
+        })
+    }
+
+    pub fn remove(&mut self, id: u32) -> bool {
+        self.shapes.remove(&id).is_some()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn len(&self) -> u32 {
+        self.shapes.len() as u32
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.shapes.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.shapes.clear();
+    }
+}
+
+impl Obstacles {
+    fn insert(&mut self, shape: Shape) -> u32 {
+        let id = self.next_id;
+        self.next_id = self.next_id.wrapping_add(1);
+        self.shapes.insert(id, shape);
+        id
+    }
+
+    /// Nearest hit along the segment from `origin` to `origin + direction * length`
+    /// as the distance travelled and the surface normal facing the origin.
+    fn cast(&self, origin: Vec2, direction: Vec2, length: f32) -> Option<(f32, Vec2)> {
+        let mut nearest: Option<(f32, Vec2)> = None;
+        for shape in self.shapes.values() {
+            let hit = match *shape {
+                Shape::Circle { center, radius } => {
+                    cast_circle(origin, direction, length, center, radius)
+                }
+                Shape::Segment { start, end } => {
+                    cast_segment(origin, direction, length, start, end)
+                }
+            };
+            if let Some(hit) = hit {
+                if nearest.is_none_or(|(distance, _)| hit.0 < distance) {
+                    nearest = Some(hit);
+                }
+            }
+        }
+        nearest
+    }
+}
+
+pub(crate) fn cast_circle(
+    origin: Vec2,
+    direction: Vec2,
+    length: f32,
+    center: Vec2,
+    radius: f32,
+) -> Option<(f32, Vec2)> {
+    let offset = origin - center;
+    if offset.length_squared() < radius * radius {
+        return Some((0.0, offset.normalize()));
+    }
+    let b = offset.dot(direction);
+    let c = offset.length_squared() - radius * radius;
+    let discriminant = b * b - c;
+    if discriminant < 0.0 {
+        return None;
+    }
+    let t = -b - discriminant.sqrt();
+    (0.0..=length).contains(&t).then(|| {
+        let point = origin + direction * t;
+        (t, (point - center).normalize())
+    })
+}
+
+pub(crate) fn cast_segment(
+    origin: Vec2,
+    direction: Vec2,
+    length: f32,
+    start: Vec2,
+    end: Vec2,
+) -> Option<(f32, Vec2)> {
+    let edge = end - start;
+    let denominator = direction.cross(edge);
+    if denominator.abs() <= f32::EPSILON {
+        return None;
+    }
+    let to_start = start - origin;
+    let t = to_start.cross(edge) / denominator;
+    let u = to_start.cross(direction) / denominator;
+    if !(0.0..=length).contains(&t) || !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+    let normal = edge.perp().normalize();
+    let facing = if normal.dot(origin - start) >= 0.0 {
+        normal
+    } else {
+        -normal
+    };
+    Some((t, facing))
+}
+
+#[wasm_bindgen]
+impl Agent {
+    /// Whisker-based obstacle avoidance: casts a feeler `feeler_length` ahead along
+    /// the heading and two shorter ones angled to each side, and pushes away from
+    /// whatever they hit, harder the deeper the feeler penetrates. Returns zero when
+    /// the way ahead is clear, so the force can simply be added to other behaviors.
+    pub fn avoid_obstacles(&self, obstacles: &Obstacles, feeler_length: f32) -> Vec2 {
+        if obstacles.is_empty() {
+            return Vec2::ZERO;
+        }
+        whiskers(self, feeler_length, |origin, direction, length| {
+            obstacles.cast(origin, direction, length)
+        })
+    }
+}
+
+/// The whisker force of `Agent::avoid_obstacles`, with `cast` finding the
+/// nearest hit along each feeler.
+pub(crate) fn whiskers(
+    agent: &Agent,
+    feeler_length: f32,
+    cast: impl Fn(Vec2, Vec2, f32) -> Option<(f32, Vec2)>,
+) -> Vec2 {
+    if feeler_length <= 0.0 {
+        return Vec2::ZERO;
+    }
+    let whiskers = [
+        (agent.heading, feeler_length),
+        (
+            agent.heading.rotate(WHISKER_ANGLE),
+            feeler_length * WHISKER_LENGTH,
+        ),
+        (
+            agent.heading.rotate(-WHISKER_ANGLE),
+            feeler_length * WHISKER_LENGTH,
+        ),
+    ];
+
+    let mut force = Vec2::ZERO;
+    for (direction, length) in whiskers {
+        if let Some((distance, normal)) = cast(agent.position, direction, length) {
+            force += normal * (agent.max_force * (length - distance) / length);
+        }
+    }
+    force.truncate(agent.max_force)
+}
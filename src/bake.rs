@@ -0,0 +1,606 @@
+use wasm_bindgen::prelude::*;
+
+use crate::error::Error;
+use crate::navmesh::NavMesh;
+use crate::search;
+
+/// Largest voxel grid footprint `bake` will allocate, in columns.
+const MAX_COLUMNS: usize = 1 << 24;
+
+const NONE: u32 = u32::MAX;
+
+/// Column offsets for the four span neighbors: +x, +z, -x, -z.
+const OFFSETS: [(i32, i32); 4] = [(1, 0), (0, 1), (-1, 0), (0, -1)];
+
+/// Parameters for `NavMesh::bake`, in world units. Defaults suit a human-sized
+/// agent in a level measured in meters.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug)]
+pub struct BakeConfig {
+    /// Voxel size on the XZ plane.
+    pub cell_size: f32,
+    /// Voxel size along Y.
+    pub cell_height: f32,
+    /// Minimum free space above walkable ground.
+    pub agent_height: f32,
+    /// Distance kept between the mesh and walls.
+    pub agent_radius: f32,
+    /// Tallest ledge the agent can step up or down.
+    pub agent_max_climb: f32,
+    /// Steepest walkable slope, in degrees.
+    pub agent_max_slope: f32,
+}
+
+#[wasm_bindgen]
+impl BakeConfig {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> BakeConfig {
+        BakeConfig {
+            cell_size: 0.3,
+            cell_height: 0.2,
+            agent_height: 2.0,
+            agent_radius: 0.6,
+            agent_max_climb: 0.9,
+            agent_max_slope: 45.0,
+        }
+    }
+}
+
+impl Default for BakeConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[wasm_bindgen]
+impl NavMesh {
+    /// Generates a navmesh from level geometry the way Recast does: rasterizes the
+    /// triangles into voxels, keeps ground the agent can stand on and reach, erodes
+    /// it by the agent radius and meshes what remains. `positions` are flat
+    /// `[x, y, z, ...]` with `y` up.
+    pub fn bake(positions: &[f32], indices: &[u32], config: &BakeConfig) -> Result<NavMesh, Error> {
+        let (vertices, indices) = bake_geometry(positions, indices, config)?;
+        NavMesh::new(&vertices, &indices)
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Span {
+    min: u32,
+    max: u32,
+    walkable: bool,
+}
+
+/// A walkable floor surface with the free space above it.
+struct OpenSpan {
+    x: i32,
+    z: i32,
+    floor: u32,
+    ceiling: u32,
+    links: [u32; 4],
+}
+
+struct Heightfield {
+    width: i32,
+    depth: i32,
+    origin: [f32; 3],
+    cell_size: f32,
+    cell_height: f32,
+    columns: Vec<Vec<Span>>,
+}
+
+/// Runs the bake pipeline and returns flat vertices and triangle indices.
+pub(crate) fn bake_geometry(
+    positions: &[f32],
+    indices: &[u32],
+    config: &BakeConfig,
+) -> Result<(Vec<f32>, Vec<u32>), Error> {
+    if !positions.len().is_multiple_of(3) || !indices.len().is_multiple_of(3) {
+        return Err(Error::InvalidInput(
+            "positions and indices must be multiples of 3".into(),
+        ));
+    }
+    if !(config.cell_size > 0.0 && config.cell_height > 0.0) {
+        return Err(Error::InvalidInput(
+            "cell_size and cell_height must be positive".into(),
+        ));
+    }
+    let vertex_count = (positions.len() / 3) as u32;
+    if let Some(&bad) = indices.iter().find(|&&i| i >= vertex_count) {
+        return Err(Error::InvalidInput(format!(
+            "index {} is out of range",
+            bad
+        )));
+    }
+    if indices.is_empty() {
+        return Ok((Vec::new(), Vec::new()));
+    }
+
+    let height = (config.agent_height / config.cell_height).ceil() as u32;
+    let climb = (config.agent_max_climb / config.cell_height).floor() as u32;
+
+    let mut heightfield = Heightfield::new(positions, indices, config)?;
+    heightfield.rasterize(positions, indices, config.agent_max_slope, climb);
+    heightfield.filter(height, climb);
+
+    let mut spans = heightfield.open_spans(height, climb);
+    erode(&mut spans, config.agent_radius / config.cell_size);
+    Ok(heightfield.triangulate(&spans))
+}
+
+impl Heightfield {
+    fn new(positions: &[f32], indices: &[u32], config: &BakeConfig) -> Result<Heightfield, Error> {
+        let mut min = [f32::INFINITY; 3];
+        let mut max = [f32::NEG_INFINITY; 3];
+        for &index in indices {
+            let p = &positions[index as usize * 3..index as usize * 3 + 3];
+            for axis in 0..3 {
+                min[axis] = min[axis].min(p[axis]);
+                max[axis] = max[axis].max(p[axis]);
+            }
+        }
+        let width = (((max[0] - min[0]) / config.cell_size).ceil() as i32).max(1);
+        let depth = (((max[2] - min[2]) / config.cell_size).ceil() as i32).max(1);
+        if width as usize * depth as usize > MAX_COLUMNS {
+            return Err(Error::InvalidInput(format!(
+                "voxel grid of {}x{} cells is too large; increase cell_size",
+                width, depth
+            )));
+        }
+        // Starting one voxel below the geometry lets every span be at least one voxel
+        // tall while its top, which becomes the floor height, stays on the surface.
+        min[1] -= config.cell_height;
+        Ok(Heightfield {
+            width,
+            depth,
+            origin: min,
+            cell_size: config.cell_size,
+            cell_height: config.cell_height,
+            columns: vec![Vec::new(); width as usize * depth as usize],
+        })
+    }
+
+    fn column(&self, x: i32, z: i32) -> Option<usize> {
+        (x >= 0 && z >= 0 && x < self.width && z < self.depth)
+            .then(|| (z * self.width + x) as usize)
+    }
+
+    /// Adds every triangle's vertical extent to the columns it overlaps. Triangles no
+    /// steeper than `max_slope` degrees produce walkable spans.
+    fn rasterize(&mut self, positions: &[f32], indices: &[u32], max_slope: f32, climb: u32) {
+        let min_normal_y = max_slope.to_radians().cos();
+        let point = |index: u32| {
+            let i = index as usize * 3;
+            [positions[i], positions[i + 1], positions[i + 2]]
+        };
+
+        for triangle in indices.chunks_exact(3) {
+            let [a, b, c] = [point(triangle[0]), point(triangle[1]), point(triangle[2])];
+            let walkable = slope_normal_y(a, b, c) >= min_normal_y;
+            let (x0, x1) = self.cell_range(a[0].min(b[0]).min(c[0]), a[0].max(b[0]).max(c[0]), 0);
+            let (z0, z1) = self.cell_range(a[2].min(b[2]).min(c[2]), a[2].max(b[2]).max(c[2]), 2);
+
+            let polygon = vec![a, b, c];
+            for z in z0..=z1 {
+                let z_min = self.origin[2] + z as f32 * self.cell_size;
+                let row = clip(&polygon, 2, z_min, z_min + self.cell_size);
+                if row.len() < 3 {
+                    continue;
+                }
+                for x in x0..=x1 {
+                    let x_min = self.origin[0] + x as f32 * self.cell_size;
+                    let cell = clip(&row, 0, x_min, x_min + self.cell_size);
+                    if cell.len() < 3 {
+                        continue;
+                    }
+                    let (low, high) = cell
+                        .iter()
+                        .fold((f32::INFINITY, f32::NEG_INFINITY), |(lo, hi), p| {
+                            (lo.min(p[1]), hi.max(p[1]))
+                        });
+                    let max = ((high - self.origin[1]) / self.cell_height).ceil() as u32;
+                    let min =
+                        (((low - self.origin[1]) / self.cell_height).floor() as u32).min(max - 1);
+                    let index = (z * self.width + x) as usize;
+                    add_span(&mut self.columns[index], Span { min, max, walkable }, climb);
+                }
+            }
+        }
+    }
+
+    fn cell_range(&self, low: f32, high: f32, axis: usize) -> (i32, i32) {
+        let limit = if axis == 0 { self.width } else { self.depth } - 1;
+        let to_cell =
+            |v: f32| (((v - self.origin[axis]) / self.cell_size).floor() as i32).clamp(0, limit);
+        (to_cell(low), to_cell(high))
+    }
+
+    /// Lets agents step onto low obstacles and removes ground without headroom.
+    fn filter(&mut self, height: u32, climb: u32) {
+        for column in &mut self.columns {
+            let mut previous_walkable = false;
+            let mut previous_max = 0;
+            for span in column.iter_mut() {
+                let walkable = span.walkable;
+                if !walkable && previous_walkable && span.max - previous_max <= climb {
+                    span.walkable = true;
+                }
+                previous_walkable = walkable;
+                previous_max = span.max;
+            }
+
+            for i in 0..column.len() {
+                let ceiling = column.get(i + 1).map_or(u32::MAX, |next| next.min);
+                if ceiling - column[i].max < height {
+                    column[i].walkable = false;
+                }
+            }
+        }
+    }
+
+    /// Collects the walkable surfaces and connects each to the neighbor surface the
+    /// agent can step to.
+    fn open_spans(&self, height: u32, climb: u32) -> Vec<OpenSpan> {
+        let mut spans = Vec::new();
+        let mut first = vec![0u32; self.columns.len() + 1];
+        for z in 0..self.depth {
+            for x in 0..self.width {
+                let index = (z * self.width + x) as usize;
+                first[index] = spans.len() as u32;
+                let column = &self.columns[index];
+                for (i, span) in column.iter().enumerate() {
+                    if span.walkable {
+                        spans.push(OpenSpan {
+                            x,
+                            z,
+                            floor: span.max,
+                            ceiling: column.get(i + 1).map_or(u32::MAX, |next| next.min),
+                            links: [NONE; 4],
+                        });
+                    }
+                }
+            }
+        }
+        first[self.columns.len()] = spans.len() as u32;
+
+        for i in 0..spans.len() {
+            for (direction, &(dx, dz)) in OFFSETS.iter().enumerate() {
+                let Some(column) = self.column(spans[i].x + dx, spans[i].z + dz) else {
+                    continue;
+                };
+                let (floor, ceiling) = (spans[i].floor, spans[i].ceiling);
+                let link = (first[column]..first[column + 1]).find(|&j| {
+                    let other = &spans[j as usize];
+                    other.floor.abs_diff(floor) <= climb
+                        && other
+                            .ceiling
+                            .min(ceiling)
+                            .saturating_sub(other.floor.max(floor))
+                            >= height
+                });
+                if let Some(link) = link {
+                    spans[i].links[direction] = link;
+                }
+            }
+        }
+        spans
+    }
+
+    /// Greedily merges surfaces of equal height into rectangles and triangulates each
+    /// as a fan around its center. Rectangle edges are split at every neighboring
+    /// rectangle's corner so adjacent triangles always share edges.
+    fn triangulate(&self, spans: &[OpenSpan]) -> (Vec<f32>, Vec<u32>) {
+        let rects = merge_rects(spans);
+
+        // One slot per span corner, counter-clockwise from (x, z); slots that meet at
+        // the same grid point on connected spans become one vertex.
+        let mut corners = UnionFind::new(spans.len() * 4);
+        for (i, span) in spans.iter().enumerate() {
+            let slot = |span: usize, corner: usize| span * 4 + corner;
+            for (direction, pairs) in [
+                [(1, 0), (2, 3)],
+                [(3, 0), (2, 1)],
+                [(0, 1), (3, 2)],
+                [(0, 3), (1, 2)],
+            ]
+            .iter()
+            .enumerate()
+            {
+                let link = span.links[direction];
+                if link != NONE {
+                    for &(mine, theirs) in pairs {
+                        corners.union(slot(i, mine), slot(link as usize, theirs));
+                    }
+                }
+            }
+        }
+
+        let mut is_rect_corner = vec![false; spans.len() * 4];
+        for rect in &rects {
+            for (span, corner) in rect.corner_slots() {
+                is_rect_corner[corners.find(span * 4 + corner)] = true;
+            }
+        }
+
+        let mut heights = vec![(0.0f32, 0u32); spans.len() * 4];
+        for (i, span) in spans.iter().enumerate() {
+            for corner in 0..4 {
+                let root = corners.find(i * 4 + corner);
+                heights[root].0 += span.floor as f32;
+                heights[root].1 += 1;
+            }
+        }
+
+        let mut vertices = Vec::new();
+        let mut vertex_of = vec![NONE; spans.len() * 4];
+        let mut indices = Vec::new();
+        let push_vertex = |vertices: &mut Vec<f32>, x: f32, z: f32, floor: f32| {
+            vertices.push(self.origin[0] + x * self.cell_size);
+            vertices.push(self.origin[1] + floor * self.cell_height);
+            vertices.push(self.origin[2] + z * self.cell_size);
+            (vertices.len() / 3 - 1) as u32
+        };
+
+        let mut ring = Vec::new();
+        for rect in &rects {
+            ring.clear();
+            for (span, corner) in rect.perimeter() {
+                let root = corners.find(span * 4 + corner);
+                if !is_rect_corner[root] {
+                    continue;
+                }
+                if vertex_of[root] == NONE {
+                    let s = &spans[span];
+                    let (x, z) = match corner {
+                        0 => (s.x, s.z),
+                        1 => (s.x + 1, s.z),
+                        2 => (s.x + 1, s.z + 1),
+                        _ => (s.x, s.z + 1),
+                    };
+                    let (sum, count) = heights[root];
+                    vertex_of[root] =
+                        push_vertex(&mut vertices, x as f32, z as f32, sum / count as f32);
+                }
+                ring.push(vertex_of[root]);
+            }
+
+            if ring.len() == 4 {
+                indices.extend_from_slice(&[ring[0], ring[1], ring[2], ring[0], ring[2], ring[3]]);
+            } else {
+                let first = &spans[rect.rows[0][0]];
+                let center = push_vertex(
+                    &mut vertices,
+                    first.x as f32 + rect.width() as f32 * 0.5,
+                    first.z as f32 + rect.rows.len() as f32 * 0.5,
+                    first.floor as f32,
+                );
+                for k in 0..ring.len() {
+                    indices.extend_from_slice(&[center, ring[k], ring[(k + 1) % ring.len()]]);
+                }
+            }
+        }
+
+        (vertices, indices)
+    }
+}
+
+/// Inserts a span into a column, merging it with any spans it overlaps. When the
+/// merged tops are within `climb` of each other the result is walkable if either was.
+fn add_span(column: &mut Vec<Span>, mut span: Span, climb: u32) {
+    let mut i = 0;
+    while i < column.len() {
+        let current = column[i];
+        if current.min > span.max {
+            break;
+        }
+        if current.max < span.min {
+            i += 1;
+            continue;
+        }
+        if current.max.abs_diff(span.max) <= climb {
+            span.walkable |= current.walkable;
+        } else if current.max > span.max {
+            span.walkable = current.walkable;
+        }
+        span.min = span.min.min(current.min);
+        span.max = span.max.max(current.max);
+        column.remove(i);
+    }
+    column.insert(i, span);
+}
+
+/// Y component of the unit normal, ignoring winding.
+fn slope_normal_y(a: [f32; 3], b: [f32; 3], c: [f32; 3]) -> f32 {
+    let u = [b[0] - a[0], b[1] - a[1], b[2] - a[2]];
+    let v = [c[0] - a[0], c[1] - a[1], c[2] - a[2]];
+    let n = [
+        u[1] * v[2] - u[2] * v[1],
+        u[2] * v[0] - u[0] * v[2],
+        u[0] * v[1] - u[1] * v[0],
+    ];
+    let length = (n[0] * n[0] + n[1] * n[1] + n[2] * n[2]).sqrt();
+    if length > 0.0 {
+        n[1].abs() / length
+    } else {
+        0.0
+    }
+}
+
+/// Clips a convex polygon to `low <= p[axis] <= high`.
+fn clip(polygon: &[[f32; 3]], axis: usize, low: f32, high: f32) -> Vec<[f32; 3]> {
+    let lower = clip_half(polygon, axis, low, 1.0);
+    clip_half(&lower, axis, high, -1.0)
+}
+
+/// Keeps the part of `polygon` where `sign * (p[axis] - value) >= 0`.
+fn clip_half(polygon: &[[f32; 3]], axis: usize, value: f32, sign: f32) -> Vec<[f32; 3]> {
+    let mut out = Vec::with_capacity(polygon.len() + 2);
+    for i in 0..polygon.len() {
+        let (a, b) = (polygon[i], polygon[(i + 1) % polygon.len()]);
+        let (da, db) = (sign * (a[axis] - value), sign * (b[axis] - value));
+        if da >= 0.0 {
+            out.push(a);
+        }
+        if (da >= 0.0) != (db >= 0.0) {
+            let t = da / (da - db);
+            out.push([
+                a[0] + (b[0] - a[0]) * t,
+                a[1] + (b[1] - a[1]) * t,
+                a[2] + (b[2] - a[2]) * t,
+            ]);
+        }
+    }
+    out
+}
+
+/// Removes spans closer than `radius` cells to the edge of the walkable area.
+fn erode(spans: &mut Vec<OpenSpan>, radius: f32) {
+    if radius <= 0.0 {
+        return;
+    }
+    let boundary: Vec<usize> = (0..spans.len())
+        .filter(|&i| spans[i].links.contains(&NONE))
+        .collect();
+    let distance = search::dijkstra(spans.len(), &boundary, |index, out| {
+        for (direction, &next) in spans[index].links.iter().enumerate() {
+            if next == NONE {
+                continue;
+            }
+            out.push((next as usize, 1.0));
+            let diagonal = spans[next as usize].links[(direction + 1) % 4];
+            if diagonal != NONE {
+                out.push((diagonal as usize, std::f32::consts::SQRT_2));
+            }
+        }
+    });
+
+    // A boundary span's center is half a cell from the edge.
+    let keep: Vec<bool> = distance.iter().map(|&d| d + 0.5 >= radius).collect();
+    let mut remap = vec![NONE; spans.len()];
+    let mut next = 0;
+    for (i, &kept) in keep.iter().enumerate() {
+        if kept {
+            remap[i] = next;
+            next += 1;
+        }
+    }
+    let mut index = 0;
+    spans.retain(|_| {
+        index += 1;
+        keep[index - 1]
+    });
+    for span in spans.iter_mut() {
+        for link in &mut span.links {
+            if *link != NONE {
+                *link = remap[*link as usize];
+            }
+        }
+    }
+}
+
+struct Rect {
+    /// Span indices, one row per z step, each ordered by increasing x.
+    rows: Vec<Vec<usize>>,
+}
+
+impl Rect {
+    fn width(&self) -> usize {
+        self.rows[0].len()
+    }
+
+    fn corner_slots(&self) -> [(usize, usize); 4] {
+        let (first, last) = (&self.rows[0], &self.rows[self.rows.len() - 1]);
+        [
+            (first[0], 0),
+            (first[first.len() - 1], 1),
+            (last[last.len() - 1], 2),
+            (last[0], 3),
+        ]
+    }
+
+    /// Every grid point on the boundary as a `(span, corner)` slot, in ring order.
+    fn perimeter(&self) -> Vec<(usize, usize)> {
+        let (width, height) = (self.width(), self.rows.len());
+        let mut ring = Vec::with_capacity(2 * (width + height));
+        ring.extend((0..width).map(|i| (self.rows[0][i], 0)));
+        ring.extend((0..height).map(|j| (self.rows[j][width - 1], 1)));
+        ring.extend((0..width).rev().map(|i| (self.rows[height - 1][i], 2)));
+        ring.extend((0..height).rev().map(|j| (self.rows[j][0], 3)));
+        ring
+    }
+}
+
+/// Covers the spans with rectangles of connected, equal-height spans.
+fn merge_rects(spans: &[OpenSpan]) -> Vec<Rect> {
+    let mut assigned = vec![false; spans.len()];
+    let mut rects = Vec::new();
+    let fits = |assigned: &[bool], link: u32, floor: u32| {
+        link != NONE && !assigned[link as usize] && spans[link as usize].floor == floor
+    };
+
+    for seed in 0..spans.len() {
+        if assigned[seed] {
+            continue;
+        }
+        let floor = spans[seed].floor;
+        assigned[seed] = true;
+        let mut row = vec![seed];
+        loop {
+            let link = spans[row[row.len() - 1]].links[0];
+            if !fits(&assigned, link, floor) {
+                break;
+            }
+            assigned[link as usize] = true;
+            row.push(link as usize);
+        }
+
+        let mut rows = vec![row];
+        'rows: loop {
+            let previous = &rows[rows.len() - 1];
+            let mut next: Vec<usize> = Vec::with_capacity(previous.len());
+            for (k, &span) in previous.iter().enumerate() {
+                let link = spans[span].links[1];
+                if !fits(&assigned, link, floor) {
+                    break 'rows;
+                }
+                if k > 0 && spans[next[k - 1]].links[0] != link {
+                    break 'rows;
+                }
+                next.push(link as usize);
+            }
+            for &span in &next {
+                assigned[span] = true;
+            }
+            rows.push(next);
+        }
+        rects.push(Rect { rows });
+    }
+    rects
+}
+
+struct UnionFind {
+    parent: Vec<usize>,
+}
+
+impl UnionFind {
+    fn new(count: usize) -> UnionFind {
+        UnionFind {
+            parent: (0..count).collect(),
+        }
+    }
+
+    fn find(&mut self, mut index: usize) -> usize {
+        while self.parent[index] != index {
+            self.parent[index] = self.parent[self.parent[index]];
+            index = self.parent[index];
+        }
+        index
+    }
+
+    fn union(&mut self, a: usize, b: usize) {
+        let (a, b) = (self.find(a), self.find(b));
+        if a != b {
+            self.parent[a] = b;
+        }
+    }
+}
@@ -0,0 +1,222 @@
+use wasm_bindgen::prelude::*;
+
+use crate::math::Vec3;
+
+/// Rounds of refinement `ballistic_lead` makes to the flight time.
+const LEAD_ITERATIONS: u32 = 12;
+/// Change in flight time, in seconds, at which `ballistic_lead` has converged.
+const LEAD_TOLERANCE: f32 = 1e-4;
+
+/// How to fire at a target: the launch direction and the flight time to the
+/// point of impact. When no shot can reach the target, `valid` is false and
+/// `direction` is a fallback that keeps a turret tracking it: straight at the
+/// target for a straight shot, the longest-range 45° arc toward it for a
+/// ballistic one.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AimSolution {
+    pub valid: bool,
+    /// Unit launch direction; the launch velocity is this times the
+    /// projectile speed.
+    pub direction: Vec3,
+    /// Seconds from launch to impact, or `NaN` when invalid.
+    pub time: f32,
+    /// Where the projectile meets the target.
+    pub point: Vec3,
+}
+
+impl AimSolution {
+    fn miss(direction: Vec3, point: Vec3) -> AimSolution {
+        AimSolution {
+            valid: false,
+            direction,
+            time: f32::NAN,
+            point,
+        }
+    }
+}
+
+/// Aims a projectile without drop, leaving `shooter` at `speed`, at a target
+/// moving at constant `target_velocity`: the earliest interception.
+#[wasm_bindgen]
+pub fn lead_target(shooter: Vec3, speed: f32, target: Vec3, target_velocity: Vec3) -> AimSolution {
+    match intercept_time(target - shooter, target_velocity, speed) {
+        Some(time) => {
+            let point = target + target_velocity * time;
+            AimSolution {
+                valid: true,
+                direction: (point - shooter).normalize(),
+                time,
+                point,
+            }
+        }
+        None => AimSolution::miss((target - shooter).normalize(), target),
+    }
+}
+
+/// Aims a projectile leaving `shooter` at `speed` under `gravity`, pulling
+/// toward `-y`, to land on a still `target`. Two arcs reach a target in
+/// range: the flat one with the shorter flight, and with `high` set the lob
+/// that comes down steeply, e.g. over a wall.
+#[wasm_bindgen]
+pub fn ballistic_arc(
+    shooter: Vec3,
+    speed: f32,
+    gravity: f32,
+    target: Vec3,
+    high: bool,
+) -> AimSolution {
+    arc(shooter, speed, gravity, target, high)
+        .unwrap_or_else(|| AimSolution::miss(longest_arc(shooter, target), target))
+}
+
+/// Like `ballistic_arc` for a target moving at constant `target_velocity`,
+/// aiming where it will be when the projectile comes down. The flight time is
+/// refined from the still-target solution, so the lead is exact for the level
+/// motion of a walking target and close for a climbing or falling one.
+#[wasm_bindgen]
+pub fn ballistic_lead(
+    shooter: Vec3,
+    speed: f32,
+    gravity: f32,
+    target: Vec3,
+    target_velocity: Vec3,
+    high: bool,
+) -> AimSolution {
+    let Some(mut solution) = arc(shooter, speed, gravity, target, high) else {
+        return AimSolution::miss(longest_arc(shooter, target), target);
+    };
+    for _ in 0..LEAD_ITERATIONS {
+        let point = target + target_velocity * solution.time;
+        let Some(next) = arc(shooter, speed, gravity, point, high) else {
+            return AimSolution::miss(longest_arc(shooter, point), point);
+        };
+        let change = (next.time - solution.time).abs();
+        solution = next;
+        if change < LEAD_TOLERANCE {
+            return solution;
+        }
+    }
+    // Still moving: a lob at a fast target may chase it out of range.
+    AimSolution {
+        valid: false,
+        ..solution
+    }
+}
+
+/// Seconds until a projectile launched from `origin` with `velocity` under
+/// `gravity` comes down through height `y`, or `NaN` when it never does.
+#[wasm_bindgen]
+pub fn time_to_impact(origin: Vec3, velocity: Vec3, gravity: f32, y: f32) -> f32 {
+    // Solve origin.y + velocity.y t - gravity t² / 2 = y for the later root.
+    let drop = origin.y - y;
+    if gravity.abs() < 1e-6 {
+        return if velocity.y < 0.0 && drop >= 0.0 {
+            drop / -velocity.y
+        } else {
+            f32::NAN
+        };
+    }
+    let discriminant = velocity.y * velocity.y + 2.0 * gravity * drop;
+    if discriminant < 0.0 {
+        return f32::NAN;
+    }
+    let time = (velocity.y + discriminant.sqrt()) / gravity;
+    if time >= 0.0 {
+        time
+    } else {
+        f32::NAN
+    }
+}
+
+/// Where a projectile launched from `origin` with `velocity` under `gravity`
+/// is after `time` seconds.
+#[wasm_bindgen]
+pub fn projectile_position(origin: Vec3, velocity: Vec3, gravity: f32, time: f32) -> Vec3 {
+    origin + velocity * time - Vec3::new(0.0, 0.5 * gravity * time * time, 0.0)
+}
+
+/// Greatest level distance a projectile leaving at `speed` under `gravity`
+/// carries, at 45°.
+#[wasm_bindgen]
+pub fn ballistic_range(speed: f32, gravity: f32) -> f32 {
+    if gravity > 0.0 {
+        speed * speed / gravity
+    } else {
+        f32::INFINITY
+    }
+}
+
+/// Earliest time at which something leaving the origin at `speed` meets a
+/// target at `offset` moving at `velocity`.
+pub(crate) fn intercept_time(offset: Vec3, velocity: Vec3, speed: f32) -> Option<f32> {
+    // Solve |offset + velocity * t| = speed * t for the smallest t >= 0.
+    let a = velocity.length_squared() - speed * speed;
+    let b = 2.0 * offset.dot(velocity);
+    let c = offset.length_squared();
+    if a.abs() < 1e-6 {
+        return (b < 0.0).then(|| -c / b);
+    }
+    let discriminant = b * b - 4.0 * a * c;
+    if discriminant < 0.0 {
+        return None;
+    }
+    let root = discriminant.sqrt();
+    [(-b - root) / (2.0 * a), (-b + root) / (2.0 * a)]
+        .into_iter()
+        .filter(|&t| t >= 0.0)
+        .reduce(f32::min)
+}
+
+fn arc(shooter: Vec3, speed: f32, gravity: f32, target: Vec3, high: bool) -> Option<AimSolution> {
+    if speed <= 0.0 {
+        return None;
+    }
+    if gravity.abs() < 1e-6 {
+        let offset = target - shooter;
+        return Some(AimSolution {
+            valid: true,
+            direction: offset.normalize(),
+            time: offset.length() / speed,
+            point: target,
+        });
+    }
+    let offset = target - shooter;
+    let level = Vec3::new(offset.x, 0.0, offset.z);
+    let across = level.length();
+    let (speed2, rise) = (speed * speed, offset.y);
+    // tan θ = (v² ± √(v⁴ - g(g x² + 2 y v²))) / (g x)
+    let discriminant =
+        speed2 * speed2 - gravity * (gravity * across * across + 2.0 * rise * speed2);
+    if discriminant < 0.0 {
+        return None;
+    }
+    if across < 1e-6 {
+        // Straight up or down.
+        let up = if rise >= 0.0 { 1.0 } else { -1.0 };
+        let root = (speed2 - 2.0 * gravity * rise).sqrt();
+        return Some(AimSolution {
+            valid: true,
+            direction: Vec3::new(0.0, up, 0.0),
+            time: up * (speed - root) / gravity,
+            point: target,
+        });
+    }
+    let root = discriminant.sqrt();
+    let lift = if high { speed2 + root } else { speed2 - root };
+    let angle = (lift / (gravity * across)).atan();
+    let heading = level / across;
+    let direction = heading * angle.cos() + Vec3::new(0.0, angle.sin(), 0.0);
+    Some(AimSolution {
+        valid: true,
+        direction,
+        time: across / (speed * angle.cos()),
+        point: target,
+    })
+}
+
+fn longest_arc(shooter: Vec3, target: Vec3) -> Vec3 {
+    let offset = target - shooter;
+    let level = Vec3::new(offset.x, 0.0, offset.z).normalize();
+    (level + Vec3::new(0.0, 1.0, 0.0)).normalize()
+}
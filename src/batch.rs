@@ -0,0 +1,326 @@
+//! Per-agent math over flat `[x, y, ...]` buffers. With the `simd` feature on a
+//! wasm32 build with the `simd128` target feature, two agents go through each
+//! 128-bit vector; otherwise, or for the leftovers, one at a time. Both paths
+//! do the same float operations in the same order, so results match bit for
+//! bit and lockstep clients may mix them.
+
+/// A length limit for every agent, or one per agent.
+#[derive(Clone, Copy)]
+pub(crate) enum Limit<'a> {
+    All(f32),
+    Each(&'a [f32]),
+}
+
+impl Limit<'_> {
+    fn get(self, index: usize) -> f32 {
+        match self {
+            Limit::All(limit) => limit,
+            Limit::Each(limits) => limits[index],
+        }
+    }
+
+    #[cfg(all(feature = "simd", target_arch = "wasm32", target_feature = "simd128"))]
+    fn skip(self, count: usize) -> Self {
+        match self {
+            Limit::All(_) => self,
+            Limit::Each(limits) => Limit::Each(&limits[count..]),
+        }
+    }
+}
+
+#[cfg(all(feature = "simd", target_arch = "wasm32", target_feature = "simd128"))]
+pub(crate) use simd::{integrate, lerp, normalize_moving, spread};
+
+#[cfg(not(all(feature = "simd", target_arch = "wasm32", target_feature = "simd128")))]
+pub(crate) use scalar::{integrate, lerp, normalize_moving, spread};
+
+mod scalar {
+    use super::Limit;
+
+    /// Truncates each force to its `max_forces` limit, adds it over `dt` to the
+    /// velocity, truncates that to `max_speeds` and moves the position by it.
+    pub(crate) fn integrate(
+        positions: &mut [f32],
+        velocities: &mut [f32],
+        forces: &[f32],
+        max_forces: Limit,
+        max_speeds: Limit,
+        dt: f32,
+    ) {
+        let pairs = positions
+            .chunks_exact_mut(2)
+            .zip(velocities.chunks_exact_mut(2))
+            .zip(forces.chunks_exact(2));
+        for (i, ((position, velocity), force)) in pairs.enumerate() {
+            let (fx, fy) = truncate(force[0], force[1], max_forces.get(i));
+            let (vx, vy) = truncate(
+                velocity[0] + fx * dt,
+                velocity[1] + fy * dt,
+                max_speeds.get(i),
+            );
+            velocity[0] = vx;
+            velocity[1] = vy;
+            position[0] += vx * dt;
+            position[1] += vy * dt;
+        }
+    }
+
+    /// Sets each heading to its velocity's direction where the velocity is not
+    /// about zero, keeping the old heading where it is.
+    pub(crate) fn normalize_moving(headings: &mut [f32], velocities: &[f32]) {
+        for (heading, velocity) in headings.chunks_exact_mut(2).zip(velocities.chunks_exact(2)) {
+            let length_squared = velocity[0] * velocity[0] + velocity[1] * velocity[1];
+            if length_squared > 1e-8 {
+                let length = length_squared.sqrt();
+                heading[0] = velocity[0] / length;
+                heading[1] = velocity[1] / length;
+            }
+        }
+    }
+
+    /// `out = from + (to - from) * t`, element by element.
+    pub(crate) fn lerp(out: &mut [f32], from: &[f32], to: &[f32], t: f32) {
+        for ((out, from), to) in out.iter_mut().zip(from).zip(to) {
+            *out = from + (to - from) * t;
+        }
+    }
+
+    /// One step of influence spreading over a `width` by `height` grid: each open
+    /// cell of `values` moves by `rate` toward the strongest of its neighbors in
+    /// `scratch`, a copy of `values`, decayed by `falloff[0]` orthogonally and
+    /// `falloff[1]` diagonally. `open` marks unblocked cells with all bits set.
+    #[cfg(not(all(feature = "simd", target_arch = "wasm32", target_feature = "simd128")))]
+    pub(crate) fn spread(
+        values: &mut [f32],
+        scratch: &[f32],
+        open: &[u32],
+        width: usize,
+        height: usize,
+        falloff: [f32; 2],
+        rate: f32,
+    ) {
+        for y in 0..height {
+            for x in 0..width {
+                spread_cell(values, scratch, open, width, height, x, y, falloff, rate);
+            }
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn spread_cell(
+        values: &mut [f32],
+        scratch: &[f32],
+        open: &[u32],
+        width: usize,
+        height: usize,
+        x: usize,
+        y: usize,
+        falloff: [f32; 2],
+        rate: f32,
+    ) {
+        let index = y * width + x;
+        if open[index] == 0 {
+            return;
+        }
+        // Spread positive and negative influence alike; keep the one with the
+        // larger magnitude.
+        let mut strongest = 0.0f32;
+        for (dx, dy, diagonal) in super::NEIGHBORS {
+            let (nx, ny) = (x as isize + dx, y as isize + dy);
+            if nx < 0 || ny < 0 || nx >= width as isize || ny >= height as isize {
+                continue;
+            }
+            let neighbor = ny as usize * width + nx as usize;
+            if open[neighbor] == 0 {
+                continue;
+            }
+            let value = scratch[neighbor] * falloff[diagonal as usize];
+            if value.abs() > strongest.abs() {
+                strongest = value;
+            }
+        }
+        let current = scratch[index];
+        values[index] = current + (strongest - current) * rate;
+    }
+
+    pub(crate) fn truncate(x: f32, y: f32, max: f32) -> (f32, f32) {
+        let length_squared = x * x + y * y;
+        if length_squared > max * max {
+            let scale = max / length_squared.sqrt();
+            (x * scale, y * scale)
+        } else {
+            (x, y)
+        }
+    }
+}
+
+/// Neighbor offsets, with whether each is diagonal, in the order `spread`
+/// visits them.
+const NEIGHBORS: [(isize, isize, bool); 8] = [
+    (1, 0, false),
+    (-1, 0, false),
+    (0, 1, false),
+    (0, -1, false),
+    (1, 1, true),
+    (1, -1, true),
+    (-1, 1, true),
+    (-1, -1, true),
+];
+
+#[cfg(all(feature = "simd", target_arch = "wasm32", target_feature = "simd128"))]
+mod simd {
+    use std::arch::wasm32::*;
+
+    use super::{scalar, Limit, NEIGHBORS};
+
+    // Loads and stores are unaligned and stay within the slices.
+    fn load(values: &[f32], at: usize) -> v128 {
+        assert!(at + 4 <= values.len());
+        unsafe { v128_load(values.as_ptr().add(at) as *const v128) }
+    }
+
+    fn load_bits(values: &[u32], at: usize) -> v128 {
+        assert!(at + 4 <= values.len());
+        unsafe { v128_load(values.as_ptr().add(at) as *const v128) }
+    }
+
+    fn store(values: &mut [f32], at: usize, vector: v128) {
+        assert!(at + 4 <= values.len());
+        unsafe { v128_store(values.as_mut_ptr().add(at) as *mut v128, vector) }
+    }
+
+    /// `x * x + y * y` of each pair, in both of its lanes.
+    fn length_squared(vector: v128) -> v128 {
+        let squares = f32x4_mul(vector, vector);
+        f32x4_add(squares, i32x4_shuffle::<1, 0, 3, 2>(squares, squares))
+    }
+
+    fn truncate(vector: v128, max: v128) -> v128 {
+        let length_squared = length_squared(vector);
+        let scaled = f32x4_mul(vector, f32x4_div(max, f32x4_sqrt(length_squared)));
+        v128_bitselect(
+            scaled,
+            vector,
+            f32x4_gt(length_squared, f32x4_mul(max, max)),
+        )
+    }
+
+    /// The limits of agents `i` and `i + 1`, one per lane.
+    fn limits(limit: Limit, i: usize) -> v128 {
+        match limit {
+            Limit::All(limit) => f32x4_splat(limit),
+            Limit::Each(limits) => f32x4(limits[i], limits[i], limits[i + 1], limits[i + 1]),
+        }
+    }
+
+    pub(crate) fn integrate(
+        positions: &mut [f32],
+        velocities: &mut [f32],
+        forces: &[f32],
+        max_forces: Limit,
+        max_speeds: Limit,
+        dt: f32,
+    ) {
+        let len = positions.len().min(velocities.len()).min(forces.len()) & !1;
+        let paired = len & !3;
+        let step = f32x4_splat(dt);
+        for at in (0..paired).step_by(4) {
+            let force = truncate(load(forces, at), limits(max_forces, at / 2));
+            let velocity = f32x4_add(load(velocities, at), f32x4_mul(force, step));
+            let velocity = truncate(velocity, limits(max_speeds, at / 2));
+            store(velocities, at, velocity);
+            let position = f32x4_add(load(positions, at), f32x4_mul(velocity, step));
+            store(positions, at, position);
+        }
+        scalar::integrate(
+            &mut positions[paired..len],
+            &mut velocities[paired..len],
+            &forces[paired..len],
+            max_forces.skip(paired / 2),
+            max_speeds.skip(paired / 2),
+            dt,
+        );
+    }
+
+    pub(crate) fn normalize_moving(headings: &mut [f32], velocities: &[f32]) {
+        let len = headings.len().min(velocities.len()) & !1;
+        let paired = len & !3;
+        let threshold = f32x4_splat(1e-8);
+        for at in (0..paired).step_by(4) {
+            let velocity = load(velocities, at);
+            let length_squared = length_squared(velocity);
+            let normalized = f32x4_div(velocity, f32x4_sqrt(length_squared));
+            let moving = f32x4_gt(length_squared, threshold);
+            store(
+                headings,
+                at,
+                v128_bitselect(normalized, load(headings, at), moving),
+            );
+        }
+        scalar::normalize_moving(&mut headings[paired..len], &velocities[paired..len]);
+    }
+
+    pub(crate) fn lerp(out: &mut [f32], from: &[f32], to: &[f32], t: f32) {
+        let len = out.len().min(from.len()).min(to.len());
+        let chunked = len & !3;
+        let ts = f32x4_splat(t);
+        for at in (0..chunked).step_by(4) {
+            let from_values = load(from, at);
+            let offset = f32x4_sub(load(to, at), from_values);
+            store(out, at, f32x4_add(from_values, f32x4_mul(offset, ts)));
+        }
+        scalar::lerp(
+            &mut out[chunked..len],
+            &from[chunked..len],
+            &to[chunked..len],
+            t,
+        );
+    }
+
+    /// Runs the cells away from the border four at a time and the rest, which
+    /// lack some neighbors, one at a time.
+    pub(crate) fn spread(
+        values: &mut [f32],
+        scratch: &[f32],
+        open: &[u32],
+        width: usize,
+        height: usize,
+        falloff: [f32; 2],
+        rate: f32,
+    ) {
+        let cell = |values: &mut [f32], x, y| {
+            scalar::spread_cell(values, scratch, open, width, height, x, y, falloff, rate)
+        };
+        let falloffs = [f32x4_splat(falloff[0]), f32x4_splat(falloff[1])];
+        let rates = f32x4_splat(rate);
+        for y in 0..height {
+            if y == 0 || y + 1 == height || width < 6 {
+                (0..width).for_each(|x| cell(values, x, y));
+                continue;
+            }
+            cell(values, 0, y);
+            let mut x = 1;
+            while x + 5 <= width {
+                let index = y * width + x;
+                let mut strongest = f32x4_splat(0.0);
+                for (dx, dy, diagonal) in NEIGHBORS {
+                    let neighbor = (index as isize + dy * width as isize + dx) as usize;
+                    let value = f32x4_mul(load(scratch, neighbor), falloffs[diagonal as usize]);
+                    let value = v128_and(value, load_bits(open, neighbor));
+                    let stronger = f32x4_gt(f32x4_abs(value), f32x4_abs(strongest));
+                    strongest = v128_bitselect(value, strongest, stronger);
+                }
+                let current = load(scratch, index);
+                let spread = f32x4_add(current, f32x4_mul(f32x4_sub(strongest, current), rates));
+                let kept = load(values, index);
+                store(
+                    values,
+                    index,
+                    v128_bitselect(spread, kept, load_bits(open, index)),
+                );
+                x += 4;
+            }
+            (x..width).for_each(|x| cell(values, x, y));
+        }
+    }
+}
@@ -0,0 +1,597 @@
+use std::collections::VecDeque;
+
+use js_sys::Function;
+use wasm_bindgen::prelude::*;
+
+use crate::blackboard::Blackboard;
+use crate::bytes::{Reader, Writer};
+use crate::error::Error;
+use crate::expression::{Expression, Inputs, NoInputs};
+
+/// Leading bytes of `BehaviorTree::save_state`, then a format version.
+const MAGIC: &[u8; 4] = b"LAIT";
+const VERSION: u32 = 1;
+
+/// Result of ticking a node. `Idle` is only reported for nodes that were not
+/// reached during the last tick.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Status {
+    Idle = 0,
+    Running = 1,
+    Success = 2,
+    Failure = 3,
+}
+
+impl Status {
+    /// Interprets a leaf's return value: booleans map to success/failure, numbers to
+    /// `Status` values, and anything else (including a thrown error) to failure.
+    pub(crate) fn from_js(result: Result<JsValue, JsValue>) -> Status {
+        let value = match result {
+            Ok(value) => value,
+            Err(_) => return Status::Failure,
+        };
+        if let Some(flag) = value.as_bool() {
+            return if flag {
+                Status::Success
+            } else {
+                Status::Failure
+            };
+        }
+        match value.as_f64().map(|n| n as u32) {
+            Some(1) => Status::Running,
+            Some(2) => Status::Success,
+            _ => Status::Failure,
+        }
+    }
+}
+
+pub type Leaf = Box<dyn FnMut() -> Status>;
+
+enum Kind {
+    Sequence,
+    Selector,
+    Parallel { success_threshold: u32 },
+    Inverter,
+    Repeater { count: u32 },
+    Action(Leaf),
+    Condition(Leaf),
+    Expression(Box<Expression>),
+}
+
+impl Kind {
+    fn is_decorator(&self) -> bool {
+        matches!(self, Kind::Inverter | Kind::Repeater { .. })
+    }
+}
+
+struct Node {
+    name: String,
+    kind: Kind,
+    children: Vec<usize>,
+    status: Status,
+    cursor: usize,
+    ticks: u32,
+}
+
+/// Builds a tree top-down. Composite and decorator nodes open a scope that is closed
+/// with `end()`; leaves are added to the innermost open scope.
+#[wasm_bindgen]
+#[derive(Default)]
+pub struct BehaviorTreeBuilder {
+    nodes: Vec<Node>,
+    stack: Vec<usize>,
+    root: Option<usize>,
+    error: Option<Error>,
+}
+
+#[wasm_bindgen]
+impl BehaviorTreeBuilder {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> BehaviorTreeBuilder {
+        BehaviorTreeBuilder::default()
+    }
+
+    /// Runs children in order until one fails or is running.
+    pub fn sequence(&mut self, name: &str) -> u32 {
+        self.open(name, Kind::Sequence)
+    }
+
+    /// Runs children in order until one succeeds or is running.
+    pub fn selector(&mut self, name: &str) -> u32 {
+        self.open(name, Kind::Selector)
+    }
+
+    /// Ticks every child each tick; succeeds once `success_threshold` children
+    /// succeed and fails once that is no longer possible.
+    pub fn parallel(&mut self, name: &str, success_threshold: u32) -> u32 {
+        self.open(name, Kind::Parallel { success_threshold })
+    }
+
+    /// Swaps the child's success and failure.
+    pub fn inverter(&mut self, name: &str) -> u32 {
+        self.open(name, Kind::Inverter)
+    }
+
+    /// Repeats the child until it has succeeded `count` times, failing if it fails.
+    /// A `count` of zero repeats forever.
+    pub fn repeater(&mut self, name: &str, count: u32) -> u32 {
+        self.open(name, Kind::Repeater { count })
+    }
+
+    /// Adds an action leaf. The callback returns a `Status` or a boolean.
+    pub fn action(&mut self, name: &str, callback: Function) -> u32 {
+        self.add(name, Kind::Action(js_leaf(callback)))
+    }
+
+    /// Adds a condition leaf. The callback returns a boolean.
+    pub fn condition(&mut self, name: &str, callback: Function) -> u32 {
+        self.add(name, Kind::Condition(js_leaf(callback)))
+    }
+
+    /// Adds a condition leaf written as an `Expression`, such as
+    /// `health < 0.3 && cooldown(5)`, that succeeds when it is nonzero. It
+    /// reads the blackboard given to `tick_with`; under `tick` every key is
+    /// missing.
+    pub fn expression(&mut self, name: &str, source: &str) -> u32 {
+        match Expression::compile(source) {
+            Ok(expression) => self.add(name, Kind::Expression(Box::new(expression))),
+            Err(Error::InvalidInput(message)) => {
+                self.fail(format!("condition '{}': {}", name, message));
+                u32::MAX
+            }
+        }
+    }
+
+    /// Closes the innermost composite or decorator.
+    pub fn end(&mut self) {
+        if self.stack.pop().is_none() {
+            self.fail("end() called without an open node".into());
+        }
+    }
+
+    pub fn build(self) -> Result<BehaviorTree, Error> {
+        if let Some(error) = self.error {
+            return Err(error);
+        }
+        if !self.stack.is_empty() {
+            return Err(Error::InvalidInput(format!(
+                "{} node(s) were not closed with end()",
+                self.stack.len()
+            )));
+        }
+        let root = self
+            .root
+            .ok_or_else(|| Error::InvalidInput("behavior tree is empty".into()))?;
+        for node in &self.nodes {
+            if node.kind.is_decorator() && node.children.len() != 1 {
+                return Err(Error::InvalidInput(format!(
+                    "decorator '{}' must have exactly one child",
+                    node.name
+                )));
+            }
+        }
+        Ok(BehaviorTree {
+            nodes: self.nodes,
+            root,
+            ticks: 0,
+            clock: 0.0,
+            previous: Vec::new(),
+            trace: VecDeque::new(),
+            trace_capacity: 0,
+        })
+    }
+}
+
+impl BehaviorTreeBuilder {
+    /// Adds an action implemented in Rust.
+    pub fn action_fn(&mut self, name: &str, leaf: impl FnMut() -> Status + 'static) -> u32 {
+        self.add(name, Kind::Action(Box::new(leaf)))
+    }
+
+    /// Adds a condition implemented in Rust.
+    pub fn condition_fn(&mut self, name: &str, leaf: impl FnMut() -> bool + 'static) -> u32 {
+        let mut leaf = leaf;
+        self.add(
+            name,
+            Kind::Condition(Box::new(move || {
+                if leaf() {
+                    Status::Success
+                } else {
+                    Status::Failure
+                }
+            })),
+        )
+    }
+
+    pub(crate) fn leaf(&mut self, name: &str, leaf: Leaf, condition: bool) -> u32 {
+        let kind = if condition {
+            Kind::Condition(leaf)
+        } else {
+            Kind::Action(leaf)
+        };
+        self.add(name, kind)
+    }
+
+    fn open(&mut self, name: &str, kind: Kind) -> u32 {
+        let id = self.add(name, kind);
+        self.stack.push(id as usize);
+        id
+    }
+
+    fn add(&mut self, name: &str, kind: Kind) -> u32 {
+        let id = self.nodes.len();
+        match self.stack.last() {
+            Some(&parent) => self.nodes[parent].children.push(id),
+            None if self.root.is_none() => self.root = Some(id),
+            None => self.fail(format!("'{}' is outside the root node", name)),
+        }
+        self.nodes.push(Node {
+            name: name.to_string(),
+            kind,
+            children: Vec::new(),
+            status: Status::Idle,
+            cursor: 0,
+            ticks: 0,
+        });
+        id as u32
+    }
+
+    fn fail(&mut self, message: String) {
+        if self.error.is_none() {
+            self.error = Some(Error::InvalidInput(message));
+        }
+    }
+}
+
+fn js_leaf(callback: Function) -> Leaf {
+    Box::new(move || Status::from_js(callback.call0(&JsValue::NULL)))
+}
+
+/// A status change of one node between two ticks, as recorded by a tree's
+/// trace.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NodeTransition {
+    /// The tree tick it happened on, counting from 1.
+    pub tick: u32,
+    pub node: u32,
+    pub from: Status,
+    pub to: Status,
+}
+
+/// A tree's state after a tick, for live debuggers.
+#[wasm_bindgen]
+#[derive(Clone, Debug, PartialEq)]
+pub struct BehaviorTreeSnapshot {
+    tick: u32,
+    statuses: Vec<u8>,
+    tick_counts: Vec<u32>,
+    active: Vec<u32>,
+}
+
+#[wasm_bindgen]
+impl BehaviorTreeSnapshot {
+    /// Ticks the tree had run.
+    #[wasm_bindgen(getter)]
+    pub fn tick(&self) -> u32 {
+        self.tick
+    }
+
+    /// Every node's `Status` in the last tick, by node id.
+    pub fn statuses(&self) -> Vec<u8> {
+        self.statuses.clone()
+    }
+
+    /// How many times each node has been ticked in all, by node id.
+    pub fn tick_counts(&self) -> Vec<u32> {
+        self.tick_counts.clone()
+    }
+
+    /// Ids of the nodes still running after the last tick, from the root
+    /// down: the branch the tree resumes next tick, or several under a
+    /// parallel.
+    pub fn active(&self) -> Vec<u32> {
+        self.active.clone()
+    }
+}
+
+#[wasm_bindgen]
+pub struct BehaviorTree {
+    nodes: Vec<Node>,
+    root: usize,
+    ticks: u32,
+    /// Seconds passed to `tick_with`, which expression timers measure.
+    clock: f64,
+    /// Statuses of the tick before the last one, while tracing.
+    previous: Vec<Status>,
+    trace: VecDeque<NodeTransition>,
+    trace_capacity: usize,
+}
+
+#[wasm_bindgen]
+impl BehaviorTree {
+    /// Ticks the tree once from the root and returns the root's status.
+    pub fn tick(&mut self) -> Status {
+        self.run(&NoInputs)
+    }
+
+    /// Ticks the tree with expression conditions reading `blackboard`, after
+    /// advancing their cooldowns and timers by `dt` seconds.
+    pub fn tick_with(&mut self, blackboard: &Blackboard, dt: f32) -> Status {
+        self.clock += dt.max(0.0) as f64;
+        self.run(blackboard)
+    }
+
+    /// The last tick's statuses, the running branch and per-node tick counts.
+    pub fn snapshot(&self) -> BehaviorTreeSnapshot {
+        BehaviorTreeSnapshot {
+            tick: self.ticks,
+            statuses: self.statuses(),
+            tick_counts: self.nodes.iter().map(|node| node.ticks).collect(),
+            active: (0..self.nodes.len() as u32)
+                .filter(|&id| self.nodes[id as usize].status == Status::Running)
+                .collect(),
+        }
+    }
+
+    /// Ids of a node's children in order, for drawing the tree.
+    pub fn children(&self, id: u32) -> Vec<u32> {
+        self.nodes
+            .get(id as usize)
+            .map(|node| node.children.iter().map(|&child| child as u32).collect())
+            .unwrap_or_default()
+    }
+
+    /// Records every node status change between ticks, keeping the latest
+    /// `capacity`; 0, the default, stops tracing and drops the trace.
+    pub fn set_trace_capacity(&mut self, capacity: u32) {
+        self.trace_capacity = capacity as usize;
+        while self.trace.len() > self.trace_capacity {
+            self.trace.pop_front();
+        }
+        if capacity == 0 {
+            self.trace = VecDeque::new();
+        }
+    }
+
+    /// The recorded status changes, oldest first, emptying the trace.
+    pub fn take_trace(&mut self) -> Vec<NodeTransition> {
+        self.trace.drain(..).collect()
+    }
+
+    /// Where every node is in its work, from the running branch and repeater
+    /// counts to expression cooldowns, with the tick counts and clock, as
+    /// bytes for `load_state`, e.g. in a `SaveGame`. The leaves themselves
+    /// and the trace are left out.
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut out = Writer::new(MAGIC, VERSION);
+        out.u32(self.ticks);
+        out.f64(self.clock);
+        out.u32(self.nodes.len() as u32);
+        for node in &self.nodes {
+            out.string(&node.name);
+            out.u32s(&[node.status as u32, node.cursor as u32, node.ticks]);
+            if let Kind::Expression(expression) = &node.kind {
+                expression.write_timers(&mut out);
+            }
+        }
+        out.finish()
+    }
+
+    /// Carries on from where the tree `save_state` came from left off. This
+    /// tree must be built the same way, which its node names are checked
+    /// against.
+    pub fn load_state(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        let (mut input, version) = Reader::new(bytes, MAGIC, "a behavior tree save")?;
+        if version != VERSION {
+            return Err(Error::InvalidInput(
+                "unsupported behavior tree save version".into(),
+            ));
+        }
+        let ticks = input.u32()?;
+        let clock = input.f64()?;
+        let mismatch = || Error::InvalidInput("the save is of a differently built tree".into());
+        if input.u32()? as usize != self.nodes.len() {
+            return Err(mismatch());
+        }
+        let mut states = Vec::with_capacity(self.nodes.len());
+        for node in &self.nodes {
+            if input.string()? != node.name {
+                return Err(mismatch());
+            }
+            let status = match input.u32()? {
+                0 => Status::Idle,
+                1 => Status::Running,
+                2 => Status::Success,
+                3 => Status::Failure,
+                _ => return Err(input.invalid()),
+            };
+            let (cursor, node_ticks) = (input.u32()? as usize, input.u32()?);
+            let timers = match &node.kind {
+                Kind::Expression(expression) => Some(expression.read_timers(&mut input)?),
+                _ => None,
+            };
+            states.push((status, cursor, node_ticks, timers));
+        }
+        input.finish()?;
+
+        self.ticks = ticks;
+        self.clock = clock;
+        for (node, (status, cursor, ticks, timers)) in self.nodes.iter_mut().zip(states) {
+            node.status = status;
+            node.cursor = cursor;
+            node.ticks = ticks;
+            if let (Kind::Expression(expression), Some(timers)) = (&mut node.kind, timers) {
+                expression.set_timers(timers);
+            }
+        }
+        Ok(())
+    }
+
+    /// Clears all running state so the next tick starts from scratch.
+    pub fn reset(&mut self) {
+        self.reset_node(self.root);
+    }
+
+    /// Status of every node from the last tick, indexed by the ids returned by the builder.
+    pub fn statuses(&self) -> Vec<u8> {
+        self.nodes.iter().map(|node| node.status as u8).collect()
+    }
+
+    pub fn status(&self, id: u32) -> Status {
+        self.nodes
+            .get(id as usize)
+            .map_or(Status::Idle, |node| node.status)
+    }
+
+    pub fn node_name(&self, id: u32) -> Option<String> {
+        self.nodes.get(id as usize).map(|node| node.name.clone())
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn node_count(&self) -> u32 {
+        self.nodes.len() as u32
+    }
+}
+
+impl BehaviorTree {
+    fn run(&mut self, inputs: &dyn Inputs) -> Status {
+        self.ticks = self.ticks.wrapping_add(1);
+        if self.trace_capacity > 0 {
+            self.previous.clear();
+            self.previous
+                .extend(self.nodes.iter().map(|node| node.status));
+        }
+        for node in &mut self.nodes {
+            node.status = Status::Idle;
+        }
+        let status = self.tick_node(self.root, inputs);
+        if self.trace_capacity > 0 {
+            self.record_transitions();
+        }
+        status
+    }
+
+    fn record_transitions(&mut self) {
+        for (id, (node, &from)) in self.nodes.iter().zip(&self.previous).enumerate() {
+            if node.status != from {
+                if self.trace.len() == self.trace_capacity {
+                    self.trace.pop_front();
+                }
+                self.trace.push_back(NodeTransition {
+                    tick: self.ticks,
+                    node: id as u32,
+                    from,
+                    to: node.status,
+                });
+            }
+        }
+    }
+
+    fn tick_node(&mut self, index: usize, inputs: &dyn Inputs) -> Status {
+        let clock = self.clock;
+        self.nodes[index].ticks = self.nodes[index].ticks.wrapping_add(1);
+        let status = match self.nodes[index].kind {
+            Kind::Sequence => self.tick_composite(index, Status::Success, inputs),
+            Kind::Selector => self.tick_composite(index, Status::Failure, inputs),
+            Kind::Parallel { success_threshold } => {
+                self.tick_parallel(index, success_threshold, inputs)
+            }
+            Kind::Inverter => match self.tick_node(self.nodes[index].children[0], inputs) {
+                Status::Success => Status::Failure,
+                Status::Failure => Status::Success,
+                other => other,
+            },
+            Kind::Repeater { count } => self.tick_repeater(index, count, inputs),
+            Kind::Action(ref mut leaf) | Kind::Condition(ref mut leaf) => leaf(),
+            Kind::Expression(ref mut expression) => {
+                if expression.run(inputs, clock) != 0.0 {
+                    Status::Success
+                } else {
+                    Status::Failure
+                }
+            }
+        };
+        self.nodes[index].status = status;
+        status
+    }
+
+    /// Sequence and selector share this loop: `proceed` is the child result that moves
+    /// on to the next child; any other result ends the tick.
+    fn tick_composite(&mut self, index: usize, proceed: Status, inputs: &dyn Inputs) -> Status {
+        while self.nodes[index].cursor < self.nodes[index].children.len() {
+            let child = self.nodes[index].children[self.nodes[index].cursor];
+            let status = self.tick_node(child, inputs);
+            if status == Status::Running {
+                return Status::Running;
+            }
+            if status != proceed {
+                self.nodes[index].cursor = 0;
+                return status;
+            }
+            self.nodes[index].cursor += 1;
+        }
+        self.nodes[index].cursor = 0;
+        proceed
+    }
+
+    fn tick_parallel(
+        &mut self,
+        index: usize,
+        success_threshold: u32,
+        inputs: &dyn Inputs,
+    ) -> Status {
+        let count = self.nodes[index].children.len();
+        let mut successes = 0;
+        let mut failures = 0;
+        for i in 0..count {
+            let child = self.nodes[index].children[i];
+            match self.tick_node(child, inputs) {
+                Status::Success => successes += 1,
+                Status::Failure => failures += 1,
+                _ => {}
+            }
+        }
+        let threshold = (success_threshold as usize).min(count);
+        let status = if successes >= threshold {
+            Status::Success
+        } else if count - failures < threshold {
+            Status::Failure
+        } else {
+            Status::Running
+        };
+        if status != Status::Running {
+            self.reset_node(index);
+        }
+        status
+    }
+
+    fn tick_repeater(&mut self, index: usize, count: u32, inputs: &dyn Inputs) -> Status {
+        let child = self.nodes[index].children[0];
+        match self.tick_node(child, inputs) {
+            Status::Success => {
+                self.nodes[index].cursor += 1;
+                if count > 0 && self.nodes[index].cursor >= count as usize {
+                    self.nodes[index].cursor = 0;
+                    Status::Success
+                } else {
+                    Status::Running
+                }
+            }
+            Status::Failure => {
+                self.nodes[index].cursor = 0;
+                Status::Failure
+            }
+            other => other,
+        }
+    }
+
+    fn reset_node(&mut self, index: usize) {
+        self.nodes[index].cursor = 0;
+        for i in 0..self.nodes[index].children.len() {
+            let child = self.nodes[index].children[i];
+            self.reset_node(child);
+        }
+    }
+}
@@ -0,0 +1,474 @@
+use wasm_bindgen::prelude::*;
+
+use crate::error::Error;
+use crate::grid::Grid;
+use crate::hpa::HierarchicalGrid;
+use crate::math::Vec2;
+use crate::orca::CrowdSimulator;
+use crate::random::Rng;
+use crate::spatial_hash::SpatialHash;
+use crate::stats::Sample;
+
+/// Step of the crowd scenario, in seconds.
+const CROWD_DT: f32 = 1.0 / 30.0;
+const AGENT_RADIUS: f32 = 0.5;
+const AGENT_SPEED: f32 = 1.5;
+
+/// Which grid pathfinder `bench_paths` runs.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PathAlgorithm {
+    /// `Grid::find_path`.
+    AStar = 0,
+    /// `Grid::find_path_jps`.
+    JumpPoint = 1,
+    /// `Grid::find_path_theta`.
+    Theta = 2,
+    /// `HierarchicalGrid::find_path`, built before timing starts.
+    Hierarchical = 3,
+}
+
+/// What a benchmark scenario measured: timings per iteration, a query or a
+/// step, and how good the results were, so runs of different configurations
+/// or builds can be compared side by side or checked with `regressed` in CI.
+/// Fields a scenario has no use for stay at zero.
+///
+/// Timings come from `performance.now()` in the browser, coarsened as noted
+/// on `AiStats`, so give scenarios enough iterations for means to settle.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct BenchReport {
+    /// Queries or steps timed.
+    pub iterations: u32,
+    /// Microseconds of untimed preparation, e.g. building an HPA* abstraction.
+    pub setup_us: f64,
+    pub total_us: f64,
+    pub mean_us: f64,
+    pub median_us: f64,
+    /// Microseconds that 95% of iterations came in under.
+    pub p95_us: f64,
+    pub max_us: f64,
+    /// A* nodes expanded by every iteration together, for the searches that
+    /// count them.
+    pub node_expansions: u32,
+    /// Paths found, or agents that reached their goal.
+    pub successes: u32,
+    /// Mean length of the paths found, in cells.
+    pub path_length: f32,
+    /// Mean of each path's length over the A* path's, below 1 for any-angle
+    /// paths and above for approximate ones.
+    pub path_ratio: f32,
+    /// Times a pair of agents overlapped at the end of a step.
+    pub collisions: u32,
+    /// Smallest gap seen between two agents' edges, negative when they
+    /// overlapped.
+    pub min_clearance: f32,
+    /// Mean seconds the agents that reached their goal took.
+    pub arrival_time: f32,
+}
+
+#[wasm_bindgen]
+impl BenchReport {
+    /// Whether this run did worse than `baseline`: a mean or 95th percentile
+    /// time over `tolerance` times, e.g. 0.2 for 20%, slower, fewer successes
+    /// or more collisions.
+    pub fn regressed(&self, baseline: &BenchReport, tolerance: f32) -> bool {
+        let slack = 1.0 + tolerance.max(0.0) as f64;
+        self.mean_us > baseline.mean_us * slack
+            || self.p95_us > baseline.p95_us * slack
+            || self.successes < baseline.successes
+            || self.collisions > baseline.collisions
+    }
+}
+
+impl BenchReport {
+    /// Fills in the timing fields from each iteration's microseconds.
+    fn timed(mut samples: Vec<f64>) -> BenchReport {
+        samples.sort_by(f64::total_cmp);
+        let count = samples.len();
+        let total_us: f64 = samples.iter().sum();
+        let percentile = |fraction: f64| {
+            let rank = (fraction * count as f64).ceil() as usize;
+            samples[rank.clamp(1, count) - 1]
+        };
+        BenchReport {
+            iterations: count as u32,
+            total_us,
+            mean_us: total_us / count as f64,
+            median_us: percentile(0.5),
+            p95_us: percentile(0.95),
+            max_us: samples[count - 1],
+            ..BenchReport::default()
+        }
+    }
+}
+
+/// A `width` by `height` maze carved by a randomized depth-first search, with
+/// corridors on odd cells and a fraction `loops` of the walls between two
+/// corridors knocked through, 0 for a perfect maze with one route between any
+/// two cells.
+#[wasm_bindgen]
+pub fn maze_grid(width: u32, height: u32, loops: f32, seed: u64) -> Result<Grid, Error> {
+    if width < 3 || height < 3 {
+        return Err(Error::InvalidInput(
+            "a maze needs at least 3 by 3 cells".into(),
+        ));
+    }
+    let mut rng = Rng::new(seed);
+    let mut grid = Grid::new(width, height)?;
+    for y in 0..height {
+        for x in 0..width {
+            grid.set_walkable(x, y, false);
+        }
+    }
+    let (rooms_x, rooms_y) = ((width - 1) / 2, (height - 1) / 2);
+    let mut visited = vec![false; (rooms_x * rooms_y) as usize];
+    let mut stack = vec![(0, 0)];
+    visited[0] = true;
+    grid.set_walkable(1, 1, true);
+    let mut options = Vec::with_capacity(4);
+    while let Some(&(x, y)) = stack.last() {
+        options.clear();
+        for (dx, dy) in [(1, 0), (-1, 0), (0, 1), (0, -1)] {
+            let (nx, ny) = (x as i32 + dx, y as i32 + dy);
+            if nx >= 0 && ny >= 0 && (nx as u32) < rooms_x && (ny as u32) < rooms_y {
+                let (nx, ny) = (nx as u32, ny as u32);
+                if !visited[(ny * rooms_x + nx) as usize] {
+                    options.push((nx, ny));
+                }
+            }
+        }
+        if options.is_empty() {
+            stack.pop();
+            continue;
+        }
+        let (nx, ny) = options[rng.below(options.len())];
+        visited[(ny * rooms_x + nx) as usize] = true;
+        grid.set_walkable(x + nx + 1, y + ny + 1, true);
+        grid.set_walkable(2 * nx + 1, 2 * ny + 1, true);
+        stack.push((nx, ny));
+    }
+
+    if loops > 0.0 {
+        for y in 1..height - 1 {
+            for x in 1..width - 1 {
+                let (cx, cy) = (x as i32, y as i32);
+                if grid.is_walkable(cx, cy) || (x + y).is_multiple_of(2) {
+                    continue;
+                }
+                let joins = (grid.is_walkable(cx - 1, cy) && grid.is_walkable(cx + 1, cy))
+                    || (grid.is_walkable(cx, cy - 1) && grid.is_walkable(cx, cy + 1));
+                if joins && rng.chance(loops) {
+                    grid.set_walkable(x, y, true);
+                }
+            }
+        }
+    }
+    Ok(grid)
+}
+
+/// A `width` by `height` grid with each cell blocked at random with
+/// probability `density`, like rubble or a forest. High densities leave parts
+/// unreachable from each other.
+#[wasm_bindgen]
+pub fn random_grid(width: u32, height: u32, density: f32, seed: u64) -> Result<Grid, Error> {
+    let mut rng = Rng::new(seed);
+    let mut grid = Grid::new(width, height)?;
+    for y in 0..height {
+        for x in 0..width {
+            if rng.chance(density) {
+                grid.set_walkable(x, y, false);
+            }
+        }
+    }
+    Ok(grid)
+}
+
+/// Times `queries` searches by `algorithm` between random pairs of walkable
+/// cells of `grid`, the same pairs for the same `seed` whatever the
+/// algorithm, and rates the paths against A*'s. `cluster_size` is only used
+/// by `Hierarchical`.
+#[wasm_bindgen]
+pub fn bench_paths(
+    grid: &Grid,
+    algorithm: PathAlgorithm,
+    queries: u32,
+    cluster_size: u32,
+    seed: u64,
+) -> Result<BenchReport, Error> {
+    if queries == 0 {
+        return Err(Error::InvalidInput(
+            "a benchmark needs at least one query".into(),
+        ));
+    }
+    let open: Vec<(u32, u32)> = (0..grid.height())
+        .flat_map(|y| (0..grid.width()).map(move |x| (x, y)))
+        .filter(|&(x, y)| grid.is_walkable(x as i32, y as i32))
+        .collect();
+    if open.len() < 2 {
+        return Err(Error::InvalidInput(
+            "a path benchmark needs two walkable cells".into(),
+        ));
+    }
+    let mut rng = Rng::new(seed);
+    let pairs: Vec<((u32, u32), (u32, u32))> = (0..queries)
+        .map(|_| (open[rng.below(open.len())], open[rng.below(open.len())]))
+        .collect();
+
+    let setup = Sample::start();
+    let hierarchy = (algorithm == PathAlgorithm::Hierarchical)
+        .then(|| HierarchicalGrid::new(grid, cluster_size));
+    let setup_us = setup.elapsed_us();
+
+    let mut samples = Vec::with_capacity(pairs.len());
+    let mut paths = Vec::with_capacity(pairs.len());
+    let mut node_expansions = 0u32;
+    for &((sx, sy), (ex, ey)) in &pairs {
+        let sample = Sample::start();
+        let path = match (&hierarchy, algorithm) {
+            (Some(hierarchy), _) => hierarchy.find_path(sx, sy, ex, ey),
+            (None, PathAlgorithm::JumpPoint) => grid.find_path_jps(sx, sy, ex, ey),
+            (None, PathAlgorithm::Theta) => grid.find_path_theta(sx, sy, ex, ey),
+            (None, _) => grid.find_path(sx, sy, ex, ey),
+        };
+        samples.push(sample.elapsed_us());
+        node_expansions = node_expansions.wrapping_add(sample.expansions());
+        paths.push(path);
+    }
+
+    let mut report = BenchReport::timed(samples);
+    report.setup_us = setup_us;
+    report.node_expansions = node_expansions;
+    let (mut length_sum, mut ratio_sum, mut compared) = (0.0, 0.0, 0);
+    for (path, &((sx, sy), (ex, ey))) in paths.iter().zip(&pairs) {
+        if path.is_empty() {
+            continue;
+        }
+        report.successes += 1;
+        let length = path_length(path);
+        length_sum += length;
+        let optimal = if algorithm == PathAlgorithm::AStar {
+            length
+        } else {
+            path_length(&grid.find_path(sx, sy, ex, ey))
+        };
+        if optimal > 0.0 {
+            ratio_sum += length / optimal;
+            compared += 1;
+        }
+    }
+    if report.successes > 0 {
+        report.path_length = length_sum / report.successes as f32;
+    }
+    report.path_ratio = if compared > 0 {
+        ratio_sum / compared as f32
+    } else {
+        1.0
+    };
+    Ok(report)
+}
+
+/// Times `steps` steps of a `CrowdSimulator` with `neighbor_distance` and
+/// `time_horizon` moving `agents` agents, spread around a circle, each to the
+/// point across from it, the standard stress test of reciprocal avoidance
+/// since every agent meets every other in the middle. Rates how often agents
+/// overlapped and how many got across. Agents walk 1.5 units a second and
+/// the circle is about 0.64 units across per agent, so big crowds need a few
+/// thousand of the 1/30 second steps to get across.
+#[wasm_bindgen]
+pub fn bench_crowd(
+    agents: u32,
+    steps: u32,
+    neighbor_distance: f32,
+    time_horizon: f32,
+    seed: u64,
+) -> Result<BenchReport, Error> {
+    if agents < 2 || steps == 0 {
+        return Err(Error::InvalidInput(
+            "a crowd benchmark needs at least two agents and one step".into(),
+        ));
+    }
+    let mut rng = Rng::new(seed);
+    // Room on the circle for each agent and a body's width between them.
+    let radius = (agents as f32 * 4.0 * AGENT_RADIUS / std::f32::consts::TAU).max(5.0);
+    let mut crowd = CrowdSimulator::new(neighbor_distance, time_horizon);
+    let mut goals = Vec::with_capacity(agents as usize);
+    for i in 0..agents {
+        // Jittered so the crowd is not perfectly symmetric, where avoidance
+        // can deadlock in ways a real scene never does.
+        let angle = (i as f32 + rng.next_signed() * 0.1) / agents as f32 * std::f32::consts::TAU;
+        let start = Vec2::new(angle.cos(), angle.sin()) * radius;
+        crowd.add_agent(start.x, start.y, AGENT_RADIUS, AGENT_SPEED);
+        goals.push(-start);
+    }
+
+    let mut arrived: Vec<Option<f32>> = vec![None; agents as usize];
+    let mut samples = Vec::with_capacity(steps as usize);
+    let mut neighbors = SpatialHash::new(AGENT_RADIUS * 2.0);
+    let mut nearby = Vec::new();
+    let mut collisions = 0;
+    let mut min_clearance = f32::INFINITY;
+    for step in 0..steps {
+        for (id, goal) in goals.iter().enumerate() {
+            let position = crowd.position(id as u32).unwrap_or_default();
+            let offset = *goal - position;
+            let distance = offset.length();
+            // Slows to land on the goal instead of overshooting it.
+            let speed = AGENT_SPEED.min(distance / CROWD_DT);
+            let velocity = if distance > 0.0 {
+                offset * (speed / distance)
+            } else {
+                Vec2::ZERO
+            };
+            crowd.set_preferred_velocity(id as u32, velocity.x, velocity.y);
+        }
+        let sample = Sample::start();
+        crowd.step(CROWD_DT);
+        samples.push(sample.elapsed_us());
+
+        let positions = crowd.positions();
+        neighbors.clear();
+        for (id, p) in positions.chunks_exact(2).enumerate() {
+            neighbors.insert(id as u32, p[0], p[1]);
+        }
+        let time = (step + 1) as f32 * CROWD_DT;
+        for (id, p) in positions.chunks_exact(2).enumerate() {
+            let position = Vec2::new(p[0], p[1]);
+            if arrived[id].is_none() && position.distance(goals[id]) <= AGENT_RADIUS {
+                arrived[id] = Some(time);
+            }
+            nearby.clear();
+            neighbors.query_radius_into(p[0], p[1], AGENT_RADIUS * 4.0, &mut nearby);
+            for &other in nearby.iter().filter(|&&other| other as usize > id) {
+                let q = &positions[other as usize * 2..other as usize * 2 + 2];
+                let gap = position.distance(Vec2::new(q[0], q[1])) - AGENT_RADIUS * 2.0;
+                min_clearance = min_clearance.min(gap);
+                // A sliver of tolerance for the solver's rounding.
+                if gap < -AGENT_RADIUS * 0.02 {
+                    collisions += 1;
+                }
+            }
+        }
+    }
+
+    let mut report = BenchReport::timed(samples);
+    let times: Vec<f32> = arrived.into_iter().flatten().collect();
+    report.successes = times.len() as u32;
+    if !times.is_empty() {
+        report.arrival_time = times.iter().sum::<f32>() / times.len() as f32;
+    }
+    report.collisions = collisions;
+    report.min_clearance = if min_clearance.is_finite() {
+        min_clearance
+    } else {
+        0.0
+    };
+    Ok(report)
+}
+
+/// Length of flat `[x0, y0, x1, y1, ...]` path points joined by straight
+/// segments.
+fn path_length(path: &[f32]) -> f32 {
+    path.chunks_exact(2)
+        .map(|p| Vec2::new(p[0], p[1]))
+        .collect::<Vec<_>>()
+        .windows(2)
+        .map(|pair| pair[0].distance(pair[1]))
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn walkable_cells(grid: &Grid) -> Vec<(i32, i32)> {
+        (0..grid.height() as i32)
+            .flat_map(|y| (0..grid.width() as i32).map(move |x| (x, y)))
+            .filter(|&(x, y)| grid.is_walkable(x, y))
+            .collect()
+    }
+
+    #[test]
+    fn maze_reaches_every_corridor() {
+        let grid = maze_grid(21, 15, 0.0, 7).unwrap();
+        for (x, y) in walkable_cells(&grid) {
+            assert!(
+                !grid.find_path(1, 1, x as u32, y as u32).is_empty(),
+                "({x}, {y}) is cut off"
+            );
+        }
+        assert!(!grid.is_walkable(0, 0));
+        assert!(maze_grid(2, 9, 0.0, 7).is_err());
+    }
+
+    #[test]
+    fn same_seed_same_grid() {
+        let cells = |grid: Grid| walkable_cells(&grid);
+        assert_eq!(
+            cells(maze_grid(31, 31, 0.2, 3).unwrap()),
+            cells(maze_grid(31, 31, 0.2, 3).unwrap())
+        );
+        assert_eq!(
+            cells(random_grid(24, 24, 0.3, 3).unwrap()),
+            cells(random_grid(24, 24, 0.3, 3).unwrap())
+        );
+        assert_eq!(cells(random_grid(8, 8, 0.0, 3).unwrap()).len(), 64);
+    }
+
+    #[test]
+    fn paths_on_an_open_grid() {
+        let grid = random_grid(32, 32, 0.0, 1).unwrap();
+        let astar = bench_paths(&grid, PathAlgorithm::AStar, 20, 8, 5).unwrap();
+        assert_eq!(astar.iterations, 20);
+        assert_eq!(astar.successes, 20);
+        assert_eq!(astar.path_ratio, 1.0);
+        assert!(astar.node_expansions > 0);
+        let theta = bench_paths(&grid, PathAlgorithm::Theta, 20, 8, 5).unwrap();
+        assert_eq!(theta.successes, 20);
+        assert!(theta.path_ratio <= 1.0 + 1e-4);
+        let hierarchical = bench_paths(&grid, PathAlgorithm::Hierarchical, 20, 8, 5).unwrap();
+        assert_eq!(hierarchical.successes, 20);
+        assert!(hierarchical.path_ratio >= 1.0 - 1e-4);
+    }
+
+    #[test]
+    fn paths_need_queries_and_room() {
+        let grid = random_grid(8, 8, 0.0, 1).unwrap();
+        assert!(bench_paths(&grid, PathAlgorithm::AStar, 0, 8, 1).is_err());
+        let blocked = random_grid(8, 8, 1.0, 1).unwrap();
+        assert!(bench_paths(&blocked, PathAlgorithm::AStar, 4, 8, 1).is_err());
+    }
+
+    #[test]
+    fn crowd_crosses_the_circle() {
+        let report = bench_crowd(32, 1500, 5.0, 2.0, 9).unwrap();
+        assert_eq!(report.iterations, 1500);
+        assert_eq!(report.successes, 32);
+        assert_eq!(report.collisions, 0);
+        assert!(report.arrival_time > 0.0);
+        assert!(bench_crowd(1, 10, 5.0, 2.0, 9).is_err());
+        assert!(bench_crowd(8, 0, 5.0, 2.0, 9).is_err());
+    }
+
+    #[test]
+    fn percentiles_and_regressions() {
+        let report = BenchReport::timed((1..=20).rev().map(f64::from).collect());
+        assert_eq!(report.iterations, 20);
+        assert_eq!(report.median_us, 10.0);
+        assert_eq!(report.p95_us, 19.0);
+        assert_eq!(report.max_us, 20.0);
+        assert_eq!(report.mean_us, 10.5);
+
+        let slower = BenchReport {
+            mean_us: report.mean_us * 1.1,
+            ..report
+        };
+        assert!(!slower.regressed(&report, 0.2));
+        assert!(slower.regressed(&report, 0.05));
+        let worse = BenchReport {
+            collisions: report.collisions + 1,
+            ..report
+        };
+        assert!(worse.regressed(&report, 0.2));
+    }
+}
@@ -0,0 +1,295 @@
+use std::collections::{HashMap, HashSet};
+
+use wasm_bindgen::prelude::*;
+
+use crate::bytes::{Reader, Writer};
+use crate::error::Error;
+use crate::math::{Vec2, Vec3};
+
+/// Leading bytes of `Blackboard::save`, then a format version.
+const MAGIC: &[u8; 4] = b"LAIB";
+const VERSION: u32 = 2;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    F64(f64),
+    Bool(bool),
+    Str(String),
+    Vec2(Vec2),
+    Vec3(Vec3),
+}
+
+impl Value {
+    /// Numeric view used by scoring systems: numbers as-is, booleans as 0 or 1.
+    pub fn as_number(&self) -> Option<f64> {
+        match self {
+            Value::F64(value) => Some(*value),
+            Value::Bool(flag) => Some(if *flag { 1.0 } else { 0.0 }),
+            _ => None,
+        }
+    }
+}
+
+struct Entry {
+    value: Value,
+    changed: bool,
+}
+
+/// Named facts shared between AI systems and JS. Writing a different value or
+/// removing the key marks it as changed until `clear_changes` is called, so
+/// consumers can react to updates instead of re-reading everything each tick.
+#[wasm_bindgen]
+#[derive(Default)]
+pub struct Blackboard {
+    entries: HashMap<String, Entry>,
+    /// Keys removed since the last `clear_changes`.
+    removed: HashSet<String>,
+}
+
+#[wasm_bindgen]
+impl Blackboard {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Blackboard {
+        Blackboard::default()
+    }
+
+    pub fn set_f64(&mut self, key: &str, value: f64) {
+        self.set(key, Value::F64(value));
+    }
+
+    pub fn get_f64(&self, key: &str) -> Option<f64> {
+        match self.get(key) {
+            Some(Value::F64(value)) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn set_bool(&mut self, key: &str, value: bool) {
+        self.set(key, Value::Bool(value));
+    }
+
+    pub fn get_bool(&self, key: &str) -> Option<bool> {
+        match self.get(key) {
+            Some(Value::Bool(value)) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn set_string(&mut self, key: &str, value: &str) {
+        self.set(key, Value::Str(value.to_string()));
+    }
+
+    pub fn get_string(&self, key: &str) -> Option<String> {
+        match self.get(key) {
+            Some(Value::Str(value)) => Some(value.clone()),
+            _ => None,
+        }
+    }
+
+    pub fn set_vec2(&mut self, key: &str, x: f32, y: f32) {
+        self.set(key, Value::Vec2(Vec2::new(x, y)));
+    }
+
+    pub fn get_vec2(&self, key: &str) -> Option<Vec2> {
+        match self.get(key) {
+            Some(Value::Vec2(value)) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn set_vec3(&mut self, key: &str, x: f32, y: f32, z: f32) {
+        self.set(key, Value::Vec3(Vec3::new(x, y, z)));
+    }
+
+    pub fn get_vec3(&self, key: &str) -> Option<Vec3> {
+        match self.get(key) {
+            Some(Value::Vec3(value)) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn has(&self, key: &str) -> bool {
+        self.entries.contains_key(key)
+    }
+
+    pub fn remove(&mut self, key: &str) -> bool {
+        let removed = self.entries.remove(key).is_some();
+        if removed {
+            self.removed.insert(key.to_string());
+        }
+        removed
+    }
+
+    pub fn keys(&self) -> Vec<String> {
+        self.entries.keys().cloned().collect()
+    }
+
+    /// Whether `key` was written with a new value or removed since the last
+    /// `clear_changes`.
+    pub fn changed(&self, key: &str) -> bool {
+        self.entries.get(key).is_some_and(|entry| entry.changed) || self.removed.contains(key)
+    }
+
+    pub fn has_changes(&self) -> bool {
+        !self.removed.is_empty() || self.entries.values().any(|entry| entry.changed)
+    }
+
+    /// The changed keys, removed ones included.
+    pub fn changed_keys(&self) -> Vec<String> {
+        self.entries
+            .iter()
+            .filter(|(_, entry)| entry.changed)
+            .map(|(key, _)| key)
+            .chain(&self.removed)
+            .cloned()
+            .collect()
+    }
+
+    pub fn clear_changes(&mut self) {
+        for entry in self.entries.values_mut() {
+            entry.changed = false;
+        }
+        self.removed.clear();
+    }
+
+    /// Removes every key, marking each as changed.
+    pub fn clear(&mut self) {
+        self.removed
+            .extend(self.entries.drain().map(|(key, _)| key));
+    }
+
+    /// Every entry and its changed flag, and the keys removed since the last
+    /// `clear_changes`, as bytes for `load`, e.g. in a `SaveGame`.
+    pub fn save(&self) -> Vec<u8> {
+        let mut out = Writer::new(MAGIC, VERSION);
+        let mut keys: Vec<&String> = self.entries.keys().collect();
+        keys.sort();
+        out.u32(keys.len() as u32);
+        for key in keys {
+            let entry = &self.entries[key];
+            out.string(key);
+            out.u32(entry.changed as u32);
+            match &entry.value {
+                Value::F64(value) => {
+                    out.u32(0);
+                    out.f64(*value);
+                }
+                Value::Bool(flag) => {
+                    out.u32(1);
+                    out.u32(*flag as u32);
+                }
+                Value::Str(text) => {
+                    out.u32(2);
+                    out.string(text);
+                }
+                Value::Vec2(vector) => {
+                    out.u32(3);
+                    out.f32s(&[vector.x, vector.y]);
+                }
+                Value::Vec3(vector) => {
+                    out.u32(4);
+                    out.f32s(&[vector.x, vector.y, vector.z]);
+                }
+            }
+        }
+        let mut removed: Vec<&String> = self.removed.iter().collect();
+        removed.sort();
+        out.u32(removed.len() as u32);
+        for key in removed {
+            out.string(key);
+        }
+        out.finish()
+    }
+
+    /// Restores a blackboard from `save`.
+    pub fn load(bytes: &[u8]) -> Result<Blackboard, Error> {
+        let (mut input, version) = Reader::new(bytes, MAGIC, "a blackboard save")?;
+        if version != VERSION {
+            return Err(Error::InvalidInput(
+                "unsupported blackboard save version".into(),
+            ));
+        }
+        let mut blackboard = Blackboard::new();
+        for _ in 0..input.u32()? {
+            let key = input.string()?;
+            let changed = input.u32()? != 0;
+            let value = match input.u32()? {
+                0 => Value::F64(input.f64()?),
+                1 => Value::Bool(input.u32()? != 0),
+                2 => Value::Str(input.string()?),
+                3 => Value::Vec2(Vec2::new(input.f32()?, input.f32()?)),
+                4 => Value::Vec3(Vec3::new(input.f32()?, input.f32()?, input.f32()?)),
+                _ => return Err(input.invalid()),
+            };
+            blackboard.entries.insert(key, Entry { value, changed });
+        }
+        for _ in 0..input.u32()? {
+            let key = input.string()?;
+            if blackboard.entries.contains_key(&key) {
+                return Err(input.invalid());
+            }
+            blackboard.removed.insert(key);
+        }
+        input.finish()?;
+        Ok(blackboard)
+    }
+}
+
+impl Blackboard {
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.entries.get(key).map(|entry| &entry.value)
+    }
+
+    /// Stores `value`, flagging the key as changed unless the value is identical.
+    pub fn set(&mut self, key: &str, value: Value) {
+        match self.entries.get_mut(key) {
+            Some(entry) => {
+                if entry.value != value {
+                    entry.value = value;
+                    entry.changed = true;
+                }
+            }
+            None => {
+                // Now changed as an entry rather than as a removal.
+                self.removed.remove(key);
+                self.entries.insert(
+                    key.to_string(),
+                    Entry {
+                        value,
+                        changed: true,
+                    },
+                );
+            }
+        }
+    }
+
+    pub fn get_number(&self, key: &str) -> Option<f64> {
+        self.get(key).and_then(Value::as_number)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn removals_count_as_changes() {
+        let mut blackboard = Blackboard::new();
+        blackboard.set_f64("a", 1.0);
+        blackboard.set_f64("b", 2.0);
+        blackboard.clear_changes();
+
+        blackboard.remove("a");
+        assert!(blackboard.changed("a"));
+        assert_eq!(blackboard.changed_keys(), vec!["a".to_string()]);
+        let loaded = Blackboard::load(&blackboard.save()).unwrap();
+        assert!(loaded.changed("a") && !loaded.changed("b"));
+
+        blackboard.clear_changes();
+        assert!(!blackboard.has_changes());
+        blackboard.clear();
+        assert!(blackboard.changed("b"));
+        blackboard.set_f64("b", 3.0);
+        assert_eq!(blackboard.changed_keys(), vec!["b".to_string()]);
+    }
+}
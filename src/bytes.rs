@@ -0,0 +1,172 @@
+use crate::error::Error;
+
+/// Builds a little-endian save: four magic bytes, a format version, then
+/// whatever the writer puts.
+pub(crate) struct Writer {
+    bytes: Vec<u8>,
+}
+
+impl Writer {
+    pub(crate) fn new(magic: &[u8; 4], version: u32) -> Writer {
+        let mut writer = Writer {
+            bytes: magic.to_vec(),
+        };
+        writer.u32(version);
+        writer
+    }
+
+    pub(crate) fn u8(&mut self, value: u8) {
+        self.bytes.push(value);
+    }
+
+    pub(crate) fn u32(&mut self, value: u32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub(crate) fn f32(&mut self, value: f32) {
+        self.u32(value.to_bits());
+    }
+
+    pub(crate) fn f64(&mut self, value: f64) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub(crate) fn u32s(&mut self, values: &[u32]) {
+        values.iter().for_each(|&value| self.u32(value));
+    }
+
+    pub(crate) fn f32s(&mut self, values: &[f32]) {
+        values.iter().for_each(|&value| self.f32(value));
+    }
+
+    pub(crate) fn string(&mut self, text: &str) {
+        self.u32(text.len() as u32);
+        self.bytes.extend_from_slice(text.as_bytes());
+    }
+
+    /// A length and then the bytes themselves.
+    pub(crate) fn bytes(&mut self, bytes: &[u8]) {
+        self.u32(bytes.len() as u32);
+        self.bytes.extend_from_slice(bytes);
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    pub(crate) fn finish(self) -> Vec<u8> {
+        self.bytes
+    }
+}
+
+/// Reads what a `Writer` wrote, failing with `what` named on truncated or
+/// foreign bytes.
+pub(crate) struct Reader<'a> {
+    bytes: &'a [u8],
+    what: &'static str,
+}
+
+impl<'a> Reader<'a> {
+    /// Checks the magic bytes and returns the reader with the format version.
+    pub(crate) fn new(
+        bytes: &'a [u8],
+        magic: &[u8; 4],
+        what: &'static str,
+    ) -> Result<(Reader<'a>, u32), Error> {
+        let mut reader = Reader { bytes, what };
+        if reader.take(4)? != magic {
+            return Err(reader.invalid());
+        }
+        let version = reader.u32()?;
+        Ok((reader, version))
+    }
+
+    pub(crate) fn invalid(&self) -> Error {
+        Error::InvalidInput(format!("not {}", self.what))
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        if len > self.bytes.len() {
+            return Err(self.invalid());
+        }
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    pub(crate) fn u8(&mut self) -> Result<u8, Error> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub(crate) fn u32(&mut self) -> Result<u32, Error> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    pub(crate) fn f32(&mut self) -> Result<f32, Error> {
+        self.u32().map(f32::from_bits)
+    }
+
+    /// A float that must be finite, as saved positions, speeds and settings
+    /// are, so a corrupted save fails here instead of in the next update.
+    pub(crate) fn finite_f32(&mut self) -> Result<f32, Error> {
+        let value = self.f32()?;
+        if value.is_finite() {
+            Ok(value)
+        } else {
+            Err(self.invalid())
+        }
+    }
+
+    pub(crate) fn f64(&mut self) -> Result<f64, Error> {
+        let bytes = self.take(8)?;
+        Ok(f64::from_le_bytes(bytes.try_into().unwrap()))
+    }
+
+    /// `len` words, checked against the bytes left before allocating.
+    pub(crate) fn u32s(&mut self, len: usize) -> Result<Vec<u32>, Error> {
+        let bytes = self.take(len.checked_mul(4).ok_or_else(|| self.invalid())?)?;
+        Ok(bytes
+            .chunks_exact(4)
+            .map(|word| u32::from_le_bytes([word[0], word[1], word[2], word[3]]))
+            .collect())
+    }
+
+    pub(crate) fn f32s(&mut self, len: usize) -> Result<Vec<f32>, Error> {
+        Ok(self.u32s(len)?.into_iter().map(f32::from_bits).collect())
+    }
+
+    pub(crate) fn finite_f32s(&mut self, len: usize) -> Result<Vec<f32>, Error> {
+        let values = self.f32s(len)?;
+        if values.iter().all(|value| value.is_finite()) {
+            Ok(values)
+        } else {
+            Err(self.invalid())
+        }
+    }
+
+    pub(crate) fn string(&mut self) -> Result<String, Error> {
+        let len = self.u32()? as usize;
+        let bytes = self.take(len)?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| self.invalid())
+    }
+
+    /// What `Writer::bytes` wrote.
+    pub(crate) fn bytes(&mut self) -> Result<&'a [u8], Error> {
+        let len = self.u32()? as usize;
+        self.take(len)
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    /// Fails unless every byte was read.
+    pub(crate) fn finish(self) -> Result<(), Error> {
+        if self.bytes.is_empty() {
+            Ok(())
+        } else {
+            Err(self.invalid())
+        }
+    }
+}
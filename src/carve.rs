@@ -0,0 +1,229 @@
+use std::ops::Range;
+
+use wasm_bindgen::prelude::*;
+
+use crate::error::Error;
+use crate::navmesh::NavMesh;
+
+type Point2 = [f32; 2];
+
+const NUDGE: f32 = 1e-3;
+
+#[derive(Clone)]
+pub(crate) enum Shape {
+    Polygon(Vec<Point2>),
+    Circle { center: Point2, radius: f32 },
+}
+
+/// A carved obstacle and the triangles it currently blocks.
+#[derive(Clone)]
+pub(crate) struct Obstacle {
+    pub shape: Shape,
+    pub triangles: Vec<usize>,
+}
+
+#[wasm_bindgen]
+impl NavMesh {
+    /// Blocks every triangle that overlaps the polygon given as flat `[x0, z0, x1, z1,
+    /// ...]` on the XZ plane, on all levels of the mesh. Paths avoid blocked triangles
+    /// until the obstacle is removed. Returns the obstacle id.
+    ///
+    /// Whole triangles are blocked, so coarse meshes may lose more area than the
+    /// obstacle itself covers.
+    pub fn add_obstacle(&mut self, points: &[f32]) -> Result<u32, Error> {
+        if points.len() < 6 || !points.len().is_multiple_of(2) {
+            return Err(Error::InvalidInput(
+                "obstacle needs at least 3 [x, z] points".into(),
+            ));
+        }
+        let polygon = points.chunks_exact(2).map(|p| [p[0], p[1]]).collect();
+        Ok(self.carve(Shape::Polygon(polygon)))
+    }
+
+    /// Like `add_obstacle`, for a disc on the XZ plane.
+    pub fn add_circle_obstacle(&mut self, x: f32, z: f32, radius: f32) -> Result<u32, Error> {
+        if radius > 0.0 {
+            Ok(self.carve(Shape::Circle {
+                center: [x, z],
+                radius,
+            }))
+        } else {
+            Err(Error::InvalidInput(format!(
+                "obstacle radius must be positive, got {}",
+                radius
+            )))
+        }
+    }
+
+    /// Unblocks the triangles covered by an obstacle, unless another obstacle still
+    /// overlaps them. Returns false for unknown ids.
+    pub fn remove_obstacle(&mut self, id: u32) -> bool {
+        let Some(obstacle) = self.obstacles.remove(&id) else {
+            return false;
+        };
+        for triangle in obstacle.triangles {
+            self.blockers[triangle] -= 1;
+        }
+        true
+    }
+
+    /// Whether the triangle under the point is blocked by an obstacle.
+    pub fn is_obstructed(&self, x: f32, y: f32, z: f32) -> bool {
+        self.locate([x, y, z])
+            .is_some_and(|triangle| self.is_blocked(triangle))
+    }
+}
+
+impl NavMesh {
+    fn carve(&mut self, shape: Shape) -> u32 {
+        let triangles = self.block(&shape, 0..self.triangles.len());
+        let id = self.next_obstacle;
+        self.next_obstacle = self.next_obstacle.wrapping_add(1);
+        self.obstacles.insert(id, Obstacle { shape, triangles });
+        id
+    }
+
+    /// Blocks the triangles in `range` that overlap `shape` and returns them.
+    fn block(&mut self, shape: &Shape, range: Range<usize>) -> Vec<usize> {
+        let (min, max) = shape.bounds();
+        let covered: Vec<usize> = range
+            .filter(|&index| {
+                let [a, b, c] = self.triangle(index).map(|p| [p[0], p[2]]);
+                let below = (0..2).any(|k| a[k].max(b[k]).max(c[k]) < min[k]);
+                let above = (0..2).any(|k| a[k].min(b[k]).min(c[k]) > max[k]);
+                !below && !above && shape.overlaps([a, b, c])
+            })
+            .collect();
+        for &triangle in &covered {
+            self.blockers[triangle] += 1;
+        }
+        covered
+    }
+
+    /// Applies every existing obstacle to newly added triangles.
+    pub(crate) fn recarve(&mut self, range: Range<usize>) {
+        let ids: Vec<u32> = self.obstacles.keys().copied().collect();
+        for id in ids {
+            let shape = self.obstacles[&id].shape.clone();
+            let covered = self.block(&shape, range.clone());
+            if let Some(obstacle) = self.obstacles.get_mut(&id) {
+                obstacle.triangles.extend(covered);
+            }
+        }
+    }
+}
+
+impl Shape {
+    fn bounds(&self) -> (Point2, Point2) {
+        match self {
+            Shape::Polygon(points) => points.iter().fold(
+                ([f32::INFINITY; 2], [f32::NEG_INFINITY; 2]),
+                |(min, max), p| {
+                    (
+                        [min[0].min(p[0]), min[1].min(p[1])],
+                        [max[0].max(p[0]), max[1].max(p[1])],
+                    )
+                },
+            ),
+            Shape::Circle { center, radius } => (
+                [center[0] - radius, center[1] - radius],
+                [center[0] + radius, center[1] + radius],
+            ),
+        }
+    }
+
+    /// Whether the shape's interior overlaps the triangle's. Shapes that only touch a
+    /// triangle along its boundary leave it open.
+    fn overlaps(&self, triangle: [Point2; 3]) -> bool {
+        let edges = |points: &[Point2]| {
+            (0..points.len())
+                .map(|i| (points[i], points[(i + 1) % points.len()]))
+                .collect::<Vec<_>>()
+        };
+        match self {
+            Shape::Polygon(polygon) => {
+                // Corners nudged toward the centroid, so that corners lying exactly on
+                // the polygon's boundary do not count as overlap.
+                let centroid = [
+                    (triangle[0][0] + triangle[1][0] + triangle[2][0]) / 3.0,
+                    (triangle[0][1] + triangle[1][1] + triangle[2][1]) / 3.0,
+                ];
+                let nudged = triangle.map(|p| {
+                    [
+                        p[0] + (centroid[0] - p[0]) * NUDGE,
+                        p[1] + (centroid[1] - p[1]) * NUDGE,
+                    ]
+                });
+                if inside_polygon(polygon, centroid)
+                    || nudged.iter().any(|&p| inside_polygon(polygon, p))
+                    || polygon.iter().any(|&p| inside_triangle(triangle, p))
+                {
+                    return true;
+                }
+                let triangle_edges = edges(&triangle);
+                edges(polygon).iter().any(|&(a, b)| {
+                    triangle_edges
+                        .iter()
+                        .any(|&(c, d)| segments_cross(a, b, c, d))
+                })
+            }
+            Shape::Circle { center, radius } => {
+                inside_triangle(triangle, *center)
+                    || edges(&triangle)
+                        .iter()
+                        .any(|&(a, b)| segment_distance(a, b, *center) < *radius)
+            }
+        }
+    }
+}
+
+fn orient(a: Point2, b: Point2, c: Point2) -> f32 {
+    (b[0] - a[0]) * (c[1] - a[1]) - (b[1] - a[1]) * (c[0] - a[0])
+}
+
+/// Strictly inside, for either winding.
+fn inside_triangle(t: [Point2; 3], p: Point2) -> bool {
+    let (d0, d1, d2) = (
+        orient(t[0], t[1], p),
+        orient(t[1], t[2], p),
+        orient(t[2], t[0], p),
+    );
+    (d0 > 0.0 && d1 > 0.0 && d2 > 0.0) || (d0 < 0.0 && d1 < 0.0 && d2 < 0.0)
+}
+
+/// Even-odd point-in-polygon test.
+fn inside_polygon(polygon: &[Point2], p: Point2) -> bool {
+    let mut inside = false;
+    let mut j = polygon.len() - 1;
+    for i in 0..polygon.len() {
+        let (a, b) = (polygon[i], polygon[j]);
+        if (a[1] > p[1]) != (b[1] > p[1])
+            && p[0] < (b[0] - a[0]) * (p[1] - a[1]) / (b[1] - a[1]) + a[0]
+        {
+            inside = !inside;
+        }
+        j = i;
+    }
+    inside
+}
+
+/// Proper crossing of segments `ab` and `cd`, excluding shared endpoints and
+/// collinear overlap.
+fn segments_cross(a: Point2, b: Point2, c: Point2, d: Point2) -> bool {
+    let (d1, d2) = (orient(a, b, c), orient(a, b, d));
+    let (d3, d4) = (orient(c, d, a), orient(c, d, b));
+    d1 * d2 < 0.0 && d3 * d4 < 0.0
+}
+
+fn segment_distance(a: Point2, b: Point2, p: Point2) -> f32 {
+    let ab = [b[0] - a[0], b[1] - a[1]];
+    let ap = [p[0] - a[0], p[1] - a[1]];
+    let length_squared = ab[0] * ab[0] + ab[1] * ab[1];
+    let t = if length_squared > 0.0 {
+        ((ap[0] * ab[0] + ap[1] * ab[1]) / length_squared).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    let (dx, dz) = (ap[0] - ab[0] * t, ap[1] - ab[1] * t);
+    (dx * dx + dz * dz).sqrt()
+}
@@ -0,0 +1,105 @@
+use wasm_bindgen::prelude::*;
+
+use crate::grid::{Grid, Traversal};
+
+const SQRT_2: f32 = std::f32::consts::SQRT_2;
+
+/// Backward-looking half of the 8-neighborhood used by the forward chamfer pass;
+/// the backward pass mirrors it.
+const CHAMFER: [(i32, i32, f32); 4] = [
+    (-1, 0, 1.0),
+    (-1, -1, SQRT_2),
+    (0, -1, 1.0),
+    (1, -1, SQRT_2),
+];
+
+#[wasm_bindgen]
+impl Grid {
+    /// Radius of the largest agent that can stand centered on a cell: the distance
+    /// to the nearest blocked cell or grid edge, minus half a cell. A corridor one
+    /// cell wide has clearance 0.5; blocked cells have 0.
+    pub fn clearance(&self, x: i32, y: i32) -> f32 {
+        if self.in_bounds(x, y) {
+            self.clearance_cache()[self.index(x, y)]
+        } else {
+            0.0
+        }
+    }
+
+    /// Clearance of every cell, row-major.
+    pub fn clearance_map(&self) -> Vec<f32> {
+        self.clearance_cache().to_vec()
+    }
+
+    /// Like `find_path`, but only through cells whose clearance is at least
+    /// `radius`, so large agents are not routed through narrow gaps.
+    ///
+    /// @deprecated Use `find_path_with`, which takes a radius and a filter.
+    pub fn find_path_with_radius(
+        &self,
+        start_x: u32,
+        start_y: u32,
+        end_x: u32,
+        end_y: u32,
+        radius: f32,
+    ) -> Vec<f32> {
+        self.find_weighted_path(
+            start_x,
+            start_y,
+            end_x,
+            end_y,
+            Traversal {
+                radius,
+                ..Traversal::default()
+            },
+        )
+    }
+}
+
+impl Grid {
+    /// Brushfire from every blocked cell and the border using a two-pass chamfer
+    /// transform, which yields exact octile distances between cell centers.
+    pub(crate) fn compute_clearance(&self) -> Vec<f32> {
+        let (width, height) = (self.width() as i32, self.height() as i32);
+        let mut distance: Vec<f32> = (0..self.cell_count())
+            .map(|index| {
+                let (x, y) = self.coords(index);
+                if self.is_walkable(x, y) {
+                    f32::INFINITY
+                } else {
+                    0.0
+                }
+            })
+            .collect();
+
+        let mut relax = |x: i32, y: i32, sign: i32| {
+            let index = self.index(x, y);
+            let mut best = distance[index];
+            for &(dx, dy, step) in &CHAMFER {
+                let (nx, ny) = (x + dx * sign, y + dy * sign);
+                let neighbor = if self.in_bounds(nx, ny) {
+                    distance[self.index(nx, ny)]
+                } else {
+                    0.0
+                };
+                best = best.min(neighbor + step);
+            }
+            distance[index] = best;
+        };
+        for y in 0..height {
+            for x in 0..width {
+                relax(x, y, 1);
+            }
+        }
+        for y in (0..height).rev() {
+            for x in (0..width).rev() {
+                relax(x, y, -1);
+            }
+        }
+
+        distance
+            .into_iter()
+            .map(|distance| (distance - 0.5).max(0.0))
+            .collect()
+    }
+}
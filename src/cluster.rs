@@ -0,0 +1,314 @@
+use wasm_bindgen::prelude::*;
+
+use crate::error::Error;
+use crate::random::Rng;
+
+/// Clusters found by `kmeans` or `dbscan` over a flat array of points.
+#[wasm_bindgen]
+pub struct Clustering {
+    dimensions: usize,
+    labels: Vec<i32>,
+    centroids: Vec<f32>,
+    sizes: Vec<u32>,
+    inertia: f32,
+    iterations: u32,
+}
+
+#[wasm_bindgen]
+impl Clustering {
+    #[wasm_bindgen(getter)]
+    pub fn cluster_count(&self) -> u32 {
+        self.sizes.len() as u32
+    }
+
+    /// The cluster of each point, in input order; -1 marks noise from `dbscan`.
+    pub fn labels(&self) -> Vec<i32> {
+        self.labels.clone()
+    }
+
+    /// The mean of each cluster's points, laid out like the input points.
+    pub fn centroids(&self) -> Vec<f32> {
+        self.centroids.clone()
+    }
+
+    /// The number of points in each cluster.
+    pub fn sizes(&self) -> Vec<u32> {
+        self.sizes.clone()
+    }
+
+    /// Sum of squared distances from the clustered points to their centroids;
+    /// lower means tighter clusters.
+    #[wasm_bindgen(getter)]
+    pub fn inertia(&self) -> f32 {
+        self.inertia
+    }
+
+    /// Assignment rounds `kmeans` ran before settling, or 1 for `dbscan`.
+    #[wasm_bindgen(getter)]
+    pub fn iterations(&self) -> u32 {
+        self.iterations
+    }
+
+    /// The indices of the points in `cluster`.
+    pub fn members(&self, cluster: u32) -> Vec<u32> {
+        (0..self.labels.len() as u32)
+            .filter(|&i| self.labels[i as usize] == cluster as i32)
+            .collect()
+    }
+
+    /// The cluster whose centroid is nearest `point`, or `None` with no clusters.
+    pub fn nearest(&self, point: &[f32]) -> Option<u32> {
+        if point.len() != self.dimensions {
+            return None;
+        }
+        self.centroids
+            .chunks_exact(self.dimensions)
+            .map(|centroid| squared_distance(point, centroid))
+            .enumerate()
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(cluster, _)| cluster as u32)
+    }
+}
+
+/// Splits the points of a flat array with `dimensions` values each into `k`
+/// clusters by Lloyd's algorithm from k-means++ starting centroids, stopping
+/// once no point changes cluster or after `max_iterations` rounds. The `seed`
+/// picks the starting centroids, so a seed always gives the same clusters.
+#[wasm_bindgen]
+pub fn kmeans(
+    points: &[f32],
+    dimensions: u32,
+    k: u32,
+    max_iterations: u32,
+    seed: u64,
+) -> Result<Clustering, Error> {
+    let dimensions = check_points(points, dimensions)?;
+    let count = points.len() / dimensions;
+    if k == 0 || k as usize > count {
+        return Err(Error::InvalidInput(format!(
+            "k must be between 1 and the {} points",
+            count
+        )));
+    }
+    let point = |i: usize| &points[i * dimensions..][..dimensions];
+    let k = k as usize;
+
+    // k-means++: each next centroid is a point drawn with probability
+    // proportional to its squared distance from the nearest centroid so far.
+    let mut rng = Rng::new(seed);
+    let mut centroids = point(rng.below(count)).to_vec();
+    let mut nearest: Vec<f32> = (0..count)
+        .map(|i| squared_distance(point(i), &centroids))
+        .collect();
+    while centroids.len() < k * dimensions {
+        let total: f32 = nearest.iter().sum();
+        let mut pick = rng.next_f32() * total;
+        let mut chosen = rng.below(count);
+        if total > 0.0 {
+            for (i, &distance) in nearest.iter().enumerate() {
+                pick -= distance;
+                if pick < 0.0 {
+                    chosen = i;
+                    break;
+                }
+            }
+        }
+        let start = centroids.len();
+        centroids.extend_from_slice(point(chosen));
+        for (i, distance) in nearest.iter_mut().enumerate() {
+            *distance = distance.min(squared_distance(point(i), &centroids[start..]));
+        }
+    }
+
+    let mut labels = vec![-1; count];
+    let mut iterations = 0;
+    while iterations < max_iterations.max(1) {
+        iterations += 1;
+        let mut changed = false;
+        for (i, label) in labels.iter_mut().enumerate() {
+            let closest = closest(point(i), &centroids, dimensions) as i32;
+            if *label != closest {
+                *label = closest;
+                changed = true;
+            }
+        }
+        if !changed {
+            break;
+        }
+        // A cluster left empty keeps its old centroid.
+        let (means, sizes) = means(points, dimensions, &labels, k);
+        for (cluster, &size) in sizes.iter().enumerate() {
+            if size > 0 {
+                let range = cluster * dimensions..(cluster + 1) * dimensions;
+                centroids[range.clone()].copy_from_slice(&means[range]);
+            }
+        }
+    }
+    Ok(finish(points, dimensions, labels, k, iterations))
+}
+
+/// Groups points of a flat array with `dimensions` values each by density:
+/// points with at least `min_points` others within `epsilon` are core points,
+/// and a cluster is every point within `epsilon` of a chain of core points.
+/// Points in no cluster are noise. Unlike `kmeans`, it finds the number of
+/// clusters itself and allows any shape.
+#[wasm_bindgen]
+pub fn dbscan(
+    points: &[f32],
+    dimensions: u32,
+    epsilon: f32,
+    min_points: u32,
+) -> Result<Clustering, Error> {
+    let dimensions = check_points(points, dimensions)?;
+    if epsilon <= 0.0 || !epsilon.is_finite() {
+        return Err(Error::InvalidInput("epsilon must be positive".into()));
+    }
+    let count = points.len() / dimensions;
+    let point = |i: usize| &points[i * dimensions..][..dimensions];
+
+    // Sorting by the first coordinate bounds each neighbor query to the points
+    // within `epsilon` of it along that axis.
+    let mut order: Vec<usize> = (0..count).collect();
+    order.sort_by(|&a, &b| point(a)[0].total_cmp(&point(b)[0]));
+    let mut rank = vec![0; count];
+    for (position, &i) in order.iter().enumerate() {
+        rank[i] = position;
+    }
+    let limit = epsilon * epsilon;
+    let neighbors = |i: usize, found: &mut Vec<usize>| {
+        found.clear();
+        let x = point(i)[0];
+        let before = order[..rank[i]]
+            .iter()
+            .rev()
+            .take_while(|&&j| x - point(j)[0] <= epsilon);
+        let after = order[rank[i] + 1..]
+            .iter()
+            .take_while(|&&j| point(j)[0] - x <= epsilon);
+        found.extend(
+            before
+                .chain(after)
+                .copied()
+                .filter(|&j| squared_distance(point(i), point(j)) <= limit),
+        );
+    };
+
+    const UNVISITED: i32 = -2;
+    const NOISE: i32 = -1;
+    let mut labels = vec![UNVISITED; count];
+    let mut clusters = 0;
+    let mut found = Vec::new();
+    let mut frontier = Vec::new();
+    for i in 0..count {
+        if labels[i] != UNVISITED {
+            continue;
+        }
+        neighbors(i, &mut found);
+        if found.len() < min_points as usize {
+            labels[i] = NOISE;
+            continue;
+        }
+        let cluster = clusters;
+        clusters += 1;
+        labels[i] = cluster;
+        frontier.clone_from(&found);
+        while let Some(j) = frontier.pop() {
+            if labels[j] == NOISE {
+                labels[j] = cluster;
+            }
+            if labels[j] != UNVISITED {
+                continue;
+            }
+            labels[j] = cluster;
+            neighbors(j, &mut found);
+            if found.len() >= min_points as usize {
+                frontier.extend_from_slice(&found);
+            }
+        }
+    }
+    Ok(finish(points, dimensions, labels, clusters as usize, 1))
+}
+
+fn check_points(points: &[f32], dimensions: u32) -> Result<usize, Error> {
+    if dimensions == 0 {
+        return Err(Error::InvalidInput("dimensions must be positive".into()));
+    }
+    if points.len().is_multiple_of(dimensions as usize) {
+        Ok(dimensions as usize)
+    } else {
+        Err(Error::InvalidInput(format!(
+            "points length must be a multiple of {}",
+            dimensions
+        )))
+    }
+}
+
+fn finish(
+    points: &[f32],
+    dimensions: usize,
+    labels: Vec<i32>,
+    clusters: usize,
+    iterations: u32,
+) -> Clustering {
+    let (centroids, sizes) = means(points, dimensions, &labels, clusters);
+    let inertia = points
+        .chunks_exact(dimensions)
+        .zip(&labels)
+        .filter(|(_, &label)| label >= 0)
+        .map(|(point, &label)| {
+            squared_distance(
+                point,
+                &centroids[label as usize * dimensions..][..dimensions],
+            )
+        })
+        .sum();
+    Clustering {
+        dimensions,
+        labels,
+        centroids,
+        sizes,
+        inertia,
+        iterations,
+    }
+}
+
+/// The centroid and size of each cluster, ignoring negative labels.
+fn means(
+    points: &[f32],
+    dimensions: usize,
+    labels: &[i32],
+    clusters: usize,
+) -> (Vec<f32>, Vec<u32>) {
+    let mut sums = vec![0.0; clusters * dimensions];
+    let mut sizes = vec![0; clusters];
+    for (point, &label) in points.chunks_exact(dimensions).zip(labels) {
+        if label >= 0 {
+            let sum = &mut sums[label as usize * dimensions..][..dimensions];
+            sum.iter_mut().zip(point).for_each(|(total, x)| *total += x);
+            sizes[label as usize] += 1;
+        }
+    }
+    for (sum, &size) in sums.chunks_exact_mut(dimensions).zip(&sizes) {
+        if size > 0 {
+            sum.iter_mut().for_each(|total| *total /= size as f32);
+        }
+    }
+    (sums, sizes)
+}
+
+fn closest(point: &[f32], centroids: &[f32], dimensions: usize) -> usize {
+    let mut best = 0;
+    let mut best_distance = f32::INFINITY;
+    for (cluster, centroid) in centroids.chunks_exact(dimensions).enumerate() {
+        let distance = squared_distance(point, centroid);
+        if distance < best_distance {
+            best = cluster;
+            best_distance = distance;
+        }
+    }
+    best
+}
+
+fn squared_distance(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(x, y)| (x - y) * (x - y)).sum()
+}
@@ -0,0 +1,165 @@
+use std::f32::consts::TAU;
+
+use wasm_bindgen::prelude::*;
+
+use crate::error::Error;
+use crate::math::Vec2;
+use crate::steering::Agent;
+
+/// Context steering: behaviors write how much they want to move (interest) and
+/// how risky it is to move (danger) into a ring of evenly spaced direction slots,
+/// and `resolve` picks the most interesting direction among the safest ones.
+/// Unlike summing forces, a strong danger can never be averaged away by a strong
+/// interest pointing the same way.
+///
+/// Clear the map each frame, write every behavior's contribution, then resolve.
+#[wasm_bindgen]
+#[derive(Clone)]
+pub struct ContextMap {
+    directions: Vec<Vec2>,
+    interest: Vec<f32>,
+    danger: Vec<f32>,
+    /// Slots whose danger exceeds the safest slot's by more than this are masked
+    /// out before choosing. 0 keeps only the safest slots.
+    pub danger_tolerance: f32,
+    /// Bonus for slots near the previously resolved direction, which stops agents
+    /// flip-flopping between two equally good ways around a threat.
+    pub persistence: f32,
+    previous: Vec2,
+}
+
+#[wasm_bindgen]
+impl ContextMap {
+    /// Creates a map with `slots` directions; 8 to 16 is typical.
+    #[wasm_bindgen(constructor)]
+    pub fn new(slots: u32) -> Result<ContextMap, Error> {
+        if slots < 4 {
+            return Err(Error::InvalidInput(format!(
+                "context map needs at least 4 slots, got {}",
+                slots
+            )));
+        }
+        let directions = (0..slots)
+            .map(|i| Vec2::from_angle(i as f32 * TAU / slots as f32))
+            .collect();
+        Ok(ContextMap {
+            directions,
+            interest: vec![0.0; slots as usize],
+            danger: vec![0.0; slots as usize],
+            danger_tolerance: 0.05,
+            persistence: 0.1,
+            previous: Vec2::ZERO,
+        })
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn slots(&self) -> u32 {
+        self.directions.len() as u32
+    }
+
+    pub fn clear(&mut self) {
+        self.interest.fill(0.0);
+        self.danger.fill(0.0);
+    }
+
+    /// Adds interest in `direction`, strongest in the slots closest to it, half as
+    /// strong at right angles and zero directly away, so a blocked agent still
+    /// prefers sidestepping to stopping. Overlapping contributions keep the
+    /// maximum.
+    pub fn add_interest(&mut self, direction: Vec2, weight: f32) {
+        write(&self.directions, &mut self.interest, direction, weight, 0.5);
+    }
+
+    /// Adds danger in `direction`, strongest in the slots closest to it and fading
+    /// to nothing at right angles.
+    pub fn add_danger(&mut self, direction: Vec2, weight: f32) {
+        write(&self.directions, &mut self.danger, direction, weight, 0.0);
+    }
+
+    /// Adds interest toward `target` as seen from `position`.
+    pub fn add_interest_toward(&mut self, position: Vec2, target: Vec2, weight: f32) {
+        self.add_interest(target - position, weight);
+    }
+
+    /// Adds danger toward `threat` as seen from `position`, scaled from `weight`
+    /// at the threat down to 0 at `radius` away from it.
+    pub fn add_danger_from(&mut self, position: Vec2, threat: Vec2, radius: f32, weight: f32) {
+        let distance = position.distance(threat);
+        if radius > 0.0 && distance < radius {
+            self.add_danger(threat - position, weight * (1.0 - distance / radius));
+        }
+    }
+
+    /// Interest of each slot, counter-clockwise from +x.
+    pub fn interest(&self) -> Vec<f32> {
+        self.interest.clone()
+    }
+
+    /// Danger of each slot, counter-clockwise from +x.
+    pub fn danger(&self) -> Vec<f32> {
+        self.danger.clone()
+    }
+
+    /// The chosen direction, with a length equal to its interest. The direction is
+    /// interpolated between the best slot and its neighbors so it does not snap
+    /// from slot to slot. Zero when no safe slot has any interest.
+    pub fn resolve(&mut self) -> Vec2 {
+        let safest = self.danger.iter().copied().fold(f32::INFINITY, f32::min);
+        let limit = safest + self.danger_tolerance.max(0.0);
+        let open = |i: usize| self.danger[i] <= limit;
+
+        let score = |i: usize| {
+            let previous = self.directions[i].dot(self.previous).max(0.0);
+            self.interest[i] + self.persistence * previous
+        };
+        let Some(best) = (0..self.interest.len())
+            .filter(|&i| open(i) && self.interest[i] > 0.0)
+            .max_by(|&a, &b| score(a).total_cmp(&score(b)))
+        else {
+            self.previous = Vec2::ZERO;
+            return Vec2::ZERO;
+        };
+
+        // Fit a parabola through the best slot and its open neighbors.
+        let n = self.interest.len();
+        let value = |i: usize| if open(i) { self.interest[i] } else { 0.0 };
+        let (left, center, right) = (
+            value((best + n - 1) % n),
+            value(best),
+            value((best + 1) % n),
+        );
+        let curvature = left - 2.0 * center + right;
+        let offset = if curvature < 0.0 {
+            (0.5 * (left - right) / curvature).clamp(-0.5, 0.5)
+        } else {
+            0.0
+        };
+        let angle = (best as f32 + offset) * TAU / n as f32;
+        self.previous = Vec2::from_angle(angle);
+        self.previous * center
+    }
+}
+
+/// Writes a contribution into `slots`: `weight` times the cosine to `direction`,
+/// remapped so that `floor` is reached at right angles, keeping the maximum.
+fn write(directions: &[Vec2], slots: &mut [f32], direction: Vec2, weight: f32, floor: f32) {
+    let direction = direction.normalize();
+    if direction == Vec2::ZERO || weight <= 0.0 {
+        return;
+    }
+    for (slot, &slot_direction) in slots.iter_mut().zip(directions) {
+        let shaped = (floor + (1.0 - floor) * slot_direction.dot(direction)).max(0.0);
+        *slot = slot.max(weight * shaped);
+    }
+}
+
+#[wasm_bindgen]
+impl Agent {
+    /// Steers toward the direction resolved from a context map, at a speed scaled
+    /// by its interest (capped at 1). Brakes when the map has nowhere to go.
+    pub fn steer_context(&self, map: &mut ContextMap) -> Vec2 {
+        let choice = map.resolve();
+        let desired = choice.normalize() * (self.max_speed * choice.length().min(1.0));
+        (desired - self.velocity).truncate(self.max_force)
+    }
+}
@@ -0,0 +1,393 @@
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+
+use wasm_bindgen::prelude::*;
+
+use crate::error::Error;
+use crate::grid::Grid;
+use crate::math::Vec2;
+use crate::search;
+
+const DIRECTIONS: [(i32, i32); 4] = [(1, 0), (-1, 0), (0, 1), (0, -1)];
+
+struct Agent {
+    cell: usize,
+    goal: usize,
+    /// Cells for the coming steps, soonest last.
+    plan: Vec<usize>,
+    /// Goal the heuristic was computed for, and the distance of every cell to it.
+    heuristic: Option<(usize, Vec<f32>)>,
+}
+
+/// Space-time search entry, popped lowest `f` first and deepest on ties.
+#[derive(PartialEq)]
+struct Open {
+    f: f32,
+    g: f32,
+    cell: usize,
+    time: u32,
+}
+
+impl Eq for Open {}
+
+impl Ord for Open {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .f
+            .total_cmp(&self.f)
+            .then_with(|| self.time.cmp(&other.time))
+    }
+}
+
+impl PartialOrd for Open {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Moves a group of agents cell by cell on a grid without two ever sharing a
+/// cell or swapping places, with Windowed Hierarchical Cooperative A*. Each
+/// agent in turn searches time as well as space for its next `window` steps,
+/// steering clear of the cells and moves the agents before it reserved, with
+/// the true distance to its goal as the heuristic beyond the window. Agents
+/// take four-way steps or wait, and keep planning after they arrive, so they
+/// step aside for others in corridors. The group replans every half window,
+/// rotating who goes first so nobody is stuck behind the rest for good. Like
+/// any windowed search it cannot see a way out that takes longer than the
+/// window, such as two long files meeting in a one-lane corridor with no
+/// place to pass.
+///
+/// Unlike `Crowd` avoidance this suits puzzle and automation games where
+/// units must never overlap and narrow corridors must not deadlock.
+#[wasm_bindgen]
+pub struct CooperativePlanner {
+    grid: Grid,
+    window: u32,
+    agents: Vec<Agent>,
+    since_plan: u32,
+    rounds: u32,
+}
+
+#[wasm_bindgen]
+impl CooperativePlanner {
+    /// A planner over a copy of `grid` looking `window` steps ahead, e.g. 8 to
+    /// 16; longer windows resolve longer corridors at a higher cost.
+    #[wasm_bindgen(constructor)]
+    pub fn new(grid: &Grid, window: u32) -> Result<CooperativePlanner, Error> {
+        if window == 0 {
+            return Err(Error::InvalidInput("window must be at least 1".into()));
+        }
+        Ok(CooperativePlanner {
+            grid: grid.clone(),
+            window,
+            agents: Vec::new(),
+            since_plan: 0,
+            rounds: 0,
+        })
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn window(&self) -> u32 {
+        self.window
+    }
+
+    /// Replaces the grid, e.g. after a door opened, and replans every agent.
+    pub fn set_grid(&mut self, grid: &Grid) {
+        self.grid = grid.clone();
+        for agent in &mut self.agents {
+            agent.heuristic = None;
+            agent.plan.clear();
+        }
+    }
+
+    /// Adds an agent standing on a free walkable cell, with that cell as its
+    /// goal, and returns its id.
+    pub fn add_agent(&mut self, x: u32, y: u32) -> Result<u32, Error> {
+        let cell = self.free_cell(x, y)?;
+        if self.agents.iter().any(|agent| agent.cell == cell) {
+            return Err(Error::InvalidInput(format!("cell ({}, {}) is taken", x, y)));
+        }
+        self.agents.push(Agent {
+            cell,
+            goal: cell,
+            plan: Vec::new(),
+            heuristic: None,
+        });
+        self.replan_soon();
+        Ok(self.agents.len() as u32 - 1)
+    }
+
+    /// Sends an agent to a walkable cell.
+    pub fn set_goal(&mut self, id: u32, x: u32, y: u32) -> Result<(), Error> {
+        let goal = self.free_cell(x, y)?;
+        let agent = self
+            .agents
+            .get_mut(id as usize)
+            .ok_or_else(|| Error::InvalidInput(format!("unknown agent {}", id)))?;
+        agent.goal = goal;
+        self.replan_soon();
+        Ok(())
+    }
+
+    /// Moves every agent one step along its plan, replanning first when due,
+    /// and returns how many moved.
+    pub fn step(&mut self) -> u32 {
+        if self.since_plan >= (self.window / 2).max(1)
+            || self.agents.iter().any(|agent| agent.plan.is_empty())
+        {
+            self.replan();
+        }
+        self.since_plan += 1;
+
+        // Plans never collide, but an agent that found none waits where it
+        // is, so give way to it and to whoever then has to wait in turn.
+        let mut next: Vec<usize> = self
+            .agents
+            .iter_mut()
+            .map(|agent| agent.plan.pop().unwrap_or(agent.cell))
+            .collect();
+        let order = self.order();
+        let occupant: HashMap<usize, usize> = self
+            .agents
+            .iter()
+            .enumerate()
+            .map(|(id, agent)| (agent.cell, id))
+            .collect();
+        loop {
+            let mut claimed: HashMap<usize, usize> = HashMap::new();
+            let mut blocked = None;
+            for &id in &order {
+                let cell = self.agents[id].cell;
+                if let Some(&other) = claimed.get(&next[id]) {
+                    // The earlier claimant keeps the cell unless this one is
+                    // staying put in it.
+                    blocked = Some(if next[id] == cell { other } else { id });
+                    break;
+                }
+                let swapped = occupant
+                    .get(&next[id])
+                    .is_some_and(|&other| other != id && next[other] == cell);
+                if swapped {
+                    blocked = Some(id);
+                    break;
+                }
+                claimed.insert(next[id], id);
+            }
+            match blocked {
+                Some(id) if next[id] != self.agents[id].cell => {
+                    next[id] = self.agents[id].cell;
+                    self.agents[id].plan.clear();
+                }
+                _ => break,
+            }
+        }
+
+        let mut moved = 0;
+        for (agent, cell) in self.agents.iter_mut().zip(next) {
+            if agent.cell != cell {
+                agent.cell = cell;
+                moved += 1;
+            }
+        }
+        moved
+    }
+
+    /// Flat `[x, y, ...]` cells of every agent, by id.
+    pub fn positions(&self) -> Vec<f32> {
+        let mut positions = Vec::with_capacity(self.agents.len() * 2);
+        for agent in &self.agents {
+            let (x, y) = self.grid.coords(agent.cell);
+            positions.extend_from_slice(&[x as f32, y as f32]);
+        }
+        positions
+    }
+
+    pub fn position(&self, id: u32) -> Option<Vec2> {
+        self.agents.get(id as usize).map(|agent| {
+            let (x, y) = self.grid.coords(agent.cell);
+            Vec2::new(x as f32, y as f32)
+        })
+    }
+
+    /// The cells an agent has reserved for its coming steps, as flat `[x, y,
+    /// ...]` in order.
+    pub fn plan(&self, id: u32) -> Vec<f32> {
+        self.agents.get(id as usize).map_or_else(Vec::new, |agent| {
+            let cells: Vec<usize> = agent.plan.iter().rev().copied().collect();
+            self.grid.to_waypoints(&cells)
+        })
+    }
+
+    pub fn at_goal(&self, id: u32) -> bool {
+        self.agents
+            .get(id as usize)
+            .is_some_and(|agent| agent.cell == agent.goal)
+    }
+
+    /// Whether every agent stands on its goal.
+    pub fn all_at_goal(&self) -> bool {
+        self.agents.iter().all(|agent| agent.cell == agent.goal)
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn len(&self) -> u32 {
+        self.agents.len() as u32
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.agents.is_empty()
+    }
+}
+
+impl CooperativePlanner {
+    fn free_cell(&self, x: u32, y: u32) -> Result<usize, Error> {
+        if !self.grid.is_walkable(x as i32, y as i32) {
+            return Err(Error::InvalidInput(format!(
+                "cell ({}, {}) is not walkable",
+                x, y
+            )));
+        }
+        Ok(self.grid.index(x as i32, y as i32))
+    }
+
+    fn replan_soon(&mut self) {
+        self.since_plan = u32::MAX;
+    }
+
+    /// Planning order: agents still travelling go before those that arrived,
+    /// each group rotated by the number of rounds planned.
+    fn order(&self) -> Vec<usize> {
+        let count = self.agents.len();
+        let rotated = (0..count).map(|i| (i + self.rounds as usize) % count.max(1));
+        let (mut order, arrived): (Vec<usize>, Vec<usize>) =
+            rotated.partition(|&id| self.agents[id].cell != self.agents[id].goal);
+        order.extend(arrived);
+        order
+    }
+
+    fn replan(&mut self) {
+        self.since_plan = 0;
+        self.rounds = self.rounds.wrapping_add(1);
+        let mut cells: HashSet<(usize, u32)> = HashSet::new();
+        let mut moves: HashSet<(usize, usize, u32)> = HashSet::new();
+        // Agents hold their cells for the first step until they plan to leave.
+        let mut unplanned: HashSet<usize> = self.agents.iter().map(|agent| agent.cell).collect();
+        for id in self.order() {
+            self.update_heuristic(id);
+            unplanned.remove(&self.agents[id].cell);
+            let plan = self.search(id, &cells, &moves, &unplanned);
+            let mut from = self.agents[id].cell;
+            for (time, &cell) in plan.iter().rev().enumerate() {
+                let time = time as u32 + 1;
+                cells.insert((cell, time));
+                moves.insert((from, cell, time));
+                from = cell;
+            }
+            self.agents[id].plan = plan;
+        }
+    }
+
+    fn update_heuristic(&mut self, id: usize) {
+        let goal = self.agents[id].goal;
+        if self.agents[id]
+            .heuristic
+            .as_ref()
+            .is_some_and(|(cached, _)| *cached == goal)
+        {
+            return;
+        }
+        let grid = &self.grid;
+        let distances = search::dijkstra(
+            (grid.width() * grid.height()) as usize,
+            &[goal],
+            |index, out| out.extend(neighbors(grid, index).map(|next| (next, 1.0))),
+        );
+        self.agents[id].heuristic = Some((goal, distances));
+    }
+
+    /// Space-time A* to the end of the window, returning the cells to visit
+    /// soonest last, or waiting in place throughout when no plan avoids the
+    /// reservations.
+    fn search(
+        &self,
+        id: usize,
+        cells: &HashSet<(usize, u32)>,
+        moves: &HashSet<(usize, usize, u32)>,
+        unplanned: &HashSet<usize>,
+    ) -> Vec<usize> {
+        let agent = &self.agents[id];
+        let distances = &agent.heuristic.as_ref().unwrap().1;
+        let reachable = distances[agent.cell].is_finite();
+        let heuristic = |cell: usize| {
+            if reachable {
+                distances[cell]
+            } else {
+                0.0
+            }
+        };
+        let free = |from: usize, cell: usize, time: u32| {
+            heuristic(cell).is_finite()
+                && !cells.contains(&(cell, time))
+                && !moves.contains(&(cell, from, time))
+                && (time > 1 || !unplanned.contains(&cell))
+        };
+
+        let mut best: HashMap<(usize, u32), f32> = HashMap::new();
+        let mut parent: HashMap<(usize, u32), usize> = HashMap::new();
+        let mut open = BinaryHeap::new();
+        best.insert((agent.cell, 0), 0.0);
+        open.push(Open {
+            f: heuristic(agent.cell),
+            g: 0.0,
+            cell: agent.cell,
+            time: 0,
+        });
+        while let Some(Open { g, cell, time, .. }) = open.pop() {
+            if best.get(&(cell, time)).is_some_and(|&known| g > known) {
+                continue;
+            }
+            if time == self.window {
+                let mut plan = vec![cell];
+                let mut at = (cell, time);
+                while at.1 > 1 {
+                    let previous = parent[&at];
+                    plan.push(previous);
+                    at = (previous, at.1 - 1);
+                }
+                return plan;
+            }
+            let wait_cost = if cell == agent.goal { 0.0 } else { 1.0 };
+            for next in neighbors(&self.grid, cell).chain([cell]) {
+                let next_time = time + 1;
+                if !free(cell, next, next_time) {
+                    continue;
+                }
+                let step = if next == cell { wait_cost } else { 1.0 };
+                let tentative = g + step;
+                if best
+                    .get(&(next, next_time))
+                    .is_none_or(|&known| tentative < known)
+                {
+                    best.insert((next, next_time), tentative);
+                    parent.insert((next, next_time), cell);
+                    open.push(Open {
+                        f: tentative + heuristic(next),
+                        g: tentative,
+                        cell: next,
+                        time: next_time,
+                    });
+                }
+            }
+        }
+        vec![agent.cell; self.window as usize]
+    }
+}
+
+fn neighbors(grid: &Grid, index: usize) -> impl Iterator<Item = usize> + '_ {
+    let (x, y) = grid.coords(index);
+    DIRECTIONS
+        .iter()
+        .map(move |&(dx, dy)| (x + dx, y + dy))
+        .filter(|&(x, y)| grid.is_walkable(x, y))
+        .map(|(x, y)| grid.index(x, y))
+}
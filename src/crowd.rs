@@ -0,0 +1,789 @@
+use std::collections::VecDeque;
+
+#[cfg(target_arch = "wasm32")]
+use js_sys::{Float32Array, Uint32Array};
+use wasm_bindgen::prelude::*;
+
+use crate::bytes::{Reader, Writer};
+use crate::debug::{self, DebugGeometry};
+use crate::error::Error;
+use crate::events::{AiEvent, EventKind};
+use crate::grid::Grid;
+use crate::locomotion::{self, Motion};
+use crate::math::Vec2;
+use crate::nav_filter::NavFilter;
+use crate::navmesh::NavMesh;
+use crate::obstacles::ObstacleSet;
+use crate::orca::CrowdSimulator;
+use crate::parallel;
+use crate::path_following::PathFollower;
+use crate::patrol::{PatrolRoute, Patroller};
+use crate::stats::{AiStats, Sample};
+use crate::stuck::{Recovery, StuckMonitor};
+
+/// Leading bytes of `Crowd::save_state`, then a format version.
+const MAGIC: &[u8; 4] = b"LAIC";
+const VERSION: u32 = 1;
+
+/// Where a crowd agent is in carrying out its move request.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MoveState {
+    /// No move target.
+    Idle = 0,
+    /// Waiting for its path to be computed.
+    Pending = 1,
+    Moving = 2,
+    Arrived = 3,
+    /// No path leads to the target.
+    Failed = 4,
+}
+
+/// Per-agent settings for `Crowd::add_agent`.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug)]
+pub struct CrowdAgentParams {
+    pub radius: f32,
+    pub max_speed: f32,
+    /// How far along the path the agent aims, which rounds off corners. ORCA only
+    /// avoids other agents, so large values let agents clip the corners of walls.
+    pub path_lookahead: f32,
+    /// Distance from the target at which the agent counts as arrived.
+    pub arrive_radius: f32,
+    /// Distance from the target within which the agent starts slowing down.
+    pub slow_radius: f32,
+}
+
+#[wasm_bindgen]
+impl CrowdAgentParams {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> CrowdAgentParams {
+        CrowdAgentParams {
+            radius: 0.5,
+            max_speed: 3.5,
+            path_lookahead: 1.5,
+            arrive_radius: 0.3,
+            slow_radius: 1.5,
+        }
+    }
+}
+
+impl Default for CrowdAgentParams {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+enum Navigation {
+    Grid(Grid),
+    Mesh(NavMesh),
+}
+
+impl Navigation {
+    /// Path from `start` to `end` as a follower, or `None` when there is none.
+    fn find_path(
+        &self,
+        start: Vec2,
+        end: Vec2,
+        filter: Option<&NavFilter>,
+    ) -> Option<PathFollower> {
+        let (path, dimensions) = match self {
+            Navigation::Grid(grid) => {
+                let cell = |p: Vec2| (p.x.round().max(0.0) as u32, p.y.round().max(0.0) as u32);
+                let ((sx, sy), (ex, ey)) = (cell(start), cell(end));
+                let path = match filter {
+                    Some(filter) => grid.find_path_filtered(sx, sy, ex, ey, filter),
+                    None => grid.find_path(sx, sy, ex, ey),
+                };
+                (path, 2)
+            }
+            Navigation::Mesh(mesh) => {
+                let (start, end) = ([start.x, 0.0, start.y], [end.x, 0.0, end.y]);
+                (mesh.find_filtered_path(start, end, 0.0, filter), 3)
+            }
+        };
+        if path.is_empty() {
+            return None;
+        }
+        PathFollower::new(&path, dimensions).ok()
+    }
+}
+
+struct Member {
+    params: CrowdAgentParams,
+    state: MoveState,
+    target: Vec2,
+    follower: Option<PathFollower>,
+    patrol: Option<Patroller>,
+    stuck: StuckMonitor,
+    filter: Option<NavFilter>,
+    /// Velocity and heading after the last step, for the locomotion buffer.
+    velocity: Vec2,
+    heading: Vec2,
+}
+
+/// High-level crowd: agents are given move targets, and each `update` plans their
+/// paths, steers them along those paths, and resolves collisions between them
+/// with ORCA. Positions are 2D; on a `Grid` they are cell coordinates with cell
+/// centers at integers, on a `NavMesh` they are XZ coordinates on a single-level
+/// mesh.
+///
+/// Both the grid and the mesh are copied; create a new crowd after editing them.
+#[wasm_bindgen]
+pub struct Crowd {
+    navigation: Navigation,
+    simulator: CrowdSimulator,
+    members: Vec<Member>,
+    requests: VecDeque<usize>,
+    /// Every agent's path points, packed for `path_waypoints`.
+    waypoints: Vec<f32>,
+    path_offsets: Vec<u32>,
+    /// Paths planned per `update`; further requests wait for later frames.
+    pub max_path_requests: u32,
+    /// Seconds a moving agent may go without getting `stuck_distance` further
+    /// along its path before it counts as stuck, or 0 to never check. A stuck
+    /// agent raises a `Stuck` event and first sidesteps, then replans, then
+    /// alternates the two, or is put back on its path a little ahead instead
+    /// once `teleport_when_stuck` is set.
+    pub stuck_time: f32,
+    pub stuck_distance: f32,
+    /// Lets stuck agents that a sidestep and a new path did not free be moved
+    /// onto their path, which always lies on the grid or mesh.
+    pub teleport_when_stuck: bool,
+    /// Turn rate, in radians per second, that the locomotion buffer reports
+    /// as a full turn.
+    pub max_turn_rate: f32,
+    /// Acceleration below which the locomotion buffer reports a moving agent
+    /// as `Steady`.
+    pub acceleration_threshold: f32,
+    /// `locomotion::STRIDE` floats per agent, refreshed by each `update`.
+    locomotion: Vec<f32>,
+    /// Per-update scratch kept so that updates stop allocating.
+    planned: Vec<usize>,
+    followers: Vec<Option<PathFollower>>,
+    recoveries: Vec<(usize, Option<Vec2>)>,
+    events: Vec<AiEvent>,
+    stats: AiStats,
+}
+
+#[wasm_bindgen]
+impl Crowd {
+    /// Queues a path request toward `target`.
+    fn send(&mut self, index: usize, target: Vec2) {
+        let member = &mut self.members[index];
+        member.target = target;
+        if member.state != MoveState::Pending {
+            member.state = MoveState::Pending;
+            self.requests.push_back(index);
+        }
+    }
+
+    #[wasm_bindgen(constructor)]
+    pub fn new(grid: &Grid) -> Crowd {
+        Crowd::with_navigation(Navigation::Grid(grid.clone()))
+    }
+
+    pub fn from_navmesh(mesh: &NavMesh) -> Crowd {
+        Crowd::with_navigation(Navigation::Mesh(mesh.clone()))
+    }
+
+    /// Adds an agent at `(x, y)` with `params` and returns its id.
+    ///
+    /// @deprecated Use `add_agent_with`, which names its settings.
+    pub fn add_agent(&mut self, x: f32, y: f32, params: &CrowdAgentParams) -> u32 {
+        self.simulator
+            .add_agent(x, y, params.radius, params.max_speed);
+        self.members.push(Member {
+            params: *params,
+            state: MoveState::Idle,
+            target: Vec2::new(x, y),
+            follower: None,
+            patrol: None,
+            stuck: StuckMonitor::default(),
+            filter: None,
+            velocity: Vec2::ZERO,
+            heading: Vec2::new(1.0, 0.0),
+        });
+        self.path_offsets.push(self.waypoints.len() as u32 / 2);
+        let start = self.locomotion.len();
+        self.locomotion.resize(start + locomotion::STRIDE, 0.0);
+        Motion::steady(Vec2::ZERO, Vec2::new(1.0, 0.0), params.max_speed).write(
+            &mut self.locomotion[start..],
+            self.max_turn_rate,
+            self.acceleration_threshold,
+        );
+        self.members.len() as u32 - 1
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn len(&self) -> u32 {
+        self.members.len() as u32
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.members.is_empty()
+    }
+
+    /// Sends an agent toward a point, ending any patrol. Its path is planned
+    /// during a later `update`.
+    pub fn request_move_target(&mut self, id: u32, x: f32, y: f32) -> bool {
+        let Some(member) = self.members.get_mut(id as usize) else {
+            return false;
+        };
+        member.patrol = None;
+        self.send(id as usize, Vec2::new(x, y));
+        true
+    }
+
+    /// Stops an agent and drops its move target and any patrol.
+    pub fn reset_move_target(&mut self, id: u32) -> bool {
+        let Some(member) = self.members.get_mut(id as usize) else {
+            return false;
+        };
+        member.state = MoveState::Idle;
+        member.patrol = None;
+        let had_path = member.follower.take().is_some();
+        self.requests.retain(|&index| index != id as usize);
+        if had_path {
+            self.pack_paths();
+        }
+        true
+    }
+
+    /// Has an agent walk `route` from its first waypoint, waiting at each as
+    /// long as the route says. A waypoint it finds no path to is waited at
+    /// from where the agent stands, then skipped. Returns `false` for unknown
+    /// agents and empty routes.
+    pub fn assign_patrol(&mut self, id: u32, route: &PatrolRoute) -> bool {
+        let (Some(member), Ok(patrol)) = (self.members.get_mut(id as usize), Patroller::new(route))
+        else {
+            return false;
+        };
+        let first = patrol.target().unwrap_or_default();
+        member.patrol = Some(patrol);
+        self.send(id as usize, first);
+        true
+    }
+
+    /// Ends an agent's patrol, leaving it to finish its current move.
+    pub fn clear_patrol(&mut self, id: u32) -> bool {
+        let Some(member) = self.members.get_mut(id as usize) else {
+            return false;
+        };
+        member.patrol = None;
+        true
+    }
+
+    /// A copy of the agent's patrol progress: its waypoint, whether it waits
+    /// there, and which way to face while it does.
+    pub fn patrol(&self, id: u32) -> Option<Patroller> {
+        self.members.get(id as usize)?.patrol.clone()
+    }
+
+    /// How many times in a row the agent has been found stuck, 0 once it is
+    /// making headway again.
+    pub fn stuck_count(&self, id: u32) -> Option<u32> {
+        self.members
+            .get(id as usize)
+            .map(|member| member.stuck.attempts())
+    }
+
+    /// Plans an agent's paths through only the areas `filter` allows, at its
+    /// area costs, from its next move on. The filter is copied.
+    pub fn set_filter(&mut self, id: u32, filter: &NavFilter) -> bool {
+        let Some(member) = self.members.get_mut(id as usize) else {
+            return false;
+        };
+        member.filter = Some(filter.clone());
+        true
+    }
+
+    pub fn clear_filter(&mut self, id: u32) -> bool {
+        let Some(member) = self.members.get_mut(id as usize) else {
+            return false;
+        };
+        member.filter = None;
+        true
+    }
+
+    pub fn state(&self, id: u32) -> Option<MoveState> {
+        self.members.get(id as usize).map(|member| member.state)
+    }
+
+    pub fn position(&self, id: u32) -> Option<Vec2> {
+        self.simulator.position(id)
+    }
+
+    /// Has the agents avoid a snapshot of `obstacles` as
+    /// `CrowdSimulator::set_obstacles` does. Their paths still follow the
+    /// copied grid or mesh, so pair this with `Grid::set_obstacle_costs`
+    /// before creating the crowd to plan around obstacles that stay put.
+    pub fn set_obstacles(&mut self, obstacles: &ObstacleSet) {
+        self.simulator.set_obstacles(obstacles);
+    }
+
+    pub fn clear_obstacles(&mut self) {
+        self.simulator.clear_obstacles();
+    }
+
+    /// Moves an agent instantly, e.g. after spawning or teleporting it. Its path
+    /// is replanned from the new position.
+    pub fn set_position(&mut self, id: u32, x: f32, y: f32) {
+        self.simulator.set_position(id, x, y);
+        if let Some(member) = self.members.get(id as usize) {
+            if matches!(member.state, MoveState::Moving | MoveState::Arrived) {
+                let target = member.target;
+                self.request_move_target(id, target.x, target.y);
+            }
+        }
+    }
+
+    /// Flat `[x0, y0, x1, y1, ...]` copy of all agent positions.
+    pub fn positions(&self) -> Vec<f32> {
+        self.simulator.positions()
+    }
+
+    /// Flat `[vx0, vy0, ...]` copy of all agent velocities.
+    pub fn velocities(&self) -> Vec<f32> {
+        self.simulator.velocities()
+    }
+
+    /// A view of every agent's speed, turn rate, heading and acceleration over
+    /// the last `update`, `locomotion_stride()` floats per agent in id order,
+    /// e.g. to drive animation blend trees. The view is invalidated when an
+    /// agent is added or the WASM memory grows, so re-fetch it after either.
+    #[cfg(target_arch = "wasm32")]
+    pub fn locomotion(&self) -> Float32Array {
+        unsafe { Float32Array::view(&self.locomotion) }
+    }
+
+    /// A view of every agent's planned path in WASM memory: flat `[x, y, ...]`
+    /// points, with agent `i`'s path from point `path_offsets()[i]` up to
+    /// `path_offsets()[i + 1]`. The view is invalidated when a path is planned or
+    /// dropped or the WASM memory grows, so re-fetch it after any of those.
+    #[cfg(target_arch = "wasm32")]
+    pub fn path_waypoints(&self) -> Float32Array {
+        unsafe { Float32Array::view(&self.waypoints) }
+    }
+
+    /// A view of where each agent's path starts in `path_waypoints`, in points,
+    /// plus the total, with the same lifetime caveats.
+    #[cfg(target_arch = "wasm32")]
+    pub fn path_offsets(&self) -> Uint32Array {
+        unsafe { Uint32Array::view(&self.path_offsets) }
+    }
+
+    /// Agents that arrived or found no path during the last `update`, as
+    /// `PathComplete` and `PathFailed` events.
+    pub fn events(&self) -> Vec<AiEvent> {
+        self.events.clone()
+    }
+
+    /// Moves patrolling agents on to their next waypoints, plans queued paths,
+    /// steers every agent along its path and advances the simulation by `dt`
+    /// seconds.
+    pub fn update(&mut self, dt: f32) {
+        self.events.clear();
+        for index in 0..self.members.len() {
+            let member = &mut self.members[index];
+            let arrived = matches!(member.state, MoveState::Arrived | MoveState::Failed);
+            let Some(patrol) = &mut member.patrol else {
+                continue;
+            };
+            if patrol.advance(arrived, dt) {
+                if let Some(target) = patrol.target() {
+                    self.send(index, target);
+                }
+            }
+        }
+        let planning = Sample::start();
+        let count = self.requests.len().min(self.max_path_requests as usize);
+        let mut planned = std::mem::take(&mut self.planned);
+        planned.clear();
+        planned.extend(self.requests.drain(..count));
+        let mut followers = std::mem::take(&mut self.followers);
+        followers.clear();
+        followers.resize(planned.len(), None);
+        parallel::fill(&mut followers, &mut (), 2, |_, i, follower| {
+            let index = planned[i];
+            let start = self.simulator.position(index as u32).unwrap_or_default();
+            let member = &self.members[index];
+            *follower = self
+                .navigation
+                .find_path(start, member.target, member.filter.as_ref());
+        });
+        for (index, follower) in planned.iter().zip(followers.drain(..)) {
+            let member = &mut self.members[*index];
+            member.state = if follower.is_some() {
+                member.stuck.restart();
+                MoveState::Moving
+            } else {
+                self.events
+                    .push(AiEvent::new(EventKind::PathFailed, *index as u32, 0));
+                MoveState::Failed
+            };
+            member.follower = follower;
+        }
+        if !planned.is_empty() {
+            self.pack_paths();
+        }
+        self.planned = planned;
+        self.followers = followers;
+        let pathfinding_us = planning.elapsed_us();
+        let node_expansions = planning.expansions();
+
+        let steering = Sample::start();
+
+        let mut recoveries = std::mem::take(&mut self.recoveries);
+        for (index, member) in self.members.iter_mut().enumerate() {
+            let position = self.simulator.position(index as u32).unwrap_or_default();
+            let velocity = match (&mut member.follower, member.state) {
+                (Some(follower), MoveState::Moving) => {
+                    let velocity = steer(follower, &member.params, position);
+                    if velocity == Vec2::ZERO {
+                        member.state = MoveState::Arrived;
+                        member.stuck.reset();
+                        self.events
+                            .push(AiEvent::new(EventKind::PathComplete, index as u32, 0));
+                        velocity
+                    } else {
+                        let recovery = member.stuck.watch(
+                            follower.progress(),
+                            dt,
+                            self.stuck_time,
+                            self.stuck_distance,
+                            self.teleport_when_stuck,
+                        );
+                        if let Some(recovery) = recovery {
+                            self.events.push(AiEvent::new(
+                                EventKind::Stuck,
+                                index as u32,
+                                member.stuck.attempts(),
+                            ));
+                            match recovery {
+                                Recovery::Sidestep => {
+                                    member.stuck.start_sidestep(velocity, index % 2 == 0)
+                                }
+                                Recovery::Repath => recoveries.push((index, None)),
+                                Recovery::Teleport => {
+                                    let ahead = member
+                                        .params
+                                        .path_lookahead
+                                        .max(2.0 * member.params.radius);
+                                    let point = follower.point_at(follower.progress() + ahead);
+                                    recoveries.push((index, Some(point)));
+                                }
+                            }
+                        }
+                        match member.stuck.sidestep(dt) {
+                            Some(side) => side * member.params.max_speed,
+                            None => velocity,
+                        }
+                    }
+                }
+                (_, MoveState::Pending) => Vec2::ZERO,
+                _ => {
+                    member.stuck.reset();
+                    Vec2::ZERO
+                }
+            };
+            self.simulator
+                .set_preferred_velocity(index as u32, velocity.x, velocity.y);
+        }
+        for (index, teleport) in recoveries.drain(..) {
+            if let Some(point) = teleport {
+                self.simulator.set_position(index as u32, point.x, point.y);
+            }
+            let target = self.members[index].target;
+            self.send(index, target);
+        }
+        self.recoveries = recoveries;
+        let steering_us = steering.elapsed_us();
+        self.simulator.step(dt);
+        for (index, member) in self.members.iter_mut().enumerate() {
+            let velocity = self.simulator.velocity(index as u32).unwrap_or_default();
+            let motion = Motion {
+                before: member.velocity,
+                after: velocity,
+                heading: member.heading,
+                max_speed: member.params.max_speed,
+                dt,
+            };
+            member.heading = motion.write(
+                &mut self.locomotion[index * locomotion::STRIDE..],
+                self.max_turn_rate,
+                self.acceleration_threshold,
+            );
+            member.velocity = velocity;
+        }
+        self.stats = AiStats {
+            steering_us,
+            pathfinding_us,
+            path_queue_length: self.requests.len() as u32,
+            node_expansions,
+            ..self.simulator.stats()
+        };
+    }
+
+    /// What the last `update` cost: every timing but `perception_us`, the
+    /// path requests left for later updates, A* expansions, `agents` and
+    /// `steps`.
+    pub fn stats(&self) -> AiStats {
+        self.stats
+    }
+
+    /// Every agent with its move request, path, patrol, filter and stuck
+    /// watch, the queued path requests, the avoidance state and the settings,
+    /// as bytes for `load_state`, e.g. in a `SaveGame`. The grid or mesh is
+    /// left out.
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut out = Writer::new(MAGIC, VERSION);
+        out.u32(self.max_path_requests);
+        out.f32s(&[self.stuck_time, self.stuck_distance]);
+        out.u32(self.teleport_when_stuck as u32);
+        out.f32s(&[self.max_turn_rate, self.acceleration_threshold]);
+        self.simulator.write(&mut out);
+        out.u32(self.members.len() as u32);
+        for member in &self.members {
+            let params = &member.params;
+            out.f32s(&[params.radius, params.max_speed, params.path_lookahead]);
+            out.f32s(&[params.arrive_radius, params.slow_radius]);
+            out.u32(member.state as u32);
+            out.f32s(&[member.target.x, member.target.y]);
+            out.u32(member.follower.is_some() as u32);
+            if let Some(follower) = &member.follower {
+                follower.write(&mut out);
+            }
+            out.u32(member.patrol.is_some() as u32);
+            if let Some(patrol) = &member.patrol {
+                patrol.write(&mut out);
+            }
+            member.stuck.write(&mut out);
+            out.u32(member.filter.is_some() as u32);
+            if let Some(filter) = &member.filter {
+                filter.write(&mut out);
+            }
+            out.f32s(&[member.velocity.x, member.velocity.y]);
+            out.f32s(&[member.heading.x, member.heading.y]);
+        }
+        out.u32(self.requests.len() as u32);
+        for &index in &self.requests {
+            out.u32(index as u32);
+        }
+        out.f32s(&self.locomotion);
+        out.finish()
+    }
+
+    /// Replaces every agent and setting with those from `save_state`, keeping
+    /// this crowd's grid or mesh, which should be the one the saved crowd
+    /// walked. The next `update` carries on as the saved crowd's would have.
+    pub fn load_state(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        let (mut input, version) = Reader::new(bytes, MAGIC, "a crowd save")?;
+        if version != VERSION {
+            return Err(Error::InvalidInput("unsupported crowd save version".into()));
+        }
+        let max_path_requests = input.u32()?;
+        let [stuck_time, stuck_distance] = [input.finite_f32()?, input.finite_f32()?];
+        let teleport_when_stuck = input.u32()? != 0;
+        let [max_turn_rate, acceleration_threshold] = [input.finite_f32()?, input.finite_f32()?];
+        let simulator = CrowdSimulator::read(&mut input)?;
+        let count = input.u32()? as usize;
+        if count != simulator.len() as usize {
+            return Err(input.invalid());
+        }
+        let mut members = Vec::with_capacity(count);
+        for _ in 0..count {
+            let params = CrowdAgentParams {
+                radius: input.finite_f32()?,
+                max_speed: input.finite_f32()?,
+                path_lookahead: input.finite_f32()?,
+                arrive_radius: input.finite_f32()?,
+                slow_radius: input.finite_f32()?,
+            };
+            let state = match input.u32()? {
+                0 => MoveState::Idle,
+                1 => MoveState::Pending,
+                2 => MoveState::Moving,
+                3 => MoveState::Arrived,
+                4 => MoveState::Failed,
+                _ => return Err(input.invalid()),
+            };
+            let target = Vec2::new(input.finite_f32()?, input.finite_f32()?);
+            let follower = match input.u32()? {
+                0 => None,
+                _ => Some(PathFollower::read(&mut input)?),
+            };
+            let patrol = match input.u32()? {
+                0 => None,
+                _ => Some(Patroller::read(&mut input)?),
+            };
+            let stuck = StuckMonitor::read(&mut input)?;
+            let filter = match input.u32()? {
+                0 => None,
+                _ => Some(NavFilter::read(&mut input)?),
+            };
+            members.push(Member {
+                params,
+                state,
+                target,
+                follower,
+                patrol,
+                stuck,
+                filter,
+                velocity: Vec2::new(input.finite_f32()?, input.finite_f32()?),
+                heading: Vec2::new(input.finite_f32()?, input.finite_f32()?),
+            });
+        }
+        let queued = input.u32()? as usize;
+        let requests = input.u32s(queued)?;
+        if requests.iter().any(|&index| index as usize >= count) {
+            return Err(input.invalid());
+        }
+        let locomotion = input.finite_f32s(count * locomotion::STRIDE)?;
+        input.finish()?;
+
+        self.max_path_requests = max_path_requests;
+        self.stuck_time = stuck_time;
+        self.stuck_distance = stuck_distance;
+        self.teleport_when_stuck = teleport_when_stuck;
+        self.max_turn_rate = max_turn_rate;
+        self.acceleration_threshold = acceleration_threshold;
+        self.simulator = simulator;
+        self.members = members;
+        self.requests = requests.into_iter().map(|index| index as usize).collect();
+        self.locomotion = locomotion;
+        self.events.clear();
+        self.stats = AiStats::default();
+        self.pack_paths();
+        Ok(())
+    }
+
+    /// Appends each agent to `out` as a point with a line for its velocity and,
+    /// while it moves, its path and move target.
+    pub fn debug_geometry(&self, out: &mut DebugGeometry) {
+        for (index, member) in self.members.iter().enumerate() {
+            let position = self.simulator.position(index as u32).unwrap_or_default();
+            if let (Some(follower), MoveState::Moving) = (&member.follower, member.state) {
+                out.polyline(follower.points(), debug::PATH);
+                out.point(member.target, debug::TARGET);
+            }
+            let velocity = self.simulator.velocity(index as u32).unwrap_or_default();
+            out.arrow(position, velocity, debug::VELOCITY);
+            out.point(position, debug::AGENT);
+        }
+    }
+}
+
+impl Crowd {
+    /// Every agent's locomotion, `locomotion_stride()` floats per agent in id
+    /// order, as `locomotion` shows it to JS.
+    pub fn locomotion_buffer(&self) -> &[f32] {
+        &self.locomotion
+    }
+
+    /// The points behind `path_waypoints`.
+    pub fn waypoint_buffer(&self) -> &[f32] {
+        &self.waypoints
+    }
+
+    /// The offsets behind `path_offsets`.
+    pub fn path_offset_buffer(&self) -> &[u32] {
+        &self.path_offsets
+    }
+
+    pub(crate) fn recent_events(&self) -> &[AiEvent] {
+        &self.events
+    }
+
+    fn with_navigation(navigation: Navigation) -> Crowd {
+        Crowd {
+            navigation,
+            simulator: CrowdSimulator::new(5.0, 2.0),
+            members: Vec::new(),
+            requests: VecDeque::new(),
+            waypoints: Vec::new(),
+            path_offsets: vec![0],
+            max_path_requests: 8,
+            stuck_time: 2.0,
+            stuck_distance: 0.5,
+            teleport_when_stuck: false,
+            max_turn_rate: std::f32::consts::PI,
+            acceleration_threshold: 0.5,
+            locomotion: Vec::new(),
+            planned: Vec::new(),
+            followers: Vec::new(),
+            recoveries: Vec::new(),
+            events: Vec::new(),
+            stats: AiStats::default(),
+        }
+    }
+
+    /// Repacks every agent's path after one changes.
+    fn pack_paths(&mut self) {
+        self.waypoints.clear();
+        self.path_offsets.clear();
+        self.path_offsets.push(0);
+        for member in &self.members {
+            if let Some(follower) = &member.follower {
+                for point in follower.points() {
+                    self.waypoints.extend_from_slice(&[point.x, point.y]);
+                }
+            }
+            self.path_offsets.push(self.waypoints.len() as u32 / 2);
+        }
+    }
+}
+
+/// Preferred velocity toward the point `path_lookahead` ahead on the path,
+/// slowing down near the end. Zero once the agent has arrived.
+fn steer(follower: &mut PathFollower, params: &CrowdAgentParams, position: Vec2) -> Vec2 {
+    let lookahead = params.path_lookahead.max(0.0);
+    follower.advance(position, (2.0 * lookahead).max(params.arrive_radius));
+    let end = follower.point_at(follower.length());
+    let to_end = position.distance(end);
+    if to_end <= params.arrive_radius {
+        return Vec2::ZERO;
+    }
+    let aim = follower.point_at(follower.progress() + lookahead);
+    let speed = if params.slow_radius > 0.0 {
+        params.max_speed * (to_end / params.slow_radius).min(1.0)
+    } else {
+        params.max_speed
+    };
+    (aim - position).normalize() * speed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn load_state_rejects_floats_that_are_not_finite() {
+        let grid = Grid::new(16, 16).unwrap();
+        let mut crowd = Crowd::new(&grid);
+        let agent = crowd.add_agent(1.5, 1.5, &CrowdAgentParams::new());
+        crowd.request_move_target(agent, 12.5, 12.5);
+        for _ in 0..5 {
+            crowd.update(0.1);
+        }
+        let saved = crowd.save_state();
+        // Overwrites every word-sized window with NaN or infinity: each
+        // corrupted save either fails to load or keeps updating without
+        // panicking.
+        for at in 8..saved.len() - 3 {
+            for bad in [f32::NAN, f32::INFINITY] {
+                let mut bytes = saved.clone();
+                bytes[at..at + 4].copy_from_slice(&bad.to_le_bytes());
+                let mut loaded = Crowd::new(&grid);
+                if loaded.load_state(&bytes).is_ok() {
+                    for _ in 0..3 {
+                        loaded.update(0.1);
+                    }
+                }
+            }
+        }
+        let mut loaded = Crowd::new(&grid);
+        loaded.load_state(&saved).unwrap();
+        assert_eq!(loaded.position(agent), crowd.position(agent));
+    }
+}
@@ -0,0 +1,328 @@
+use wasm_bindgen::prelude::*;
+
+use crate::error::Error;
+use crate::json::Json;
+
+/// Samples `inverse` takes along the curve to find where it crosses a value,
+/// before narrowing each crossing down.
+const INVERSE_SAMPLES: u32 = 64;
+
+#[derive(Clone, Debug, PartialEq)]
+enum Shape {
+    Linear {
+        slope: f32,
+        intercept: f32,
+    },
+    Quadratic {
+        slope: f32,
+        exponent: f32,
+        x_shift: f32,
+        y_shift: f32,
+    },
+    Polynomial(Vec<f32>),
+    Logistic {
+        steepness: f32,
+        midpoint: f32,
+        scale: f32,
+        y_shift: f32,
+    },
+    Points(Vec<(f32, f32)>),
+}
+
+/// A response curve mapping a normalized input in `[0, 1]` to a score in `[0, 1]`,
+/// e.g. how much an NPC's fear grows with the damage it has taken. Utility
+/// considerations feed their inputs through one; tuning data can describe one
+/// as JSON.
+#[wasm_bindgen]
+#[derive(Clone, Debug, PartialEq)]
+pub struct Curve {
+    shape: Shape,
+}
+
+#[wasm_bindgen]
+impl Curve {
+    /// `y = slope * x + intercept`
+    pub fn linear(slope: f32, intercept: f32) -> Curve {
+        Curve {
+            shape: Shape::Linear { slope, intercept },
+        }
+    }
+
+    /// `y = slope * (x - x_shift)^exponent + y_shift`
+    pub fn quadratic(slope: f32, exponent: f32, x_shift: f32, y_shift: f32) -> Curve {
+        Curve {
+            shape: Shape::Quadratic {
+                slope,
+                exponent,
+                x_shift,
+                y_shift,
+            },
+        }
+    }
+
+    /// `y = c0 + c1 * x + c2 * x^2 + ...` from `coefficients` `[c0, c1, c2,
+    /// ...]`, e.g. `[0, 0, 3, -2]` for a smoothstep ease in and out.
+    pub fn polynomial(coefficients: &[f32]) -> Result<Curve, Error> {
+        if coefficients.is_empty() {
+            return Err(Error::InvalidInput(
+                "a polynomial needs at least one coefficient".into(),
+            ));
+        }
+        Ok(Curve {
+            shape: Shape::Polynomial(coefficients.to_vec()),
+        })
+    }
+
+    /// `y = scale / (1 + e^(-steepness * (x - midpoint))) + y_shift`
+    pub fn logistic(steepness: f32, midpoint: f32, scale: f32, y_shift: f32) -> Curve {
+        Curve {
+            shape: Shape::Logistic {
+                steepness,
+                midpoint,
+                scale,
+                y_shift,
+            },
+        }
+    }
+
+    /// Piecewise-linear curve through flat `[x0, y0, x1, y1, ...]` control points.
+    pub fn points(points: &[f32]) -> Result<Curve, Error> {
+        if points.len() < 2 || !points.len().is_multiple_of(2) {
+            return Err(Error::InvalidInput(
+                "curve points must be non-empty x, y pairs".into(),
+            ));
+        }
+        let mut points: Vec<(f32, f32)> = points.chunks_exact(2).map(|p| (p[0], p[1])).collect();
+        points.sort_by(|a, b| a.0.total_cmp(&b.0));
+        Ok(Curve {
+            shape: Shape::Points(points),
+        })
+    }
+
+    /// Evaluates the curve, clamping both input and output to `[0, 1]`.
+    pub fn evaluate(&self, x: f32) -> f32 {
+        let x = x.clamp(0.0, 1.0);
+        let y = match &self.shape {
+            Shape::Linear { slope, intercept } => slope * x + intercept,
+            Shape::Quadratic {
+                slope,
+                exponent,
+                x_shift,
+                y_shift,
+            } => slope * (x - x_shift).powf(*exponent) + y_shift,
+            Shape::Polynomial(coefficients) => coefficients
+                .iter()
+                .rev()
+                .fold(0.0, |sum, coefficient| sum * x + coefficient),
+            Shape::Logistic {
+                steepness,
+                midpoint,
+                scale,
+                y_shift,
+            } => scale / (1.0 + (-steepness * (x - midpoint)).exp()) + y_shift,
+            Shape::Points(points) => sample_points(points, x),
+        };
+        if y.is_nan() {
+            0.0
+        } else {
+            y.clamp(0.0, 1.0)
+        }
+    }
+
+    /// Evaluates the curve at `value` taken from `[min, max]` to `[0, 1]`.
+    pub fn evaluate_in(&self, value: f32, min: f32, max: f32) -> f32 {
+        self.evaluate(Curve::normalize(value, min, max))
+    }
+
+    /// Where `value` lies between `min` and `max`, as a fraction clamped to
+    /// `[0, 1]`; 0 when the range is empty.
+    pub fn normalize(value: f32, min: f32, max: f32) -> f32 {
+        let range = max - min;
+        if range.abs() > f32::EPSILON {
+            ((value - min) / range).clamp(0.0, 1.0)
+        } else {
+            0.0
+        }
+    }
+
+    /// The smallest input in `[0, 1]` at which the curve reaches `y`, e.g. to
+    /// find how hurt an NPC must be before its fear passes a threshold.
+    /// Returns `undefined` when the curve never reaches it.
+    pub fn inverse(&self, y: f32) -> Option<f32> {
+        let step = 1.0 / INVERSE_SAMPLES as f32;
+        let mut low = (0.0, self.evaluate(0.0) - y);
+        if low.1 == 0.0 {
+            return Some(0.0);
+        }
+        for i in 1..=INVERSE_SAMPLES {
+            let x = i as f32 * step;
+            let high = (x, self.evaluate(x) - y);
+            if high.1 == 0.0 {
+                return Some(x);
+            }
+            if (low.1 < 0.0) != (high.1 < 0.0) {
+                return Some(self.bisect(low, high, y));
+            }
+            low = high;
+        }
+        None
+    }
+
+    /// `count` evenly spaced outputs from `x = 0` to `x = 1`, e.g. to plot the
+    /// curve in a tuning tool.
+    pub fn sample(&self, count: u32) -> Vec<f32> {
+        match count {
+            0 => Vec::new(),
+            1 => vec![self.evaluate(0.0)],
+            _ => (0..count)
+                .map(|i| self.evaluate(i as f32 / (count - 1) as f32))
+                .collect(),
+        }
+    }
+
+    /// The curve as JSON that `from_json` reads back, such as
+    /// `{"type":"logistic","steepness":8,"midpoint":0.5,"scale":1,"y_shift":0}`.
+    pub fn to_json(&self) -> String {
+        self.write().to_string()
+    }
+
+    /// Reads a curve such as `{"type": "logistic", "steepness": 8}`, with the
+    /// parameters of the matching constructor and any left out at their
+    /// defaults; `polynomial` curves take a `coefficients` array and `points`
+    /// curves a flat `points` array.
+    pub fn from_json(json: &str) -> Result<Curve, Error> {
+        Curve::read(&Json::parse(json)?)
+    }
+}
+
+impl Curve {
+    pub(crate) fn read(json: &Json) -> Result<Curve, Error> {
+        let kind = json.field("type", Json::as_str)?;
+        let number = |key: &str, default: f32| match json.get(key) {
+            Some(value) => value.as_f64().map(|value| value as f32).ok_or_else(|| {
+                Error::InvalidInput(format!("curve field '{}' must be a number", key))
+            }),
+            None => Ok(default),
+        };
+        match kind {
+            "linear" => Ok(Curve::linear(
+                number("slope", 1.0)?,
+                number("intercept", 0.0)?,
+            )),
+            "quadratic" => Ok(Curve::quadratic(
+                number("slope", 1.0)?,
+                number("exponent", 2.0)?,
+                number("x_shift", 0.0)?,
+                number("y_shift", 0.0)?,
+            )),
+            "polynomial" => Curve::polynomial(&json.field("coefficients", Json::as_f32s)?),
+            "logistic" => Ok(Curve::logistic(
+                number("steepness", 10.0)?,
+                number("midpoint", 0.5)?,
+                number("scale", 1.0)?,
+                number("y_shift", 0.0)?,
+            )),
+            "points" => Curve::points(&json.field("points", Json::as_f32s)?),
+            _ => Err(Error::InvalidInput(format!(
+                "unknown curve type '{}'",
+                kind
+            ))),
+        }
+    }
+
+    pub(crate) fn write(&self) -> Json {
+        let numbers =
+            |values: &[f32]| Json::Array(values.iter().copied().map(Json::from_f32).collect());
+        let (kind, fields) = match &self.shape {
+            Shape::Linear { slope, intercept } => (
+                "linear",
+                numbered(&[("slope", *slope), ("intercept", *intercept)]),
+            ),
+            Shape::Quadratic {
+                slope,
+                exponent,
+                x_shift,
+                y_shift,
+            } => (
+                "quadratic",
+                numbered(&[
+                    ("slope", *slope),
+                    ("exponent", *exponent),
+                    ("x_shift", *x_shift),
+                    ("y_shift", *y_shift),
+                ]),
+            ),
+            Shape::Polynomial(coefficients) => (
+                "polynomial",
+                vec![("coefficients".into(), numbers(coefficients))],
+            ),
+            Shape::Logistic {
+                steepness,
+                midpoint,
+                scale,
+                y_shift,
+            } => (
+                "logistic",
+                numbered(&[
+                    ("steepness", *steepness),
+                    ("midpoint", *midpoint),
+                    ("scale", *scale),
+                    ("y_shift", *y_shift),
+                ]),
+            ),
+            Shape::Points(points) => {
+                let flat: Vec<f32> = points.iter().flat_map(|&(x, y)| [x, y]).collect();
+                ("points", vec![("points".into(), numbers(&flat))])
+            }
+        };
+        let mut entries = vec![("type".to_string(), Json::String(kind.into()))];
+        entries.extend(fields);
+        Json::Object(entries)
+    }
+
+    /// Narrows down where the curve crosses `y` between two samples on
+    /// either side of it.
+    fn bisect(&self, mut low: (f32, f32), mut high: (f32, f32), y: f32) -> f32 {
+        for _ in 0..24 {
+            let x = (low.0 + high.0) * 0.5;
+            let middle = (x, self.evaluate(x) - y);
+            if (middle.1 < 0.0) == (low.1 < 0.0) {
+                low = middle;
+            } else {
+                high = middle;
+            }
+        }
+        // The crossing nearer the start, as curves with steps jump past `y`.
+        if low.1.abs() <= high.1.abs() {
+            low.0
+        } else {
+            high.0
+        }
+    }
+}
+
+fn numbered(fields: &[(&str, f32)]) -> Vec<(String, Json)> {
+    fields
+        .iter()
+        .map(|&(key, value)| (key.to_string(), Json::from_f32(value)))
+        .collect()
+}
+
+fn sample_points(points: &[(f32, f32)], x: f32) -> f32 {
+    let first = points[0];
+    let last = points[points.len() - 1];
+    if x <= first.0 {
+        return first.1;
+    }
+    if x >= last.0 {
+        return last.1;
+    }
+    for pair in points.windows(2) {
+        let ((x0, y0), (x1, y1)) = (pair[0], pair[1]);
+        if x <= x1 {
+            let t = if x1 > x0 { (x - x0) / (x1 - x0) } else { 1.0 };
+            return y0 + (y1 - y0) * t;
+        }
+    }
+    last.1
+}
@@ -0,0 +1,167 @@
+use wasm_bindgen::prelude::*;
+
+use crate::math::Vec2;
+
+pub(crate) type Color = [f32; 3];
+
+pub(crate) const PATH: Color = [1.0, 0.85, 0.2];
+pub(crate) const AGENT: Color = [1.0, 1.0, 1.0];
+pub(crate) const TARGET: Color = [0.6, 0.6, 0.6];
+pub(crate) const VELOCITY: Color = [0.2, 0.9, 0.3];
+pub(crate) const FORCE: Color = [0.9, 0.3, 0.9];
+pub(crate) const HEADING: Color = [0.3, 0.6, 1.0];
+pub(crate) const MESH: Color = [0.15, 0.45, 0.5];
+pub(crate) const MESH_EDGE: Color = [0.3, 0.8, 0.85];
+pub(crate) const BLOCKED: Color = [0.6, 0.15, 0.15];
+pub(crate) const LINK: Color = [0.9, 0.5, 0.1];
+pub(crate) const CONE: Color = [0.35, 0.3, 0.1];
+pub(crate) const SIGHT: Color = [1.0, 0.25, 0.2];
+pub(crate) const OPEN: Color = [0.2, 0.4, 0.25];
+pub(crate) const CHOKEPOINT: Color = [0.95, 0.3, 0.1];
+pub(crate) const DEAD_END: Color = [0.5, 0.2, 0.6];
+pub(crate) const CORRIDOR: Color = [0.7, 0.6, 0.3];
+
+/// Line, point and triangle buffers for drawing AI state, e.g. as three.js
+/// `LineSegments`, `Points` and `Mesh` geometry with vertex colors. Components
+/// append to it with their `debug_geometry` methods; `clear` it each frame.
+///
+/// Positions are `[x, y, z, ...]` and colors `[r, g, b, ...]` in `[0, 1]`, one
+/// per vertex. 2D `(x, y)` positions are drawn on the XZ plane at `(x,
+/// elevation, y)`, where a `Crowd` on a `NavMesh` already moves.
+#[wasm_bindgen]
+#[derive(Clone, Debug, Default)]
+pub struct DebugGeometry {
+    /// Height the 2D geometry is drawn at.
+    pub elevation: f32,
+    line_positions: Vec<f32>,
+    line_colors: Vec<f32>,
+    point_positions: Vec<f32>,
+    point_colors: Vec<f32>,
+    triangle_positions: Vec<f32>,
+    triangle_colors: Vec<f32>,
+}
+
+#[wasm_bindgen]
+impl DebugGeometry {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> DebugGeometry {
+        DebugGeometry::default()
+    }
+
+    pub fn clear(&mut self) {
+        let elevation = self.elevation;
+        *self = DebugGeometry {
+            elevation,
+            ..DebugGeometry::default()
+        };
+    }
+
+    /// Two vertices per line segment.
+    pub fn line_positions(&self) -> Vec<f32> {
+        self.line_positions.clone()
+    }
+
+    pub fn line_colors(&self) -> Vec<f32> {
+        self.line_colors.clone()
+    }
+
+    pub fn point_positions(&self) -> Vec<f32> {
+        self.point_positions.clone()
+    }
+
+    pub fn point_colors(&self) -> Vec<f32> {
+        self.point_colors.clone()
+    }
+
+    /// Three vertices per triangle.
+    pub fn triangle_positions(&self) -> Vec<f32> {
+        self.triangle_positions.clone()
+    }
+
+    pub fn triangle_colors(&self) -> Vec<f32> {
+        self.triangle_colors.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn line_count(&self) -> u32 {
+        self.line_positions.len() as u32 / 6
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn point_count(&self) -> u32 {
+        self.point_positions.len() as u32 / 3
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn triangle_count(&self) -> u32 {
+        self.triangle_positions.len() as u32 / 9
+    }
+}
+
+impl DebugGeometry {
+    fn lift(&self, point: Vec2) -> [f32; 3] {
+        [point.x, self.elevation, point.y]
+    }
+
+    pub(crate) fn line_3d(&mut self, from: [f32; 3], to: [f32; 3], color: Color) {
+        self.line_positions.extend_from_slice(&from);
+        self.line_positions.extend_from_slice(&to);
+        self.line_colors.extend_from_slice(&color);
+        self.line_colors.extend_from_slice(&color);
+    }
+
+    pub(crate) fn line(&mut self, from: Vec2, to: Vec2, color: Color) {
+        self.line_3d(self.lift(from), self.lift(to), color);
+    }
+
+    /// A line from `from` along `vector`, skipped when the vector is zero.
+    pub(crate) fn arrow(&mut self, from: Vec2, vector: Vec2, color: Color) {
+        if vector != Vec2::ZERO {
+            self.line(from, from + vector, color);
+        }
+    }
+
+    /// Segments joining consecutive points.
+    pub(crate) fn polyline(&mut self, points: &[Vec2], color: Color) {
+        for pair in points.windows(2) {
+            self.line(pair[0], pair[1], color);
+        }
+    }
+
+    pub(crate) fn point_3d(&mut self, point: [f32; 3], color: Color) {
+        self.point_positions.extend_from_slice(&point);
+        self.point_colors.extend_from_slice(&color);
+    }
+
+    pub(crate) fn point(&mut self, point: Vec2, color: Color) {
+        self.point_3d(self.lift(point), color);
+    }
+
+    pub(crate) fn triangle_3d(&mut self, corners: [[f32; 3]; 3], color: Color) {
+        for corner in corners {
+            self.triangle_positions.extend_from_slice(&corner);
+            self.triangle_colors.extend_from_slice(&color);
+        }
+    }
+
+    pub(crate) fn triangle(&mut self, a: Vec2, b: Vec2, c: Vec2, color: Color) {
+        self.triangle_3d([self.lift(a), self.lift(b), self.lift(c)], color);
+    }
+
+    /// An axis-aligned square of side 1 centered on a cell.
+    pub(crate) fn cell(&mut self, x: f32, y: f32, color: Color) {
+        let (x0, y0, x1, y1) = (x - 0.5, y - 0.5, x + 0.5, y + 0.5);
+        self.triangle(
+            Vec2::new(x0, y0),
+            Vec2::new(x0, y1),
+            Vec2::new(x1, y1),
+            color,
+        );
+        self.triangle(
+            Vec2::new(x0, y0),
+            Vec2::new(x1, y1),
+            Vec2::new(x1, y0),
+            color,
+        );
+    }
+}
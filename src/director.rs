@@ -0,0 +1,233 @@
+use wasm_bindgen::prelude::*;
+
+use crate::events::{AiEvent, EventKind};
+
+/// Where a `Director` is in its pacing cycle.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PacingPhase {
+    /// Spawning freely while the players' intensity climbs to its peak.
+    BuildUp = 0,
+    /// Holding the peak for `peak_duration`, replacing losses only.
+    SustainPeak = 1,
+    /// No spawns until the intensity falls below `relax_intensity`.
+    PeakFade = 2,
+    /// A breather of `relax_duration` with no spawns.
+    Relax = 3,
+}
+
+/// Paces a game the way the Left 4 Dead director does: the game reports
+/// damage to the players, fights and the enemies near them, the director folds
+/// that into a tension value and cycles through building up, holding the peak,
+/// letting it fade and a relaxed lull, recommending how many enemies to spawn
+/// along the way.
+///
+/// Phase changes come out as `PacingChanged` events with the new phase as
+/// `other`, and growth of the spawn budget as `SpawnBudget` events with the
+/// whole number of enemies to spawn as `other`; read them from `events` or
+/// publish them to an `EventBus`.
+#[wasm_bindgen]
+#[derive(Clone, Debug)]
+pub struct Director {
+    /// Intensity each point of damage to the players adds.
+    pub damage_weight: f32,
+    /// Intensity per second each nearby enemy adds.
+    pub enemy_weight: f32,
+    /// Intensity lost per second once the players have been out of combat
+    /// for `decay_delay` seconds.
+    pub decay_rate: f32,
+    pub decay_delay: f32,
+    /// Intensity, from 0 to 1, that ends the build-up.
+    pub peak_intensity: f32,
+    /// Seconds the peak is held.
+    pub peak_duration: f32,
+    /// Intensity below which the fading peak gives way to the lull.
+    pub relax_intensity: f32,
+    /// Seconds the lull lasts.
+    pub relax_duration: f32,
+    /// Enemies per second the budget grows by while building up.
+    pub spawn_rate: f32,
+    /// Most enemies the director wants near the players at once.
+    pub max_population: u32,
+    intensity: f32,
+    phase: PacingPhase,
+    phase_time: f32,
+    since_combat: f32,
+    enemies: u32,
+    /// Enemies near the players when the peak began.
+    held: u32,
+    budget: f32,
+    events: Vec<AiEvent>,
+}
+
+impl Default for Director {
+    fn default() -> Director {
+        Director {
+            damage_weight: 0.02,
+            enemy_weight: 0.01,
+            decay_rate: 0.1,
+            decay_delay: 3.0,
+            peak_intensity: 0.9,
+            peak_duration: 5.0,
+            relax_intensity: 0.3,
+            relax_duration: 30.0,
+            spawn_rate: 0.5,
+            max_population: 20,
+            intensity: 0.0,
+            phase: PacingPhase::BuildUp,
+            phase_time: 0.0,
+            since_combat: f32::INFINITY,
+            enemies: 0,
+            held: 0,
+            budget: 0.0,
+            events: Vec::new(),
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl Director {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Director {
+        Director::default()
+    }
+
+    /// Reports `amount` damage the players took, which also counts as combat.
+    pub fn report_damage(&mut self, amount: f32) {
+        self.intensity = (self.intensity + amount.max(0.0) * self.damage_weight).min(1.0);
+        self.since_combat = 0.0;
+    }
+
+    /// Reports a fight without damage to the players, such as them shooting
+    /// enemies, which holds the intensity up.
+    pub fn report_combat(&mut self) {
+        self.since_combat = 0.0;
+    }
+
+    /// Reports how many enemies are near the players now.
+    pub fn set_enemies_nearby(&mut self, count: u32) {
+        self.enemies = count;
+    }
+
+    /// Tells the director `count` enemies were spawned from its budget.
+    pub fn consume_spawns(&mut self, count: u32) {
+        self.budget = (self.budget - count as f32).max(0.0);
+    }
+
+    /// Advances the pacing by `dt` seconds.
+    pub fn update(&mut self, dt: f32) {
+        self.events.clear();
+        self.since_combat += dt;
+        self.phase_time += dt;
+        self.intensity = (self.intensity + self.enemy_weight * self.enemies as f32 * dt).min(1.0);
+        if self.since_combat >= self.decay_delay {
+            self.intensity = (self.intensity - self.decay_rate * dt).max(0.0);
+        }
+
+        let next = match self.phase {
+            PacingPhase::BuildUp if self.intensity >= self.peak_intensity => {
+                Some(PacingPhase::SustainPeak)
+            }
+            PacingPhase::SustainPeak if self.phase_time >= self.peak_duration => {
+                Some(PacingPhase::PeakFade)
+            }
+            PacingPhase::PeakFade if self.intensity < self.relax_intensity => {
+                Some(PacingPhase::Relax)
+            }
+            PacingPhase::Relax if self.phase_time >= self.relax_duration => {
+                Some(PacingPhase::BuildUp)
+            }
+            _ => None,
+        };
+        if let Some(phase) = next {
+            self.phase = phase;
+            self.phase_time = 0.0;
+            self.held = self.enemies;
+            self.events
+                .push(AiEvent::new(EventKind::PacingChanged, 0, phase as u32));
+        }
+
+        let room = self.max_population.saturating_sub(self.enemies) as f32;
+        let before = self.spawn_budget();
+        self.budget = match self.phase {
+            PacingPhase::BuildUp => (self.budget + self.spawn_rate * dt).min(room),
+            // Only losses are replaced, up to the population the peak began with.
+            PacingPhase::SustainPeak => (self
+                .held
+                .min(self.max_population)
+                .saturating_sub(self.enemies) as f32)
+                .max(self.budget.min(room)),
+            PacingPhase::PeakFade | PacingPhase::Relax => 0.0,
+        };
+        let after = self.spawn_budget();
+        if after > before {
+            self.events
+                .push(AiEvent::new(EventKind::SpawnBudget, 0, after));
+        }
+    }
+
+    /// Tension from 0, calm, to 1, overwhelmed.
+    #[wasm_bindgen(getter)]
+    pub fn intensity(&self) -> f32 {
+        self.intensity
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn phase(&self) -> PacingPhase {
+        self.phase
+    }
+
+    /// Seconds spent in the current phase.
+    #[wasm_bindgen(getter)]
+    pub fn phase_time(&self) -> f32 {
+        self.phase_time
+    }
+
+    /// Seconds since the players last fought or took damage.
+    #[wasm_bindgen(getter)]
+    pub fn time_since_combat(&self) -> f32 {
+        self.since_combat
+    }
+
+    /// Enemies the director recommends spawning now.
+    #[wasm_bindgen(getter)]
+    pub fn spawn_budget(&self) -> u32 {
+        self.budget.floor() as u32
+    }
+
+    /// How hard to push the players right now, from 0 to 1: rising with the
+    /// build-up, full at the peak and nothing while fading or relaxing, e.g.
+    /// for choosing tougher enemies or louder music.
+    #[wasm_bindgen(getter)]
+    pub fn spawn_intensity(&self) -> f32 {
+        match self.phase {
+            PacingPhase::BuildUp if self.peak_intensity > 0.0 => {
+                (self.intensity / self.peak_intensity).clamp(0.0, 1.0)
+            }
+            PacingPhase::BuildUp | PacingPhase::SustainPeak => 1.0,
+            PacingPhase::PeakFade | PacingPhase::Relax => 0.0,
+        }
+    }
+
+    /// Starts the cycle over, calm and building up.
+    pub fn reset(&mut self) {
+        self.intensity = 0.0;
+        self.phase = PacingPhase::BuildUp;
+        self.phase_time = 0.0;
+        self.since_combat = f32::INFINITY;
+        self.held = 0;
+        self.budget = 0.0;
+        self.events.clear();
+    }
+
+    /// `PacingChanged` and `SpawnBudget` events of the last `update`.
+    pub fn events(&self) -> Vec<AiEvent> {
+        self.events.clone()
+    }
+}
+
+impl Director {
+    pub(crate) fn recent_events(&self) -> &[AiEvent] {
+        &self.events
+    }
+}
@@ -0,0 +1,327 @@
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+use wasm_bindgen::prelude::*;
+
+use crate::grid::{octile, Grid, Traversal};
+
+type Key = (f32, f32);
+
+const KEY_TOLERANCE: f32 = 1e-4;
+
+#[derive(Clone, Copy)]
+struct QueueEntry {
+    key: Key,
+    index: usize,
+}
+
+impl PartialEq for QueueEntry {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for QueueEntry {}
+
+impl PartialOrd for QueueEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for QueueEntry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Reversed so `BinaryHeap` pops the smallest key first.
+        compare_keys(other.key, self.key)
+    }
+}
+
+fn compare_keys(a: Key, b: Key) -> Ordering {
+    a.0.total_cmp(&b.0).then(a.1.total_cmp(&b.1))
+}
+
+/// Whether `a` may still be below `b`. Primary keys that tie exactly in theory can
+/// drift apart in `f32`, so near-ties fall through to the secondary key; erring
+/// toward "less" only costs a few extra expansions.
+fn key_below(a: Key, b: Key) -> bool {
+    let tolerance = KEY_TOLERANCE * b.0.abs().max(1.0);
+    if a.0 < b.0 - tolerance {
+        true
+    } else if a.0 <= b.0 + tolerance {
+        a.1 < b.1
+    } else {
+        false
+    }
+}
+
+/// A grid path that repairs itself after `Grid::update_cell` edits using D* Lite,
+/// reusing the previous search instead of planning from scratch. The search runs
+/// backward from the goal, so moving the start with `set_start` is also cheap.
+///
+/// Only edits made through `update_cell` are seen; after `set_walkable` or terrain
+/// edits, call `replan`. Very long edit histories also fall back to a full replan.
+#[wasm_bindgen]
+pub struct Path {
+    width: u32,
+    height: u32,
+    start: usize,
+    last_start: usize,
+    goal: usize,
+    km: f32,
+    heuristic_scale: f32,
+    g: Vec<f32>,
+    rhs: Vec<f32>,
+    queued: Vec<Option<Key>>,
+    open: BinaryHeap<QueueEntry>,
+    revision: u32,
+    cells: Vec<usize>,
+    edges: Vec<(usize, f32)>,
+}
+
+#[wasm_bindgen]
+impl Path {
+    /// Plans a path on `grid` from the start cell to the end cell.
+    #[wasm_bindgen(constructor)]
+    pub fn new(grid: &Grid, start_x: u32, start_y: u32, end_x: u32, end_y: u32) -> Path {
+        let start = grid.index(start_x as i32, start_y as i32);
+        let mut path = Path {
+            width: grid.width(),
+            height: grid.height(),
+            start,
+            last_start: start,
+            goal: grid.index(end_x as i32, end_y as i32),
+            km: 0.0,
+            heuristic_scale: 1.0,
+            g: Vec::new(),
+            rhs: Vec::new(),
+            queued: Vec::new(),
+            open: BinaryHeap::new(),
+            revision: 0,
+            cells: Vec::new(),
+            edges: Vec::new(),
+        };
+        path.reinitialize(grid);
+        path
+    }
+
+    /// Brings the path up to date with every `update_cell` edit made since the last
+    /// plan. Returns whether a path currently exists.
+    pub fn repair(&mut self, grid: &Grid) -> bool {
+        if grid.width() != self.width
+            || grid.height() != self.height
+            || grid.heuristic_scale(Traversal::default()) != self.heuristic_scale
+        {
+            return self.replan(grid);
+        }
+        let Some(changes) = grid.changes_since(self.revision) else {
+            return self.replan(grid);
+        };
+
+        self.km += self.heuristic(self.last_start, self.start);
+        self.last_start = self.start;
+        for &index in changes {
+            // Toggling a cell changes its own edges and any diagonal passing its
+            // corner, all of which start within the surrounding 3x3 block.
+            let (x, y) = grid.coords(index);
+            for dy in -1..=1 {
+                for dx in -1..=1 {
+                    if grid.in_bounds(x + dx, y + dy) {
+                        self.update_vertex(grid, grid.index(x + dx, y + dy));
+                    }
+                }
+            }
+        }
+        self.revision = grid.revision();
+        self.compute_shortest_path(grid);
+        self.extract(grid);
+        self.found()
+    }
+
+    /// Discards the previous search and plans from scratch on `grid`.
+    pub fn replan(&mut self, grid: &Grid) -> bool {
+        self.width = grid.width();
+        self.height = grid.height();
+        self.reinitialize(grid);
+        self.found()
+    }
+
+    /// Moves the start cell, e.g. as the agent advances along the path. Takes effect
+    /// on the next `repair`.
+    pub fn set_start(&mut self, x: u32, y: u32) {
+        if x < self.width && y < self.height {
+            self.start = (y * self.width + x) as usize;
+        }
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn found(&self) -> bool {
+        !self.cells.is_empty()
+    }
+
+    /// Path cost from the start cell to the goal, `Infinity` if unreachable.
+    #[wasm_bindgen(getter)]
+    pub fn cost(&self) -> f32 {
+        self.g.get(self.start).copied().unwrap_or(f32::INFINITY)
+    }
+
+    /// The current path as flat `[x0, y0, x1, y1, ...]` cell coordinates, in the same
+    /// format as `Grid::find_path`.
+    pub fn waypoints(&self) -> Vec<f32> {
+        let mut waypoints = Vec::with_capacity(self.cells.len() * 2);
+        for &index in &self.cells {
+            let index = index as u32;
+            waypoints.push((index % self.width) as f32);
+            waypoints.push((index / self.width) as f32);
+        }
+        waypoints
+    }
+}
+
+impl Path {
+    fn reinitialize(&mut self, grid: &Grid) {
+        let cell_count = (self.width * self.height) as usize;
+        self.start = self.start.min(cell_count.saturating_sub(1));
+        self.last_start = self.start;
+        self.km = 0.0;
+        self.heuristic_scale = grid.heuristic_scale(Traversal::default());
+        self.g = vec![f32::INFINITY; cell_count];
+        self.rhs = vec![f32::INFINITY; cell_count];
+        self.queued = vec![None; cell_count];
+        self.open.clear();
+        self.revision = grid.revision();
+        self.cells.clear();
+        if self.goal >= cell_count {
+            return;
+        }
+
+        self.rhs[self.goal] = 0.0;
+        let key = self.calculate_key(self.goal);
+        self.push(self.goal, key);
+        self.compute_shortest_path(grid);
+        self.extract(grid);
+    }
+
+    fn heuristic(&self, a: usize, b: usize) -> f32 {
+        let (a, b, width) = (a as u32, b as u32, self.width);
+        let dx = (a % width) as i32 - (b % width) as i32;
+        let dy = (a / width) as i32 - (b / width) as i32;
+        octile(dx, dy) * self.heuristic_scale
+    }
+
+    fn calculate_key(&self, index: usize) -> Key {
+        let best = self.g[index].min(self.rhs[index]);
+        (best + self.heuristic(self.start, index) + self.km, best)
+    }
+
+    fn push(&mut self, index: usize, key: Key) {
+        self.queued[index] = Some(key);
+        self.open.push(QueueEntry { key, index });
+    }
+
+    /// Smallest live key, discarding entries superseded by a later push or removal.
+    fn top(&mut self) -> Option<QueueEntry> {
+        while let Some(&entry) = self.open.peek() {
+            if self.queued[entry.index] == Some(entry.key) {
+                return Some(entry);
+            }
+            self.open.pop();
+        }
+        None
+    }
+
+    fn update_vertex(&mut self, grid: &Grid, index: usize) {
+        if index != self.goal {
+            let mut best = f32::INFINITY;
+            let (x, y) = grid.coords(index);
+            if grid.is_passable(x, y, Traversal::default()) {
+                self.edges.clear();
+                grid.neighbors(index, &mut self.edges);
+                for &(next, cost) in &self.edges {
+                    best = best.min(cost + self.g[next]);
+                }
+            }
+            self.rhs[index] = best;
+        }
+
+        if self.g[index] != self.rhs[index] {
+            let key = self.calculate_key(index);
+            self.push(index, key);
+        } else {
+            self.queued[index] = None;
+        }
+    }
+
+    /// Calls `update_vertex` on every in-bounds cell adjacent to `index`, a superset
+    /// of its predecessors.
+    fn update_adjacent(&mut self, grid: &Grid, index: usize) {
+        let (x, y) = grid.coords(index);
+        for dy in -1..=1 {
+            for dx in -1..=1 {
+                if (dx, dy) != (0, 0) && grid.in_bounds(x + dx, y + dy) {
+                    self.update_vertex(grid, grid.index(x + dx, y + dy));
+                }
+            }
+        }
+    }
+
+    fn compute_shortest_path(&mut self, grid: &Grid) {
+        while let Some(QueueEntry { key, index }) = self.top() {
+            let start_key = self.calculate_key(self.start);
+            if !key_below(key, start_key) && self.rhs[self.start] == self.g[self.start] {
+                break;
+            }
+
+            let fresh = self.calculate_key(index);
+            if compare_keys(key, fresh) == Ordering::Less {
+                self.push(index, fresh);
+            } else if self.g[index] > self.rhs[index] {
+                self.g[index] = self.rhs[index];
+                self.queued[index] = None;
+                self.update_adjacent(grid, index);
+            } else {
+                self.g[index] = f32::INFINITY;
+                self.update_vertex(grid, index);
+                self.update_adjacent(grid, index);
+            }
+        }
+    }
+
+    /// Follows the cheapest successors from the start to the goal.
+    fn extract(&mut self, grid: &Grid) {
+        self.cells.clear();
+        let (sx, sy) = grid.coords(self.start);
+        let (ex, ey) = grid.coords(self.goal);
+        if !grid.is_passable(sx, sy, Traversal::default())
+            || !grid.is_passable(ex, ey, Traversal::default())
+            || !self.cost().is_finite()
+        {
+            return;
+        }
+
+        let mut current = self.start;
+        self.cells.push(current);
+        while current != self.goal {
+            if self.cells.len() > self.g.len() {
+                self.cells.clear();
+                return;
+            }
+            self.edges.clear();
+            grid.neighbors(current, &mut self.edges);
+            let best = self
+                .edges
+                .iter()
+                .map(|&(next, cost)| (next, cost + self.g[next]))
+                .min_by(|a, b| a.1.total_cmp(&b.1));
+            match best {
+                Some((next, cost)) if cost.is_finite() => {
+                    current = next;
+                    self.cells.push(current);
+                }
+                _ => {
+                    self.cells.clear();
+                    return;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,24 @@
+use std::fmt;
+
+use wasm_bindgen::prelude::*;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    InvalidInput(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::InvalidInput(message) => write!(f, "invalid input: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<Error> for JsValue {
+    fn from(error: Error) -> JsValue {
+        JsError::new(&error.to_string()).into()
+    }
+}
@@ -0,0 +1,214 @@
+use js_sys::Function;
+use wasm_bindgen::prelude::*;
+
+use crate::crowd::Crowd;
+use crate::director::Director;
+use crate::error::Error;
+use crate::perception::Perception;
+use crate::queues::Queues;
+use crate::state_machine::StateMachine;
+use crate::timers::Timers;
+
+/// What happened in an `AiEvent`.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EventKind {
+    /// A crowd agent reached the end of its path; `subject` is the agent.
+    PathComplete = 0,
+    /// No path leads to a crowd agent's move target; `subject` is the agent.
+    PathFailed = 1,
+    /// An observer saw a target it did not see before; `subject` is the
+    /// observer and `other` the target.
+    TargetSpotted = 2,
+    /// An observer stopped seeing a target.
+    TargetLost = 3,
+    /// A state machine entered state number `other`, counting states in the
+    /// order they were added.
+    StateChanged = 4,
+    /// An event raised by the game through `EventBus::emit`.
+    Custom = 5,
+    /// A crowd agent made no headway along its path for `Crowd::stuck_time`;
+    /// `subject` is the agent and `other` counts the times in a row.
+    Stuck = 6,
+    /// A `Director` entered pacing phase number `other`.
+    PacingChanged = 7,
+    /// A `Director`'s spawn budget grew to `other` enemies.
+    SpawnBudget = 8,
+    /// An action scheduled on `Timers` came due; `subject` is the agent and
+    /// `other` the action.
+    ActionDue = 9,
+    /// An agent's place in a `Queues` line changed to `other`, 0 at the
+    /// front; `subject` is the agent.
+    QueuePosition = 10,
+}
+
+impl EventKind {
+    const NAMES: [(&'static str, EventKind); 11] = [
+        ("path_complete", EventKind::PathComplete),
+        ("path_failed", EventKind::PathFailed),
+        ("target_spotted", EventKind::TargetSpotted),
+        ("target_lost", EventKind::TargetLost),
+        ("state_changed", EventKind::StateChanged),
+        ("custom", EventKind::Custom),
+        ("stuck", EventKind::Stuck),
+        ("pacing_changed", EventKind::PacingChanged),
+        ("spawn_budget", EventKind::SpawnBudget),
+        ("action_due", EventKind::ActionDue),
+        ("queue_position", EventKind::QueuePosition),
+    ];
+
+    fn from_name(name: &str) -> Option<EventKind> {
+        EventKind::NAMES
+            .iter()
+            .find(|(known, _)| *known == name)
+            .map(|&(_, kind)| kind)
+    }
+}
+
+/// A milestone reached by an AI component.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AiEvent {
+    pub kind: EventKind,
+    pub subject: u32,
+    pub other: u32,
+}
+
+impl AiEvent {
+    pub(crate) fn new(kind: EventKind, subject: u32, other: u32) -> AiEvent {
+        AiEvent {
+            kind,
+            subject,
+            other,
+        }
+    }
+}
+
+pub type Listener = Box<dyn FnMut(AiEvent)>;
+
+struct Subscription {
+    id: u32,
+    kind: Option<EventKind>,
+    listener: Listener,
+}
+
+/// Gathers the events of crowds, perception and state machines and hands them
+/// to subscribers, so gameplay code reacts to milestones instead of polling
+/// every agent. Publish each component after its update, then `dispatch` once
+/// a frame.
+#[wasm_bindgen]
+#[derive(Default)]
+pub struct EventBus {
+    subscriptions: Vec<Subscription>,
+    next_id: u32,
+    pending: Vec<AiEvent>,
+}
+
+#[wasm_bindgen]
+impl EventBus {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> EventBus {
+        EventBus::default()
+    }
+
+    /// Calls `callback` with every dispatched event called `name`, one of
+    /// `path_complete`, `path_failed`, `target_spotted`, `target_lost`,
+    /// `state_changed`, `custom`, `stuck`, `pacing_changed`, `spawn_budget`,
+    /// `action_due` and `queue_position`, or `*` for all of them. Returns an
+    /// id for `off`.
+    pub fn on(&mut self, name: &str, callback: Function) -> Result<u32, Error> {
+        let kind = match name {
+            "*" => None,
+            _ => Some(
+                EventKind::from_name(name)
+                    .ok_or_else(|| Error::InvalidInput(format!("unknown event '{}'", name)))?,
+            ),
+        };
+        Ok(self.on_with(
+            kind,
+            Box::new(move |event| {
+                let _ = callback.call1(&JsValue::NULL, &JsValue::from(event));
+            }),
+        ))
+    }
+
+    /// Ends a subscription.
+    pub fn off(&mut self, id: u32) -> bool {
+        let count = self.subscriptions.len();
+        self.subscriptions
+            .retain(|subscription| subscription.id != id);
+        self.subscriptions.len() < count
+    }
+
+    /// Queues an event of the game's own.
+    pub fn emit(&mut self, kind: EventKind, subject: u32, other: u32) {
+        self.pending.push(AiEvent::new(kind, subject, other));
+    }
+
+    /// Queues the events of the crowd's last update.
+    pub fn publish_crowd(&mut self, crowd: &Crowd) {
+        self.pending.extend_from_slice(crowd.recent_events());
+    }
+
+    /// Queues the events of the perception's last update.
+    pub fn publish_perception(&mut self, perception: &Perception) {
+        self.pending.extend_from_slice(perception.recent_events());
+    }
+
+    /// Queues the pacing events of the director's last update.
+    pub fn publish_director(&mut self, director: &Director) {
+        self.pending.extend_from_slice(director.recent_events());
+    }
+
+    /// Queues the place changes of the queues' last update.
+    pub fn publish_queues(&mut self, queues: &Queues) {
+        self.pending.extend_from_slice(queues.recent_events());
+    }
+
+    /// Queues the actions that came due in the timers' last update.
+    pub fn publish_timers(&mut self, timers: &Timers) {
+        self.pending.extend_from_slice(timers.recent_events());
+    }
+
+    /// Queues the state changes of the machine's last update, with `subject`,
+    /// e.g. the id of the agent it drives, as their subject.
+    pub fn publish_state_machine(&mut self, machine: &StateMachine, subject: u32) {
+        self.pending.extend(
+            machine
+                .recent_events()
+                .iter()
+                .map(|event| AiEvent { subject, ..*event }),
+        );
+    }
+
+    /// Hands every queued event to its subscribers, in the order queued, and
+    /// returns them for polling.
+    pub fn dispatch(&mut self) -> Vec<AiEvent> {
+        let events = std::mem::take(&mut self.pending);
+        for event in &events {
+            for subscription in &mut self.subscriptions {
+                if subscription.kind.is_none_or(|kind| kind == event.kind) {
+                    (subscription.listener)(*event);
+                }
+            }
+        }
+        events
+    }
+
+    /// Events queued since the last `dispatch`.
+    #[wasm_bindgen(getter)]
+    pub fn pending(&self) -> u32 {
+        self.pending.len() as u32
+    }
+}
+
+impl EventBus {
+    /// Subscribes a listener written in Rust to events of `kind`, or to all
+    /// events.
+    pub fn on_with(&mut self, kind: Option<EventKind>, listener: Listener) -> u32 {
+        let id = self.next_id;
+        self.next_id = self.next_id.wrapping_add(1);
+        self.subscriptions.push(Subscription { id, kind, listener });
+        id
+    }
+}
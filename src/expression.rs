@@ -0,0 +1,948 @@
+use wasm_bindgen::prelude::*;
+
+use crate::blackboard::{Blackboard, Value};
+use crate::bytes::{Reader, Writer};
+use crate::error::Error;
+
+/// Deepest nesting the parser accepts, so a hostile document cannot overflow
+/// the stack.
+const MAX_DEPTH: u32 = 64;
+
+/// Where an expression reads its inputs from.
+pub(crate) trait Inputs {
+    /// A number, with booleans as 0 or 1.
+    fn number(&self, key: &str) -> Option<f64>;
+    /// Component `axis` of a vector.
+    fn component(&self, key: &str, axis: usize) -> Option<f64>;
+    fn text(&self, key: &str) -> Option<&str>;
+    fn has(&self, key: &str) -> bool;
+}
+
+impl Inputs for Blackboard {
+    fn number(&self, key: &str) -> Option<f64> {
+        self.get_number(key)
+    }
+
+    fn component(&self, key: &str, axis: usize) -> Option<f64> {
+        let value = match (self.get(key)?, axis) {
+            (Value::Vec2(v), 0) => v.x,
+            (Value::Vec2(v), 1) => v.y,
+            (Value::Vec3(v), 0) => v.x,
+            (Value::Vec3(v), 1) => v.y,
+            (Value::Vec3(v), 2) => v.z,
+            _ => return None,
+        };
+        Some(value as f64)
+    }
+
+    fn text(&self, key: &str) -> Option<&str> {
+        match self.get(key)? {
+            Value::Str(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    fn has(&self, key: &str) -> bool {
+        Blackboard::has(self, key)
+    }
+}
+
+/// Inputs for an expression evaluated without a blackboard: every key reads
+/// as missing.
+pub(crate) struct NoInputs;
+
+impl Inputs for NoInputs {
+    fn number(&self, _: &str) -> Option<f64> {
+        None
+    }
+
+    fn component(&self, _: &str, _: usize) -> Option<f64> {
+        None
+    }
+
+    fn text(&self, _: &str) -> Option<&str> {
+        None
+    }
+
+    fn has(&self, _: &str) -> bool {
+        false
+    }
+}
+
+/// Numeric inputs read through a function, as `UtilityBrain::evaluate_with`
+/// takes them.
+pub(crate) struct NumberInputs<'a>(pub &'a dyn Fn(&str) -> Option<f32>);
+
+impl Inputs for NumberInputs<'_> {
+    fn number(&self, key: &str) -> Option<f64> {
+        (self.0)(key).map(f64::from)
+    }
+
+    fn component(&self, _: &str, _: usize) -> Option<f64> {
+        None
+    }
+
+    fn text(&self, _: &str) -> Option<&str> {
+        None
+    }
+
+    fn has(&self, key: &str) -> bool {
+        (self.0)(key).is_some()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Builtin {
+    Min,
+    Max,
+    Abs,
+    Clamp,
+    Sqrt,
+    Floor,
+    Ceil,
+    Round,
+    Lerp,
+}
+
+impl Builtin {
+    const NAMES: [(&'static str, Builtin, usize, usize); 9] = [
+        ("min", Builtin::Min, 1, usize::MAX),
+        ("max", Builtin::Max, 1, usize::MAX),
+        ("abs", Builtin::Abs, 1, 1),
+        ("clamp", Builtin::Clamp, 3, 3),
+        ("sqrt", Builtin::Sqrt, 1, 1),
+        ("floor", Builtin::Floor, 1, 1),
+        ("ceil", Builtin::Ceil, 1, 1),
+        ("round", Builtin::Round, 1, 1),
+        ("lerp", Builtin::Lerp, 3, 3),
+    ];
+
+    fn apply(self, args: &[f64]) -> f64 {
+        match self {
+            Builtin::Min => args.iter().copied().fold(f64::INFINITY, f64::min),
+            Builtin::Max => args.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+            Builtin::Abs => args[0].abs(),
+            Builtin::Clamp => args[0].max(args[1]).min(args[2]),
+            Builtin::Sqrt => args[0].sqrt(),
+            Builtin::Floor => args[0].floor(),
+            Builtin::Ceil => args[0].ceil(),
+            Builtin::Round => args[0].round(),
+            Builtin::Lerp => args[0] + (args[1] - args[0]) * args[2],
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Binary {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Eq,
+    Ne,
+}
+
+impl Binary {
+    fn apply(self, a: f64, b: f64) -> f64 {
+        let flag = |value: bool| if value { 1.0 } else { 0.0 };
+        match self {
+            Binary::Add => a + b,
+            Binary::Sub => a - b,
+            Binary::Mul => a * b,
+            Binary::Div => a / b,
+            Binary::Rem => a % b,
+            Binary::Lt => flag(a < b),
+            Binary::Le => flag(a <= b),
+            Binary::Gt => flag(a > b),
+            Binary::Ge => flag(a >= b),
+            Binary::Eq => flag(a == b),
+            Binary::Ne => flag(a != b),
+        }
+    }
+}
+
+/// One instruction of a compiled expression, run on a stack of numbers.
+#[derive(Clone, Debug, PartialEq)]
+enum Op {
+    Const(f64),
+    /// Pushes blackboard key number `n`, 0 when missing.
+    Load(usize),
+    /// Pushes a component of a vector key.
+    Component(usize, usize),
+    Has(usize),
+    /// Pushes whether text key `n` equals text constant `m`.
+    TextEq(usize, usize),
+    Time,
+    Neg,
+    Not,
+    /// Turns the top into 0 or 1.
+    Truth,
+    Binary(Binary),
+    Call(Builtin, usize),
+    Distance(usize, usize),
+    /// Pops a duration and pushes whether timer `n` let it through.
+    Cooldown(usize),
+    /// Pops a duration and a condition and pushes whether the condition has
+    /// held that long, by timer `n`.
+    Held(usize),
+    /// Pops and jumps when the value is false.
+    JumpUnless(usize),
+    Jump(usize),
+    /// Leaves 0 and jumps when the top is false, else pops it: `&&`.
+    AndThen(usize),
+    /// Leaves 1 and jumps when the top is true, else pops it: `||`.
+    OrElse(usize),
+}
+
+/// A parsed expression. Operator chains such as `a + b - c` are kept flat,
+/// so that long ones do not nest, and only brackets, calls and unary
+/// operators, which `MAX_DEPTH` limits, make the tree deeper.
+#[derive(Clone, Debug)]
+enum Node {
+    Number(f64),
+    Key(String),
+    Component(String, usize),
+    Text(String),
+    Time,
+    Neg(Box<Node>),
+    Not(Box<Node>),
+    /// The first operand, then each operator with the operand to its right,
+    /// applied left to right.
+    Binary(Box<Node>, Vec<(Binary, Node)>),
+    And(Vec<Node>),
+    Or(Vec<Node>),
+    Choose(Box<Node>, Box<Node>, Box<Node>),
+    Call(String, Vec<Node>),
+}
+
+struct Parser<'a> {
+    bytes: &'a [u8],
+    at: usize,
+    depth: u32,
+}
+
+impl Parser<'_> {
+    fn error(&self, message: &str) -> Error {
+        Error::InvalidInput(format!(
+            "invalid expression at byte {}: {}",
+            self.at, message
+        ))
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self
+            .bytes
+            .get(self.at)
+            .is_some_and(|byte| byte.is_ascii_whitespace())
+        {
+            self.at += 1;
+        }
+    }
+
+    /// Consumes `token` when it comes next.
+    fn eat(&mut self, token: &str) -> bool {
+        self.skip_whitespace();
+        let matches = self.bytes[self.at..].starts_with(token.as_bytes());
+        // A word must not run on into an identifier, as `or` in `order`.
+        let word = token.bytes().all(|byte| byte.is_ascii_alphabetic());
+        if matches
+            && !(word
+                && self
+                    .bytes
+                    .get(self.at + token.len())
+                    .is_some_and(|&byte| byte.is_ascii_alphanumeric() || byte == b'_'))
+        {
+            self.at += token.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, token: &str) -> Result<(), Error> {
+        if self.eat(token) {
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected '{}'", token)))
+        }
+    }
+
+    fn nested(&mut self) -> Result<(), Error> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            return Err(self.error("nested too deeply"));
+        }
+        Ok(())
+    }
+
+    fn expression(&mut self) -> Result<Node, Error> {
+        self.nested()?;
+        let condition = self.or()?;
+        let node = if self.eat("?") {
+            let then = self.expression()?;
+            self.expect(":")?;
+            let otherwise = self.expression()?;
+            Node::Choose(Box::new(condition), Box::new(then), Box::new(otherwise))
+        } else {
+            condition
+        };
+        self.depth -= 1;
+        Ok(node)
+    }
+
+    fn or(&mut self) -> Result<Node, Error> {
+        let mut operands = vec![self.and()?];
+        while self.eat("||") || self.eat("or") {
+            operands.push(self.and()?);
+        }
+        Ok(match operands.len() {
+            1 => operands.pop().unwrap(),
+            _ => Node::Or(operands),
+        })
+    }
+
+    fn and(&mut self) -> Result<Node, Error> {
+        let mut operands = vec![self.equality()?];
+        while self.eat("&&") || self.eat("and") {
+            operands.push(self.equality()?);
+        }
+        Ok(match operands.len() {
+            1 => operands.pop().unwrap(),
+            _ => Node::And(operands),
+        })
+    }
+
+    fn equality(&mut self) -> Result<Node, Error> {
+        let first = self.comparison()?;
+        let mut rest = Vec::new();
+        loop {
+            let op = if self.eat("==") {
+                Binary::Eq
+            } else if self.eat("!=") {
+                Binary::Ne
+            } else {
+                return Ok(chain(first, rest));
+            };
+            rest.push((op, self.comparison()?));
+        }
+    }
+
+    fn comparison(&mut self) -> Result<Node, Error> {
+        let first = self.sum()?;
+        let mut rest = Vec::new();
+        loop {
+            let op = if self.eat("<=") {
+                Binary::Le
+            } else if self.eat(">=") {
+                Binary::Ge
+            } else if self.eat("<") {
+                Binary::Lt
+            } else if self.eat(">") {
+                Binary::Gt
+            } else {
+                return Ok(chain(first, rest));
+            };
+            rest.push((op, self.sum()?));
+        }
+    }
+
+    fn sum(&mut self) -> Result<Node, Error> {
+        let first = self.product()?;
+        let mut rest = Vec::new();
+        loop {
+            let op = if self.eat("+") {
+                Binary::Add
+            } else if self.eat("-") {
+                Binary::Sub
+            } else {
+                return Ok(chain(first, rest));
+            };
+            rest.push((op, self.product()?));
+        }
+    }
+
+    fn product(&mut self) -> Result<Node, Error> {
+        let first = self.unary()?;
+        let mut rest = Vec::new();
+        loop {
+            let op = if self.eat("*") {
+                Binary::Mul
+            } else if self.eat("/") {
+                Binary::Div
+            } else if self.eat("%") {
+                Binary::Rem
+            } else {
+                return Ok(chain(first, rest));
+            };
+            rest.push((op, self.unary()?));
+        }
+    }
+
+    fn unary(&mut self) -> Result<Node, Error> {
+        self.nested()?;
+        let node = if self.eat("-") {
+            Node::Neg(Box::new(self.unary()?))
+        } else if self.eat("!") || self.eat("not") {
+            Node::Not(Box::new(self.unary()?))
+        } else {
+            self.primary()?
+        };
+        self.depth -= 1;
+        Ok(node)
+    }
+
+    fn primary(&mut self) -> Result<Node, Error> {
+        self.skip_whitespace();
+        if self.eat("(") {
+            let node = self.expression()?;
+            self.expect(")")?;
+            return Ok(node);
+        }
+        match self.bytes.get(self.at) {
+            Some(byte) if byte.is_ascii_digit() || *byte == b'.' => self.number(),
+            Some(b'"') | Some(b'\'') => self.text(),
+            Some(byte) if byte.is_ascii_alphabetic() || *byte == b'_' => {
+                let name = self.identifier();
+                match name.as_str() {
+                    "true" => return Ok(Node::Number(1.0)),
+                    "false" => return Ok(Node::Number(0.0)),
+                    "time" => return Ok(Node::Time),
+                    _ => {}
+                }
+                if self.eat("(") {
+                    let mut args = Vec::new();
+                    if !self.eat(")") {
+                        loop {
+                            args.push(self.expression()?);
+                            if self.eat(")") {
+                                break;
+                            }
+                            self.expect(",")?;
+                        }
+                    }
+                    return Ok(Node::Call(name, args));
+                }
+                if self.eat(".") {
+                    let axis = match self.identifier().as_str() {
+                        "x" => 0,
+                        "y" => 1,
+                        "z" => 2,
+                        _ => return Err(self.error("vectors have x, y and z")),
+                    };
+                    return Ok(Node::Component(name, axis));
+                }
+                Ok(Node::Key(name))
+            }
+            Some(_) => Err(self.error("unexpected character")),
+            None => Err(self.error("unexpected end")),
+        }
+    }
+
+    fn identifier(&mut self) -> String {
+        let start = self.at;
+        while self
+            .bytes
+            .get(self.at)
+            .is_some_and(|&byte| byte.is_ascii_alphanumeric() || byte == b'_')
+        {
+            self.at += 1;
+        }
+        String::from_utf8_lossy(&self.bytes[start..self.at]).into_owned()
+    }
+
+    fn number(&mut self) -> Result<Node, Error> {
+        let start = self.at;
+        while self
+            .bytes
+            .get(self.at)
+            .is_some_and(|&byte| byte.is_ascii_digit() || byte == b'.')
+        {
+            self.at += 1;
+        }
+        std::str::from_utf8(&self.bytes[start..self.at])
+            .ok()
+            .and_then(|text| text.parse().ok())
+            .map(Node::Number)
+            .ok_or_else(|| self.error("malformed number"))
+    }
+
+    fn text(&mut self) -> Result<Node, Error> {
+        let quote = self.bytes[self.at];
+        self.at += 1;
+        let start = self.at;
+        while self.bytes.get(self.at).is_some_and(|&byte| byte != quote) {
+            self.at += 1;
+        }
+        if self.at >= self.bytes.len() {
+            return Err(self.error("unterminated string"));
+        }
+        let text = String::from_utf8_lossy(&self.bytes[start..self.at]).into_owned();
+        self.at += 1;
+        Ok(Node::Text(text))
+    }
+}
+
+/// A condition or score written as text, such as
+/// `health < 0.3 && enemies_near > 2 && cooldown(5)`, compiled once to
+/// bytecode and evaluated against a blackboard without calling back into JS.
+///
+/// Bare names read blackboard keys: numbers as they are, booleans as 1 or 0,
+/// missing keys as 0, and `name.x`, `name.y` and `name.z` the components of
+/// vectors. `name == "text"` compares a text key. There are the arithmetic
+/// operators, comparisons, `&&` (or `and`), `||` (or `or`), `!` (or `not`),
+/// `cond ? a : b` and parentheses; `true`, `false` and `time`, the seconds
+/// the expression's clock has run, are reserved. Functions are `min`, `max`,
+/// `abs`, `clamp`, `sqrt`, `floor`, `ceil`, `round`, `lerp`, `has(key)`,
+/// `distance(a, b)` between vector keys, `cooldown(seconds)`, true at most
+/// once every `seconds`, and `held(cond, seconds)`, true once `cond` has
+/// been true for `seconds` on end.
+#[wasm_bindgen]
+#[derive(Clone, Debug)]
+pub struct Expression {
+    source: String,
+    ops: Vec<Op>,
+    keys: Vec<String>,
+    texts: Vec<String>,
+    /// When each timer last let a cooldown through, or since when its
+    /// condition has held.
+    timers: Vec<Option<f64>>,
+    clock: f64,
+    stack: Vec<f64>,
+}
+
+#[wasm_bindgen]
+impl Expression {
+    /// Parses and compiles `source`.
+    pub fn compile(source: &str) -> Result<Expression, Error> {
+        let mut parser = Parser {
+            bytes: source.as_bytes(),
+            at: 0,
+            depth: 0,
+        };
+        let node = parser.expression()?;
+        parser.skip_whitespace();
+        if parser.at < parser.bytes.len() {
+            return Err(parser.error("trailing characters"));
+        }
+        let mut expression = Expression {
+            source: source.to_string(),
+            ops: Vec::new(),
+            keys: Vec::new(),
+            texts: Vec::new(),
+            timers: Vec::new(),
+            clock: 0.0,
+            stack: Vec::new(),
+        };
+        expression.emit(&node)?;
+        Ok(expression)
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn source(&self) -> String {
+        self.source.clone()
+    }
+
+    /// Advances the expression's clock by `dt` seconds and evaluates it
+    /// against `blackboard`.
+    pub fn evaluate(&mut self, blackboard: &Blackboard, dt: f32) -> f64 {
+        self.clock += dt.max(0.0) as f64;
+        self.run(blackboard, self.clock)
+    }
+
+    /// Like `evaluate`, as a condition: whether the result is nonzero.
+    pub fn test(&mut self, blackboard: &Blackboard, dt: f32) -> bool {
+        self.evaluate(blackboard, dt) != 0.0
+    }
+
+    /// Restarts the clock and every cooldown and `held` timer.
+    pub fn reset(&mut self) {
+        self.clock = 0.0;
+        self.timers.fill(None);
+    }
+}
+
+impl Expression {
+    /// When each cooldown last fired and since when each `held` condition
+    /// has held, for `BehaviorTree::save_state`.
+    pub(crate) fn write_timers(&self, out: &mut Writer) {
+        out.u32(self.timers.len() as u32);
+        for timer in &self.timers {
+            out.u32(timer.is_some() as u32);
+            out.f64(timer.unwrap_or(0.0));
+        }
+    }
+
+    /// What `write_timers` wrote for an expression compiled from the same
+    /// source, for `set_timers`.
+    pub(crate) fn read_timers(&self, input: &mut Reader) -> Result<Vec<Option<f64>>, Error> {
+        if input.u32()? as usize != self.timers.len() {
+            return Err(input.invalid());
+        }
+        (0..self.timers.len())
+            .map(|_| {
+                let set = input.u32()? != 0;
+                let at = input.f64()?;
+                Ok(set.then_some(at))
+            })
+            .collect()
+    }
+
+    pub(crate) fn set_timers(&mut self, timers: Vec<Option<f64>>) {
+        self.timers = timers;
+    }
+
+    /// Evaluates against `inputs` with `now` seconds on the caller's clock,
+    /// which its timers measure against.
+    pub(crate) fn run(&mut self, inputs: &dyn Inputs, now: f64) -> f64 {
+        let mut stack = std::mem::take(&mut self.stack);
+        stack.clear();
+        let mut at = 0;
+        while let Some(op) = self.ops.get(at) {
+            at += 1;
+            match *op {
+                Op::Const(value) => stack.push(value),
+                Op::Load(key) => stack.push(inputs.number(&self.keys[key]).unwrap_or(0.0)),
+                Op::Component(key, axis) => {
+                    stack.push(inputs.component(&self.keys[key], axis).unwrap_or(0.0))
+                }
+                Op::Has(key) => stack.push(truth(inputs.has(&self.keys[key]))),
+                Op::TextEq(key, text) => stack.push(truth(
+                    inputs.text(&self.keys[key]) == Some(self.texts[text].as_str()),
+                )),
+                Op::Time => stack.push(now),
+                Op::Neg => {
+                    let value = pop(&mut stack);
+                    stack.push(-value);
+                }
+                Op::Not => {
+                    let value = pop(&mut stack);
+                    stack.push(truth(value == 0.0));
+                }
+                Op::Truth => {
+                    let value = pop(&mut stack);
+                    stack.push(truth(value != 0.0));
+                }
+                Op::Binary(op) => {
+                    let b = pop(&mut stack);
+                    let a = pop(&mut stack);
+                    stack.push(op.apply(a, b));
+                }
+                Op::Call(builtin, count) => {
+                    let start = stack.len() - count;
+                    let value = builtin.apply(&stack[start..]);
+                    stack.truncate(start);
+                    stack.push(value);
+                }
+                Op::Distance(a, b) => {
+                    let axis = |key: usize, axis: usize| {
+                        inputs.component(&self.keys[key], axis).unwrap_or(0.0)
+                    };
+                    let squared: f64 = (0..3).map(|i| (axis(a, i) - axis(b, i)).powi(2)).sum();
+                    stack.push(squared.sqrt());
+                }
+                Op::Cooldown(timer) => {
+                    let seconds = pop(&mut stack);
+                    let ready = self.timers[timer].is_none_or(|last| now - last >= seconds);
+                    if ready {
+                        self.timers[timer] = Some(now);
+                    }
+                    stack.push(truth(ready));
+                }
+                Op::Held(timer) => {
+                    let seconds = pop(&mut stack);
+                    let condition = pop(&mut stack) != 0.0;
+                    let held = if condition {
+                        let since = *self.timers[timer].get_or_insert(now);
+                        now - since >= seconds
+                    } else {
+                        self.timers[timer] = None;
+                        false
+                    };
+                    stack.push(truth(held));
+                }
+                Op::JumpUnless(target) => {
+                    if pop(&mut stack) == 0.0 {
+                        at = target;
+                    }
+                }
+                Op::Jump(target) => at = target,
+                Op::AndThen(target) => {
+                    if stack.last() == Some(&0.0) {
+                        at = target;
+                    } else {
+                        stack.pop();
+                    }
+                }
+                Op::OrElse(target) => {
+                    if stack.last().is_some_and(|&value| value != 0.0) {
+                        *stack.last_mut().unwrap() = 1.0;
+                        at = target;
+                    } else {
+                        stack.pop();
+                    }
+                }
+            }
+        }
+        let value = stack.pop().unwrap_or(0.0);
+        self.stack = stack;
+        value
+    }
+
+    fn key(&mut self, name: &str) -> usize {
+        match self.keys.iter().position(|key| key == name) {
+            Some(index) => index,
+            None => {
+                self.keys.push(name.to_string());
+                self.keys.len() - 1
+            }
+        }
+    }
+
+    fn timer(&mut self) -> usize {
+        self.timers.push(None);
+        self.timers.len() - 1
+    }
+
+    /// Index of the next instruction, for jumps.
+    fn here(&self) -> usize {
+        self.ops.len()
+    }
+
+    /// Points the jump at `op` to the next instruction.
+    fn patch(&mut self, op: usize) {
+        let target = self.here();
+        match &mut self.ops[op] {
+            Op::JumpUnless(at) | Op::Jump(at) | Op::AndThen(at) | Op::OrElse(at) => *at = target,
+            _ => unreachable!("only jumps are patched"),
+        }
+    }
+
+    fn emit(&mut self, node: &Node) -> Result<(), Error> {
+        match node {
+            Node::Number(value) => self.ops.push(Op::Const(*value)),
+            Node::Key(name) => {
+                let key = self.key(name);
+                self.ops.push(Op::Load(key));
+            }
+            Node::Component(name, axis) => {
+                let key = self.key(name);
+                self.ops.push(Op::Component(key, *axis));
+            }
+            Node::Text(_) => {
+                return Err(Error::InvalidInput(format!(
+                    "'{}': text can only be compared to a key with == or !=",
+                    self.source
+                )))
+            }
+            Node::Time => self.ops.push(Op::Time),
+            Node::Neg(inner) => {
+                self.emit(inner)?;
+                self.ops.push(Op::Neg);
+            }
+            Node::Not(inner) => {
+                self.emit(inner)?;
+                self.ops.push(Op::Not);
+            }
+            Node::Binary(first, rest) => {
+                let mut rest = rest.as_slice();
+                let text = match rest {
+                    [(op @ (Binary::Eq | Binary::Ne), second), ..] => {
+                        self.text_comparison(first, second)?.map(|text| (*op, text))
+                    }
+                    _ => None,
+                };
+                match text {
+                    Some((op, (key, text))) => {
+                        let key = self.key(key);
+                        self.texts.push(text.to_string());
+                        self.ops.push(Op::TextEq(key, self.texts.len() - 1));
+                        if op == Binary::Ne {
+                            self.ops.push(Op::Not);
+                        }
+                        rest = &rest[1..];
+                    }
+                    None => self.emit(first)?,
+                }
+                for (op, operand) in rest {
+                    if matches!(op, Binary::Eq | Binary::Ne) && matches!(operand, Node::Text(_)) {
+                        return Err(self.text_comparison_error());
+                    }
+                    self.emit(operand)?;
+                    self.ops.push(Op::Binary(*op));
+                }
+            }
+            Node::And(operands) | Node::Or(operands) => {
+                self.emit(&operands[0])?;
+                for operand in &operands[1..] {
+                    let jump = self.here();
+                    self.ops.push(match node {
+                        Node::And(_) => Op::AndThen(0),
+                        _ => Op::OrElse(0),
+                    });
+                    self.emit(operand)?;
+                    self.ops.push(Op::Truth);
+                    self.patch(jump);
+                }
+            }
+            Node::Choose(condition, then, otherwise) => {
+                self.emit(condition)?;
+                let skip_then = self.here();
+                self.ops.push(Op::JumpUnless(0));
+                self.emit(then)?;
+                let skip_otherwise = self.here();
+                self.ops.push(Op::Jump(0));
+                self.patch(skip_then);
+                self.emit(otherwise)?;
+                self.patch(skip_otherwise);
+            }
+            Node::Call(name, args) => self.emit_call(name, args)?,
+        }
+        Ok(())
+    }
+
+    /// The key and text of `a == b` when one side is text, which the other
+    /// must then be a key for.
+    fn text_comparison<'n>(
+        &self,
+        a: &'n Node,
+        b: &'n Node,
+    ) -> Result<Option<(&'n str, &'n str)>, Error> {
+        match (a, b) {
+            (Node::Key(key), Node::Text(text)) | (Node::Text(text), Node::Key(key)) => {
+                Ok(Some((key, text)))
+            }
+            (Node::Text(_), _) | (_, Node::Text(_)) => Err(self.text_comparison_error()),
+            _ => Ok(None),
+        }
+    }
+
+    fn text_comparison_error(&self) -> Error {
+        Error::InvalidInput(format!(
+            "'{}': text can only be compared to a key",
+            self.source
+        ))
+    }
+
+    fn emit_call(&mut self, name: &str, args: &[Node]) -> Result<(), Error> {
+        let arity = |expected: usize| {
+            if args.len() == expected {
+                Ok(())
+            } else {
+                Err(Error::InvalidInput(format!(
+                    "{}() takes {} argument(s), got {}",
+                    name,
+                    expected,
+                    args.len()
+                )))
+            }
+        };
+        let key_argument = |index: usize| match &args[index] {
+            Node::Key(key) => Ok(key.clone()),
+            _ => Err(Error::InvalidInput(format!(
+                "{}() takes blackboard keys",
+                name
+            ))),
+        };
+        match name {
+            "has" => {
+                arity(1)?;
+                let key = self.key(&key_argument(0)?);
+                self.ops.push(Op::Has(key));
+            }
+            "distance" => {
+                arity(2)?;
+                let a = self.key(&key_argument(0)?);
+                let b = self.key(&key_argument(1)?);
+                self.ops.push(Op::Distance(a, b));
+            }
+            "cooldown" => {
+                arity(1)?;
+                self.emit(&args[0])?;
+                let timer = self.timer();
+                self.ops.push(Op::Cooldown(timer));
+            }
+            "held" => {
+                arity(2)?;
+                self.emit(&args[0])?;
+                self.emit(&args[1])?;
+                let timer = self.timer();
+                self.ops.push(Op::Held(timer));
+            }
+            _ => {
+                let &(_, builtin, least, most) = Builtin::NAMES
+                    .iter()
+                    .find(|(known, ..)| *known == name)
+                    .ok_or_else(|| Error::InvalidInput(format!("unknown function '{}'", name)))?;
+                if args.len() < least || args.len() > most {
+                    return Err(Error::InvalidInput(format!(
+                        "{}() does not take {} argument(s)",
+                        name,
+                        args.len()
+                    )));
+                }
+                for arg in args {
+                    self.emit(arg)?;
+                }
+                self.ops.push(Op::Call(builtin, args.len()));
+            }
+        }
+        Ok(())
+    }
+}
+
+fn truth(value: bool) -> f64 {
+    if value {
+        1.0
+    } else {
+        0.0
+    }
+}
+
+/// `first` alone, or combined with the operators and operands after it.
+fn chain(first: Node, rest: Vec<(Binary, Node)>) -> Node {
+    if rest.is_empty() {
+        first
+    } else {
+        Node::Binary(Box::new(first), rest)
+    }
+}
+
+/// Pops an operand; compiled code never underflows, but a missing value
+/// reads as 0 rather than panicking.
+fn pop(stack: &mut Vec<f64>) -> f64 {
+    stack.pop().unwrap_or(0.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn long_operator_chains_do_not_nest() {
+        let blackboard = Blackboard::new();
+        let sum = format!("1{}", " + 1".repeat(50_000));
+        assert_eq!(
+            Expression::compile(&sum)
+                .unwrap()
+                .evaluate(&blackboard, 0.0),
+            50_001.0
+        );
+        let all = format!("1 < 2{}", " && 2 > 1".repeat(50_000));
+        assert!(Expression::compile(&all).unwrap().test(&blackboard, 0.0));
+        let any = format!("1 > 2{}", " || 1 > 2".repeat(50_000));
+        assert!(!Expression::compile(&any).unwrap().test(&blackboard, 0.0));
+    }
+
+    #[test]
+    fn deep_nesting_is_an_error() {
+        let deep = format!("{}1{}", "(".repeat(100_000), ")".repeat(100_000));
+        assert!(Expression::compile(&deep).is_err());
+        assert!(Expression::compile(&"-".repeat(100_000)).is_err());
+        assert!(Expression::compile("((1))").is_ok());
+    }
+}
@@ -0,0 +1,218 @@
+#[cfg(target_arch = "wasm32")]
+use js_sys::Float32Array;
+use wasm_bindgen::prelude::*;
+
+use crate::batch::{self, Limit};
+use crate::debug::{self, DebugGeometry};
+use crate::math::Vec2;
+use crate::parallel;
+use crate::spatial_hash::SpatialHash;
+#[cfg(feature = "webgpu")]
+use crate::webgpu::GpuFlock;
+
+/// A batch of boids updated together. Positions and velocities are stored as flat
+/// `[x0, y0, x1, y1, ...]` buffers so they can be handed to JS without copying.
+#[wasm_bindgen]
+pub struct Flock {
+    positions: Vec<f32>,
+    velocities: Vec<f32>,
+    /// Flocking forces of the update being run, already truncated.
+    accelerations: Vec<[f32; 2]>,
+    neighbors: SpatialHash,
+    scratch: Vec<u32>,
+    #[cfg(feature = "webgpu")]
+    pub(crate) gpu: Option<GpuFlock>,
+    pub max_speed: f32,
+    pub max_force: f32,
+    pub neighbor_radius: f32,
+    pub separation_radius: f32,
+    pub separation_weight: f32,
+    pub alignment_weight: f32,
+    pub cohesion_weight: f32,
+}
+
+#[wasm_bindgen]
+impl Flock {
+    #[wasm_bindgen(constructor)]
+    pub fn new(max_speed: f32, max_force: f32) -> Flock {
+        Flock {
+            positions: Vec::new(),
+            velocities: Vec::new(),
+            accelerations: Vec::new(),
+            neighbors: SpatialHash::new(5.0),
+            scratch: Vec::new(),
+            #[cfg(feature = "webgpu")]
+            gpu: None,
+            max_speed,
+            max_force,
+            neighbor_radius: 5.0,
+            separation_radius: 1.5,
+            separation_weight: 1.5,
+            alignment_weight: 1.0,
+            cohesion_weight: 1.0,
+        }
+    }
+
+    /// Adds a boid and returns its index into the position and velocity buffers.
+    pub fn add_boid(&mut self, x: f32, y: f32, vx: f32, vy: f32) -> u32 {
+        self.positions.extend_from_slice(&[x, y]);
+        self.velocities.extend_from_slice(&[vx, vy]);
+        self.accelerations.push([0.0; 2]);
+        (self.accelerations.len() - 1) as u32
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn len(&self) -> u32 {
+        self.accelerations.len() as u32
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.accelerations.is_empty()
+    }
+
+    /// Applies separation, alignment, and cohesion to every boid, then integrates.
+    pub fn update(&mut self, dt: f32) {
+        let mut accelerations = std::mem::take(&mut self.accelerations);
+        #[cfg(feature = "webgpu")]
+        let on_gpu = self
+            .gpu
+            .as_ref()
+            .map(|gpu| gpu.exchange(self, &mut accelerations))
+            .is_some();
+        #[cfg(not(feature = "webgpu"))]
+        let on_gpu = false;
+        if !on_gpu {
+            self.flocking_forces(&mut accelerations);
+        }
+        self.accelerations = accelerations;
+
+        batch::integrate(
+            &mut self.positions,
+            &mut self.velocities,
+            self.accelerations.as_flattened(),
+            Limit::All(f32::INFINITY),
+            Limit::All(self.max_speed),
+            dt,
+        );
+    }
+
+    /// A view of the flat position buffer in WASM memory. The view is invalidated when
+    /// boids are added or the WASM memory grows, so re-fetch it after either.
+    #[cfg(target_arch = "wasm32")]
+    pub fn positions(&self) -> Float32Array {
+        unsafe { Float32Array::view(&self.positions) }
+    }
+
+    /// A view of the flat velocity buffer, with the same lifetime caveats as `positions`.
+    #[cfg(target_arch = "wasm32")]
+    pub fn velocities(&self) -> Float32Array {
+        unsafe { Float32Array::view(&self.velocities) }
+    }
+
+    /// Appends each boid to `out` as a point with a line for its velocity.
+    pub fn debug_geometry(&self, out: &mut DebugGeometry) {
+        for (position, velocity) in self
+            .positions
+            .chunks_exact(2)
+            .zip(self.velocities.chunks_exact(2))
+        {
+            let position = Vec2::new(position[0], position[1]);
+            out.arrow(
+                position,
+                Vec2::new(velocity[0], velocity[1]),
+                debug::VELOCITY,
+            );
+            out.point(position, debug::AGENT);
+        }
+    }
+}
+
+impl Flock {
+    pub(crate) fn position(&self, index: usize) -> Vec2 {
+        Vec2::new(self.positions[index * 2], self.positions[index * 2 + 1])
+    }
+
+    pub(crate) fn velocity(&self, index: usize) -> Vec2 {
+        Vec2::new(self.velocities[index * 2], self.velocities[index * 2 + 1])
+    }
+
+    /// Reynolds steering toward a desired direction at full speed.
+    fn steer(&self, direction: Vec2, velocity: Vec2) -> Vec2 {
+        if direction.length_squared() <= f32::EPSILON {
+            return Vec2::ZERO;
+        }
+        (direction.normalize() * self.max_speed - velocity).truncate(self.max_force)
+    }
+
+    /// Sets each boid's separation, alignment and cohesion force, truncated
+    /// to `max_force`.
+    fn flocking_forces(&mut self, accelerations: &mut [[f32; 2]]) {
+        let count = accelerations.len();
+        let separation_radius_squared = self.separation_radius * self.separation_radius;
+
+        self.neighbors.clear();
+        self.neighbors.set_cell_size(self.neighbor_radius);
+        for i in 0..count {
+            let position = self.position(i);
+            self.neighbors.insert(i as u32, position.x, position.y);
+        }
+
+        let mut scratch = std::mem::take(&mut self.scratch);
+        parallel::fill(
+            accelerations,
+            &mut scratch,
+            parallel::AGENT_BATCH,
+            |nearby, i, acceleration| {
+                let position = self.position(i);
+                let velocity = self.velocity(i);
+                let mut separation = Vec2::ZERO;
+                let mut heading = Vec2::ZERO;
+                let mut center = Vec2::ZERO;
+                let mut neighbors = 0;
+
+                nearby.clear();
+                self.neighbors.query_radius_into(
+                    position.x,
+                    position.y,
+                    self.neighbor_radius,
+                    nearby,
+                );
+                for &j in nearby.iter() {
+                    let j = j as usize;
+                    if i == j {
+                        continue;
+                    }
+                    let offset = position - self.position(j);
+                    let distance_squared = offset.length_squared();
+                    if distance_squared < separation_radius_squared && distance_squared > 0.0 {
+                        separation += offset / distance_squared;
+                    }
+                    heading += self.velocity(j);
+                    center += self.position(j);
+                    neighbors += 1;
+                }
+
+                let mut force = Vec2::ZERO;
+                if neighbors > 0 {
+                    let n = neighbors as f32;
+                    force += self.steer(separation, velocity) * self.separation_weight;
+                    force += self.steer(heading / n, velocity) * self.alignment_weight;
+                    force += self.steer(center / n - position, velocity) * self.cohesion_weight;
+                }
+                let force = force.truncate(self.max_force);
+                *acceleration = [force.x, force.y];
+            },
+        );
+        self.scratch = scratch;
+    }
+
+    /// The flat `[x, y, ...]` positions that `positions` views from JS.
+    pub fn position_buffer(&self) -> &[f32] {
+        &self.positions
+    }
+
+    /// The flat `[x, y, ...]` velocities.
+    pub fn velocity_buffer(&self) -> &[f32] {
+        &self.velocities
+    }
+}
@@ -0,0 +1,168 @@
+use wasm_bindgen::prelude::*;
+
+use crate::grid::{Grid, Traversal};
+use crate::math::Vec2;
+use crate::search;
+
+/// Per-cell travel directions toward a single target, shared by any number of agents.
+/// Cell `(x, y)` is sampled at integer coordinates, matching `Grid::find_path`.
+#[wasm_bindgen]
+pub struct FlowField {
+    width: u32,
+    height: u32,
+    target: (u32, u32),
+    costs: Vec<f32>,
+    directions: Vec<f32>,
+}
+
+#[wasm_bindgen]
+impl Grid {
+    /// Integrates path costs outward from the target and points every reachable cell
+    /// at the neighbor it reaches the target through most cheaply. Blocked and unreachable cells get a zero vector.
+    pub fn generate_flow_field(&self, target_x: u32, target_y: u32) -> FlowField {
+        let cell_count = self.cell_count();
+        let costs = if self.is_passable(target_x as i32, target_y as i32, Traversal::default()) {
+            let target = self.index(target_x as i32, target_y as i32);
+            search::dijkstra(cell_count, &[target], |index, out| {
+                self.neighbors(index, out)
+            })
+        } else {
+            vec![f32::INFINITY; cell_count]
+        };
+        self.flow_field(target_x, target_y, costs)
+    }
+}
+
+impl Grid {
+    /// Points every reachable cell at the neighbor it reaches the target
+    /// through most cheaply, given each cell's path cost to the target.
+    pub(crate) fn flow_field(&self, target_x: u32, target_y: u32, costs: Vec<f32>) -> FlowField {
+        let (width, height) = (self.width(), self.height());
+        let cell_count = self.cell_count();
+        let mut directions = vec![0.0; cell_count * 2];
+        let mut edges = Vec::new();
+        for index in 0..cell_count {
+            if !costs[index].is_finite() || costs[index] == 0.0 {
+                continue;
+            }
+            edges.clear();
+            self.neighbors(index, &mut edges);
+            let best = edges
+                .iter()
+                .map(|&(next, step)| (next, step + costs[next]))
+                .min_by(|a, b| a.1.total_cmp(&b.1));
+            if let Some((next, _)) = best {
+                let (x, y) = self.coords(index);
+                let (nx, ny) = self.coords(next);
+                let direction = Vec2::new((nx - x) as f32, (ny - y) as f32).normalize();
+                directions[index * 2] = direction.x;
+                directions[index * 2 + 1] = direction.y;
+            }
+        }
+
+        FlowField {
+            width,
+            height,
+            target: (target_x, target_y),
+            costs,
+            directions,
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl FlowField {
+    #[wasm_bindgen(getter)]
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn target_x(&self) -> u32 {
+        self.target.0
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn target_y(&self) -> u32 {
+        self.target.1
+    }
+
+    /// Flat `[dx, dy]` unit direction per cell, row-major.
+    pub fn directions(&self) -> Vec<f32> {
+        self.directions.clone()
+    }
+
+    /// Path cost from each cell to the target, `Infinity` where unreachable.
+    pub fn costs(&self) -> Vec<f32> {
+        self.costs.clone()
+    }
+
+    /// Direction of the cell nearest to `(x, y)`.
+    pub fn direction(&self, x: f32, y: f32) -> Vec2 {
+        match self.cell(x.round() as i32, y.round() as i32) {
+            Some(index) => self.direction_at(index),
+            None => Vec2::ZERO,
+        }
+    }
+
+    /// Cost to the target from the cell nearest to `(x, y)`.
+    pub fn cost(&self, x: f32, y: f32) -> f32 {
+        match self.cell(x.round() as i32, y.round() as i32) {
+            Some(index) => self.costs[index],
+            None => f32::INFINITY,
+        }
+    }
+
+    /// Bilinearly blends the directions of the four cells around `(x, y)` for smooth
+    /// steering, ignoring cells that have no direction. Returns a unit vector or zero.
+    pub fn sample(&self, x: f32, y: f32) -> Vec2 {
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (x - x0, y - y0);
+        let (x0, y0) = (x0 as i32, y0 as i32);
+        let corners = [
+            (x0, y0, (1.0 - fx) * (1.0 - fy)),
+            (x0 + 1, y0, fx * (1.0 - fy)),
+            (x0, y0 + 1, (1.0 - fx) * fy),
+            (x0 + 1, y0 + 1, fx * fy),
+        ];
+
+        let mut blended = Vec2::ZERO;
+        for &(cx, cy, weight) in &corners {
+            if let Some(index) = self.cell(cx, cy) {
+                blended += self.direction_at(index) * weight;
+            }
+        }
+        blended.normalize()
+    }
+
+    /// Samples every position in flat `[x0, y0, x1, y1, ...]` and returns the
+    /// directions in the same layout.
+    pub fn sample_many(&self, positions: &[f32]) -> Vec<f32> {
+        positions
+            .chunks_exact(2)
+            .flat_map(|p| {
+                let direction = self.sample(p[0], p[1]);
+                [direction.x, direction.y]
+            })
+            .collect()
+    }
+}
+
+impl FlowField {
+    fn cell(&self, x: i32, y: i32) -> Option<usize> {
+        if x >= 0 && y >= 0 && (x as u32) < self.width && (y as u32) < self.height {
+            Some((y as u32 * self.width + x as u32) as usize)
+        } else {
+            None
+        }
+    }
+
+    fn direction_at(&self, index: usize) -> Vec2 {
+        Vec2::new(self.directions[index * 2], self.directions[index * 2 + 1])
+    }
+}
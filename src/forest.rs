@@ -0,0 +1,389 @@
+use wasm_bindgen::prelude::*;
+
+use crate::error::Error;
+use crate::json::Json;
+
+/// How `DecisionForest` combines the leaves its trees reach.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Aggregation {
+    /// Mean of the leaf values, as random forests predict probabilities or
+    /// regression targets.
+    Average = 0,
+    /// Share of trees whose leaf favors each output, for majority voting.
+    Vote = 1,
+    /// Sum of the leaf values plus the base, as gradient-boosted trees predict
+    /// raw scores.
+    Sum = 2,
+}
+
+impl Aggregation {
+    const NAMES: [(&'static str, Aggregation); 3] = [
+        ("average", Aggregation::Average),
+        ("vote", Aggregation::Vote),
+        ("sum", Aggregation::Sum),
+    ];
+
+    fn from_name(name: &str) -> Option<Aggregation> {
+        Aggregation::NAMES
+            .iter()
+            .find(|(known, _)| *known == name)
+            .map(|&(_, aggregation)| aggregation)
+    }
+
+    fn name(self) -> &'static str {
+        Aggregation::NAMES
+            .iter()
+            .find(|(_, aggregation)| *aggregation == self)
+            .map(|(name, _)| *name)
+            .unwrap_or("average")
+    }
+}
+
+/// A split, or a leaf when `left` is `LEAF`; child indices are absolute.
+#[derive(Clone, Copy, Debug)]
+struct Node {
+    feature: u32,
+    threshold: f32,
+    left: u32,
+    right: u32,
+}
+
+const LEAF: u32 = u32::MAX;
+
+/// Decision trees trained offline, e.g. with scikit-learn, evaluated over
+/// batches of feature vectors. A single tree is a forest of one.
+///
+/// Trees use scikit-learn's flat layout: per node, the feature and threshold it
+/// splits on, its left and right children (-1 at leaves), and its values.
+/// Samples go left when `feature <= threshold`, and right otherwise, including
+/// when the feature is `NaN`.
+#[wasm_bindgen]
+pub struct DecisionForest {
+    feature_count: usize,
+    output_count: usize,
+    nodes: Vec<Node>,
+    /// `output_count` values per node, in node order.
+    values: Vec<f32>,
+    roots: Vec<u32>,
+    base: Vec<f32>,
+    pub aggregation: Aggregation,
+}
+
+#[wasm_bindgen]
+impl DecisionForest {
+    /// An empty forest over `feature_count` features predicting `output_count`
+    /// values, e.g. one probability per class.
+    #[wasm_bindgen(constructor)]
+    pub fn new(feature_count: u32, output_count: u32) -> Result<DecisionForest, Error> {
+        if feature_count == 0 || output_count == 0 {
+            return Err(Error::InvalidInput(
+                "feature and output counts must be positive".into(),
+            ));
+        }
+        Ok(DecisionForest {
+            feature_count: feature_count as usize,
+            output_count: output_count as usize,
+            nodes: Vec::new(),
+            values: Vec::new(),
+            roots: Vec::new(),
+            base: vec![0.0; output_count as usize],
+            aggregation: Aggregation::Average,
+        })
+    }
+
+    /// Adds a tree from scikit-learn's `tree_.feature`, `tree_.threshold`,
+    /// `tree_.children_left`, `tree_.children_right` and the flattened
+    /// `tree_.value`, with `output_count` values per node. Children must come
+    /// after their parents, as they do in scikit-learn's depth-first order.
+    /// Returns the tree's index.
+    pub fn add_tree(
+        &mut self,
+        feature: &[i32],
+        threshold: &[f32],
+        left: &[i32],
+        right: &[i32],
+        values: &[f32],
+    ) -> Result<u32, Error> {
+        let count = feature.len();
+        if count == 0
+            || threshold.len() != count
+            || left.len() != count
+            || right.len() != count
+            || values.len() != count * self.output_count
+        {
+            return Err(Error::InvalidInput(format!(
+                "expected {} nodes in every array and {} values per node",
+                count, self.output_count
+            )));
+        }
+        let offset = self.nodes.len() as u32;
+        let mut nodes = Vec::with_capacity(count);
+        for i in 0..count {
+            let child = |child: i32| {
+                if child as usize > i && (child as usize) < count {
+                    Ok(offset + child as u32)
+                } else {
+                    Err(Error::InvalidInput(format!(
+                        "node {} has child {} outside the nodes after it",
+                        i, child
+                    )))
+                }
+            };
+            let node = if left[i] < 0 && right[i] < 0 {
+                Node {
+                    feature: 0,
+                    threshold: 0.0,
+                    left: LEAF,
+                    right: LEAF,
+                }
+            } else if feature[i] >= 0 && (feature[i] as usize) < self.feature_count {
+                Node {
+                    feature: feature[i] as u32,
+                    threshold: threshold[i],
+                    left: child(left[i])?,
+                    right: child(right[i])?,
+                }
+            } else {
+                return Err(Error::InvalidInput(format!(
+                    "node {} splits on feature {} of {}",
+                    i, feature[i], self.feature_count
+                )));
+            };
+            nodes.push(node);
+        }
+        self.nodes.extend(nodes);
+        self.values.extend_from_slice(values);
+        self.roots.push(offset);
+        Ok(self.roots.len() as u32 - 1)
+    }
+
+    /// Values added to every `Sum` prediction, such as a boosted model's base
+    /// score.
+    pub fn set_base(&mut self, base: &[f32]) -> Result<(), Error> {
+        if base.len() == self.output_count {
+            self.base.copy_from_slice(base);
+            Ok(())
+        } else {
+            Err(Error::InvalidInput(format!(
+                "expected {} base values, got {}",
+                self.output_count,
+                base.len()
+            )))
+        }
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn tree_count(&self) -> u32 {
+        self.roots.len() as u32
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn feature_count(&self) -> u32 {
+        self.feature_count as u32
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn output_count(&self) -> u32 {
+        self.output_count as u32
+    }
+
+    /// Predictions for a batch of feature vectors laid end to end, returned the
+    /// same way with `output_count` values each.
+    pub fn predict(&self, features: &[f32]) -> Result<Vec<f32>, Error> {
+        self.check(features)?;
+        let mut outputs = vec![0.0; features.len() / self.feature_count * self.output_count];
+        for (sample, output) in features
+            .chunks_exact(self.feature_count)
+            .zip(outputs.chunks_exact_mut(self.output_count))
+        {
+            self.predict_into(sample, output);
+        }
+        Ok(outputs)
+    }
+
+    /// The highest-valued output of each feature vector in the batch, such as
+    /// its most likely class; the lowest index on ties.
+    pub fn classify(&self, features: &[f32]) -> Result<Vec<u32>, Error> {
+        self.check(features)?;
+        let mut output = vec![0.0; self.output_count];
+        Ok(features
+            .chunks_exact(self.feature_count)
+            .map(|sample| {
+                self.predict_into(sample, &mut output);
+                argmax(&output) as u32
+            })
+            .collect())
+    }
+
+    /// Reads a forest such as `{"features": 4, "outputs": 3, "aggregation":
+    /// "average", "trees": [{"feature": [...], "threshold": [...], "left":
+    /// [...], "right": [...], "value": [...]}]}`, with each tree's arrays as in
+    /// `add_tree` and an optional `base` for `sum`.
+    pub fn from_json(json: &str) -> Result<DecisionForest, Error> {
+        let json = Json::parse(json)?;
+        let mut forest = DecisionForest::new(
+            json.field("features", Json::as_f64)? as u32,
+            json.field("outputs", Json::as_f64)? as u32,
+        )?;
+        if let Some(aggregation) = json.get("aggregation") {
+            forest.aggregation = aggregation
+                .as_str()
+                .and_then(Aggregation::from_name)
+                .ok_or_else(|| Error::InvalidInput("unknown aggregation".into()))?;
+        }
+        if json.get("base").is_some() {
+            forest.set_base(&json.field("base", Json::as_f32s)?)?;
+        }
+        let ints = |values: Vec<f32>| values.into_iter().map(|value| value as i32).collect();
+        for tree in json.field("trees", Json::as_array)? {
+            let feature: Vec<i32> = ints(tree.field("feature", Json::as_f32s)?);
+            let left: Vec<i32> = ints(tree.field("left", Json::as_f32s)?);
+            let right: Vec<i32> = ints(tree.field("right", Json::as_f32s)?);
+            forest.add_tree(
+                &feature,
+                &tree.field("threshold", Json::as_f32s)?,
+                &left,
+                &right,
+                &tree.field("value", Json::as_f32s)?,
+            )?;
+        }
+        Ok(forest)
+    }
+
+    /// The forest in the format `from_json` reads.
+    pub fn to_json(&self) -> String {
+        let numbers = |values: &mut dyn Iterator<Item = f32>| {
+            Json::Array(values.map(Json::from_f32).collect())
+        };
+        let trees = (0..self.roots.len())
+            .map(|tree| {
+                let start = self.roots[tree] as usize;
+                let end = self
+                    .roots
+                    .get(tree + 1)
+                    .map_or(self.nodes.len(), |&root| root as usize);
+                let nodes = &self.nodes[start..end];
+                let child = |child: u32| {
+                    if child == LEAF {
+                        -1.0
+                    } else {
+                        (child as usize - start) as f32
+                    }
+                };
+                Json::Object(vec![
+                    (
+                        "feature".into(),
+                        numbers(&mut nodes.iter().map(|node| {
+                            if node.left == LEAF {
+                                -2.0
+                            } else {
+                                node.feature as f32
+                            }
+                        })),
+                    ),
+                    (
+                        "threshold".into(),
+                        numbers(&mut nodes.iter().map(|node| node.threshold)),
+                    ),
+                    (
+                        "left".into(),
+                        numbers(&mut nodes.iter().map(|node| child(node.left))),
+                    ),
+                    (
+                        "right".into(),
+                        numbers(&mut nodes.iter().map(|node| child(node.right))),
+                    ),
+                    (
+                        "value".into(),
+                        numbers(
+                            &mut self.values[start * self.output_count..end * self.output_count]
+                                .iter()
+                                .copied(),
+                        ),
+                    ),
+                ])
+            })
+            .collect();
+        Json::Object(vec![
+            ("features".into(), Json::Number(self.feature_count as f64)),
+            ("outputs".into(), Json::Number(self.output_count as f64)),
+            (
+                "aggregation".into(),
+                Json::String(self.aggregation.name().into()),
+            ),
+            ("base".into(), numbers(&mut self.base.iter().copied())),
+            ("trees".into(), Json::Array(trees)),
+        ])
+        .to_string()
+    }
+}
+
+impl DecisionForest {
+    /// The prediction for one vector of `feature_count` features, written into
+    /// `output`, which holds `output_count` values.
+    pub fn predict_into(&self, features: &[f32], output: &mut [f32]) {
+        output.fill(0.0);
+        for &root in &self.roots {
+            let leaf = self.leaf(root, features);
+            let values = &self.values[leaf * self.output_count..][..self.output_count];
+            match self.aggregation {
+                Aggregation::Average | Aggregation::Sum => {
+                    for (total, value) in output.iter_mut().zip(values) {
+                        *total += value;
+                    }
+                }
+                Aggregation::Vote => output[argmax(values)] += 1.0,
+            }
+        }
+        match self.aggregation {
+            Aggregation::Average | Aggregation::Vote if !self.roots.is_empty() => {
+                let trees = self.roots.len() as f32;
+                output.iter_mut().for_each(|total| *total /= trees);
+            }
+            Aggregation::Sum => {
+                for (total, base) in output.iter_mut().zip(&self.base) {
+                    *total += base;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn leaf(&self, root: u32, features: &[f32]) -> usize {
+        let mut index = root as usize;
+        loop {
+            let node = self.nodes[index];
+            if node.left == LEAF {
+                return index;
+            }
+            index = if features[node.feature as usize] <= node.threshold {
+                node.left
+            } else {
+                node.right
+            } as usize;
+        }
+    }
+
+    fn check(&self, features: &[f32]) -> Result<(), Error> {
+        if features.len().is_multiple_of(self.feature_count) {
+            Ok(())
+        } else {
+            Err(Error::InvalidInput(format!(
+                "expected a multiple of {} features, got {}",
+                self.feature_count,
+                features.len()
+            )))
+        }
+    }
+}
+
+fn argmax(values: &[f32]) -> usize {
+    let mut best = 0;
+    for (index, &value) in values.iter().enumerate() {
+        if value > values[best] {
+            best = index;
+        }
+    }
+    best
+}
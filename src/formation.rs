@@ -0,0 +1,269 @@
+use wasm_bindgen::prelude::*;
+
+use crate::error::Error;
+use crate::grid::Grid;
+use crate::math::Vec2;
+use crate::path_following::PathFollower;
+
+/// Fractions of a slot's side offset tried, in order, when pulling it in behind
+/// a wall.
+const REFORM_SCALES: [f32; 5] = [1.0, 0.75, 0.5, 0.25, 0.0];
+
+/// How far ahead on the path the heading is measured, so it turns smoothly
+/// through corners instead of snapping at each path point.
+const HEADING_LOOKAHEAD: f32 = 1.0;
+
+/// A formation template moving along a single leader path. The path is followed
+/// by an anchor point, and each slot is an offset from the anchor in its local
+/// frame, with +x along the heading and +y to its left. Members steer toward
+/// their slot targets themselves, e.g. with `Agent::arrive`; a member far from
+/// its slot should path to it rather than steer straight at it.
+///
+/// Slot 0 is the leader's slot at the anchor for `wedge` and `column`, and the
+/// center slot for `line`.
+#[wasm_bindgen]
+#[derive(Clone)]
+pub struct Formation {
+    offsets: Vec<Vec2>,
+    path: Option<PathFollower>,
+    progress: f32,
+    anchor: Vec2,
+    heading: Vec2,
+    /// Speed of the anchor along the path.
+    pub speed: f32,
+    /// How far a member may fall behind its slot before the anchor slows down to
+    /// wait for it; it stops once a member is twice this far behind.
+    pub max_lag: f32,
+}
+
+#[wasm_bindgen]
+impl Formation {
+    /// Members side by side, filled from the center outward.
+    pub fn line(slots: u32, spacing: f32) -> Result<Formation, Error> {
+        validate(slots, spacing)?;
+        let center = (slots - 1) as f32 / 2.0;
+        let mut offsets: Vec<Vec2> = (0..slots)
+            .map(|i| Vec2::new(0.0, (center - i as f32) * spacing))
+            .collect();
+        offsets.sort_by(|a, b| a.y.abs().total_cmp(&b.y.abs()).then(b.y.total_cmp(&a.y)));
+        Ok(Formation::with_offsets(offsets))
+    }
+
+    /// A V behind the leader, alternating left and right.
+    pub fn wedge(slots: u32, spacing: f32) -> Result<Formation, Error> {
+        validate(slots, spacing)?;
+        let offsets = (0..slots)
+            .map(|i| {
+                let rank = i.div_ceil(2) as f32 * spacing;
+                let side = if i % 2 == 1 { 1.0 } else { -1.0 };
+                Vec2::new(-rank, side * rank)
+            })
+            .collect();
+        Ok(Formation::with_offsets(offsets))
+    }
+
+    /// Single file behind the leader.
+    pub fn column(slots: u32, spacing: f32) -> Result<Formation, Error> {
+        validate(slots, spacing)?;
+        let offsets = (0..slots)
+            .map(|i| Vec2::new(-(i as f32) * spacing, 0.0))
+            .collect();
+        Ok(Formation::with_offsets(offsets))
+    }
+
+    /// Slots from flat `[forward0, left0, forward1, left1, ...]` offsets.
+    pub fn custom(offsets: &[f32]) -> Result<Formation, Error> {
+        if offsets.is_empty() || !offsets.len().is_multiple_of(2) {
+            return Err(Error::InvalidInput(format!(
+                "expected a non-empty list of 2-component offsets, got {} values",
+                offsets.len()
+            )));
+        }
+        let offsets = offsets
+            .chunks_exact(2)
+            .map(|o| Vec2::new(o[0], o[1]))
+            .collect();
+        Ok(Formation::with_offsets(offsets))
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn slot_count(&self) -> u32 {
+        self.offsets.len() as u32
+    }
+
+    /// Places the formation without a path, facing `heading`.
+    pub fn place(&mut self, position: Vec2, heading: Vec2) {
+        self.path = None;
+        self.progress = 0.0;
+        self.anchor = position;
+        if heading != Vec2::ZERO {
+            self.heading = heading.normalize();
+        }
+    }
+
+    /// Sets the leader path, in the same format as `PathFollower::new`, and moves
+    /// the anchor to its start. Compute it once for the whole formation.
+    pub fn set_path(&mut self, path: &[f32], dimensions: u32) -> Result<(), Error> {
+        let follower = PathFollower::new(path, dimensions)?;
+        self.progress = 0.0;
+        self.anchor = follower.point_at(0.0);
+        self.path = Some(follower);
+        self.turn();
+        Ok(())
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn anchor(&self) -> Vec2 {
+        self.anchor
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn heading(&self) -> Vec2 {
+        self.heading
+    }
+
+    /// Whether the anchor has reached the end of its path, or has none.
+    pub fn is_finished(&self) -> bool {
+        self.path
+            .as_ref()
+            .is_none_or(|path| self.progress >= path.length())
+    }
+
+    /// Moves the anchor along the path by `speed * dt`, slowed down while any
+    /// member lags more than `max_lag` behind its slot. `members` holds the flat
+    /// `[x0, y0, x1, y1, ...]` positions of the members in slot order; missing
+    /// members are ignored.
+    pub fn update(&mut self, dt: f32, members: &[f32]) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        let lag = members
+            .chunks_exact(2)
+            .zip(&self.offsets)
+            .map(|(member, &offset)| Vec2::new(member[0], member[1]).distance(self.slot(offset)))
+            .fold(0.0, f32::max);
+        let factor = if self.max_lag > 0.0 && lag > self.max_lag {
+            (2.0 - lag / self.max_lag).max(0.0)
+        } else {
+            1.0
+        };
+        self.progress = (self.progress + self.speed * factor * dt).min(path.length());
+        self.anchor = path.point_at(self.progress);
+        self.turn();
+    }
+
+    /// World position of one slot, or undefined when out of range.
+    pub fn slot_target(&self, slot: u32) -> Option<Vec2> {
+        self.offsets
+            .get(slot as usize)
+            .map(|&offset| self.slot(offset))
+    }
+
+    /// Flat `[x0, y0, x1, y1, ...]` world positions of all slots.
+    pub fn slot_targets(&self) -> Vec<f32> {
+        self.offsets
+            .iter()
+            .flat_map(|&offset| {
+                let slot = self.slot(offset);
+                [slot.x, slot.y]
+            })
+            .collect()
+    }
+
+    /// Like `slot_targets`, but reforms around walls. A slot is only kept where it
+    /// is in line of sight of both the anchor and its point on the leader path, and
+    /// has room to keep moving forward. Otherwise it falls back onto the leader path
+    /// at its forward offset, so rear slots trail through doors instead of cutting
+    /// across walls, with its side offset shrunk until it is clear again. The
+    /// formation squeezes through corridors and spreads out once past them.
+    /// Positions are grid cell coordinates.
+    pub fn slot_targets_on(&self, grid: &Grid) -> Vec<f32> {
+        let cell = |p: Vec2| (p.x.round() as i32, p.y.round() as i32);
+        let sees = |from: Vec2, to: Vec2| {
+            let ((x0, y0), (x1, y1)) = (cell(from), cell(to));
+            grid.line_of_sight(x0, y0, x1, y1)
+        };
+        self.offsets
+            .iter()
+            .flat_map(|&offset| {
+                let (base, heading) = self.frame(offset.x);
+                let clear = |slot: Vec2, heading: Vec2| {
+                    sees(base, slot) && sees(slot, slot + heading * HEADING_LOOKAHEAD)
+                };
+                let rigid = self.slot(offset);
+                let slot = if sees(self.anchor, rigid) && clear(rigid, self.heading) {
+                    rigid
+                } else {
+                    REFORM_SCALES
+                        .iter()
+                        .map(|&scale| base + heading.perp() * (offset.y * scale))
+                        .find(|&slot| clear(slot, heading))
+                        .unwrap_or(base)
+                };
+                [slot.x, slot.y]
+            })
+            .collect()
+    }
+}
+
+impl Formation {
+    fn with_offsets(offsets: Vec<Vec2>) -> Formation {
+        Formation {
+            offsets,
+            path: None,
+            progress: 0.0,
+            anchor: Vec2::ZERO,
+            heading: Vec2::new(1.0, 0.0),
+            speed: 3.0,
+            max_lag: 2.0,
+        }
+    }
+
+    fn slot(&self, offset: Vec2) -> Vec2 {
+        self.anchor + self.heading * offset.x + self.heading.perp() * offset.y
+    }
+
+    /// Point and heading on the leader path `along` ahead of the anchor, or behind
+    /// it when negative. Without a path this is the anchor's own frame.
+    fn frame(&self, along: f32) -> (Vec2, Vec2) {
+        let Some(path) = &self.path else {
+            return (self.anchor + self.heading * along, self.heading);
+        };
+        let distance = (self.progress + along).max(0.0);
+        let point = path.point_at(distance);
+        let ahead = path.point_at(distance + HEADING_LOOKAHEAD) - point;
+        let heading = if ahead == Vec2::ZERO {
+            self.heading
+        } else {
+            ahead.normalize()
+        };
+        (point, heading)
+    }
+
+    /// Points the heading along the path ahead of the anchor, keeping the old
+    /// heading at the end of the path.
+    fn turn(&mut self) {
+        if let Some(path) = &self.path {
+            let ahead = path.point_at(self.progress + HEADING_LOOKAHEAD) - self.anchor;
+            if ahead != Vec2::ZERO {
+                self.heading = ahead.normalize();
+            }
+        }
+    }
+}
+
+fn validate(slots: u32, spacing: f32) -> Result<(), Error> {
+    if slots == 0 {
+        return Err(Error::InvalidInput(
+            "formation needs at least one slot".into(),
+        ));
+    }
+    if spacing > 0.0 {
+        Ok(())
+    } else {
+        Err(Error::InvalidInput(format!(
+            "formation spacing must be positive, got {}",
+            spacing
+        )))
+    }
+}
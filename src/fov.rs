@@ -0,0 +1,127 @@
+use wasm_bindgen::prelude::*;
+
+use crate::grid::Grid;
+
+/// Transforms from octant-local `(column, row)` steps to grid offsets, one per
+/// octant: `[xx, xy, yx, yy]`.
+const OCTANTS: [[i32; 4]; 8] = [
+    [1, 0, 0, 1],
+    [0, 1, 1, 0],
+    [0, -1, 1, 0],
+    [-1, 0, 0, 1],
+    [-1, 0, 0, -1],
+    [0, -1, -1, 0],
+    [0, 1, -1, 0],
+    [1, 0, 0, -1],
+];
+
+#[wasm_bindgen]
+impl Grid {
+    /// Cells visible from `(x, y)` within `radius`, found by recursive
+    /// shadowcasting with unwalkable cells as opaque. Returns a row-major mask
+    /// with 1 for visible cells, including the walls that bound the view.
+    pub fn field_of_view(&self, x: i32, y: i32, radius: u32) -> Vec<u8> {
+        let mut mask = vec![0; self.cell_count()];
+        self.cast_field_of_view(x, y, radius, |index| mask[index] = 1);
+        mask
+    }
+
+    /// Like `field_of_view`, as flat `[x0, y0, x1, y1, ...]` cell coordinates.
+    pub fn field_of_view_cells(&self, x: i32, y: i32, radius: u32) -> Vec<i32> {
+        let mut mask = vec![false; self.cell_count()];
+        let mut cells = Vec::new();
+        self.cast_field_of_view(x, y, radius, |index| {
+            if !std::mem::replace(&mut mask[index], true) {
+                let (cx, cy) = self.coords(index);
+                cells.extend([cx, cy]);
+            }
+        });
+        cells
+    }
+}
+
+impl Grid {
+    /// Calls `visit` with the index of every cell visible from `(x, y)`. Cells on
+    /// octant boundaries may be visited twice.
+    pub(crate) fn cast_field_of_view(
+        &self,
+        x: i32,
+        y: i32,
+        radius: u32,
+        mut visit: impl FnMut(usize),
+    ) {
+        if !self.in_bounds(x, y) {
+            return;
+        }
+        visit(self.index(x, y));
+        let radius = radius as i32;
+        for transform in OCTANTS {
+            self.cast_octant(x, y, radius, 1, 1.0, 0.0, transform, &mut visit);
+        }
+    }
+
+    /// Scans rows of one octant outward from `row`, between the `start` and `end`
+    /// slopes, recursing past every run of opaque cells.
+    #[allow(clippy::too_many_arguments)]
+    fn cast_octant(
+        &self,
+        x: i32,
+        y: i32,
+        radius: i32,
+        row: i32,
+        mut start: f32,
+        end: f32,
+        [xx, xy, yx, yy]: [i32; 4],
+        visit: &mut impl FnMut(usize),
+    ) {
+        if start < end {
+            return;
+        }
+        let radius_squared = radius * radius;
+        for distance in row..=radius {
+            let mut blocked = false;
+            let mut next_start = start;
+            let dy = -distance;
+            for dx in -distance..=0 {
+                let left = (dx as f32 - 0.5) / (dy as f32 + 0.5);
+                let right = (dx as f32 + 0.5) / (dy as f32 - 0.5);
+                if start < right {
+                    continue;
+                }
+                if end > left {
+                    break;
+                }
+                let (cx, cy) = (x + dx * xx + dy * xy, y + dx * yx + dy * yy);
+                let in_bounds = self.in_bounds(cx, cy);
+                if in_bounds && dx * dx + dy * dy <= radius_squared {
+                    visit(self.index(cx, cy));
+                }
+                let opaque = !in_bounds || !self.is_walkable(cx, cy);
+                if blocked {
+                    if opaque {
+                        next_start = right;
+                    } else {
+                        blocked = false;
+                        start = next_start;
+                    }
+                } else if opaque && distance < radius {
+                    blocked = true;
+                    self.cast_octant(
+                        x,
+                        y,
+                        radius,
+                        distance + 1,
+                        start,
+                        left,
+                        [xx, xy, yx, yy],
+                        visit,
+                    );
+                    next_start = right;
+                }
+            }
+            if blocked {
+                return;
+            }
+        }
+    }
+}
@@ -0,0 +1,716 @@
+use wasm_bindgen::prelude::*;
+
+use crate::error::Error;
+use crate::json::Json;
+
+/// Deepest nesting of parentheses and NOTs a rule may use, so a hostile rule
+/// cannot overflow the stack.
+const MAX_DEPTH: u32 = 64;
+
+/// How `FuzzySystem` turns the combined output sets back into one crisp value.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Defuzzification {
+    /// Center of the area under the combined sets: smooth, and the usual choice.
+    Centroid = 0,
+    /// Middle of the values where the combined sets peak: decisive.
+    MeanOfMaximum = 1,
+}
+
+impl Defuzzification {
+    const NAMES: [(&'static str, Defuzzification); 2] = [
+        ("centroid", Defuzzification::Centroid),
+        ("max", Defuzzification::MeanOfMaximum),
+    ];
+
+    fn from_name(name: &str) -> Option<Defuzzification> {
+        Defuzzification::NAMES
+            .iter()
+            .find(|(known, _)| *known == name)
+            .map(|&(_, method)| method)
+    }
+
+    fn name(self) -> &'static str {
+        Defuzzification::NAMES
+            .iter()
+            .find(|(_, method)| *method == self)
+            .map(|(name, _)| *name)
+            .unwrap_or("centroid")
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Membership {
+    Triangle(f32, f32, f32),
+    Trapezoid(f32, f32, f32, f32),
+    Gaussian(f32, f32),
+}
+
+impl Membership {
+    fn degree(self, x: f32) -> f32 {
+        match self {
+            Membership::Triangle(a, b, c) => Membership::Trapezoid(a, b, b, c).degree(x),
+            Membership::Trapezoid(a, b, c, d) => {
+                if x < b {
+                    ramp(a, b, x)
+                } else if x > c {
+                    1.0 - ramp(c, d, x)
+                } else {
+                    1.0
+                }
+            }
+            Membership::Gaussian(mean, sigma) => {
+                let z = (x - mean) / sigma;
+                (-0.5 * z * z).exp()
+            }
+        }
+    }
+
+    fn to_json(self) -> Json {
+        let (kind, points) = match self {
+            Membership::Triangle(a, b, c) => ("triangle", vec![a, b, c]),
+            Membership::Trapezoid(a, b, c, d) => ("trapezoid", vec![a, b, c, d]),
+            Membership::Gaussian(mean, sigma) => ("gaussian", vec![mean, sigma]),
+        };
+        Json::Object(vec![(
+            kind.into(),
+            Json::Array(points.into_iter().map(Json::from_f32).collect()),
+        )])
+    }
+}
+
+/// 0 at `from`, rising linearly to 1 at `to`; a step when they are equal.
+fn ramp(from: f32, to: f32, x: f32) -> f32 {
+    if x >= to {
+        1.0
+    } else if x > from {
+        (x - from) / (to - from)
+    } else {
+        0.0
+    }
+}
+
+struct Variable {
+    name: String,
+    min: f32,
+    max: f32,
+    sets: Vec<(String, Membership)>,
+}
+
+/// A rule's condition over input sets, as `(variable, set)` indices.
+enum Condition {
+    Is(usize, usize),
+    Not(Box<Condition>),
+    And(Vec<Condition>),
+    Or(Vec<Condition>),
+}
+
+impl Condition {
+    /// Degree of truth, with min for AND, max for OR and `1 - x` for NOT.
+    fn truth(&self, degrees: &[Vec<f32>]) -> f32 {
+        match self {
+            Condition::Is(variable, set) => degrees[*variable][*set],
+            Condition::Not(inner) => 1.0 - inner.truth(degrees),
+            Condition::And(all) => all
+                .iter()
+                .map(|condition| condition.truth(degrees))
+                .fold(1.0, f32::min),
+            Condition::Or(any) => any
+                .iter()
+                .map(|condition| condition.truth(degrees))
+                .fold(0.0, f32::max),
+        }
+    }
+}
+
+struct Rule {
+    source: String,
+    condition: Condition,
+    consequences: Vec<(usize, usize)>,
+}
+
+/// Mamdani fuzzy inference: crisp inputs are fuzzified through membership
+/// functions, rules written like
+/// `IF speed IS fast AND NOT distance IS far THEN brake IS hard` fire to the
+/// degree their conditions hold, and each output is defuzzified back into a
+/// crisp value.
+///
+/// Sets must be added before the rules that name them. Keywords are
+/// case-insensitive; variable and set names are not.
+#[wasm_bindgen]
+pub struct FuzzySystem {
+    inputs: Vec<Variable>,
+    outputs: Vec<Variable>,
+    rules: Vec<Rule>,
+    values: Vec<f32>,
+    results: Vec<Option<f32>>,
+    pub defuzzification: Defuzzification,
+    /// Samples taken across each output's range when defuzzifying.
+    pub resolution: u32,
+}
+
+impl Default for FuzzySystem {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[wasm_bindgen]
+impl FuzzySystem {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> FuzzySystem {
+        FuzzySystem {
+            inputs: Vec::new(),
+            outputs: Vec::new(),
+            rules: Vec::new(),
+            values: Vec::new(),
+            results: Vec::new(),
+            defuzzification: Defuzzification::Centroid,
+            resolution: 101,
+        }
+    }
+
+    /// Adds an input over `[min, max]`, which values are clamped to, and returns
+    /// its index among the inputs.
+    pub fn add_input(&mut self, name: &str, min: f32, max: f32) -> Result<u32, Error> {
+        let variable = self.variable(name, min, max)?;
+        self.inputs.push(variable);
+        self.values.push(min);
+        Ok(self.inputs.len() as u32 - 1)
+    }
+
+    /// Adds an output over `[min, max]` and returns its index among the outputs.
+    pub fn add_output(&mut self, name: &str, min: f32, max: f32) -> Result<u32, Error> {
+        let variable = self.variable(name, min, max)?;
+        self.outputs.push(variable);
+        self.results.push(None);
+        Ok(self.outputs.len() as u32 - 1)
+    }
+
+    /// A set rising from `a` to full membership at `b` and falling to `c`.
+    pub fn add_triangle(
+        &mut self,
+        variable: &str,
+        set: &str,
+        a: f32,
+        b: f32,
+        c: f32,
+    ) -> Result<(), Error> {
+        self.add_set(variable, set, Membership::Triangle(a, b, c))
+    }
+
+    /// A set rising from `a` to full membership over `[b, c]` and falling to `d`.
+    pub fn add_trapezoid(
+        &mut self,
+        variable: &str,
+        set: &str,
+        a: f32,
+        b: f32,
+        c: f32,
+        d: f32,
+    ) -> Result<(), Error> {
+        self.add_set(variable, set, Membership::Trapezoid(a, b, c, d))
+    }
+
+    /// A bell-shaped set centered on `mean` with standard deviation `sigma`.
+    pub fn add_gaussian(
+        &mut self,
+        variable: &str,
+        set: &str,
+        mean: f32,
+        sigma: f32,
+    ) -> Result<(), Error> {
+        if sigma > 0.0 {
+            self.add_set(variable, set, Membership::Gaussian(mean, sigma))
+        } else {
+            Err(Error::InvalidInput("sigma must be positive".into()))
+        }
+    }
+
+    /// Parses and adds a rule of the form `IF <condition> THEN <output> IS <set>`,
+    /// where conditions combine `<input> IS [NOT] <set>` with AND, OR, NOT and
+    /// parentheses, and several consequences may be joined with AND.
+    pub fn add_rule(&mut self, rule: &str) -> Result<(), Error> {
+        let rule = RuleParser::new(self, rule).rule()?;
+        self.rules.push(rule);
+        Ok(())
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn rule_count(&self) -> u32 {
+        self.rules.len() as u32
+    }
+
+    pub fn set_input(&mut self, name: &str, value: f32) -> Result<(), Error> {
+        let index = find(&self.inputs, name)?;
+        let variable = &self.inputs[index];
+        self.values[index] = value.clamp(variable.min, variable.max);
+        Ok(())
+    }
+
+    /// Runs every rule on the current inputs and defuzzifies the outputs.
+    pub fn evaluate(&mut self) {
+        let degrees: Vec<Vec<f32>> = self
+            .inputs
+            .iter()
+            .zip(&self.values)
+            .map(|(variable, &x)| {
+                variable
+                    .sets
+                    .iter()
+                    .map(|(_, membership)| membership.degree(x))
+                    .collect()
+            })
+            .collect();
+        let mut activations: Vec<Vec<f32>> = self
+            .outputs
+            .iter()
+            .map(|variable| vec![0.0; variable.sets.len()])
+            .collect();
+        for rule in &self.rules {
+            let truth = rule.condition.truth(&degrees);
+            for &(output, set) in &rule.consequences {
+                let activation = &mut activations[output][set];
+                *activation = activation.max(truth);
+            }
+        }
+        self.results = self
+            .outputs
+            .iter()
+            .zip(&activations)
+            .map(|(variable, activation)| self.defuzzify(variable, activation))
+            .collect();
+    }
+
+    /// Sets every input, in the order they were added, evaluates, and returns
+    /// every output in order. An output no rule fired for is `NaN`.
+    pub fn infer(&mut self, inputs: &[f32]) -> Result<Vec<f32>, Error> {
+        if inputs.len() != self.inputs.len() {
+            return Err(Error::InvalidInput(format!(
+                "expected {} inputs, got {}",
+                self.inputs.len(),
+                inputs.len()
+            )));
+        }
+        for (index, &value) in inputs.iter().enumerate() {
+            let variable = &self.inputs[index];
+            self.values[index] = value.clamp(variable.min, variable.max);
+        }
+        self.evaluate();
+        Ok(self
+            .results
+            .iter()
+            .map(|result| result.unwrap_or(f32::NAN))
+            .collect())
+    }
+
+    /// The output's value from the last `evaluate`, or `None` if no rule fired
+    /// for it.
+    pub fn output(&self, name: &str) -> Option<f32> {
+        let index = find(&self.outputs, name).ok()?;
+        self.results[index]
+    }
+
+    /// Reads a system such as
+    /// `{"inputs": [{"name": "speed", "min": 0, "max": 50, "sets": {"slow":
+    /// {"triangle": [0, 0, 25]}, ...}}], "outputs": [...], "rules": ["IF ..."],
+    /// "defuzzification": "centroid"}`. Sets take `triangle`, `trapezoid` or
+    /// `gaussian` (mean and sigma) points, and `defuzzification` is `centroid`
+    /// or `max`.
+    pub fn from_json(json: &str) -> Result<FuzzySystem, Error> {
+        let json = Json::parse(json)?;
+        let mut system = FuzzySystem::new();
+        for (key, output) in [("inputs", false), ("outputs", true)] {
+            for variable in json.get(key).and_then(Json::as_array).unwrap_or(&[]) {
+                let name = variable.field("name", Json::as_str)?;
+                let min = variable.field("min", Json::as_f64)? as f32;
+                let max = variable.field("max", Json::as_f64)? as f32;
+                if output {
+                    system.add_output(name, min, max)?;
+                } else {
+                    system.add_input(name, min, max)?;
+                }
+                let Some(Json::Object(sets)) = variable.get("sets") else {
+                    return Err(Error::InvalidInput(format!(
+                        "missing or invalid field 'sets' of '{}'",
+                        name
+                    )));
+                };
+                for (set, shape) in sets {
+                    let invalid =
+                        || Error::InvalidInput(format!("invalid membership for set '{}'", set));
+                    let points = |kind| shape.get(kind).and_then(Json::as_f32s);
+                    match (
+                        points("triangle").as_deref(),
+                        points("trapezoid").as_deref(),
+                        points("gaussian").as_deref(),
+                    ) {
+                        (Some(&[a, b, c]), None, None) => {
+                            system.add_triangle(name, set, a, b, c)?
+                        }
+                        (None, Some(&[a, b, c, d]), None) => {
+                            system.add_trapezoid(name, set, a, b, c, d)?
+                        }
+                        (None, None, Some(&[mean, sigma])) => {
+                            system.add_gaussian(name, set, mean, sigma)?
+                        }
+                        _ => return Err(invalid()),
+                    }
+                }
+            }
+        }
+        for rule in json.get("rules").and_then(Json::as_array).unwrap_or(&[]) {
+            let rule = rule
+                .as_str()
+                .ok_or_else(|| Error::InvalidInput("rules must be strings".into()))?;
+            system.add_rule(rule)?;
+        }
+        if let Some(method) = json.get("defuzzification") {
+            system.defuzzification = method
+                .as_str()
+                .and_then(Defuzzification::from_name)
+                .ok_or_else(|| Error::InvalidInput("unknown defuzzification".into()))?;
+        }
+        if let Some(resolution) = json.get("resolution") {
+            system.resolution = resolution
+                .as_f64()
+                .ok_or_else(|| Error::InvalidInput("invalid resolution".into()))?
+                as u32;
+        }
+        Ok(system)
+    }
+
+    /// The system in the format `from_json` reads.
+    pub fn to_json(&self) -> String {
+        let variables = |variables: &[Variable]| {
+            Json::Array(
+                variables
+                    .iter()
+                    .map(|variable| {
+                        Json::Object(vec![
+                            ("name".into(), Json::String(variable.name.clone())),
+                            ("min".into(), Json::from_f32(variable.min)),
+                            ("max".into(), Json::from_f32(variable.max)),
+                            (
+                                "sets".into(),
+                                Json::Object(
+                                    variable
+                                        .sets
+                                        .iter()
+                                        .map(|(name, membership)| {
+                                            (name.clone(), membership.to_json())
+                                        })
+                                        .collect(),
+                                ),
+                            ),
+                        ])
+                    })
+                    .collect(),
+            )
+        };
+        Json::Object(vec![
+            ("inputs".into(), variables(&self.inputs)),
+            ("outputs".into(), variables(&self.outputs)),
+            (
+                "rules".into(),
+                Json::Array(
+                    self.rules
+                        .iter()
+                        .map(|rule| Json::String(rule.source.clone()))
+                        .collect(),
+                ),
+            ),
+            (
+                "defuzzification".into(),
+                Json::String(self.defuzzification.name().into()),
+            ),
+            ("resolution".into(), Json::Number(self.resolution as f64)),
+        ])
+        .to_string()
+    }
+}
+
+impl FuzzySystem {
+    fn variable(&self, name: &str, min: f32, max: f32) -> Result<Variable, Error> {
+        if find(&self.inputs, name).is_ok() || find(&self.outputs, name).is_ok() {
+            return Err(Error::InvalidInput(format!(
+                "variable '{}' already exists",
+                name
+            )));
+        }
+        if min < max {
+            Ok(Variable {
+                name: name.into(),
+                min,
+                max,
+                sets: Vec::new(),
+            })
+        } else {
+            Err(Error::InvalidInput(format!(
+                "range of '{}' must have min below max",
+                name
+            )))
+        }
+    }
+
+    fn add_set(&mut self, variable: &str, set: &str, membership: Membership) -> Result<(), Error> {
+        let variable = match find(&self.inputs, variable) {
+            Ok(index) => &mut self.inputs[index],
+            Err(_) => {
+                let index = find(&self.outputs, variable)?;
+                &mut self.outputs[index]
+            }
+        };
+        if variable.sets.iter().any(|(name, _)| name == set) {
+            return Err(Error::InvalidInput(format!(
+                "set '{}' of '{}' already exists",
+                set, variable.name
+            )));
+        }
+        variable.sets.push((set.into(), membership));
+        Ok(())
+    }
+
+    /// The crisp value of an output whose sets are clipped to `activation`.
+    fn defuzzify(&self, variable: &Variable, activation: &[f32]) -> Option<f32> {
+        if activation.iter().all(|&degree| degree <= 0.0) {
+            return None;
+        }
+        let samples = self.resolution.max(2);
+        let step = (variable.max - variable.min) / (samples - 1) as f32;
+        let mut weighted = 0.0;
+        let mut area = 0.0;
+        let mut peak = 0.0;
+        let mut peak_sum = 0.0;
+        let mut peak_count = 0;
+        for i in 0..samples {
+            let x = variable.min + step * i as f32;
+            let degree = variable
+                .sets
+                .iter()
+                .zip(activation)
+                .map(|((_, membership), &level)| membership.degree(x).min(level))
+                .fold(0.0, f32::max);
+            weighted += x * degree;
+            area += degree;
+            if degree > peak + 1e-6 {
+                peak = degree;
+                peak_sum = x;
+                peak_count = 1;
+            } else if degree >= peak - 1e-6 && degree > 0.0 {
+                peak_sum += x;
+                peak_count += 1;
+            }
+        }
+        match self.defuzzification {
+            _ if area <= 0.0 => None,
+            Defuzzification::Centroid => Some(weighted / area),
+            Defuzzification::MeanOfMaximum => Some(peak_sum / peak_count as f32),
+        }
+    }
+}
+
+fn find(variables: &[Variable], name: &str) -> Result<usize, Error> {
+    variables
+        .iter()
+        .position(|variable| variable.name == name)
+        .ok_or_else(|| Error::InvalidInput(format!("unknown variable '{}'", name)))
+}
+
+fn set_index(variable: &Variable, set: &str) -> Result<usize, Error> {
+    variable
+        .sets
+        .iter()
+        .position(|(name, _)| name == set)
+        .ok_or_else(|| Error::InvalidInput(format!("unknown set '{}' of '{}'", set, variable.name)))
+}
+
+/// Recursive descent over a rule's words, with OR binding looser than AND.
+struct RuleParser<'a> {
+    system: &'a FuzzySystem,
+    source: &'a str,
+    words: Vec<String>,
+    at: usize,
+    depth: u32,
+}
+
+impl<'a> RuleParser<'a> {
+    fn new(system: &'a FuzzySystem, source: &'a str) -> RuleParser<'a> {
+        let words = source
+            .replace('(', " ( ")
+            .replace(')', " ) ")
+            .split_whitespace()
+            .map(String::from)
+            .collect();
+        RuleParser {
+            system,
+            source,
+            words,
+            at: 0,
+            depth: 0,
+        }
+    }
+
+    fn error(&self, message: &str) -> Error {
+        Error::InvalidInput(format!("invalid rule '{}': {}", self.source, message))
+    }
+
+    fn peek_keyword(&self, keyword: &str) -> bool {
+        self.words
+            .get(self.at)
+            .is_some_and(|word| word.eq_ignore_ascii_case(keyword))
+    }
+
+    fn keyword(&mut self, keyword: &str) -> Result<(), Error> {
+        if self.peek_keyword(keyword) {
+            self.at += 1;
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected {}", keyword)))
+        }
+    }
+
+    fn word(&mut self) -> Result<String, Error> {
+        let word = self
+            .words
+            .get(self.at)
+            .cloned()
+            .ok_or_else(|| self.error("unexpected end"))?;
+        self.at += 1;
+        Ok(word)
+    }
+
+    fn rule(mut self) -> Result<Rule, Error> {
+        self.keyword("IF")?;
+        let condition = self.any()?;
+        self.keyword("THEN")?;
+        let mut consequences = Vec::new();
+        loop {
+            let output = find(&self.system.outputs, &self.word()?)?;
+            self.keyword("IS")?;
+            let set = set_index(&self.system.outputs[output], &self.word()?)?;
+            consequences.push((output, set));
+            if self.peek_keyword("AND") {
+                self.at += 1;
+            } else {
+                break;
+            }
+        }
+        if self.at < self.words.len() {
+            return Err(self.error("unexpected words after the consequences"));
+        }
+        Ok(Rule {
+            source: self.source.trim().into(),
+            condition,
+            consequences,
+        })
+    }
+
+    /// Operands joined by OR, kept flat so a long chain evaluates and drops
+    /// without recursing once per operand.
+    fn any(&mut self) -> Result<Condition, Error> {
+        let mut operands = vec![self.all()?];
+        while self.peek_keyword("OR") {
+            self.at += 1;
+            operands.push(self.all()?);
+        }
+        Ok(match operands.len() {
+            1 => operands.pop().unwrap(),
+            _ => Condition::Or(operands),
+        })
+    }
+
+    fn all(&mut self) -> Result<Condition, Error> {
+        let mut operands = vec![self.unary()?];
+        while self.peek_keyword("AND") {
+            self.at += 1;
+            operands.push(self.unary()?);
+        }
+        Ok(match operands.len() {
+            1 => operands.pop().unwrap(),
+            _ => Condition::And(operands),
+        })
+    }
+
+    fn unary(&mut self) -> Result<Condition, Error> {
+        if self.peek_keyword("NOT") || self.peek_keyword("(") {
+            if self.depth == MAX_DEPTH {
+                return Err(self.error("nested too deeply"));
+            }
+            self.depth += 1;
+            let condition = self.nested();
+            self.depth -= 1;
+            return condition;
+        }
+        let input = find(&self.system.inputs, &self.word()?)?;
+        self.keyword("IS")?;
+        let negated = self.peek_keyword("NOT");
+        if negated {
+            self.at += 1;
+        }
+        let set = set_index(&self.system.inputs[input], &self.word()?)?;
+        let condition = Condition::Is(input, set);
+        Ok(if negated {
+            Condition::Not(Box::new(condition))
+        } else {
+            condition
+        })
+    }
+
+    /// A NOT or a parenthesized condition, one level deeper than the caller.
+    fn nested(&mut self) -> Result<Condition, Error> {
+        if self.peek_keyword("NOT") {
+            self.at += 1;
+            return Ok(Condition::Not(Box::new(self.unary()?)));
+        }
+        self.keyword("(")?;
+        let condition = self.any()?;
+        self.keyword(")")?;
+        Ok(condition)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn system() -> FuzzySystem {
+        let mut system = FuzzySystem::new();
+        system.add_input("x", 0.0, 10.0).unwrap();
+        system.add_output("y", 0.0, 10.0).unwrap();
+        for variable in ["x", "y"] {
+            system.add_triangle(variable, "low", 0.0, 0.0, 5.0).unwrap();
+            system
+                .add_triangle(variable, "high", 5.0, 10.0, 10.0)
+                .unwrap();
+        }
+        system
+    }
+
+    #[test]
+    fn deep_nesting_is_an_error() {
+        let mut system = system();
+        let brackets = format!(
+            "IF {}x IS low{} THEN y IS low",
+            "(".repeat(100_000),
+            ")".repeat(100_000)
+        );
+        assert!(system.add_rule(&brackets).is_err());
+        let nots = format!("IF {}x IS low THEN y IS low", "NOT ".repeat(100_000));
+        assert!(system.add_rule(&nots).is_err());
+        assert!(system.add_rule("IF NOT (x IS low) THEN y IS high").is_ok());
+    }
+
+    #[test]
+    fn long_chains_do_not_nest() {
+        let mut system = system();
+        let rule = format!(
+            "IF x IS high{} THEN y IS high",
+            " AND x IS high OR x IS NOT low".repeat(50_000)
+        );
+        system.add_rule(&rule).unwrap();
+        system.set_input("x", 9.0).unwrap();
+        system.evaluate();
+        assert!(system.output("y").unwrap() > 5.0);
+    }
+}
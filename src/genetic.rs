@@ -0,0 +1,440 @@
+use js_sys::{Array, Float32Array, Function};
+use wasm_bindgen::prelude::*;
+
+use crate::error::Error;
+use crate::random::Rng;
+
+/// How a genome's genes are read.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Encoding {
+    /// Genes of 0 or 1.
+    Bits = 0,
+    /// Genes anywhere within the bounds.
+    Real = 1,
+    /// Every index from 0 to the gene count minus one exactly once, e.g. a tour.
+    Permutation = 2,
+}
+
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Selection {
+    /// The fittest of `tournament_size` random genomes.
+    Tournament = 0,
+    /// Chance in proportion to fitness above the generation's worst.
+    Roulette = 1,
+}
+
+/// How two parents combine. Permutations always use order crossover, which keeps
+/// every index exactly once.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Crossover {
+    OnePoint = 0,
+    TwoPoint = 1,
+    Uniform = 2,
+    /// A random blend of the parents per gene, slightly beyond either; bit
+    /// genomes fall back to `Uniform`.
+    Blend = 3,
+}
+
+/// A genetic algorithm over fixed-length genomes, for tuning parameters or
+/// searching level layouts. Score every genome of the generation with
+/// `evaluate`, `evaluate_batch` or `set_fitness`, then call `step` to breed the
+/// next one from the fittest.
+///
+/// Genomes are flat rows of `gene_count` numbers.
+#[wasm_bindgen]
+pub struct GeneticAlgorithm {
+    encoding: Encoding,
+    genes: usize,
+    size: usize,
+    pub selection: Selection,
+    pub crossover: Crossover,
+    /// Chance that a child has two parents rather than copying one.
+    pub crossover_rate: f32,
+    /// Chance that each gene of a child mutates: bits flip, real genes move by a
+    /// normal step of `mutation_scale` times the bounds' width, and permutation
+    /// genes swap places with another.
+    pub mutation_rate: f32,
+    pub mutation_scale: f32,
+    pub tournament_size: u32,
+    /// Fittest genomes copied unchanged into the next generation.
+    pub elitism: u32,
+    min: f32,
+    max: f32,
+    population: Vec<f32>,
+    fitness: Vec<f32>,
+    best: Vec<f32>,
+    best_fitness: f32,
+    generation: u32,
+    rng: Rng,
+}
+
+#[wasm_bindgen]
+impl GeneticAlgorithm {
+    /// A random first generation. Real genes start within `[0, 1]` until
+    /// `set_bounds` changes them.
+    #[wasm_bindgen(constructor)]
+    pub fn new(
+        encoding: Encoding,
+        gene_count: u32,
+        population_size: u32,
+        seed: u64,
+    ) -> Result<GeneticAlgorithm, Error> {
+        if gene_count == 0 || population_size == 0 {
+            return Err(Error::InvalidInput(
+                "gene count and population size must be positive".into(),
+            ));
+        }
+        let mut ga = GeneticAlgorithm {
+            encoding,
+            genes: gene_count as usize,
+            size: population_size as usize,
+            selection: Selection::Tournament,
+            crossover: Crossover::Uniform,
+            crossover_rate: 0.9,
+            mutation_rate: 1.0 / gene_count as f32,
+            mutation_scale: 0.1,
+            tournament_size: 3,
+            elitism: 1,
+            min: 0.0,
+            max: 1.0,
+            population: Vec::new(),
+            fitness: Vec::new(),
+            best: Vec::new(),
+            best_fitness: f32::NEG_INFINITY,
+            generation: 0,
+            rng: Rng::new(seed),
+        };
+        ga.reset();
+        Ok(ga)
+    }
+
+    /// Draws all further randomness from a copy of `rng`, e.g. before a `reset`.
+    pub fn set_rng(&mut self, rng: &Rng) {
+        self.rng = rng.clone();
+    }
+
+    /// Limits real genes to `[min, max]` and starts over with a random generation.
+    pub fn set_bounds(&mut self, min: f32, max: f32) -> Result<(), Error> {
+        if min < max {
+            (self.min, self.max) = (min, max);
+            self.reset();
+            Ok(())
+        } else {
+            Err(Error::InvalidInput(format!(
+                "bounds must have min below max, got {} and {}",
+                min, max
+            )))
+        }
+    }
+
+    /// Replaces the population with random genomes and forgets the best one.
+    pub fn reset(&mut self) {
+        self.population.clear();
+        for _ in 0..self.size {
+            match self.encoding {
+                Encoding::Bits => {
+                    for _ in 0..self.genes {
+                        let bit = self.rng.next_u32() & 1;
+                        self.population.push(bit as f32);
+                    }
+                }
+                Encoding::Real => {
+                    for _ in 0..self.genes {
+                        let gene = self.min + self.rng.next_f32() * (self.max - self.min);
+                        self.population.push(gene);
+                    }
+                }
+                Encoding::Permutation => {
+                    let start = self.population.len();
+                    self.population
+                        .extend((0..self.genes).map(|index| index as f32));
+                    let genome = &mut self.population[start..];
+                    for i in (1..genome.len()).rev() {
+                        genome.swap(i, self.rng.below(i + 1));
+                    }
+                }
+            }
+        }
+        self.fitness = vec![0.0; self.size];
+        self.best.clear();
+        self.best_fitness = f32::NEG_INFINITY;
+        self.generation = 0;
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn gene_count(&self) -> u32 {
+        self.genes as u32
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn len(&self) -> u32 {
+        self.size as u32
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn generation(&self) -> u32 {
+        self.generation
+    }
+
+    pub fn genome(&self, index: u32) -> Vec<f32> {
+        self.row(index as usize)
+            .map(<[f32]>::to_vec)
+            .unwrap_or_default()
+    }
+
+    /// Overwrites a genome, e.g. to seed the population with a known good one.
+    pub fn set_genome(&mut self, index: u32, genes: &[f32]) -> Result<(), Error> {
+        let index = index as usize;
+        if index >= self.size || genes.len() != self.genes {
+            return Err(Error::InvalidInput(format!(
+                "expected {} genes for one of {} genomes",
+                self.genes, self.size
+            )));
+        }
+        self.population[index * self.genes..][..self.genes].copy_from_slice(genes);
+        Ok(())
+    }
+
+    /// Every genome of the generation as one flat array.
+    pub fn genomes(&self) -> Vec<f32> {
+        self.population.clone()
+    }
+
+    pub fn set_fitness(&mut self, index: u32, fitness: f32) -> bool {
+        self.fitness
+            .get_mut(index as usize)
+            .map(|slot| *slot = fitness)
+            .is_some()
+    }
+
+    /// Sets every genome's fitness at once, in genome order.
+    pub fn set_fitnesses(&mut self, fitness: &[f32]) -> Result<(), Error> {
+        if fitness.len() != self.size {
+            return Err(Error::InvalidInput(format!(
+                "expected {} fitness values, got {}",
+                self.size,
+                fitness.len()
+            )));
+        }
+        self.fitness.copy_from_slice(fitness);
+        Ok(())
+    }
+
+    pub fn fitnesses(&self) -> Vec<f32> {
+        self.fitness.clone()
+    }
+
+    /// Scores every genome with `fitness(genes, index)`, where `genes` is a
+    /// `Float32Array`; higher is fitter.
+    pub fn evaluate(&mut self, fitness: &Function) -> Result<(), Error> {
+        for index in 0..self.size {
+            let genes = Float32Array::from(&self.population[index * self.genes..][..self.genes]);
+            let score = fitness
+                .call2(&JsValue::NULL, &genes, &JsValue::from(index as u32))
+                .ok()
+                .and_then(|value| value.as_f64())
+                .ok_or_else(|| {
+                    Error::InvalidInput(format!("fitness of genome {} is not a number", index))
+                })?;
+            self.fitness[index] = score as f32;
+        }
+        Ok(())
+    }
+
+    /// Scores the whole generation in one call of `fitness(genomes, gene_count)`,
+    /// which receives every genome as one flat `Float32Array` and returns an
+    /// array of scores in genome order.
+    pub fn evaluate_batch(&mut self, fitness: &Function) -> Result<(), Error> {
+        let genomes = Float32Array::from(&self.population[..]);
+        let scores = fitness
+            .call2(&JsValue::NULL, &genomes, &JsValue::from(self.genes as u32))
+            .map_err(|_| Error::InvalidInput("batch fitness threw".into()))?;
+        let scores: Vec<f32> = Array::from(&scores)
+            .iter()
+            .map(|score| score.as_f64().unwrap_or(f64::NAN) as f32)
+            .collect();
+        self.set_fitnesses(&scores)
+    }
+
+    /// The fittest genome of any generation stepped so far.
+    pub fn best(&self) -> Vec<f32> {
+        self.best.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn best_fitness(&self) -> f32 {
+        self.best_fitness
+    }
+
+    /// Replaces the scored generation with its children.
+    pub fn step(&mut self) {
+        let mut ranked: Vec<usize> = (0..self.size).collect();
+        ranked.sort_by(|&a, &b| self.fitness[b].total_cmp(&self.fitness[a]));
+        if self.fitness[ranked[0]] > self.best_fitness {
+            self.best_fitness = self.fitness[ranked[0]];
+            self.best = self.genome(ranked[0] as u32);
+        }
+
+        let mut next = Vec::with_capacity(self.population.len());
+        let elites = (self.elitism as usize).min(self.size);
+        for &index in &ranked[..elites] {
+            next.extend_from_slice(&self.population[index * self.genes..][..self.genes]);
+        }
+        let mut child = vec![0.0; self.genes];
+        for _ in elites..self.size {
+            let a = self.select();
+            if self.rng.chance(self.crossover_rate) {
+                let b = self.select();
+                self.cross(a, b, &mut child);
+            } else {
+                child.copy_from_slice(&self.population[a * self.genes..][..self.genes]);
+            }
+            self.mutate(&mut child);
+            next.extend_from_slice(&child);
+        }
+        self.population = next;
+        self.fitness.iter_mut().for_each(|fitness| *fitness = 0.0);
+        self.generation += 1;
+    }
+
+    /// Scores the generation with `fitness`, as in `evaluate`, and breeds the
+    /// next one.
+    pub fn evolve(&mut self, fitness: &Function) -> Result<(), Error> {
+        self.evaluate(fitness)?;
+        self.step();
+        Ok(())
+    }
+}
+
+impl GeneticAlgorithm {
+    /// Scores every genome with a Rust-side fitness function.
+    pub fn evaluate_with(&mut self, mut fitness: impl FnMut(&[f32]) -> f32) {
+        for (genes, score) in self
+            .population
+            .chunks_exact(self.genes)
+            .zip(&mut self.fitness)
+        {
+            *score = fitness(genes);
+        }
+    }
+
+    /// Scores the generation with a Rust-side fitness function and breeds
+    /// the next one.
+    pub fn evolve_with(&mut self, fitness: impl FnMut(&[f32]) -> f32) {
+        self.evaluate_with(fitness);
+        self.step();
+    }
+
+    fn row(&self, index: usize) -> Option<&[f32]> {
+        (index < self.size).then(|| &self.population[index * self.genes..][..self.genes])
+    }
+
+    fn select(&mut self) -> usize {
+        match self.selection {
+            Selection::Tournament => {
+                let mut best = self.rng.below(self.size);
+                for _ in 1..self.tournament_size.max(1) {
+                    let other = self.rng.below(self.size);
+                    if self.fitness[other] > self.fitness[best] {
+                        best = other;
+                    }
+                }
+                best
+            }
+            Selection::Roulette => {
+                let worst = self.fitness.iter().copied().fold(f32::INFINITY, f32::min);
+                let weight = |fitness: f32| fitness - worst + 1e-6;
+                let total: f32 = self.fitness.iter().map(|&fitness| weight(fitness)).sum();
+                let mut pick = self.rng.next_f32() * total;
+                for (index, &fitness) in self.fitness.iter().enumerate() {
+                    pick -= weight(fitness);
+                    if pick <= 0.0 {
+                        return index;
+                    }
+                }
+                self.size - 1
+            }
+        }
+    }
+
+    fn cross(&mut self, a: usize, b: usize, child: &mut [f32]) {
+        let n = self.genes;
+        let (a, b) = (
+            &self.population[a * n..][..n],
+            &self.population[b * n..][..n],
+        );
+        if self.encoding == Encoding::Permutation {
+            // Order crossover: a slice of `a` in place, the rest in `b`'s order.
+            let (mut start, mut end) = (self.rng.below(n), self.rng.below(n));
+            if start > end {
+                std::mem::swap(&mut start, &mut end);
+            }
+            let mut taken = vec![false; n];
+            for i in start..=end {
+                child[i] = a[i];
+                taken[a[i] as usize] = true;
+            }
+            let mut fill = b.iter().filter(|&&gene| !taken[gene as usize]);
+            for (i, slot) in child.iter_mut().enumerate() {
+                if !(start..=end).contains(&i) {
+                    *slot = *fill.next().unwrap_or(&0.0);
+                }
+            }
+            return;
+        }
+        let crossover = match self.crossover {
+            Crossover::Blend if self.encoding == Encoding::Bits => Crossover::Uniform,
+            crossover => crossover,
+        };
+        match crossover {
+            Crossover::OnePoint => {
+                let cut = self.rng.below(n + 1);
+                child[..cut].copy_from_slice(&a[..cut]);
+                child[cut..].copy_from_slice(&b[cut..]);
+            }
+            Crossover::TwoPoint => {
+                let (mut start, mut end) = (self.rng.below(n + 1), self.rng.below(n + 1));
+                if start > end {
+                    std::mem::swap(&mut start, &mut end);
+                }
+                child.copy_from_slice(a);
+                child[start..end].copy_from_slice(&b[start..end]);
+            }
+            Crossover::Uniform => {
+                for ((slot, &x), &y) in child.iter_mut().zip(a).zip(b) {
+                    *slot = if self.rng.chance(0.5) { x } else { y };
+                }
+            }
+            Crossover::Blend => {
+                for ((slot, &x), &y) in child.iter_mut().zip(a).zip(b) {
+                    let t = self.rng.next_f32() * 1.5 - 0.25;
+                    *slot = (x + (y - x) * t).clamp(self.min, self.max);
+                }
+            }
+        }
+    }
+
+    fn mutate(&mut self, child: &mut [f32]) {
+        for i in 0..child.len() {
+            if !self.rng.chance(self.mutation_rate) {
+                continue;
+            }
+            match self.encoding {
+                Encoding::Bits => child[i] = 1.0 - child[i],
+                Encoding::Real => {
+                    let step =
+                        self.rng.next_gaussian() * self.mutation_scale * (self.max - self.min);
+                    child[i] = (child[i] + step).clamp(self.min, self.max);
+                }
+                Encoding::Permutation => child.swap(i, self.rng.below(child.len())),
+            }
+        }
+    }
+}
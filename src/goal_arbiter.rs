@@ -0,0 +1,276 @@
+use js_sys::Function;
+use wasm_bindgen::prelude::*;
+
+use crate::error::Error;
+use crate::events::{AiEvent, EventKind};
+use crate::state_machine::Guard;
+
+/// Called on entering a goal with whether it is resumed after an
+/// interruption, and on leaving one with whether it is suspended to resume
+/// later.
+pub type GoalHook = Box<dyn FnMut(bool)>;
+
+struct Goal {
+    name: String,
+    priority: f32,
+    condition: Option<Guard>,
+    on_enter: Option<GoalHook>,
+    on_exit: Option<GoalHook>,
+    interruptible: bool,
+    resumable: bool,
+    posted: bool,
+    suspended: bool,
+}
+
+/// Picks which of an agent's goals to pursue, e.g. Flee over Attack over
+/// Patrol over Idle, so that each goal's behavior tree or state machine only
+/// has to pursue it and not also decide when something else matters more.
+///
+/// A goal wants to run while it is posted with `post`, until `finish`, or
+/// while its condition holds. Each `update` runs the one with the highest
+/// priority among those; ties keep the current goal, then go to the first
+/// added. A higher goal interrupts the current one unless that is marked not
+/// interruptible, in which case it waits for the current goal to finish or
+/// its condition to fail. An interrupted goal is suspended and entered again,
+/// as resumed, once nothing above it wants to run; one marked not resumable
+/// is dropped instead, and must be posted again or have its condition hold.
+#[wasm_bindgen]
+#[derive(Default)]
+pub struct GoalArbiter {
+    goals: Vec<Goal>,
+    current: Option<usize>,
+    time_in_goal: f32,
+    events: Vec<AiEvent>,
+}
+
+#[wasm_bindgen]
+impl GoalArbiter {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> GoalArbiter {
+        GoalArbiter::default()
+    }
+
+    /// Registers a goal. `condition`, if given, is called each `update` and
+    /// makes the goal want to run while it returns true. `on_enter` and
+    /// `on_exit` receive whether the goal is being resumed or suspended.
+    pub fn add_goal(
+        &mut self,
+        name: &str,
+        priority: f32,
+        condition: Option<Function>,
+        on_enter: Option<Function>,
+        on_exit: Option<Function>,
+    ) -> Result<(), Error> {
+        self.add_goal_with(
+            name,
+            priority,
+            condition.map(js_guard),
+            on_enter.map(js_hook),
+            on_exit.map(js_hook),
+        )
+    }
+
+    /// Changes a goal's priority, e.g. as a threat grows; takes effect at the
+    /// next `update`.
+    pub fn set_priority(&mut self, name: &str, priority: f32) -> Result<(), Error> {
+        let index = self.find(name)?;
+        self.goals[index].priority = priority;
+        Ok(())
+    }
+
+    /// Whether a higher goal may take over while this one runs. Defaults to
+    /// true.
+    pub fn set_interruptible(&mut self, name: &str, interruptible: bool) -> Result<(), Error> {
+        let index = self.find(name)?;
+        self.goals[index].interruptible = interruptible;
+        Ok(())
+    }
+
+    /// Whether this goal is suspended and later resumed when interrupted,
+    /// rather than dropped. Defaults to true.
+    pub fn set_resumable(&mut self, name: &str, resumable: bool) -> Result<(), Error> {
+        let index = self.find(name)?;
+        self.goals[index].resumable = resumable;
+        Ok(())
+    }
+
+    /// Makes a goal want to run until `finish`.
+    pub fn post(&mut self, name: &str) -> Result<(), Error> {
+        let index = self.find(name)?;
+        self.goals[index].posted = true;
+        Ok(())
+    }
+
+    /// Withdraws a posted goal, leaving it at once if it is running, and
+    /// forgets that it was suspended. A goal with a condition still runs again
+    /// while the condition holds.
+    pub fn finish(&mut self, name: &str) -> Result<(), Error> {
+        let index = self.find(name)?;
+        let goal = &mut self.goals[index];
+        goal.posted = false;
+        goal.suspended = false;
+        if self.current == Some(index) {
+            self.leave(false);
+        }
+        Ok(())
+    }
+
+    pub fn current_goal(&self) -> Option<String> {
+        self.current.map(|index| self.goals[index].name.clone())
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn time_in_goal(&self) -> f32 {
+        self.time_in_goal
+    }
+
+    pub fn is_suspended(&self, name: &str) -> bool {
+        self.find(name)
+            .is_ok_and(|index| self.goals[index].suspended)
+    }
+
+    /// Names of the suspended goals, the first to resume first.
+    pub fn suspended(&self) -> Vec<String> {
+        let mut suspended: Vec<&Goal> = self.goals.iter().filter(|goal| goal.suspended).collect();
+        suspended.sort_by(|a, b| b.priority.total_cmp(&a.priority));
+        suspended.iter().map(|goal| goal.name.clone()).collect()
+    }
+
+    /// Name of the goal added `index`th, as `StateChanged` events report it.
+    pub fn goal_name(&self, index: u32) -> Option<String> {
+        self.goals.get(index as usize).map(|goal| goal.name.clone())
+    }
+
+    /// Goals entered since the last `update` began, as `StateChanged` events
+    /// with a subject of 0.
+    pub fn events(&self) -> Vec<AiEvent> {
+        self.events.clone()
+    }
+
+    /// Checks every condition, switches to the goal that should run if it is
+    /// not the current one, and advances `time_in_goal` by `dt` seconds.
+    pub fn update(&mut self, dt: f32) {
+        self.events.clear();
+        let wants: Vec<bool> = self
+            .goals
+            .iter_mut()
+            .map(|goal| goal.posted || goal.condition.as_mut().is_some_and(|condition| condition()))
+            .collect();
+        for (goal, &wants) in self.goals.iter_mut().zip(&wants) {
+            if !wants {
+                goal.suspended = false;
+            }
+        }
+
+        let mut best = self.current.filter(|&current| wants[current]);
+        for (index, goal) in self.goals.iter().enumerate() {
+            if wants[index] && best.is_none_or(|best| goal.priority > self.goals[best].priority) {
+                best = Some(index);
+            }
+        }
+
+        if best != self.current {
+            match self.current {
+                Some(current) if wants[current] => {
+                    if self.goals[current].interruptible {
+                        self.leave(true);
+                        self.enter(best);
+                    }
+                }
+                Some(_) => {
+                    self.leave(false);
+                    self.enter(best);
+                }
+                None => self.enter(best),
+            }
+        }
+        if self.current.is_some() {
+            self.time_in_goal += dt;
+        }
+    }
+}
+
+impl GoalArbiter {
+    pub fn add_goal_with(
+        &mut self,
+        name: &str,
+        priority: f32,
+        condition: Option<Guard>,
+        on_enter: Option<GoalHook>,
+        on_exit: Option<GoalHook>,
+    ) -> Result<(), Error> {
+        if self.goals.iter().any(|goal| goal.name == name) {
+            return Err(Error::InvalidInput(format!(
+                "goal '{}' already exists",
+                name
+            )));
+        }
+        self.goals.push(Goal {
+            name: name.to_string(),
+            priority,
+            condition,
+            on_enter,
+            on_exit,
+            interruptible: true,
+            resumable: true,
+            posted: false,
+            suspended: false,
+        });
+        Ok(())
+    }
+
+    fn find(&self, name: &str) -> Result<usize, Error> {
+        self.goals
+            .iter()
+            .position(|goal| goal.name == name)
+            .ok_or_else(|| Error::InvalidInput(format!("unknown goal '{}'", name)))
+    }
+
+    /// Exits the current goal, suspending it if it was `interrupted` and can
+    /// resume, and dropping its post if it cannot.
+    fn leave(&mut self, interrupted: bool) {
+        let Some(current) = self.current.take() else {
+            return;
+        };
+        let goal = &mut self.goals[current];
+        let suspend = interrupted && goal.resumable;
+        if interrupted && !goal.resumable {
+            goal.posted = false;
+        }
+        goal.suspended = suspend;
+        if let Some(on_exit) = goal.on_exit.as_mut() {
+            on_exit(suspend);
+        }
+        self.time_in_goal = 0.0;
+    }
+
+    fn enter(&mut self, index: Option<usize>) {
+        self.current = index;
+        self.time_in_goal = 0.0;
+        let Some(index) = index else {
+            return;
+        };
+        let goal = &mut self.goals[index];
+        let resumed = std::mem::take(&mut goal.suspended);
+        self.events
+            .push(AiEvent::new(EventKind::StateChanged, 0, index as u32));
+        if let Some(on_enter) = goal.on_enter.as_mut() {
+            on_enter(resumed);
+        }
+    }
+}
+
+fn js_hook(callback: Function) -> GoalHook {
+    Box::new(move |flag| {
+        let _ = callback.call1(&JsValue::NULL, &JsValue::from_bool(flag));
+    })
+}
+
+fn js_guard(callback: Function) -> Guard {
+    Box::new(move || {
+        callback
+            .call0(&JsValue::NULL)
+            .map(|value| value.is_truthy())
+            .unwrap_or(false)
+    })
+}
@@ -0,0 +1,162 @@
+use std::collections::{BinaryHeap, HashMap};
+
+use wasm_bindgen::prelude::*;
+
+use crate::search::{self, OpenNode};
+
+struct Action {
+    name: String,
+    cost: f32,
+    pre_mask: u32,
+    pre_values: u32,
+    effect_mask: u32,
+    effect_values: u32,
+}
+
+impl Action {
+    fn applies(&self, state: u32) -> bool {
+        state & self.pre_mask == self.pre_values & self.pre_mask
+    }
+
+    fn apply(&self, state: u32) -> u32 {
+        (state & !self.effect_mask) | (self.effect_values & self.effect_mask)
+    }
+}
+
+/// Goal-oriented action planner. World state is a bitset of up to 32 facts; each
+/// condition is a `(mask, values)` pair where only the bits in `mask` matter.
+#[wasm_bindgen]
+pub struct Planner {
+    actions: Vec<Action>,
+    pub max_expansions: u32,
+}
+
+impl Default for Planner {
+    fn default() -> Planner {
+        Planner {
+            actions: Vec::new(),
+            max_expansions: 10_000,
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl Planner {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Planner {
+        Planner::default()
+    }
+
+    /// Registers an action and returns its id. Costs must be positive.
+    pub fn add_action(
+        &mut self,
+        name: &str,
+        cost: f32,
+        pre_mask: u32,
+        pre_values: u32,
+        effect_mask: u32,
+        effect_values: u32,
+    ) -> u32 {
+        self.actions.push(Action {
+            name: name.to_string(),
+            cost: cost.max(f32::EPSILON),
+            pre_mask,
+            pre_values,
+            effect_mask,
+            effect_values,
+        });
+        (self.actions.len() - 1) as u32
+    }
+
+    pub fn action_name(&self, id: u32) -> Option<String> {
+        self.actions
+            .get(id as usize)
+            .map(|action| action.name.clone())
+    }
+
+    /// Returns the cheapest ordered list of action ids that takes `world_state` to a
+    /// state matching the goal, an empty list if it already matches, or `undefined`
+    /// when no plan is found within `max_expansions`.
+    pub fn plan(&self, world_state: u32, goal_mask: u32, goal_values: u32) -> Option<Vec<u32>> {
+        let goal_values = goal_values & goal_mask;
+        let satisfied = |state: u32| state & goal_mask == goal_values;
+
+        // Each action fixes at most `max_bits` goal bits for at least `min_cost`, which
+        // keeps the heuristic admissible.
+        let min_cost = self
+            .actions
+            .iter()
+            .map(|action| action.cost)
+            .reduce(f32::min)
+            .unwrap_or(0.0);
+        let max_bits = self
+            .actions
+            .iter()
+            .map(|action| (action.effect_mask & goal_mask).count_ones())
+            .max()
+            .unwrap_or(0)
+            .max(1);
+        let heuristic = |state: u32| {
+            let unsatisfied = ((state & goal_mask) ^ goal_values).count_ones();
+            unsatisfied.div_ceil(max_bits) as f32 * min_cost
+        };
+
+        let mut states = vec![world_state];
+        let mut g = vec![0.0f32];
+        let mut parent = vec![usize::MAX];
+        let mut via = vec![u32::MAX];
+        let mut closed = vec![false];
+        let mut seen = HashMap::new();
+        seen.insert(world_state, 0);
+
+        let mut open = BinaryHeap::new();
+        open.push(OpenNode {
+            cost: heuristic(world_state),
+            index: 0,
+        });
+
+        let mut expansions = 0;
+        while let Some(OpenNode { index, .. }) = open.pop() {
+            if satisfied(states[index]) {
+                let nodes = search::reconstruct(&parent, index);
+                return Some(nodes[1..].iter().map(|&node| via[node]).collect());
+            }
+            if closed[index] {
+                continue;
+            }
+            closed[index] = true;
+            expansions += 1;
+            if expansions > self.max_expansions {
+                return None;
+            }
+
+            let state = states[index];
+            for (id, action) in self.actions.iter().enumerate() {
+                if !action.applies(state) {
+                    continue;
+                }
+                let next = action.apply(state);
+                let tentative = g[index] + action.cost;
+                let next_index = *seen.entry(next).or_insert_with(|| {
+                    states.push(next);
+                    g.push(f32::INFINITY);
+                    parent.push(usize::MAX);
+                    via.push(u32::MAX);
+                    closed.push(false);
+                    states.len() - 1
+                });
+                if !closed[next_index] && tentative < g[next_index] {
+                    g[next_index] = tentative;
+                    parent[next_index] = index;
+                    via[next_index] = id as u32;
+                    open.push(OpenNode {
+                        cost: tentative + heuristic(next),
+                        index: next_index,
+                    });
+                }
+            }
+        }
+
+        None
+    }
+}
@@ -0,0 +1,167 @@
+use js_sys::Function;
+use wasm_bindgen::prelude::*;
+
+use crate::error::Error;
+use crate::search;
+
+/// A directed graph of numbered nodes and weighted edges for pathfinding over
+/// abstract spaces such as room graphs or waypoint networks. Nodes are numbered
+/// from 0 in the order they are added.
+#[wasm_bindgen]
+#[derive(Clone, Default)]
+pub struct Graph {
+    edges: Vec<Vec<(usize, f32)>>,
+}
+
+#[wasm_bindgen]
+impl Graph {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Graph {
+        Graph::default()
+    }
+
+    /// Builds a graph of `node_count` nodes from parallel edge arrays, as produced
+    /// by level tooling.
+    pub fn from_edges(
+        node_count: u32,
+        from: &[u32],
+        to: &[u32],
+        costs: &[f32],
+    ) -> Result<Graph, Error> {
+        let mut graph = Graph::new();
+        graph.add_nodes(node_count);
+        graph.add_edges(from, to, costs)?;
+        Ok(graph)
+    }
+
+    /// Adds a node and returns its id.
+    pub fn add_node(&mut self) -> u32 {
+        self.edges.push(Vec::new());
+        self.edges.len() as u32 - 1
+    }
+
+    /// Adds `count` nodes and returns the id of the first.
+    pub fn add_nodes(&mut self, count: u32) -> u32 {
+        let first = self.edges.len() as u32;
+        self.edges
+            .resize_with(self.edges.len() + count as usize, Vec::new);
+        first
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn node_count(&self) -> u32 {
+        self.edges.len() as u32
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn edge_count(&self) -> u32 {
+        self.edges.iter().map(|edges| edges.len() as u32).sum()
+    }
+
+    /// Adds a one-way edge. Costs must be finite and non-negative. Call twice with
+    /// the endpoints swapped for a two-way connection.
+    pub fn add_edge(&mut self, from: u32, to: u32, cost: f32) -> Result<(), Error> {
+        self.validate_edge(from, to, cost)?;
+        self.edges[from as usize].push((to as usize, cost));
+        Ok(())
+    }
+
+    /// Adds one edge per index of the parallel arrays. Nothing is added if any edge
+    /// is invalid.
+    pub fn add_edges(&mut self, from: &[u32], to: &[u32], costs: &[f32]) -> Result<(), Error> {
+        if from.len() != to.len() || from.len() != costs.len() {
+            return Err(Error::InvalidInput(
+                "edge arrays must have the same length".into(),
+            ));
+        }
+        for i in 0..from.len() {
+            self.validate_edge(from[i], to[i], costs[i])?;
+        }
+        for i in 0..from.len() {
+            self.edges[from[i] as usize].push((to[i] as usize, costs[i]));
+        }
+        Ok(())
+    }
+
+    /// Removes every edge from `from` to `to`. Returns whether any existed.
+    pub fn remove_edge(&mut self, from: u32, to: u32) -> bool {
+        let Some(edges) = self.edges.get_mut(from as usize) else {
+            return false;
+        };
+        let before = edges.len();
+        edges.retain(|&(next, _)| next != to as usize);
+        edges.len() != before
+    }
+
+    /// Finds a cheapest path and returns its node ids, including both endpoints, or
+    /// an empty array when there is none. `heuristic(node, goal)` estimates the
+    /// remaining cost and must never overestimate it; without one this is Dijkstra.
+    pub fn find_path(&self, start: u32, goal: u32, heuristic: Option<Function>) -> Vec<u32> {
+        match heuristic {
+            Some(heuristic) => self.find_path_with(start, goal, |node| {
+                heuristic
+                    .call2(&JsValue::NULL, &JsValue::from(node), &JsValue::from(goal))
+                    .ok()
+                    .and_then(|value| value.as_f64())
+                    .unwrap_or(0.0) as f32
+            }),
+            None => self.find_path_with(start, goal, |_| 0.0),
+        }
+    }
+
+    /// Cost of the cheapest path from `source` to every node, `Infinity` where
+    /// unreachable.
+    pub fn distances(&self, source: u32) -> Vec<f32> {
+        if source as usize >= self.edges.len() {
+            return vec![f32::INFINITY; self.edges.len()];
+        }
+        search::dijkstra(self.edges.len(), &[source as usize], |index, out| {
+            out.extend_from_slice(&self.edges[index])
+        })
+    }
+}
+
+impl Graph {
+    /// Like `find_path`, with a Rust heuristic taking the node id.
+    pub fn find_path_with(
+        &self,
+        start: u32,
+        goal: u32,
+        heuristic: impl Fn(u32) -> f32,
+    ) -> Vec<u32> {
+        let node_count = self.edges.len();
+        if start as usize >= node_count || goal as usize >= node_count {
+            return Vec::new();
+        }
+        let path = search::astar(
+            node_count,
+            start as usize,
+            goal as usize,
+            |index, out| out.extend_from_slice(&self.edges[index]),
+            |index| heuristic(index as u32),
+        );
+        path.map_or_else(Vec::new, |path| {
+            path.into_iter().map(|index| index as u32).collect()
+        })
+    }
+
+    pub(crate) fn neighbors(&self, index: usize, out: &mut Vec<(usize, f32)>) {
+        out.extend_from_slice(&self.edges[index]);
+    }
+
+    fn validate_edge(&self, from: u32, to: u32, cost: f32) -> Result<(), Error> {
+        for node in [from, to] {
+            if node as usize >= self.edges.len() {
+                return Err(Error::InvalidInput(format!("node {} does not exist", node)));
+            }
+        }
+        if cost >= 0.0 && cost.is_finite() {
+            Ok(())
+        } else {
+            Err(Error::InvalidInput(format!(
+                "edge cost must be finite and non-negative, got {}",
+                cost
+            )))
+        }
+    }
+}
@@ -0,0 +1,338 @@
+use std::sync::OnceLock;
+
+use js_sys::Promise;
+use wasm_bindgen::prelude::*;
+
+use crate::error::Error;
+use crate::links::OffMeshLink;
+use crate::nav_filter::NavFilter;
+use crate::terrain::{TerrainCosts, TERRAIN_TYPES};
+use crate::{search, task};
+
+const SQRT_2: f32 = std::f32::consts::SQRT_2;
+
+/// Runtime edits kept for incremental replanning; older ones force a full replan.
+const MAX_CHANGES: usize = 4096;
+
+const DIRECTIONS: [(i32, i32); 8] = [
+    (1, 0),
+    (-1, 0),
+    (0, 1),
+    (0, -1),
+    (1, 1),
+    (1, -1),
+    (-1, 1),
+    (-1, -1),
+];
+
+/// Per-query restrictions on which cells an agent may enter and what they cost.
+#[derive(Clone, Copy, Default)]
+pub(crate) struct Traversal<'a> {
+    pub costs: Option<&'a TerrainCosts>,
+    /// Minimum clearance a cell needs, see `Grid::clearance`.
+    pub radius: f32,
+    /// Area flags the agent may enter; its costs go in `costs`.
+    pub filter: Option<&'a NavFilter>,
+}
+
+impl<'a> Traversal<'a> {
+    pub(crate) fn filtered(filter: &'a NavFilter) -> Traversal<'a> {
+        Traversal {
+            costs: Some(filter.costs()),
+            filter: Some(filter),
+            ..Traversal::default()
+        }
+    }
+}
+
+/// A rectangular tile map of walkable and blocked cells, with an optional terrain
+/// type per cell that scales movement cost.
+#[wasm_bindgen]
+#[derive(Clone)]
+pub struct Grid {
+    width: u32,
+    height: u32,
+    walkable: Vec<bool>,
+    pub(crate) terrain: Vec<u8>,
+    pub(crate) terrain_costs: Vec<f32>,
+    /// Area flags of each cell, for `NavFilter`s.
+    pub(crate) flags: Vec<u32>,
+    /// Per-cell multipliers from `set_obstacle_costs`, empty when there are none.
+    pub(crate) obstacle_costs: Vec<f32>,
+    clearance: OnceLock<Vec<f32>>,
+    changes: Vec<usize>,
+    changes_base: u32,
+    pub(crate) off_mesh_links: Vec<OffMeshLink<usize>>,
+    pub(crate) next_link: u32,
+}
+
+#[wasm_bindgen]
+impl Grid {
+    /// Creates a grid where every cell is walkable. Fails when the cells do not
+    /// fit in a `u32` index or a side in an `i32` coordinate.
+    #[wasm_bindgen(constructor)]
+    pub fn new(width: u32, height: u32) -> Result<Grid, Error> {
+        let cells = width
+            .checked_mul(height)
+            .filter(|_| width <= i32::MAX as u32 && height <= i32::MAX as u32)
+            .ok_or_else(|| {
+                Error::InvalidInput(format!("a {} by {} grid is too large", width, height))
+            })? as usize;
+        Ok(Grid {
+            width,
+            height,
+            walkable: vec![true; cells],
+            terrain: vec![0; cells],
+            terrain_costs: vec![1.0; TERRAIN_TYPES],
+            flags: vec![0; cells],
+            obstacle_costs: Vec::new(),
+            clearance: OnceLock::new(),
+            changes: Vec::new(),
+            changes_base: 0,
+            off_mesh_links: Vec::new(),
+            next_link: 0,
+        })
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Marks a cell as walkable or blocked. Out-of-bounds cells are ignored.
+    pub fn set_walkable(&mut self, x: u32, y: u32, walkable: bool) {
+        if x < self.width && y < self.height {
+            let index = self.index(x as i32, y as i32);
+            self.walkable[index] = walkable;
+            self.clearance.take();
+        }
+    }
+
+    /// Like `set_walkable`, but records the edit so live `Path`s can repair around it.
+    /// Returns whether the cell actually changed.
+    pub fn update_cell(&mut self, x: u32, y: u32, walkable: bool) -> bool {
+        if x >= self.width || y >= self.height {
+            return false;
+        }
+        let index = self.index(x as i32, y as i32);
+        if self.walkable[index] == walkable {
+            return false;
+        }
+        self.walkable[index] = walkable;
+        self.clearance.take();
+        self.record_change(index);
+        true
+    }
+
+    /// Returns false for blocked and out-of-bounds cells.
+    pub fn is_walkable(&self, x: i32, y: i32) -> bool {
+        self.in_bounds(x, y) && self.walkable[self.index(x, y)]
+    }
+
+    /// Finds a shortest 8-connected path and returns it as flat `[x0, y0, x1, y1, ...]`
+    /// cell coordinates, including both endpoints. Diagonal moves may not cut corners.
+    /// Returns an empty array when no path exists.
+    pub fn find_path(&self, start_x: u32, start_y: u32, end_x: u32, end_y: u32) -> Vec<f32> {
+        self.find_weighted_path(start_x, start_y, end_x, end_y, Traversal::default())
+    }
+
+    /// Like `find_path`, but returns a `Promise<Float32Array>` instead of blocking.
+    /// The search runs on a snapshot of the grid taken at call time.
+    pub fn find_path_async(&self, start_x: u32, start_y: u32, end_x: u32, end_y: u32) -> Promise {
+        let grid = self.clone();
+        task::spawn_path(move || grid.find_path(start_x, start_y, end_x, end_y))
+    }
+}
+
+impl Grid {
+    pub(crate) fn find_weighted_path(
+        &self,
+        start_x: u32,
+        start_y: u32,
+        end_x: u32,
+        end_y: u32,
+        traversal: Traversal,
+    ) -> Vec<f32> {
+        let (sx, sy, ex, ey) = (start_x as i32, start_y as i32, end_x as i32, end_y as i32);
+        if !self.is_passable(sx, sy, traversal) || !self.is_passable(ex, ey, traversal) {
+            return Vec::new();
+        }
+
+        let scale = self.heuristic_scale(traversal);
+        let path = search::astar(
+            self.walkable.len(),
+            self.index(sx, sy),
+            self.index(ex, ey),
+            |index, out| self.neighbors_for(index, traversal, out),
+            |index| {
+                let (x, y) = self.coords(index);
+                octile(x - ex, y - ey) * scale
+            },
+        );
+
+        match path {
+            Some(path) => self.to_waypoints(&path),
+            None => Vec::new(),
+        }
+    }
+
+    pub(crate) fn cell_count(&self) -> usize {
+        self.walkable.len()
+    }
+
+    pub(crate) fn in_bounds(&self, x: i32, y: i32) -> bool {
+        x >= 0 && y >= 0 && (x as u32) < self.width && (y as u32) < self.height
+    }
+
+    pub(crate) fn index(&self, x: i32, y: i32) -> usize {
+        (y as u32 * self.width + x as u32) as usize
+    }
+
+    pub(crate) fn coords(&self, index: usize) -> (i32, i32) {
+        let index = index as u32;
+        ((index % self.width) as i32, (index / self.width) as i32)
+    }
+
+    pub(crate) fn record_change(&mut self, index: usize) {
+        if self.changes.len() >= MAX_CHANGES {
+            let dropped = MAX_CHANGES / 2;
+            self.changes.drain(..dropped);
+            self.changes_base += dropped as u32;
+        }
+        self.changes.push(index);
+    }
+
+    /// Counter advanced by every recorded edit.
+    pub(crate) fn revision(&self) -> u32 {
+        self.changes_base + self.changes.len() as u32
+    }
+
+    /// Cells edited since `revision`, or `None` if that history has been discarded.
+    pub(crate) fn changes_since(&self, revision: u32) -> Option<&[usize]> {
+        if revision < self.changes_base {
+            return None;
+        }
+        self.changes.get((revision - self.changes_base) as usize..)
+    }
+
+    pub(crate) fn neighbors(&self, index: usize, out: &mut Vec<(usize, f32)>) {
+        self.neighbors_for(index, Traversal::default(), out);
+    }
+
+    /// Like `neighbors`, restricted and weighted for a particular agent.
+    pub(crate) fn neighbors_for(
+        &self,
+        index: usize,
+        traversal: Traversal,
+        out: &mut Vec<(usize, f32)>,
+    ) {
+        let (x, y) = self.coords(index);
+        for &(dx, dy) in &DIRECTIONS {
+            let (nx, ny) = (x + dx, y + dy);
+            if !self.is_passable(nx, ny, traversal) {
+                continue;
+            }
+            if dx != 0
+                && dy != 0
+                && (!self.is_passable(x + dx, y, traversal)
+                    || !self.is_passable(x, y + dy, traversal))
+            {
+                continue;
+            }
+            let next = self.index(nx, ny);
+            out.push((next, self.step_cost_for(index, next, traversal)));
+        }
+    }
+
+    /// Walkable, wide enough for the agent, allowed by its filter, and not made
+    /// impassable by an infinite terrain cost.
+    pub(crate) fn is_passable(&self, x: i32, y: i32, traversal: Traversal) -> bool {
+        if !self.is_walkable(x, y) {
+            return false;
+        }
+        let index = self.index(x, y);
+        (traversal.radius <= 0.0 || self.clearance_cache()[index] >= traversal.radius)
+            && traversal
+                .filter
+                .is_none_or(|filter| filter.allows(self.flags[index]))
+            && self.cell_cost(index, traversal).is_finite()
+    }
+
+    /// Movement cost per unit distance through a cell.
+    pub(crate) fn cell_cost(&self, index: usize, traversal: Traversal) -> f32 {
+        let terrain = self.terrain[index];
+        let base = self.terrain_costs[terrain as usize]
+            * self.obstacle_costs.get(index).copied().unwrap_or(1.0);
+        match traversal.costs {
+            Some(costs) => base * costs.get(terrain),
+            None => base,
+        }
+    }
+
+    /// Cost of moving between two adjacent cells: the step length times the mean
+    /// of both cells' terrain costs, so edges cost the same in either direction.
+    pub(crate) fn step_cost(&self, from: usize, to: usize) -> f32 {
+        self.step_cost_for(from, to, Traversal::default())
+    }
+
+    fn step_cost_for(&self, from: usize, to: usize, traversal: Traversal) -> f32 {
+        let (fx, fy) = self.coords(from);
+        let (tx, ty) = self.coords(to);
+        let length = if fx != tx && fy != ty { SQRT_2 } else { 1.0 };
+        length * 0.5 * (self.cell_cost(from, traversal) + self.cell_cost(to, traversal))
+    }
+
+    /// Cheapest cost per unit distance, so that `octile` times this never
+    /// overestimates the remaining cost.
+    pub(crate) fn heuristic_scale(&self, traversal: Traversal) -> f32 {
+        (0..TERRAIN_TYPES)
+            .map(|terrain| {
+                let base = self.terrain_costs[terrain];
+                match traversal.costs {
+                    Some(costs) => base * costs.get(terrain as u8),
+                    None => base,
+                }
+            })
+            .reduce(f32::min)
+            .unwrap_or(1.0)
+    }
+
+    /// Per-cell clearance, computed on first use after the layout changes.
+    pub(crate) fn clearance_cache(&self) -> &[f32] {
+        self.clearance.get_or_init(|| self.compute_clearance())
+    }
+
+    /// Whether every step costs exactly its length, as Jump Point Search requires.
+    /// Also rules out infinite costs, which make walkable cells impassable.
+    pub(crate) fn has_uniform_costs(&self) -> bool {
+        self.terrain_costs.iter().all(|&cost| cost == 1.0) && self.obstacle_costs.is_empty()
+    }
+
+    /// Total cost of a cell path produced by the search routines.
+    pub(crate) fn path_cost(&self, path: &[usize]) -> f32 {
+        path.windows(2)
+            .map(|pair| self.step_cost(pair[0], pair[1]))
+            .sum()
+    }
+
+    pub(crate) fn to_waypoints(&self, path: &[usize]) -> Vec<f32> {
+        let mut waypoints = Vec::with_capacity(path.len() * 2);
+        for &index in path {
+            let (x, y) = self.coords(index);
+            waypoints.push(x as f32);
+            waypoints.push(y as f32);
+        }
+        waypoints
+    }
+}
+
+/// Exact distance on an 8-connected grid with unit orthogonal and `sqrt(2)` diagonal steps.
+pub(crate) fn octile(dx: i32, dy: i32) -> f32 {
+    let (dx, dy) = (dx.unsigned_abs() as f32, dy.unsigned_abs() as f32);
+    dx.max(dy) + (SQRT_2 - 1.0) * dx.min(dy)
+}
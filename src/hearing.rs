@@ -0,0 +1,170 @@
+use std::f32::consts::SQRT_2;
+
+use wasm_bindgen::prelude::*;
+
+use crate::grid::Grid;
+use crate::math::Vec2;
+use crate::perception::{segments_cross, Perception, Stimulus};
+use crate::search::dijkstra;
+
+#[wasm_bindgen]
+impl Perception {
+    /// Emits a sound at `position`, delivered to observers on the next `update`.
+    /// Loudness is the distance it carries in the open; every wall cell or
+    /// occluder on its way costs another `wall_damping`. `source` identifies the
+    /// emitter, e.g. an entity id.
+    pub fn emit_sound(&mut self, source: u32, position: Vec2, loudness: f32) {
+        if loudness > 0.0 {
+            self.sounds.push(Stimulus {
+                source,
+                position,
+                strength: loudness,
+            });
+        }
+    }
+
+    /// Scales the loudness of every sound the observer hears; 0 makes it deaf.
+    pub fn set_hearing(&mut self, observer: u32, sensitivity: f32) -> bool {
+        self.observers
+            .get_mut(&observer)
+            .map(|observer| observer.hearing = sensitivity.max(0.0))
+            .is_some()
+    }
+
+    /// Sounds the observer heard in the last `update`, loudest first, each with
+    /// the loudness left when it arrived.
+    pub fn heard(&self, observer: u32) -> Vec<Stimulus> {
+        self.observers
+            .get(&observer)
+            .map(|observer| observer.heard.clone())
+            .unwrap_or_default()
+    }
+}
+
+impl Perception {
+    /// Propagates the pending sounds and replaces every observer's heard list.
+    pub(crate) fn hear(&mut self) {
+        for observer in self.observers.values_mut() {
+            observer.heard.clear();
+        }
+        let sounds = std::mem::take(&mut self.sounds);
+        let keenest = self
+            .observers
+            .values()
+            .map(|observer| observer.hearing)
+            .fold(0.0, f32::max);
+        for sound in &sounds {
+            let reach = sound.strength * keenest;
+            let field = self
+                .grid
+                .as_ref()
+                .map(|grid| SoundField::propagate(grid, sound.position, reach, self.wall_damping));
+            for observer in self.observers.values_mut() {
+                let range = sound.strength * observer.hearing;
+                let travelled = match &field {
+                    Some(field) => field.cost(observer.position),
+                    None => sound.position.distance(observer.position),
+                };
+                let occluded = self
+                    .occluders
+                    .values()
+                    .filter(|&&(start, end)| {
+                        segments_cross(sound.position, observer.position, start, end)
+                    })
+                    .count()
+                    + self
+                        .obstacles
+                        .count_blocking(sound.position, observer.position);
+                let occluded = occluded as f32;
+                let strength = range - travelled - occluded * self.wall_damping;
+                if strength > 0.0 {
+                    observer.heard.push(Stimulus { strength, ..*sound });
+                }
+            }
+        }
+        for observer in self.observers.values_mut() {
+            observer
+                .heard
+                .sort_by(|a, b| b.strength.total_cmp(&a.strength));
+        }
+    }
+}
+
+/// Cost of a sound reaching each cell in a box around it, which bounds the flood
+/// to the cells it could ever be heard in.
+struct SoundField {
+    min: (i32, i32),
+    width: i32,
+    height: i32,
+    cost: Vec<f32>,
+}
+
+impl SoundField {
+    fn propagate(grid: &Grid, origin: Vec2, reach: f32, wall_damping: f32) -> SoundField {
+        let reach = reach.ceil() as i32;
+        let (x, y) = (origin.x.round() as i32, origin.y.round() as i32);
+        let min = ((x - reach).max(0), (y - reach).max(0));
+        let max = (
+            (x + reach).min(grid.width() as i32 - 1),
+            (y + reach).min(grid.height() as i32 - 1),
+        );
+        let (width, height) = (max.0 - min.0 + 1, max.1 - min.1 + 1);
+        if width <= 0 || height <= 0 || !grid.in_bounds(x, y) {
+            return SoundField {
+                min,
+                width: 0,
+                height: 0,
+                cost: Vec::new(),
+            };
+        }
+
+        let local = |cx: i32, cy: i32| ((cy - min.1) * width + (cx - min.0)) as usize;
+        let cost = dijkstra(
+            (width * height) as usize,
+            &[local(x, y)],
+            |index: usize, out: &mut Vec<(usize, f32)>| {
+                let (cx, cy) = (min.0 + index as i32 % width, min.1 + index as i32 / width);
+                for (dx, dy) in [
+                    (1, 0),
+                    (-1, 0),
+                    (0, 1),
+                    (0, -1),
+                    (1, 1),
+                    (1, -1),
+                    (-1, 1),
+                    (-1, -1),
+                ] {
+                    let (nx, ny) = (cx + dx, cy + dy);
+                    if nx < min.0 || ny < min.1 || nx > max.0 || ny > max.1 {
+                        continue;
+                    }
+                    let step = if dx != 0 && dy != 0 { SQRT_2 } else { 1.0 };
+                    let damping = if grid.is_walkable(nx, ny) {
+                        0.0
+                    } else {
+                        wall_damping
+                    };
+                    out.push((local(nx, ny), step + damping));
+                }
+            },
+        );
+        SoundField {
+            min,
+            width,
+            height,
+            cost,
+        }
+    }
+
+    /// Cost of reaching `position`, `INFINITY` outside the field.
+    fn cost(&self, position: Vec2) -> f32 {
+        let (x, y) = (
+            position.x.round() as i32 - self.min.0,
+            position.y.round() as i32 - self.min.1,
+        );
+        if x < 0 || y < 0 || x >= self.width || y >= self.height {
+            return f32::INFINITY;
+        }
+        self.cost[(y * self.width + x) as usize]
+    }
+}
@@ -0,0 +1,294 @@
+use wasm_bindgen::prelude::*;
+
+use crate::math::Vec2;
+use crate::search;
+
+const SQRT_3: f32 = 1.732_050_8;
+
+/// Axial `(q, r)` steps to the six neighbors, counterclockwise from east.
+const DIRECTIONS: [(i32, i32); 6] = [(1, 0), (1, -1), (0, -1), (-1, 0), (-1, 1), (0, 1)];
+
+/// How a `HexGrid` lays out its rows and columns. Pointy-top hexes sit in rows
+/// with every odd (or even) row pushed right by half a hex; flat-top hexes sit
+/// in columns with every odd (or even) column pushed down.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HexLayout {
+    /// Pointy tops, odd rows shoved right.
+    OddRows = 0,
+    /// Pointy tops, even rows shoved right.
+    EvenRows = 1,
+    /// Flat tops, odd columns shoved down.
+    OddColumns = 2,
+    /// Flat tops, even columns shoved down.
+    EvenColumns = 3,
+}
+
+impl HexLayout {
+    fn pointy(self) -> bool {
+        matches!(self, HexLayout::OddRows | HexLayout::EvenRows)
+    }
+}
+
+/// A rectangular map of hexagons, each walkable or blocked with a cost to
+/// enter. Cells are stored by offset `(column, row)` but addressed by axial
+/// `(q, r)` coordinates, where neighbors, distances and lines need no case
+/// for odd and even rows; convert with `to_axial` and `to_offset`.
+#[wasm_bindgen]
+#[derive(Clone, Debug)]
+pub struct HexGrid {
+    width: u32,
+    height: u32,
+    layout: HexLayout,
+    walkable: Vec<bool>,
+    costs: Vec<f32>,
+}
+
+#[wasm_bindgen]
+impl HexGrid {
+    /// A grid of `width` columns and `height` rows, all walkable at cost 1.
+    #[wasm_bindgen(constructor)]
+    pub fn new(width: u32, height: u32, layout: HexLayout) -> HexGrid {
+        let len = (width * height) as usize;
+        HexGrid {
+            width,
+            height,
+            layout,
+            walkable: vec![true; len],
+            costs: vec![1.0; len],
+        }
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn layout(&self) -> HexLayout {
+        self.layout
+    }
+
+    /// Axial `[q, r]` of the cell at offset `(column, row)`.
+    pub fn to_axial(&self, column: i32, row: i32) -> Vec<i32> {
+        let (q, r) = self.axial(column, row);
+        vec![q, r]
+    }
+
+    /// Offset `[column, row]` of the cell at axial `(q, r)`.
+    pub fn to_offset(&self, q: i32, r: i32) -> Vec<i32> {
+        let (column, row) = self.offset(q, r);
+        vec![column, row]
+    }
+
+    pub fn contains(&self, q: i32, r: i32) -> bool {
+        self.index(q, r).is_some()
+    }
+
+    /// Marks a cell as walkable or blocked. Cells off the map are ignored.
+    pub fn set_walkable(&mut self, q: i32, r: i32, walkable: bool) {
+        if let Some(index) = self.index(q, r) {
+            self.walkable[index] = walkable;
+        }
+    }
+
+    /// Returns false for blocked cells and cells off the map.
+    pub fn is_walkable(&self, q: i32, r: i32) -> bool {
+        self.index(q, r).is_some_and(|index| self.walkable[index])
+    }
+
+    /// Sets what moving into a cell costs, e.g. 2 for forest or hills. Costs
+    /// below a hundredth are raised to it so searches stay bounded.
+    pub fn set_cost(&mut self, q: i32, r: i32, cost: f32) {
+        if let Some(index) = self.index(q, r) {
+            self.costs[index] = cost.max(0.01);
+        }
+    }
+
+    pub fn cost(&self, q: i32, r: i32) -> f32 {
+        self.index(q, r)
+            .map_or(f32::INFINITY, |index| self.costs[index])
+    }
+
+    /// Walkable neighbors as flat axial `[q0, r0, q1, r1, ...]`.
+    pub fn neighbors(&self, q: i32, r: i32) -> Vec<i32> {
+        DIRECTIONS
+            .iter()
+            .map(|&(dq, dr)| (q + dq, r + dr))
+            .filter(|&(q, r)| self.is_walkable(q, r))
+            .flat_map(|(q, r)| [q, r])
+            .collect()
+    }
+
+    /// Finds a cheapest path and returns it as flat axial `[q0, r0, q1, r1,
+    /// ...]`, including both ends, or an empty array when there is none. A
+    /// path costs the sum of the costs of the cells it moves into.
+    pub fn find_path(&self, start_q: i32, start_r: i32, end_q: i32, end_r: i32) -> Vec<i32> {
+        let (Some(start), Some(goal)) = (self.index(start_q, start_r), self.index(end_q, end_r))
+        else {
+            return Vec::new();
+        };
+        if !self.walkable[start] || !self.walkable[goal] {
+            return Vec::new();
+        }
+        let scale = self
+            .costs
+            .iter()
+            .zip(&self.walkable)
+            .filter(|(_, &walkable)| walkable)
+            .map(|(&cost, _)| cost)
+            .fold(f32::INFINITY, f32::min);
+        let path = search::astar(
+            self.walkable.len(),
+            start,
+            goal,
+            |index, out| {
+                let (q, r) = self.coords(index);
+                for (dq, dr) in DIRECTIONS {
+                    if let Some(next) = self.index(q + dq, r + dr) {
+                        if self.walkable[next] {
+                            out.push((next, self.costs[next]));
+                        }
+                    }
+                }
+            },
+            |index| {
+                let (q, r) = self.coords(index);
+                distance(q, r, end_q, end_r) as f32 * scale
+            },
+        );
+        path.map(|path| {
+            path.into_iter()
+                .flat_map(|index| {
+                    let (q, r) = self.coords(index);
+                    [q, r]
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+    }
+
+    /// Whether every cell on the `hex_line` between two cells is walkable.
+    pub fn has_line_of_sight(&self, from_q: i32, from_r: i32, to_q: i32, to_r: i32) -> bool {
+        hex_line(from_q, from_r, to_q, to_r)
+            .chunks_exact(2)
+            .all(|cell| self.is_walkable(cell[0], cell[1]))
+    }
+
+    /// Center of a cell for hexes of circumradius `size`, with `(0, 0)` at the
+    /// center of axial `(0, 0)` and y growing with `r`.
+    pub fn center(&self, q: i32, r: i32, size: f32) -> Vec2 {
+        let (q, r) = (q as f32, r as f32);
+        if self.layout.pointy() {
+            Vec2::new(SQRT_3 * (q + r / 2.0), 1.5 * r) * size
+        } else {
+            Vec2::new(1.5 * q, SQRT_3 * (r + q / 2.0)) * size
+        }
+    }
+
+    /// Axial `[q, r]` of the cell holding the point `(x, y)`, inverting
+    /// `center`.
+    pub fn cell_at(&self, x: f32, y: f32, size: f32) -> Vec<i32> {
+        let (x, y) = (x / size, y / size);
+        if self.layout.pointy() {
+            hex_round(SQRT_3 / 3.0 * x - y / 3.0, 2.0 / 3.0 * y)
+        } else {
+            hex_round(2.0 / 3.0 * x, SQRT_3 / 3.0 * y - x / 3.0)
+        }
+    }
+}
+
+impl HexGrid {
+    fn axial(&self, column: i32, row: i32) -> (i32, i32) {
+        match self.layout {
+            HexLayout::OddRows => (column - (row - (row & 1)) / 2, row),
+            HexLayout::EvenRows => (column - (row + (row & 1)) / 2, row),
+            HexLayout::OddColumns => (column, row - (column - (column & 1)) / 2),
+            HexLayout::EvenColumns => (column, row - (column + (column & 1)) / 2),
+        }
+    }
+
+    fn offset(&self, q: i32, r: i32) -> (i32, i32) {
+        match self.layout {
+            HexLayout::OddRows => (q + (r - (r & 1)) / 2, r),
+            HexLayout::EvenRows => (q + (r + (r & 1)) / 2, r),
+            HexLayout::OddColumns => (q, r + (q - (q & 1)) / 2),
+            HexLayout::EvenColumns => (q, r + (q + (q & 1)) / 2),
+        }
+    }
+
+    fn index(&self, q: i32, r: i32) -> Option<usize> {
+        let (column, row) = self.offset(q, r);
+        (column >= 0 && row >= 0 && (column as u32) < self.width && (row as u32) < self.height)
+            .then(|| (row as u32 * self.width + column as u32) as usize)
+    }
+
+    fn coords(&self, index: usize) -> (i32, i32) {
+        let index = index as u32;
+        self.axial((index % self.width) as i32, (index / self.width) as i32)
+    }
+}
+
+fn distance(q1: i32, r1: i32, q2: i32, r2: i32) -> u32 {
+    let (dq, dr) = (q1 - q2, r1 - r2);
+    (dq.unsigned_abs() + dr.unsigned_abs() + (dq + dr).unsigned_abs()) / 2
+}
+
+/// Steps between two hexes in axial coordinates.
+#[wasm_bindgen]
+pub fn hex_distance(q1: i32, r1: i32, q2: i32, r2: i32) -> u32 {
+    distance(q1, r1, q2, r2)
+}
+
+/// The six axial neighbors of a hex as flat `[q0, r0, ...]`, counterclockwise
+/// from east.
+#[wasm_bindgen]
+pub fn hex_neighbors(q: i32, r: i32) -> Vec<i32> {
+    DIRECTIONS
+        .iter()
+        .flat_map(|&(dq, dr)| [q + dq, r + dr])
+        .collect()
+}
+
+/// Axial `[q, r]` of the hex holding fractional axial coordinates.
+#[wasm_bindgen]
+pub fn hex_round(q: f32, r: f32) -> Vec<i32> {
+    let s = -q - r;
+    let (mut rq, mut rr, rs) = (q.round(), r.round(), s.round());
+    let (dq, dr, ds) = ((rq - q).abs(), (rr - r).abs(), (rs - s).abs());
+    if dq > dr && dq > ds {
+        rq = -rr - rs;
+    } else if dr > ds {
+        rr = -rq - rs;
+    }
+    vec![rq as i32, rr as i32]
+}
+
+/// The hexes a straight line between two hex centers passes through, as flat
+/// axial `[q0, r0, ...]` from one end to the other, one per step. Lines along
+/// the edge between two hexes lean the same way in both directions.
+#[wasm_bindgen]
+pub fn hex_line(q1: i32, r1: i32, q2: i32, r2: i32) -> Vec<i32> {
+    let steps = distance(q1, r1, q2, r2);
+    // The nudge keeps points on an edge from rounding either way.
+    let (from_q, from_r) = (q1 as f32 + 1e-6, r1 as f32 + 1e-6);
+    let (to_q, to_r) = (q2 as f32 + 1e-6, r2 as f32 + 1e-6);
+    let mut cells = Vec::with_capacity((steps as usize + 1) * 2);
+    for step in 0..=steps {
+        let t = if steps == 0 {
+            0.0
+        } else {
+            step as f32 / steps as f32
+        };
+        cells.extend(hex_round(
+            from_q + (to_q - from_q) * t,
+            from_r + (to_r - from_r) * t,
+        ));
+    }
+    cells
+}
@@ -0,0 +1,398 @@
+use std::collections::HashMap;
+
+use wasm_bindgen::prelude::*;
+
+use crate::grid::{octile, Grid, Traversal};
+use crate::search;
+
+/// Border runs at least this long get a transition at each end instead of one in
+/// the middle.
+const WIDE_ENTRANCE: i32 = 6;
+
+#[derive(Clone, Copy)]
+struct Rect {
+    x0: i32,
+    y0: i32,
+    x1: i32,
+    y1: i32,
+}
+
+impl Rect {
+    fn contains(&self, x: i32, y: i32) -> bool {
+        x >= self.x0 && x < self.x1 && y >= self.y0 && y < self.y1
+    }
+
+    fn width(&self) -> i32 {
+        self.x1 - self.x0
+    }
+
+    fn area(&self) -> usize {
+        (self.width() * (self.y1 - self.y0)) as usize
+    }
+}
+
+struct Edge {
+    to: usize,
+    cost: f32,
+    /// Index into `paths` and whether to walk it backwards; `None` for the unit
+    /// step between two entrance cells on either side of a cluster border.
+    path: Option<(usize, bool)>,
+}
+
+/// Hierarchical pathfinding (HPA*) over a snapshot of a `Grid`. The map is split into
+/// square clusters; entrances along cluster borders form an abstract graph whose
+/// intra-cluster edges are precomputed, so long queries only search that graph and
+/// the start and goal clusters.
+#[wasm_bindgen]
+pub struct HierarchicalGrid {
+    grid: Grid,
+    cluster_size: i32,
+    clusters_x: i32,
+    nodes: Vec<usize>,
+    node_ids: HashMap<usize, usize>,
+    cluster_nodes: Vec<Vec<usize>>,
+    edges: Vec<Vec<Edge>>,
+    paths: Vec<Vec<usize>>,
+}
+
+#[wasm_bindgen]
+impl HierarchicalGrid {
+    /// Builds the abstraction from a copy of `grid`. Rebuild it after editing the grid.
+    #[wasm_bindgen(constructor)]
+    pub fn new(grid: &Grid, cluster_size: u32) -> HierarchicalGrid {
+        let cluster_size = cluster_size.max(2) as i32;
+        let (width, height) = (grid.width() as i32, grid.height() as i32);
+        let clusters_x = (width + cluster_size - 1) / cluster_size;
+        let clusters_y = (height + cluster_size - 1) / cluster_size;
+
+        let mut hierarchy = HierarchicalGrid {
+            grid: grid.clone(),
+            cluster_size,
+            clusters_x,
+            nodes: Vec::new(),
+            node_ids: HashMap::new(),
+            cluster_nodes: vec![Vec::new(); (clusters_x * clusters_y) as usize],
+            edges: Vec::new(),
+            paths: Vec::new(),
+        };
+
+        for cy in 0..clusters_y {
+            for cx in 0..clusters_x {
+                if cx + 1 < clusters_x {
+                    let x = (cx + 1) * cluster_size - 1;
+                    let rect = hierarchy.rect(cx, cy);
+                    hierarchy.add_entrances((x, rect.y0), (0, 1), (1, 0), rect.y1 - rect.y0);
+                }
+                if cy + 1 < clusters_y {
+                    let y = (cy + 1) * cluster_size - 1;
+                    let rect = hierarchy.rect(cx, cy);
+                    hierarchy.add_entrances((rect.x0, y), (1, 0), (0, 1), rect.width());
+                }
+            }
+        }
+
+        for cluster in 0..hierarchy.cluster_nodes.len() {
+            hierarchy.connect_cluster(cluster);
+        }
+        hierarchy
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn cluster_size(&self) -> u32 {
+        self.cluster_size as u32
+    }
+
+    /// Number of entrance nodes in the abstract graph.
+    #[wasm_bindgen(getter)]
+    pub fn node_count(&self) -> u32 {
+        self.nodes.len() as u32
+    }
+
+    /// Same output format as `Grid::find_path`. Paths are near-optimal: they route
+    /// through cluster entrances rather than taking the exact shortest line.
+    pub fn find_path(&self, start_x: u32, start_y: u32, end_x: u32, end_y: u32) -> Vec<f32> {
+        match self.find_cells(start_x as i32, start_y as i32, end_x as i32, end_y as i32) {
+            Some(cells) => self.grid.to_waypoints(&cells),
+            None => Vec::new(),
+        }
+    }
+}
+
+impl HierarchicalGrid {
+    fn rect(&self, cx: i32, cy: i32) -> Rect {
+        let size = self.cluster_size;
+        Rect {
+            x0: cx * size,
+            y0: cy * size,
+            x1: ((cx + 1) * size).min(self.grid.width() as i32),
+            y1: ((cy + 1) * size).min(self.grid.height() as i32),
+        }
+    }
+
+    fn cluster_of(&self, x: i32, y: i32) -> usize {
+        ((y / self.cluster_size) * self.clusters_x + x / self.cluster_size) as usize
+    }
+
+    fn cluster_rect(&self, cluster: usize) -> Rect {
+        let cluster = cluster as i32;
+        self.rect(cluster % self.clusters_x, cluster / self.clusters_x)
+    }
+
+    /// Scans `length` cells along a border starting at `origin` in direction `along`;
+    /// `across` steps into the neighboring cluster.
+    fn add_entrances(
+        &mut self,
+        origin: (i32, i32),
+        along: (i32, i32),
+        across: (i32, i32),
+        length: i32,
+    ) {
+        let open = |grid: &Grid, i: i32| {
+            let (x, y) = (origin.0 + along.0 * i, origin.1 + along.1 * i);
+            // Passable also rules out cells an infinite terrain or obstacle cost
+            // closes off.
+            let passable = |x, y| grid.is_passable(x, y, Traversal::default());
+            passable(x, y) && passable(x + across.0, y + across.1)
+        };
+
+        let mut i = 0;
+        while i < length {
+            if !open(&self.grid, i) {
+                i += 1;
+                continue;
+            }
+            let start = i;
+            while i < length && open(&self.grid, i) {
+                i += 1;
+            }
+            let end = i - 1;
+            let transitions = if end - start + 1 >= WIDE_ENTRANCE {
+                vec![start, end]
+            } else {
+                vec![(start + end) / 2]
+            };
+            for t in transitions {
+                let (x, y) = (origin.0 + along.0 * t, origin.1 + along.1 * t);
+                let a = self.node_at(x, y);
+                let b = self.node_at(x + across.0, y + across.1);
+                let cost = self.grid.step_cost(self.nodes[a], self.nodes[b]);
+                self.edges[a].push(Edge {
+                    to: b,
+                    cost,
+                    path: None,
+                });
+                self.edges[b].push(Edge {
+                    to: a,
+                    cost,
+                    path: None,
+                });
+            }
+        }
+    }
+
+    fn node_at(&mut self, x: i32, y: i32) -> usize {
+        let cell = self.grid.index(x, y);
+        if let Some(&node) = self.node_ids.get(&cell) {
+            return node;
+        }
+        let node = self.nodes.len();
+        self.nodes.push(cell);
+        self.node_ids.insert(cell, node);
+        self.edges.push(Vec::new());
+        let cluster = self.cluster_of(x, y);
+        self.cluster_nodes[cluster].push(node);
+        node
+    }
+
+    /// Precomputes paths between every pair of entrances inside a cluster, growing one
+    /// shortest-path tree per entrance.
+    fn connect_cluster(&mut self, cluster: usize) {
+        let rect = self.cluster_rect(cluster);
+        let members = self.cluster_nodes[cluster].clone();
+        for (i, &a) in members.iter().enumerate() {
+            if i + 1 == members.len() {
+                break;
+            }
+            let (costs, parents) = self.local_tree(rect, self.nodes[a]);
+            for &b in &members[i + 1..] {
+                let target = self.local_index(rect, self.nodes[b]);
+                if !costs[target].is_finite() {
+                    continue;
+                }
+                let path: Vec<usize> = search::reconstruct(&parents, target)
+                    .into_iter()
+                    .map(|index| self.global_index(rect, index))
+                    .collect();
+                let cost = costs[target];
+                let id = self.paths.len();
+                self.paths.push(path);
+                self.edges[a].push(Edge {
+                    to: b,
+                    cost,
+                    path: Some((id, false)),
+                });
+                self.edges[b].push(Edge {
+                    to: a,
+                    cost,
+                    path: Some((id, true)),
+                });
+            }
+        }
+    }
+
+    fn local_index(&self, rect: Rect, cell: usize) -> usize {
+        let (x, y) = self.grid.coords(cell);
+        ((y - rect.y0) * rect.width() + (x - rect.x0)) as usize
+    }
+
+    fn global_index(&self, rect: Rect, index: usize) -> usize {
+        let index = index as i32;
+        self.grid.index(
+            rect.x0 + index % rect.width(),
+            rect.y0 + index / rect.width(),
+        )
+    }
+
+    /// Pushes the in-cluster neighbors of a cluster-local node.
+    fn local_neighbors(
+        &self,
+        rect: Rect,
+        index: usize,
+        edges: &mut Vec<(usize, f32)>,
+        out: &mut Vec<(usize, f32)>,
+    ) {
+        edges.clear();
+        self.grid.neighbors(self.global_index(rect, index), edges);
+        for &(next, cost) in edges.iter() {
+            let (x, y) = self.grid.coords(next);
+            if rect.contains(x, y) {
+                out.push((self.local_index(rect, next), cost));
+            }
+        }
+    }
+
+    fn local_tree(&self, rect: Rect, from: usize) -> (Vec<f32>, Vec<usize>) {
+        let mut edges = Vec::new();
+        search::shortest_path_tree(
+            rect.area(),
+            &[self.local_index(rect, from)],
+            |index, out| self.local_neighbors(rect, index, &mut edges, out),
+        )
+    }
+
+    /// A* confined to `rect`, searching over cluster-local indices.
+    fn local_path(&self, rect: Rect, from: usize, to: usize) -> Option<Vec<usize>> {
+        let (gx, gy) = self.grid.coords(to);
+        let scale = self.grid.heuristic_scale(Traversal::default());
+        let mut edges = Vec::new();
+        let path = search::astar(
+            rect.area(),
+            self.local_index(rect, from),
+            self.local_index(rect, to),
+            |index, out| self.local_neighbors(rect, index, &mut edges, out),
+            |index| {
+                let (x, y) = self.grid.coords(self.global_index(rect, index));
+                octile(x - gx, y - gy) * scale
+            },
+        )?;
+        Some(
+            path.into_iter()
+                .map(|index| self.global_index(rect, index))
+                .collect(),
+        )
+    }
+
+    fn find_cells(&self, sx: i32, sy: i32, ex: i32, ey: i32) -> Option<Vec<usize>> {
+        if !self.grid.is_passable(sx, sy, Traversal::default())
+            || !self.grid.is_passable(ex, ey, Traversal::default())
+        {
+            return None;
+        }
+        let scale = self.grid.heuristic_scale(Traversal::default());
+        let start = self.grid.index(sx, sy);
+        let goal = self.grid.index(ex, ey);
+        let start_cluster = self.cluster_of(sx, sy);
+        let goal_cluster = self.cluster_of(ex, ey);
+
+        if start_cluster == goal_cluster {
+            if let Some(path) = self.local_path(self.cluster_rect(start_cluster), start, goal) {
+                return Some(path);
+            }
+        }
+
+        // Temporary links from the start into its cluster's entrances and from the
+        // goal cluster's entrances to the goal.
+        let link = |cluster: usize, from_start: bool| -> HashMap<usize, Vec<usize>> {
+            let rect = self.cluster_rect(cluster);
+            self.cluster_nodes[cluster]
+                .iter()
+                .filter_map(|&node| {
+                    let path = if from_start {
+                        self.local_path(rect, start, self.nodes[node])
+                    } else {
+                        self.local_path(rect, self.nodes[node], goal)
+                    };
+                    path.map(|path| (node, path))
+                })
+                .collect()
+        };
+        let start_links = link(start_cluster, true);
+        let goal_links = link(goal_cluster, false);
+
+        let source = self.nodes.len();
+        let target = source + 1;
+        let abstract_path = search::astar(
+            self.nodes.len() + 2,
+            source,
+            target,
+            |node, out| {
+                if node == source {
+                    for (&next, path) in &start_links {
+                        out.push((next, self.grid.path_cost(path)));
+                    }
+                    return;
+                }
+                for edge in &self.edges[node] {
+                    out.push((edge.to, edge.cost));
+                }
+                if let Some(path) = goal_links.get(&node) {
+                    out.push((target, self.grid.path_cost(path)));
+                }
+            },
+            |node| {
+                if node >= source {
+                    return if node == source {
+                        octile(ex - sx, ey - sy) * scale
+                    } else {
+                        0.0
+                    };
+                }
+                let (x, y) = self.grid.coords(self.nodes[node]);
+                octile(x - ex, y - ey) * scale
+            },
+        )?;
+
+        let mut cells = vec![start];
+        for pair in abstract_path.windows(2) {
+            let (from, to) = (pair[0], pair[1]);
+            let segment: Vec<usize> = if from == source {
+                start_links[&to].clone()
+            } else if to == target {
+                goal_links[&from].clone()
+            } else {
+                let edge = self.edges[from]
+                    .iter()
+                    .filter(|edge| edge.to == to)
+                    .min_by(|a, b| a.cost.total_cmp(&b.cost))
+                    .expect("abstract path follows graph edges");
+                match edge.path {
+                    None => vec![self.nodes[from], self.nodes[to]],
+                    Some((id, false)) => self.paths[id].clone(),
+                    Some((id, true)) => self.paths[id].iter().rev().copied().collect(),
+                }
+            };
+            cells.extend(segment.into_iter().skip(1));
+        }
+        Some(cells)
+    }
+}
@@ -0,0 +1,366 @@
+use js_sys::Function;
+use wasm_bindgen::prelude::*;
+
+use crate::behavior_tree::Status;
+use crate::error::Error;
+
+/// Carries out a primitive task for one tick, given the frame's `dt`.
+pub type Operator = Box<dyn FnMut(f32) -> Status>;
+
+/// A `(mask, values)` condition over the world state bits, as in `Planner`.
+#[derive(Clone, Copy)]
+struct Condition {
+    mask: u32,
+    values: u32,
+}
+
+impl Condition {
+    fn holds(&self, state: u32) -> bool {
+        state & self.mask == self.values & self.mask
+    }
+}
+
+struct Method {
+    precondition: Condition,
+    subtasks: Vec<usize>,
+}
+
+enum Body {
+    Primitive {
+        precondition: Condition,
+        effect_mask: u32,
+        effect_values: u32,
+        operator: Option<Operator>,
+    },
+    Compound {
+        methods: Vec<Method>,
+    },
+}
+
+struct Task {
+    name: String,
+    body: Body,
+}
+
+/// Hierarchical task network planner and runner, for authored behaviors that
+/// break down the same way each time, e.g. "attack" into "draw weapon" then
+/// "close in" then "strike", where `Planner` would search freely. World state
+/// is a bitset of up to 32 facts, with conditions as `(mask, values)` pairs
+/// like `Planner`'s.
+///
+/// Compound tasks list methods in order of preference; planning takes the
+/// first method whose precondition holds and whose subtasks can all be
+/// planned in turn, backtracking to the next when they cannot. Primitive
+/// tasks have a precondition, effects and an operator that carries them out.
+///
+/// `tick` runs the plan for the root task. Each tick it checks the rest of the
+/// plan against the world state it is given, which is the game's truth, and
+/// replans when a remaining primitive could no longer run, e.g. when the
+/// target moved out of reach.
+#[wasm_bindgen]
+pub struct HtnPlanner {
+    tasks: Vec<Task>,
+    root: Option<usize>,
+    plan: Vec<usize>,
+    /// Index in `plan` of the task being carried out.
+    cursor: usize,
+    plans: u32,
+    /// Decomposition steps a plan may take before planning gives up.
+    pub max_expansions: u32,
+}
+
+impl Default for HtnPlanner {
+    fn default() -> HtnPlanner {
+        HtnPlanner {
+            tasks: Vec::new(),
+            root: None,
+            plan: Vec::new(),
+            cursor: 0,
+            plans: 0,
+            max_expansions: 10_000,
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl HtnPlanner {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> HtnPlanner {
+        HtnPlanner::default()
+    }
+
+    /// Registers a primitive task and returns its id. `operator` is called
+    /// with `dt` while the task runs and returns a `Status` or a boolean;
+    /// without one the task succeeds as soon as it starts.
+    #[allow(clippy::too_many_arguments)]
+    pub fn add_primitive(
+        &mut self,
+        name: &str,
+        pre_mask: u32,
+        pre_values: u32,
+        effect_mask: u32,
+        effect_values: u32,
+        operator: Option<Function>,
+    ) -> u32 {
+        self.add_primitive_with(
+            name,
+            pre_mask,
+            pre_values,
+            effect_mask,
+            effect_values,
+            operator.map(js_operator),
+        )
+    }
+
+    /// Registers a compound task, to be given methods with `add_method`, and
+    /// returns its id.
+    pub fn add_compound(&mut self, name: &str) -> u32 {
+        self.tasks.push(Task {
+            name: name.to_string(),
+            body: Body::Compound {
+                methods: Vec::new(),
+            },
+        });
+        self.tasks.len() as u32 - 1
+    }
+
+    /// Adds a way to carry out `compound`: the tasks `subtasks` in order,
+    /// usable when `(pre_mask, pre_values)` holds. Methods added first are
+    /// preferred.
+    pub fn add_method(
+        &mut self,
+        compound: u32,
+        pre_mask: u32,
+        pre_values: u32,
+        subtasks: &[u32],
+    ) -> Result<(), Error> {
+        if let Some(&bad) = subtasks
+            .iter()
+            .find(|&&task| task as usize >= self.tasks.len())
+        {
+            return Err(Error::InvalidInput(format!("unknown task {}", bad)));
+        }
+        let Some(Task {
+            body: Body::Compound { methods },
+            ..
+        }) = self.tasks.get_mut(compound as usize)
+        else {
+            return Err(Error::InvalidInput(format!(
+                "task {} is not a compound task",
+                compound
+            )));
+        };
+        methods.push(Method {
+            precondition: Condition {
+                mask: pre_mask,
+                values: pre_values,
+            },
+            subtasks: subtasks.iter().map(|&task| task as usize).collect(),
+        });
+        Ok(())
+    }
+
+    pub fn task_name(&self, id: u32) -> Option<String> {
+        self.tasks.get(id as usize).map(|task| task.name.clone())
+    }
+
+    /// Breaks `task` down into primitive task ids for `world_state`. Returns
+    /// `undefined` when no method applies all the way down, or when planning
+    /// takes more than `max_expansions` steps.
+    pub fn plan(&self, world_state: u32, task: u32) -> Option<Vec<u32>> {
+        if task as usize >= self.tasks.len() {
+            return None;
+        }
+        self.decompose(world_state, task as usize)
+            .map(|plan| plan.into_iter().map(|task| task as u32).collect())
+    }
+
+    /// Sets the task that `tick` plans for, dropping the current plan.
+    pub fn set_root(&mut self, task: u32) -> Result<(), Error> {
+        if task as usize >= self.tasks.len() {
+            return Err(Error::InvalidInput(format!("unknown task {}", task)));
+        }
+        self.root = Some(task as usize);
+        self.reset();
+        Ok(())
+    }
+
+    /// Drops the current plan, so the next `tick` plans afresh.
+    pub fn reset(&mut self) {
+        self.plan.clear();
+        self.cursor = 0;
+    }
+
+    /// Runs the plan for the root task one step against `world_state`,
+    /// planning first if there is no plan, or replanning if the rest of it no
+    /// longer works. Returns `Running` while tasks remain, `Success` once the
+    /// last one succeeds, and `Failure` when there is no root, no plan for it,
+    /// or a task fails. After `Success` or `Failure` the next tick plans again.
+    pub fn tick(&mut self, world_state: u32, dt: f32) -> Status {
+        let Some(root) = self.root else {
+            return Status::Failure;
+        };
+        if self.plan.is_empty() || !self.is_valid(world_state) {
+            let Some(plan) = self.decompose(world_state, root) else {
+                self.reset();
+                return Status::Failure;
+            };
+            self.plans += 1;
+            self.plan = plan;
+            self.cursor = 0;
+            if self.plan.is_empty() {
+                return Status::Success;
+            }
+        }
+
+        let task = self.plan[self.cursor];
+        let status = match &mut self.tasks[task].body {
+            Body::Primitive {
+                operator: Some(operator),
+                ..
+            } => operator(dt),
+            _ => Status::Success,
+        };
+        match status {
+            Status::Success => {
+                self.cursor += 1;
+                if self.cursor < self.plan.len() {
+                    return Status::Running;
+                }
+                self.reset();
+                Status::Success
+            }
+            Status::Failure => {
+                self.reset();
+                Status::Failure
+            }
+            _ => Status::Running,
+        }
+    }
+
+    /// The current plan, as primitive task ids.
+    pub fn current_plan(&self) -> Vec<u32> {
+        self.plan.iter().map(|&task| task as u32).collect()
+    }
+
+    /// The primitive task being carried out.
+    pub fn current_task(&self) -> Option<u32> {
+        self.plan.get(self.cursor).map(|&task| task as u32)
+    }
+
+    /// Plans made by `tick` so far, including replans.
+    #[wasm_bindgen(getter)]
+    pub fn plan_count(&self) -> u32 {
+        self.plans
+    }
+}
+
+impl HtnPlanner {
+    pub fn add_primitive_with(
+        &mut self,
+        name: &str,
+        pre_mask: u32,
+        pre_values: u32,
+        effect_mask: u32,
+        effect_values: u32,
+        operator: Option<Operator>,
+    ) -> u32 {
+        self.tasks.push(Task {
+            name: name.to_string(),
+            body: Body::Primitive {
+                precondition: Condition {
+                    mask: pre_mask,
+                    values: pre_values,
+                },
+                effect_mask,
+                effect_values,
+                operator,
+            },
+        });
+        self.tasks.len() as u32 - 1
+    }
+
+    fn decompose(&self, state: u32, task: usize) -> Option<Vec<usize>> {
+        let mut pending = vec![task];
+        let mut plan = Vec::new();
+        let mut budget = self.max_expansions;
+        self.seek(state, &mut pending, &mut plan, &mut budget)
+            .then_some(plan)
+    }
+
+    /// Plans the tasks on `pending`, last first, from `state`, appending
+    /// primitives to `plan`. Leaves both as they were when it fails.
+    fn seek(
+        &self,
+        state: u32,
+        pending: &mut Vec<usize>,
+        plan: &mut Vec<usize>,
+        budget: &mut u32,
+    ) -> bool {
+        let Some(task) = pending.pop() else {
+            return true;
+        };
+        if *budget > 0 {
+            *budget -= 1;
+            match &self.tasks[task].body {
+                Body::Primitive {
+                    precondition,
+                    effect_mask,
+                    effect_values,
+                    ..
+                } => {
+                    if precondition.holds(state) {
+                        let next = (state & !effect_mask) | (effect_values & effect_mask);
+                        plan.push(task);
+                        if self.seek(next, pending, plan, budget) {
+                            return true;
+                        }
+                        plan.pop();
+                    }
+                }
+                Body::Compound { methods } => {
+                    for method in methods {
+                        if !method.precondition.holds(state) {
+                            continue;
+                        }
+                        let depth = pending.len();
+                        pending.extend(method.subtasks.iter().rev());
+                        if self.seek(state, pending, plan, budget) {
+                            return true;
+                        }
+                        pending.truncate(depth);
+                    }
+                }
+            }
+        }
+        pending.push(task);
+        false
+    }
+
+    /// Whether every task left in the plan can still run in turn from
+    /// `state`.
+    fn is_valid(&self, mut state: u32) -> bool {
+        for &task in &self.plan[self.cursor..] {
+            let Body::Primitive {
+                precondition,
+                effect_mask,
+                effect_values,
+                ..
+            } = &self.tasks[task].body
+            else {
+                return false;
+            };
+            if !precondition.holds(state) {
+                return false;
+            }
+            state = (state & !effect_mask) | (effect_values & effect_mask);
+        }
+        true
+    }
+}
+
+fn js_operator(callback: Function) -> Operator {
+    Box::new(move |dt| {
+        Status::from_js(callback.call1(&JsValue::NULL, &JsValue::from_f64(dt as f64)))
+    })
+}
@@ -0,0 +1,417 @@
+use std::collections::HashMap;
+use std::f32::consts::SQRT_2;
+
+use wasm_bindgen::prelude::*;
+
+use crate::batch;
+use crate::debug::{self, DebugGeometry};
+use crate::error::Error;
+use crate::grid::Grid;
+use crate::math::Vec2;
+
+const NEIGHBORS: [(i32, i32, f32); 8] = [
+    (1, 0, 1.0),
+    (-1, 0, 1.0),
+    (0, 1, 1.0),
+    (0, -1, 1.0),
+    (1, 1, SQRT_2),
+    (1, -1, SQRT_2),
+    (-1, 1, SQRT_2),
+    (-1, -1, SQRT_2),
+];
+
+#[derive(Clone, Copy)]
+struct Source {
+    position: Vec2,
+    strength: f32,
+    radius: f32,
+}
+
+/// A 2D grid of influence values, such as how strongly each side controls a cell
+/// or how dangerous it is. Sources stamp influence that falls off with distance,
+/// `propagate` and `blur` spread it, and maps combine with `add`, `subtract` and
+/// `normalize` into e.g. tension or vulnerability maps.
+///
+/// Cells are addressed with cell centers at integer coordinates, like `Grid`.
+#[wasm_bindgen]
+#[derive(Clone)]
+pub struct InfluenceMap {
+    width: u32,
+    height: u32,
+    values: Vec<f32>,
+    /// Cells that hold no influence and block its spread, e.g. walls.
+    blocked: Vec<bool>,
+    sources: HashMap<u32, Source>,
+    next_source: u32,
+    scratch: Vec<f32>,
+    /// All bits set for each unblocked cell, for `batch::spread`.
+    open: Vec<u32>,
+}
+
+#[wasm_bindgen]
+impl InfluenceMap {
+    #[wasm_bindgen(constructor)]
+    pub fn new(width: u32, height: u32) -> InfluenceMap {
+        let cells = (width * height) as usize;
+        InfluenceMap {
+            width,
+            height,
+            values: vec![0.0; cells],
+            blocked: vec![false; cells],
+            sources: HashMap::new(),
+            next_source: 0,
+            scratch: Vec::new(),
+            open: Vec::new(),
+        }
+    }
+
+    /// A map the size of `grid` whose unwalkable cells are blocked.
+    pub fn from_grid(grid: &Grid) -> InfluenceMap {
+        let mut map = InfluenceMap::new(grid.width(), grid.height());
+        map.set_mask(grid);
+        map
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Blocks the unwalkable cells of `grid`, clearing their influence.
+    pub fn set_mask(&mut self, grid: &Grid) {
+        for (index, blocked) in self.blocked.iter_mut().enumerate() {
+            let (x, y) = (
+                (index % self.width as usize) as i32,
+                (index / self.width as usize) as i32,
+            );
+            *blocked = !grid.is_walkable(x, y);
+            if *blocked {
+                self.values[index] = 0.0;
+            }
+        }
+    }
+
+    /// Adds a source of `strength` that falls off linearly to zero at `radius`
+    /// cells away, and returns its id. Sources act on `propagate` and
+    /// `stamp_sources`.
+    pub fn add_source(&mut self, x: f32, y: f32, strength: f32, radius: f32) -> Result<u32, Error> {
+        if radius > 0.0 {
+            let id = self.next_source;
+            self.next_source = self.next_source.wrapping_add(1);
+            self.sources.insert(
+                id,
+                Source {
+                    position: Vec2::new(x, y),
+                    strength,
+                    radius,
+                },
+            );
+            Ok(id)
+        } else {
+            Err(Error::InvalidInput(format!(
+                "influence radius must be positive, got {}",
+                radius
+            )))
+        }
+    }
+
+    pub fn move_source(&mut self, id: u32, x: f32, y: f32) -> bool {
+        self.sources
+            .get_mut(&id)
+            .map(|source| source.position = Vec2::new(x, y))
+            .is_some()
+    }
+
+    pub fn set_source_strength(&mut self, id: u32, strength: f32) -> bool {
+        self.sources
+            .get_mut(&id)
+            .map(|source| source.strength = strength)
+            .is_some()
+    }
+
+    pub fn remove_source(&mut self, id: u32) -> bool {
+        self.sources.remove(&id).is_some()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn source_count(&self) -> u32 {
+        self.sources.len() as u32
+    }
+
+    /// Adds every source's influence to the map, e.g. after `clear` to build a map
+    /// from the sources alone, where overlapping sources sum.
+    pub fn stamp_sources(&mut self) {
+        let sources: Vec<Source> = self.sources.values().copied().collect();
+        for source in sources {
+            self.stamp(source.position, source.strength, source.radius);
+        }
+    }
+
+    /// Adds influence around one point without registering a source.
+    pub fn stamp(&mut self, position: Vec2, strength: f32, radius: f32) {
+        self.for_each_in_falloff(position, radius, |value, falloff| {
+            *value += strength * falloff
+        });
+    }
+
+    /// One step of spreading influence to neighboring cells: each cell moves
+    /// toward the strongest neighboring influence, decayed by `exp(-decay)` per
+    /// cell of distance, by a fraction `1 - momentum`. The cells around each
+    /// source are then held at no less than the source's own falloff. Stepping
+    /// every frame spreads influence outward from the sources while the trail of a
+    /// moved or removed source fades away; a higher momentum changes the map more
+    /// slowly.
+    pub fn propagate(&mut self, decay: f32, momentum: f32) {
+        let momentum = momentum.clamp(0.0, 1.0);
+        self.scratch.clone_from(&self.values);
+        self.open.clear();
+        self.open.extend(
+            self.blocked
+                .iter()
+                .map(|&blocked| if blocked { 0 } else { !0 }),
+        );
+        batch::spread(
+            &mut self.values,
+            &self.scratch,
+            &self.open,
+            self.width as usize,
+            self.height as usize,
+            [(-decay).exp(), (-decay * SQRT_2).exp()],
+            1.0 - momentum,
+        );
+        let sources: Vec<Source> = self.sources.values().copied().collect();
+        for source in sources {
+            self.for_each_in_falloff(source.position, source.radius, |value, falloff| {
+                let held = source.strength * falloff;
+                if held.abs() > value.abs() {
+                    *value = held;
+                }
+            });
+        }
+    }
+
+    /// Smooths the map by averaging each cell with its open neighbors `passes`
+    /// times.
+    pub fn blur(&mut self, passes: u32) {
+        for _ in 0..passes {
+            self.scratch.clone_from(&self.values);
+            for y in 0..self.height as i32 {
+                for x in 0..self.width as i32 {
+                    let index = self.index(x, y);
+                    if self.blocked[index] {
+                        continue;
+                    }
+                    let (mut sum, mut count) = (self.scratch[index], 1.0);
+                    for (dx, dy, _) in NEIGHBORS {
+                        if let Some(neighbor) = self.open(x + dx, y + dy) {
+                            sum += self.scratch[neighbor];
+                            count += 1.0;
+                        }
+                    }
+                    self.values[index] = sum / count;
+                }
+            }
+        }
+    }
+
+    /// Adds `other` scaled by `weight`, cell by cell. Both maps must be the same
+    /// size.
+    pub fn add(&mut self, other: &InfluenceMap, weight: f32) -> Result<(), Error> {
+        if self.width != other.width || self.height != other.height {
+            return Err(Error::InvalidInput(format!(
+                "influence maps differ in size: {}x{} and {}x{}",
+                self.width, self.height, other.width, other.height
+            )));
+        }
+        for (index, value) in self.values.iter_mut().enumerate() {
+            if !self.blocked[index] {
+                *value += other.values[index] * weight;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn subtract(&mut self, other: &InfluenceMap) -> Result<(), Error> {
+        self.add(other, -1.0)
+    }
+
+    pub fn scale(&mut self, factor: f32) {
+        for value in &mut self.values {
+            *value *= factor;
+        }
+    }
+
+    /// Scales the map so that its largest magnitude is 1. Leaves an all-zero map
+    /// unchanged.
+    pub fn normalize(&mut self) {
+        let largest = self
+            .values
+            .iter()
+            .fold(0.0f32, |max, value| max.max(value.abs()));
+        if largest > 0.0 {
+            self.scale(1.0 / largest);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.values.fill(0.0);
+    }
+
+    /// Influence of a cell, 0 outside the map.
+    pub fn value(&self, x: i32, y: i32) -> f32 {
+        if self.in_bounds(x, y) {
+            self.values[self.index(x, y)]
+        } else {
+            0.0
+        }
+    }
+
+    pub fn set_value(&mut self, x: i32, y: i32, value: f32) {
+        if self.in_bounds(x, y) {
+            let index = self.index(x, y);
+            if !self.blocked[index] {
+                self.values[index] = value;
+            }
+        }
+    }
+
+    /// Influence at any point, interpolated bilinearly between cell centers.
+    pub fn sample(&self, position: Vec2) -> f32 {
+        let (x0, y0) = (position.x.floor(), position.y.floor());
+        let (tx, ty) = (position.x - x0, position.y - y0);
+        let (x0, y0) = (x0 as i32, y0 as i32);
+        let top = self.value(x0, y0) * (1.0 - tx) + self.value(x0 + 1, y0) * tx;
+        let bottom = self.value(x0, y0 + 1) * (1.0 - tx) + self.value(x0 + 1, y0 + 1) * tx;
+        top * (1.0 - ty) + bottom * ty
+    }
+
+    /// The open cell with the highest influence, or undefined for a fully blocked
+    /// map.
+    pub fn argmax(&self) -> Option<Vec2> {
+        self.best(None, |a, b| a > b)
+    }
+
+    /// The open cell with the lowest influence, e.g. the safest place to retreat
+    /// to on a danger map.
+    pub fn argmin(&self) -> Option<Vec2> {
+        self.best(None, |a, b| a < b)
+    }
+
+    /// Like `argmax`, among the cells within `radius` of `center`.
+    pub fn argmax_within(&self, center: Vec2, radius: f32) -> Option<Vec2> {
+        self.best(Some((center, radius)), |a, b| a > b)
+    }
+
+    /// Like `argmin`, among the cells within `radius` of `center`.
+    pub fn argmin_within(&self, center: Vec2, radius: f32) -> Option<Vec2> {
+        self.best(Some((center, radius)), |a, b| a < b)
+    }
+
+    /// Flat row-major copy of every cell's influence.
+    pub fn values(&self) -> Vec<f32> {
+        self.values.clone()
+    }
+
+    /// Appends a square per cell holding influence to `out`, red for positive
+    /// and blue for negative, brighter toward the strongest magnitude on the
+    /// map, and a dark red one per blocked cell.
+    pub fn debug_geometry(&self, out: &mut DebugGeometry) {
+        let strongest = self
+            .values
+            .iter()
+            .filter(|value| value.is_finite())
+            .fold(0.0f32, |strongest, value| strongest.max(value.abs()));
+        for (index, &value) in self.values.iter().enumerate() {
+            let (x, y) = (index % self.width as usize, index / self.width as usize);
+            let level = (value.abs() / strongest).min(1.0);
+            let color = if self.blocked[index] {
+                debug::BLOCKED
+            } else if value > 0.0 {
+                [level, 0.1 * level, 0.05]
+            } else if value < 0.0 {
+                [0.05, 0.1 * level, level]
+            } else {
+                continue;
+            };
+            out.cell(x as f32, y as f32, color);
+        }
+    }
+}
+
+impl InfluenceMap {
+    /// Calls `apply` with every open cell within `radius` of `position` and its
+    /// linear falloff, from 1 at `position` to 0 at `radius`.
+    fn for_each_in_falloff(
+        &mut self,
+        position: Vec2,
+        radius: f32,
+        mut apply: impl FnMut(&mut f32, f32),
+    ) {
+        if radius <= 0.0 {
+            return;
+        }
+        let min_x = (position.x - radius).floor().max(0.0) as i32;
+        let min_y = (position.y - radius).floor().max(0.0) as i32;
+        let max_x = ((position.x + radius).ceil() as i32).min(self.width as i32 - 1);
+        let max_y = ((position.y + radius).ceil() as i32).min(self.height as i32 - 1);
+        for y in min_y..=max_y {
+            for x in min_x..=max_x {
+                let distance = position.distance(Vec2::new(x as f32, y as f32));
+                if let Some(index) = self.open(x, y).filter(|_| distance < radius) {
+                    apply(&mut self.values[index], 1.0 - distance / radius);
+                }
+            }
+        }
+    }
+
+    fn in_bounds(&self, x: i32, y: i32) -> bool {
+        x >= 0 && y >= 0 && x < self.width as i32 && y < self.height as i32
+    }
+
+    fn index(&self, x: i32, y: i32) -> usize {
+        y as usize * self.width as usize + x as usize
+    }
+
+    /// Index of an in-bounds, unblocked cell.
+    fn open(&self, x: i32, y: i32) -> Option<usize> {
+        if !self.in_bounds(x, y) {
+            return None;
+        }
+        let index = self.index(x, y);
+        (!self.blocked[index]).then_some(index)
+    }
+
+    fn best(&self, area: Option<(Vec2, f32)>, better: impl Fn(f32, f32) -> bool) -> Option<Vec2> {
+        let (min_x, min_y, max_x, max_y) = match area {
+            Some((center, radius)) => (
+                (center.x - radius).floor().max(0.0) as i32,
+                (center.y - radius).floor().max(0.0) as i32,
+                ((center.x + radius).ceil() as i32).min(self.width as i32 - 1),
+                ((center.y + radius).ceil() as i32).min(self.height as i32 - 1),
+            ),
+            None => (0, 0, self.width as i32 - 1, self.height as i32 - 1),
+        };
+        let mut best: Option<(f32, Vec2)> = None;
+        for y in min_y..=max_y {
+            for x in min_x..=max_x {
+                let Some(index) = self.open(x, y) else {
+                    continue;
+                };
+                let cell = Vec2::new(x as f32, y as f32);
+                if area.is_some_and(|(center, radius)| center.distance(cell) > radius) {
+                    continue;
+                }
+                let value = self.values[index];
+                if best.is_none_or(|(current, _)| better(value, current)) {
+                    best = Some((value, cell));
+                }
+            }
+        }
+        best.map(|(_, cell)| cell)
+    }
+}
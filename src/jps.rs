@@ -0,0 +1,192 @@
+use wasm_bindgen::prelude::*;
+
+use crate::arena::{self, SearchBuffers};
+use crate::grid::{octile, Grid};
+use crate::search::{self, OpenNode};
+
+#[wasm_bindgen]
+impl Grid {
+    /// Jump Point Search. Returns the same shortest paths and output format as
+    /// `find_path`, but prunes symmetric expansions on uniform-cost open areas.
+    /// Falls back to `find_path` once any terrain or obstacle cost differs from 1,
+    /// infinite ones included.
+    pub fn find_path_jps(&self, start_x: u32, start_y: u32, end_x: u32, end_y: u32) -> Vec<f32> {
+        if !self.has_uniform_costs() {
+            return self.find_path(start_x, start_y, end_x, end_y);
+        }
+        let (sx, sy, ex, ey) = (start_x as i32, start_y as i32, end_x as i32, end_y as i32);
+        if !self.is_walkable(sx, sy) || !self.is_walkable(ex, ey) {
+            return Vec::new();
+        }
+        match self.jump_points(sx, sy, ex, ey) {
+            Some(points) => {
+                let cells = self.expand_jumps(&points);
+                self.to_waypoints(&cells)
+            }
+            None => Vec::new(),
+        }
+    }
+}
+
+impl Grid {
+    fn jump_points(&self, sx: i32, sy: i32, ex: i32, ey: i32) -> Option<Vec<usize>> {
+        let cell_count = self.cell_count();
+        let start = self.index(sx, sy);
+        let goal = self.index(ex, ey);
+        let mut buffers = arena::search(cell_count);
+        let SearchBuffers {
+            g,
+            parent,
+            closed,
+            open,
+            ..
+        } = &mut *buffers;
+        let mut successors = Vec::new();
+
+        g[start] = 0.0;
+        open.push(OpenNode {
+            cost: octile(ex - sx, ey - sy),
+            index: start,
+        });
+
+        while let Some(OpenNode { index, .. }) = open.pop() {
+            if index == goal {
+                return Some(search::reconstruct(parent, goal));
+            }
+            if closed[index] {
+                continue;
+            }
+            closed[index] = true;
+
+            let (x, y) = self.coords(index);
+            let from = (parent[index] != usize::MAX).then(|| self.coords(parent[index]));
+            successors.clear();
+            self.pruned_neighbors(x, y, from, &mut successors);
+
+            for &(nx, ny) in &successors {
+                let Some((jx, jy)) = self.jump(nx, ny, x, y, ex, ey) else {
+                    continue;
+                };
+                let next = self.index(jx, jy);
+                if closed[next] {
+                    continue;
+                }
+                let tentative = g[index] + octile(jx - x, jy - y);
+                if tentative < g[next] {
+                    g[next] = tentative;
+                    parent[next] = index;
+                    open.push(OpenNode {
+                        cost: tentative + octile(ex - jx, ey - jy),
+                        index: next,
+                    });
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Natural and forced neighbors of `(x, y)` given the direction it was entered from.
+    fn pruned_neighbors(
+        &self,
+        x: i32,
+        y: i32,
+        from: Option<(i32, i32)>,
+        out: &mut Vec<(i32, i32)>,
+    ) {
+        let walkable = |x, y| self.is_walkable(x, y);
+        let Some((px, py)) = from else {
+            let mut edges = Vec::new();
+            self.neighbors(self.index(x, y), &mut edges);
+            out.extend(edges.into_iter().map(|(next, _)| self.coords(next)));
+            return;
+        };
+
+        let (dx, dy) = ((x - px).signum(), (y - py).signum());
+        if dx != 0 && dy != 0 {
+            let vertical = walkable(x, y + dy);
+            let horizontal = walkable(x + dx, y);
+            if vertical {
+                out.push((x, y + dy));
+            }
+            if horizontal {
+                out.push((x + dx, y));
+            }
+            if vertical && horizontal && walkable(x + dx, y + dy) {
+                out.push((x + dx, y + dy));
+            }
+        } else {
+            // A side cell is only forced when the cell behind it is blocked; otherwise
+            // the parent reaches it just as cheaply by moving diagonally.
+            let (sx, sy) = (dy.abs(), dx.abs());
+            let next = walkable(x + dx, y + dy);
+            if next {
+                out.push((x + dx, y + dy));
+            }
+            for side in [1, -1] {
+                let (ox, oy) = (sx * side, sy * side);
+                if walkable(x + ox, y + oy) && !walkable(x + ox - dx, y + oy - dy) {
+                    out.push((x + ox, y + oy));
+                    if next && walkable(x + dx + ox, y + dy + oy) {
+                        out.push((x + dx + ox, y + dy + oy));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Steps from `(px, py)` through `(x, y)` until reaching the goal, a cell with a
+    /// forced neighbor, or an obstacle.
+    fn jump(&self, x: i32, y: i32, px: i32, py: i32, ex: i32, ey: i32) -> Option<(i32, i32)> {
+        let (dx, dy) = (x - px, y - py);
+        let (mut x, mut y) = (x, y);
+        loop {
+            if !self.is_walkable(x, y) {
+                return None;
+            }
+            if (x, y) == (ex, ey) {
+                return Some((x, y));
+            }
+
+            if dx != 0 && dy != 0 {
+                if self.jump(x + dx, y, x, y, ex, ey).is_some()
+                    || self.jump(x, y + dy, x, y, ex, ey).is_some()
+                {
+                    return Some((x, y));
+                }
+                if !self.is_walkable(x + dx, y) || !self.is_walkable(x, y + dy) {
+                    return None;
+                }
+            } else if dx != 0 {
+                if (self.is_walkable(x, y - 1) && !self.is_walkable(x - dx, y - 1))
+                    || (self.is_walkable(x, y + 1) && !self.is_walkable(x - dx, y + 1))
+                {
+                    return Some((x, y));
+                }
+            } else if (self.is_walkable(x - 1, y) && !self.is_walkable(x - 1, y - dy))
+                || (self.is_walkable(x + 1, y) && !self.is_walkable(x + 1, y - dy))
+            {
+                return Some((x, y));
+            }
+
+            x += dx;
+            y += dy;
+        }
+    }
+
+    /// Fills in the straight or diagonal runs between consecutive jump points.
+    fn expand_jumps(&self, points: &[usize]) -> Vec<usize> {
+        let mut cells = vec![points[0]];
+        for pair in points.windows(2) {
+            let (mut x, mut y) = self.coords(pair[0]);
+            let (tx, ty) = self.coords(pair[1]);
+            let (dx, dy) = ((tx - x).signum(), (ty - y).signum());
+            while (x, y) != (tx, ty) {
+                x += dx;
+                y += dy;
+                cells.push(self.index(x, y));
+            }
+        }
+        cells
+    }
+}
@@ -0,0 +1,526 @@
+use wasm_bindgen::prelude::*;
+
+use crate::bake::BakeConfig;
+use crate::error::Error;
+use crate::json::Json;
+use crate::navmesh::NavMesh;
+
+const GLB_MAGIC: u32 = 0x4654_6C67;
+const JSON_CHUNK: u32 = 0x4E4F_534A;
+const BIN_CHUNK: u32 = 0x004E_4942;
+
+const FLOAT: u32 = 5126;
+const UNSIGNED_BYTE: u32 = 5121;
+const UNSIGNED_SHORT: u32 = 5123;
+const UNSIGNED_INT: u32 = 5125;
+
+const TRIANGLES: u32 = 4;
+const TRIANGLE_STRIP: u32 = 5;
+const TRIANGLE_FAN: u32 = 6;
+
+/// A column-major 4x4 matrix, as in three.js `Matrix4.elements` and glTF.
+type Matrix = [f32; 16];
+
+const IDENTITY: Matrix = [
+    1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0,
+];
+
+/// Level geometry gathered for `NavMesh::bake_level` straight from the buffers
+/// a renderer already holds: flat or interleaved three.js `BufferGeometry`
+/// attributes and binary glTF files, each optionally placed by a transform.
+/// Everything is merged into one triangle soup in world space, `y` up.
+#[wasm_bindgen]
+#[derive(Clone, Debug, Default)]
+pub struct LevelGeometry {
+    positions: Vec<f32>,
+    indices: Vec<u32>,
+}
+
+#[wasm_bindgen]
+impl LevelGeometry {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> LevelGeometry {
+        LevelGeometry::default()
+    }
+
+    /// Adds flat `[x, y, z, ...]` positions, e.g. `geometry.attributes.position.array`.
+    /// Empty `indices` reads the positions as consecutive triangles, for
+    /// geometry without an index. `matrix` is a column-major 4x4 transform such
+    /// as `mesh.matrixWorld.elements`.
+    pub fn add_positions(
+        &mut self,
+        positions: &[f32],
+        indices: &[u32],
+        matrix: Option<Vec<f32>>,
+    ) -> Result<(), Error> {
+        self.add_interleaved(positions, 3, 0, indices, matrix)
+    }
+
+    /// Adds positions read from an interleaved buffer, `stride` floats per
+    /// vertex with the position `offset` floats in, matching three.js
+    /// `InterleavedBuffer.stride` and `InterleavedBufferAttribute.offset`.
+    /// `indices` and `matrix` work as in `add_positions`.
+    pub fn add_interleaved(
+        &mut self,
+        data: &[f32],
+        stride: u32,
+        offset: u32,
+        indices: &[u32],
+        matrix: Option<Vec<f32>>,
+    ) -> Result<(), Error> {
+        let (stride, offset) = (stride as usize, offset as usize);
+        if stride < 3 || offset + 3 > stride {
+            return Err(Error::InvalidInput(format!(
+                "a position {} floats into a {}-float vertex does not fit",
+                offset, stride
+            )));
+        }
+        let matrix = match matrix {
+            Some(values) => <Matrix>::try_from(values.as_slice()).map_err(|_| {
+                Error::InvalidInput(format!("a matrix has 16 values, got {}", values.len()))
+            })?,
+            None => IDENTITY,
+        };
+        // The last vertex may stop right after its position.
+        let count = (data.len() + stride - offset - 3) / stride;
+        let points = (0..count).map(|vertex| {
+            let at = vertex * stride + offset;
+            [data[at], data[at + 1], data[at + 2]]
+        });
+        match indices {
+            [] => {
+                let sequential: Vec<u32> = (0..count as u32).collect();
+                self.append(points, count, &sequential, &matrix)
+            }
+            _ => self.append(points, count, indices, &matrix),
+        }
+    }
+
+    /// Adds every triangle mesh of a binary glTF (`.glb`) file, reading
+    /// accessors by their byte offsets and strides. With `apply_transforms`,
+    /// meshes are placed by the node hierarchy of the default scene; without,
+    /// each mesh is added once in its own space. Returns how many primitives
+    /// were added. Positions must be plain floats embedded in the file.
+    pub fn add_glb(&mut self, glb: &[u8], apply_transforms: bool) -> Result<u32, Error> {
+        let (json, bin) = read_glb(glb)?;
+        let gltf = Gltf {
+            json: &json,
+            bin,
+            buffer_views: json
+                .get("bufferViews")
+                .and_then(Json::as_array)
+                .unwrap_or(&[]),
+            accessors: json
+                .get("accessors")
+                .and_then(Json::as_array)
+                .unwrap_or(&[]),
+        };
+        let meshes = json.get("meshes").and_then(Json::as_array).unwrap_or(&[]);
+        let mut placements = Vec::new();
+        if apply_transforms {
+            for root in gltf.scene_roots()? {
+                gltf.place(root, &IDENTITY, 0, &mut placements)?;
+            }
+        } else {
+            placements.extend((0..meshes.len()).map(|mesh| (mesh, IDENTITY)));
+        }
+        let mut added = 0;
+        for (mesh, matrix) in placements {
+            let mesh = meshes
+                .get(mesh)
+                .ok_or_else(|| Error::InvalidInput(format!("no mesh {}", mesh)))?;
+            for primitive in mesh.field("primitives", Json::as_array)? {
+                if gltf.add_primitive(self, primitive, &matrix)? {
+                    added += 1;
+                }
+            }
+        }
+        Ok(added)
+    }
+
+    /// Flat world-space positions gathered so far.
+    pub fn positions(&self) -> Vec<f32> {
+        self.positions.clone()
+    }
+
+    pub fn indices(&self) -> Vec<u32> {
+        self.indices.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn vertex_count(&self) -> u32 {
+        (self.positions.len() / 3) as u32
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn triangle_count(&self) -> u32 {
+        (self.indices.len() / 3) as u32
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.indices.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.positions.clear();
+        self.indices.clear();
+    }
+}
+
+#[wasm_bindgen]
+impl NavMesh {
+    /// Like `bake`, from geometry gathered in a `LevelGeometry`.
+    pub fn bake_level(level: &LevelGeometry, config: &BakeConfig) -> Result<NavMesh, Error> {
+        NavMesh::bake(&level.positions, &level.indices, config)
+    }
+}
+
+impl LevelGeometry {
+    /// Transforms `count` points into the soup and adds the triangles
+    /// `indices` make of them, flipping their winding under mirroring
+    /// transforms so they keep facing the same way.
+    fn append(
+        &mut self,
+        points: impl Iterator<Item = [f32; 3]>,
+        count: usize,
+        indices: &[u32],
+        matrix: &Matrix,
+    ) -> Result<(), Error> {
+        if !indices.len().is_multiple_of(3) {
+            return Err(Error::InvalidInput(format!(
+                "{} indices do not make whole triangles",
+                indices.len()
+            )));
+        }
+        if let Some(&bad) = indices.iter().find(|&&index| index as usize >= count) {
+            return Err(Error::InvalidInput(format!(
+                "index {} is out of range of {} vertices",
+                bad, count
+            )));
+        }
+        let base = self.vertex_count();
+        for point in points {
+            self.positions.extend_from_slice(&transform(matrix, point));
+        }
+        let mirrored = determinant(matrix) < 0.0;
+        for triangle in indices.chunks_exact(3) {
+            let [a, b, c] = [triangle[0], triangle[1], triangle[2]].map(|index| base + index);
+            if mirrored {
+                self.indices.extend_from_slice(&[a, c, b]);
+            } else {
+                self.indices.extend_from_slice(&[a, b, c]);
+            }
+        }
+        Ok(())
+    }
+}
+
+fn transform(m: &Matrix, [x, y, z]: [f32; 3]) -> [f32; 3] {
+    [
+        m[0] * x + m[4] * y + m[8] * z + m[12],
+        m[1] * x + m[5] * y + m[9] * z + m[13],
+        m[2] * x + m[6] * y + m[10] * z + m[14],
+    ]
+}
+
+/// Determinant of the upper 3x3 part, negative for transforms that mirror.
+fn determinant(m: &Matrix) -> f32 {
+    m[0] * (m[5] * m[10] - m[9] * m[6]) - m[4] * (m[1] * m[10] - m[9] * m[2])
+        + m[8] * (m[1] * m[6] - m[5] * m[2])
+}
+
+fn multiply(a: &Matrix, b: &Matrix) -> Matrix {
+    let mut out = [0.0; 16];
+    for column in 0..4 {
+        for row in 0..4 {
+            out[column * 4 + row] = (0..4).map(|k| a[k * 4 + row] * b[column * 4 + k]).sum();
+        }
+    }
+    out
+}
+
+/// The matrix for a glTF translation, rotation quaternion and scale.
+fn compose(translation: [f32; 3], [x, y, z, w]: [f32; 4], scale: [f32; 3]) -> Matrix {
+    let [sx, sy, sz] = scale;
+    [
+        (1.0 - 2.0 * (y * y + z * z)) * sx,
+        2.0 * (x * y + z * w) * sx,
+        2.0 * (x * z - y * w) * sx,
+        0.0,
+        2.0 * (x * y - z * w) * sy,
+        (1.0 - 2.0 * (x * x + z * z)) * sy,
+        2.0 * (y * z + x * w) * sy,
+        0.0,
+        2.0 * (x * z + y * w) * sz,
+        2.0 * (y * z - x * w) * sz,
+        (1.0 - 2.0 * (x * x + y * y)) * sz,
+        0.0,
+        translation[0],
+        translation[1],
+        translation[2],
+        1.0,
+    ]
+}
+
+/// Splits a `.glb` file into its JSON document and binary chunk.
+fn read_glb(glb: &[u8]) -> Result<(Json, &[u8]), Error> {
+    let word = |at: usize| {
+        glb.get(at..at + 4)
+            .map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+            .ok_or_else(|| Error::InvalidInput("the glTF file is cut short".into()))
+    };
+    if word(0)? != GLB_MAGIC {
+        return Err(Error::InvalidInput("not a binary glTF file".into()));
+    }
+    if word(4)? != 2 {
+        return Err(Error::InvalidInput(format!(
+            "glTF version {} is not supported",
+            word(4)?
+        )));
+    }
+    let length = (word(8)? as usize).min(glb.len());
+    let (mut json, mut bin) = (None, &[][..]);
+    let mut at = 12;
+    while at + 8 <= length {
+        let (size, kind) = (word(at)? as usize, word(at + 4)?);
+        let chunk = glb
+            .get(at + 8..at + 8 + size)
+            .ok_or_else(|| Error::InvalidInput("the glTF file is cut short".into()))?;
+        match kind {
+            JSON_CHUNK => {
+                let text = std::str::from_utf8(chunk)
+                    .map_err(|_| Error::InvalidInput("the glTF JSON is not UTF-8".into()))?;
+                json = Some(Json::parse(text)?);
+            }
+            BIN_CHUNK => bin = chunk,
+            _ => {}
+        }
+        at += 8 + size.next_multiple_of(4);
+    }
+    let json = json.ok_or_else(|| Error::InvalidInput("the glTF file has no JSON".into()))?;
+    Ok((json, bin))
+}
+
+/// Index of a `u32` field, if present.
+fn index(json: &Json, key: &str) -> Option<usize> {
+    json.get(key)
+        .and_then(Json::as_f64)
+        .map(|value| value as usize)
+}
+
+fn floats<const N: usize>(json: &Json, key: &str, default: [f32; N]) -> Result<[f32; N], Error> {
+    match json.get(key) {
+        None => Ok(default),
+        Some(value) => value
+            .as_f32s()
+            .and_then(|values| values.try_into().ok())
+            .ok_or_else(|| Error::InvalidInput(format!("'{}' needs {} numbers", key, N))),
+    }
+}
+
+struct Gltf<'a> {
+    json: &'a Json,
+    bin: &'a [u8],
+    buffer_views: &'a [Json],
+    accessors: &'a [Json],
+}
+
+impl Gltf<'_> {
+    /// Root nodes of the default scene, or of the first one.
+    fn scene_roots(&self) -> Result<Vec<usize>, Error> {
+        let scenes = self
+            .json
+            .get("scenes")
+            .and_then(Json::as_array)
+            .unwrap_or(&[]);
+        let Some(scene) = scenes.get(index(self.json, "scene").unwrap_or(0)) else {
+            return Ok(Vec::new());
+        };
+        Ok(scene
+            .get("nodes")
+            .and_then(Json::as_f32s)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|node| node as usize)
+            .collect())
+    }
+
+    /// Collects the meshes under `node` with their world matrices.
+    fn place(
+        &self,
+        node: usize,
+        parent: &Matrix,
+        depth: usize,
+        out: &mut Vec<(usize, Matrix)>,
+    ) -> Result<(), Error> {
+        let nodes = self
+            .json
+            .get("nodes")
+            .and_then(Json::as_array)
+            .unwrap_or(&[]);
+        let json = nodes
+            .get(node)
+            .ok_or_else(|| Error::InvalidInput(format!("no node {}", node)))?;
+        if depth > nodes.len() {
+            return Err(Error::InvalidInput(
+                "the glTF node hierarchy has a cycle".into(),
+            ));
+        }
+        let local = match json.get("matrix") {
+            Some(_) => floats(json, "matrix", IDENTITY)?,
+            None => compose(
+                floats(json, "translation", [0.0; 3])?,
+                floats(json, "rotation", [0.0, 0.0, 0.0, 1.0])?,
+                floats(json, "scale", [1.0; 3])?,
+            ),
+        };
+        let world = multiply(parent, &local);
+        if let Some(mesh) = index(json, "mesh") {
+            out.push((mesh, world));
+        }
+        for child in json
+            .get("children")
+            .and_then(Json::as_f32s)
+            .unwrap_or_default()
+        {
+            self.place(child as usize, &world, depth + 1, out)?;
+        }
+        Ok(())
+    }
+
+    /// Adds a primitive if it is made of triangles, returning whether it was.
+    fn add_primitive(
+        &self,
+        level: &mut LevelGeometry,
+        primitive: &Json,
+        matrix: &Matrix,
+    ) -> Result<bool, Error> {
+        let mode = index(primitive, "mode").unwrap_or(TRIANGLES as usize) as u32;
+        if !matches!(mode, TRIANGLES | TRIANGLE_STRIP | TRIANGLE_FAN) {
+            return Ok(false);
+        }
+        let position = primitive
+            .get("attributes")
+            .and_then(|attributes| index(attributes, "POSITION"))
+            .ok_or_else(|| Error::InvalidInput("a glTF primitive has no POSITION".into()))?;
+        let (data, stride, count) = self.view(position, FLOAT, "VEC3")?;
+        let points = (0..count).map(|vertex| {
+            let at = vertex * stride;
+            let read = |i: usize| {
+                f32::from_le_bytes([
+                    data[at + i * 4],
+                    data[at + i * 4 + 1],
+                    data[at + i * 4 + 2],
+                    data[at + i * 4 + 3],
+                ])
+            };
+            [read(0), read(1), read(2)]
+        });
+        let corners: Vec<u32> = match index(primitive, "indices") {
+            Some(accessor) => self.indices(accessor)?,
+            None => (0..count as u32).collect(),
+        };
+        let triangles = match mode {
+            TRIANGLE_STRIP => (2..corners.len())
+                .flat_map(|i| match i % 2 {
+                    0 => [corners[i - 2], corners[i - 1], corners[i]],
+                    _ => [corners[i - 1], corners[i - 2], corners[i]],
+                })
+                .collect(),
+            TRIANGLE_FAN => (2..corners.len())
+                .flat_map(|i| [corners[0], corners[i - 1], corners[i]])
+                .collect(),
+            _ => corners,
+        };
+        level.append(points, count, &triangles, matrix)?;
+        Ok(true)
+    }
+
+    fn indices(&self, accessor: usize) -> Result<Vec<u32>, Error> {
+        let component_type = self
+            .accessor(accessor)?
+            .field("componentType", Json::as_f64)? as u32;
+        let size = match component_type {
+            UNSIGNED_BYTE => 1,
+            UNSIGNED_SHORT => 2,
+            UNSIGNED_INT => 4,
+            _ => {
+                return Err(Error::InvalidInput(format!(
+                    "glTF index type {} is not supported",
+                    component_type
+                )))
+            }
+        };
+        let (data, stride, count) = self.view(accessor, component_type, "SCALAR")?;
+        Ok((0..count)
+            .map(|i| {
+                let bytes = &data[i * stride..i * stride + size];
+                match size {
+                    1 => bytes[0] as u32,
+                    2 => u16::from_le_bytes([bytes[0], bytes[1]]) as u32,
+                    _ => u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+                }
+            })
+            .collect())
+    }
+
+    fn accessor(&self, accessor: usize) -> Result<&Json, Error> {
+        self.accessors
+            .get(accessor)
+            .ok_or_else(|| Error::InvalidInput(format!("no accessor {}", accessor)))
+    }
+
+    /// The bytes from an accessor's first element on, its byte stride and its
+    /// element count, checking its layout and that every element lies inside
+    /// the binary chunk.
+    fn view(
+        &self,
+        accessor: usize,
+        component_type: u32,
+        kind: &str,
+    ) -> Result<(&[u8], usize, usize), Error> {
+        let json = self.accessor(accessor)?;
+        let found = (
+            json.field("componentType", Json::as_f64)? as u32,
+            json.field("type", Json::as_str)?,
+        );
+        if found != (component_type, kind) || json.get("sparse").is_some() {
+            return Err(Error::InvalidInput(format!(
+                "glTF accessor {} is not a plain {} of type {}",
+                accessor, kind, component_type
+            )));
+        }
+        let count = json.field("count", Json::as_f64)? as usize;
+        let view = index(json, "bufferView")
+            .and_then(|view| self.buffer_views.get(view))
+            .ok_or_else(|| {
+                Error::InvalidInput(format!("glTF accessor {} has no buffer view", accessor))
+            })?;
+        if index(view, "buffer").unwrap_or(0) != 0 || self.json.get("buffers").is_none() {
+            return Err(Error::InvalidInput(
+                "only buffers embedded in the .glb are supported".into(),
+            ));
+        }
+        let element = match (component_type, kind) {
+            (FLOAT, "VEC3") => 12,
+            (UNSIGNED_BYTE, _) => 1,
+            (UNSIGNED_SHORT, _) => 2,
+            _ => 4,
+        };
+        let stride = index(view, "byteStride").unwrap_or(element);
+        let start = index(view, "byteOffset").unwrap_or(0) + index(json, "byteOffset").unwrap_or(0);
+        let end = match count {
+            0 => start,
+            _ => start + (count - 1) * stride + element,
+        };
+        let view_end = index(view, "byteOffset").unwrap_or(0)
+            + view.field("byteLength", Json::as_f64)? as usize;
+        if end > view_end || end > self.bin.len() {
+            return Err(Error::InvalidInput(format!(
+                "glTF accessor {} runs past its buffer",
+                accessor
+            )));
+        }
+        Ok((&self.bin[start..end], stride, count))
+    }
+}
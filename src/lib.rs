@@ -1,5 +1,220 @@
+//! Game AI building blocks, from steering and pathfinding to decision making
+//! and learning, exported to JavaScript through wasm-bindgen.
+//!
+//! The crate builds for native targets as well, e.g. for an authoritative
+//! game server running the simulation that browsers predict. Whatever takes a
+//! JS callback has a Rust twin taking a closure or trait object, such as
+//! `BehaviorTreeBuilder::action_fn`, `EventBus::on_with`, `Mcts::search_with`
+//! and `GeneticAlgorithm::evolve_with`, and buffers handed out as
+//! `Float32Array` views also come as slices or copies. The views only exist
+//! on wasm32; the `*_async` methods, WebGPU and the shared-memory world worker
+//! build everywhere but need a JS host to run.
+
 use wasm_bindgen::prelude::*;
 
+mod arena;
+pub mod archetype;
+pub mod avoidance;
+pub mod bake;
+pub mod ballistics;
+mod batch;
+pub mod behavior_tree;
+pub mod bench;
+pub mod blackboard;
+mod bytes;
+mod carve;
+pub mod clearance;
+mod clock;
+pub mod cluster;
+pub mod context_steering;
+pub mod cooperative;
+pub mod crowd;
+pub mod curves;
+pub mod debug;
+pub mod director;
+pub mod dstar;
+pub mod error;
+pub mod events;
+pub mod expression;
+pub mod flock;
+pub mod flow_field;
+pub mod forest;
+pub mod formation;
+mod fov;
+pub mod fuzzy;
+pub mod genetic;
+pub mod goal_arbiter;
+pub mod goap;
+pub mod graph;
+pub mod grid;
+mod hearing;
+pub mod hex;
+pub mod hpa;
+pub mod htn;
+pub mod influence;
+mod json;
+mod jps;
+pub mod level;
+mod links;
+mod locomotion;
+pub mod markov;
+pub mod math;
+pub mod mcts;
+pub mod memory;
+mod mesh_query;
+pub mod mlp;
+pub mod nav_filter;
+pub mod navmesh;
+mod nearest;
+pub mod neat;
+pub mod needs;
+pub mod negamax;
+pub mod noise;
+pub mod observation;
+pub mod obstacles;
+#[cfg(feature = "onnx")]
+pub mod onnx;
+pub mod options;
+pub mod orca;
+mod parallel;
+pub mod path_cache;
+pub mod path_following;
+pub mod path_queue;
+pub mod patrol;
+pub mod perception;
+pub mod placement;
+pub mod qlearning;
+pub mod queues;
+pub mod random;
+mod raycast;
+mod replay;
+pub mod save_game;
+mod search;
+pub mod shared_world;
+pub mod smoothing;
+pub mod spatial_hash;
+pub mod squad;
+pub mod state_machine;
+pub mod stats;
+pub mod steering;
+pub mod steering3d;
+pub mod steering_pipeline;
+mod stuck;
+pub mod tactical;
+pub mod targeting;
+mod task;
+pub mod terrain;
+pub mod territory;
+mod theta;
+mod tiles;
+pub mod timers;
+pub mod topology;
+pub mod traffic;
+mod tree_loader;
+pub mod utility;
+pub mod vector_index;
+pub mod voxel;
+#[cfg(feature = "webgpu")]
+pub mod webgpu;
+pub mod world;
+mod xml;
+
+pub use archetype::Archetypes;
+pub use arena::{arena_bytes, reset_frame};
+pub use avoidance::Obstacles;
+pub use bake::BakeConfig;
+pub use ballistics::{
+    ballistic_arc, ballistic_lead, ballistic_range, lead_target, projectile_position, time_to_impact,
+    AimSolution,
+};
+pub use behavior_tree::{BehaviorTree, BehaviorTreeBuilder, BehaviorTreeSnapshot, NodeTransition, Status};
+pub use bench::{bench_crowd, bench_paths, maze_grid, random_grid, BenchReport, PathAlgorithm};
+pub use blackboard::Blackboard;
+pub use cluster::{dbscan, kmeans, Clustering};
+pub use context_steering::ContextMap;
+pub use cooperative::CooperativePlanner;
+pub use crowd::{Crowd, CrowdAgentParams, MoveState};
+pub use curves::Curve;
+pub use debug::DebugGeometry;
+pub use director::{Director, PacingPhase};
+pub use dstar::Path;
+pub use error::Error;
+pub use events::{AiEvent, EventBus, EventKind};
+pub use expression::Expression;
+pub use flock::Flock;
+pub use flow_field::FlowField;
+pub use forest::{Aggregation, DecisionForest};
+pub use formation::Formation;
+pub use fuzzy::{Defuzzification, FuzzySystem};
+pub use genetic::{Crossover, Encoding, GeneticAlgorithm, Selection};
+pub use goal_arbiter::GoalArbiter;
+pub use goap::Planner;
+pub use graph::Graph;
+pub use grid::Grid;
+pub use hex::{hex_distance, hex_line, hex_neighbors, hex_round, HexGrid, HexLayout};
+pub use hpa::HierarchicalGrid;
+pub use htn::HtnPlanner;
+pub use influence::InfluenceMap;
+pub use level::LevelGeometry;
+pub use locomotion::{locomotion_stride, MotionPhase};
+pub use markov::MarkovChain;
+pub use math::{Vec2, Vec3};
+pub use mcts::{Game, Mcts};
+pub use memory::{MemoryEntry, Sense, SensoryMemory};
+pub use mlp::{Activation, Mlp};
+pub use nav_filter::NavFilter;
+pub use navmesh::NavMesh;
+pub use nearest::NearestGoal;
+pub use neat::{Genome, NeatConfig, Population};
+pub use needs::Needs;
+pub use negamax::{Negamax, Position};
+pub use noise::Noise;
+pub use observation::ObservationEncoder;
+pub use obstacles::ObstacleSet;
+#[cfg(feature = "onnx")]
+pub use onnx::Model;
+pub use options::{
+    CrowdAgentOptions, FlockOptions, GridPathQuery, MeshPathQuery, NavFilterOptions,
+    ObserverOptions, WorldAgentOptions,
+};
+pub use orca::CrowdSimulator;
+#[cfg(feature = "threads")]
+pub use parallel::{set_thread_count, thread_count};
+pub use path_cache::PathCache;
+pub use path_following::PathFollower;
+pub use path_queue::PathRequestQueue;
+pub use patrol::{PatrolMode, PatrolRoute, Patroller, WaypointGraph};
+pub use perception::{Perception, Stimulus};
+pub use placement::PlacementQuery;
+pub use qlearning::QLearner;
+pub use queues::Queues;
+pub use random::Rng;
+pub use raycast::RaycastHit;
+pub use save_game::SaveGame;
+pub use shared_world::{run_world_worker, SharedWorld, WorldWorker};
+pub use smoothing::{resample_bezier, resample_catmull_rom};
+pub use spatial_hash::SpatialHash;
+pub use squad::{Order, Role, Squad};
+pub use state_machine::{StateHistory, StateMachine};
+pub use stats::AiStats;
+pub use steering::{intercept_point, Agent};
+pub use steering3d::{Agent3, Flock3};
+pub use steering_pipeline::SteeringPipeline;
+pub use tactical::TacticalQuery;
+pub use targeting::Targeting;
+pub use terrain::TerrainCosts;
+pub use territory::Territory;
+pub use timers::Timers;
+pub use topology::{Topology, TopologyTag};
+pub use traffic::{RoadNetwork, Traffic};
+pub use tree_loader::LeafRegistry;
+pub use utility::UtilityBrain;
+pub use vector_index::{Metric, Neighbor, VectorIndex};
+pub use voxel::{VoxelCell, VoxelGrid};
+#[cfg(feature = "webgpu")]
+pub use webgpu::{request_gpu_device, GpuDevice};
+pub use world::{AiWorld, Behavior, Lod};
+
 #[wasm_bindgen]
 extern {
     pub fn alert(s: &str);
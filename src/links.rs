@@ -0,0 +1,331 @@
+use std::collections::HashMap;
+
+use wasm_bindgen::prelude::*;
+
+use crate::error::Error;
+use crate::grid::{octile, Grid, Traversal};
+use crate::navmesh::{distance, string_pull, NavMesh, Point};
+use crate::search;
+
+/// Marker for waypoints reached by ordinary movement in linked paths.
+const WALK: f32 = -1.0;
+
+/// A user-defined shortcut such as a jump, ladder or teleporter.
+#[derive(Clone)]
+pub(crate) struct OffMeshLink<P> {
+    pub id: u32,
+    pub from: P,
+    pub to: P,
+    pub cost: f32,
+    pub tag: u32,
+    pub bidirectional: bool,
+}
+
+impl<P: Copy> OffMeshLink<P> {
+    /// The link as one or two one-way `(from, to, link)` hops.
+    fn hops(&self) -> impl Iterator<Item = (P, P, &Self)> {
+        let back = self.bidirectional.then_some((self.to, self.from, self));
+        std::iter::once((self.from, self.to, self)).chain(back)
+    }
+}
+
+fn validate_link_cost(cost: f32) -> Result<(), Error> {
+    if cost >= 0.0 && cost.is_finite() {
+        Ok(())
+    } else {
+        Err(Error::InvalidInput(format!(
+            "link cost must be finite and non-negative, got {}",
+            cost
+        )))
+    }
+}
+
+/// Lowers a heuristic scale so it never overestimates across a link whose cost
+/// is below its straight-line length.
+fn link_scale(scale: f32, span: f32, cost: f32) -> f32 {
+    if span > 0.0 {
+        scale.min(cost / span)
+    } else {
+        scale
+    }
+}
+
+/// Removes the link with `id` from `links`. Returns whether it existed.
+fn remove_link<P>(links: &mut Vec<OffMeshLink<P>>, id: u32) -> bool {
+    let before = links.len();
+    links.retain(|link| link.id != id);
+    links.len() != before
+}
+
+#[wasm_bindgen]
+impl Grid {
+    /// Registers a connection between two cells that is not a regular step, such as
+    /// a jump or teleporter, costing `cost` to traverse. `tag` is reported in paths
+    /// from `find_path_with_links` and must fit exactly in an f32 (below 2^24).
+    /// Returns the link id.
+    #[allow(clippy::too_many_arguments)]
+    pub fn add_off_mesh_link(
+        &mut self,
+        from_x: u32,
+        from_y: u32,
+        to_x: u32,
+        to_y: u32,
+        cost: f32,
+        tag: u32,
+        bidirectional: bool,
+    ) -> Result<u32, Error> {
+        validate_link_cost(cost)?;
+        for (x, y) in [(from_x, from_y), (to_x, to_y)] {
+            if x >= self.width() || y >= self.height() {
+                return Err(Error::InvalidInput(format!(
+                    "link endpoint ({}, {}) is outside the grid",
+                    x, y
+                )));
+            }
+        }
+        let id = self.next_link;
+        self.next_link = self.next_link.wrapping_add(1);
+        self.off_mesh_links.push(OffMeshLink {
+            id,
+            from: self.index(from_x as i32, from_y as i32),
+            to: self.index(to_x as i32, to_y as i32),
+            cost,
+            tag,
+            bidirectional,
+        });
+        Ok(id)
+    }
+
+    /// Returns false for unknown ids.
+    pub fn remove_off_mesh_link(&mut self, id: u32) -> bool {
+        remove_link(&mut self.off_mesh_links, id)
+    }
+
+    /// Like `find_path`, but may also use off-mesh links, and returns flat
+    /// `[x0, y0, link0, x1, y1, link1, ...]`. A waypoint's `link` is the tag of the
+    /// link taken to reach it from the previous waypoint, or -1 for a normal step.
+    /// Links with a blocked endpoint are ignored. Other pathfinding methods do not
+    /// use links.
+    pub fn find_path_with_links(
+        &self,
+        start_x: u32,
+        start_y: u32,
+        end_x: u32,
+        end_y: u32,
+    ) -> Vec<f32> {
+        let traversal = Traversal::default();
+        let (sx, sy, ex, ey) = (start_x as i32, start_y as i32, end_x as i32, end_y as i32);
+        if !self.is_passable(sx, sy, traversal) || !self.is_passable(ex, ey, traversal) {
+            return Vec::new();
+        }
+
+        let passable = |index| {
+            let (x, y) = self.coords(index);
+            self.is_passable(x, y, traversal)
+        };
+        let hops: Vec<_> = self
+            .off_mesh_links
+            .iter()
+            .flat_map(|link| link.hops())
+            .filter(|&(from, to, _)| passable(from) && passable(to))
+            .collect();
+        let mut outgoing: HashMap<usize, Vec<usize>> = HashMap::new();
+        let mut scale = self.heuristic_scale(traversal);
+        for (hop, &(from, to, link)) in hops.iter().enumerate() {
+            outgoing.entry(from).or_default().push(hop);
+            let ((fx, fy), (tx, ty)) = (self.coords(from), self.coords(to));
+            scale = link_scale(scale, octile(tx - fx, ty - fy), link.cost);
+        }
+
+        // Each hop is a node of its own between its two cells, so the result
+        // records which link was taken.
+        let cells = self.cell_count();
+        let heuristic = |index: usize| {
+            let cell = if index < cells {
+                index
+            } else {
+                hops[index - cells].1
+            };
+            let (x, y) = self.coords(cell);
+            octile(x - ex, y - ey) * scale
+        };
+        let path = search::astar(
+            cells + hops.len(),
+            self.index(sx, sy),
+            self.index(ex, ey),
+            |index, out| {
+                if index >= cells {
+                    out.push((hops[index - cells].1, 0.0));
+                    return;
+                }
+                self.neighbors_for(index, traversal, out);
+                for &hop in outgoing.get(&index).into_iter().flatten() {
+                    out.push((cells + hop, hops[hop].2.cost));
+                }
+            },
+            heuristic,
+        );
+
+        let Some(path) = path else {
+            return Vec::new();
+        };
+        let mut waypoints = Vec::with_capacity(path.len() * 3);
+        let mut marker = WALK;
+        for index in path {
+            if index >= cells {
+                marker = hops[index - cells].2.tag as f32;
+                continue;
+            }
+            let (x, y) = self.coords(index);
+            waypoints.extend_from_slice(&[x as f32, y as f32, marker]);
+            marker = WALK;
+        }
+        waypoints
+    }
+}
+
+#[wasm_bindgen]
+impl NavMesh {
+    /// Registers a connection between two points on the mesh that is not walkable
+    /// surface, such as a jump, ladder or teleporter, costing `cost` to traverse.
+    /// Endpoints are resolved to triangles at query time, so links survive tile
+    /// streaming and are inactive while an endpoint is off the mesh or obstructed.
+    /// `tag` is reported in paths from `find_path_with_links` and must fit exactly
+    /// in an f32 (below 2^24). Returns the link id.
+    #[allow(clippy::too_many_arguments)]
+    pub fn add_off_mesh_link(
+        &mut self,
+        from_x: f32,
+        from_y: f32,
+        from_z: f32,
+        to_x: f32,
+        to_y: f32,
+        to_z: f32,
+        cost: f32,
+        tag: u32,
+        bidirectional: bool,
+    ) -> Result<u32, Error> {
+        validate_link_cost(cost)?;
+        let id = self.next_link;
+        self.next_link = self.next_link.wrapping_add(1);
+        self.off_mesh_links.push(OffMeshLink {
+            id,
+            from: [from_x, from_y, from_z],
+            to: [to_x, to_y, to_z],
+            cost,
+            tag,
+            bidirectional,
+        });
+        Ok(id)
+    }
+
+    /// Returns false for unknown ids.
+    pub fn remove_off_mesh_link(&mut self, id: u32) -> bool {
+        remove_link(&mut self.off_mesh_links, id)
+    }
+
+    /// Like `find_path`, but may also use off-mesh links, and returns flat
+    /// `[x0, y0, z0, link0, x1, y1, z1, link1, ...]`. A waypoint's `link` is the tag
+    /// of the link taken to reach it from the previous waypoint, or -1 for normal
+    /// movement. Other pathfinding methods do not use links.
+    pub fn find_path_with_links(
+        &self,
+        start_x: f32,
+        start_y: f32,
+        start_z: f32,
+        end_x: f32,
+        end_y: f32,
+        end_z: f32,
+    ) -> Vec<f32> {
+        let start = [start_x, start_y, start_z];
+        let end = [end_x, end_y, end_z];
+        let (Some(from), Some(to)) = (self.locate(start), self.locate(end)) else {
+            return Vec::new();
+        };
+        if self.is_blocked(from) || self.is_blocked(to) {
+            return Vec::new();
+        }
+
+        // One-way hops whose endpoints are on open triangles.
+        let hops: Vec<(usize, usize, Point, Point, &OffMeshLink<Point>)> = self
+            .off_mesh_links
+            .iter()
+            .flat_map(|link| link.hops())
+            .filter_map(|(a, b, link)| {
+                let (ta, tb) = (self.locate(a)?, self.locate(b)?);
+                (!self.is_blocked(ta) && !self.is_blocked(tb)).then_some((ta, tb, a, b, link))
+            })
+            .collect();
+        let mut outgoing: HashMap<usize, Vec<usize>> = HashMap::new();
+        let mut scale = self.cost_scale(None);
+        for (hop, &(triangle, _, a, b, link)) in hops.iter().enumerate() {
+            outgoing.entry(triangle).or_default().push(hop);
+            scale = link_scale(scale, distance(a, b), link.cost);
+        }
+
+        let triangles = self.triangles.len();
+        let heuristic = |index: usize| {
+            let point = if index < triangles {
+                self.centroids[index]
+            } else {
+                hops[index - triangles].3
+            };
+            distance(point, end) * scale
+        };
+        let corridor = search::astar(
+            triangles + hops.len(),
+            from,
+            to,
+            |index, out| {
+                if index >= triangles {
+                    let (_, target, _, b, _) = hops[index - triangles];
+                    out.push((target, distance(b, self.centroids[target])));
+                    return;
+                }
+                self.corridor_edges(index, 0.0, None, out);
+                for &hop in outgoing.get(&index).into_iter().flatten() {
+                    let (_, _, a, _, link) = hops[hop];
+                    let cost = distance(self.centroids[index], a) + link.cost;
+                    out.push((triangles + hop, cost));
+                }
+            },
+            heuristic,
+        );
+        let Some(corridor) = corridor else {
+            return Vec::new();
+        };
+
+        // String-pull each stretch of walkable corridor between links separately.
+        let mut waypoints = Vec::new();
+        let (mut section_start, mut marker) = (start, WALK);
+        let mut section = Vec::new();
+        for index in corridor {
+            if index < triangles {
+                section.push(index);
+                continue;
+            }
+            let (_, _, a, b, link) = hops[index - triangles];
+            push_section(&mut waypoints, self, &section, section_start, a, marker);
+            section.clear();
+            (section_start, marker) = (b, link.tag as f32);
+        }
+        push_section(&mut waypoints, self, &section, section_start, end, marker);
+        waypoints
+    }
+}
+
+/// Appends the string-pulled path across `corridor`, marking its first point.
+fn push_section(
+    waypoints: &mut Vec<f32>,
+    mesh: &NavMesh,
+    corridor: &[usize],
+    start: Point,
+    end: Point,
+    marker: f32,
+) {
+    let portals = mesh.portals(corridor, start, end, 0.0);
+    for (i, point) in string_pull(&portals).into_iter().enumerate() {
+        let marker = if i == 0 { marker } else { WALK };
+        waypoints.extend_from_slice(&[point[0], point[1], point[2], marker]);
+    }
+}
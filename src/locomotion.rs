@@ -0,0 +1,114 @@
+use wasm_bindgen::prelude::*;
+
+use crate::math::Vec2;
+
+/// Floats per agent in a locomotion buffer.
+pub(crate) const STRIDE: usize = 12;
+
+/// Speeds below which an agent counts as standing still, as a fraction of its
+/// top speed.
+const STILL: f32 = 0.01;
+
+/// Whether an agent is speeding up, holding its speed or slowing down, e.g.
+/// to pick start, loop and stop animations.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MotionPhase {
+    Still = 0,
+    Accelerating = 1,
+    Steady = 2,
+    Braking = 3,
+}
+
+/// Floats per agent in the `locomotion` buffers of `Crowd` and `AiWorld`,
+/// laid out as:
+///
+/// - 0: speed
+/// - 1: speed as a fraction of the agent's top speed
+/// - 2: turn rate in radians per second, positive counterclockwise
+/// - 3: turn rate as a fraction of the fastest turn, from -1 to 1
+/// - 4, 5: heading `[x, y]`, kept while the agent stands still
+/// - 6 to 9: the heading as an `[x, y, z, w]` quaternion turning about the up
+///   axis, with positions `(x, y)` drawn at `(x, height, y)` and models
+///   facing `+z`, ready for three.js `Quaternion.fromArray`
+/// - 10: acceleration along the heading, negative when slowing down
+/// - 11: the `MotionPhase`
+#[wasm_bindgen]
+pub fn locomotion_stride() -> u32 {
+    STRIDE as u32
+}
+
+/// One agent's motion over a step, from velocity `before` and heading
+/// `heading` to velocity `after`.
+pub(crate) struct Motion {
+    pub before: Vec2,
+    pub after: Vec2,
+    pub heading: Vec2,
+    pub max_speed: f32,
+    pub dt: f32,
+}
+
+impl Motion {
+    /// Motion that stays as it is, e.g. for an agent just added.
+    pub(crate) fn steady(velocity: Vec2, heading: Vec2, max_speed: f32) -> Motion {
+        Motion {
+            before: velocity,
+            after: velocity,
+            heading,
+            max_speed,
+            dt: 0.0,
+        }
+    }
+
+    /// Writes the motion into `row` and returns the new heading. `max_turn_rate`
+    /// maps the turn rate to a fraction, and accelerations under `threshold`
+    /// count as steady.
+    pub(crate) fn write(&self, row: &mut [f32], max_turn_rate: f32, threshold: f32) -> Vec2 {
+        let speed = self.after.length();
+        let top = self.max_speed.max(f32::EPSILON);
+        let heading = if speed > STILL * top {
+            self.after / speed
+        } else {
+            self.heading
+        };
+        let (turn, acceleration) = if self.dt > 0.0 {
+            let turn = self.heading.cross(heading).atan2(self.heading.dot(heading));
+            (
+                turn / self.dt,
+                (self.after - self.before).dot(heading) / self.dt,
+            )
+        } else {
+            (0.0, 0.0)
+        };
+        let turn_fraction = if max_turn_rate > 0.0 {
+            (turn / max_turn_rate).clamp(-1.0, 1.0)
+        } else {
+            0.0
+        };
+        let phase = if speed <= STILL * top {
+            MotionPhase::Still
+        } else if acceleration > threshold {
+            MotionPhase::Accelerating
+        } else if acceleration < -threshold {
+            MotionPhase::Braking
+        } else {
+            MotionPhase::Steady
+        };
+        let half_yaw = heading.x.atan2(heading.y) * 0.5;
+        row[..STRIDE].copy_from_slice(&[
+            speed,
+            (speed / top).min(1.0),
+            turn,
+            turn_fraction,
+            heading.x,
+            heading.y,
+            0.0,
+            half_yaw.sin(),
+            0.0,
+            half_yaw.cos(),
+            acceleration,
+            phase as u32 as f32,
+        ]);
+        heading
+    }
+}
@@ -0,0 +1,278 @@
+use std::collections::{BTreeMap, HashMap};
+
+use wasm_bindgen::prelude::*;
+
+use crate::error::Error;
+use crate::json::Json;
+use crate::random::Rng;
+
+/// Context slot before the first token of a sequence.
+const START: u32 = u32::MAX;
+/// Follower meaning the sequence ends here. It never appears in a context, so it
+/// shares the start's value.
+const END: u32 = u32::MAX;
+
+/// An n-order Markov chain over string tokens: each token is drawn from those
+/// that followed the previous `order` tokens in training. Train it on words for
+/// barks, on characters for names, or on waypoint ids for patrol routes.
+///
+/// Sampling uses its own seeded generator, so a chain with the same training and
+/// seed always produces the same sequences.
+#[wasm_bindgen]
+pub struct MarkovChain {
+    order: usize,
+    tokens: Vec<String>,
+    lookup: HashMap<String, u32>,
+    /// Followers of each context, with counts, in the order they were first seen.
+    transitions: BTreeMap<Vec<u32>, Vec<(u32, u32)>>,
+    /// Above 1 flattens the training frequencies toward uniform, below 1 sharpens
+    /// them, and 0 always picks the most frequent follower.
+    pub temperature: f32,
+    rng: Rng,
+}
+
+#[wasm_bindgen]
+impl MarkovChain {
+    #[wasm_bindgen(constructor)]
+    pub fn new(order: u32) -> Result<MarkovChain, Error> {
+        if order == 0 {
+            return Err(Error::InvalidInput("order must be positive".into()));
+        }
+        Ok(MarkovChain {
+            order: order as usize,
+            tokens: Vec::new(),
+            lookup: HashMap::new(),
+            transitions: BTreeMap::new(),
+            temperature: 1.0,
+            rng: Rng::default(),
+        })
+    }
+
+    pub fn set_seed(&mut self, seed: u64) {
+        self.rng = Rng::new(seed);
+    }
+
+    /// Samples from a copy of `rng` from now on.
+    pub fn set_rng(&mut self, rng: &Rng) {
+        self.rng = rng.clone();
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn order(&self) -> u32 {
+        self.order as u32
+    }
+
+    /// Distinct tokens seen in training.
+    #[wasm_bindgen(getter)]
+    pub fn vocabulary_size(&self) -> u32 {
+        self.tokens.len() as u32
+    }
+
+    /// Learns one whole sequence, including where it starts and ends.
+    pub fn train(&mut self, sequence: Vec<String>) {
+        let mut context = vec![START; self.order];
+        for token in sequence {
+            let id = self.intern(token);
+            self.count(&context, id, 1);
+            context.remove(0);
+            context.push(id);
+        }
+        self.count(&context, END, 1);
+    }
+
+    /// Learns each of `texts` as a sequence of characters, e.g. a list of names.
+    pub fn train_characters(&mut self, texts: Vec<String>) {
+        for text in texts {
+            self.train(text.chars().map(String::from).collect());
+        }
+    }
+
+    /// A new sequence from the start, of at most `max_tokens` tokens.
+    pub fn generate(&mut self, max_tokens: u32) -> Vec<String> {
+        self.generate_from(Vec::new(), max_tokens)
+    }
+
+    /// Continues `prefix` by at most `max_tokens` tokens, returning only the new
+    /// ones. Shorter prefixes than the order are taken as the sequence's start.
+    pub fn generate_from(&mut self, prefix: Vec<String>, max_tokens: u32) -> Vec<String> {
+        let Some(mut context) = self.context(&prefix) else {
+            return Vec::new();
+        };
+        let mut generated = Vec::new();
+        while generated.len() < max_tokens as usize {
+            match self.sample(&context) {
+                Some(id) => {
+                    generated.push(self.tokens[id as usize].clone());
+                    context.remove(0);
+                    context.push(id);
+                }
+                None => break,
+            }
+        }
+        generated
+    }
+
+    /// A new sequence of characters joined into one string, e.g. a name.
+    pub fn generate_text(&mut self, max_characters: u32) -> String {
+        self.generate(max_characters).concat()
+    }
+
+    /// The token to follow the `context` so far, or `None` if the sequence ends
+    /// there or the context never appeared in training. Stepping one token at a
+    /// time this way suits state sequences such as patrols.
+    pub fn next_token(&mut self, context: Vec<String>) -> Option<String> {
+        let context = self.context(&context)?;
+        self.sample(&context)
+            .map(|id| self.tokens[id as usize].clone())
+    }
+
+    /// Forgets all training.
+    pub fn clear(&mut self) {
+        self.tokens.clear();
+        self.lookup.clear();
+        self.transitions.clear();
+    }
+
+    /// The trained chain as JSON, for `from_json`. Contexts list token indices,
+    /// with -1 for the start of a sequence, and a -1 follower ends it.
+    pub fn to_json(&self) -> String {
+        let index = |id: u32| Json::Number(if id == START { -1.0 } else { id as f64 });
+        let transitions = self
+            .transitions
+            .iter()
+            .map(|(context, followers)| {
+                Json::Object(vec![
+                    (
+                        "context".into(),
+                        Json::Array(context.iter().map(|&id| index(id)).collect()),
+                    ),
+                    (
+                        "next".into(),
+                        Json::Array(followers.iter().map(|&(id, _)| index(id)).collect()),
+                    ),
+                    (
+                        "counts".into(),
+                        Json::Array(
+                            followers
+                                .iter()
+                                .map(|&(_, count)| Json::Number(count as f64))
+                                .collect(),
+                        ),
+                    ),
+                ])
+            })
+            .collect();
+        Json::Object(vec![
+            ("order".into(), Json::Number(self.order as f64)),
+            (
+                "tokens".into(),
+                Json::Array(self.tokens.iter().cloned().map(Json::String).collect()),
+            ),
+            ("transitions".into(), Json::Array(transitions)),
+        ])
+        .to_string()
+    }
+
+    pub fn from_json(json: &str) -> Result<MarkovChain, Error> {
+        let json = Json::parse(json)?;
+        let mut chain = MarkovChain::new(json.field("order", Json::as_f64)? as u32)?;
+        let tokens = json.field("tokens", Json::as_array)?;
+        for token in tokens {
+            let token = token
+                .as_str()
+                .ok_or_else(|| Error::InvalidInput("tokens must be strings".into()))?;
+            chain.intern(token.into());
+        }
+        if chain.tokens.len() != tokens.len() {
+            return Err(Error::InvalidInput("tokens must be distinct".into()));
+        }
+        let vocabulary = chain.tokens.len() as f32;
+        let ids = |values: Vec<f32>| -> Result<Vec<u32>, Error> {
+            values
+                .into_iter()
+                .map(|value| match value {
+                    -1.0 => Ok(START),
+                    value if value >= 0.0 && value < vocabulary => Ok(value as u32),
+                    _ => Err(Error::InvalidInput(format!(
+                        "token index {} is out of range",
+                        value
+                    ))),
+                })
+                .collect()
+        };
+        for transition in json.field("transitions", Json::as_array)? {
+            let context = ids(transition.field("context", Json::as_f32s)?)?;
+            let next = ids(transition.field("next", Json::as_f32s)?)?;
+            let counts = transition.field("counts", Json::as_f32s)?;
+            if context.len() != chain.order || next.len() != counts.len() {
+                return Err(Error::InvalidInput("malformed transition".into()));
+            }
+            for (id, count) in next.into_iter().zip(counts) {
+                if count >= 1.0 {
+                    chain.count(&context, id, count as u32);
+                }
+            }
+        }
+        Ok(chain)
+    }
+}
+
+impl MarkovChain {
+    fn intern(&mut self, token: String) -> u32 {
+        if let Some(&id) = self.lookup.get(&token) {
+            return id;
+        }
+        let id = self.tokens.len() as u32;
+        self.lookup.insert(token.clone(), id);
+        self.tokens.push(token);
+        id
+    }
+
+    fn count(&mut self, context: &[u32], next: u32, times: u32) {
+        let followers = self.transitions.entry(context.to_vec()).or_default();
+        match followers.iter_mut().find(|(id, _)| *id == next) {
+            Some((_, count)) => *count += times,
+            None => followers.push((next, times)),
+        }
+    }
+
+    /// The last `order` tokens of `tokens` as ids, padded with the start; `None`
+    /// if any was never trained on.
+    fn context(&self, tokens: &[String]) -> Option<Vec<u32>> {
+        let skip = tokens.len().saturating_sub(self.order);
+        let mut context = vec![START; self.order - (tokens.len() - skip)];
+        for token in &tokens[skip..] {
+            context.push(*self.lookup.get(token)?);
+        }
+        Some(context)
+    }
+
+    /// A follower of `context` drawn by tempered frequency; `None` at the end.
+    fn sample(&mut self, context: &[u32]) -> Option<u32> {
+        let followers = self.transitions.get(context)?;
+        let next = if self.temperature > 0.0 {
+            let exponent = self.temperature.recip();
+            let weight = |count: u32| (count as f32).powf(exponent);
+            let total: f32 = followers.iter().map(|&(_, count)| weight(count)).sum();
+            let mut pick = self.rng.next_f32() * total;
+            let mut chosen = followers[followers.len() - 1].0;
+            for &(id, count) in followers {
+                pick -= weight(count);
+                if pick < 0.0 {
+                    chosen = id;
+                    break;
+                }
+            }
+            chosen
+        } else {
+            let mut best = followers[0];
+            for &follower in followers {
+                if follower.1 > best.1 {
+                    best = follower;
+                }
+            }
+            best.0
+        };
+        (next != END).then_some(next)
+    }
+}
@@ -0,0 +1,333 @@
+use std::cell::RefCell;
+
+use js_sys::{Array, Function, Object, Reflect};
+use wasm_bindgen::prelude::*;
+
+use crate::clock;
+use crate::error::Error;
+use crate::random::Rng;
+
+/// A turn-based game for `Mcts`, with moves identified by `u32`. States are never
+/// mutated: `play` returns the state after a move.
+pub trait Game {
+    type State: Clone;
+
+    /// The player to move.
+    fn player(&self, state: &Self::State) -> u32;
+
+    /// Pushes the legal moves into `out`; none means the game is over.
+    fn moves(&self, state: &Self::State, out: &mut Vec<u32>);
+
+    fn play(&self, state: &Self::State, action: u32) -> Self::State;
+
+    /// How good the state is for `player`, from 0 for a loss to 1 for a win. Called
+    /// on finished games, and on unfinished ones when a rollout hits its depth limit.
+    fn score(&self, state: &Self::State, player: u32) -> f32;
+}
+
+struct Node<S> {
+    state: S,
+    /// The move that led here from the parent, and the player who made it.
+    action: u32,
+    mover: u32,
+    children: Vec<usize>,
+    untried: Vec<u32>,
+    visits: u32,
+    /// Sum of the mover's scores over every visit.
+    value: f32,
+}
+
+/// Monte Carlo tree search: plays random games from the current state, grows a
+/// tree toward the moves that win most often, and picks the most visited move.
+/// Works with any number of players as long as `score` rates a state for each.
+///
+/// From JS, `search` takes a game object with four pure functions over states of
+/// any shape, such as an array or a typed array:
+/// `player(state)`, `moves(state)` returning an array of move ids,
+/// `play(state, move)` returning the next state, and `score(state, player)`.
+#[wasm_bindgen]
+pub struct Mcts {
+    /// Weight of the UCT exploration term; higher tries more unlikely moves.
+    pub exploration: f32,
+    /// Iterations per search.
+    pub iterations: u32,
+    /// Wall-clock budget per search in milliseconds, 0 for none. The search stops
+    /// at whichever of the two limits it reaches first.
+    pub time_limit_ms: f64,
+    /// Moves a rollout plays before scoring an unfinished game.
+    pub max_rollout_depth: u32,
+    rng: Rng,
+    /// `(move, visits, mean score)` of each root child from the last search.
+    stats: Vec<(u32, u32, f32)>,
+    last_iterations: u32,
+}
+
+#[wasm_bindgen]
+impl Mcts {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Mcts {
+        Mcts {
+            exploration: std::f32::consts::SQRT_2,
+            iterations: 1000,
+            time_limit_ms: 0.0,
+            max_rollout_depth: 200,
+            rng: Rng::default(),
+            stats: Vec::new(),
+            last_iterations: 0,
+        }
+    }
+
+    pub fn set_seed(&mut self, seed: u64) {
+        self.rng = Rng::new(seed);
+    }
+
+    /// Draws rollout moves from a copy of `rng`.
+    pub fn set_rng(&mut self, rng: &Rng) {
+        self.rng = rng.clone();
+    }
+
+    /// Searches from `state` and returns the best move for the player to move, or
+    /// undefined when the game is over. Fails when a game function is missing or
+    /// throws.
+    pub fn search(&mut self, game: &Object, state: JsValue) -> Result<Option<u32>, Error> {
+        let game = JsGame::new(game)?;
+        let best = self.search_with(&game, &state);
+        match game.error.into_inner() {
+            Some(error) => Err(error),
+            None => Ok(best),
+        }
+    }
+
+    /// Iterations the last search completed.
+    #[wasm_bindgen(getter)]
+    pub fn last_iterations(&self) -> u32 {
+        self.last_iterations
+    }
+
+    /// Root moves from the last search as flat `[move0, visits0, score0, ...]`,
+    /// most visited first, where the score is the mover's mean.
+    pub fn move_stats(&self) -> Vec<f32> {
+        self.stats
+            .iter()
+            .flat_map(|&(action, visits, score)| [action as f32, visits as f32, score])
+            .collect()
+    }
+}
+
+impl Default for Mcts {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Mcts {
+    /// Searches a Rust-side game from `state`; see `search`.
+    pub fn search_with<G: Game>(&mut self, game: &G, state: &G::State) -> Option<u32> {
+        let deadline = (self.time_limit_ms > 0.0).then(|| clock::now_ms() + self.time_limit_ms);
+        let mut untried = Vec::new();
+        game.moves(state, &mut untried);
+        self.stats.clear();
+        self.last_iterations = 0;
+        if untried.is_empty() {
+            return None;
+        }
+        let mut nodes = vec![Node {
+            state: state.clone(),
+            action: 0,
+            mover: 0,
+            children: Vec::new(),
+            untried,
+            visits: 0,
+            value: 0.0,
+        }];
+        let mut path = Vec::new();
+        let mut moves = Vec::new();
+        let mut scores: Vec<(u32, f32)> = Vec::new();
+        while self.last_iterations < self.iterations
+            && deadline.is_none_or(|deadline| clock::now_ms() < deadline)
+        {
+            // Selection: descend through fully expanded nodes by UCT.
+            path.clear();
+            let mut current = 0;
+            path.push(current);
+            while nodes[current].untried.is_empty() && !nodes[current].children.is_empty() {
+                let log_visits = (nodes[current].visits as f32).ln();
+                current = *nodes[current]
+                    .children
+                    .iter()
+                    .max_by(|&&a, &&b| {
+                        let (a, b) = (&nodes[a], &nodes[b]);
+                        self.uct(a, log_visits).total_cmp(&self.uct(b, log_visits))
+                    })
+                    .expect("expanded nodes have children");
+                path.push(current);
+            }
+
+            // Expansion: add one untried move.
+            if !nodes[current].untried.is_empty() {
+                let pick = self.rng.below(nodes[current].untried.len());
+                let action = nodes[current].untried.swap_remove(pick);
+                let parent = &nodes[current].state;
+                let mover = game.player(parent);
+                let state = game.play(parent, action);
+                let mut untried = Vec::new();
+                game.moves(&state, &mut untried);
+                nodes.push(Node {
+                    state,
+                    action,
+                    mover,
+                    children: Vec::new(),
+                    untried,
+                    visits: 0,
+                    value: 0.0,
+                });
+                let child = nodes.len() - 1;
+                nodes[current].children.push(child);
+                current = child;
+                path.push(current);
+            }
+
+            // Rollout: random moves to the end of the game or the depth limit.
+            let mut state = nodes[current].state.clone();
+            for _ in 0..self.max_rollout_depth {
+                moves.clear();
+                game.moves(&state, &mut moves);
+                if moves.is_empty() {
+                    break;
+                }
+                state = game.play(&state, moves[self.rng.below(moves.len())]);
+            }
+
+            // Backpropagation, scoring the final state once per mover.
+            scores.clear();
+            for &index in &path {
+                let node = &mut nodes[index];
+                let score = match scores.iter().find(|(player, _)| *player == node.mover) {
+                    Some(&(_, score)) => score,
+                    None => {
+                        let score = game.score(&state, node.mover);
+                        scores.push((node.mover, score));
+                        score
+                    }
+                };
+                node.visits += 1;
+                node.value += score;
+            }
+            self.last_iterations += 1;
+        }
+
+        self.stats = nodes[0]
+            .children
+            .iter()
+            .map(|&child| {
+                let node = &nodes[child];
+                (
+                    node.action,
+                    node.visits,
+                    node.value / node.visits.max(1) as f32,
+                )
+            })
+            .collect();
+        self.stats
+            .sort_by(|a, b| b.1.cmp(&a.1).then(b.2.total_cmp(&a.2)));
+        self.stats
+            .first()
+            .map(|&(action, _, _)| action)
+            .or_else(|| nodes[0].untried.first().copied())
+    }
+
+    fn uct<S>(&self, node: &Node<S>, log_parent_visits: f32) -> f32 {
+        let visits = node.visits as f32;
+        node.value / visits + self.exploration * (log_parent_visits / visits).sqrt()
+    }
+}
+
+/// A `Game` backed by the functions of a JS object. The first error a function
+/// throws is kept, and the search carries on with neutral values until it ends.
+pub(crate) struct JsGame {
+    player: Function,
+    moves: Function,
+    play: Function,
+    score: Function,
+    pub(crate) error: RefCell<Option<Error>>,
+}
+
+impl JsGame {
+    pub(crate) fn new(game: &Object) -> Result<JsGame, Error> {
+        let function = |name: &str| {
+            Reflect::get(game, &JsValue::from_str(name))
+                .ok()
+                .and_then(|value| value.dyn_into::<Function>().ok())
+                .ok_or_else(|| {
+                    Error::InvalidInput(format!("game is missing a '{}' function", name))
+                })
+        };
+        Ok(JsGame {
+            player: function("player")?,
+            moves: function("moves")?,
+            play: function("play")?,
+            score: function("score")?,
+            error: RefCell::new(None),
+        })
+    }
+
+    pub(crate) fn check(&self, name: &str, result: Result<JsValue, JsValue>) -> Option<JsValue> {
+        let mut error = self.error.borrow_mut();
+        if error.is_some() {
+            return None;
+        }
+        match result {
+            Ok(value) => Some(value),
+            Err(thrown) => {
+                *error = Some(Error::InvalidInput(format!(
+                    "game.{} threw {}",
+                    name,
+                    thrown
+                        .as_string()
+                        .unwrap_or_else(|| format!("{:?}", thrown))
+                )));
+                None
+            }
+        }
+    }
+}
+
+impl Game for JsGame {
+    type State = JsValue;
+
+    fn player(&self, state: &JsValue) -> u32 {
+        self.check("player", self.player.call1(&JsValue::NULL, state))
+            .and_then(|value| value.as_f64())
+            .unwrap_or(0.0) as u32
+    }
+
+    fn moves(&self, state: &JsValue, out: &mut Vec<u32>) {
+        if let Some(moves) = self.check("moves", self.moves.call1(&JsValue::NULL, state)) {
+            out.extend(
+                Array::from(&moves)
+                    .iter()
+                    .filter_map(|action| action.as_f64())
+                    .map(|action| action as u32),
+            );
+        }
+    }
+
+    fn play(&self, state: &JsValue, action: u32) -> JsValue {
+        self.check(
+            "play",
+            self.play
+                .call2(&JsValue::NULL, state, &JsValue::from(action)),
+        )
+        .unwrap_or_else(|| state.clone())
+    }
+
+    fn score(&self, state: &JsValue, player: u32) -> f32 {
+        self.check(
+            "score",
+            self.score
+                .call2(&JsValue::NULL, state, &JsValue::from(player)),
+        )
+        .and_then(|value| value.as_f64())
+        .unwrap_or(0.5) as f32
+    }
+}
@@ -0,0 +1,197 @@
+use std::collections::HashMap;
+
+use wasm_bindgen::prelude::*;
+
+use crate::math::Vec2;
+use crate::perception::Perception;
+
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Sense {
+    Sight = 0,
+    Hearing = 1,
+}
+
+/// What a `SensoryMemory` recalls about one source.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MemoryEntry {
+    pub source: u32,
+    /// Last known position.
+    pub position: Vec2,
+    /// The sense that last updated the entry.
+    pub sense: Sense,
+    /// Memory time when the source was first and last sensed.
+    pub first_sensed: f32,
+    pub last_sensed: f32,
+    /// How sure the agent still is, from 1 down to 0 when it forgets.
+    pub confidence: f32,
+}
+
+/// One agent's memory of what it has sensed. Each source is remembered at its
+/// last known position with a confidence that fades linearly to zero over
+/// `span` seconds after it was last sensed, at which point it is forgotten. An
+/// agent that loses sight of its target can investigate where it was last seen
+/// instead of forgetting it at once.
+#[wasm_bindgen]
+#[derive(Clone)]
+pub struct SensoryMemory {
+    entries: HashMap<u32, MemoryEntry>,
+    time: f32,
+    /// Seconds a fully confident memory lasts after the source was last sensed.
+    pub span: f32,
+    /// Confidence of a memory refreshed by hearing; sight always gives 1.
+    pub hearing_confidence: f32,
+}
+
+#[wasm_bindgen]
+impl SensoryMemory {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> SensoryMemory {
+        SensoryMemory {
+            entries: HashMap::new(),
+            time: 0.0,
+            span: 10.0,
+            hearing_confidence: 0.5,
+        }
+    }
+
+    /// Seconds of memory time, advanced by `update`.
+    #[wasm_bindgen(getter)]
+    pub fn time(&self) -> f32 {
+        self.time
+    }
+
+    /// Advances memory time by `dt` seconds and forgets faded memories.
+    pub fn update(&mut self, dt: f32) {
+        self.time += dt.max(0.0);
+        let (time, span) = (self.time, self.span);
+        self.entries
+            .retain(|_, entry| fade(entry, time, span) > 0.0);
+    }
+
+    /// Records sensing `source` at `position` now. A weaker impression, such as
+    /// a faint sound of a target still in view, does not override a stronger
+    /// memory.
+    pub fn remember(&mut self, source: u32, position: Vec2, sense: Sense, confidence: f32) {
+        let confidence = confidence.clamp(0.0, 1.0);
+        if confidence <= 0.0 {
+            return;
+        }
+        let time = self.time;
+        let span = self.span;
+        let entry = self.entries.entry(source).or_insert(MemoryEntry {
+            source,
+            position,
+            sense,
+            first_sensed: time,
+            last_sensed: time,
+            confidence: 0.0,
+        });
+        if confidence >= fade(entry, time, span) {
+            entry.position = position;
+            entry.sense = sense;
+            entry.last_sensed = time;
+            entry.confidence = confidence;
+        }
+    }
+
+    /// Records what `observer` saw and heard in the perception's last `update`.
+    pub fn observe(&mut self, perception: &Perception, observer: u32) {
+        let Some(senses) = perception.observers.get(&observer) else {
+            return;
+        };
+        for &target in &senses.visible {
+            if let Some(position) = perception.target_position(target) {
+                self.remember(target, position, Sense::Sight, 1.0);
+            }
+        }
+        for sound in &senses.heard {
+            self.remember(
+                sound.source,
+                sound.position,
+                Sense::Hearing,
+                self.hearing_confidence,
+            );
+        }
+    }
+
+    /// Where `source` was last sensed, or undefined when it is not remembered.
+    pub fn last_known_position(&self, source: u32) -> Option<Vec2> {
+        self.entries.get(&source).map(|entry| entry.position)
+    }
+
+    /// Current confidence in the memory of `source`, 0 when it is not remembered.
+    pub fn confidence(&self, source: u32) -> f32 {
+        self.entries
+            .get(&source)
+            .map_or(0.0, |entry| fade(entry, self.time, self.span))
+    }
+
+    /// Seconds since `source` was last sensed, or undefined when it is not
+    /// remembered.
+    pub fn time_since_sensed(&self, source: u32) -> Option<f32> {
+        self.entries
+            .get(&source)
+            .map(|entry| self.time - entry.last_sensed)
+    }
+
+    /// The remembered source with the highest confidence, e.g. the target to chase
+    /// or investigate.
+    pub fn strongest(&self) -> Option<MemoryEntry> {
+        self.entries().into_iter().next()
+    }
+
+    /// Every memory with its current confidence, most confident first.
+    pub fn entries(&self) -> Vec<MemoryEntry> {
+        let mut entries: Vec<MemoryEntry> = self
+            .entries
+            .values()
+            .map(|entry| MemoryEntry {
+                confidence: fade(entry, self.time, self.span),
+                ..*entry
+            })
+            .collect();
+        entries.sort_by(|a, b| {
+            b.confidence
+                .total_cmp(&a.confidence)
+                .then(a.source.cmp(&b.source))
+        });
+        entries
+    }
+
+    pub fn forget(&mut self, source: u32) -> bool {
+        self.entries.remove(&source).is_some()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn len(&self) -> u32 {
+        self.entries.len() as u32
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+impl Default for SensoryMemory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Confidence of `entry` at `time`, fading from its value when last sensed.
+fn fade(entry: &MemoryEntry, time: f32, span: f32) -> f32 {
+    if span <= 0.0 {
+        return if time > entry.last_sensed {
+            0.0
+        } else {
+            entry.confidence
+        };
+    }
+    (entry.confidence * (1.0 - (time - entry.last_sensed) / span)).max(0.0)
+}
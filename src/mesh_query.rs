@@ -0,0 +1,188 @@
+use wasm_bindgen::prelude::*;
+
+use crate::math::Vec3;
+use crate::navmesh::{distance, NavMesh, Point};
+use crate::random::Rng;
+
+/// Random points tried per call before `random_point_in_radius` settles for
+/// the center.
+const SAMPLE_ATTEMPTS: u32 = 32;
+
+#[wasm_bindgen]
+impl NavMesh {
+    /// The point on the walkable mesh nearest the given one, skipping
+    /// triangles that carved obstacles block, e.g. to snap a spawn or
+    /// teleport position onto the mesh. Returns undefined when no triangle is
+    /// walkable.
+    pub fn closest_point(&self, x: f32, y: f32, z: f32) -> Option<Vec3> {
+        let (_, point) = self.closest([x, y, z])?;
+        Some(Vec3::new(point[0], point[1], point[2]))
+    }
+
+    /// Whether the point lies within `tolerance` of the walkable mesh.
+    pub fn is_on_mesh(&self, x: f32, y: f32, z: f32, tolerance: f32) -> bool {
+        self.closest([x, y, z])
+            .is_some_and(|(_, point)| distance(point, [x, y, z]) <= tolerance.max(0.0))
+    }
+
+    /// A random walkable point within `radius` of the center on the XZ plane,
+    /// spread evenly by area and reachable from where the center snaps onto
+    /// the mesh, e.g. to scatter spawns around a marker. Falls back to that
+    /// snapped center when no sample lands in range, and returns undefined
+    /// when no triangle is walkable.
+    pub fn random_point_in_radius(
+        &self,
+        x: f32,
+        y: f32,
+        z: f32,
+        radius: f32,
+        rng: &mut Rng,
+    ) -> Option<Vec3> {
+        let (start, center) = self.closest([x, y, z])?;
+        let radius = radius.max(0.0);
+        let reached = self.triangles_within(start, center, radius);
+        let areas: Vec<f32> = reached
+            .iter()
+            .map(|&index| {
+                let [a, b, c] = self.triangle(index);
+                area_xz(a, b, c)
+            })
+            .collect();
+        let total: f32 = areas.iter().sum();
+        let point = (0..SAMPLE_ATTEMPTS)
+            .filter(|_| total > 0.0)
+            .find_map(|_| {
+                let mut pick = rng.next_f32() * total;
+                let index = areas
+                    .iter()
+                    .position(|&area| {
+                        pick -= area;
+                        pick < 0.0
+                    })
+                    .unwrap_or(areas.len() - 1);
+                let [a, b, c] = self.triangle(reached[index]);
+                let point = sample_triangle(a, b, c, rng.next_f32(), rng.next_f32());
+                (distance_xz(point, center) <= radius).then_some(point)
+            })
+            .unwrap_or(center);
+        Some(Vec3::new(point[0], point[1], point[2]))
+    }
+}
+
+impl NavMesh {
+    /// The walkable triangle nearest `point` and the nearest point on it.
+    fn closest(&self, point: Point) -> Option<(usize, Point)> {
+        if let Some(index) = self.locate(point).filter(|&t| !self.is_blocked(t)) {
+            let [a, b, c] = self.triangle(index);
+            return Some((index, closest_on_triangle(point, a, b, c)));
+        }
+        (0..self.triangles.len())
+            .filter(|&index| !self.is_blocked(index))
+            .map(|index| {
+                let [a, b, c] = self.triangle(index);
+                (index, closest_on_triangle(point, a, b, c))
+            })
+            .min_by(|a, b| distance(a.1, point).total_cmp(&distance(b.1, point)))
+    }
+
+    /// Walkable triangles reachable from `start` without leaving the circle of
+    /// `radius` around `center` on XZ.
+    fn triangles_within(&self, start: usize, center: Point, radius: f32) -> Vec<usize> {
+        let flat = |[x, _, z]: Point| [x, 0.0, z];
+        let mut seen = vec![false; self.triangles.len()];
+        seen[start] = true;
+        let mut reached = vec![start];
+        let mut next = 0;
+        while next < reached.len() {
+            let index = reached[next];
+            next += 1;
+            for link in &self.links[index] {
+                let neighbor = link.triangle;
+                if seen[neighbor] || self.is_blocked(neighbor) {
+                    continue;
+                }
+                seen[neighbor] = true;
+                let [a, b, c] = self.triangle(neighbor);
+                let nearest = closest_on_triangle(flat(center), flat(a), flat(b), flat(c));
+                if distance_xz(nearest, center) <= radius {
+                    reached.push(neighbor);
+                }
+            }
+        }
+        reached
+    }
+}
+
+/// The point of triangle `abc` nearest `p`, from Ericson's Real-Time
+/// Collision Detection.
+fn closest_on_triangle(p: Point, a: Point, b: Point, c: Point) -> Point {
+    let sub = |u: Point, v: Point| [u[0] - v[0], u[1] - v[1], u[2] - v[2]];
+    let dot = |u: Point, v: Point| u[0] * v[0] + u[1] * v[1] + u[2] * v[2];
+    let along = |from: Point, to: Point, t: f32| {
+        [
+            from[0] + (to[0] - from[0]) * t,
+            from[1] + (to[1] - from[1]) * t,
+            from[2] + (to[2] - from[2]) * t,
+        ]
+    };
+    let (ab, ac, ap) = (sub(b, a), sub(c, a), sub(p, a));
+    let (d1, d2) = (dot(ab, ap), dot(ac, ap));
+    if d1 <= 0.0 && d2 <= 0.0 {
+        return a;
+    }
+    let bp = sub(p, b);
+    let (d3, d4) = (dot(ab, bp), dot(ac, bp));
+    if d3 >= 0.0 && d4 <= d3 {
+        return b;
+    }
+    let vc = d1 * d4 - d3 * d2;
+    if vc <= 0.0 && d1 >= 0.0 && d3 <= 0.0 {
+        return along(a, b, d1 / (d1 - d3));
+    }
+    let cp = sub(p, c);
+    let (d5, d6) = (dot(ab, cp), dot(ac, cp));
+    if d6 >= 0.0 && d5 <= d6 {
+        return c;
+    }
+    let vb = d5 * d2 - d1 * d6;
+    if vb <= 0.0 && d2 >= 0.0 && d6 <= 0.0 {
+        return along(a, c, d2 / (d2 - d6));
+    }
+    let va = d3 * d6 - d5 * d4;
+    if va <= 0.0 && d4 - d3 >= 0.0 && d5 - d6 >= 0.0 {
+        return along(b, c, (d4 - d3) / ((d4 - d3) + (d5 - d6)));
+    }
+    let denominator = va + vb + vc;
+    if denominator.abs() < f32::EPSILON {
+        return a;
+    }
+    let (v, w) = (vb / denominator, vc / denominator);
+    [
+        a[0] + ab[0] * v + ac[0] * w,
+        a[1] + ab[1] * v + ac[1] * w,
+        a[2] + ab[2] * v + ac[2] * w,
+    ]
+}
+
+/// A point spread evenly over triangle `abc` from two uniform numbers.
+fn sample_triangle(a: Point, b: Point, c: Point, s: f32, t: f32) -> Point {
+    let (s, t) = if s + t > 1.0 {
+        (1.0 - s, 1.0 - t)
+    } else {
+        (s, t)
+    };
+    [
+        a[0] + (b[0] - a[0]) * s + (c[0] - a[0]) * t,
+        a[1] + (b[1] - a[1]) * s + (c[1] - a[1]) * t,
+        a[2] + (b[2] - a[2]) * s + (c[2] - a[2]) * t,
+    ]
+}
+
+fn area_xz(a: Point, b: Point, c: Point) -> f32 {
+    ((b[0] - a[0]) * (c[2] - a[2]) - (b[2] - a[2]) * (c[0] - a[0])).abs() * 0.5
+}
+
+fn distance_xz(a: Point, b: Point) -> f32 {
+    let (dx, dz) = (a[0] - b[0], a[2] - b[2]);
+    (dx * dx + dz * dz).sqrt()
+}
@@ -0,0 +1,241 @@
+use wasm_bindgen::prelude::*;
+
+use crate::error::Error;
+use crate::json::Json;
+
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Activation {
+    Linear = 0,
+    Relu = 1,
+    Tanh = 2,
+    Sigmoid = 3,
+}
+
+impl Activation {
+    const NAMES: [(&'static str, Activation); 4] = [
+        ("linear", Activation::Linear),
+        ("relu", Activation::Relu),
+        ("tanh", Activation::Tanh),
+        ("sigmoid", Activation::Sigmoid),
+    ];
+
+    fn apply(self, x: f32) -> f32 {
+        match self {
+            Activation::Linear => x,
+            Activation::Relu => x.max(0.0),
+            Activation::Tanh => x.tanh(),
+            Activation::Sigmoid => 1.0 / (1.0 + (-x).exp()),
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Activation> {
+        Activation::NAMES
+            .iter()
+            .find(|(known, _)| *known == name)
+            .map(|&(_, activation)| activation)
+    }
+
+    fn name(self) -> &'static str {
+        Activation::NAMES
+            .iter()
+            .find(|(_, activation)| *activation == self)
+            .map(|(name, _)| *name)
+            .unwrap_or("linear")
+    }
+}
+
+/// A fully connected feed-forward network for inference, such as a small policy
+/// trained offline. Hidden layers share one activation and the output layer has
+/// its own.
+///
+/// Weights are one flat array, layer by layer: each layer's weight matrix row by
+/// row, one row of input weights per output, followed by its biases.
+#[wasm_bindgen]
+#[derive(Clone, Debug, PartialEq)]
+pub struct Mlp {
+    layers: Vec<usize>,
+    weights: Vec<f32>,
+    hidden: Activation,
+    output: Activation,
+}
+
+#[wasm_bindgen]
+impl Mlp {
+    /// Builds a network from its layer sizes, input first, and flat weights,
+    /// e.g. a `Float32Array`.
+    #[wasm_bindgen(constructor)]
+    pub fn new(
+        layer_sizes: &[u32],
+        weights: &[f32],
+        hidden: Activation,
+        output: Activation,
+    ) -> Result<Mlp, Error> {
+        if layer_sizes.len() < 2 || layer_sizes.contains(&0) {
+            return Err(Error::InvalidInput(
+                "a network needs at least two non-empty layers".into(),
+            ));
+        }
+        let layers: Vec<usize> = layer_sizes.iter().map(|&size| size as usize).collect();
+        let expected = parameter_count(&layers);
+        if weights.len() != expected {
+            return Err(Error::InvalidInput(format!(
+                "layers {:?} need {} weights, got {}",
+                layer_sizes,
+                expected,
+                weights.len()
+            )));
+        }
+        Ok(Mlp {
+            layers,
+            weights: weights.to_vec(),
+            hidden,
+            output,
+        })
+    }
+
+    /// Parses `{"layers": [...], "weights": [...], "activation": "relu",
+    /// "output_activation": "tanh"}`. Activations are `linear`, `relu`, `tanh` or
+    /// `sigmoid`, defaulting to `relu` for hidden layers and `linear` for output.
+    pub fn from_json(json: &str) -> Result<Mlp, Error> {
+        let json = Json::parse(json)?;
+        let layers = json
+            .field("layers", Json::as_f32s)?
+            .into_iter()
+            .map(|size| size as u32)
+            .collect::<Vec<_>>();
+        let weights = json.field("weights", Json::as_f32s)?;
+        let activation = |key: &str, default: Activation| match json.get(key) {
+            None => Ok(default),
+            Some(value) => value
+                .as_str()
+                .and_then(Activation::from_name)
+                .ok_or_else(|| Error::InvalidInput(format!("unknown activation in '{}'", key))),
+        };
+        Mlp::new(
+            &layers,
+            &weights,
+            activation("activation", Activation::Relu)?,
+            activation("output_activation", Activation::Linear)?,
+        )
+    }
+
+    /// The network in the format `from_json` reads.
+    pub fn to_json(&self) -> String {
+        Json::Object(vec![
+            (
+                "layers".into(),
+                Json::Array(
+                    self.layers
+                        .iter()
+                        .map(|&size| Json::Number(size as f64))
+                        .collect(),
+                ),
+            ),
+            (
+                "weights".into(),
+                Json::Array(self.weights.iter().copied().map(Json::from_f32).collect()),
+            ),
+            ("activation".into(), Json::String(self.hidden.name().into())),
+            (
+                "output_activation".into(),
+                Json::String(self.output.name().into()),
+            ),
+        ])
+        .to_string()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn input_size(&self) -> u32 {
+        self.layers[0] as u32
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn output_size(&self) -> u32 {
+        self.layers[self.layers.len() - 1] as u32
+    }
+
+    pub fn layer_sizes(&self) -> Vec<u32> {
+        self.layers.iter().map(|&size| size as u32).collect()
+    }
+
+    pub fn weights(&self) -> Vec<f32> {
+        self.weights.clone()
+    }
+
+    /// Replaces every weight, keeping the layer sizes.
+    pub fn set_weights(&mut self, weights: &[f32]) -> Result<(), Error> {
+        if weights.len() != self.weights.len() {
+            return Err(Error::InvalidInput(format!(
+                "expected {} weights, got {}",
+                self.weights.len(),
+                weights.len()
+            )));
+        }
+        self.weights.copy_from_slice(weights);
+        Ok(())
+    }
+
+    /// Runs a batch of inputs, one row of `input_size` values per agent, and
+    /// returns one row of `output_size` values per agent.
+    pub fn forward(&self, inputs: &[f32]) -> Result<Vec<f32>, Error> {
+        let mut outputs = Vec::new();
+        self.forward_into(inputs, &mut outputs)?;
+        Ok(outputs)
+    }
+}
+
+impl Mlp {
+    /// Like `forward`, replacing the contents of `outputs`.
+    pub fn forward_into(&self, inputs: &[f32], outputs: &mut Vec<f32>) -> Result<(), Error> {
+        let (input_size, output_size) = (self.layers[0], self.layers[self.layers.len() - 1]);
+        if !inputs.len().is_multiple_of(input_size) {
+            return Err(Error::InvalidInput(format!(
+                "inputs must be rows of {} values, got {}",
+                input_size,
+                inputs.len()
+            )));
+        }
+        outputs.clear();
+        outputs.reserve(inputs.len() / input_size * output_size);
+        let widest = self.layers.iter().copied().max().unwrap_or(0);
+        let (mut current, mut next) = (vec![0.0; widest], vec![0.0; widest]);
+        for row in inputs.chunks_exact(input_size) {
+            current[..input_size].copy_from_slice(row);
+            let mut offset = 0;
+            for (layer, pair) in self.layers.windows(2).enumerate() {
+                let (fan_in, fan_out) = (pair[0], pair[1]);
+                let activation = if layer + 2 == self.layers.len() {
+                    self.output
+                } else {
+                    self.hidden
+                };
+                let matrix = &self.weights[offset..offset + fan_in * fan_out];
+                let biases = &self.weights[offset + fan_in * fan_out..][..fan_out];
+                for (out, (weights, bias)) in next[..fan_out]
+                    .iter_mut()
+                    .zip(matrix.chunks_exact(fan_in).zip(biases))
+                {
+                    let sum: f32 = weights
+                        .iter()
+                        .zip(&current[..fan_in])
+                        .map(|(w, x)| w * x)
+                        .sum();
+                    *out = activation.apply(sum + bias);
+                }
+                offset += fan_in * fan_out + fan_out;
+                std::mem::swap(&mut current, &mut next);
+            }
+            outputs.extend_from_slice(&current[..output_size]);
+        }
+        Ok(())
+    }
+}
+
+/// Weights and biases of a network with the given layer sizes.
+fn parameter_count(layers: &[usize]) -> usize {
+    layers
+        .windows(2)
+        .map(|pair| pair[0] * pair[1] + pair[1])
+        .sum()
+}
@@ -0,0 +1,220 @@
+use wasm_bindgen::prelude::*;
+
+use crate::bytes::{Reader, Writer};
+use crate::error::Error;
+use crate::grid::{Grid, Traversal};
+use crate::navmesh::NavMesh;
+use crate::terrain::{validate_cost, TerrainCosts, TERRAIN_TYPES};
+
+/// Which flagged areas an agent may enter and what each area type costs it,
+/// so agents with different abilities share the same navigation data, e.g.
+/// zombies wading through water that villagers refuse to touch.
+///
+/// Grid cells and mesh triangles carry a bitmask of flags the game defines,
+/// such as water, door or crawlspace. A cell is open to the agent when it has
+/// none of `exclude_flags` and, unless it has no flags at all, at least one
+/// of `include_flags`. Area costs multiply a grid's terrain costs or a mesh's
+/// area costs, like `TerrainCosts`.
+#[wasm_bindgen]
+#[derive(Clone)]
+pub struct NavFilter {
+    /// Flags of which a flagged area needs at least one; every flag by default.
+    pub include_flags: u32,
+    /// Flags that close an area to the agent; none by default.
+    pub exclude_flags: u32,
+    costs: TerrainCosts,
+}
+
+impl Default for NavFilter {
+    fn default() -> NavFilter {
+        NavFilter {
+            include_flags: u32::MAX,
+            exclude_flags: 0,
+            costs: TerrainCosts::new(),
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl NavFilter {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> NavFilter {
+        NavFilter::default()
+    }
+
+    /// Sets the agent's multiplier on the cost of an area type, a terrain type
+    /// on grids. Defaults to 1; `Infinity` makes the area impassable.
+    pub fn set_area_cost(&mut self, area: u8, multiplier: f32) -> Result<(), Error> {
+        self.costs.set(area, multiplier)
+    }
+
+    pub fn area_cost(&self, area: u8) -> f32 {
+        self.costs.get(area)
+    }
+
+    /// Whether an area with `flags` is open to the agent.
+    pub fn allows(&self, flags: u32) -> bool {
+        flags & self.exclude_flags == 0 && (flags == 0 || flags & self.include_flags != 0)
+    }
+}
+
+impl NavFilter {
+    pub(crate) fn costs(&self) -> &TerrainCosts {
+        &self.costs
+    }
+
+    pub(crate) fn write(&self, out: &mut Writer) {
+        out.u32s(&[self.include_flags, self.exclude_flags]);
+        out.f32s(
+            &(0..TERRAIN_TYPES)
+                .map(|area| self.area_cost(area as u8))
+                .collect::<Vec<_>>(),
+        );
+    }
+
+    pub(crate) fn read(input: &mut Reader) -> Result<NavFilter, Error> {
+        let mut filter = NavFilter::new();
+        filter.include_flags = input.u32()?;
+        filter.exclude_flags = input.u32()?;
+        for (area, cost) in input.f32s(TERRAIN_TYPES)?.into_iter().enumerate() {
+            filter.set_area_cost(area as u8, cost)?;
+        }
+        Ok(filter)
+    }
+}
+
+#[wasm_bindgen]
+impl Grid {
+    /// Sets a cell's area flags. All cells start with none. Out-of-bounds
+    /// cells are ignored.
+    pub fn set_flags(&mut self, x: u32, y: u32, flags: u32) {
+        if x < self.width() && y < self.height() {
+            let index = self.index(x as i32, y as i32);
+            if self.flags[index] != flags {
+                self.flags[index] = flags;
+                self.record_change(index);
+            }
+        }
+    }
+
+    /// Area flags of a cell, 0 when out of bounds.
+    pub fn flags(&self, x: i32, y: i32) -> u32 {
+        if self.in_bounds(x, y) {
+            self.flags[self.index(x, y)]
+        } else {
+            0
+        }
+    }
+
+    /// Like `find_path`, through only the cells `filter` allows and with its
+    /// area cost multipliers applied to the terrain costs.
+    ///
+    /// @deprecated Use `find_path_with`, which takes a radius and a filter.
+    pub fn find_path_filtered(
+        &self,
+        start_x: u32,
+        start_y: u32,
+        end_x: u32,
+        end_y: u32,
+        filter: &NavFilter,
+    ) -> Vec<f32> {
+        self.find_weighted_path(start_x, start_y, end_x, end_y, Traversal::filtered(filter))
+    }
+}
+
+#[wasm_bindgen]
+impl NavMesh {
+    /// Sets a triangle's area type, 0 for every triangle to begin with.
+    pub fn set_area(&mut self, triangle: u32, area: u8) -> bool {
+        self.areas
+            .get_mut(triangle as usize)
+            .map(|slot| *slot = area)
+            .is_some()
+    }
+
+    pub fn area(&self, triangle: u32) -> Option<u8> {
+        self.areas.get(triangle as usize).copied()
+    }
+
+    /// Sets a triangle's area flags; triangles start with none.
+    pub fn set_flags(&mut self, triangle: u32, flags: u32) -> bool {
+        self.flags
+            .get_mut(triangle as usize)
+            .map(|slot| *slot = flags)
+            .is_some()
+    }
+
+    pub fn flags(&self, triangle: u32) -> Option<u32> {
+        self.flags.get(triangle as usize).copied()
+    }
+
+    /// Sets the cost per unit distance of crossing an area type, shared by
+    /// every agent. Defaults to 1; `Infinity` blocks the area outright.
+    pub fn set_area_cost(&mut self, area: u8, cost: f32) -> Result<(), Error> {
+        validate_cost(cost)?;
+        self.area_costs[area as usize] = cost;
+        Ok(())
+    }
+
+    pub fn area_cost(&self, area: u8) -> f32 {
+        self.area_costs[area as usize]
+    }
+
+    /// Like `find_path_with_radius`, through only the triangles `filter`
+    /// allows and with its area cost multipliers applied.
+    ///
+    /// @deprecated Use `find_path_with`, which takes a radius and a filter.
+    #[allow(clippy::too_many_arguments)]
+    pub fn find_path_filtered(
+        &self,
+        start_x: f32,
+        start_y: f32,
+        start_z: f32,
+        end_x: f32,
+        end_y: f32,
+        end_z: f32,
+        radius: f32,
+        filter: &NavFilter,
+    ) -> Vec<f32> {
+        self.find_filtered_path(
+            [start_x, start_y, start_z],
+            [end_x, end_y, end_z],
+            radius,
+            Some(filter),
+        )
+    }
+}
+
+impl NavMesh {
+    /// Whether `filter` lets an agent cross a triangle at all.
+    pub(crate) fn allows(&self, triangle: usize, filter: Option<&NavFilter>) -> bool {
+        !self.is_blocked(triangle)
+            && filter.is_none_or(|filter| filter.allows(self.flags[triangle]))
+            && self.triangle_cost(triangle, filter).is_finite()
+    }
+
+    /// Cost per unit distance of crossing a triangle.
+    pub(crate) fn triangle_cost(&self, triangle: usize, filter: Option<&NavFilter>) -> f32 {
+        let area = self.areas[triangle];
+        let base = self.area_costs[area as usize];
+        match filter {
+            Some(filter) => base * filter.area_cost(area),
+            None => base,
+        }
+    }
+
+    /// Cheapest cost per unit distance, so scaling the straight-line distance
+    /// by it never overestimates.
+    pub(crate) fn cost_scale(&self, filter: Option<&NavFilter>) -> f32 {
+        (0..=u8::MAX)
+            .map(|area| {
+                let base = self.area_costs[area as usize];
+                match filter {
+                    Some(filter) => base * filter.area_cost(area),
+                    None => base,
+                }
+            })
+            .reduce(f32::min)
+            .unwrap_or(1.0)
+    }
+}
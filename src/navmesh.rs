@@ -0,0 +1,531 @@
+use std::collections::{HashMap, HashSet};
+
+use js_sys::Promise;
+use wasm_bindgen::prelude::*;
+
+use crate::carve::Obstacle;
+use crate::debug::{self, DebugGeometry};
+use crate::error::Error;
+use crate::links::OffMeshLink;
+use crate::nav_filter::NavFilter;
+use crate::terrain::TERRAIN_TYPES;
+use crate::tiles::Tile;
+use crate::{search, task};
+
+pub(crate) type Point = [f32; 3];
+
+#[derive(Clone, Copy)]
+pub(crate) struct Link {
+    pub triangle: usize,
+    /// The shared edge, as this triangle's own vertex indices.
+    pub edge: (u32, u32),
+}
+
+/// A triangle navigation mesh. Vertices are `[x, y, z]` with `y` up; paths are
+/// straightened on the XZ plane.
+#[wasm_bindgen]
+#[derive(Clone)]
+pub struct NavMesh {
+    pub(crate) vertices: Vec<Point>,
+    pub(crate) triangles: Vec<[u32; 3]>,
+    pub(crate) centroids: Vec<Point>,
+    pub(crate) links: Vec<Vec<Link>>,
+    /// Whether each vertex touches an edge with no neighboring triangle.
+    pub(crate) boundary: Vec<bool>,
+    /// Number of carved obstacles overlapping each triangle.
+    pub(crate) blockers: Vec<u32>,
+    pub(crate) obstacles: HashMap<u32, Obstacle>,
+    /// Area type and flags of each triangle, for `NavFilter`s.
+    pub(crate) areas: Vec<u8>,
+    pub(crate) flags: Vec<u32>,
+    /// Cost per unit distance of each area type.
+    pub(crate) area_costs: Vec<f32>,
+    pub(crate) next_obstacle: u32,
+    /// Tile edge length for meshes built from tiles, 0 otherwise.
+    pub(crate) tile_size: f32,
+    pub(crate) tiles: HashMap<(i32, i32), Tile>,
+    pub(crate) off_mesh_links: Vec<OffMeshLink<Point>>,
+    pub(crate) next_link: u32,
+}
+
+#[wasm_bindgen]
+impl NavMesh {
+    /// Builds a mesh from flat `[x, y, z, ...]` vertices and triangle indices.
+    /// Triangles sharing an edge are connected.
+    #[wasm_bindgen(constructor)]
+    pub fn new(vertices: &[f32], indices: &[u32]) -> Result<NavMesh, Error> {
+        validate_geometry(vertices, indices)?;
+        let mut mesh = NavMesh::empty(0.0);
+        mesh.append(vertices, indices);
+        Ok(mesh)
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn triangle_count(&self) -> u32 {
+        self.triangles.len() as u32
+    }
+
+    /// Returns the index of the triangle under the point, or -1 when it is off the mesh.
+    pub fn find_triangle(&self, x: f32, y: f32, z: f32) -> i32 {
+        self.locate([x, y, z]).map_or(-1, |t| t as i32)
+    }
+
+    /// Finds a path between two points on the mesh and returns it as flat
+    /// `[x0, y0, z0, x1, y1, z1, ...]`. Returns an empty array when either point is
+    /// off the mesh or the two are not connected.
+    pub fn find_path(
+        &self,
+        start_x: f32,
+        start_y: f32,
+        start_z: f32,
+        end_x: f32,
+        end_y: f32,
+        end_z: f32,
+    ) -> Vec<f32> {
+        self.find_path_with_radius(start_x, start_y, start_z, end_x, end_y, end_z, 0.0)
+    }
+
+    /// Like `find_path`, but keeps the path at least `radius` away from the mesh
+    /// boundary at every portal and skips portals too narrow for the agent.
+    ///
+    /// @deprecated Use `find_path_with`, which takes a radius and a filter.
+    #[allow(clippy::too_many_arguments)]
+    pub fn find_path_with_radius(
+        &self,
+        start_x: f32,
+        start_y: f32,
+        start_z: f32,
+        end_x: f32,
+        end_y: f32,
+        end_z: f32,
+        radius: f32,
+    ) -> Vec<f32> {
+        self.find_filtered_path(
+            [start_x, start_y, start_z],
+            [end_x, end_y, end_z],
+            radius,
+            None,
+        )
+    }
+
+    /// Like `find_path`, but returns a `Promise<Float32Array>` instead of blocking.
+    /// The search runs on a snapshot of the mesh taken at call time.
+    pub fn find_path_async(
+        &self,
+        start_x: f32,
+        start_y: f32,
+        start_z: f32,
+        end_x: f32,
+        end_y: f32,
+        end_z: f32,
+    ) -> Promise {
+        let mesh = self.clone();
+        task::spawn_path(move || mesh.find_path(start_x, start_y, start_z, end_x, end_y, end_z))
+    }
+
+    /// Appends the mesh's triangles to `out`, dark red where carved obstacles
+    /// block them, an outline of every edge and its off-mesh links. Drawn in the
+    /// mesh's own 3D coordinates.
+    pub fn debug_geometry(&self, out: &mut DebugGeometry) {
+        let mut edges = HashSet::new();
+        for (index, triangle) in self.triangles.iter().enumerate() {
+            let color = if self.blockers[index] > 0 {
+                debug::BLOCKED
+            } else {
+                debug::MESH
+            };
+            out.triangle_3d(self.triangle(index), color);
+            for i in 0..3 {
+                let (a, b) = (triangle[i], triangle[(i + 1) % 3]);
+                if edges.insert((a.min(b), a.max(b))) {
+                    out.line_3d(
+                        self.vertices[a as usize],
+                        self.vertices[b as usize],
+                        debug::MESH_EDGE,
+                    );
+                }
+            }
+        }
+        for link in &self.off_mesh_links {
+            out.line_3d(link.from, link.to, debug::LINK);
+        }
+    }
+}
+
+impl NavMesh {
+    pub(crate) fn empty(tile_size: f32) -> NavMesh {
+        NavMesh {
+            vertices: Vec::new(),
+            triangles: Vec::new(),
+            centroids: Vec::new(),
+            links: Vec::new(),
+            boundary: Vec::new(),
+            blockers: Vec::new(),
+            obstacles: HashMap::new(),
+            areas: Vec::new(),
+            flags: Vec::new(),
+            area_costs: vec![1.0; TERRAIN_TYPES],
+            next_obstacle: 0,
+            tile_size,
+            tiles: HashMap::new(),
+            off_mesh_links: Vec::new(),
+            next_link: 0,
+        }
+    }
+
+    /// Adds already validated geometry, connecting the new triangles to each other
+    /// but not to existing ones.
+    pub(crate) fn append(&mut self, vertices: &[f32], indices: &[u32]) {
+        let vertex_base = self.vertices.len() as u32;
+        let triangle_base = self.triangles.len();
+        self.vertices
+            .extend(vertices.chunks_exact(3).map(|v| [v[0], v[1], v[2]]));
+        self.triangles.extend(
+            indices
+                .chunks_exact(3)
+                .map(|t| [t[0] + vertex_base, t[1] + vertex_base, t[2] + vertex_base]),
+        );
+        for index in triangle_base..self.triangles.len() {
+            let [a, b, c] = self.triangle(index);
+            self.centroids.push([
+                (a[0] + b[0] + c[0]) / 3.0,
+                (a[1] + b[1] + c[1]) / 3.0,
+                (a[2] + b[2] + c[2]) / 3.0,
+            ]);
+        }
+
+        let mut edges: HashMap<(u32, u32), Vec<usize>> = HashMap::new();
+        for index in triangle_base..self.triangles.len() {
+            let t = self.triangles[index];
+            for k in 0..3 {
+                edges
+                    .entry(edge_key(t[k], t[(k + 1) % 3]))
+                    .or_default()
+                    .push(index);
+            }
+        }
+
+        self.links.resize(self.triangles.len(), Vec::new());
+        self.blockers.resize(self.triangles.len(), 0);
+        self.areas.resize(self.triangles.len(), 0);
+        self.flags.resize(self.triangles.len(), 0);
+        for (&edge, shared) in &edges {
+            for &from in shared {
+                for &to in shared {
+                    if from != to {
+                        self.links[from].push(Link { triangle: to, edge });
+                    }
+                }
+            }
+        }
+        self.boundary.resize(self.vertices.len(), false);
+        self.refresh_boundary(triangle_base..self.triangles.len());
+    }
+
+    /// Recomputes the boundary flags of the vertices used by `triangles`.
+    pub(crate) fn refresh_boundary(&mut self, triangles: std::ops::Range<usize>) {
+        for index in triangles.clone() {
+            for vertex in self.triangles[index] {
+                self.boundary[vertex as usize] = false;
+            }
+        }
+        for index in triangles {
+            let t = self.triangles[index];
+            for k in 0..3 {
+                let edge = edge_key(t[k], t[(k + 1) % 3]);
+                if !self.links[index].iter().any(|link| link.edge == edge) {
+                    self.boundary[edge.0 as usize] = true;
+                    self.boundary[edge.1 as usize] = true;
+                }
+            }
+        }
+    }
+
+    pub(crate) fn triangle(&self, index: usize) -> [Point; 3] {
+        let t = self.triangles[index];
+        [
+            self.vertices[t[0] as usize],
+            self.vertices[t[1] as usize],
+            self.vertices[t[2] as usize],
+        ]
+    }
+
+    /// Finds the triangle containing the point on the XZ plane, preferring the one
+    /// whose surface is vertically closest when several overlap.
+    pub(crate) fn locate(&self, point: Point) -> Option<usize> {
+        let mut best = None;
+        let mut best_distance = f32::INFINITY;
+        let ranges = self
+            .tile_candidates(point[0], point[2])
+            .unwrap_or_else(|| std::iter::once(0..self.triangles.len()).collect());
+        for index in ranges.into_iter().flatten() {
+            let [a, b, c] = self.triangle(index);
+            if let Some(height) = height_on_triangle(a, b, c, point) {
+                let distance = (height - point[1]).abs();
+                if distance < best_distance {
+                    best_distance = distance;
+                    best = Some(index);
+                }
+            }
+        }
+        best
+    }
+
+    pub(crate) fn find_filtered_path(
+        &self,
+        start: Point,
+        end: Point,
+        radius: f32,
+        filter: Option<&NavFilter>,
+    ) -> Vec<f32> {
+        let corridor = match self.find_corridor(start, end, radius, filter) {
+            Some(corridor) => corridor,
+            None => return Vec::new(),
+        };
+        let portals = self.portals(&corridor, start, end, radius);
+        string_pull(&portals).into_iter().flatten().collect()
+    }
+
+    /// Runs A* over triangle adjacency and returns the triangle corridor, using only
+    /// unobstructed triangles `filter` allows and portals wide enough for an agent
+    /// of `radius`.
+    pub(crate) fn find_corridor(
+        &self,
+        start: Point,
+        end: Point,
+        radius: f32,
+        filter: Option<&NavFilter>,
+    ) -> Option<Vec<usize>> {
+        let from = self.locate(start)?;
+        let to = self.locate(end)?;
+        if !self.allows(from, filter) || !self.allows(to, filter) {
+            return None;
+        }
+        let scale = self.cost_scale(filter);
+        search::astar(
+            self.triangles.len(),
+            from,
+            to,
+            |index, out| self.corridor_edges(index, radius, filter, out),
+            |index| distance(self.centroids[index], end) * scale,
+        )
+    }
+
+    /// Pushes the unobstructed neighbors of a triangle that an agent of `radius`
+    /// can reach and `filter` allows, with centroid-to-centroid distances times
+    /// the mean cost of both triangles.
+    pub(crate) fn corridor_edges(
+        &self,
+        index: usize,
+        radius: f32,
+        filter: Option<&NavFilter>,
+        out: &mut Vec<(usize, f32)>,
+    ) {
+        for link in &self.links[index] {
+            if !self.allows(link.triangle, filter)
+                || (radius > 0.0 && self.portal_width(link.edge, radius) < 0.0)
+            {
+                continue;
+            }
+            let cost = distance(self.centroids[index], self.centroids[link.triangle])
+                * 0.5
+                * (self.triangle_cost(index, filter) + self.triangle_cost(link.triangle, filter));
+            out.push((link.triangle, cost));
+        }
+    }
+
+    pub(crate) fn is_blocked(&self, triangle: usize) -> bool {
+        self.blockers[triangle] > 0
+    }
+
+    /// Length of a shared edge left over once `radius` is kept clear of each
+    /// endpoint on the mesh boundary. Negative when the agent cannot fit through.
+    fn portal_width(&self, edge: (u32, u32), radius: f32) -> f32 {
+        let (a, b) = (edge.0 as usize, edge.1 as usize);
+        let length = distance_xz(self.vertices[a], self.vertices[b]);
+        let inset = (self.boundary[a] as u32 + self.boundary[b] as u32) as f32;
+        length - radius * inset
+    }
+
+    /// Builds the `(left, right)` portal list for a corridor, bracketed by the
+    /// degenerate start and end portals. Boundary endpoints are pulled `radius`
+    /// along the portal so the funnel keeps that distance from walls.
+    pub(crate) fn portals(
+        &self,
+        corridor: &[usize],
+        start: Point,
+        end: Point,
+        radius: f32,
+    ) -> Vec<(Point, Point)> {
+        let mut portals = vec![(start, start)];
+        for pair in corridor.windows(2) {
+            let link = self.links[pair[0]]
+                .iter()
+                .find(|link| link.triangle == pair[1])
+                .expect("corridor triangles are adjacent");
+            let (mut a, mut b) = (
+                self.vertices[link.edge.0 as usize],
+                self.vertices[link.edge.1 as usize],
+            );
+            if radius > 0.0 {
+                let length = distance_xz(a, b).max(f32::EPSILON);
+                let (ta, tb) = (
+                    if self.boundary[link.edge.0 as usize] {
+                        radius / length
+                    } else {
+                        0.0
+                    },
+                    if self.boundary[link.edge.1 as usize] {
+                        radius / length
+                    } else {
+                        0.0
+                    },
+                );
+                (a, b) = (lerp(a, b, ta), lerp(b, a, tb));
+            }
+            let c = self.centroids[pair[0]];
+            let middle = [(a[0] + b[0]) * 0.5, 0.0, (a[2] + b[2]) * 0.5];
+            let direction = [middle[0] - c[0], 0.0, middle[2] - c[2]];
+            let toward_a = [a[0] - c[0], 0.0, a[2] - c[2]];
+            if cross(direction, toward_a) > 0.0 {
+                portals.push((a, b));
+            } else {
+                portals.push((b, a));
+            }
+        }
+        portals.push((end, end));
+        portals
+    }
+}
+
+/// Simple stupid funnel algorithm over `(left, right)` portals on the XZ plane.
+pub(crate) fn string_pull(portals: &[(Point, Point)]) -> Vec<Point> {
+    let mut path = vec![portals[0].0];
+    let mut apex = portals[0].0;
+    let (mut left, mut right) = portals[0];
+    let (mut left_index, mut right_index) = (0, 0);
+
+    let mut i = 1;
+    while i < portals.len() {
+        let (next_left, next_right) = portals[i];
+
+        if triarea2(apex, right, next_right) <= 0.0 {
+            if same_xz(apex, right) || triarea2(apex, left, next_right) > 0.0 {
+                right = next_right;
+                right_index = i;
+            } else {
+                push_unique(&mut path, left);
+                apex = left;
+                let apex_index = left_index;
+                left = apex;
+                right = apex;
+                left_index = apex_index;
+                right_index = apex_index;
+                i = apex_index + 1;
+                continue;
+            }
+        }
+
+        if triarea2(apex, left, next_left) >= 0.0 {
+            if same_xz(apex, left) || triarea2(apex, right, next_left) < 0.0 {
+                left = next_left;
+                left_index = i;
+            } else {
+                push_unique(&mut path, right);
+                apex = right;
+                let apex_index = right_index;
+                left = apex;
+                right = apex;
+                left_index = apex_index;
+                right_index = apex_index;
+                i = apex_index + 1;
+                continue;
+            }
+        }
+
+        i += 1;
+    }
+
+    push_unique(&mut path, portals[portals.len() - 1].0);
+    path
+}
+
+pub(crate) fn validate_geometry(vertices: &[f32], indices: &[u32]) -> Result<(), Error> {
+    if !vertices.len().is_multiple_of(3) {
+        return Err(Error::InvalidInput(
+            "vertex data must be a multiple of 3".into(),
+        ));
+    }
+    if !indices.len().is_multiple_of(3) {
+        return Err(Error::InvalidInput(
+            "index data must be a multiple of 3".into(),
+        ));
+    }
+    let vertex_count = (vertices.len() / 3) as u32;
+    if let Some(&bad) = indices.iter().find(|&&i| i >= vertex_count) {
+        return Err(Error::InvalidInput(format!(
+            "index {} is out of range",
+            bad
+        )));
+    }
+    Ok(())
+}
+
+pub(crate) fn edge_key(a: u32, b: u32) -> (u32, u32) {
+    (a.min(b), a.max(b))
+}
+
+fn push_unique(path: &mut Vec<Point>, point: Point) {
+    if !same_xz(path[path.len() - 1], point) {
+        path.push(point);
+    }
+}
+
+fn triarea2(a: Point, b: Point, c: Point) -> f32 {
+    let (ax, az) = (b[0] - a[0], b[2] - a[2]);
+    let (bx, bz) = (c[0] - a[0], c[2] - a[2]);
+    bx * az - ax * bz
+}
+
+fn cross(u: Point, v: Point) -> f32 {
+    u[0] * v[2] - u[2] * v[0]
+}
+
+fn same_xz(a: Point, b: Point) -> bool {
+    (a[0] - b[0]).abs() < 1e-6 && (a[2] - b[2]).abs() < 1e-6
+}
+
+fn distance_xz(a: Point, b: Point) -> f32 {
+    let (dx, dz) = (a[0] - b[0], a[2] - b[2]);
+    (dx * dx + dz * dz).sqrt()
+}
+
+fn lerp(a: Point, b: Point, t: f32) -> Point {
+    [
+        a[0] + (b[0] - a[0]) * t,
+        a[1] + (b[1] - a[1]) * t,
+        a[2] + (b[2] - a[2]) * t,
+    ]
+}
+
+pub(crate) fn distance(a: Point, b: Point) -> f32 {
+    let (dx, dy, dz) = (a[0] - b[0], a[1] - b[1], a[2] - b[2]);
+    (dx * dx + dy * dy + dz * dz).sqrt()
+}
+
+/// Height of the triangle's plane under `point` if the point lies inside it on XZ.
+pub(crate) fn height_on_triangle(a: Point, b: Point, c: Point, point: Point) -> Option<f32> {
+    let denominator = (b[2] - c[2]) * (a[0] - c[0]) + (c[0] - b[0]) * (a[2] - c[2]);
+    if denominator.abs() < f32::EPSILON {
+        return None;
+    }
+    let u = ((b[2] - c[2]) * (point[0] - c[0]) + (c[0] - b[0]) * (point[2] - c[2])) / denominator;
+    let v = ((c[2] - a[2]) * (point[0] - c[0]) + (a[0] - c[0]) * (point[2] - c[2])) / denominator;
+    let w = 1.0 - u - v;
+    const EPSILON: f32 = -1e-5;
+    if u >= EPSILON && v >= EPSILON && w >= EPSILON {
+        Some(u * a[1] + v * b[1] + w * c[1])
+    } else {
+        None
+    }
+}
@@ -0,0 +1,151 @@
+use wasm_bindgen::prelude::*;
+
+use crate::error::Error;
+use crate::graph::Graph;
+use crate::grid::Grid;
+use crate::search;
+
+/// The cheapest of several goals to reach, as found by `find_nearest`.
+#[wasm_bindgen]
+#[derive(Clone, Debug, PartialEq)]
+pub struct NearestGoal {
+    /// Position of the goal in the list searched for.
+    pub goal: u32,
+    pub cost: f32,
+    nodes: Vec<u32>,
+    path: Vec<f32>,
+}
+
+#[wasm_bindgen]
+impl NearestGoal {
+    /// Graph node ids or grid cell indices (`y * width + x`) along the path,
+    /// including both ends.
+    pub fn nodes(&self) -> Vec<u32> {
+        self.nodes.clone()
+    }
+
+    /// The path as flat `[x0, y0, x1, y1, ...]` cell coordinates for grids;
+    /// empty for graphs, whose nodes have no position.
+    pub fn path(&self) -> Vec<f32> {
+        self.path.clone()
+    }
+}
+
+#[wasm_bindgen]
+impl Grid {
+    /// Finds whichever of the cells in `goals`, flat `[x0, y0, x1, y1, ...]`,
+    /// is cheapest to reach and the path there, in one search however many
+    /// goals there are. Returns `None` when none can be reached; goals outside
+    /// the grid or blocked are skipped.
+    pub fn find_nearest(
+        &self,
+        start_x: u32,
+        start_y: u32,
+        goals: &[u32],
+    ) -> Result<Option<NearestGoal>, Error> {
+        let goal_at = self.cells_of(goals)?;
+        let (sx, sy) = (start_x as i32, start_y as i32);
+        if !self.is_walkable(sx, sy) {
+            return Ok(None);
+        }
+        let found = search::nearest(
+            goal_at.len(),
+            self.index(sx, sy),
+            |index| goal_at[index] != u32::MAX,
+            |index, out| self.neighbors(index, out),
+        );
+        Ok(found.map(|(path, cost)| NearestGoal {
+            goal: goal_at[*path.last().unwrap()],
+            cost,
+            path: self.to_waypoints(&path),
+            nodes: path.into_iter().map(|index| index as u32).collect(),
+        }))
+    }
+
+    /// Cost of reaching every cell from the nearest of `sources`, flat `[x0,
+    /// y0, ...]`, indexed by `y * width + x` and `Infinity` where unreachable,
+    /// e.g. to pick among many candidates or to walk downhill to the closest
+    /// source. Sources outside the grid or blocked are skipped.
+    pub fn distance_field(&self, sources: &[u32]) -> Result<Vec<f32>, Error> {
+        let sources: Vec<usize> = self
+            .cells_of(sources)?
+            .iter()
+            .enumerate()
+            .filter(|&(_, &goal)| goal != u32::MAX)
+            .map(|(index, _)| index)
+            .collect();
+        Ok(search::dijkstra(
+            self.cell_count(),
+            &sources,
+            |index, out| self.neighbors(index, out),
+        ))
+    }
+}
+
+impl Grid {
+    /// For each cell, the position in `cells` of the first walkable pair
+    /// naming it, or `u32::MAX`.
+    pub(crate) fn cells_of(&self, cells: &[u32]) -> Result<Vec<u32>, Error> {
+        if !cells.len().is_multiple_of(2) {
+            return Err(Error::InvalidInput(
+                "expected flat [x, y] cell pairs".into(),
+            ));
+        }
+        let mut goal_at = vec![u32::MAX; self.cell_count()];
+        for (goal, cell) in cells.chunks_exact(2).enumerate() {
+            let (x, y) = (cell[0] as i32, cell[1] as i32);
+            if self.is_walkable(x, y) {
+                let slot = &mut goal_at[self.index(x, y)];
+                if *slot == u32::MAX {
+                    *slot = goal as u32;
+                }
+            }
+        }
+        Ok(goal_at)
+    }
+}
+
+#[wasm_bindgen]
+impl Graph {
+    /// Finds whichever node in `goals` is cheapest to reach from `start` and
+    /// the path there, in one search. Returns `None` when none can be reached;
+    /// ids of missing nodes are skipped.
+    pub fn find_nearest(&self, start: u32, goals: &[u32]) -> Option<NearestGoal> {
+        let node_count = self.node_count() as usize;
+        if start as usize >= node_count {
+            return None;
+        }
+        let mut goal_at = vec![u32::MAX; node_count];
+        for (goal, &node) in goals.iter().enumerate().rev() {
+            if let Some(slot) = goal_at.get_mut(node as usize) {
+                *slot = goal as u32;
+            }
+        }
+        let (path, cost) = search::nearest(
+            node_count,
+            start as usize,
+            |index| goal_at[index] != u32::MAX,
+            |index, out| self.neighbors(index, out),
+        )?;
+        Some(NearestGoal {
+            goal: goal_at[*path.last().unwrap()],
+            cost,
+            nodes: path.into_iter().map(|index| index as u32).collect(),
+            path: Vec::new(),
+        })
+    }
+
+    /// Cost of the cheapest path to every node from the nearest of `sources`,
+    /// `Infinity` where unreachable. Ids of missing nodes are skipped.
+    pub fn distance_field(&self, sources: &[u32]) -> Vec<f32> {
+        let node_count = self.node_count() as usize;
+        let sources: Vec<usize> = sources
+            .iter()
+            .map(|&node| node as usize)
+            .filter(|&node| node < node_count)
+            .collect();
+        search::dijkstra(node_count, &sources, |index, out| {
+            self.neighbors(index, out)
+        })
+    }
+}
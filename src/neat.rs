@@ -0,0 +1,1063 @@
+use std::collections::HashMap;
+
+use js_sys::Function;
+use wasm_bindgen::prelude::*;
+
+use crate::error::Error;
+use crate::json::Json;
+use crate::random::Rng;
+
+/// Steepness of the node activation, as in the original NEAT paper.
+const STEEPNESS: f32 = 4.9;
+
+/// Tuning for `Population`. The defaults follow the original NEAT paper.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct NeatConfig {
+    pub population_size: u32,
+    /// Genomes closer than this share a species.
+    pub compatibility_threshold: f32,
+    /// Weights of excess genes, disjoint genes and mean weight difference in the
+    /// compatibility distance.
+    pub excess_coefficient: f32,
+    pub disjoint_coefficient: f32,
+    pub weight_coefficient: f32,
+    /// Chance that a child's weights mutate, and then per weight the chance of a
+    /// fresh random value instead of a nudge of up to `weight_perturbation`.
+    pub weight_mutation_rate: f32,
+    pub weight_replace_rate: f32,
+    pub weight_perturbation: f32,
+    pub add_connection_rate: f32,
+    pub add_node_rate: f32,
+    /// Chance of flipping one connection between enabled and disabled.
+    pub toggle_rate: f32,
+    /// Chance that a child has two parents rather than one.
+    pub crossover_rate: f32,
+    /// Fraction of each species, fittest first, allowed to reproduce.
+    pub survival_threshold: f32,
+    /// Champions each species copies unchanged into the next generation.
+    pub elitism: u32,
+    /// Generations without improvement before a species is culled.
+    pub stagnation_limit: u32,
+}
+
+#[wasm_bindgen]
+impl NeatConfig {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> NeatConfig {
+        NeatConfig {
+            population_size: 150,
+            compatibility_threshold: 3.0,
+            excess_coefficient: 1.0,
+            disjoint_coefficient: 1.0,
+            weight_coefficient: 0.4,
+            weight_mutation_rate: 0.8,
+            weight_replace_rate: 0.1,
+            weight_perturbation: 0.5,
+            add_connection_rate: 0.05,
+            add_node_rate: 0.03,
+            toggle_rate: 0.01,
+            crossover_rate: 0.75,
+            survival_threshold: 0.2,
+            elitism: 1,
+            stagnation_limit: 15,
+        }
+    }
+}
+
+impl Default for NeatConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum NodeKind {
+    Input,
+    Bias,
+    Output,
+    Hidden,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Connection {
+    innovation: u32,
+    from: u32,
+    to: u32,
+    weight: f32,
+    enabled: bool,
+}
+
+/// A NEAT network: its node and connection genes, and the fitness it scored.
+/// Networks are feed-forward; node ids number the inputs first, then a bias node,
+/// then the outputs, then hidden nodes.
+#[wasm_bindgen]
+#[derive(Clone, Debug)]
+pub struct Genome {
+    /// Sorted by id.
+    nodes: Vec<(u32, NodeKind)>,
+    /// Sorted by innovation number.
+    connections: Vec<Connection>,
+    inputs: u32,
+    outputs: u32,
+    fitness: f32,
+    /// Node indices in evaluation order, each with its enabled inputs as
+    /// `(node index, weight)`.
+    steps: Vec<(usize, Vec<(usize, f32)>)>,
+}
+
+#[wasm_bindgen]
+impl Genome {
+    /// Feeds one row of inputs through the network and returns its outputs, each
+    /// in `[0, 1]`.
+    pub fn activate(&self, inputs: &[f32]) -> Vec<f32> {
+        let mut values = vec![0.0; self.nodes.len()];
+        for (value, &input) in values.iter_mut().zip(inputs).take(self.inputs as usize) {
+            *value = input;
+        }
+        values[self.inputs as usize] = 1.0;
+        for (node, incoming) in &self.steps {
+            let sum: f32 = incoming
+                .iter()
+                .map(|&(from, weight)| values[from] * weight)
+                .sum();
+            values[*node] = 1.0 / (1.0 + (-STEEPNESS * sum).exp());
+        }
+        let first = self.inputs as usize + 1;
+        values[first..first + self.outputs as usize].to_vec()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn fitness(&self) -> f32 {
+        self.fitness
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn input_count(&self) -> u32 {
+        self.inputs
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn output_count(&self) -> u32 {
+        self.outputs
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn node_count(&self) -> u32 {
+        self.nodes.len() as u32
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn connection_count(&self) -> u32 {
+        self.connections.len() as u32
+    }
+
+    /// Connection genes as flat `[from0, to0, weight0, enabled0, ...]` node ids,
+    /// with `enabled` 1 or 0, for drawing the network.
+    pub fn connections(&self) -> Vec<f32> {
+        self.connections
+            .iter()
+            .flat_map(|c| {
+                [
+                    c.from as f32,
+                    c.to as f32,
+                    c.weight,
+                    if c.enabled { 1.0 } else { 0.0 },
+                ]
+            })
+            .collect()
+    }
+
+    pub fn to_json(&self) -> String {
+        self.json().to_string()
+    }
+
+    pub fn from_json(json: &str) -> Result<Genome, Error> {
+        Genome::parse(&Json::parse(json)?)
+    }
+}
+
+impl Genome {
+    /// Inputs and bias fully connected to the outputs, with innovation numbers in
+    /// the same order for every genome.
+    fn minimal(inputs: u32, outputs: u32, rng: &mut Rng) -> Genome {
+        let mut nodes: Vec<(u32, NodeKind)> = (0..inputs).map(|id| (id, NodeKind::Input)).collect();
+        nodes.push((inputs, NodeKind::Bias));
+        nodes.extend((0..outputs).map(|k| (inputs + 1 + k, NodeKind::Output)));
+        let mut connections = Vec::new();
+        for to in 0..outputs {
+            for from in 0..=inputs {
+                connections.push(Connection {
+                    innovation: connections.len() as u32,
+                    from,
+                    to: inputs + 1 + to,
+                    weight: rng.next_signed(),
+                    enabled: true,
+                });
+            }
+        }
+        let mut genome = Genome {
+            nodes,
+            connections,
+            inputs,
+            outputs,
+            fitness: 0.0,
+            steps: Vec::new(),
+        };
+        genome.compile();
+        genome
+    }
+
+    fn node_index(&self, id: u32) -> Option<usize> {
+        self.nodes.binary_search_by_key(&id, |&(id, _)| id).ok()
+    }
+
+    fn connection(&self, innovation: u32) -> Option<&Connection> {
+        self.connections
+            .binary_search_by_key(&innovation, |c| c.innovation)
+            .ok()
+            .map(|index| &self.connections[index])
+    }
+
+    /// Rebuilds the evaluation order after the genes change.
+    fn compile(&mut self) {
+        let count = self.nodes.len();
+        let mut incoming: Vec<Vec<(usize, f32)>> = vec![Vec::new(); count];
+        let mut pending = vec![0usize; count];
+        let mut outgoing: Vec<Vec<usize>> = vec![Vec::new(); count];
+        for c in self.connections.iter().filter(|c| c.enabled) {
+            if let (Some(from), Some(to)) = (self.node_index(c.from), self.node_index(c.to)) {
+                incoming[to].push((from, c.weight));
+                outgoing[from].push(to);
+                pending[to] += 1;
+            }
+        }
+        // Kahn's algorithm from the nodes with no enabled inputs.
+        let mut ready: Vec<usize> = (0..count).filter(|&index| pending[index] == 0).collect();
+        let mut steps = Vec::new();
+        while let Some(index) = ready.pop() {
+            if matches!(self.nodes[index].1, NodeKind::Output | NodeKind::Hidden) {
+                steps.push((index, std::mem::take(&mut incoming[index])));
+            }
+            for &next in &outgoing[index] {
+                pending[next] -= 1;
+                if pending[next] == 0 {
+                    ready.push(next);
+                }
+            }
+        }
+        self.steps = steps;
+    }
+
+    /// Whether `to` already reaches `from`, so that connecting them would close a
+    /// loop. Disabled connections count, since they may be enabled again.
+    fn reaches(&self, to: u32, from: u32) -> bool {
+        let mut stack = vec![to];
+        let mut seen = vec![to];
+        while let Some(id) = stack.pop() {
+            if id == from {
+                return true;
+            }
+            for c in self.connections.iter().filter(|c| c.from == id) {
+                if !seen.contains(&c.to) {
+                    seen.push(c.to);
+                    stack.push(c.to);
+                }
+            }
+        }
+        false
+    }
+
+    fn distance(&self, other: &Genome, config: &NeatConfig) -> f32 {
+        let (mut i, mut j) = (0, 0);
+        let (mut disjoint, mut matching, mut weight_difference) = (0, 0, 0.0);
+        let (a, b) = (&self.connections, &other.connections);
+        while i < a.len() && j < b.len() {
+            match a[i].innovation.cmp(&b[j].innovation) {
+                std::cmp::Ordering::Equal => {
+                    matching += 1;
+                    weight_difference += (a[i].weight - b[j].weight).abs();
+                    i += 1;
+                    j += 1;
+                }
+                std::cmp::Ordering::Less => {
+                    disjoint += 1;
+                    i += 1;
+                }
+                std::cmp::Ordering::Greater => {
+                    disjoint += 1;
+                    j += 1;
+                }
+            }
+        }
+        let excess = (a.len() - i) + (b.len() - j);
+        // Small genomes are not normalized by size, as in the paper.
+        let genes = a.len().max(b.len());
+        let size = if genes < 20 { 1.0 } else { genes as f32 };
+        let mean_difference = if matching > 0 {
+            weight_difference / matching as f32
+        } else {
+            0.0
+        };
+        config.excess_coefficient * excess as f32 / size
+            + config.disjoint_coefficient * disjoint as f32 / size
+            + config.weight_coefficient * mean_difference
+    }
+
+    fn json(&self) -> Json {
+        let hidden = self
+            .nodes
+            .iter()
+            .filter(|(_, kind)| *kind == NodeKind::Hidden)
+            .map(|&(id, _)| Json::Number(id as f64))
+            .collect();
+        let connections = self
+            .connections
+            .iter()
+            .map(|c| {
+                Json::Array(vec![
+                    Json::Number(c.innovation as f64),
+                    Json::Number(c.from as f64),
+                    Json::Number(c.to as f64),
+                    Json::from_f32(c.weight),
+                    Json::Bool(c.enabled),
+                ])
+            })
+            .collect();
+        Json::Object(vec![
+            ("inputs".into(), Json::Number(self.inputs as f64)),
+            ("outputs".into(), Json::Number(self.outputs as f64)),
+            ("hidden".into(), Json::Array(hidden)),
+            ("connections".into(), Json::Array(connections)),
+            ("fitness".into(), Json::from_f32(self.fitness)),
+        ])
+    }
+
+    /// Reads the format of `to_json`: `{"inputs", "outputs", "hidden": [ids],
+    /// "connections": [[innovation, from, to, weight, enabled], ...], "fitness"}`.
+    fn parse(json: &Json) -> Result<Genome, Error> {
+        let inputs = json.field("inputs", Json::as_f64)? as u32;
+        let outputs = json.field("outputs", Json::as_f64)? as u32;
+        let mut nodes: Vec<(u32, NodeKind)> = (0..inputs).map(|id| (id, NodeKind::Input)).collect();
+        nodes.push((inputs, NodeKind::Bias));
+        nodes.extend((0..outputs).map(|k| (inputs + 1 + k, NodeKind::Output)));
+        for id in json.field("hidden", Json::as_f32s)? {
+            nodes.push((id as u32, NodeKind::Hidden));
+        }
+        nodes.sort_by_key(|&(id, _)| id);
+        nodes.dedup_by_key(|&mut (id, _)| id);
+
+        let invalid = || Error::InvalidInput("invalid connection gene".into());
+        let mut connections = json
+            .field("connections", Json::as_array)?
+            .iter()
+            .map(|gene| {
+                let gene = gene
+                    .as_array()
+                    .filter(|gene| gene.len() == 5)
+                    .ok_or_else(invalid)?;
+                let number = |index: usize| gene[index].as_f64().ok_or_else(invalid);
+                Ok(Connection {
+                    innovation: number(0)? as u32,
+                    from: number(1)? as u32,
+                    to: number(2)? as u32,
+                    weight: number(3)? as f32,
+                    enabled: matches!(gene[4], Json::Bool(true)),
+                })
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        connections.sort_by_key(|c| c.innovation);
+        let mut genome = Genome {
+            nodes,
+            connections,
+            inputs,
+            outputs,
+            fitness: json.get("fitness").and_then(Json::as_f64).unwrap_or(0.0) as f32,
+            steps: Vec::new(),
+        };
+        let dangling = genome
+            .connections
+            .iter()
+            .any(|c| genome.node_index(c.from).is_none() || genome.node_index(c.to).is_none());
+        if dangling {
+            return Err(Error::InvalidInput(
+                "connection refers to a missing node".into(),
+            ));
+        }
+        genome.compile();
+        Ok(genome)
+    }
+}
+
+struct Species {
+    id: u32,
+    representative: Genome,
+    members: Vec<usize>,
+    best_fitness: f32,
+    last_improved: u32,
+}
+
+/// A NEAT population: genomes grouped into species by structural similarity,
+/// where each generation the fitter species breed more children through
+/// crossover and mutation, and networks grow new nodes and connections over time.
+///
+/// Score every genome, either with `evaluate(fitness)` or one at a time with
+/// `set_fitness`, then call `epoch` to breed the next generation.
+#[wasm_bindgen]
+pub struct Population {
+    config: NeatConfig,
+    inputs: u32,
+    outputs: u32,
+    genomes: Vec<Genome>,
+    species: Vec<Species>,
+    next_species: u32,
+    generation: u32,
+    /// Innovation numbers of every connection ever added, by endpoints.
+    innovations: HashMap<(u32, u32), u32>,
+    /// Hidden node created by splitting each connection, by its innovation.
+    splits: HashMap<u32, u32>,
+    next_innovation: u32,
+    next_node: u32,
+    best: Option<Genome>,
+    rng: Rng,
+}
+
+#[wasm_bindgen]
+impl Population {
+    /// A population of minimal networks, each connecting every input straight to
+    /// every output.
+    #[wasm_bindgen(constructor)]
+    pub fn new(
+        inputs: u32,
+        outputs: u32,
+        config: &NeatConfig,
+        seed: u64,
+    ) -> Result<Population, Error> {
+        if inputs == 0 || outputs == 0 || config.population_size == 0 {
+            return Err(Error::InvalidInput(
+                "inputs, outputs and population size must be positive".into(),
+            ));
+        }
+        let mut rng = Rng::new(seed);
+        let genomes: Vec<Genome> = (0..config.population_size)
+            .map(|_| Genome::minimal(inputs, outputs, &mut rng))
+            .collect();
+        let mut innovations = HashMap::new();
+        for c in &genomes[0].connections {
+            innovations.insert((c.from, c.to), c.innovation);
+        }
+        Ok(Population {
+            config: *config,
+            inputs,
+            outputs,
+            next_innovation: innovations.len() as u32,
+            next_node: inputs + 1 + outputs,
+            genomes,
+            species: Vec::new(),
+            next_species: 0,
+            generation: 0,
+            innovations,
+            splits: HashMap::new(),
+            best: None,
+            rng,
+        })
+    }
+
+    /// Draws all further mutation and breeding randomness from a copy of `rng`.
+    pub fn set_rng(&mut self, rng: &Rng) {
+        self.rng = rng.clone();
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn generation(&self) -> u32 {
+        self.generation
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn len(&self) -> u32 {
+        self.genomes.len() as u32
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.genomes.is_empty()
+    }
+
+    /// Species found by the last `epoch`.
+    #[wasm_bindgen(getter)]
+    pub fn species_count(&self) -> u32 {
+        self.species.len() as u32
+    }
+
+    /// A copy of a genome of the current generation.
+    pub fn genome(&self, index: u32) -> Option<Genome> {
+        self.genomes.get(index as usize).cloned()
+    }
+
+    /// Runs a genome of the current generation without copying it.
+    pub fn activate(&self, index: u32, inputs: &[f32]) -> Vec<f32> {
+        self.genomes
+            .get(index as usize)
+            .map(|genome| genome.activate(inputs))
+            .unwrap_or_default()
+    }
+
+    pub fn set_fitness(&mut self, index: u32, fitness: f32) -> bool {
+        self.genomes
+            .get_mut(index as usize)
+            .map(|genome| genome.fitness = fitness)
+            .is_some()
+    }
+
+    /// Scores every genome with `fitness(genome, index)`, which returns a number
+    /// where higher is fitter.
+    pub fn evaluate(&mut self, fitness: &Function) -> Result<(), Error> {
+        for (index, genome) in self.genomes.iter_mut().enumerate() {
+            let score = fitness
+                .call2(
+                    &JsValue::NULL,
+                    &JsValue::from(genome.clone()),
+                    &JsValue::from(index as u32),
+                )
+                .ok()
+                .and_then(|value| value.as_f64())
+                .ok_or_else(|| {
+                    Error::InvalidInput(format!("fitness of genome {} is not a number", index))
+                })?;
+            genome.fitness = score as f32;
+        }
+        Ok(())
+    }
+
+    /// The fittest genome seen in any generation so far.
+    pub fn best(&self) -> Option<Genome> {
+        self.best.clone()
+    }
+
+    /// Groups the scored generation into species and replaces it with their
+    /// children.
+    pub fn epoch(&mut self) {
+        self.remember_best();
+        self.speciate();
+        let offspring = self.allot_offspring();
+
+        let ranked: Vec<Vec<usize>> = self
+            .species
+            .iter()
+            .map(|species| {
+                let mut ranked = species.members.clone();
+                ranked
+                    .sort_by(|&a, &b| self.genomes[b].fitness.total_cmp(&self.genomes[a].fitness));
+                ranked
+            })
+            .collect();
+        let mut children = Vec::with_capacity(self.config.population_size as usize);
+        for (ranked, count) in ranked.into_iter().zip(offspring) {
+            let elites = (self.config.elitism as usize).min(count);
+            children.extend(
+                ranked
+                    .iter()
+                    .take(elites)
+                    .map(|&index| self.genomes[index].clone()),
+            );
+            let parents = ((ranked.len() as f32 * self.config.survival_threshold).ceil() as usize)
+                .clamp(1, ranked.len());
+            for _ in elites.min(ranked.len())..count {
+                let a = ranked[self.rng.below(parents)];
+                let mut child = if parents > 1 && self.rng.chance(self.config.crossover_rate) {
+                    let b = ranked[self.rng.below(parents)];
+                    crossover(&self.genomes[a], &self.genomes[b], &mut self.rng)
+                } else {
+                    self.genomes[a].clone()
+                };
+                self.mutate(&mut child);
+                children.push(child);
+            }
+        }
+
+        // Each species is represented by a random member of the old generation.
+        for species in &mut self.species {
+            let pick = species.members[self.rng.below(species.members.len())];
+            species.representative = self.genomes[pick].clone();
+            species.members.clear();
+        }
+        for child in &mut children {
+            child.fitness = 0.0;
+            child.compile();
+        }
+        self.genomes = children;
+        self.generation += 1;
+    }
+
+    /// Scores the generation with `fitness` and breeds the next one.
+    pub fn evolve(&mut self, fitness: &Function) -> Result<(), Error> {
+        self.evaluate(fitness)?;
+        self.epoch();
+        Ok(())
+    }
+
+    /// The whole population, including innovation history and the random state,
+    /// so that evolution resumes exactly where it stopped.
+    pub fn to_json(&self) -> String {
+        let number = |value: u32| Json::Number(value as f64);
+        let mut innovations: Vec<_> = self.innovations.iter().collect();
+        innovations.sort_by_key(|(_, &innovation)| innovation);
+        let mut splits: Vec<_> = self.splits.iter().collect();
+        splits.sort();
+        let species = self
+            .species
+            .iter()
+            .map(|species| {
+                Json::Object(vec![
+                    ("id".into(), number(species.id)),
+                    ("best_fitness".into(), Json::from_f32(species.best_fitness)),
+                    ("last_improved".into(), number(species.last_improved)),
+                    ("representative".into(), species.representative.json()),
+                ])
+            })
+            .collect();
+        Json::Object(vec![
+            ("config".into(), config_json(&self.config)),
+            ("inputs".into(), number(self.inputs)),
+            ("outputs".into(), number(self.outputs)),
+            ("generation".into(), number(self.generation)),
+            ("next_species".into(), number(self.next_species)),
+            ("next_innovation".into(), number(self.next_innovation)),
+            ("next_node".into(), number(self.next_node)),
+            (
+                "rng".into(),
+                Json::Array(self.rng.state().into_iter().map(number).collect()),
+            ),
+            (
+                "innovations".into(),
+                Json::Array(
+                    innovations
+                        .into_iter()
+                        .map(|(&(from, to), &innovation)| {
+                            Json::Array(vec![number(from), number(to), number(innovation)])
+                        })
+                        .collect(),
+                ),
+            ),
+            (
+                "splits".into(),
+                Json::Array(
+                    splits
+                        .into_iter()
+                        .map(|(&innovation, &node)| {
+                            Json::Array(vec![number(innovation), number(node)])
+                        })
+                        .collect(),
+                ),
+            ),
+            ("species".into(), Json::Array(species)),
+            (
+                "genomes".into(),
+                Json::Array(self.genomes.iter().map(Genome::json).collect()),
+            ),
+            (
+                "best".into(),
+                self.best.as_ref().map(Genome::json).unwrap_or(Json::Null),
+            ),
+        ])
+        .to_string()
+    }
+
+    pub fn from_json(json: &str) -> Result<Population, Error> {
+        let json = Json::parse(json)?;
+        let number = |key: &str| json.field(key, Json::as_f64).map(|value| value as u32);
+        let pairs = |key: &str| -> Result<Vec<Vec<u32>>, Error> {
+            Ok(json
+                .field(key, Json::as_array)?
+                .iter()
+                .filter_map(Json::as_f32s)
+                .map(|values| values.into_iter().map(|value| value as u32).collect())
+                .collect())
+        };
+        let genomes = json
+            .field("genomes", Json::as_array)?
+            .iter()
+            .map(Genome::parse)
+            .collect::<Result<Vec<_>, Error>>()?;
+        if genomes.is_empty() {
+            return Err(Error::InvalidInput("population has no genomes".into()));
+        }
+        let species = json
+            .field("species", Json::as_array)?
+            .iter()
+            .map(|species| {
+                Ok(Species {
+                    id: species.field("id", Json::as_f64)? as u32,
+                    representative: Genome::parse(species.field("representative", Some)?)?,
+                    members: Vec::new(),
+                    best_fitness: species.field("best_fitness", Json::as_f64)? as f32,
+                    last_improved: species.field("last_improved", Json::as_f64)? as u32,
+                })
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        let mut rng = Rng::new(0);
+        rng.set_state(
+            &json
+                .field("rng", Json::as_array)?
+                .iter()
+                .map(|word| word.as_f64().map(|word| word as u32))
+                .collect::<Option<Vec<_>>>()
+                .ok_or_else(|| Error::InvalidInput("invalid field 'rng'".into()))?,
+        )?;
+        Ok(Population {
+            config: parse_config(json.field("config", Some)?)?,
+            inputs: number("inputs")?,
+            outputs: number("outputs")?,
+            genomes,
+            species,
+            next_species: number("next_species")?,
+            generation: number("generation")?,
+            innovations: pairs("innovations")?
+                .into_iter()
+                .filter(|triple| triple.len() == 3)
+                .map(|triple| ((triple[0], triple[1]), triple[2]))
+                .collect(),
+            splits: pairs("splits")?
+                .into_iter()
+                .filter(|pair| pair.len() == 2)
+                .map(|pair| (pair[0], pair[1]))
+                .collect(),
+            next_innovation: number("next_innovation")?,
+            next_node: number("next_node")?,
+            best: match json.get("best") {
+                None | Some(Json::Null) => None,
+                Some(best) => Some(Genome::parse(best)?),
+            },
+            rng,
+        })
+    }
+}
+
+impl Population {
+    /// The genomes of the current generation.
+    pub fn genomes(&self) -> &[Genome] {
+        &self.genomes
+    }
+
+    /// Scores every genome with a Rust-side `fitness(genome, index)`.
+    pub fn evaluate_with(&mut self, mut fitness: impl FnMut(&Genome, usize) -> f32) {
+        for (index, genome) in self.genomes.iter_mut().enumerate() {
+            genome.fitness = fitness(genome, index);
+        }
+    }
+
+    /// Scores the generation with a Rust-side fitness function and breeds
+    /// the next one.
+    pub fn evolve_with(&mut self, fitness: impl FnMut(&Genome, usize) -> f32) {
+        self.evaluate_with(fitness);
+        self.epoch();
+    }
+
+    fn remember_best(&mut self) {
+        let fittest = self
+            .genomes
+            .iter()
+            .max_by(|a, b| a.fitness.total_cmp(&b.fitness));
+        if let Some(fittest) = fittest {
+            if self
+                .best
+                .as_ref()
+                .is_none_or(|best| fittest.fitness > best.fitness)
+            {
+                self.best = Some(fittest.clone());
+            }
+        }
+    }
+
+    /// Assigns every genome to the first species whose representative is close
+    /// enough, founding new species as needed, then culls stagnant species.
+    fn speciate(&mut self) {
+        for (index, genome) in self.genomes.iter().enumerate() {
+            let home = self.species.iter_mut().find(|species| {
+                species.representative.distance(genome, &self.config)
+                    < self.config.compatibility_threshold
+            });
+            match home {
+                Some(species) => species.members.push(index),
+                None => {
+                    self.species.push(Species {
+                        id: self.next_species,
+                        representative: genome.clone(),
+                        members: vec![index],
+                        best_fitness: f32::NEG_INFINITY,
+                        last_improved: self.generation,
+                    });
+                    self.next_species += 1;
+                }
+            }
+        }
+        self.species.retain(|species| !species.members.is_empty());
+
+        for species in &mut self.species {
+            let best = species
+                .members
+                .iter()
+                .map(|&index| self.genomes[index].fitness)
+                .fold(f32::NEG_INFINITY, f32::max);
+            if best > species.best_fitness {
+                species.best_fitness = best;
+                species.last_improved = self.generation;
+            }
+        }
+        // The two best species survive stagnation so the population never dies out.
+        self.species
+            .sort_by(|a, b| b.best_fitness.total_cmp(&a.best_fitness));
+        let (generation, limit) = (self.generation, self.config.stagnation_limit);
+        let mut rank = 0;
+        self.species.retain(|species| {
+            rank += 1;
+            rank <= 2 || generation - species.last_improved < limit
+        });
+    }
+
+    /// Children per species, in proportion to the mean fitness of its members, so
+    /// that large species do not crowd out small ones.
+    fn allot_offspring(&self) -> Vec<usize> {
+        let size = self.config.population_size as usize;
+        let lowest = self
+            .species
+            .iter()
+            .flat_map(|species| &species.members)
+            .map(|&index| self.genomes[index].fitness)
+            .fold(f32::INFINITY, f32::min);
+        let shares: Vec<f32> = self
+            .species
+            .iter()
+            .map(|species| {
+                let total: f32 = species
+                    .members
+                    .iter()
+                    .map(|&index| self.genomes[index].fitness - lowest + 1e-3)
+                    .sum();
+                total / species.members.len() as f32
+            })
+            .collect();
+        let total: f32 = shares.iter().sum();
+        let exact: Vec<f32> = shares
+            .iter()
+            .map(|share| share / total * size as f32)
+            .collect();
+        let mut counts: Vec<usize> = exact.iter().map(|&count| count.floor() as usize).collect();
+        // Hand the rounding remainder to the largest fractions.
+        let mut order: Vec<usize> = (0..counts.len()).collect();
+        order.sort_by(|&a, &b| {
+            (exact[b] - exact[b].floor()).total_cmp(&(exact[a] - exact[a].floor()))
+        });
+        let given: usize = counts.iter().sum();
+        for &species in order.iter().cycle().take(size.saturating_sub(given)) {
+            counts[species] += 1;
+        }
+        counts
+    }
+
+    fn mutate(&mut self, genome: &mut Genome) {
+        let config = self.config;
+        if self.rng.chance(config.weight_mutation_rate) {
+            for c in &mut genome.connections {
+                if self.rng.chance(config.weight_replace_rate) {
+                    c.weight = self.rng.next_signed() * 2.0;
+                } else {
+                    c.weight += self.rng.next_signed() * config.weight_perturbation;
+                }
+            }
+        }
+        if self.rng.chance(config.add_connection_rate) {
+            self.add_connection(genome);
+        }
+        if self.rng.chance(config.add_node_rate) {
+            self.add_node(genome);
+        }
+        if !genome.connections.is_empty() && self.rng.chance(config.toggle_rate) {
+            let pick = self.rng.below(genome.connections.len());
+            genome.connections[pick].enabled = !genome.connections[pick].enabled;
+        }
+    }
+
+    /// Connects two unconnected nodes, from an input, the bias or a hidden node to
+    /// a hidden or output node, without closing a loop.
+    fn add_connection(&mut self, genome: &mut Genome) {
+        for _ in 0..20 {
+            let from = genome.nodes[self.rng.below(genome.nodes.len())];
+            let to = genome.nodes[self.rng.below(genome.nodes.len())];
+            if from.1 == NodeKind::Output || matches!(to.1, NodeKind::Input | NodeKind::Bias) {
+                continue;
+            }
+            let (from, to) = (from.0, to.0);
+            if from == to
+                || genome
+                    .connections
+                    .iter()
+                    .any(|c| c.from == from && c.to == to)
+                || genome.reaches(to, from)
+            {
+                continue;
+            }
+            let innovation = self.innovation(from, to);
+            let weight = self.rng.next_signed();
+            insert_connection(
+                genome,
+                Connection {
+                    innovation,
+                    from,
+                    to,
+                    weight,
+                    enabled: true,
+                },
+            );
+            return;
+        }
+    }
+
+    /// Splits an enabled connection with a new hidden node: the old connection is
+    /// disabled, the link into the node has weight 1 and the link out keeps the
+    /// old weight.
+    fn add_node(&mut self, genome: &mut Genome) {
+        let enabled: Vec<usize> = (0..genome.connections.len())
+            .filter(|&index| genome.connections[index].enabled)
+            .collect();
+        if enabled.is_empty() {
+            return;
+        }
+        let split = enabled[self.rng.below(enabled.len())];
+        genome.connections[split].enabled = false;
+        let old = genome.connections[split];
+        // Splitting the same connection gives the same node in every genome,
+        // unless this genome already has it.
+        let node = match self.splits.get(&old.innovation) {
+            Some(&node) if genome.node_index(node).is_none() => node,
+            Some(_) => self.new_node(),
+            None => {
+                let node = self.new_node();
+                self.splits.insert(old.innovation, node);
+                node
+            }
+        };
+        let position = genome.nodes.partition_point(|&(id, _)| id < node);
+        genome.nodes.insert(position, (node, NodeKind::Hidden));
+        for (from, to, weight) in [(old.from, node, 1.0), (node, old.to, old.weight)] {
+            let innovation = self.innovation(from, to);
+            insert_connection(
+                genome,
+                Connection {
+                    innovation,
+                    from,
+                    to,
+                    weight,
+                    enabled: true,
+                },
+            );
+        }
+    }
+
+    fn innovation(&mut self, from: u32, to: u32) -> u32 {
+        *self.innovations.entry((from, to)).or_insert_with(|| {
+            self.next_innovation += 1;
+            self.next_innovation - 1
+        })
+    }
+
+    fn new_node(&mut self) -> u32 {
+        self.next_node += 1;
+        self.next_node - 1
+    }
+}
+
+fn insert_connection(genome: &mut Genome, connection: Connection) {
+    let position = genome
+        .connections
+        .partition_point(|c| c.innovation < connection.innovation);
+    genome.connections.insert(position, connection);
+}
+
+/// A child with the structure of the fitter parent, taking each gene the parents
+/// share from either one at random.
+fn crossover(a: &Genome, b: &Genome, rng: &mut Rng) -> Genome {
+    let (fitter, other) = if b.fitness > a.fitness {
+        (b, a)
+    } else {
+        (a, b)
+    };
+    let mut child = fitter.clone();
+    for c in &mut child.connections {
+        if let Some(shared) = other.connection(c.innovation) {
+            if rng.chance(0.5) {
+                c.weight = shared.weight;
+            }
+            // A gene disabled in either parent usually stays disabled.
+            if !c.enabled || !shared.enabled {
+                c.enabled = !rng.chance(0.75);
+            }
+        }
+    }
+    child
+}
+
+fn config_json(config: &NeatConfig) -> Json {
+    let fields = [
+        ("population_size", config.population_size as f32),
+        ("compatibility_threshold", config.compatibility_threshold),
+        ("excess_coefficient", config.excess_coefficient),
+        ("disjoint_coefficient", config.disjoint_coefficient),
+        ("weight_coefficient", config.weight_coefficient),
+        ("weight_mutation_rate", config.weight_mutation_rate),
+        ("weight_replace_rate", config.weight_replace_rate),
+        ("weight_perturbation", config.weight_perturbation),
+        ("add_connection_rate", config.add_connection_rate),
+        ("add_node_rate", config.add_node_rate),
+        ("toggle_rate", config.toggle_rate),
+        ("crossover_rate", config.crossover_rate),
+        ("survival_threshold", config.survival_threshold),
+        ("elitism", config.elitism as f32),
+        ("stagnation_limit", config.stagnation_limit as f32),
+    ];
+    Json::Object(
+        fields
+            .into_iter()
+            .map(|(key, value)| (key.to_string(), Json::from_f32(value)))
+            .collect(),
+    )
+}
+
+/// Reads the fields of `config_json`, keeping defaults for missing ones.
+fn parse_config(json: &Json) -> Result<NeatConfig, Error> {
+    let mut config = NeatConfig::new();
+    let read = |key: &str, value: &mut f32| {
+        if let Some(number) = json.get(key).and_then(Json::as_f64) {
+            *value = number as f32;
+        }
+    };
+    let mut population_size = config.population_size as f32;
+    let mut elitism = config.elitism as f32;
+    let mut stagnation_limit = config.stagnation_limit as f32;
+    read("population_size", &mut population_size);
+    read(
+        "compatibility_threshold",
+        &mut config.compatibility_threshold,
+    );
+    read("excess_coefficient", &mut config.excess_coefficient);
+    read("disjoint_coefficient", &mut config.disjoint_coefficient);
+    read("weight_coefficient", &mut config.weight_coefficient);
+    read("weight_mutation_rate", &mut config.weight_mutation_rate);
+    read("weight_replace_rate", &mut config.weight_replace_rate);
+    read("weight_perturbation", &mut config.weight_perturbation);
+    read("add_connection_rate", &mut config.add_connection_rate);
+    read("add_node_rate", &mut config.add_node_rate);
+    read("toggle_rate", &mut config.toggle_rate);
+    read("crossover_rate", &mut config.crossover_rate);
+    read("survival_threshold", &mut config.survival_threshold);
+    read("elitism", &mut elitism);
+    read("stagnation_limit", &mut stagnation_limit);
+    config.population_size = population_size as u32;
+    config.elitism = elitism as u32;
+    config.stagnation_limit = stagnation_limit as u32;
+    if config.population_size == 0 {
+        return Err(Error::InvalidInput(
+            "population size must be positive".into(),
+        ));
+    }
+    Ok(config)
+}
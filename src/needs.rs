@@ -0,0 +1,408 @@
+use std::collections::HashMap;
+
+use wasm_bindgen::prelude::*;
+
+use crate::blackboard::Blackboard;
+use crate::curves::Curve;
+use crate::error::Error;
+use crate::math::Vec2;
+
+#[derive(Clone, Debug)]
+struct Need {
+    name: String,
+    /// Level lost per second.
+    decay_rate: f32,
+    /// Maps the deficit, 1 minus the level, to how pressing the need is.
+    curve: Curve,
+    /// GOAP fact bit that is set while the need is met.
+    fact: Option<u32>,
+}
+
+#[derive(Clone, Debug)]
+struct Advertisement {
+    object: u32,
+    activity: String,
+    position: Vec2,
+    /// Seconds the activity takes to give its whole reward.
+    duration: f32,
+    /// Level each need gains from the whole activity, by need index.
+    rewards: Vec<(usize, f32)>,
+    available: bool,
+}
+
+#[derive(Clone, Debug, Default)]
+struct Agent {
+    levels: Vec<f32>,
+    /// Per-need multipliers of the decay rate, e.g. for a glutton.
+    decay_scales: Vec<f32>,
+}
+
+/// Sims-style motives: every agent has a level from 0, desperate, to 1,
+/// content, for each need such as hunger, energy or social, which decays over
+/// time, and objects advertise activities that restore needs. An agent picks
+/// the activity that most relieves its needs, discounted by how far away it
+/// is, so behavior follows from whatever is most pressing.
+///
+/// How pressing a need is comes from its curve applied to the deficit, 1
+/// minus the level, so a steep curve leaves a need ignored until it runs
+/// low. For the utility layer, `write_blackboard` stores the levels and
+/// urgencies for `UtilityBrain::evaluate_blackboard` and expressions; for
+/// GOAP, needs given a fact bit make up `world_state`, and `pressing_goal`
+/// names the bit to plan for.
+#[wasm_bindgen]
+#[derive(Clone, Debug)]
+pub struct Needs {
+    needs: Vec<Need>,
+    advertisements: Vec<Option<Advertisement>>,
+    agents: HashMap<u32, Agent>,
+    /// How much each unit of distance divides an activity's score by, as
+    /// `score / (1 + distance * distance_penalty)`.
+    pub distance_penalty: f32,
+    /// Level a need must reach to count as met in `world_state`.
+    pub met_level: f32,
+}
+
+impl Default for Needs {
+    fn default() -> Needs {
+        Needs {
+            needs: Vec::new(),
+            advertisements: Vec::new(),
+            agents: HashMap::new(),
+            distance_penalty: 0.1,
+            met_level: 0.5,
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl Needs {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Needs {
+        Needs::default()
+    }
+
+    /// Hunger, energy and social, emptying in about 8, 16 and 12 minutes,
+    /// each growing pressing faster the lower it runs.
+    pub fn standard() -> Needs {
+        let mut needs = Needs::default();
+        let curve = Curve::quadratic(1.0, 2.0, 0.0, 0.0);
+        needs.add_need("hunger", 1.0 / 480.0, &curve);
+        needs.add_need("energy", 1.0 / 960.0, &curve);
+        needs.add_need("social", 1.0 / 720.0, &curve);
+        needs
+    }
+
+    /// Adds a need decaying by `decay_rate` a second, with `curve` mapping
+    /// its deficit to its urgency, and returns its index. Agents start with
+    /// it full.
+    pub fn add_need(&mut self, name: &str, decay_rate: f32, curve: &Curve) -> u32 {
+        self.needs.push(Need {
+            name: name.to_string(),
+            decay_rate,
+            curve: curve.clone(),
+            fact: None,
+        });
+        self.needs.len() as u32 - 1
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn need_count(&self) -> u32 {
+        self.needs.len() as u32
+    }
+
+    pub fn need_name(&self, need: u32) -> Option<String> {
+        self.needs.get(need as usize).map(|need| need.name.clone())
+    }
+
+    pub fn find_need(&self, name: &str) -> Option<u32> {
+        self.needs
+            .iter()
+            .position(|need| need.name == name)
+            .map(|index| index as u32)
+    }
+
+    /// Makes GOAP fact `bit` of `world_state` stand for `need` being met.
+    pub fn set_fact(&mut self, need: u32, bit: u32) -> Result<(), Error> {
+        if bit >= 32 {
+            return Err(Error::InvalidInput(format!(
+                "fact bit {} is out of range",
+                bit
+            )));
+        }
+        self.need_mut(need)?.fact = Some(bit);
+        Ok(())
+    }
+
+    pub fn set_level(&mut self, agent: u32, need: u32, level: f32) -> Result<(), Error> {
+        let need = self.check(need)?;
+        self.agent(agent).levels[need] = level.clamp(0.0, 1.0);
+        Ok(())
+    }
+
+    /// `agent`'s level of `need`, 1 for agents not seen yet.
+    pub fn level(&self, agent: u32, need: u32) -> f32 {
+        self.agents
+            .get(&agent)
+            .and_then(|state| state.levels.get(need as usize))
+            .copied()
+            .unwrap_or(1.0)
+    }
+
+    /// Every level of `agent`, by need index.
+    pub fn levels(&self, agent: u32) -> Vec<f32> {
+        (0..self.needs.len() as u32)
+            .map(|need| self.level(agent, need))
+            .collect()
+    }
+
+    /// Makes `need` decay `scale` times as fast for `agent`.
+    pub fn set_decay_scale(&mut self, agent: u32, need: u32, scale: f32) -> Result<(), Error> {
+        let need = self.check(need)?;
+        self.agent(agent).decay_scales[need] = scale.max(0.0);
+        Ok(())
+    }
+
+    /// How pressing `need` is for `agent`, from its curve.
+    pub fn urgency(&self, agent: u32, need: u32) -> f32 {
+        self.needs.get(need as usize).map_or(0.0, |definition| {
+            definition.curve.evaluate(1.0 - self.level(agent, need))
+        })
+    }
+
+    /// `agent`'s most pressing need, if any is pressing at all.
+    pub fn most_pressing(&self, agent: u32) -> Option<u32> {
+        (0..self.needs.len() as u32)
+            .map(|need| (need, self.urgency(agent, need)))
+            .filter(|&(_, urgency)| urgency > 0.0)
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(need, _)| need)
+    }
+
+    /// Forgets `agent`.
+    pub fn remove_agent(&mut self, agent: u32) -> bool {
+        self.agents.remove(&agent).is_some()
+    }
+
+    /// Advertises `activity` at object `object`, standing at `(x, y)` and
+    /// taking `duration` seconds, and returns the advertisement's id. Give it
+    /// rewards with `set_reward`.
+    pub fn advertise(&mut self, object: u32, activity: &str, x: f32, y: f32, duration: f32) -> u32 {
+        self.advertisements.push(Some(Advertisement {
+            object,
+            activity: activity.to_string(),
+            position: Vec2::new(x, y),
+            duration: duration.max(0.0),
+            rewards: Vec::new(),
+            available: true,
+        }));
+        self.advertisements.len() as u32 - 1
+    }
+
+    /// Sets how much the whole of advertisement `ad` raises `need`, or
+    /// lowers it when negative, e.g. exercise costing energy.
+    pub fn set_reward(&mut self, ad: u32, need: u32, amount: f32) -> Result<(), Error> {
+        let need = self.check(need)?;
+        let ad = self.advertisement_mut(ad)?;
+        match ad.rewards.iter_mut().find(|(index, _)| *index == need) {
+            Some(reward) => reward.1 = amount,
+            None => ad.rewards.push((need, amount)),
+        }
+        Ok(())
+    }
+
+    /// Takes advertisement `ad` out of the running while unavailable, e.g.
+    /// while someone else uses the object.
+    pub fn set_available(&mut self, ad: u32, available: bool) -> Result<(), Error> {
+        self.advertisement_mut(ad)?.available = available;
+        Ok(())
+    }
+
+    /// Withdraws every advertisement of `object`, returning how many there
+    /// were.
+    pub fn remove_object(&mut self, object: u32) -> u32 {
+        let mut removed = 0;
+        for slot in &mut self.advertisements {
+            if slot.as_ref().is_some_and(|ad| ad.object == object) {
+                *slot = None;
+                removed += 1;
+            }
+        }
+        removed
+    }
+
+    pub fn activity_name(&self, ad: u32) -> Option<String> {
+        self.advertisement(ad).map(|ad| ad.activity.clone())
+    }
+
+    /// The object advertising `ad`.
+    pub fn advertiser(&self, ad: u32) -> Option<u32> {
+        self.advertisement(ad).map(|ad| ad.object)
+    }
+
+    /// Where an agent goes to take up `ad`.
+    pub fn advertisement_position(&self, ad: u32) -> Option<Vec2> {
+        self.advertisement(ad).map(|ad| ad.position)
+    }
+
+    /// How much `ad` appeals to `agent` standing at `(x, y)`: the urgency its
+    /// rewards would take away, divided down with distance.
+    pub fn score(&self, agent: u32, ad: u32, x: f32, y: f32) -> f32 {
+        self.advertisement(ad)
+            .map_or(0.0, |ad| self.appeal(agent, ad, Vec2::new(x, y)))
+    }
+
+    /// The available advertisement that best relieves `agent`'s needs from
+    /// `(x, y)`, if any would help.
+    pub fn choose(&self, agent: u32, x: f32, y: f32) -> Option<u32> {
+        self.rank(agent, x, y, 1).first().copied()
+    }
+
+    /// The `count` available advertisements that best relieve `agent`'s
+    /// needs from `(x, y)`, best first, leaving out any that would not help,
+    /// e.g. to pick among the top few at random for variety.
+    pub fn rank(&self, agent: u32, x: f32, y: f32, count: u32) -> Vec<u32> {
+        let position = Vec2::new(x, y);
+        let mut scored: Vec<(f32, u32)> = self
+            .advertisements
+            .iter()
+            .enumerate()
+            .filter_map(|(id, ad)| {
+                let ad = ad.as_ref().filter(|ad| ad.available)?;
+                let score = self.appeal(agent, ad, position);
+                (score > 0.0).then_some((score, id as u32))
+            })
+            .collect();
+        scored.sort_by(|a, b| b.0.total_cmp(&a.0).then(a.1.cmp(&b.1)));
+        scored
+            .into_iter()
+            .take(count as usize)
+            .map(|(_, id)| id)
+            .collect()
+    }
+
+    /// Gives `agent` the share of `ad`'s rewards for `dt` seconds of it, or
+    /// all of them for an activity without duration.
+    pub fn perform(&mut self, agent: u32, ad: u32, dt: f32) -> Result<(), Error> {
+        let ad = self
+            .advertisement(ad)
+            .ok_or_else(|| Error::InvalidInput(format!("unknown advertisement {}", ad)))?;
+        let share = if ad.duration > 0.0 {
+            (dt / ad.duration).clamp(0.0, 1.0)
+        } else {
+            1.0
+        };
+        let rewards = ad.rewards.clone();
+        let levels = &mut self.agent(agent).levels;
+        for (need, amount) in rewards {
+            levels[need] = (levels[need] + amount * share).clamp(0.0, 1.0);
+        }
+        Ok(())
+    }
+
+    /// Decays every need of every agent by `dt` seconds.
+    pub fn update(&mut self, dt: f32) {
+        let count = self.needs.len();
+        for state in self.agents.values_mut() {
+            state.levels.resize(count, 1.0);
+            state.decay_scales.resize(count, 1.0);
+            for (need, definition) in self.needs.iter().enumerate() {
+                let decay = definition.decay_rate * state.decay_scales[need] * dt;
+                state.levels[need] = (state.levels[need] - decay).clamp(0.0, 1.0);
+            }
+        }
+    }
+
+    /// Stores `agent`'s level of every need under its name, e.g. `"hunger"`,
+    /// and its urgency under the name with `_urgency` appended.
+    pub fn write_blackboard(&self, agent: u32, blackboard: &mut Blackboard) {
+        for (need, definition) in self.needs.iter().enumerate() {
+            let need = need as u32;
+            blackboard.set_f64(&definition.name, self.level(agent, need) as f64);
+            blackboard.set_f64(
+                &format!("{}_urgency", definition.name),
+                self.urgency(agent, need) as f64,
+            );
+        }
+    }
+
+    /// GOAP world state with the fact bit of every need `agent` has at
+    /// `met_level` or above set.
+    pub fn world_state(&self, agent: u32) -> u32 {
+        self.needs
+            .iter()
+            .enumerate()
+            .filter_map(|(need, definition)| {
+                let bit = definition.fact?;
+                (self.level(agent, need as u32) >= self.met_level).then_some(1 << bit)
+            })
+            .fold(0, |state, bit| state | bit)
+    }
+
+    /// The fact bit, as a mask, of `agent`'s most pressing need that has one,
+    /// to plan for with `Planner::plan(state, goal, goal)`; 0 when none is
+    /// pressing.
+    pub fn pressing_goal(&self, agent: u32) -> u32 {
+        self.needs
+            .iter()
+            .enumerate()
+            .filter_map(|(need, definition)| {
+                let urgency = self.urgency(agent, need as u32);
+                definition
+                    .fact
+                    .filter(|_| urgency > 0.0)
+                    .map(|bit| (urgency, bit))
+            })
+            .max_by(|a, b| a.0.total_cmp(&b.0))
+            .map_or(0, |(_, bit)| 1 << bit)
+    }
+}
+
+impl Needs {
+    fn appeal(&self, agent: u32, ad: &Advertisement, position: Vec2) -> f32 {
+        let relief: f32 = ad
+            .rewards
+            .iter()
+            .map(|&(need, amount)| {
+                let level = self.level(agent, need as u32);
+                let after = (level + amount).clamp(0.0, 1.0);
+                let curve = &self.needs[need].curve;
+                curve.evaluate(1.0 - level) - curve.evaluate(1.0 - after)
+            })
+            .sum();
+        relief / (1.0 + position.distance(ad.position) * self.distance_penalty.max(0.0))
+    }
+
+    /// `agent`'s state, made with every need full when new.
+    fn agent(&mut self, agent: u32) -> &mut Agent {
+        let count = self.needs.len();
+        let state = self.agents.entry(agent).or_default();
+        state.levels.resize(count, 1.0);
+        state.decay_scales.resize(count, 1.0);
+        state
+    }
+
+    fn check(&self, need: u32) -> Result<usize, Error> {
+        if (need as usize) < self.needs.len() {
+            Ok(need as usize)
+        } else {
+            Err(Error::InvalidInput(format!("unknown need {}", need)))
+        }
+    }
+
+    fn need_mut(&mut self, need: u32) -> Result<&mut Need, Error> {
+        self.needs
+            .get_mut(need as usize)
+            .ok_or_else(|| Error::InvalidInput(format!("unknown need {}", need)))
+    }
+
+    fn advertisement(&self, ad: u32) -> Option<&Advertisement> {
+        self.advertisements.get(ad as usize)?.as_ref()
+    }
+
+    fn advertisement_mut(&mut self, ad: u32) -> Result<&mut Advertisement, Error> {
+        self.advertisements
+            .get_mut(ad as usize)
+            .and_then(Option::as_mut)
+            .ok_or_else(|| Error::InvalidInput(format!("unknown advertisement {}", ad)))
+    }
+}
@@ -0,0 +1,360 @@
+use js_sys::{Array, Function, Object, Reflect, JSON};
+use wasm_bindgen::prelude::*;
+
+use crate::clock;
+use crate::error::Error;
+use crate::mcts::{Game, JsGame};
+
+/// Magnitude of a won position, less one per ply so that faster wins score higher.
+const WIN: f32 = 1.0e6;
+
+/// A two-player zero-sum `Game` that `Negamax` can search.
+pub trait Position: Game {
+    /// Identifies the state, including whose move it is, in the transposition table.
+    fn key(&self, state: &Self::State) -> u64;
+
+    /// Static evaluation from the view of the player to move; higher is better.
+    fn evaluate(&self, state: &Self::State) -> f32;
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Bound {
+    Exact,
+    Lower,
+    Upper,
+}
+
+#[derive(Clone, Copy)]
+struct Entry {
+    key: u64,
+    depth: u32,
+    score: f32,
+    bound: Bound,
+    best: Option<u32>,
+}
+
+/// Alpha-beta negamax search for perfect-information two-player games, with
+/// iterative deepening and a transposition table. Deepens one ply at a time until
+/// `max_depth` or `time_limit_ms`, and plays the best move of the deepest
+/// finished iteration. Players may move several times in a row.
+///
+/// From JS, `search` takes the same game object as `Mcts`, where `score` rates
+/// finished games. Two functions are optional: `evaluate(state)` for the player
+/// to move, and `key(state)` returning a number or string that identifies the
+/// state. Without `evaluate` the state must be an array of piece codes, scored by
+/// material from `set_piece_values`; without `key` states are identified by their
+/// JSON.
+#[wasm_bindgen]
+pub struct Negamax {
+    pub max_depth: u32,
+    /// Wall-clock budget per search in milliseconds, 0 for none.
+    pub time_limit_ms: f64,
+    table: Vec<Option<Entry>>,
+    table_size: usize,
+    piece_values: Vec<f32>,
+    deadline: Option<f64>,
+    aborted: bool,
+    nodes: u32,
+    last_depth: u32,
+    last_score: f32,
+}
+
+#[wasm_bindgen]
+impl Negamax {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Negamax {
+        Negamax {
+            max_depth: 8,
+            time_limit_ms: 0.0,
+            table: Vec::new(),
+            table_size: 1 << 16,
+            piece_values: vec![0.0, 1.0, 3.0, 3.0, 5.0, 9.0, 0.0],
+            deadline: None,
+            aborted: false,
+            nodes: 0,
+            last_depth: 0,
+            last_score: 0.0,
+        }
+    }
+
+    /// Resizes the transposition table to `entries` slots, clearing it.
+    pub fn set_table_size(&mut self, entries: u32) {
+        self.table_size = entries.max(1) as usize;
+        self.table.clear();
+    }
+
+    /// Forgets every stored position, e.g. when starting a new game.
+    pub fn clear_table(&mut self) {
+        self.table.clear();
+    }
+
+    /// Values of the pieces in a board for the built-in material evaluation,
+    /// indexed by piece code. Positive codes belong to player 0 and negative codes
+    /// to the other player; 0 is an empty square. Defaults to chess values for
+    /// pawn, knight, bishop, rook, queen and king as codes 1 to 6.
+    pub fn set_piece_values(&mut self, values: &[f32]) {
+        self.piece_values = values.to_vec();
+    }
+
+    /// Material balance of a board of piece codes for `player`.
+    pub fn material(&self, board: &[f32], player: u32) -> f32 {
+        material(&self.piece_values, board.iter().copied(), player)
+    }
+
+    /// Searches from `state` and returns the best move for the player to move, or
+    /// undefined when the game is over. Fails when a game function is missing or
+    /// throws.
+    pub fn search(&mut self, game: &Object, state: JsValue) -> Result<Option<u32>, Error> {
+        let optional = |name: &str| {
+            Reflect::get(game, &JsValue::from_str(name))
+                .ok()
+                .and_then(|value| value.dyn_into::<Function>().ok())
+        };
+        let position = JsPosition {
+            game: JsGame::new(game)?,
+            evaluate: optional("evaluate"),
+            key: optional("key"),
+            piece_values: self.piece_values.clone(),
+        };
+        let best = self.search_with(&position, &state);
+        match position.game.error.into_inner() {
+            Some(error) => Err(error),
+            None => Ok(best),
+        }
+    }
+
+    /// Depth of the deepest iteration the last search finished.
+    #[wasm_bindgen(getter)]
+    pub fn last_depth(&self) -> u32 {
+        self.last_depth
+    }
+
+    /// Score of the last search's move for the player who moves, where won and
+    /// lost games are about plus or minus a million.
+    #[wasm_bindgen(getter)]
+    pub fn last_score(&self) -> f32 {
+        self.last_score
+    }
+
+    /// Positions the last search visited.
+    #[wasm_bindgen(getter)]
+    pub fn nodes(&self) -> u32 {
+        self.nodes
+    }
+}
+
+impl Default for Negamax {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Negamax {
+    /// Searches a Rust-side game from `state`; see `search`.
+    pub fn search_with<P: Position>(&mut self, position: &P, state: &P::State) -> Option<u32> {
+        if self.table.len() != self.table_size {
+            self.table = vec![None; self.table_size];
+        }
+        self.deadline = (self.time_limit_ms > 0.0).then(|| clock::now_ms() + self.time_limit_ms);
+        self.aborted = false;
+        self.nodes = 0;
+        self.last_depth = 0;
+        self.last_score = 0.0;
+
+        let mut moves = Vec::new();
+        position.moves(state, &mut moves);
+        let mut best = moves.first().copied()?;
+        let key = position.key(state);
+        for depth in 1..=self.max_depth.max(1) {
+            let Some(score) =
+                self.negamax(position, state, depth, 0, -f32::INFINITY, f32::INFINITY)
+            else {
+                break;
+            };
+            if let Some(action) = self.probe(key).and_then(|entry| entry.best) {
+                best = action;
+            }
+            self.last_depth = depth;
+            self.last_score = score;
+            if score.abs() >= WIN - depth as f32 {
+                // A forced result: searching deeper finds nothing new.
+                break;
+            }
+        }
+        Some(best)
+    }
+
+    /// Score of `state` for the player to move, searching `depth` more plies, or
+    /// `None` once the time limit aborts the search.
+    fn negamax<P: Position>(
+        &mut self,
+        position: &P,
+        state: &P::State,
+        depth: u32,
+        ply: u32,
+        mut alpha: f32,
+        beta: f32,
+    ) -> Option<f32> {
+        self.nodes += 1;
+        if self.nodes.is_multiple_of(1024)
+            && self
+                .deadline
+                .is_some_and(|deadline| clock::now_ms() >= deadline)
+        {
+            self.aborted = true;
+        }
+        if self.aborted {
+            return None;
+        }
+
+        let key = position.key(state);
+        let entry = self.probe(key);
+        // The root always searches, so that it reports a move for this depth.
+        if let Some(entry) = entry.filter(|entry| ply > 0 && entry.depth >= depth) {
+            let cutoff = match entry.bound {
+                Bound::Exact => true,
+                Bound::Lower => entry.score >= beta,
+                Bound::Upper => entry.score <= alpha,
+            };
+            if cutoff {
+                return Some(entry.score);
+            }
+        }
+
+        let mut moves = Vec::new();
+        position.moves(state, &mut moves);
+        let player = position.player(state);
+        if moves.is_empty() {
+            return Some((position.score(state, player) - 0.5) * 2.0 * (WIN - ply as f32));
+        }
+        if depth == 0 {
+            return Some(position.evaluate(state));
+        }
+        // Try the best move from an earlier, shallower search first.
+        if let Some(first) = entry.and_then(|entry| entry.best) {
+            if let Some(index) = moves.iter().position(|&action| action == first) {
+                moves.swap(0, index);
+            }
+        }
+
+        let original_alpha = alpha;
+        let mut best_score = -f32::INFINITY;
+        let mut best = None;
+        for action in moves {
+            let next = position.play(state, action);
+            let score = if position.player(&next) == player {
+                self.negamax(position, &next, depth - 1, ply + 1, alpha, beta)?
+            } else {
+                -self.negamax(position, &next, depth - 1, ply + 1, -beta, -alpha)?
+            };
+            if score > best_score {
+                best_score = score;
+                best = Some(action);
+            }
+            alpha = alpha.max(score);
+            if alpha >= beta {
+                break;
+            }
+        }
+
+        let bound = if best_score <= original_alpha {
+            Bound::Upper
+        } else if best_score >= beta {
+            Bound::Lower
+        } else {
+            Bound::Exact
+        };
+        let slot = key as usize % self.table.len();
+        self.table[slot] = Some(Entry {
+            key,
+            depth,
+            score: best_score,
+            bound,
+            best,
+        });
+        Some(best_score)
+    }
+
+    fn probe(&self, key: u64) -> Option<Entry> {
+        self.table[key as usize % self.table.len()].filter(|entry| entry.key == key)
+    }
+}
+
+/// A `Position` backed by a JS game object, falling back to material and JSON
+/// keys where the object has no `evaluate` or `key`.
+struct JsPosition {
+    game: JsGame,
+    evaluate: Option<Function>,
+    key: Option<Function>,
+    piece_values: Vec<f32>,
+}
+
+impl Game for JsPosition {
+    type State = JsValue;
+
+    fn player(&self, state: &JsValue) -> u32 {
+        self.game.player(state)
+    }
+
+    fn moves(&self, state: &JsValue, out: &mut Vec<u32>) {
+        self.game.moves(state, out)
+    }
+
+    fn play(&self, state: &JsValue, action: u32) -> JsValue {
+        self.game.play(state, action)
+    }
+
+    fn score(&self, state: &JsValue, player: u32) -> f32 {
+        self.game.score(state, player)
+    }
+}
+
+impl Position for JsPosition {
+    fn key(&self, state: &JsValue) -> u64 {
+        let key = match &self.key {
+            Some(key) => self.game.check("key", key.call1(&JsValue::NULL, state)),
+            None => self
+                .game
+                .check("key", JSON::stringify(state).map(JsValue::from)),
+        };
+        let Some(key) = key else {
+            return 0;
+        };
+        if let Some(number) = key.as_f64() {
+            return number.to_bits();
+        }
+        // FNV-1a over the string form.
+        let text = key.as_string().unwrap_or_default();
+        text.bytes().fold(0xcbf2_9ce4_8422_2325, |hash: u64, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+        })
+    }
+
+    fn evaluate(&self, state: &JsValue) -> f32 {
+        if let Some(evaluate) = &self.evaluate {
+            return self
+                .game
+                .check("evaluate", evaluate.call1(&JsValue::NULL, state))
+                .and_then(|value| value.as_f64())
+                .unwrap_or(0.0) as f32;
+        }
+        let codes = Array::from(state)
+            .iter()
+            .filter_map(|code| code.as_f64())
+            .map(|code| code as f32)
+            .collect::<Vec<_>>();
+        material(&self.piece_values, codes, self.player(state))
+    }
+}
+
+fn material(values: &[f32], board: impl IntoIterator<Item = f32>, player: u32) -> f32 {
+    let balance: f32 = board
+        .into_iter()
+        .map(|code| values.get(code.abs() as usize).copied().unwrap_or(0.0) * code.signum())
+        .sum();
+    if player == 0 {
+        balance
+    } else {
+        -balance
+    }
+}
@@ -0,0 +1,215 @@
+use wasm_bindgen::prelude::*;
+
+use crate::random::Rng;
+
+/// The 12 cube edge directions of Perlin's improved noise.
+const GRADIENTS_3D: [[f32; 3]; 12] = [
+    [1.0, 1.0, 0.0],
+    [-1.0, 1.0, 0.0],
+    [1.0, -1.0, 0.0],
+    [-1.0, -1.0, 0.0],
+    [1.0, 0.0, 1.0],
+    [-1.0, 0.0, 1.0],
+    [1.0, 0.0, -1.0],
+    [-1.0, 0.0, -1.0],
+    [0.0, 1.0, 1.0],
+    [0.0, -1.0, 1.0],
+    [0.0, 1.0, -1.0],
+    [0.0, -1.0, -1.0],
+];
+
+const GRADIENTS_2D: [[f32; 2]; 8] = [
+    [1.0, 1.0],
+    [-1.0, 1.0],
+    [1.0, -1.0],
+    [-1.0, -1.0],
+    [1.0, 0.0],
+    [-1.0, 0.0],
+    [0.0, 1.0],
+    [0.0, -1.0],
+];
+
+/// Seeded gradient noise: smooth pseudo-random values in `[-1, 1]` that vary
+/// continuously with the input, e.g. for idle sway, wandering that meanders
+/// rather than jitters, or varying an agent's speed over time. The same seed
+/// always gives the same field.
+///
+/// `perlin*` repeat every `period` units along each axis when `period` is set,
+/// so the field tiles seamlessly, e.g. over a looping animation or a wrapped
+/// map; `simplex2` is cheaper and shows fewer axis-aligned artifacts but never
+/// tiles.
+#[wasm_bindgen]
+#[derive(Clone, Debug)]
+pub struct Noise {
+    permutation: Vec<u8>,
+    /// Lattice cells after which the Perlin field repeats, or 0 for none.
+    pub period: u32,
+}
+
+#[wasm_bindgen]
+impl Noise {
+    #[wasm_bindgen(constructor)]
+    pub fn new(seed: u64) -> Noise {
+        let mut rng = Rng::new(seed);
+        let mut permutation: Vec<u8> = (0..=255).collect();
+        for i in (1..permutation.len()).rev() {
+            permutation.swap(i, rng.below(i + 1));
+        }
+        Noise {
+            permutation,
+            period: 0,
+        }
+    }
+
+    /// A field that repeats every `period` units along each axis.
+    pub fn tileable(seed: u64, period: u32) -> Noise {
+        Noise {
+            period,
+            ..Noise::new(seed)
+        }
+    }
+
+    /// 1D Perlin noise.
+    pub fn perlin1(&self, x: f32) -> f32 {
+        let (i, fx) = split(x);
+        let gradient = |i: i32| self.hash([i]) as f32 / 127.5 - 1.0;
+        let a = gradient(i) * fx;
+        let b = gradient(i + 1) * (fx - 1.0);
+        (lerp(a, b, fade(fx)) * 2.0).clamp(-1.0, 1.0)
+    }
+
+    /// 2D Perlin noise.
+    pub fn perlin2(&self, x: f32, y: f32) -> f32 {
+        let (ix, fx) = split(x);
+        let (iy, fy) = split(y);
+        let corner = |dx: i32, dy: i32| {
+            let [gx, gy] = GRADIENTS_2D[self.hash([ix + dx, iy + dy]) as usize % 8];
+            gx * (fx - dx as f32) + gy * (fy - dy as f32)
+        };
+        let (u, v) = (fade(fx), fade(fy));
+        let bottom = lerp(corner(0, 0), corner(1, 0), u);
+        let top = lerp(corner(0, 1), corner(1, 1), u);
+        lerp(bottom, top, v).clamp(-1.0, 1.0)
+    }
+
+    /// 3D Perlin noise, e.g. a 2D field that changes over time.
+    pub fn perlin3(&self, x: f32, y: f32, z: f32) -> f32 {
+        let (ix, fx) = split(x);
+        let (iy, fy) = split(y);
+        let (iz, fz) = split(z);
+        let corner = |dx: i32, dy: i32, dz: i32| {
+            let [gx, gy, gz] = GRADIENTS_3D[self.hash([ix + dx, iy + dy, iz + dz]) as usize % 12];
+            gx * (fx - dx as f32) + gy * (fy - dy as f32) + gz * (fz - dz as f32)
+        };
+        let (u, v, w) = (fade(fx), fade(fy), fade(fz));
+        let near = lerp(
+            lerp(corner(0, 0, 0), corner(1, 0, 0), u),
+            lerp(corner(0, 1, 0), corner(1, 1, 0), u),
+            v,
+        );
+        let far = lerp(
+            lerp(corner(0, 0, 1), corner(1, 0, 1), u),
+            lerp(corner(0, 1, 1), corner(1, 1, 1), u),
+            v,
+        );
+        lerp(near, far, w).clamp(-1.0, 1.0)
+    }
+
+    /// 2D simplex noise, which ignores `period`.
+    pub fn simplex2(&self, x: f32, y: f32) -> f32 {
+        let skew = (3.0f32.sqrt() - 1.0) * 0.5;
+        let unskew = (3.0 - 3.0f32.sqrt()) / 6.0;
+        let s = (x + y) * skew;
+        let (i, j) = ((x + s).floor() as i32, (y + s).floor() as i32);
+        let t = (i + j) as f32 * unskew;
+        let (x0, y0) = (x - (i as f32 - t), y - (j as f32 - t));
+        let (di, dj) = if x0 > y0 { (1, 0) } else { (0, 1) };
+        let corners = [
+            (0, 0, x0, y0),
+            (di, dj, x0 - di as f32 + unskew, y0 - dj as f32 + unskew),
+            (1, 1, x0 - 1.0 + 2.0 * unskew, y0 - 1.0 + 2.0 * unskew),
+        ];
+        let total: f32 = corners
+            .iter()
+            .map(|&(ci, cj, cx, cy)| {
+                let falloff = 0.5 - cx * cx - cy * cy;
+                if falloff <= 0.0 {
+                    return 0.0;
+                }
+                let [gx, gy] = GRADIENTS_2D[self.lattice([i + ci, j + cj]) as usize % 8];
+                falloff.powi(4) * (gx * cx + gy * cy)
+            })
+            .sum();
+        (total * 70.0).clamp(-1.0, 1.0)
+    }
+
+    /// Fractal Brownian motion over `perlin2`: `octaves` layers, each at
+    /// `lacunarity` times the frequency and `gain` times the amplitude of the
+    /// last, normalized to `[-1, 1]`. Still tiles when `lacunarity` is a whole
+    /// number.
+    pub fn fractal2(&self, x: f32, y: f32, octaves: u32, lacunarity: f32, gain: f32) -> f32 {
+        fractal(octaves, lacunarity, gain, |scale| {
+            self.perlin2(x * scale, y * scale)
+        })
+    }
+
+    /// Fractal Brownian motion over `perlin3`, as in `fractal2`.
+    pub fn fractal3(
+        &self,
+        x: f32,
+        y: f32,
+        z: f32,
+        octaves: u32,
+        lacunarity: f32,
+        gain: f32,
+    ) -> f32 {
+        fractal(octaves, lacunarity, gain, |scale| {
+            self.perlin3(x * scale, y * scale, z * scale)
+        })
+    }
+}
+
+impl Noise {
+    /// Hashes lattice coordinates, wrapped by `period` so the field tiles.
+    fn hash<const N: usize>(&self, coordinates: [i32; N]) -> u8 {
+        let period = self.period as i32;
+        self.lattice(coordinates.map(|c| if period > 0 { c.rem_euclid(period) } else { c }))
+    }
+
+    fn lattice<const N: usize>(&self, coordinates: [i32; N]) -> u8 {
+        coordinates.iter().fold(0u8, |hash, &c| {
+            self.permutation[(hash as i32 + c).rem_euclid(256) as usize]
+        })
+    }
+}
+
+/// Lattice cell and offset within it.
+fn split(x: f32) -> (i32, f32) {
+    let floor = x.floor();
+    (floor as i32, x - floor)
+}
+
+/// Perlin's quintic ease, `6t^5 - 15t^4 + 10t^3`.
+fn fade(t: f32) -> f32 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
+
+fn fractal(octaves: u32, lacunarity: f32, gain: f32, layer: impl Fn(f32) -> f32) -> f32 {
+    let (mut scale, mut amplitude) = (1.0, 1.0);
+    let (mut total, mut weight) = (0.0, 0.0);
+    for _ in 0..octaves.max(1) {
+        total += layer(scale) * amplitude;
+        weight += amplitude;
+        scale *= lacunarity;
+        amplitude *= gain;
+    }
+    if weight > 0.0 {
+        (total / weight).clamp(-1.0, 1.0)
+    } else {
+        0.0
+    }
+}
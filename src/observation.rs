@@ -0,0 +1,436 @@
+#[cfg(target_arch = "wasm32")]
+use js_sys::Float32Array;
+use wasm_bindgen::prelude::*;
+
+use crate::error::Error;
+use crate::grid::Grid;
+use crate::math::Vec2;
+use crate::spatial_hash::SpatialHash;
+
+/// Floats per agent in the `states` of `encode_batch`.
+const STATE_STRIDE: usize = 7;
+
+#[derive(Clone, Copy, Debug)]
+enum Feature {
+    /// Velocity along and left of the facing, over `max_speed`.
+    Velocity {
+        max_speed: f32,
+    },
+    /// The unit facing in world space.
+    Facing,
+    Health,
+    /// The `count` nearest entities within `range`, nearest first, each as a
+    /// presence flag, its offset along and left of the facing over `range`
+    /// and, with `kinds` above 0, a one-hot of its kind.
+    Nearby {
+        count: u32,
+        range: f32,
+        kinds: u32,
+    },
+    /// `count` rays fanned evenly across `fov` around the facing, each the
+    /// distance to the first wall over `range`, 1 when clear.
+    Rays {
+        count: u32,
+        fov: f32,
+        range: f32,
+    },
+    /// `count` values of the game's own, in the order passed in.
+    Custom {
+        count: u32,
+    },
+}
+
+impl Feature {
+    fn size(self) -> usize {
+        match self {
+            Feature::Velocity { .. } | Feature::Facing => 2,
+            Feature::Health => 1,
+            Feature::Nearby { count, kinds, .. } => count as usize * (3 + kinds as usize),
+            Feature::Rays { count, .. } | Feature::Custom { count } => count as usize,
+        }
+    }
+}
+
+/// One agent to encode, as `encode` and `encode_batch` receive it.
+struct AgentState {
+    id: u32,
+    position: Vec2,
+    facing: Vec2,
+    velocity: Vec2,
+    health: f32,
+}
+
+/// Packs what an agent perceives into a fixed-size feature vector for a policy
+/// network, the same way every tick: its own velocity, facing and health, the
+/// nearest entities around it, ray distances to walls and values of the
+/// game's own, each in the agent's frame and scaled to about `[-1, 1]`.
+///
+/// Features are laid out in the order they are added, so the encoder doubles
+/// as the schema shared with a training pipeline; `feature_offsets` gives
+/// where each starts. Feed it the entities with `set_entities` and the walls
+/// with `set_grid` each tick, then `encode` one agent or `encode_batch` many
+/// into a `[agents, size]` tensor for `Mlp::forward` or `Model::run`.
+#[wasm_bindgen]
+pub struct ObservationEncoder {
+    features: Vec<Feature>,
+    size: usize,
+    grid: Option<Grid>,
+    entity_ids: Vec<u32>,
+    entity_positions: Vec<Vec2>,
+    entity_kinds: Vec<u32>,
+    entities: SpatialHash,
+    /// Per-encode scratch kept so that encoding stops allocating.
+    found: Vec<u32>,
+    nearest: Vec<(f32, usize)>,
+    tensor: Vec<f32>,
+}
+
+impl Default for ObservationEncoder {
+    fn default() -> ObservationEncoder {
+        ObservationEncoder {
+            features: Vec::new(),
+            size: 0,
+            grid: None,
+            entity_ids: Vec::new(),
+            entity_positions: Vec::new(),
+            entity_kinds: Vec::new(),
+            entities: SpatialHash::new(1.0),
+            found: Vec::new(),
+            nearest: Vec::new(),
+            tensor: Vec::new(),
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl ObservationEncoder {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> ObservationEncoder {
+        ObservationEncoder::default()
+    }
+
+    /// Floats in one observation.
+    #[wasm_bindgen(getter)]
+    pub fn size(&self) -> u32 {
+        self.size as u32
+    }
+
+    /// Where each feature starts in an observation, in the order added.
+    pub fn feature_offsets(&self) -> Vec<u32> {
+        self.features
+            .iter()
+            .scan(0, |offset, feature| {
+                let start = *offset;
+                *offset += feature.size() as u32;
+                Some(start)
+            })
+            .collect()
+    }
+
+    /// Adds the agent's velocity along and left of its facing, divided by
+    /// `max_speed`: 2 floats.
+    pub fn add_velocity(&mut self, max_speed: f32) -> Result<(), Error> {
+        positive("max speed", max_speed)?;
+        self.push(Feature::Velocity { max_speed });
+        Ok(())
+    }
+
+    /// Adds the agent's unit facing in world space: 2 floats.
+    pub fn add_facing(&mut self) {
+        self.push(Feature::Facing);
+    }
+
+    /// Adds the agent's health as passed in, normally from 0 to 1: 1 float.
+    pub fn add_health(&mut self) {
+        self.push(Feature::Health);
+    }
+
+    /// Adds the `count` nearest entities within `range`, nearest first, each
+    /// as 1 for a present slot, its offset along and left of the agent's
+    /// facing divided by `range` and a one-hot of its kind among `kinds`:
+    /// `count * (3 + kinds)` floats, zero for empty slots.
+    pub fn add_nearby(&mut self, count: u32, range: f32, kinds: u32) -> Result<(), Error> {
+        positive("range", range)?;
+        self.push(Feature::Nearby {
+            count,
+            range,
+            kinds,
+        });
+        Ok(())
+    }
+
+    /// Adds `count` rays evenly spread across `fov` radians around the
+    /// agent's facing, each its distance to the first wall of the grid
+    /// divided by `range`, or 1 when nothing is in range: `count` floats.
+    pub fn add_raycasts(&mut self, count: u32, fov: f32, range: f32) -> Result<(), Error> {
+        positive("range", range)?;
+        self.push(Feature::Rays { count, fov, range });
+        Ok(())
+    }
+
+    /// Adds `count` values the game supplies itself, such as ammo or a
+    /// cooldown, taken in order from the `custom` array of each encode.
+    pub fn add_custom(&mut self, count: u32) {
+        self.push(Feature::Custom { count });
+    }
+
+    /// Walls the rays stop at, using a copy of the grid.
+    pub fn set_grid(&mut self, grid: &Grid) {
+        self.grid = Some(grid.clone());
+    }
+
+    /// Replaces the entities nearby features see: parallel `ids`, flat
+    /// `[x0, y0, ...]` positions and kinds below each feature's `kinds`. An
+    /// agent never sees the entity with its own id.
+    pub fn set_entities(
+        &mut self,
+        ids: &[u32],
+        positions: &[f32],
+        kinds: &[u32],
+    ) -> Result<(), Error> {
+        if positions.len() != ids.len() * 2 || kinds.len() != ids.len() {
+            return Err(Error::InvalidInput(
+                "entities need one id, one x, y pair and one kind each".into(),
+            ));
+        }
+        self.entity_ids.clear();
+        self.entity_ids.extend_from_slice(ids);
+        self.entity_positions.clear();
+        self.entity_positions
+            .extend(positions.chunks_exact(2).map(|p| Vec2::new(p[0], p[1])));
+        self.entity_kinds.clear();
+        self.entity_kinds.extend_from_slice(kinds);
+        self.rehash();
+        Ok(())
+    }
+
+    /// Encodes one agent, `id` as in `set_entities` or `u32::MAX` when it is
+    /// not an entity, with `custom` holding the values of every custom
+    /// feature in order.
+    pub fn encode(
+        &mut self,
+        id: u32,
+        position: Vec2,
+        facing: Vec2,
+        velocity: Vec2,
+        health: f32,
+        custom: &[f32],
+    ) -> Result<Vec<f32>, Error> {
+        self.check_custom(custom, 1)?;
+        let mut out = vec![0.0; self.size];
+        let agent = AgentState {
+            id,
+            position,
+            facing,
+            velocity,
+            health,
+        };
+        self.encode_into(&agent, custom, &mut out);
+        Ok(out)
+    }
+
+    /// Encodes every agent into one `[agents, size]` tensor, row by row, and
+    /// returns a view of it. `states` holds `[x, y, facing_x, facing_y,
+    /// velocity_x, velocity_y, health]` per agent in the order of `ids`, and
+    /// `custom` the custom values of each agent after one another. The view
+    /// is invalidated by the next batch or when the WASM memory grows, so
+    /// copy it or run the model on it first.
+    #[cfg(target_arch = "wasm32")]
+    pub fn encode_batch(
+        &mut self,
+        ids: &[u32],
+        states: &[f32],
+        custom: &[f32],
+    ) -> Result<Float32Array, Error> {
+        self.encode_rows(ids, states, custom)?;
+        Ok(unsafe { Float32Array::view(&self.tensor) })
+    }
+}
+
+impl ObservationEncoder {
+    /// Like `encode_batch`, returning the tensor as a slice.
+    pub fn encode_rows(
+        &mut self,
+        ids: &[u32],
+        states: &[f32],
+        custom: &[f32],
+    ) -> Result<&[f32], Error> {
+        if states.len() != ids.len() * STATE_STRIDE {
+            return Err(Error::InvalidInput(format!(
+                "states need {} floats per agent",
+                STATE_STRIDE
+            )));
+        }
+        self.check_custom(custom, ids.len())?;
+        let custom_size = self.custom_size();
+        let mut tensor = std::mem::take(&mut self.tensor);
+        tensor.clear();
+        tensor.resize(ids.len() * self.size, 0.0);
+        if self.size > 0 {
+            for (index, (row, state)) in tensor
+                .chunks_exact_mut(self.size)
+                .zip(states.chunks_exact(STATE_STRIDE))
+                .enumerate()
+            {
+                let agent = AgentState {
+                    id: ids[index],
+                    position: Vec2::new(state[0], state[1]),
+                    facing: Vec2::new(state[2], state[3]),
+                    velocity: Vec2::new(state[4], state[5]),
+                    health: state[6],
+                };
+                let values = &custom[index * custom_size..(index + 1) * custom_size];
+                self.encode_into(&agent, values, row);
+            }
+        }
+        self.tensor = tensor;
+        Ok(&self.tensor)
+    }
+
+    fn push(&mut self, feature: Feature) {
+        self.size += feature.size();
+        self.features.push(feature);
+        self.rehash();
+    }
+
+    fn custom_size(&self) -> usize {
+        self.features
+            .iter()
+            .map(|feature| match feature {
+                Feature::Custom { count } => *count as usize,
+                _ => 0,
+            })
+            .sum()
+    }
+
+    fn check_custom(&self, custom: &[f32], agents: usize) -> Result<(), Error> {
+        let expected = self.custom_size() * agents;
+        if custom.len() == expected {
+            Ok(())
+        } else {
+            Err(Error::InvalidInput(format!(
+                "expected {} custom values, got {}",
+                expected,
+                custom.len()
+            )))
+        }
+    }
+
+    /// Re-buckets the entities with cells as wide as the widest nearby range.
+    fn rehash(&mut self) {
+        let range = self
+            .features
+            .iter()
+            .map(|feature| match feature {
+                Feature::Nearby { range, .. } => *range,
+                _ => 0.0,
+            })
+            .fold(1.0, f32::max);
+        self.entities.clear();
+        self.entities.set_cell_size(range);
+        for (index, position) in self.entity_positions.iter().enumerate() {
+            self.entities.insert(index as u32, position.x, position.y);
+        }
+    }
+
+    fn encode_into(&mut self, agent: &AgentState, custom: &[f32], out: &mut [f32]) {
+        let mut forward = agent.facing.normalize();
+        if forward == Vec2::ZERO {
+            forward = Vec2::new(1.0, 0.0);
+        }
+        let left = forward.perp();
+        let local = |v: Vec2| (v.dot(forward), v.dot(left));
+        let mut found = std::mem::take(&mut self.found);
+        let mut nearest = std::mem::take(&mut self.nearest);
+        let (mut at, mut custom_at) = (0, 0);
+        for &feature in &self.features {
+            let slot = &mut out[at..at + feature.size()];
+            slot.fill(0.0);
+            match feature {
+                Feature::Velocity { max_speed } => {
+                    let (along, across) = local(agent.velocity / max_speed);
+                    slot[0] = along.clamp(-1.0, 1.0);
+                    slot[1] = across.clamp(-1.0, 1.0);
+                }
+                Feature::Facing => {
+                    slot[0] = forward.x;
+                    slot[1] = forward.y;
+                }
+                Feature::Health => slot[0] = agent.health,
+                Feature::Nearby {
+                    count,
+                    range,
+                    kinds,
+                } => {
+                    found.clear();
+                    self.entities.query_radius_into(
+                        agent.position.x,
+                        agent.position.y,
+                        range,
+                        &mut found,
+                    );
+                    nearest.clear();
+                    nearest.extend(
+                        found
+                            .iter()
+                            .map(|&index| index as usize)
+                            .filter(|&index| self.entity_ids[index] != agent.id)
+                            .map(|index| {
+                                (self.entity_positions[index].distance(agent.position), index)
+                            }),
+                    );
+                    nearest.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+                    let stride = 3 + kinds as usize;
+                    for (entry, &(_, index)) in slot
+                        .chunks_exact_mut(stride)
+                        .zip(nearest.iter().take(count as usize))
+                    {
+                        let (along, across) =
+                            local((self.entity_positions[index] - agent.position) / range);
+                        entry[0] = 1.0;
+                        entry[1] = along;
+                        entry[2] = across;
+                        let kind = self.entity_kinds[index];
+                        if kind < kinds {
+                            entry[3 + kind as usize] = 1.0;
+                        }
+                    }
+                }
+                Feature::Rays { count, fov, range } => {
+                    for (ray, value) in slot.iter_mut().enumerate() {
+                        let angle = if count > 1 {
+                            fov * (ray as f32 / (count - 1) as f32 - 0.5)
+                        } else {
+                            0.0
+                        };
+                        let direction = forward * angle.cos() + left * angle.sin();
+                        *value = self
+                            .grid
+                            .as_ref()
+                            .and_then(|grid| grid.raycast(agent.position, direction, range))
+                            .map_or(1.0, |hit| hit.distance / range);
+                    }
+                }
+                Feature::Custom { count } => {
+                    let count = count as usize;
+                    slot.copy_from_slice(&custom[custom_at..custom_at + count]);
+                    custom_at += count;
+                }
+            }
+            at += feature.size();
+        }
+        self.found = found;
+        self.nearest = nearest;
+    }
+}
+
+fn positive(what: &str, value: f32) -> Result<(), Error> {
+    if value > 0.0 && value.is_finite() {
+        Ok(())
+    } else {
+        Err(Error::InvalidInput(format!(
+            "{} must be positive, got {}",
+            what, value
+        )))
+    }
+}
@@ -0,0 +1,632 @@
+use std::collections::BTreeMap;
+
+use wasm_bindgen::prelude::*;
+
+use crate::avoidance::{self, cast_circle, cast_segment};
+use crate::bytes::{Reader, Writer};
+use crate::debug::{self, DebugGeometry};
+use crate::error::Error;
+use crate::grid::Grid;
+use crate::math::Vec2;
+use crate::steering::Agent;
+
+/// Segments per circle or cap outline in `debug_geometry`.
+const ARC_SEGMENTS: usize = 16;
+
+#[derive(Clone)]
+enum Shape {
+    Circle { center: Vec2, radius: f32 },
+    Capsule { start: Vec2, end: Vec2, radius: f32 },
+    Box { min: Vec2, max: Vec2 },
+    Polygon(Vec<Vec2>),
+}
+
+#[derive(Clone)]
+pub(crate) struct Obstacle {
+    shape: Shape,
+    velocity: Vec2,
+}
+
+impl Obstacle {
+    fn center(&self) -> Vec2 {
+        match &self.shape {
+            Shape::Circle { center, .. } => *center,
+            Shape::Capsule { start, end, .. } => (*start + *end) * 0.5,
+            Shape::Box { min, max } => (*min + *max) * 0.5,
+            Shape::Polygon(points) => {
+                points.iter().fold(Vec2::ZERO, |sum, &point| sum + point) / points.len() as f32
+            }
+        }
+    }
+
+    fn translate(&mut self, offset: Vec2) {
+        match &mut self.shape {
+            Shape::Circle { center, .. } => *center += offset,
+            Shape::Capsule { start, end, .. } => {
+                *start += offset;
+                *end += offset;
+            }
+            Shape::Box { min, max } => {
+                *min += offset;
+                *max += offset;
+            }
+            Shape::Polygon(points) => {
+                for point in points {
+                    *point += offset;
+                }
+            }
+        }
+    }
+
+    pub(crate) fn velocity(&self) -> Vec2 {
+        self.velocity
+    }
+
+    /// The disc of a round obstacle nearest `point`, as its center and
+    /// radius: a circle itself or the cross-section of a capsule.
+    pub(crate) fn disc_near(&self, point: Vec2) -> Option<(Vec2, f32)> {
+        match &self.shape {
+            Shape::Circle { center, radius } => Some((*center, *radius)),
+            Shape::Capsule { start, end, radius } => {
+                Some((closest_on_segment(point, *start, *end), *radius))
+            }
+            Shape::Box { .. } | Shape::Polygon(_) => None,
+        }
+    }
+
+    /// The corners of the axis-aligned box around the obstacle.
+    pub(crate) fn bounds(&self) -> (Vec2, Vec2) {
+        let (min, max) = match &self.shape {
+            Shape::Circle { center, radius } => (
+                *center - Vec2::new(*radius, *radius),
+                *center + Vec2::new(*radius, *radius),
+            ),
+            Shape::Capsule { start, end, radius } => (
+                Vec2::new(start.x.min(end.x) - radius, start.y.min(end.y) - radius),
+                Vec2::new(start.x.max(end.x) + radius, start.y.max(end.y) + radius),
+            ),
+            Shape::Box { min, max } => (*min, *max),
+            Shape::Polygon(points) => points.iter().fold(
+                (
+                    Vec2::new(f32::INFINITY, f32::INFINITY),
+                    Vec2::new(f32::NEG_INFINITY, f32::NEG_INFINITY),
+                ),
+                |(min, max), point| {
+                    (
+                        Vec2::new(min.x.min(point.x), min.y.min(point.y)),
+                        Vec2::new(max.x.max(point.x), max.y.max(point.y)),
+                    )
+                },
+            ),
+        };
+        (min, max)
+    }
+
+    /// Signed distance from `point` to the obstacle's outline, negative
+    /// inside, and the unit direction pointing away from the obstacle there.
+    pub(crate) fn distance(&self, point: Vec2) -> (f32, Vec2) {
+        match &self.shape {
+            Shape::Circle { center, radius } => away_from(point, *center, *radius),
+            Shape::Capsule { start, end, radius } => {
+                away_from(point, closest_on_segment(point, *start, *end), *radius)
+            }
+            Shape::Box { min, max } => {
+                let clamped = Vec2::new(point.x.clamp(min.x, max.x), point.y.clamp(min.y, max.y));
+                if clamped != point {
+                    return away_from(point, clamped, 0.0);
+                }
+                // Inside: out through the nearest side.
+                [
+                    (point.x - min.x, Vec2::new(-1.0, 0.0)),
+                    (max.x - point.x, Vec2::new(1.0, 0.0)),
+                    (point.y - min.y, Vec2::new(0.0, -1.0)),
+                    (max.y - point.y, Vec2::new(0.0, 1.0)),
+                ]
+                .into_iter()
+                .min_by(|a, b| a.0.total_cmp(&b.0))
+                .map(|(depth, normal)| (-depth, normal))
+                .unwrap_or((0.0, Vec2::new(1.0, 0.0)))
+            }
+            Shape::Polygon(points) => {
+                let closest = edges(points)
+                    .map(|(a, b)| closest_on_segment(point, a, b))
+                    .min_by(|a, b| {
+                        (*a - point)
+                            .length_squared()
+                            .total_cmp(&(*b - point).length_squared())
+                    })
+                    .unwrap_or(point);
+                let (distance, normal) = away_from(point, closest, 0.0);
+                if contains_point(points, point) {
+                    (-distance, -normal)
+                } else {
+                    (distance, normal)
+                }
+            }
+        }
+    }
+
+    /// Nearest hit along the segment from `origin` to `origin + direction *
+    /// length`, as the distance travelled and the surface normal facing the
+    /// origin; a hit at 0 when the origin is inside.
+    pub(crate) fn cast(&self, origin: Vec2, direction: Vec2, length: f32) -> Option<(f32, Vec2)> {
+        let (depth, normal) = self.distance(origin);
+        if depth < 0.0 {
+            return Some((0.0, normal));
+        }
+        let edge = |(a, b)| cast_segment(origin, direction, length, a, b);
+        match &self.shape {
+            Shape::Circle { center, radius } => {
+                cast_circle(origin, direction, length, *center, *radius)
+            }
+            Shape::Capsule { start, end, radius } => {
+                let side = (*end - *start).normalize().perp() * *radius;
+                nearest([
+                    cast_circle(origin, direction, length, *start, *radius),
+                    cast_circle(origin, direction, length, *end, *radius),
+                    edge((*start + side, *end + side)),
+                    edge((*start - side, *end - side)),
+                ])
+            }
+            Shape::Box { min, max } => {
+                let corners = [*min, Vec2::new(max.x, min.y), *max, Vec2::new(min.x, max.y)];
+                nearest(edges(&corners).map(edge))
+            }
+            Shape::Polygon(points) => nearest(edges(points).map(edge)),
+        }
+    }
+
+    fn debug_geometry(&self, out: &mut DebugGeometry) {
+        let ring = |center: Vec2, radius: f32, from: f32, sweep: f32| -> Vec<Vec2> {
+            (0..=ARC_SEGMENTS)
+                .map(|step| {
+                    let angle = from + sweep * step as f32 / ARC_SEGMENTS as f32;
+                    center + Vec2::from_angle(angle) * radius
+                })
+                .collect()
+        };
+        let outline = match &self.shape {
+            Shape::Circle { center, radius } => ring(*center, *radius, 0.0, std::f32::consts::TAU),
+            Shape::Capsule { start, end, radius } => {
+                let axis = *end - *start;
+                let angle = axis.y.atan2(axis.x);
+                let half = std::f32::consts::FRAC_PI_2;
+                let mut points = ring(*end, *radius, angle - half, std::f32::consts::PI);
+                points.extend(ring(*start, *radius, angle + half, std::f32::consts::PI));
+                points.push(points[0]);
+                points
+            }
+            Shape::Box { min, max } => vec![
+                *min,
+                Vec2::new(max.x, min.y),
+                *max,
+                Vec2::new(min.x, max.y),
+                *min,
+            ],
+            Shape::Polygon(points) => {
+                let mut points = points.clone();
+                points.push(points[0]);
+                points
+            }
+        };
+        out.polyline(&outline, debug::BLOCKED);
+        out.arrow(self.center(), self.velocity, debug::VELOCITY);
+    }
+}
+
+/// Obstacles every subsystem can share: circles, capsules, boxes and polygons,
+/// each with an optional velocity, added, moved and removed by id. Hand the
+/// same set to `Agent::avoid_obstacle_set`, `CrowdSimulator::set_obstacles`,
+/// `Perception::set_obstacles` and `Grid::set_obstacle_costs` instead of
+/// keeping each in step by hand, e.g. for doors, vehicles or a boulder rolling
+/// through a crowd.
+///
+/// Consumers that keep state take a snapshot, so pass the set again after
+/// moving its obstacles.
+#[wasm_bindgen]
+#[derive(Clone, Default)]
+pub struct ObstacleSet {
+    obstacles: BTreeMap<u32, Obstacle>,
+    next_id: u32,
+}
+
+#[wasm_bindgen]
+impl ObstacleSet {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> ObstacleSet {
+        ObstacleSet::default()
+    }
+
+    /// Adds a circle and returns its id.
+    pub fn add_circle(&mut self, x: f32, y: f32, radius: f32) -> Result<u32, Error> {
+        check_radius(radius)?;
+        Ok(self.insert(Shape::Circle {
+            center: Vec2::new(x, y),
+            radius,
+        }))
+    }
+
+    /// Adds the points within `radius` of the segment from `(x0, y0)` to
+    /// `(x1, y1)`, e.g. a log or a thick wall, and returns its id.
+    pub fn add_capsule(
+        &mut self,
+        x0: f32,
+        y0: f32,
+        x1: f32,
+        y1: f32,
+        radius: f32,
+    ) -> Result<u32, Error> {
+        check_radius(radius)?;
+        Ok(self.insert(Shape::Capsule {
+            start: Vec2::new(x0, y0),
+            end: Vec2::new(x1, y1),
+            radius,
+        }))
+    }
+
+    /// Adds an axis-aligned box between two opposite corners and returns its
+    /// id.
+    pub fn add_box(&mut self, x0: f32, y0: f32, x1: f32, y1: f32) -> Result<u32, Error> {
+        let (min, max) = (
+            Vec2::new(x0.min(x1), y0.min(y1)),
+            Vec2::new(x0.max(x1), y0.max(y1)),
+        );
+        if min.x == max.x || min.y == max.y {
+            return Err(Error::InvalidInput("box must have a non-zero area".into()));
+        }
+        Ok(self.insert(Shape::Box { min, max }))
+    }
+
+    /// Adds a simple polygon given as flat `[x0, y0, x1, y1, ...]` points and
+    /// returns its id.
+    pub fn add_polygon(&mut self, points: &[f32]) -> Result<u32, Error> {
+        if points.len() < 6 || !points.len().is_multiple_of(2) {
+            return Err(Error::InvalidInput(
+                "polygon needs at least 3 [x, y] points".into(),
+            ));
+        }
+        let points = points
+            .chunks_exact(2)
+            .map(|point| Vec2::new(point[0], point[1]))
+            .collect();
+        Ok(self.insert(Shape::Polygon(points)))
+    }
+
+    pub fn remove(&mut self, id: u32) -> bool {
+        self.obstacles.remove(&id).is_some()
+    }
+
+    /// Moves an obstacle so its center lands on `(x, y)`, keeping its shape.
+    /// The center is a circle's center, a capsule's midpoint, a box's middle
+    /// or the mean of a polygon's points.
+    pub fn move_to(&mut self, id: u32, x: f32, y: f32) -> bool {
+        self.obstacles
+            .get_mut(&id)
+            .map(|obstacle| obstacle.translate(Vec2::new(x, y) - obstacle.center()))
+            .is_some()
+    }
+
+    pub fn translate(&mut self, id: u32, dx: f32, dy: f32) -> bool {
+        self.obstacles
+            .get_mut(&id)
+            .map(|obstacle| obstacle.translate(Vec2::new(dx, dy)))
+            .is_some()
+    }
+
+    pub fn center(&self, id: u32) -> Option<Vec2> {
+        self.obstacles.get(&id).map(Obstacle::center)
+    }
+
+    /// Sets how fast an obstacle moves, which `update` applies and
+    /// `CrowdSimulator` predicts around.
+    pub fn set_velocity(&mut self, id: u32, vx: f32, vy: f32) -> bool {
+        self.obstacles
+            .get_mut(&id)
+            .map(|obstacle| obstacle.velocity = Vec2::new(vx, vy))
+            .is_some()
+    }
+
+    pub fn velocity(&self, id: u32) -> Option<Vec2> {
+        self.obstacles.get(&id).map(Obstacle::velocity)
+    }
+
+    /// Moves every obstacle along its velocity for `dt` seconds.
+    pub fn update(&mut self, dt: f32) {
+        for obstacle in self.obstacles.values_mut() {
+            if obstacle.velocity != Vec2::ZERO {
+                obstacle.translate(obstacle.velocity * dt);
+            }
+        }
+    }
+
+    /// Whether the point is inside any obstacle.
+    pub fn contains(&self, x: f32, y: f32) -> bool {
+        self.distance(x, y) < 0.0
+    }
+
+    /// Signed distance from the point to the nearest obstacle outline,
+    /// negative inside one, or `Infinity` when the set is empty.
+    pub fn distance(&self, x: f32, y: f32) -> f32 {
+        self.obstacles
+            .values()
+            .map(|obstacle| obstacle.distance(Vec2::new(x, y)).0)
+            .fold(f32::INFINITY, f32::min)
+    }
+
+    /// Whether any obstacle blocks the segment between the two points.
+    pub fn blocks_segment(&self, x0: f32, y0: f32, x1: f32, y1: f32) -> bool {
+        self.count_blocking(Vec2::new(x0, y0), Vec2::new(x1, y1)) > 0
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn len(&self) -> u32 {
+        self.obstacles.len() as u32
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.obstacles.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.obstacles.clear();
+    }
+
+    /// Appends each obstacle's outline to `out`, with a line for its velocity.
+    pub fn debug_geometry(&self, out: &mut DebugGeometry) {
+        for obstacle in self.obstacles.values() {
+            obstacle.debug_geometry(out);
+        }
+    }
+}
+
+impl ObstacleSet {
+    fn insert(&mut self, shape: Shape) -> u32 {
+        let id = self.next_id;
+        self.next_id = self.next_id.wrapping_add(1);
+        self.obstacles.insert(
+            id,
+            Obstacle {
+                shape,
+                velocity: Vec2::ZERO,
+            },
+        );
+        id
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = &Obstacle> {
+        self.obstacles.values()
+    }
+
+    pub(crate) fn write(&self, out: &mut Writer) {
+        out.u32(self.next_id);
+        out.u32(self.obstacles.len() as u32);
+        for (&id, obstacle) in &self.obstacles {
+            out.u32(id);
+            match &obstacle.shape {
+                Shape::Circle { center, radius } => {
+                    out.u32(0);
+                    out.f32s(&[center.x, center.y, *radius]);
+                }
+                Shape::Capsule { start, end, radius } => {
+                    out.u32(1);
+                    out.f32s(&[start.x, start.y, end.x, end.y, *radius]);
+                }
+                Shape::Box { min, max } => {
+                    out.u32(2);
+                    out.f32s(&[min.x, min.y, max.x, max.y]);
+                }
+                Shape::Polygon(points) => {
+                    out.u32(3);
+                    out.u32(points.len() as u32);
+                    for point in points {
+                        out.f32s(&[point.x, point.y]);
+                    }
+                }
+            }
+            out.f32s(&[obstacle.velocity.x, obstacle.velocity.y]);
+        }
+    }
+
+    pub(crate) fn read(input: &mut Reader) -> Result<ObstacleSet, Error> {
+        let mut set = ObstacleSet::new();
+        let next_id = input.u32()?;
+        for _ in 0..input.u32()? {
+            set.next_id = input.u32()?;
+            let id = match input.u32()? {
+                0 => set.add_circle(
+                    input.finite_f32()?,
+                    input.finite_f32()?,
+                    input.finite_f32()?,
+                )?,
+                1 => {
+                    let [x0, y0, x1, y1, radius] = [
+                        input.finite_f32()?,
+                        input.finite_f32()?,
+                        input.finite_f32()?,
+                        input.finite_f32()?,
+                        input.finite_f32()?,
+                    ];
+                    set.add_capsule(x0, y0, x1, y1, radius)?
+                }
+                2 => {
+                    let [x0, y0, x1, y1] = [
+                        input.finite_f32()?,
+                        input.finite_f32()?,
+                        input.finite_f32()?,
+                        input.finite_f32()?,
+                    ];
+                    set.add_box(x0, y0, x1, y1)?
+                }
+                3 => {
+                    let len = input.u32()? as usize;
+                    let points =
+                        input.finite_f32s(len.checked_mul(2).ok_or_else(|| input.invalid())?)?;
+                    set.add_polygon(&points)?
+                }
+                _ => return Err(input.invalid()),
+            };
+            set.set_velocity(id, input.finite_f32()?, input.finite_f32()?);
+        }
+        set.next_id = next_id;
+        Ok(set)
+    }
+
+    /// How many obstacles the segment from `from` to `to` passes into.
+    pub(crate) fn count_blocking(&self, from: Vec2, to: Vec2) -> usize {
+        let offset = to - from;
+        let length = offset.length();
+        let direction = offset.normalize();
+        self.obstacles
+            .values()
+            .filter(|obstacle| {
+                if length <= f32::EPSILON {
+                    obstacle.distance(from).0 < 0.0
+                } else {
+                    obstacle.cast(from, direction, length).is_some()
+                }
+            })
+            .count()
+    }
+
+    /// Nearest hit of any obstacle along a feeler, as `Obstacle::cast`.
+    fn cast(&self, origin: Vec2, direction: Vec2, length: f32) -> Option<(f32, Vec2)> {
+        self.obstacles
+            .values()
+            .filter_map(|obstacle| obstacle.cast(origin, direction, length))
+            .min_by(|a, b| a.0.total_cmp(&b.0))
+    }
+}
+
+#[wasm_bindgen]
+impl Agent {
+    /// `avoid_obstacles` against an `ObstacleSet`, at the obstacles' current
+    /// positions.
+    pub fn avoid_obstacle_set(&self, obstacles: &ObstacleSet, feeler_length: f32) -> Vec2 {
+        if obstacles.is_empty() {
+            return Vec2::ZERO;
+        }
+        avoidance::whiskers(self, feeler_length, |origin, direction, length| {
+            obstacles.cast(origin, direction, length)
+        })
+    }
+}
+
+#[wasm_bindgen]
+impl Grid {
+    /// Multiplies the cost of every cell within `padding` of an obstacle by
+    /// `cost`, on top of its terrain cost, e.g. `Infinity` to route around
+    /// them or 4 to merely prefer other ways. Replaces the costs of any
+    /// earlier call, so call it again after the obstacles move; changed cells
+    /// are recorded so live `Path`s repair around them.
+    pub fn set_obstacle_costs(
+        &mut self,
+        obstacles: &ObstacleSet,
+        cost: f32,
+        padding: f32,
+    ) -> Result<(), Error> {
+        if cost.is_nan() || cost < 1.0 {
+            return Err(Error::InvalidInput(format!(
+                "obstacle cost must be at least 1, got {}",
+                cost
+            )));
+        }
+        let mut costs = vec![1.0; self.cell_count()];
+        // Cells are unit squares around their coordinates, so take in those
+        // whose center lies within half a cell of the padded obstacle.
+        let reach = padding.max(0.0) + 0.5;
+        for obstacle in obstacles.iter() {
+            let (min, max) = obstacle.bounds();
+            let x0 = ((min.x - reach).floor() as i32).max(0);
+            let y0 = ((min.y - reach).floor() as i32).max(0);
+            let x1 = ((max.x + reach).ceil() as i32).min(self.width() as i32 - 1);
+            let y1 = ((max.y + reach).ceil() as i32).min(self.height() as i32 - 1);
+            for y in y0..=y1 {
+                for x in x0..=x1 {
+                    let cell = Vec2::new(x as f32, y as f32);
+                    if obstacle.distance(cell).0 <= reach {
+                        costs[self.index(x, y)] = cost;
+                    }
+                }
+            }
+        }
+        self.replace_obstacle_costs(costs);
+        Ok(())
+    }
+
+    /// Drops the costs `set_obstacle_costs` added.
+    pub fn clear_obstacle_costs(&mut self) {
+        let costs = vec![1.0; self.cell_count()];
+        self.replace_obstacle_costs(costs);
+    }
+}
+
+impl Grid {
+    fn replace_obstacle_costs(&mut self, costs: Vec<f32>) {
+        let old = std::mem::take(&mut self.obstacle_costs);
+        for (index, &cost) in costs.iter().enumerate() {
+            if old.get(index).copied().unwrap_or(1.0) != cost {
+                self.record_change(index);
+            }
+        }
+        if costs.iter().any(|&cost| cost != 1.0) {
+            self.obstacle_costs = costs;
+        }
+    }
+}
+
+fn check_radius(radius: f32) -> Result<(), Error> {
+    if radius > 0.0 {
+        Ok(())
+    } else {
+        Err(Error::InvalidInput(format!(
+            "obstacle radius must be positive, got {}",
+            radius
+        )))
+    }
+}
+
+/// Signed distance from `point` to a disc of `radius` around `center`, and the
+/// direction from the disc toward the point.
+fn away_from(point: Vec2, center: Vec2, radius: f32) -> (f32, Vec2) {
+    let offset = point - center;
+    let length = offset.length();
+    let normal = if length > f32::EPSILON {
+        offset / length
+    } else {
+        Vec2::new(1.0, 0.0)
+    };
+    (length - radius, normal)
+}
+
+fn closest_on_segment(point: Vec2, start: Vec2, end: Vec2) -> Vec2 {
+    let edge = end - start;
+    let length_squared = edge.length_squared();
+    if length_squared <= f32::EPSILON {
+        return start;
+    }
+    start + edge * ((point - start).dot(edge) / length_squared).clamp(0.0, 1.0)
+}
+
+/// The hit travelled least to, as `Obstacle::cast`.
+fn nearest(hits: impl IntoIterator<Item = Option<(f32, Vec2)>>) -> Option<(f32, Vec2)> {
+    hits.into_iter()
+        .flatten()
+        .min_by(|a, b| a.0.total_cmp(&b.0))
+}
+
+/// Consecutive point pairs around a closed outline.
+fn edges(points: &[Vec2]) -> impl Iterator<Item = (Vec2, Vec2)> + '_ {
+    (0..points.len()).map(|i| (points[i], points[(i + 1) % points.len()]))
+}
+
+/// Even-odd test for `point` inside the polygon.
+fn contains_point(points: &[Vec2], point: Vec2) -> bool {
+    edges(points)
+        .filter(|&(a, b)| {
+            (a.y > point.y) != (b.y > point.y)
+                && point.x < a.x + (point.y - a.y) * (b.x - a.x) / (b.y - a.y)
+        })
+        .count()
+        % 2
+        == 1
+}
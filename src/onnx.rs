@@ -0,0 +1,60 @@
+use tract_onnx::prelude::*;
+use wasm_bindgen::prelude::*;
+
+use crate::error::Error;
+
+/// A pre-trained ONNX model, such as a behavior-cloned policy or a small
+/// classifier, run by the pure-Rust tract runtime. Needs the `onnx` feature.
+#[wasm_bindgen]
+pub struct Model {
+    plan: TypedRunnableModel<TypedModel>,
+    output_shape: Vec<u32>,
+}
+
+#[wasm_bindgen]
+impl Model {
+    /// Loads and optimizes a model from the bytes of an `.onnx` file.
+    pub fn load(bytes: &[u8]) -> Result<Model, Error> {
+        let plan = tract_onnx::onnx()
+            .model_for_read(&mut &bytes[..])
+            .and_then(|model| model.into_optimized())
+            .and_then(|model| model.into_runnable())
+            .map_err(|error| Error::InvalidInput(format!("could not load model: {}", error)))?;
+        Ok(Model {
+            plan,
+            output_shape: Vec::new(),
+        })
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn input_count(&self) -> u32 {
+        self.plan.model().inputs.len() as u32
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn output_count(&self) -> u32 {
+        self.plan.model().outputs.len() as u32
+    }
+
+    /// Runs a model with one `f32` input, given as flat values in row-major order
+    /// with its `shape`, e.g. `[agents, features]`, and returns its first output
+    /// flattened the same way. Its shape is in `output_shape` afterwards.
+    pub fn run(&mut self, inputs: &[f32], shape: &[u32]) -> Result<Vec<f32>, Error> {
+        let shape: Vec<usize> = shape.iter().map(|&size| size as usize).collect();
+        let failed = |error: TractError| Error::InvalidInput(format!("model failed: {}", error));
+        let input = Tensor::from_shape(&shape, inputs).map_err(failed)?;
+        let outputs = self.plan.run(tvec!(input.into())).map_err(failed)?;
+        let output = outputs
+            .first()
+            .ok_or_else(|| Error::InvalidInput("model has no outputs".into()))?
+            .cast_to::<f32>()
+            .map_err(failed)?;
+        self.output_shape = output.shape().iter().map(|&size| size as u32).collect();
+        Ok(output.as_slice::<f32>().map_err(failed)?.to_vec())
+    }
+
+    /// Shape of the output of the last `run`.
+    pub fn output_shape(&self) -> Vec<u32> {
+        self.output_shape.clone()
+    }
+}
@@ -0,0 +1,326 @@
+use std::collections::BTreeMap;
+
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use wasm_bindgen::prelude::*;
+
+use crate::crowd::{Crowd, CrowdAgentParams};
+use crate::error::Error;
+use crate::flock::Flock;
+use crate::grid::{Grid, Traversal};
+use crate::math::Vec2;
+use crate::nav_filter::NavFilter;
+use crate::navmesh::NavMesh;
+use crate::perception::Perception;
+use crate::world::AiWorld;
+
+/// Declares an options object once, as the struct serde reads it into, the
+/// TypeScript interface JS sees and the type that names it in signatures, so
+/// they cannot drift apart. Fields given a `(default ...)` may be left out,
+/// which the interface marks optional.
+macro_rules! options {
+    (
+        $(#[doc = $doc:literal])*
+        $js:ident = $interface:literal for struct $name:ident {
+            $(
+                $(#[doc = $field_doc:literal])*
+                $field:ident: $ty:ty as $key:literal $(($($default:tt)*))? => $ts:literal,
+            )*
+        }
+    ) => {
+        #[derive(Deserialize)]
+        #[serde(deny_unknown_fields)]
+        struct $name {
+            $(
+                $(#[doc = $field_doc])*
+                #[serde(rename = $key $(, $($default)*)?)]
+                $field: $ty,
+            )*
+        }
+
+        #[wasm_bindgen]
+        extern "C" {
+            $(#[doc = $doc])*
+            #[wasm_bindgen(typescript_type = $interface)]
+            pub type $js;
+        }
+
+        const _: () = {
+            #[wasm_bindgen(typescript_custom_section)]
+            const INTERFACE: &str = concat!(
+                "\n",
+                ts_doc!("", $($doc),*),
+                "export interface ",
+                $interface,
+                " {\n",
+                $(
+                    ts_doc!("  ", $($field_doc),*),
+                    "  ",
+                    $key,
+                    $(ts_optional!($($default)*),)?
+                    ": ",
+                    $ts,
+                    ";\n",
+                )*
+                "}\n",
+            );
+        };
+    };
+}
+
+/// A JSDoc comment of doc lines, or nothing without any. Like the marker
+/// below, it only expands where wasm-bindgen emits the interfaces.
+#[cfg_attr(not(target_arch = "wasm32"), allow(unused_macros))]
+macro_rules! ts_doc {
+    ($indent:literal $(,)?) => {
+        ""
+    };
+    ($indent:literal, $($doc:literal),+) => {
+        concat!($indent, "/**", $($doc,)+ " */\n")
+    };
+}
+
+/// Marks a field with a serde default optional.
+#[cfg_attr(not(target_arch = "wasm32"), allow(unused_macros))]
+macro_rules! ts_optional {
+    ($($default:tt)*) => {
+        "?"
+    };
+}
+
+options! {
+    /// Settings for `Crowd.add_agent_with`; omitted ones take the
+    /// `CrowdAgentParams` defaults.
+    CrowdAgentOptions = "CrowdAgentOptions" for struct CrowdAgentConfig {
+        /// Starting position.
+        x: f32 as "x" => "number",
+        y: f32 as "y" => "number",
+        radius: Option<f32> as "radius" (default) => "number",
+        max_speed: Option<f32> as "maxSpeed" (default) => "number",
+        /// How far along its path the agent aims.
+        path_lookahead: Option<f32> as "pathLookahead" (default) => "number",
+        /// Distance from the target at which the agent counts as arrived.
+        arrive_radius: Option<f32> as "arriveRadius" (default) => "number",
+        /// Distance from the target within which the agent starts slowing down.
+        slow_radius: Option<f32> as "slowRadius" (default) => "number",
+    }
+}
+
+options! {
+    /// Settings for `AiWorld.add_agent_with`.
+    WorldAgentOptions = "WorldAgentOptions" for struct WorldAgentConfig {
+        x: f32 as "x" => "number",
+        y: f32 as "y" => "number",
+        max_speed: f32 as "maxSpeed" => "number",
+        max_force: f32 as "maxForce" => "number",
+    }
+}
+
+options! {
+    /// Settings for `Perception.add_observer_with`.
+    ObserverOptions = "ObserverOptions" for struct ObserverConfig {
+        position: [f32; 2] as "position" => "[number, number]",
+        /// Direction the observer looks along, `[1, 0]` by default.
+        facing: [f32; 2] as "facing" (default = "forward") => "[number, number]",
+        view_distance: f32 as "viewDistance" => "number",
+        /// Full field of view in radians, all around by default.
+        fov: f32 as "fov" (default = "all_around") => "number",
+    }
+}
+
+options! {
+    /// A `NavFilter` as a plain object, for `NavFilter.from_options` and path
+    /// queries.
+    NavFilterOptions = "NavFilterOptions" for struct FilterConfig {
+        /// Flags of which a flagged area needs at least one; every flag by
+        /// default.
+        include_flags: Option<u32> as "includeFlags" (default) => "number",
+        /// Flags that close an area to the agent; none by default.
+        exclude_flags: Option<u32> as "excludeFlags" (default) => "number",
+        /// Cost multipliers keyed by area type, 1 for the rest.
+        area_costs: BTreeMap<String, f32> as "areaCosts" (default) => "Record<number, number>",
+    }
+}
+
+options! {
+    /// A path request for `Grid.find_path_with`, in cell coordinates.
+    GridPathQuery = "GridPathQuery" for struct GridPathConfig {
+        start: [f32; 2] as "start" => "[number, number]",
+        end: [f32; 2] as "end" => "[number, number]",
+        /// Clearance the agent needs, 0 by default.
+        radius: f32 as "radius" (default) => "number",
+        filter: Option<FilterConfig> as "filter" (default) => "NavFilterOptions",
+    }
+}
+
+options! {
+    /// A path request for `NavMesh.find_path_with`.
+    MeshPathQuery = "MeshPathQuery" for struct MeshPathConfig {
+        start: [f32; 3] as "start" => "[number, number, number]",
+        end: [f32; 3] as "end" => "[number, number, number]",
+        /// Radius the agent keeps from walls, 0 by default.
+        radius: f32 as "radius" (default) => "number",
+        filter: Option<FilterConfig> as "filter" (default) => "NavFilterOptions",
+    }
+}
+
+options! {
+    /// Settings for `Flock.from_options`; omitted weights and radii take the
+    /// `Flock` defaults.
+    FlockOptions = "FlockOptions" for struct FlockConfig {
+        max_speed: f32 as "maxSpeed" => "number",
+        max_force: f32 as "maxForce" => "number",
+        neighbor_radius: Option<f32> as "neighborRadius" (default) => "number",
+        separation_radius: Option<f32> as "separationRadius" (default) => "number",
+        separation_weight: Option<f32> as "separationWeight" (default) => "number",
+        alignment_weight: Option<f32> as "alignmentWeight" (default) => "number",
+        cohesion_weight: Option<f32> as "cohesionWeight" (default) => "number",
+    }
+}
+
+/// Reads an options object, naming `what` it was for when it does not fit.
+fn parse<T: DeserializeOwned>(options: &JsValue, what: &str) -> Result<T, Error> {
+    serde_wasm_bindgen::from_value(options.clone())
+        .map_err(|error| Error::InvalidInput(format!("{}: {}", what, error)))
+}
+
+fn forward() -> [f32; 2] {
+    [1.0, 0.0]
+}
+
+fn all_around() -> f32 {
+    std::f32::consts::TAU
+}
+
+impl FilterConfig {
+    fn build(&self) -> Result<NavFilter, Error> {
+        let mut filter = NavFilter::new();
+        if let Some(flags) = self.include_flags {
+            filter.include_flags = flags;
+        }
+        if let Some(flags) = self.exclude_flags {
+            filter.exclude_flags = flags;
+        }
+        for (area, &cost) in &self.area_costs {
+            let area = area.parse::<u8>().map_err(|_| {
+                Error::InvalidInput(format!("area types run from 0 to 255, got {}", area))
+            })?;
+            filter.set_area_cost(area, cost)?;
+        }
+        Ok(filter)
+    }
+}
+
+#[wasm_bindgen]
+impl Crowd {
+    /// Adds an agent and returns its id, e.g.
+    /// `crowd.add_agent_with({ x, y, radius: 0.4, maxSpeed: 5 })`.
+    pub fn add_agent_with(&mut self, options: &CrowdAgentOptions) -> Result<u32, Error> {
+        let config: CrowdAgentConfig = parse(options, "crowd agent options")?;
+        let defaults = CrowdAgentParams::new();
+        let params = CrowdAgentParams {
+            radius: config.radius.unwrap_or(defaults.radius),
+            max_speed: config.max_speed.unwrap_or(defaults.max_speed),
+            path_lookahead: config.path_lookahead.unwrap_or(defaults.path_lookahead),
+            arrive_radius: config.arrive_radius.unwrap_or(defaults.arrive_radius),
+            slow_radius: config.slow_radius.unwrap_or(defaults.slow_radius),
+        };
+        Ok(self.add_agent(config.x, config.y, &params))
+    }
+}
+
+#[wasm_bindgen]
+impl AiWorld {
+    /// Adds an idle agent and returns its handle, e.g.
+    /// `world.add_agent_with({ x, y, maxSpeed: 4, maxForce: 10 })`.
+    pub fn add_agent_with(&mut self, options: &WorldAgentOptions) -> Result<u32, Error> {
+        let config: WorldAgentConfig = parse(options, "agent options")?;
+        self.add_agent(config.x, config.y, config.max_speed, config.max_force)
+    }
+}
+
+#[wasm_bindgen]
+impl Perception {
+    /// Adds an observer and returns its id, e.g.
+    /// `perception.add_observer_with({ position: [x, y], viewDistance: 12 })`.
+    pub fn add_observer_with(&mut self, options: &ObserverOptions) -> Result<u32, Error> {
+        let config: ObserverConfig = parse(options, "observer options")?;
+        let [x, y] = config.position;
+        let [fx, fy] = config.facing;
+        self.add_observer(
+            Vec2::new(x, y),
+            Vec2::new(fx, fy),
+            config.view_distance,
+            config.fov,
+        )
+    }
+}
+
+#[wasm_bindgen]
+impl NavFilter {
+    /// A filter built from a plain object such as
+    /// `{ excludeFlags: WATER, areaCosts: { 2: 4 } }`.
+    pub fn from_options(options: &NavFilterOptions) -> Result<NavFilter, Error> {
+        parse::<FilterConfig>(options, "filter options")?.build()
+    }
+}
+
+#[wasm_bindgen]
+impl Grid {
+    /// Finds a path from `query.start` to `query.end` through the cells with at
+    /// least `query.radius` of clearance that `query.filter` allows, with its
+    /// area costs applied. Returns an empty array when no path exists.
+    pub fn find_path_with(&self, query: &GridPathQuery) -> Result<Vec<f32>, Error> {
+        let query: GridPathConfig = parse(query, "path query")?;
+        let cell = |value: f32| value.round().max(0.0) as u32;
+        let filter = query.filter.as_ref().map(FilterConfig::build).transpose()?;
+        let mut traversal = match &filter {
+            Some(filter) => Traversal::filtered(filter),
+            None => Traversal::default(),
+        };
+        traversal.radius = query.radius;
+        Ok(self.find_weighted_path(
+            cell(query.start[0]),
+            cell(query.start[1]),
+            cell(query.end[0]),
+            cell(query.end[1]),
+            traversal,
+        ))
+    }
+}
+
+#[wasm_bindgen]
+impl NavMesh {
+    /// Finds a path from `query.start` to `query.end` that keeps `query.radius`
+    /// from the mesh boundary, through the triangles `query.filter` allows.
+    pub fn find_path_with(&self, query: &MeshPathQuery) -> Result<Vec<f32>, Error> {
+        let query: MeshPathConfig = parse(query, "path query")?;
+        let filter = query.filter.as_ref().map(FilterConfig::build).transpose()?;
+        Ok(self.find_filtered_path(query.start, query.end, query.radius, filter.as_ref()))
+    }
+}
+
+#[wasm_bindgen]
+impl Flock {
+    /// A flock with its limits, radii and weights named in one object.
+    pub fn from_options(options: &FlockOptions) -> Result<Flock, Error> {
+        let config: FlockConfig = parse(options, "flock options")?;
+        let mut flock = Flock::new(config.max_speed, config.max_force);
+        if let Some(radius) = config.neighbor_radius {
+            flock.neighbor_radius = radius;
+        }
+        if let Some(radius) = config.separation_radius {
+            flock.separation_radius = radius;
+        }
+        if let Some(weight) = config.separation_weight {
+            flock.separation_weight = weight;
+        }
+        if let Some(weight) = config.alignment_weight {
+            flock.alignment_weight = weight;
+        }
+        if let Some(weight) = config.cohesion_weight {
+            flock.cohesion_weight = weight;
+        }
+        Ok(flock)
+    }
+}
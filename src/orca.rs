@@ -0,0 +1,492 @@
+use wasm_bindgen::prelude::*;
+
+use crate::bytes::{Reader, Writer};
+use crate::error::Error;
+use crate::math::Vec2;
+use crate::obstacles::{Obstacle, ObstacleSet};
+use crate::parallel;
+use crate::spatial_hash::SpatialHash;
+use crate::stats::{AiStats, Sample};
+
+const EPSILON: f32 = 1e-5;
+
+/// A half-plane of permitted velocities: everything to the left of `direction`
+/// through `point`.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Line {
+    pub point: Vec2,
+    pub direction: Vec2,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Body {
+    pub position: Vec2,
+    pub velocity: Vec2,
+    pub radius: f32,
+}
+
+/// Computes the ORCA half-plane induced on `agent` by `other`, with `agent` taking
+/// on `share` of the avoidance: a half when both avoid, all of it when `other`
+/// does not.
+pub(crate) fn orca_line(
+    agent: &Body,
+    other: &Body,
+    time_horizon: f32,
+    dt: f32,
+    share: f32,
+) -> Line {
+    let relative_position = other.position - agent.position;
+    let relative_velocity = agent.velocity - other.velocity;
+    let distance_squared = relative_position.length_squared();
+    let combined_radius = agent.radius + other.radius;
+    let combined_radius_squared = combined_radius * combined_radius;
+
+    let direction;
+    let u;
+    if distance_squared > combined_radius_squared {
+        let inv_time_horizon = 1.0 / time_horizon;
+        let w = relative_velocity - relative_position * inv_time_horizon;
+        let w_length_squared = w.length_squared();
+        let dot = w.dot(relative_position);
+
+        if dot < 0.0 && dot * dot > combined_radius_squared * w_length_squared {
+            // Project on the cut-off circle.
+            let w_length = w_length_squared.sqrt();
+            let unit_w = w / w_length;
+            direction = Vec2::new(unit_w.y, -unit_w.x);
+            u = unit_w * (combined_radius * inv_time_horizon - w_length);
+        } else {
+            // Project on the nearer leg of the velocity obstacle.
+            let leg = (distance_squared - combined_radius_squared).sqrt();
+            let p = relative_position;
+            direction = if p.cross(w) > 0.0 {
+                Vec2::new(
+                    p.x * leg - p.y * combined_radius,
+                    p.x * combined_radius + p.y * leg,
+                ) / distance_squared
+            } else {
+                -Vec2::new(
+                    p.x * leg + p.y * combined_radius,
+                    -p.x * combined_radius + p.y * leg,
+                ) / distance_squared
+            };
+            u = direction * relative_velocity.dot(direction) - relative_velocity;
+        }
+    } else {
+        // Already colliding: resolve within a single time step.
+        let inv_dt = 1.0 / dt;
+        let w = relative_velocity - relative_position * inv_dt;
+        let w_length = w.length().max(EPSILON);
+        let unit_w = w / w_length;
+        direction = Vec2::new(unit_w.y, -unit_w.x);
+        u = unit_w * (combined_radius * inv_dt - w_length);
+    }
+
+    Line {
+        point: agent.velocity + u * share,
+        direction,
+    }
+}
+
+/// Computes the half-plane keeping `agent` from reaching `obstacle` within
+/// `time_horizon`, or `None` when it is too far away to matter. Round
+/// obstacles get a full velocity obstacle, so agents sidestep them; the rest
+/// are treated as a wall along their surface nearest the agent, moving at
+/// their velocity.
+pub(crate) fn obstacle_line(
+    agent: &Body,
+    obstacle: &Obstacle,
+    max_speed: f32,
+    time_horizon: f32,
+    dt: f32,
+) -> Option<Line> {
+    let (distance, normal) = obstacle.distance(agent.position);
+    let gap = distance - agent.radius;
+    let velocity = obstacle.velocity();
+    if gap > (max_speed + velocity.length()) * time_horizon {
+        return None;
+    }
+    if let Some((position, radius)) = obstacle.disc_near(agent.position) {
+        let other = Body {
+            position,
+            velocity,
+            radius,
+        };
+        return Some(orca_line(agent, &other, time_horizon, dt, 1.0));
+    }
+    // Approach the wall no faster than closes the gap within the horizon, or
+    // back out of it within a single step when already overlapping.
+    let closing = if gap > 0.0 {
+        gap / time_horizon
+    } else {
+        gap / dt
+    };
+    Some(Line {
+        point: velocity - normal * closing,
+        direction: Vec2::new(normal.y, -normal.x),
+    })
+}
+
+/// Finds the velocity closest to `preferred` (up to `max_speed`) that satisfies all
+/// `lines`, relaxing them uniformly when they are infeasible except for the first
+/// `obstacle_lines`, which are kept as long as they can be.
+pub(crate) fn solve(
+    lines: &[Line],
+    obstacle_lines: usize,
+    max_speed: f32,
+    preferred: Vec2,
+) -> Vec2 {
+    let mut result = Vec2::ZERO;
+    let failed = linear_program2(lines, max_speed, preferred, false, &mut result);
+    if failed < lines.len() {
+        linear_program3(lines, obstacle_lines, failed, max_speed, &mut result);
+    }
+    result
+}
+
+fn linear_program1(
+    lines: &[Line],
+    line_no: usize,
+    radius: f32,
+    optimal: Vec2,
+    direction_optimal: bool,
+    result: &mut Vec2,
+) -> bool {
+    let line = lines[line_no];
+    let dot = line.point.dot(line.direction);
+    let discriminant = dot * dot + radius * radius - line.point.length_squared();
+    if discriminant < 0.0 {
+        return false;
+    }
+    let sqrt_discriminant = discriminant.sqrt();
+    let mut t_left = -dot - sqrt_discriminant;
+    let mut t_right = -dot + sqrt_discriminant;
+
+    for other in &lines[..line_no] {
+        let denominator = line.direction.cross(other.direction);
+        let numerator = other.direction.cross(line.point - other.point);
+        if denominator.abs() <= EPSILON {
+            if numerator < 0.0 {
+                return false;
+            }
+            continue;
+        }
+        let t = numerator / denominator;
+        if denominator >= 0.0 {
+            t_right = t_right.min(t);
+        } else {
+            t_left = t_left.max(t);
+        }
+        if t_left > t_right {
+            return false;
+        }
+    }
+
+    *result = if direction_optimal {
+        if optimal.dot(line.direction) > 0.0 {
+            line.point + line.direction * t_right
+        } else {
+            line.point + line.direction * t_left
+        }
+    } else {
+        let t = line.direction.dot(optimal - line.point);
+        line.point + line.direction * t.max(t_left).min(t_right)
+    };
+    true
+}
+
+fn linear_program2(
+    lines: &[Line],
+    radius: f32,
+    optimal: Vec2,
+    direction_optimal: bool,
+    result: &mut Vec2,
+) -> usize {
+    *result = if direction_optimal {
+        optimal * radius
+    } else {
+        optimal.truncate(radius)
+    };
+
+    for i in 0..lines.len() {
+        if lines[i].direction.cross(lines[i].point - *result) > 0.0 {
+            let previous = *result;
+            if !linear_program1(lines, i, radius, optimal, direction_optimal, result) {
+                *result = previous;
+                return i;
+            }
+        }
+    }
+    lines.len()
+}
+
+fn linear_program3(
+    lines: &[Line],
+    obstacle_lines: usize,
+    begin: usize,
+    radius: f32,
+    result: &mut Vec2,
+) {
+    let mut distance = 0.0;
+    for i in begin..lines.len() {
+        if lines[i].direction.cross(lines[i].point - *result) <= distance {
+            continue;
+        }
+
+        let mut projected: Vec<Line> = lines[..obstacle_lines].to_vec();
+        for j in obstacle_lines..i {
+            let determinant = lines[i].direction.cross(lines[j].direction);
+            let point = if determinant.abs() <= EPSILON {
+                if lines[i].direction.dot(lines[j].direction) > 0.0 {
+                    continue;
+                }
+                (lines[i].point + lines[j].point) * 0.5
+            } else {
+                lines[i].point
+                    + lines[i].direction
+                        * (lines[j].direction.cross(lines[i].point - lines[j].point) / determinant)
+            };
+            projected.push(Line {
+                point,
+                direction: (lines[j].direction - lines[i].direction).normalize(),
+            });
+        }
+
+        let previous = *result;
+        let optimal = Vec2::new(-lines[i].direction.y, lines[i].direction.x);
+        if linear_program2(&projected, radius, optimal, true, result) < projected.len() {
+            *result = previous;
+        }
+        distance = lines[i].direction.cross(lines[i].point - *result);
+    }
+}
+
+struct CrowdAgent {
+    body: Body,
+    preferred: Vec2,
+    max_speed: f32,
+}
+
+/// Reciprocal collision avoidance for disc-shaped agents (ORCA / RVO2). Set each
+/// agent's preferred velocity, then `step` picks collision-free velocities and
+/// integrates positions.
+#[wasm_bindgen]
+pub struct CrowdSimulator {
+    agents: Vec<CrowdAgent>,
+    neighbors: SpatialHash,
+    lines: Vec<Line>,
+    nearby: Vec<u32>,
+    /// Velocities picked this step, applied once every agent has one.
+    next_velocities: Vec<Vec2>,
+    obstacles: ObstacleSet,
+    stats: AiStats,
+    pub neighbor_distance: f32,
+    pub max_neighbors: u32,
+    pub time_horizon: f32,
+}
+
+#[wasm_bindgen]
+impl CrowdSimulator {
+    #[wasm_bindgen(constructor)]
+    pub fn new(neighbor_distance: f32, time_horizon: f32) -> CrowdSimulator {
+        CrowdSimulator {
+            agents: Vec::new(),
+            neighbors: SpatialHash::new(neighbor_distance),
+            lines: Vec::new(),
+            nearby: Vec::new(),
+            next_velocities: Vec::new(),
+            obstacles: ObstacleSet::new(),
+            stats: AiStats::default(),
+            neighbor_distance,
+            max_neighbors: 10,
+            time_horizon,
+        }
+    }
+
+    pub fn add_agent(&mut self, x: f32, y: f32, radius: f32, max_speed: f32) -> u32 {
+        self.agents.push(CrowdAgent {
+            body: Body {
+                position: Vec2::new(x, y),
+                velocity: Vec2::ZERO,
+                radius,
+            },
+            preferred: Vec2::ZERO,
+            max_speed,
+        });
+        (self.agents.len() - 1) as u32
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn len(&self) -> u32 {
+        self.agents.len() as u32
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.agents.is_empty()
+    }
+
+    pub fn set_preferred_velocity(&mut self, id: u32, vx: f32, vy: f32) {
+        if let Some(agent) = self.agents.get_mut(id as usize) {
+            agent.preferred = Vec2::new(vx, vy);
+        }
+    }
+
+    pub fn set_position(&mut self, id: u32, x: f32, y: f32) {
+        if let Some(agent) = self.agents.get_mut(id as usize) {
+            agent.body.position = Vec2::new(x, y);
+        }
+    }
+
+    pub fn position(&self, id: u32) -> Option<Vec2> {
+        self.agents
+            .get(id as usize)
+            .map(|agent| agent.body.position)
+    }
+
+    pub fn velocity(&self, id: u32) -> Option<Vec2> {
+        self.agents
+            .get(id as usize)
+            .map(|agent| agent.body.velocity)
+    }
+
+    /// Flat `[x0, y0, x1, y1, ...]` copy of all agent positions.
+    pub fn positions(&self) -> Vec<f32> {
+        self.agents
+            .iter()
+            .flat_map(|agent| [agent.body.position.x, agent.body.position.y])
+            .collect()
+    }
+
+    /// Flat `[vx0, vy0, ...]` copy of all agent velocities.
+    pub fn velocities(&self) -> Vec<f32> {
+        self.agents
+            .iter()
+            .flat_map(|agent| [agent.body.velocity.x, agent.body.velocity.y])
+            .collect()
+    }
+
+    /// Avoids a snapshot of `obstacles` without expecting them to give way.
+    /// `step` moves the snapshot along the obstacles' velocities, so it only
+    /// needs setting again when they change course, are added or removed.
+    pub fn set_obstacles(&mut self, obstacles: &ObstacleSet) {
+        self.obstacles = obstacles.clone();
+    }
+
+    pub fn clear_obstacles(&mut self) {
+        self.obstacles.clear();
+    }
+
+    pub fn step(&mut self, dt: f32) {
+        if dt <= 0.0 {
+            return;
+        }
+        let sample = Sample::start();
+        self.neighbors.clear();
+        self.neighbors.set_cell_size(self.neighbor_distance);
+        for (id, agent) in self.agents.iter().enumerate() {
+            let p = agent.body.position;
+            self.neighbors.insert(id as u32, p.x, p.y);
+        }
+
+        let mut next_velocities = std::mem::take(&mut self.next_velocities);
+        next_velocities.resize(self.agents.len(), Vec2::ZERO);
+        let mut scratch = (
+            std::mem::take(&mut self.nearby),
+            std::mem::take(&mut self.lines),
+        );
+        parallel::fill(
+            &mut next_velocities,
+            &mut scratch,
+            parallel::AGENT_BATCH,
+            |(nearby, lines), i, next_velocity| {
+                let body = self.agents[i].body;
+                nearby.clear();
+                self.neighbors.query_radius_into(
+                    body.position.x,
+                    body.position.y,
+                    self.neighbor_distance,
+                    nearby,
+                );
+                nearby.retain(|&j| j as usize != i);
+                let agents = &self.agents;
+                nearby.sort_by(|&a, &b| {
+                    let da = (agents[a as usize].body.position - body.position).length_squared();
+                    let db = (agents[b as usize].body.position - body.position).length_squared();
+                    da.total_cmp(&db)
+                });
+                nearby.truncate(self.max_neighbors as usize);
+
+                lines.clear();
+                let agent = &self.agents[i];
+                lines.extend(self.obstacles.iter().filter_map(|obstacle| {
+                    obstacle_line(&body, obstacle, agent.max_speed, self.time_horizon, dt)
+                }));
+                let obstacle_lines = lines.len();
+                for &j in nearby.iter() {
+                    let other = &self.agents[j as usize].body;
+                    lines.push(orca_line(&body, other, self.time_horizon, dt, 0.5));
+                }
+                *next_velocity = solve(lines, obstacle_lines, agent.max_speed, agent.preferred);
+            },
+        );
+        (self.nearby, self.lines) = scratch;
+
+        for (agent, &velocity) in self.agents.iter_mut().zip(&next_velocities) {
+            agent.body.velocity = velocity;
+            agent.body.position += agent.body.velocity * dt;
+        }
+        self.next_velocities = next_velocities;
+        self.obstacles.update(dt);
+        self.stats = AiStats {
+            avoidance_us: sample.elapsed_us(),
+            agents: self.agents.len() as u32,
+            steps: 1,
+            ..AiStats::default()
+        };
+    }
+
+    /// What the last `step` cost: `avoidance_us`, `agents` and `steps`.
+    pub fn stats(&self) -> AiStats {
+        self.stats
+    }
+}
+
+impl CrowdSimulator {
+    /// Every agent, the obstacle snapshot and the avoidance settings.
+    pub(crate) fn write(&self, out: &mut Writer) {
+        out.f32(self.neighbor_distance);
+        out.u32(self.max_neighbors);
+        out.f32(self.time_horizon);
+        out.u32(self.agents.len() as u32);
+        for agent in &self.agents {
+            let body = &agent.body;
+            out.f32s(&[body.position.x, body.position.y]);
+            out.f32s(&[body.velocity.x, body.velocity.y, body.radius]);
+            out.f32s(&[agent.preferred.x, agent.preferred.y, agent.max_speed]);
+        }
+        self.obstacles.write(out);
+    }
+
+    pub(crate) fn read(input: &mut Reader) -> Result<CrowdSimulator, Error> {
+        let neighbor_distance = input.finite_f32()?;
+        let mut simulator = CrowdSimulator::new(neighbor_distance, 0.0);
+        simulator.max_neighbors = input.u32()?;
+        simulator.time_horizon = input.finite_f32()?;
+        for _ in 0..input.u32()? {
+            let position = Vec2::new(input.finite_f32()?, input.finite_f32()?);
+            let velocity = Vec2::new(input.finite_f32()?, input.finite_f32()?);
+            let radius = input.finite_f32()?;
+            simulator.agents.push(CrowdAgent {
+                body: Body {
+                    position,
+                    velocity,
+                    radius,
+                },
+                preferred: Vec2::new(input.finite_f32()?, input.finite_f32()?),
+                max_speed: input.finite_f32()?,
+            });
+        }
+        simulator.obstacles = ObstacleSet::read(input)?;
+        Ok(simulator)
+    }
+}
@@ -0,0 +1,91 @@
+#[cfg(feature = "threads")]
+use std::sync::{Arc, Mutex};
+
+#[cfg(feature = "threads")]
+use rayon::prelude::*;
+#[cfg(feature = "threads")]
+use wasm_bindgen::prelude::*;
+
+#[cfg(feature = "threads")]
+use crate::error::Error;
+
+/// A `fill` threshold for cheap per-agent work, below which handing the
+/// agents out to threads would cost more than it saves.
+pub(crate) const AGENT_BATCH: usize = 128;
+
+#[cfg(feature = "threads")]
+#[derive(Clone)]
+enum Pool {
+    Single,
+    Global,
+    Own(Arc<rayon::ThreadPool>),
+}
+
+#[cfg(feature = "threads")]
+static POOL: Mutex<Pool> = Mutex::new(Pool::Single);
+
+/// Spreads the hot loops of `Flock`, `CrowdSimulator` and `Crowd` path planning
+/// over `count` threads. 1, the default, runs everything on the calling thread;
+/// 0 uses rayon's global pool, which on the web is the one started from JS by
+/// `wasm-bindgen-rayon`'s `initThreadPool`.
+///
+/// Results are the same for any thread count. Needs the `threads` feature; on
+/// wasm32 the module must also be built with the `atomics` target feature and
+/// its memory shared with the pool's workers.
+#[cfg(feature = "threads")]
+#[wasm_bindgen]
+pub fn set_thread_count(count: u32) -> Result<(), Error> {
+    let pool = match count {
+        0 => Pool::Global,
+        1 => Pool::Single,
+        _ => rayon::ThreadPoolBuilder::new()
+            .num_threads(count as usize)
+            .build()
+            .map(|pool| Pool::Own(Arc::new(pool)))
+            .map_err(|error| Error::InvalidInput(format!("could not start threads: {}", error)))?,
+    };
+    *POOL.lock().unwrap() = pool;
+    Ok(())
+}
+
+/// Threads the hot loops run on.
+#[cfg(feature = "threads")]
+#[wasm_bindgen]
+pub fn thread_count() -> u32 {
+    let pool = POOL.lock().unwrap().clone();
+    match pool {
+        Pool::Single => 1,
+        Pool::Global => rayon::current_num_threads() as u32,
+        Pool::Own(pool) => pool.current_num_threads() as u32,
+    }
+}
+
+/// Sets each `out[i]` with `compute(scratch, i, &mut out[i])`, on the pool when
+/// there are at least `min_parallel` outputs. Threads get scratch of their
+/// own; on the calling thread it is `scratch`, so buffers kept between calls
+/// are reused.
+#[cfg_attr(not(feature = "threads"), allow(unused_variables))]
+pub(crate) fn fill<T, S, F>(out: &mut [T], scratch: &mut S, min_parallel: usize, compute: F)
+where
+    T: Send,
+    S: Default + Send,
+    F: Fn(&mut S, usize, &mut T) + Sync,
+{
+    #[cfg(feature = "threads")]
+    if out.len() >= min_parallel.max(2) {
+        let pool = POOL.lock().unwrap().clone();
+        let mut run = || {
+            out.par_iter_mut()
+                .enumerate()
+                .for_each_init(S::default, |scratch, (i, out)| compute(scratch, i, out))
+        };
+        match pool {
+            Pool::Single => {}
+            Pool::Global => return run(),
+            Pool::Own(pool) => return pool.install(run),
+        }
+    }
+    for (i, out) in out.iter_mut().enumerate() {
+        compute(scratch, i, out);
+    }
+}
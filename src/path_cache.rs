@@ -0,0 +1,311 @@
+use std::collections::HashMap;
+
+use wasm_bindgen::prelude::*;
+
+use crate::error::Error;
+use crate::grid::{Grid, Traversal};
+use crate::terrain::TerrainCosts;
+
+/// Start region, end region and agent profile.
+type Key = ((u32, u32), (u32, u32), u32);
+
+struct Entry {
+    /// Flat `[x, y, ...]` waypoints, as `Grid::find_path` returns them.
+    path: Vec<f32>,
+    /// Cells the path covers, grown by the region size: `[x0, y0, x1, y1]`.
+    bounds: [i32; 4],
+    /// Clearance the path was planned with.
+    radius: f32,
+    last_used: u64,
+}
+
+impl Entry {
+    /// Whether closing `(x, y)` may break the path: it passes within a cell of
+    /// it, where a diagonal step could now cut the corner, or within reach of
+    /// the clearance it needs.
+    fn passes_near(&self, x: i32, y: i32) -> bool {
+        let reach = 1 + self.radius.max(0.0).ceil() as i32;
+        self.path
+            .chunks_exact(2)
+            .any(|cell| (cell[0] as i32 - x).abs() <= reach && (cell[1] as i32 - y).abs() <= reach)
+    }
+
+    fn covers(&self, x: i32, y: i32) -> bool {
+        let [x0, y0, x1, y1] = self.bounds;
+        (x0..=x1).contains(&x) && (y0..=y1).contains(&y)
+    }
+}
+
+/// Remembers recent grid paths so that repeated requests between the same
+/// places, e.g. from a spawner sending waves to one base, skip the search.
+/// Starts and ends are grouped into square regions of `region_size` cells; a
+/// request that finds a path cached for its regions joins it with short
+/// searches from its own start and to its own end, so with regions larger
+/// than one cell a hit may be slightly longer than a fresh path. Once
+/// `capacity` paths are cached the least recently used goes first.
+///
+/// Entries drop by themselves when the grid reports edits made with
+/// `Grid::update_cell`: a cell that closes drops the paths that pass next to
+/// it or within their clearance radius of it, and one that opens drops the
+/// paths near it, which might now have a shortcut. Edits made any other way,
+/// and changes to terrain costs, need a `clear`. A cache serves one grid;
+/// navmesh paths are not cached.
+#[wasm_bindgen]
+pub struct PathCache {
+    capacity: u32,
+    region_size: u32,
+    entries: HashMap<Key, Entry>,
+    revision: Option<u32>,
+    clock: u64,
+    hits: u32,
+    misses: u32,
+}
+
+#[wasm_bindgen]
+impl PathCache {
+    #[wasm_bindgen(constructor)]
+    pub fn new(capacity: u32, region_size: u32) -> Result<PathCache, Error> {
+        if capacity == 0 || region_size == 0 {
+            return Err(Error::InvalidInput(
+                "capacity and region size must be at least 1".into(),
+            ));
+        }
+        Ok(PathCache {
+            capacity,
+            region_size,
+            entries: HashMap::new(),
+            revision: None,
+            clock: 0,
+            hits: 0,
+            misses: 0,
+        })
+    }
+
+    /// Like `Grid::find_path`, from the cache when it can be.
+    pub fn find_path(
+        &mut self,
+        grid: &Grid,
+        start_x: u32,
+        start_y: u32,
+        end_x: u32,
+        end_y: u32,
+    ) -> Vec<f32> {
+        self.find(
+            grid,
+            (start_x, start_y),
+            (end_x, end_y),
+            0,
+            Traversal::default(),
+        )
+    }
+
+    /// Like `Grid::find_path_for` with a clearance `radius` as in
+    /// `Grid::find_path_with_radius`, cached apart for each `profile`, a number
+    /// the caller gives each distinct pair of costs and radius.
+    #[allow(clippy::too_many_arguments)]
+    pub fn find_path_for(
+        &mut self,
+        grid: &Grid,
+        start_x: u32,
+        start_y: u32,
+        end_x: u32,
+        end_y: u32,
+        profile: u32,
+        costs: &TerrainCosts,
+        radius: f32,
+    ) -> Vec<f32> {
+        let traversal = Traversal {
+            costs: Some(costs),
+            radius,
+            ..Traversal::default()
+        };
+        self.find(grid, (start_x, start_y), (end_x, end_y), profile, traversal)
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn capacity(&self) -> u32 {
+        self.capacity
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn region_size(&self) -> u32 {
+        self.region_size
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn len(&self) -> u32 {
+        self.entries.len() as u32
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Requests answered from the cache so far.
+    #[wasm_bindgen(getter)]
+    pub fn hits(&self) -> u32 {
+        self.hits
+    }
+
+    /// Requests that needed a full search so far.
+    #[wasm_bindgen(getter)]
+    pub fn misses(&self) -> u32 {
+        self.misses
+    }
+}
+
+impl PathCache {
+    fn find(
+        &mut self,
+        grid: &Grid,
+        start: (u32, u32),
+        end: (u32, u32),
+        profile: u32,
+        traversal: Traversal,
+    ) -> Vec<f32> {
+        self.invalidate(grid);
+        self.clock += 1;
+        let key = (self.region(start), self.region(end), profile);
+        if let Some(entry) = self.entries.get_mut(&key) {
+            entry.last_used = self.clock;
+            let path = &entry.path;
+            if let Some(joined) = join(grid, start, path, end, traversal) {
+                self.hits += 1;
+                return joined;
+            }
+        }
+
+        self.misses += 1;
+        let path = grid.find_weighted_path(start.0, start.1, end.0, end.1, traversal);
+        if path.is_empty() {
+            return path;
+        }
+        if self.entries.len() as u32 >= self.capacity && !self.entries.contains_key(&key) {
+            let oldest = self
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(&key, _)| key);
+            if let Some(oldest) = oldest {
+                self.entries.remove(&oldest);
+            }
+        }
+        let margin = self.region_size as i32;
+        let mut bounds = [i32::MAX, i32::MAX, i32::MIN, i32::MIN];
+        for cell in path.chunks_exact(2) {
+            let (x, y) = (cell[0] as i32, cell[1] as i32);
+            bounds = [
+                bounds[0].min(x - margin),
+                bounds[1].min(y - margin),
+                bounds[2].max(x + margin),
+                bounds[3].max(y + margin),
+            ];
+        }
+        self.entries.insert(
+            key,
+            Entry {
+                path: path.clone(),
+                bounds,
+                radius: traversal.radius,
+                last_used: self.clock,
+            },
+        );
+        path
+    }
+
+    fn region(&self, (x, y): (u32, u32)) -> (u32, u32) {
+        (x / self.region_size, y / self.region_size)
+    }
+
+    /// Drops the entries that the grid's recorded edits since the last call
+    /// may have made wrong, or all of them if that history is gone.
+    fn invalidate(&mut self, grid: &Grid) {
+        let revision = grid.revision();
+        let Some(seen) = self.revision.replace(revision) else {
+            return;
+        };
+        if seen == revision {
+            return;
+        }
+        let Some(changes) = grid.changes_since(seen) else {
+            self.entries.clear();
+            return;
+        };
+        for &index in changes {
+            let (x, y) = grid.coords(index);
+            if grid.is_walkable(x, y) {
+                self.entries.retain(|_, entry| !entry.covers(x, y));
+            } else {
+                self.entries.retain(|_, entry| !entry.passes_near(x, y));
+            }
+        }
+    }
+}
+
+/// `path` with searches from `start` to its first cell and from its last cell
+/// to `end` spliced on, or `None` when either has no path.
+fn join(
+    grid: &Grid,
+    start: (u32, u32),
+    path: &[f32],
+    end: (u32, u32),
+    traversal: Traversal,
+) -> Option<Vec<f32>> {
+    let (first, last) = (
+        (path[0] as u32, path[1] as u32),
+        (path[path.len() - 2] as u32, path[path.len() - 1] as u32),
+    );
+    let lead = if start == first {
+        vec![start.0 as f32, start.1 as f32]
+    } else {
+        grid.find_weighted_path(start.0, start.1, first.0, first.1, traversal)
+    };
+    let tail = if end == last {
+        vec![end.0 as f32, end.1 as f32]
+    } else {
+        grid.find_weighted_path(last.0, last.1, end.0, end.1, traversal)
+    };
+    if lead.is_empty() || tail.is_empty() {
+        return None;
+    }
+    let mut joined = Vec::with_capacity(lead.len() + path.len() + tail.len());
+    joined.extend_from_slice(&lead[..lead.len() - 2]);
+    joined.extend_from_slice(path);
+    joined.extend_from_slice(&tail[2..]);
+    Some(joined)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Caches a path along row 5 with `radius` clearance and reports whether
+    /// it is still a hit after closing `(x, y)`.
+    fn hit_after_closing(radius: f32, x: u32, y: u32) -> bool {
+        let mut grid = Grid::new(12, 12).unwrap();
+        let costs = TerrainCosts::new();
+        let mut cache = PathCache::new(8, 1).unwrap();
+        assert!(!cache
+            .find_path_for(&grid, 2, 5, 9, 5, 0, &costs, radius)
+            .is_empty());
+        grid.update_cell(x, y, false);
+        cache.find_path_for(&grid, 2, 5, 9, 5, 0, &costs, radius);
+        cache.hits() == 1
+    }
+
+    #[test]
+    fn closing_a_cell_beside_the_path_drops_it() {
+        assert!(!hit_after_closing(0.0, 5, 6));
+        assert!(hit_after_closing(0.0, 5, 8));
+    }
+
+    #[test]
+    fn closing_a_cell_within_clearance_drops_it() {
+        assert!(!hit_after_closing(1.0, 5, 7));
+        assert!(hit_after_closing(1.0, 5, 9));
+    }
+}
@@ -0,0 +1,227 @@
+use wasm_bindgen::prelude::*;
+
+use crate::bytes::{Reader, Writer};
+use crate::debug::{self, DebugGeometry};
+use crate::error::Error;
+use crate::math::Vec2;
+use crate::steering::Agent;
+
+/// Tracks an agent's progress along a path for `Agent::follow_path`. Progress only
+/// moves forward, so paths that double back or cross themselves are followed in
+/// order instead of short-cutting to a later stretch.
+#[wasm_bindgen]
+#[derive(Clone)]
+pub struct PathFollower {
+    points: Vec<Vec2>,
+    /// Distance along the path at each point.
+    lengths: Vec<f32>,
+    progress: f32,
+    /// How close to the last point the agent must get for the path to count as
+    /// finished.
+    pub arrive_radius: f32,
+}
+
+#[wasm_bindgen]
+impl PathFollower {
+    /// Follows a flat path of 2D `[x, y, ...]` points, or of 3D `[x, y, z, ...]`
+    /// points projected onto XZ as returned by `NavMesh::find_path`.
+    #[wasm_bindgen(constructor)]
+    pub fn new(path: &[f32], dimensions: u32) -> Result<PathFollower, Error> {
+        let mut follower = PathFollower {
+            points: Vec::new(),
+            lengths: Vec::new(),
+            progress: 0.0,
+            arrive_radius: 0.5,
+        };
+        follower.set_path(path, dimensions)?;
+        Ok(follower)
+    }
+
+    /// Replaces the path and restarts from its beginning.
+    pub fn set_path(&mut self, path: &[f32], dimensions: u32) -> Result<(), Error> {
+        let points: Vec<Vec2> = match dimensions {
+            2 if path.len().is_multiple_of(2) => path
+                .chunks_exact(2)
+                .map(|p| Vec2::new(p[0], p[1]))
+                .collect(),
+            3 if path.len().is_multiple_of(3) => path
+                .chunks_exact(3)
+                .map(|p| Vec2::new(p[0], p[2]))
+                .collect(),
+            _ => {
+                return Err(Error::InvalidInput(format!(
+                    "expected a path of {}-component points",
+                    dimensions
+                )))
+            }
+        };
+        let mut total = 0.0;
+        self.lengths = Vec::with_capacity(points.len());
+        for (i, &point) in points.iter().enumerate() {
+            if i > 0 {
+                total += point.distance(points[i - 1]);
+            }
+            self.lengths.push(total);
+        }
+        self.points = points;
+        self.progress = 0.0;
+        Ok(())
+    }
+
+    /// Distance covered along the path, as of the last `follow_path` call.
+    #[wasm_bindgen(getter)]
+    pub fn progress(&self) -> f32 {
+        self.progress
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn length(&self) -> f32 {
+        self.lengths.last().copied().unwrap_or(0.0)
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn remaining(&self) -> f32 {
+        self.length() - self.progress
+    }
+
+    /// Whether the agent reached the end of the path, as of the last `follow_path`
+    /// call. Empty paths are always finished.
+    pub fn is_finished(&self, agent: &Agent) -> bool {
+        match self.points.last() {
+            Some(&end) => {
+                self.remaining() <= self.arrive_radius
+                    && agent.position.distance(end) <= self.arrive_radius
+            }
+            None => true,
+        }
+    }
+
+    /// Point on the path at `distance` along it, clamped to the ends.
+    pub fn point_at(&self, distance: f32) -> Vec2 {
+        let Some(&last) = self.points.last() else {
+            return Vec2::ZERO;
+        };
+        let segment = self.lengths.partition_point(|&length| length <= distance);
+        if segment == 0 {
+            return self.points[0];
+        }
+        if segment >= self.points.len() {
+            return last;
+        }
+        let (start, end) = (self.lengths[segment - 1], self.lengths[segment]);
+        let t = if end > start {
+            (distance - start) / (end - start)
+        } else {
+            0.0
+        };
+        self.points[segment - 1].lerp(self.points[segment], t)
+    }
+
+    /// Appends the path to `out`, with a point where progress along it stands.
+    pub fn debug_geometry(&self, out: &mut DebugGeometry) {
+        out.polyline(&self.points, debug::PATH);
+        out.point(self.point_at(self.progress), debug::TARGET);
+    }
+}
+
+impl PathFollower {
+    pub(crate) fn points(&self) -> &[Vec2] {
+        &self.points
+    }
+
+    pub(crate) fn write(&self, out: &mut Writer) {
+        out.u32(self.points.len() as u32);
+        for point in &self.points {
+            out.f32s(&[point.x, point.y]);
+        }
+        out.f32s(&[self.progress, self.arrive_radius]);
+    }
+
+    pub(crate) fn read(input: &mut Reader) -> Result<PathFollower, Error> {
+        let len = input.u32()? as usize;
+        let points = input.finite_f32s(len.checked_mul(2).ok_or_else(|| input.invalid())?)?;
+        let mut follower = PathFollower::new(&points, 2)?;
+        follower.progress = input.finite_f32()?;
+        follower.arrive_radius = input.finite_f32()?;
+        Ok(follower)
+    }
+
+    /// Moves progress to the closest point on the path within `window` ahead of
+    /// the current progress.
+    pub(crate) fn advance(&mut self, position: Vec2, window: f32) {
+        let limit = self.progress + window;
+        let mut best = (f32::INFINITY, self.progress);
+        let first = self
+            .lengths
+            .partition_point(|&length| length <= self.progress)
+            .saturating_sub(1);
+        for i in first..self.points.len().saturating_sub(1) {
+            if self.lengths[i] > limit {
+                break;
+            }
+            let (a, b) = (self.points[i], self.points[i + 1]);
+            let ab = b - a;
+            let length_squared = ab.length_squared();
+            let t = if length_squared > 0.0 {
+                ((position - a).dot(ab) / length_squared).clamp(0.0, 1.0)
+            } else {
+                0.0
+            };
+            let along = (self.lengths[i] + (self.lengths[i + 1] - self.lengths[i]) * t)
+                .clamp(self.progress, limit);
+            let distance = position.distance(self.point_at(along));
+            if distance < best.0 {
+                best = (distance, along);
+            }
+        }
+        self.progress = best.1;
+    }
+}
+
+#[wasm_bindgen]
+impl Agent {
+    /// Steers along a path: projects the agent onto it, then seeks the point
+    /// `lookahead` further along, arriving at the final point instead of
+    /// overshooting it. Once the path is finished the agent brakes to a stop.
+    pub fn follow_path(&self, follower: &mut PathFollower, lookahead: f32) -> Vec2 {
+        if follower.points.is_empty() {
+            return Vec2::ZERO;
+        }
+        let lookahead = lookahead.max(0.0);
+        follower.advance(self.position, (2.0 * lookahead).max(follower.arrive_radius));
+        if follower.is_finished(self) {
+            return (-self.velocity).truncate(self.max_force);
+        }
+        let target_distance = follower.progress + lookahead;
+        if target_distance >= follower.length() {
+            let end = follower.points[follower.points.len() - 1];
+            self.arrive(end, lookahead.max(follower.arrive_radius))
+        } else {
+            self.seek(follower.point_at(target_distance))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read(progress: f32) -> Result<PathFollower, Error> {
+        let mut out = Writer::new(b"TEST", 1);
+        out.u32(2);
+        out.f32s(&[0.0, 0.0, 10.0, 0.0]);
+        out.f32s(&[progress, 0.5]);
+        let bytes = out.finish();
+        let (mut input, _) = Reader::new(&bytes, b"TEST", "a path follower")?;
+        PathFollower::read(&mut input)
+    }
+
+    #[test]
+    fn read_rejects_progress_that_is_not_finite() {
+        assert!(read(f32::NAN).is_err());
+        assert!(read(f32::INFINITY).is_err());
+        let mut follower = read(4.0).unwrap();
+        follower.advance(Vec2::new(6.0, 0.0), 5.0);
+        assert_eq!(follower.progress, 6.0);
+    }
+}
@@ -0,0 +1,204 @@
+use std::collections::VecDeque;
+
+use js_sys::{Float32Array, Function, Promise};
+use wasm_bindgen::prelude::*;
+
+use crate::clock;
+use crate::grid::{octile, Grid, Traversal};
+use crate::search::{AStar, Progress};
+use crate::stats::{AiStats, Sample};
+
+/// Receives the request id and its path once a search finishes.
+pub type Completion = Box<dyn FnOnce(u32, &[f32])>;
+
+struct Request {
+    id: u32,
+    start: (u32, u32),
+    end: (u32, u32),
+    search: Option<AStar>,
+    completion: Completion,
+}
+
+/// Queues grid path requests and runs them a slice at a time so that many agents
+/// repathing at once cannot blow the frame budget. Call `process` once per frame.
+///
+/// Paths use the same format as `Grid::find_path` and are computed against the
+/// queue's own copy of the grid; call `set_grid` after editing the original.
+#[wasm_bindgen]
+pub struct PathRequestQueue {
+    grid: Grid,
+    requests: VecDeque<Request>,
+    next_id: u32,
+    stats: AiStats,
+    /// Node expansions between clock checks.
+    pub slice_size: u32,
+}
+
+#[wasm_bindgen]
+impl PathRequestQueue {
+    #[wasm_bindgen(constructor)]
+    pub fn new(grid: &Grid) -> PathRequestQueue {
+        PathRequestQueue {
+            grid: grid.clone(),
+            requests: VecDeque::new(),
+            next_id: 0,
+            stats: AiStats::default(),
+            slice_size: 256,
+        }
+    }
+
+    /// Replaces the grid searched by future slices. Searches already in progress
+    /// restart so they never mix the old and new layouts.
+    pub fn set_grid(&mut self, grid: &Grid) {
+        self.grid = grid.clone();
+        for request in &mut self.requests {
+            request.search = None;
+        }
+    }
+
+    /// Queues a request. `callback(path, id)` is called from within `process` when
+    /// the search finishes and must not call back into this queue.
+    pub fn submit(
+        &mut self,
+        start_x: u32,
+        start_y: u32,
+        end_x: u32,
+        end_y: u32,
+        callback: Option<Function>,
+    ) -> u32 {
+        self.submit_with(start_x, start_y, end_x, end_y, move |id, path| {
+            if let Some(callback) = callback {
+                let _ = callback.call2(
+                    &JsValue::NULL,
+                    &Float32Array::from(path),
+                    &JsValue::from(id),
+                );
+            }
+        })
+    }
+
+    /// Queues a request and returns a Promise that resolves to its path.
+    pub fn submit_async(&mut self, start_x: u32, start_y: u32, end_x: u32, end_y: u32) -> Promise {
+        let mut resolve = None;
+        let promise = Promise::new(&mut |resolve_fn, _| resolve = Some(resolve_fn));
+        self.submit(start_x, start_y, end_x, end_y, resolve);
+        promise
+    }
+
+    /// Drops a pending request without calling its completion. Returns whether it
+    /// was still queued.
+    pub fn cancel(&mut self, id: u32) -> bool {
+        let before = self.requests.len();
+        self.requests.retain(|request| request.id != id);
+        self.requests.len() != before
+    }
+
+    pub fn is_pending(&self, id: u32) -> bool {
+        self.requests.iter().any(|request| request.id == id)
+    }
+
+    /// Number of requests still waiting for a result.
+    #[wasm_bindgen(getter)]
+    pub fn len(&self) -> u32 {
+        self.requests.len() as u32
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.requests.is_empty()
+    }
+
+    /// What the last `process` cost: `pathfinding_us`, the requests left and
+    /// A* expansions.
+    pub fn stats(&self) -> AiStats {
+        self.stats
+    }
+
+    /// Advances queued searches in submission order until `budget_ms` has elapsed
+    /// or the queue is empty. At least one slice runs per call, so the queue makes
+    /// progress even with a zero budget. Returns the number of requests completed.
+    pub fn process(&mut self, budget_ms: f64) -> u32 {
+        let sample = Sample::start();
+        let deadline = sample.start_ms() + budget_ms;
+        let slice_size = self.slice_size.max(1) as usize;
+        let mut completed = 0;
+
+        while let Some(request) = self.requests.front_mut() {
+            let path = match step(&self.grid, request, slice_size) {
+                Progress::Running => None,
+                Progress::Found(cells) => Some(self.grid.to_waypoints(&cells)),
+                Progress::Failed => Some(Vec::new()),
+            };
+            if let Some(path) = path {
+                let request = self.requests.pop_front().unwrap();
+                (request.completion)(request.id, &path);
+                completed += 1;
+            }
+            if clock::now_ms() >= deadline {
+                break;
+            }
+        }
+        self.stats = AiStats {
+            pathfinding_us: sample.elapsed_us(),
+            path_queue_length: self.requests.len() as u32,
+            node_expansions: sample.expansions(),
+            steps: 1,
+            ..AiStats::default()
+        };
+
+        completed
+    }
+}
+
+impl PathRequestQueue {
+    /// Like `submit`, with a Rust completion.
+    pub fn submit_with(
+        &mut self,
+        start_x: u32,
+        start_y: u32,
+        end_x: u32,
+        end_y: u32,
+        completion: impl FnOnce(u32, &[f32]) + 'static,
+    ) -> u32 {
+        let id = self.next_id;
+        self.next_id = self.next_id.wrapping_add(1);
+        self.requests.push_back(Request {
+            id,
+            start: (start_x, start_y),
+            end: (end_x, end_y),
+            search: None,
+            completion: Box::new(completion),
+        });
+        id
+    }
+}
+
+/// Runs up to `slice_size` expansions of `request`, starting its search if needed.
+fn step(grid: &Grid, request: &mut Request, slice_size: usize) -> Progress {
+    let (sx, sy) = (request.start.0 as i32, request.start.1 as i32);
+    let (ex, ey) = (request.end.0 as i32, request.end.1 as i32);
+    if !grid.is_passable(sx, sy, Traversal::default())
+        || !grid.is_passable(ex, ey, Traversal::default())
+    {
+        return Progress::Failed;
+    }
+
+    let scale = grid.heuristic_scale(Traversal::default());
+    let heuristic = |index| {
+        let (x, y) = grid.coords(index);
+        octile(x - ex, y - ey) * scale
+    };
+    let search = request.search.get_or_insert_with(|| {
+        let cell_count = (grid.width() * grid.height()) as usize;
+        AStar::new(
+            cell_count,
+            grid.index(sx, sy),
+            grid.index(ex, ey),
+            octile(ex - sx, ey - sy) * scale,
+        )
+    });
+    search.step(
+        slice_size,
+        &mut |index, out| grid.neighbors(index, out),
+        &heuristic,
+    )
+}
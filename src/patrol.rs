@@ -0,0 +1,445 @@
+use wasm_bindgen::prelude::*;
+
+use crate::bytes::{Reader, Writer};
+use crate::error::Error;
+use crate::math::Vec2;
+use crate::search;
+
+/// What a patrol does after its last waypoint.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PatrolMode {
+    /// Goes back to the first waypoint and round again.
+    Loop = 0,
+    /// Turns around and walks the waypoints in reverse, back and forth.
+    PingPong = 1,
+    /// Stops at the last waypoint.
+    Once = 2,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Waypoint {
+    position: Vec2,
+    wait: f32,
+    facing: Option<f32>,
+}
+
+/// An ordered list of places to visit, each with a time to wait there and
+/// optionally a direction to face while waiting, e.g. a guard looking out of
+/// a window.
+#[wasm_bindgen]
+#[derive(Clone, Debug, PartialEq)]
+pub struct PatrolRoute {
+    waypoints: Vec<Waypoint>,
+    pub mode: PatrolMode,
+}
+
+#[wasm_bindgen]
+impl PatrolRoute {
+    #[wasm_bindgen(constructor)]
+    pub fn new(mode: PatrolMode) -> PatrolRoute {
+        PatrolRoute {
+            waypoints: Vec::new(),
+            mode,
+        }
+    }
+
+    /// Appends a waypoint to wait at for `wait` seconds, facing the angle
+    /// `facing` in radians from +x if given, and returns its index.
+    pub fn add_waypoint(&mut self, x: f32, y: f32, wait: f32, facing: Option<f32>) -> u32 {
+        self.waypoints.push(Waypoint {
+            position: Vec2::new(x, y),
+            wait: wait.max(0.0),
+            facing,
+        });
+        self.waypoints.len() as u32 - 1
+    }
+
+    pub fn position(&self, index: u32) -> Option<Vec2> {
+        self.waypoints
+            .get(index as usize)
+            .map(|waypoint| waypoint.position)
+    }
+
+    pub fn wait(&self, index: u32) -> Option<f32> {
+        self.waypoints
+            .get(index as usize)
+            .map(|waypoint| waypoint.wait)
+    }
+
+    pub fn facing(&self, index: u32) -> Option<f32> {
+        self.waypoints
+            .get(index as usize)
+            .and_then(|waypoint| waypoint.facing)
+    }
+
+    /// Flat `[x0, y0, x1, y1, ...]` waypoint positions.
+    pub fn positions(&self) -> Vec<f32> {
+        self.waypoints
+            .iter()
+            .flat_map(|waypoint| [waypoint.position.x, waypoint.position.y])
+            .collect()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn len(&self) -> u32 {
+        self.waypoints.len() as u32
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.waypoints.is_empty()
+    }
+}
+
+/// Walks a `PatrolRoute`: hands out the waypoint to head for, and once the
+/// agent is there, keeps it waiting before handing out the next. Feed its
+/// target to any steering, e.g. `AiWorld::set_target`; a `Crowd` runs one
+/// itself for agents given `Crowd::assign_patrol`.
+#[wasm_bindgen]
+#[derive(Clone, Debug)]
+pub struct Patroller {
+    route: PatrolRoute,
+    index: usize,
+    forward: bool,
+    waiting: bool,
+    wait_left: f32,
+    finished: bool,
+    /// Distance from a waypoint at which `update` counts the agent as there.
+    pub arrive_radius: f32,
+}
+
+#[wasm_bindgen]
+impl Patroller {
+    /// Starts at the route's first waypoint.
+    #[wasm_bindgen(constructor)]
+    pub fn new(route: &PatrolRoute) -> Result<Patroller, Error> {
+        if route.is_empty() {
+            return Err(Error::InvalidInput(
+                "a patrol route needs a waypoint".into(),
+            ));
+        }
+        Ok(Patroller {
+            route: route.clone(),
+            index: 0,
+            forward: true,
+            waiting: false,
+            wait_left: 0.0,
+            finished: false,
+            arrive_radius: 0.5,
+        })
+    }
+
+    /// Advances by `dt` seconds with the agent at `(x, y)` and returns where it
+    /// should go, its current waypoint, or `None` once a `Once` route is done.
+    pub fn update(&mut self, x: f32, y: f32, dt: f32) -> Option<Vec2> {
+        let target = self.route.waypoints[self.index].position;
+        let arrived = target.distance(Vec2::new(x, y)) <= self.arrive_radius;
+        self.advance(arrived, dt);
+        self.target()
+    }
+
+    /// The waypoint to head for, or `None` once the patrol is finished.
+    pub fn target(&self) -> Option<Vec2> {
+        (!self.finished).then(|| self.route.waypoints[self.index].position)
+    }
+
+    /// Index of the current waypoint.
+    #[wasm_bindgen(getter)]
+    pub fn index(&self) -> u32 {
+        self.index as u32
+    }
+
+    /// Whether the agent is waiting at its waypoint.
+    #[wasm_bindgen(getter)]
+    pub fn is_waiting(&self) -> bool {
+        self.waiting
+    }
+
+    /// Whether a `Once` route has been walked to the end.
+    #[wasm_bindgen(getter)]
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// The angle to face while waiting at a waypoint that has one.
+    pub fn facing(&self) -> Option<f32> {
+        if self.waiting || self.finished {
+            self.route.waypoints[self.index].facing
+        } else {
+            None
+        }
+    }
+
+    pub fn route(&self) -> PatrolRoute {
+        self.route.clone()
+    }
+}
+
+impl Patroller {
+    pub(crate) fn write(&self, out: &mut Writer) {
+        out.u32(self.route.mode as u32);
+        out.u32(self.route.waypoints.len() as u32);
+        for waypoint in &self.route.waypoints {
+            out.f32s(&[waypoint.position.x, waypoint.position.y, waypoint.wait]);
+            out.u32(waypoint.facing.is_some() as u32);
+            out.f32(waypoint.facing.unwrap_or(0.0));
+        }
+        out.u32(self.index as u32);
+        out.u32s(&[self.forward as u32, self.waiting as u32]);
+        out.f32(self.wait_left);
+        out.u32(self.finished as u32);
+        out.f32(self.arrive_radius);
+    }
+
+    pub(crate) fn read(input: &mut Reader) -> Result<Patroller, Error> {
+        let mode = match input.u32()? {
+            0 => PatrolMode::Loop,
+            1 => PatrolMode::PingPong,
+            2 => PatrolMode::Once,
+            _ => return Err(input.invalid()),
+        };
+        let mut route = PatrolRoute::new(mode);
+        for _ in 0..input.u32()? {
+            let (x, y, wait) = (input.finite_f32()?, input.finite_f32()?, read_wait(input)?);
+            let has_facing = input.u32()? != 0;
+            let facing = input.finite_f32()?;
+            route.add_waypoint(x, y, wait, has_facing.then_some(facing));
+        }
+        let mut patroller = Patroller::new(&route)?;
+        patroller.index = input.u32()? as usize;
+        if patroller.index >= route.waypoints.len() {
+            return Err(input.invalid());
+        }
+        patroller.forward = input.u32()? != 0;
+        patroller.waiting = input.u32()? != 0;
+        patroller.wait_left = read_wait(input)?;
+        patroller.finished = input.u32()? != 0;
+        patroller.arrive_radius = input.finite_f32()?;
+        Ok(patroller)
+    }
+
+    /// Runs the wait once `arrived` and moves on when it is over. Returns
+    /// whether the target changed.
+    pub(crate) fn advance(&mut self, arrived: bool, dt: f32) -> bool {
+        if self.finished {
+            return false;
+        }
+        if !self.waiting {
+            if !arrived {
+                return false;
+            }
+            self.waiting = true;
+            self.wait_left = self.route.waypoints[self.index].wait;
+        }
+        self.wait_left -= dt;
+        if self.wait_left > 0.0 {
+            return false;
+        }
+        let last = self.route.waypoints.len() - 1;
+        match self.route.mode {
+            PatrolMode::Loop => self.index = (self.index + 1) % (last + 1),
+            PatrolMode::Once if self.index == last => {
+                self.finished = true;
+                return false;
+            }
+            PatrolMode::Once => self.index += 1,
+            PatrolMode::PingPong => {
+                if last == 0 {
+                    return false;
+                }
+                if (self.forward && self.index == last) || (!self.forward && self.index == 0) {
+                    self.forward = !self.forward;
+                }
+                self.index = if self.forward {
+                    self.index + 1
+                } else {
+                    self.index - 1
+                };
+            }
+        }
+        self.waiting = false;
+        true
+    }
+}
+
+/// A saved wait, which may be endless but not NaN.
+fn read_wait(input: &mut Reader) -> Result<f32, Error> {
+    let wait = input.f32()?;
+    if wait.is_nan() {
+        Err(input.invalid())
+    } else {
+        Ok(wait)
+    }
+}
+
+/// Named spots joined by walkable links, e.g. a level's guard posts, to ask
+/// which spot is nearest, which lie within reach and how to get from one to
+/// another, and to build patrol routes that follow the links.
+#[wasm_bindgen]
+#[derive(Clone, Debug, Default)]
+pub struct WaypointGraph {
+    positions: Vec<Vec2>,
+    links: Vec<Vec<(usize, f32)>>,
+}
+
+#[wasm_bindgen]
+impl WaypointGraph {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> WaypointGraph {
+        WaypointGraph::default()
+    }
+
+    /// Adds a waypoint and returns its id.
+    pub fn add_waypoint(&mut self, x: f32, y: f32) -> u32 {
+        self.positions.push(Vec2::new(x, y));
+        self.links.push(Vec::new());
+        self.positions.len() as u32 - 1
+    }
+
+    /// Links two waypoints both ways, at the distance between them.
+    pub fn connect(&mut self, a: u32, b: u32) -> Result<(), Error> {
+        let (a, b) = (a as usize, b as usize);
+        if a >= self.positions.len() || b >= self.positions.len() || a == b {
+            return Err(Error::InvalidInput(
+                "links join two different existing waypoints".into(),
+            ));
+        }
+        if self.links[a].iter().any(|&(to, _)| to == b) {
+            return Ok(());
+        }
+        let cost = self.positions[a].distance(self.positions[b]);
+        self.links[a].push((b, cost));
+        self.links[b].push((a, cost));
+        Ok(())
+    }
+
+    /// Removes the link between two waypoints, returning whether there was one.
+    pub fn disconnect(&mut self, a: u32, b: u32) -> bool {
+        let (a, b) = (a as usize, b as usize);
+        if a >= self.links.len() || b >= self.links.len() {
+            return false;
+        }
+        let before = self.links[a].len();
+        self.links[a].retain(|&(to, _)| to != b);
+        self.links[b].retain(|&(to, _)| to != a);
+        self.links[a].len() != before
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn len(&self) -> u32 {
+        self.positions.len() as u32
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    pub fn position(&self, id: u32) -> Option<Vec2> {
+        self.positions.get(id as usize).copied()
+    }
+
+    /// Flat `[x0, y0, x1, y1, ...]` positions of all waypoints by id.
+    pub fn positions(&self) -> Vec<f32> {
+        self.positions.iter().flat_map(|p| [p.x, p.y]).collect()
+    }
+
+    /// Ids of the waypoints linked to `id`.
+    pub fn neighbors(&self, id: u32) -> Vec<u32> {
+        self.links
+            .get(id as usize)
+            .map(|links| links.iter().map(|&(to, _)| to as u32).collect())
+            .unwrap_or_default()
+    }
+
+    /// The waypoint closest to `(x, y)`, or `None` when there are none.
+    pub fn nearest(&self, x: f32, y: f32) -> Option<u32> {
+        let point = Vec2::new(x, y);
+        (0..self.positions.len())
+            .min_by(|&a, &b| {
+                let (da, db) = (
+                    (self.positions[a] - point).length_squared(),
+                    (self.positions[b] - point).length_squared(),
+                );
+                da.total_cmp(&db)
+            })
+            .map(|id| id as u32)
+    }
+
+    /// Ids of the waypoints within `radius` of `(x, y)`, nearest first.
+    pub fn within(&self, x: f32, y: f32, radius: f32) -> Vec<u32> {
+        let point = Vec2::new(x, y);
+        let mut found: Vec<(f32, u32)> = self
+            .positions
+            .iter()
+            .enumerate()
+            .map(|(id, position)| ((*position - point).length_squared(), id as u32))
+            .filter(|&(distance, _)| distance <= radius * radius)
+            .collect();
+        found.sort_by(|a, b| a.0.total_cmp(&b.0));
+        found.into_iter().map(|(_, id)| id).collect()
+    }
+
+    /// Ids along a shortest chain of links from `from` to `to`, both
+    /// included, or an empty array when they are not connected.
+    pub fn find_path(&self, from: u32, to: u32) -> Vec<u32> {
+        self.path(from as usize, to as usize)
+            .map(|path| path.into_iter().map(|id| id as u32).collect())
+            .unwrap_or_default()
+    }
+
+    /// A route visiting the waypoints `stops` in order along the links, and
+    /// for `Loop` back to the first, waiting `wait` seconds at each stop and
+    /// passing through the waypoints between without stopping.
+    pub fn route(&self, stops: &[u32], mode: PatrolMode, wait: f32) -> Result<PatrolRoute, Error> {
+        let mut route = PatrolRoute::new(mode);
+        let Some(&first) = stops.first() else {
+            return Ok(route);
+        };
+        let mut legs: Vec<u32> = stops.to_vec();
+        if mode == PatrolMode::Loop && stops.len() > 1 {
+            legs.push(first);
+        }
+        let position = |id: u32| {
+            self.position(id)
+                .ok_or_else(|| Error::InvalidInput(format!("no waypoint {id}")))
+        };
+        let start = position(first)?;
+        route.add_waypoint(start.x, start.y, wait, None);
+        for pair in legs.windows(2) {
+            let path = self
+                .path(pair[0] as usize, pair[1] as usize)
+                .ok_or_else(|| {
+                    Error::InvalidInput(format!(
+                        "waypoints {} and {} are not connected",
+                        pair[0], pair[1]
+                    ))
+                })?;
+            for (step, &id) in path.iter().enumerate().skip(1) {
+                let point = position(id as u32)?;
+                let stop = step == path.len() - 1;
+                route.add_waypoint(point.x, point.y, if stop { wait } else { 0.0 }, None);
+            }
+        }
+        if legs.len() > stops.len() {
+            // The loop's last leg ends back on the first waypoint.
+            route.waypoints.pop();
+        }
+        Ok(route)
+    }
+}
+
+impl WaypointGraph {
+    fn path(&self, from: usize, to: usize) -> Option<Vec<usize>> {
+        if from >= self.positions.len() || to >= self.positions.len() {
+            return None;
+        }
+        let goal = self.positions[to];
+        search::astar(
+            self.positions.len(),
+            from,
+            to,
+            |id, out| out.extend_from_slice(&self.links[id]),
+            |id| self.positions[id].distance(goal),
+        )
+    }
+}
@@ -0,0 +1,431 @@
+use std::collections::HashMap;
+use std::f32::consts::PI;
+
+use wasm_bindgen::prelude::*;
+
+use crate::debug::{self, DebugGeometry};
+use crate::error::Error;
+use crate::events::{AiEvent, EventKind};
+use crate::grid::Grid;
+use crate::math::Vec2;
+use crate::obstacles::ObstacleSet;
+use crate::spatial_hash::SpatialHash;
+use crate::stats::{AiStats, Sample};
+
+/// Something an observer sensed: the id of what caused it, where it happened and
+/// how strongly it came across.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Stimulus {
+    pub source: u32,
+    pub position: Vec2,
+    pub strength: f32,
+}
+
+pub(crate) struct Observer {
+    pub position: Vec2,
+    facing: Vec2,
+    view_distance: f32,
+    /// Cosine of half the field of view.
+    cos_half_fov: f32,
+    /// Visible targets, nearest first.
+    pub visible: Vec<u32>,
+    /// Multiplier on the loudness of sounds, 0 for deaf observers.
+    pub hearing: f32,
+    /// Sounds heard in the last `update`, loudest first.
+    pub heard: Vec<Stimulus>,
+}
+
+/// Vision for a set of observers against a set of targets. Each observer sees
+/// the targets within its view distance and field of view whose sight line is
+/// not blocked by a grid cell or an occluder segment, and hears the sounds that
+/// reach it. Call `update` after moving observers and targets, then read each
+/// observer's `visible` and `heard` lists.
+///
+/// Positions are 2D; with a grid they are cell coordinates with cell centers at
+/// integers.
+#[wasm_bindgen]
+pub struct Perception {
+    pub(crate) observers: HashMap<u32, Observer>,
+    next_observer: u32,
+    targets: SpatialHash,
+    pub(crate) grid: Option<Grid>,
+    pub(crate) occluders: HashMap<u32, (Vec2, Vec2)>,
+    next_occluder: u32,
+    pub(crate) obstacles: ObstacleSet,
+    scratch: Vec<u32>,
+    /// Per-update scratch kept so that updates stop allocating.
+    sightings: Vec<(f32, u32)>,
+    seen: Vec<u32>,
+    pub(crate) sounds: Vec<Stimulus>,
+    events: Vec<AiEvent>,
+    stats: AiStats,
+    /// Loudness a sound loses for every wall cell or occluder it passes through.
+    pub wall_damping: f32,
+}
+
+#[wasm_bindgen]
+impl Perception {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Perception {
+        Perception {
+            observers: HashMap::new(),
+            next_observer: 0,
+            targets: SpatialHash::new(8.0),
+            grid: None,
+            occluders: HashMap::new(),
+            next_occluder: 0,
+            obstacles: ObstacleSet::new(),
+            scratch: Vec::new(),
+            sightings: Vec::new(),
+            seen: Vec::new(),
+            sounds: Vec::new(),
+            events: Vec::new(),
+            stats: AiStats::default(),
+            wall_damping: 10.0,
+        }
+    }
+
+    /// Adds an observer looking along `facing` and returns its id. `fov` is the
+    /// full field of view in radians; `TAU` sees all around.
+    ///
+    /// @deprecated Use `add_observer_with`, which names its arguments.
+    pub fn add_observer(
+        &mut self,
+        position: Vec2,
+        facing: Vec2,
+        view_distance: f32,
+        fov: f32,
+    ) -> Result<u32, Error> {
+        if !(view_distance > 0.0 && view_distance.is_finite() && fov > 0.0) {
+            return Err(Error::InvalidInput(format!(
+                "view distance must be positive and finite and field of view positive, got {} and {}",
+                view_distance, fov
+            )));
+        }
+        let id = self.next_observer;
+        self.next_observer = self.next_observer.wrapping_add(1);
+        self.observers.insert(
+            id,
+            Observer {
+                position,
+                facing: Vec2::new(1.0, 0.0),
+                view_distance,
+                cos_half_fov: (fov.min(std::f32::consts::TAU) / 2.0).cos(),
+                visible: Vec::new(),
+                hearing: 1.0,
+                heard: Vec::new(),
+            },
+        );
+        self.set_observer(id, position, facing);
+        Ok(id)
+    }
+
+    /// Moves and turns an observer. A zero `facing` keeps the old one.
+    pub fn set_observer(&mut self, id: u32, position: Vec2, facing: Vec2) -> bool {
+        let Some(observer) = self.observers.get_mut(&id) else {
+            return false;
+        };
+        observer.position = position;
+        if facing != Vec2::ZERO {
+            observer.facing = facing.normalize();
+        }
+        true
+    }
+
+    pub fn remove_observer(&mut self, id: u32) -> bool {
+        self.observers.remove(&id).is_some()
+    }
+
+    /// Adds or moves the target with the caller's `id`, e.g. an entity id.
+    pub fn set_target(&mut self, id: u32, x: f32, y: f32) {
+        self.targets.move_to(id, x, y);
+    }
+
+    pub fn remove_target(&mut self, id: u32) -> bool {
+        self.targets.remove(id)
+    }
+
+    /// Blocks sight through the grid's unwalkable cells, using a copy of it.
+    pub fn set_grid(&mut self, grid: &Grid) {
+        self.grid = Some(grid.clone());
+    }
+
+    pub fn clear_grid(&mut self) {
+        self.grid = None;
+    }
+
+    /// Adds a segment that blocks sight, such as a wall, and returns its id.
+    pub fn add_occluder(&mut self, x0: f32, y0: f32, x1: f32, y1: f32) -> u32 {
+        let id = self.next_occluder;
+        self.next_occluder = self.next_occluder.wrapping_add(1);
+        self.occluders
+            .insert(id, (Vec2::new(x0, y0), Vec2::new(x1, y1)));
+        id
+    }
+
+    pub fn remove_occluder(&mut self, id: u32) -> bool {
+        self.occluders.remove(&id).is_some()
+    }
+
+    /// Blocks sight and muffles sound through a snapshot of `obstacles`; set
+    /// them again after they move.
+    pub fn set_obstacles(&mut self, obstacles: &ObstacleSet) {
+        self.obstacles = obstacles.clone();
+    }
+
+    pub fn clear_obstacles(&mut self) {
+        self.obstacles.clear();
+    }
+
+    /// Recomputes every observer's visible targets and delivers the sounds emitted
+    /// since the last update.
+    pub fn update(&mut self) {
+        let sample = Sample::start();
+        self.hear();
+        let mut observers = std::mem::take(&mut self.observers);
+        let mut sightings = std::mem::take(&mut self.sightings);
+        let mut seen = std::mem::take(&mut self.seen);
+        self.events.clear();
+        for (&id, observer) in observers.iter_mut() {
+            self.scratch.clear();
+            let eye = observer.position;
+            self.targets
+                .query_radius_into(eye.x, eye.y, observer.view_distance, &mut self.scratch);
+            sightings.clear();
+            sightings.extend(self.scratch.iter().filter_map(|&id| {
+                let (x, y) = self.targets.position(id)?;
+                let target = Vec2::new(x, y);
+                let offset = target - eye;
+                let distance = offset.length();
+                let in_cone = distance <= f32::EPSILON
+                    || observer.facing.dot(offset / distance) >= observer.cos_half_fov;
+                (in_cone && self.clear(eye, target)).then_some((distance, id))
+            }));
+            sightings.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+            seen.clear();
+            seen.extend(sightings.iter().map(|&(_, id)| id));
+            for &target in &seen {
+                if !observer.visible.contains(&target) {
+                    self.events
+                        .push(AiEvent::new(EventKind::TargetSpotted, id, target));
+                }
+            }
+            for &target in &observer.visible {
+                if !seen.contains(&target) {
+                    self.events
+                        .push(AiEvent::new(EventKind::TargetLost, id, target));
+                }
+            }
+            std::mem::swap(&mut observer.visible, &mut seen);
+        }
+        self.observers = observers;
+        self.sightings = sightings;
+        self.seen = seen;
+        self.events
+            .sort_by_key(|event| (event.subject, event.kind as u32, event.other));
+        self.stats = AiStats {
+            perception_us: sample.elapsed_us(),
+            agents: self.observers.len() as u32,
+            steps: 1,
+            ..AiStats::default()
+        };
+    }
+
+    /// What the last `update` cost: `perception_us` and `agents` for the
+    /// observers.
+    pub fn stats(&self) -> AiStats {
+        self.stats
+    }
+
+    /// Targets observers began or stopped seeing in the last `update`, as
+    /// `TargetSpotted` and `TargetLost` events ordered by observer.
+    pub fn events(&self) -> Vec<AiEvent> {
+        self.events.clone()
+    }
+
+    /// Targets the observer saw in the last `update`, nearest first.
+    pub fn visible(&self, observer: u32) -> Vec<u32> {
+        self.observers
+            .get(&observer)
+            .map(|observer| observer.visible.clone())
+            .unwrap_or_default()
+    }
+
+    /// Whether the observer saw `target` in the last `update`.
+    pub fn can_see(&self, observer: u32, target: u32) -> bool {
+        self.observers
+            .get(&observer)
+            .is_some_and(|observer| observer.visible.contains(&target))
+    }
+
+    /// Whether nothing blocks the sight line between two points, regardless of
+    /// any observer's view distance or field of view.
+    pub fn is_clear(&self, from: Vec2, to: Vec2) -> bool {
+        self.clear(from, to)
+    }
+
+    /// Appends each observer to `out` as a point with its vision cone as a fan
+    /// of triangles, plus a line and a point for every target it saw in the
+    /// last `update`.
+    pub fn debug_geometry(&self, out: &mut DebugGeometry) {
+        let mut ids: Vec<u32> = self.observers.keys().copied().collect();
+        ids.sort_unstable();
+        for id in ids {
+            let observer = &self.observers[&id];
+            let eye = observer.position;
+            let half = observer.cos_half_fov.clamp(-1.0, 1.0).acos();
+            let segments = ((half / (PI / 16.0)).ceil() as usize).max(1) * 2;
+            let edge = |i: usize| {
+                let angle = -half + 2.0 * half * i as f32 / segments as f32;
+                eye + observer.facing.rotate(angle) * observer.view_distance
+            };
+            for i in 0..segments {
+                out.triangle(eye, edge(i), edge(i + 1), debug::CONE);
+            }
+            for &target in &observer.visible {
+                if let Some(position) = self.target_position(target) {
+                    out.line(eye, position, debug::SIGHT);
+                    out.point(position, debug::SIGHT);
+                }
+            }
+            out.point(eye, debug::AGENT);
+        }
+    }
+}
+
+impl Default for Perception {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Perception {
+    pub(crate) fn recent_events(&self) -> &[AiEvent] {
+        &self.events
+    }
+
+    pub(crate) fn target_position(&self, id: u32) -> Option<Vec2> {
+        self.targets.position(id).map(|(x, y)| Vec2::new(x, y))
+    }
+
+    pub(crate) fn clear(&self, from: Vec2, to: Vec2) -> bool {
+        line_clear(
+            self.grid.as_ref(),
+            self.occluders.values(),
+            &self.obstacles,
+            from,
+            to,
+        )
+    }
+
+    /// A copy of what every observer can see, for judging points long after
+    /// the observers have moved on.
+    pub(crate) fn sight(&self) -> Sight {
+        Sight {
+            viewers: self
+                .observers
+                .values()
+                .map(|observer| Viewer {
+                    position: observer.position,
+                    facing: observer.facing,
+                    view_distance: observer.view_distance,
+                    cos_half_fov: observer.cos_half_fov,
+                })
+                .collect(),
+            grid: self.grid.clone(),
+            occluders: self.occluders.values().copied().collect(),
+            obstacles: self.obstacles.clone(),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Viewer {
+    position: Vec2,
+    facing: Vec2,
+    view_distance: f32,
+    cos_half_fov: f32,
+}
+
+/// Observers' view cones and everything that blocks them, from
+/// `Perception::sight`.
+#[derive(Clone)]
+pub(crate) struct Sight {
+    viewers: Vec<Viewer>,
+    grid: Option<Grid>,
+    occluders: Vec<(Vec2, Vec2)>,
+    obstacles: ObstacleSet,
+}
+
+impl Sight {
+    /// Whether any observer would see `point`, as `Perception::update` judges.
+    pub(crate) fn sees(&self, point: Vec2) -> bool {
+        self.viewers.iter().any(|viewer| {
+            let offset = point - viewer.position;
+            let distance = offset.length();
+            distance <= viewer.view_distance
+                && (distance <= f32::EPSILON
+                    || viewer.facing.dot(offset / distance) >= viewer.cos_half_fov)
+                && line_clear(
+                    self.grid.as_ref(),
+                    &self.occluders,
+                    &self.obstacles,
+                    viewer.position,
+                    point,
+                )
+        })
+    }
+}
+
+/// Whether nothing blocks the segment from `from` to `to`: no wall cell of
+/// `grid` on the line between their cells, no occluder crossing it and no
+/// obstacle in the way.
+fn line_clear<'a>(
+    grid: Option<&Grid>,
+    occluders: impl IntoIterator<Item = &'a (Vec2, Vec2)>,
+    obstacles: &ObstacleSet,
+    from: Vec2,
+    to: Vec2,
+) -> bool {
+    if let Some(grid) = grid {
+        let cell = |p: Vec2| (p.x.round() as i32, p.y.round() as i32);
+        let ((x0, y0), (x1, y1)) = (cell(from), cell(to));
+        if !grid.line_of_sight(x0, y0, x1, y1) {
+            return false;
+        }
+    }
+    !occluders
+        .into_iter()
+        .any(|&(start, end)| segments_cross(from, to, start, end))
+        && obstacles.count_blocking(from, to) == 0
+}
+
+/// Whether segments `a0-a1` and `b0-b1` intersect.
+pub(crate) fn segments_cross(a0: Vec2, a1: Vec2, b0: Vec2, b1: Vec2) -> bool {
+    let (a, b) = (a1 - a0, b1 - b0);
+    let denominator = a.cross(b);
+    if denominator.abs() <= f32::EPSILON {
+        return false;
+    }
+    let offset = b0 - a0;
+    let t = offset.cross(b) / denominator;
+    let u = offset.cross(a) / denominator;
+    (0.0..=1.0).contains(&t) && (0.0..=1.0).contains(&u)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_a_view_distance_that_is_not_finite() {
+        let mut perception = Perception::new();
+        let (eye, facing) = (Vec2::ZERO, Vec2::new(1.0, 0.0));
+        assert!(perception
+            .add_observer(eye, facing, f32::INFINITY, 1.0)
+            .is_err());
+        assert!(perception.add_observer(eye, facing, f32::NAN, 1.0).is_err());
+        assert!(perception.add_observer(eye, facing, 10.0, 1.0).is_ok());
+    }
+}
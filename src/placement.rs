@@ -0,0 +1,397 @@
+use wasm_bindgen::prelude::*;
+
+use crate::error::Error;
+use crate::grid::Grid;
+use crate::influence::InfluenceMap;
+use crate::math::Vec2;
+use crate::navmesh::NavMesh;
+use crate::perception::{Perception, Sight};
+
+#[derive(Clone)]
+enum Rule {
+    /// Discards positions nearer than `min` to any point, or farther than
+    /// `max` from all of them.
+    Distance {
+        points: Vec<Vec2>,
+        min: f32,
+        max: f32,
+    },
+    /// Discards positions off the walkable mesh.
+    OnNavMesh { mesh: NavMesh, height: f32 },
+    /// Discards positions any viewer within `range` has a line of sight to.
+    HiddenFrom {
+        grid: Grid,
+        viewers: Vec<Vec2>,
+        range: f32,
+    },
+    /// Discards positions a perception observer would see.
+    UnseenBy { sight: Sight },
+    /// Discards positions where the map's value is outside `min..=max`.
+    InfluenceBand {
+        map: InfluenceMap,
+        min: f32,
+        max: f32,
+    },
+    /// The map's value rescaled from its lowest to its highest to `[0, 1]`,
+    /// or one minus that when `avoid` is set.
+    Influence {
+        map: InfluenceMap,
+        low: f32,
+        high: f32,
+        avoid: bool,
+        weight: f32,
+    },
+    /// 1 at `ideal` distance from the nearest point, falling to 0 at twice or
+    /// no distance.
+    PreferDistance {
+        points: Vec<Vec2>,
+        ideal: f32,
+        weight: f32,
+    },
+}
+
+/// Ranks candidate points for spawns, loot and objectives: requirements such as
+/// a minimum distance from every player, lying on the navmesh or staying out
+/// of every agent's sight discard candidates, and weighted preferences such as
+/// the heat of an influence map score the rest in `[0, 1]`, like
+/// `TacticalQuery`.
+///
+/// Points are 2D: grid cell coordinates, or `(x, z)` on a navmesh. Grids,
+/// meshes, maps and perceptions handed to the query are copied, so it can be
+/// kept and rerun while the game goes on; set its rules again to pick up
+/// changes.
+#[wasm_bindgen]
+#[derive(Clone, Default)]
+pub struct PlacementQuery {
+    rules: Vec<Rule>,
+    candidates: Vec<Vec2>,
+    /// Least distance between two returned points, so spawns do not stack.
+    pub min_spacing: f32,
+}
+
+#[wasm_bindgen]
+impl PlacementQuery {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> PlacementQuery {
+        PlacementQuery::default()
+    }
+
+    /// Uses every walkable cell of `grid`, `spacing` cells apart, as a candidate.
+    pub fn candidates_on_grid(&mut self, grid: &Grid, spacing: u32) {
+        let spacing = spacing.max(1) as usize;
+        self.candidates.clear();
+        for y in (0..grid.height() as i32).step_by(spacing) {
+            for x in (0..grid.width() as i32).step_by(spacing) {
+                if grid.is_walkable(x, y) {
+                    self.candidates.push(Vec2::new(x as f32, y as f32));
+                }
+            }
+        }
+    }
+
+    /// Uses points `spacing` apart on the XZ plane wherever the walkable part
+    /// of `mesh` lies under them as candidates.
+    pub fn candidates_on_navmesh(&mut self, mesh: &NavMesh, spacing: f32) -> Result<(), Error> {
+        if spacing.is_nan() || spacing <= 0.0 {
+            return Err(Error::InvalidInput(format!(
+                "spacing must be positive, got {}",
+                spacing
+            )));
+        }
+        self.candidates.clear();
+        let Some((min, max)) = bounds(mesh) else {
+            return Ok(());
+        };
+        let columns = ((max[0] - min[0]) / spacing).floor() as u32 + 1;
+        let rows = ((max[2] - min[2]) / spacing).floor() as u32 + 1;
+        let height = (min[1] + max[1]) * 0.5;
+        for row in 0..rows {
+            for column in 0..columns {
+                let point = Vec2::new(
+                    min[0] + column as f32 * spacing,
+                    min[2] + row as f32 * spacing,
+                );
+                if on_mesh(mesh, point, height) {
+                    self.candidates.push(point);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Uses flat `[x0, y0, x1, y1, ...]` positions as candidates, such as
+    /// hand-placed spawn markers.
+    pub fn set_candidates(&mut self, points: &[f32]) -> Result<(), Error> {
+        self.candidates = pairs(points)?;
+        Ok(())
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn candidate_count(&self) -> u32 {
+        self.candidates.len() as u32
+    }
+
+    /// Keeps only candidates at least `min` from every point of flat
+    /// `[x0, y0, ...]` `points`, such as the players, and within `max` of at
+    /// least one; pass `Infinity` for no upper bound.
+    pub fn require_distance_from(
+        &mut self,
+        points: &[f32],
+        min: f32,
+        max: f32,
+    ) -> Result<(), Error> {
+        self.rules.push(Rule::Distance {
+            points: pairs(points)?,
+            min,
+            max,
+        });
+        Ok(())
+    }
+
+    /// Keeps only candidates over the walkable part of `mesh`, looking for it
+    /// nearest `height` where floors overlap.
+    pub fn require_on_navmesh(&mut self, mesh: &NavMesh, height: f32) {
+        self.rules.push(Rule::OnNavMesh {
+            mesh: mesh.clone(),
+            height,
+        });
+    }
+
+    /// Keeps only candidates that no viewer among flat `[x0, y0, ...]`
+    /// positions within `range` has a line of sight to through `grid`.
+    pub fn require_hidden_from(
+        &mut self,
+        grid: &Grid,
+        viewers: &[f32],
+        range: f32,
+    ) -> Result<(), Error> {
+        self.rules.push(Rule::HiddenFrom {
+            grid: grid.clone(),
+            viewers: pairs(viewers)?,
+            range,
+        });
+        Ok(())
+    }
+
+    /// Keeps only candidates none of the observers of `perception` would see
+    /// where they stand now, with their view cones, walls, occluders and
+    /// obstacles.
+    pub fn require_unseen_by(&mut self, perception: &Perception) {
+        self.rules.push(Rule::UnseenBy {
+            sight: perception.sight(),
+        });
+    }
+
+    /// Keeps only candidates where `map` reads between `min` and `max`, e.g.
+    /// away from the front line of a territory map.
+    pub fn require_influence(&mut self, map: &InfluenceMap, min: f32, max: f32) {
+        self.rules.push(Rule::InfluenceBand {
+            map: map.clone(),
+            min,
+            max,
+        });
+    }
+
+    /// Prefers candidates where `map` is high, relative to its range.
+    pub fn prefer_influence(&mut self, map: &InfluenceMap, weight: f32) {
+        self.push_influence(map, false, weight);
+    }
+
+    /// Prefers candidates where `map` is low, e.g. far from recent deaths.
+    pub fn avoid_influence(&mut self, map: &InfluenceMap, weight: f32) {
+        self.push_influence(map, true, weight);
+    }
+
+    /// Prefers candidates about `ideal` from the nearest point of flat
+    /// `[x0, y0, ...]` `points`, e.g. just out of sight range of the players.
+    pub fn prefer_distance_from(
+        &mut self,
+        points: &[f32],
+        ideal: f32,
+        weight: f32,
+    ) -> Result<(), Error> {
+        self.rules.push(Rule::PreferDistance {
+            points: pairs(points)?,
+            ideal: ideal.max(0.0),
+            weight,
+        });
+        Ok(())
+    }
+
+    /// Removes every rule but keeps the candidates.
+    pub fn clear_rules(&mut self) {
+        self.rules.clear();
+    }
+
+    /// The best `count` candidates as flat `[x0, y0, score0, x1, y1, score1, ...]`,
+    /// highest score first and at least `min_spacing` apart.
+    pub fn run(&self, count: u32) -> Vec<f32> {
+        let total_weight: f32 = self.rules.iter().map(weight).sum();
+        let mut scored: Vec<(f32, Vec2)> = self
+            .candidates
+            .iter()
+            .filter_map(|&candidate| {
+                // Requirements first, so rejected candidates skip the scoring.
+                let (requirements, preferences) = (
+                    self.rules.iter().filter(|rule| weight(rule) == 0.0),
+                    self.rules.iter().filter(|rule| weight(rule) != 0.0),
+                );
+                let mut sum = 0.0;
+                for rule in requirements.chain(preferences) {
+                    sum += weight(rule) * score(rule, candidate)?;
+                }
+                let score = if total_weight > 0.0 {
+                    sum / total_weight
+                } else {
+                    0.0
+                };
+                Some((score, candidate))
+            })
+            .collect();
+        scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+        let mut picked: Vec<(f32, Vec2)> = Vec::new();
+        for (score, point) in scored {
+            if picked.len() >= count as usize {
+                break;
+            }
+            if picked
+                .iter()
+                .all(|&(_, other)| other.distance(point) >= self.min_spacing)
+            {
+                picked.push((score, point));
+            }
+        }
+        picked
+            .into_iter()
+            .flat_map(|(score, point)| [point.x, point.y, score])
+            .collect()
+    }
+}
+
+impl PlacementQuery {
+    fn push_influence(&mut self, map: &InfluenceMap, avoid: bool, weight: f32) {
+        let values = map.values();
+        let low = values.iter().copied().fold(f32::INFINITY, f32::min);
+        let high = values.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+        self.rules.push(Rule::Influence {
+            map: map.clone(),
+            low,
+            high,
+            avoid,
+            weight: weight.max(0.0),
+        });
+    }
+}
+
+fn pairs(points: &[f32]) -> Result<Vec<Vec2>, Error> {
+    if !points.len().is_multiple_of(2) {
+        return Err(Error::InvalidInput("points must be x, y pairs".into()));
+    }
+    Ok(points
+        .chunks_exact(2)
+        .map(|p| Vec2::new(p[0], p[1]))
+        .collect())
+}
+
+fn bounds(mesh: &NavMesh) -> Option<([f32; 3], [f32; 3])> {
+    let first = *mesh.vertices.first()?;
+    Some(mesh.vertices.iter().fold((first, first), |(min, max), p| {
+        (
+            [min[0].min(p[0]), min[1].min(p[1]), min[2].min(p[2])],
+            [max[0].max(p[0]), max[1].max(p[1]), max[2].max(p[2])],
+        )
+    }))
+}
+
+fn on_mesh(mesh: &NavMesh, point: Vec2, height: f32) -> bool {
+    mesh.locate([point.x, height, point.y])
+        .is_some_and(|triangle| !mesh.is_blocked(triangle))
+}
+
+fn weight(rule: &Rule) -> f32 {
+    match rule {
+        Rule::Influence { weight, .. } | Rule::PreferDistance { weight, .. } => *weight,
+        _ => 0.0,
+    }
+}
+
+/// A rule's score for `candidate`, or `None` when a requirement rejects it.
+fn score(rule: &Rule, candidate: Vec2) -> Option<f32> {
+    Some(match rule {
+        Rule::Distance { points, min, max } => {
+            let nearest = points
+                .iter()
+                .map(|point| point.distance(candidate))
+                .fold(f32::INFINITY, f32::min);
+            if nearest < *min || (!points.is_empty() && nearest > *max) {
+                return None;
+            }
+            0.0
+        }
+        Rule::OnNavMesh { mesh, height } => {
+            if !on_mesh(mesh, candidate, *height) {
+                return None;
+            }
+            0.0
+        }
+        Rule::HiddenFrom {
+            grid,
+            viewers,
+            range,
+        } => {
+            let (x, y) = (candidate.x.round() as i32, candidate.y.round() as i32);
+            let seen = viewers.iter().any(|viewer| {
+                viewer.distance(candidate) <= *range
+                    && grid.line_of_sight(viewer.x.round() as i32, viewer.y.round() as i32, x, y)
+            });
+            if seen {
+                return None;
+            }
+            0.0
+        }
+        Rule::UnseenBy { sight } => {
+            if sight.sees(candidate) {
+                return None;
+            }
+            0.0
+        }
+        Rule::InfluenceBand { map, min, max } => {
+            let value = map.sample(candidate);
+            if value < *min || value > *max {
+                return None;
+            }
+            0.0
+        }
+        Rule::Influence {
+            map,
+            low,
+            high,
+            avoid,
+            ..
+        } => {
+            let heat = if high > low {
+                ((map.sample(candidate) - low) / (high - low)).clamp(0.0, 1.0)
+            } else {
+                0.0
+            };
+            if *avoid {
+                1.0 - heat
+            } else {
+                heat
+            }
+        }
+        Rule::PreferDistance { points, ideal, .. } => {
+            let nearest = points
+                .iter()
+                .map(|point| point.distance(candidate))
+                .fold(f32::INFINITY, f32::min);
+            if !nearest.is_finite() {
+                0.0
+            } else if *ideal > 0.0 {
+                (1.0 - (nearest - ideal).abs() / ideal).max(0.0)
+            } else {
+                1.0 / (1.0 + nearest)
+            }
+        }
+    })
+}
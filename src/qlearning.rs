@@ -0,0 +1,208 @@
+use wasm_bindgen::prelude::*;
+
+use crate::error::Error;
+use crate::random::Rng;
+
+/// Tabular reinforcement learning over a small discrete world: one value per
+/// state and action, learned from rewards as the agent plays. `update` learns
+/// off-policy (Q-learning) and `update_sarsa` on-policy (SARSA), so an agent can
+/// pick whichever suits it.
+///
+/// States and actions are indices the caller assigns, e.g. a bucketed health and
+/// distance for the state and one of a few tactics for the action.
+#[wasm_bindgen]
+pub struct QLearner {
+    states: u32,
+    actions: u32,
+    table: Vec<f32>,
+    /// How far each update moves a value toward its target, in `[0, 1]`.
+    pub learning_rate: f32,
+    /// Weight of future rewards against the immediate one, in `[0, 1)`.
+    pub discount: f32,
+    /// Chance that `choose_action` explores with a random action.
+    pub epsilon: f32,
+    /// Factor `decay_epsilon` applies, never going below `min_epsilon`.
+    pub epsilon_decay: f32,
+    pub min_epsilon: f32,
+    rng: Rng,
+}
+
+#[wasm_bindgen]
+impl QLearner {
+    /// A table of zeros for `states` states and `actions` actions.
+    #[wasm_bindgen(constructor)]
+    pub fn new(states: u32, actions: u32) -> Result<QLearner, Error> {
+        if states == 0 || actions == 0 {
+            return Err(Error::InvalidInput(
+                "state and action counts must be positive".into(),
+            ));
+        }
+        Ok(QLearner {
+            states,
+            actions,
+            table: vec![0.0; states as usize * actions as usize],
+            learning_rate: 0.1,
+            discount: 0.95,
+            epsilon: 0.1,
+            epsilon_decay: 0.995,
+            min_epsilon: 0.01,
+            rng: Rng::default(),
+        })
+    }
+
+    pub fn set_seed(&mut self, seed: u64) {
+        self.rng = Rng::new(seed);
+    }
+
+    /// Explores with a copy of `rng` from now on.
+    pub fn set_rng(&mut self, rng: &Rng) {
+        self.rng = rng.clone();
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn state_count(&self) -> u32 {
+        self.states
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn action_count(&self) -> u32 {
+        self.actions
+    }
+
+    /// A random action with chance `epsilon`, the best known one otherwise.
+    pub fn choose_action(&mut self, state: u32) -> Result<u32, Error> {
+        self.check_state(state)?;
+        if self.rng.chance(self.epsilon) {
+            Ok(self.rng.below(self.actions as usize) as u32)
+        } else {
+            self.best_action(state)
+        }
+    }
+
+    /// The action with the highest value in `state`, the lowest index on ties.
+    pub fn best_action(&self, state: u32) -> Result<u32, Error> {
+        let row = self.row(state)?;
+        let mut best = 0;
+        for (action, &value) in row.iter().enumerate() {
+            if value > row[best] {
+                best = action;
+            }
+        }
+        Ok(best as u32)
+    }
+
+    pub fn value(&self, state: u32, action: u32) -> Result<f32, Error> {
+        self.check_action(action)?;
+        Ok(self.row(state)?[action as usize])
+    }
+
+    /// The values of every action in `state`.
+    pub fn values(&self, state: u32) -> Result<Vec<f32>, Error> {
+        self.row(state).map(<[f32]>::to_vec)
+    }
+
+    /// Learns from taking `action` in `state`, receiving `reward` and landing in
+    /// `next_state`, toward the best action there. Returns the temporal-difference
+    /// error, which shrinks as the values settle.
+    pub fn update(
+        &mut self,
+        state: u32,
+        action: u32,
+        reward: f32,
+        next_state: u32,
+    ) -> Result<f32, Error> {
+        let best = self.best_action(next_state)?;
+        let future = self.table[self.index(next_state, best)];
+        self.learn(state, action, reward + self.discount * future)
+    }
+
+    /// Like `update`, toward the action the policy actually took next.
+    pub fn update_sarsa(
+        &mut self,
+        state: u32,
+        action: u32,
+        reward: f32,
+        next_state: u32,
+        next_action: u32,
+    ) -> Result<f32, Error> {
+        let future = self.value(next_state, next_action)?;
+        self.learn(state, action, reward + self.discount * future)
+    }
+
+    /// Learns from an action that ended the episode, with no future reward.
+    pub fn update_terminal(&mut self, state: u32, action: u32, reward: f32) -> Result<f32, Error> {
+        self.learn(state, action, reward)
+    }
+
+    /// Shrinks `epsilon` by `epsilon_decay`, e.g. once per episode.
+    pub fn decay_epsilon(&mut self) {
+        self.epsilon = (self.epsilon * self.epsilon_decay).max(self.min_epsilon);
+    }
+
+    /// The whole table, row by row: every action's value for state 0, then state
+    /// 1, and so on.
+    pub fn table(&self) -> Vec<f32> {
+        self.table.clone()
+    }
+
+    /// Replaces the table with one from `table`, e.g. saved from an earlier session.
+    pub fn set_table(&mut self, table: &[f32]) -> Result<(), Error> {
+        if table.len() != self.table.len() {
+            return Err(Error::InvalidInput(format!(
+                "expected {} values, got {}",
+                self.table.len(),
+                table.len()
+            )));
+        }
+        self.table.copy_from_slice(table);
+        Ok(())
+    }
+
+    /// Sets every value back to zero.
+    pub fn clear(&mut self) {
+        self.table.fill(0.0);
+    }
+}
+
+impl QLearner {
+    fn learn(&mut self, state: u32, action: u32, target: f32) -> Result<f32, Error> {
+        self.check_state(state)?;
+        self.check_action(action)?;
+        let index = self.index(state, action);
+        let error = target - self.table[index];
+        self.table[index] += self.learning_rate * error;
+        Ok(error)
+    }
+
+    fn index(&self, state: u32, action: u32) -> usize {
+        state as usize * self.actions as usize + action as usize
+    }
+
+    fn row(&self, state: u32) -> Result<&[f32], Error> {
+        self.check_state(state)?;
+        let start = self.index(state, 0);
+        Ok(&self.table[start..start + self.actions as usize])
+    }
+
+    fn check_state(&self, state: u32) -> Result<(), Error> {
+        if state < self.states {
+            Ok(())
+        } else {
+            Err(Error::InvalidInput(format!(
+                "state {} is out of range for {} states",
+                state, self.states
+            )))
+        }
+    }
+
+    fn check_action(&self, action: u32) -> Result<(), Error> {
+        if action < self.actions {
+            Ok(())
+        } else {
+            Err(Error::InvalidInput(format!(
+                "action {} is out of range for {} actions",
+                action, self.actions
+            )))
+        }
+    }
+}
@@ -0,0 +1,359 @@
+use std::collections::HashMap;
+
+use wasm_bindgen::prelude::*;
+
+use crate::debug::{self, DebugGeometry};
+use crate::error::Error;
+use crate::events::{AiEvent, EventKind};
+use crate::math::Vec2;
+
+/// Fraction of the spacing within which a member counts as standing on its
+/// spot, and beyond which the one ahead counts as having left it.
+const SETTLE_FRACTION: f32 = 0.5;
+
+#[derive(Clone, Debug)]
+struct Member {
+    agent: u32,
+    /// The spot the member stands on or walks to, 0 at the front. It moves up
+    /// one spot at a time once the spot ahead is free, so a line shuffles
+    /// forward from the front instead of all at once.
+    spot: u32,
+    /// Position in line last reported in an event.
+    reported: Option<u32>,
+}
+
+#[derive(Clone, Debug)]
+struct Line {
+    /// From the front, e.g. a counter, back along the way the line runs.
+    points: Vec<Vec2>,
+    /// Distance along the line at each point.
+    lengths: Vec<f32>,
+    spacing: f32,
+    join_radius: f32,
+    members: Vec<Member>,
+}
+
+impl Line {
+    /// Where spot `spot` stands: `spot * spacing` back along the line, then
+    /// on past its last point in the direction of its last stretch.
+    fn spot(&self, spot: u32) -> Vec2 {
+        let distance = spot as f32 * self.spacing;
+        // The stretch the spot falls on, the last one for spots past the end.
+        let segment = self
+            .lengths
+            .partition_point(|&length| length <= distance)
+            .clamp(1, self.points.len() - 1);
+        let (from, to) = (self.points[segment - 1], self.points[segment]);
+        from + (to - from).normalize() * (distance - self.lengths[segment - 1])
+    }
+
+    fn length(&self) -> f32 {
+        self.lengths[self.lengths.len() - 1]
+    }
+}
+
+/// Orderly lines at doorways, counters and rides: agents heading for a queue
+/// join at its back when they come near it, stand `spacing` apart along it
+/// and shuffle forward one after another as the front is served and leaves.
+///
+/// A queue runs from its front back along a polyline, and past its last point
+/// in the direction of its last stretch when it grows longer. Agents steer
+/// toward `target` themselves, e.g. with `Agent::arrive`, and face `facing`.
+/// `update` joins agents and moves members up; each change of an agent's place
+/// in line comes out as a `QueuePosition` event with the agent as `subject`
+/// and its place, 0 at the front, as `other`.
+#[wasm_bindgen]
+#[derive(Clone, Debug, Default)]
+pub struct Queues {
+    lines: Vec<Line>,
+    /// Queue each approaching agent is heading for.
+    approaching: HashMap<u32, u32>,
+    /// Queue each member stands in.
+    membership: HashMap<u32, u32>,
+    events: Vec<AiEvent>,
+}
+
+#[wasm_bindgen]
+impl Queues {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Queues {
+        Queues::default()
+    }
+
+    /// Adds a queue along flat `[x, y, ...]` points from its front backward,
+    /// at least two, with members `spacing` apart, and returns its id.
+    /// Approaching agents join once within `join_radius` of its back.
+    pub fn add_queue(
+        &mut self,
+        points: &[f32],
+        spacing: f32,
+        join_radius: f32,
+    ) -> Result<u32, Error> {
+        if !points.len().is_multiple_of(2) || points.len() < 4 {
+            return Err(Error::InvalidInput(
+                "a queue needs at least two [x, y] points".into(),
+            ));
+        }
+        if spacing.is_nan() || spacing <= 0.0 {
+            return Err(Error::InvalidInput("queue spacing must be positive".into()));
+        }
+        let points: Vec<Vec2> = points
+            .chunks_exact(2)
+            .map(|p| Vec2::new(p[0], p[1]))
+            .collect();
+        let mut total = 0.0;
+        let mut lengths = Vec::with_capacity(points.len());
+        for (i, &point) in points.iter().enumerate() {
+            if i > 0 {
+                total += point.distance(points[i - 1]);
+            }
+            lengths.push(total);
+        }
+        if total <= 0.0 {
+            return Err(Error::InvalidInput("a queue needs a direction".into()));
+        }
+        self.lines.push(Line {
+            points,
+            lengths,
+            spacing,
+            join_radius,
+            members: Vec::new(),
+        });
+        Ok(self.lines.len() as u32 - 1)
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn queue_count(&self) -> u32 {
+        self.lines.len() as u32
+    }
+
+    /// Sends `agent` toward the back of `queue`, to join it once near, taking
+    /// it out of any line it stands in.
+    pub fn approach(&mut self, agent: u32, queue: u32) -> Result<(), Error> {
+        self.check(queue)?;
+        self.leave(agent);
+        self.approaching.insert(agent, queue);
+        Ok(())
+    }
+
+    /// Puts `agent` at the back of `queue` at once, wherever it is.
+    pub fn join(&mut self, agent: u32, queue: u32) -> Result<(), Error> {
+        self.check(queue)?;
+        self.leave(agent);
+        let line = &mut self.lines[queue as usize];
+        line.members.push(Member {
+            agent,
+            spot: line.members.len() as u32,
+            reported: None,
+        });
+        self.membership.insert(agent, queue);
+        Ok(())
+    }
+
+    /// Takes `agent` out of its line, or stops it approaching one, returning
+    /// whether it was in either. Those behind it close up.
+    pub fn leave(&mut self, agent: u32) -> bool {
+        if self.approaching.remove(&agent).is_some() {
+            return true;
+        }
+        let Some(queue) = self.membership.remove(&agent) else {
+            return false;
+        };
+        let members = &mut self.lines[queue as usize].members;
+        members.retain(|member| member.agent != agent);
+        true
+    }
+
+    /// Takes the agent at the front of `queue` out of it, e.g. once the
+    /// counter is free, and returns it.
+    pub fn serve(&mut self, queue: u32) -> Option<u32> {
+        let agent = self.lines.get(queue as usize)?.members.first()?.agent;
+        self.leave(agent);
+        Some(agent)
+    }
+
+    /// Agents in `queue`, front first.
+    pub fn members(&self, queue: u32) -> Vec<u32> {
+        self.lines
+            .get(queue as usize)
+            .map(|line| line.members.iter().map(|member| member.agent).collect())
+            .unwrap_or_default()
+    }
+
+    /// Agents standing in `queue`.
+    pub fn queue_len(&self, queue: u32) -> u32 {
+        self.lines
+            .get(queue as usize)
+            .map_or(0, |line| line.members.len() as u32)
+    }
+
+    /// The agent at the front of `queue`.
+    pub fn front(&self, queue: u32) -> Option<u32> {
+        self.lines
+            .get(queue as usize)?
+            .members
+            .first()
+            .map(|member| member.agent)
+    }
+
+    /// The queue `agent` stands in.
+    pub fn queue_of(&self, agent: u32) -> Option<u32> {
+        self.membership.get(&agent).copied()
+    }
+
+    /// Whether `agent` is on its way to a queue, not yet in it.
+    pub fn is_approaching(&self, agent: u32) -> bool {
+        self.approaching.contains_key(&agent)
+    }
+
+    /// `agent`'s place in its line, 0 at the front.
+    pub fn place(&self, agent: u32) -> Option<u32> {
+        let (_, index) = self.find(agent)?;
+        Some(index as u32)
+    }
+
+    /// Where `agent` should head: its spot in line, or the back of the queue
+    /// it approaches.
+    pub fn target(&self, agent: u32) -> Option<Vec2> {
+        if let Some(&queue) = self.approaching.get(&agent) {
+            let line = &self.lines[queue as usize];
+            return Some(line.spot(line.members.len() as u32));
+        }
+        let (line, index) = self.find(agent)?;
+        Some(line.spot(line.members[index].spot))
+    }
+
+    /// Which way `agent` should face while it stands in line: toward the spot
+    /// ahead, and along the line at the front.
+    pub fn facing(&self, agent: u32) -> Option<Vec2> {
+        let (line, index) = self.find(agent)?;
+        let spot = line.members[index].spot;
+        let direction = if spot == 0 {
+            line.spot(0) - line.spot(1)
+        } else {
+            line.spot(spot - 1) - line.spot(spot)
+        };
+        Some(direction.normalize())
+    }
+
+    /// Joins approaching agents that reached the back of their queue and
+    /// moves members up into free spots, given the flat `[x, y, ...]`
+    /// `positions` of the agents `ids`. Agents left out count as wherever
+    /// they should be.
+    pub fn update(&mut self, ids: &[u32], positions: &[f32]) -> Result<(), Error> {
+        if positions.len() != ids.len() * 2 {
+            return Err(Error::InvalidInput(format!(
+                "expected {} positions for {} agents, got {}",
+                ids.len() * 2,
+                ids.len(),
+                positions.len()
+            )));
+        }
+        self.events.clear();
+        let located: HashMap<u32, Vec2> = ids
+            .iter()
+            .zip(positions.chunks_exact(2))
+            .map(|(&id, p)| (id, Vec2::new(p[0], p[1])))
+            .collect();
+
+        // Nearest first, so two arriving together join in the order they came.
+        let mut arriving: Vec<(f32, u32, u32)> = self
+            .approaching
+            .iter()
+            .filter_map(|(&agent, &queue)| {
+                let position = located.get(&agent)?;
+                let line = &self.lines[queue as usize];
+                let distance = position.distance(line.spot(line.members.len() as u32));
+                (distance <= line.join_radius).then_some((distance, agent, queue))
+            })
+            .collect();
+        arriving.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+        for (_, agent, queue) in arriving {
+            self.approaching.remove(&agent);
+            let line = &mut self.lines[queue as usize];
+            line.members.push(Member {
+                agent,
+                spot: line.members.len() as u32,
+                reported: None,
+            });
+            self.membership.insert(agent, queue);
+        }
+
+        for line in &mut self.lines {
+            let settle = line.spacing * SETTLE_FRACTION;
+            for index in 0..line.members.len() {
+                let member = &line.members[index];
+                let next = member.spot.saturating_sub(1);
+                if member.spot > index as u32 {
+                    let settled = located
+                        .get(&member.agent)
+                        .is_none_or(|position| position.distance(line.spot(member.spot)) <= settle);
+                    let ahead_free = index == 0 || {
+                        let ahead = &line.members[index - 1];
+                        ahead.spot < next
+                            && located
+                                .get(&ahead.agent)
+                                .is_none_or(|position| position.distance(line.spot(next)) > settle)
+                    };
+                    if settled && ahead_free {
+                        line.members[index].spot = next;
+                    }
+                }
+                let member = &mut line.members[index];
+                if member.reported != Some(index as u32) {
+                    member.reported = Some(index as u32);
+                    self.events.push(AiEvent::new(
+                        EventKind::QueuePosition,
+                        member.agent,
+                        index as u32,
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// `QueuePosition` events of the last `update`.
+    pub fn events(&self) -> Vec<AiEvent> {
+        self.events.clone()
+    }
+
+    /// Appends each queue to `out` as its line with a point on every spot
+    /// taken.
+    pub fn debug_geometry(&self, out: &mut DebugGeometry) {
+        for line in &self.lines {
+            let back = line.members.len() as u32;
+            let mut points = line.points.clone();
+            if back as f32 * line.spacing > line.length() {
+                points.push(line.spot(back));
+            }
+            out.polyline(&points, debug::PATH);
+            for member in &line.members {
+                out.point(line.spot(member.spot), debug::TARGET);
+            }
+        }
+    }
+}
+
+impl Queues {
+    pub(crate) fn recent_events(&self) -> &[AiEvent] {
+        &self.events
+    }
+
+    fn check(&self, queue: u32) -> Result<(), Error> {
+        if (queue as usize) < self.lines.len() {
+            Ok(())
+        } else {
+            Err(Error::InvalidInput(format!("unknown queue {}", queue)))
+        }
+    }
+
+    fn find(&self, agent: u32) -> Option<(&Line, usize)> {
+        let line = &self.lines[*self.membership.get(&agent)? as usize];
+        let index = line
+            .members
+            .iter()
+            .position(|member| member.agent == agent)?;
+        Some((line, index))
+    }
+}
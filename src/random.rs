@@ -0,0 +1,156 @@
+use wasm_bindgen::prelude::*;
+
+use crate::error::Error;
+
+const MULTIPLIER: u64 = 6_364_136_223_846_793_005;
+
+/// Seed of the generators systems start with until given one of their own.
+pub(crate) const DEFAULT_SEED: u64 = 0x9e37_79b9;
+
+/// The PCG32 generator every stochastic system in the crate draws from, so that
+/// a run seeded the same way plays out the same way, for replays and lockstep
+/// multiplayer.
+///
+/// Each generator has a seed and a stream; different streams give independent
+/// sequences from the same seed. Give each agent or system its own with
+/// `stream(id)` or `split`, and hand it over with that system's `set_rng`, so
+/// that adding or removing one never shifts the numbers the others see.
+/// Seeds are `u64` here and in every system's `set_seed` or constructor, a
+/// `BigInt` from JS.
+#[wasm_bindgen]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Rng {
+    state: u64,
+    /// Odd step that selects the stream.
+    increment: u64,
+}
+
+impl Default for Rng {
+    fn default() -> Rng {
+        Rng::new(DEFAULT_SEED)
+    }
+}
+
+#[wasm_bindgen]
+impl Rng {
+    /// A generator on stream 0.
+    #[wasm_bindgen(constructor)]
+    pub fn new(seed: u64) -> Rng {
+        Rng::with_stream(seed, 0)
+    }
+
+    pub fn with_stream(seed: u64, stream: u64) -> Rng {
+        let mut rng = Rng {
+            state: 0,
+            increment: (stream << 1) | 1,
+        };
+        rng.next_u32();
+        rng.state = rng.state.wrapping_add(seed);
+        rng.next_u32();
+        rng
+    }
+
+    /// Restarts the sequence from `seed`, on the same stream.
+    pub fn set_seed(&mut self, seed: u64) {
+        *self = Rng::with_stream(seed, self.increment >> 1);
+    }
+
+    /// A generator on stream `id` with this one's current state, e.g. one per
+    /// agent id. It does not advance this generator.
+    pub fn stream(&self, id: u64) -> Rng {
+        Rng::with_stream(self.state, id)
+    }
+
+    /// A new generator seeded from this one's next numbers, which it advances.
+    pub fn split(&mut self) -> Rng {
+        let seed = self.next_u64();
+        let stream = self.next_u64();
+        Rng::with_stream(seed, stream)
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        let old = self.state;
+        self.state = old.wrapping_mul(MULTIPLIER).wrapping_add(self.increment);
+        let shifted = (((old >> 18) ^ old) >> 27) as u32;
+        shifted.rotate_right((old >> 59) as u32)
+    }
+
+    /// Sample in `[0, 1)`.
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u32() >> 8) as f32 / (1u32 << 24) as f32
+    }
+
+    /// Sample in `[-1, 1)`.
+    pub fn next_signed(&mut self) -> f32 {
+        self.next_f32() * 2.0 - 1.0
+    }
+
+    /// Sample in `[min, max)`.
+    pub fn next_range(&mut self, min: f32, max: f32) -> f32 {
+        min + (max - min) * self.next_f32()
+    }
+
+    /// Sample in `0..len`, or 0 when `len` is 0.
+    pub fn next_index(&mut self, len: u32) -> u32 {
+        if len == 0 {
+            0
+        } else {
+            self.below(len as usize) as u32
+        }
+    }
+
+    /// Standard normal sample, by the Box-Muller transform.
+    pub fn next_gaussian(&mut self) -> f32 {
+        let u = 1.0 - self.next_f32();
+        let v = self.next_f32();
+        (-2.0 * u.ln()).sqrt() * (std::f32::consts::TAU * v).cos()
+    }
+
+    pub fn chance(&mut self, probability: f32) -> bool {
+        self.next_f32() < probability
+    }
+
+    /// The full state as four words, for `set_state`, e.g. in a save or replay.
+    pub fn state(&self) -> Vec<u32> {
+        vec![
+            (self.state >> 32) as u32,
+            self.state as u32,
+            (self.increment >> 32) as u32,
+            self.increment as u32,
+        ]
+    }
+
+    /// Resumes exactly where the generator that gave `state` left off.
+    pub fn set_state(&mut self, state: &[u32]) -> Result<(), Error> {
+        match *state {
+            [state_high, state_low, increment_high, increment_low] => {
+                self.state = (state_high as u64) << 32 | state_low as u64;
+                self.increment = ((increment_high as u64) << 32 | increment_low as u64) | 1;
+                Ok(())
+            }
+            _ => Err(Error::InvalidInput(format!(
+                "expected 4 state words, got {}",
+                state.len()
+            ))),
+        }
+    }
+}
+
+impl Rng {
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        (self.next_u32() as u64) << 32 | self.next_u32() as u64
+    }
+
+    /// Sample in `0..len`; `len` must be positive.
+    pub(crate) fn below(&mut self, len: usize) -> usize {
+        // Lemire's multiply-and-reject, which avoids the bias of a plain modulo.
+        let len = len as u32;
+        let threshold = len.wrapping_neg() % len;
+        loop {
+            let product = self.next_u32() as u64 * len as u64;
+            if product as u32 >= threshold {
+                return (product >> 32) as usize;
+            }
+        }
+    }
+}
@@ -0,0 +1,215 @@
+use wasm_bindgen::prelude::*;
+
+use crate::grid::Grid;
+use crate::math::{Vec2, Vec3};
+use crate::navmesh::{edge_key, height_on_triangle, NavMesh, Point};
+
+/// Where a ray first hit something opaque.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RaycastHit {
+    pub position: Vec2,
+    /// Unit normal of the face that was hit, pointing back toward the ray.
+    pub normal: Vec2,
+    pub distance: f32,
+    /// The blocking cell, which may lie outside the grid.
+    pub cell_x: i32,
+    pub cell_y: i32,
+}
+
+#[wasm_bindgen]
+impl Grid {
+    /// Whether the segment between two cell centers crosses only walkable cells.
+    /// Where it passes exactly through a cell corner, both cells beside the corner
+    /// must be walkable, matching the no-corner-cutting rule of the 8-connected
+    /// search.
+    pub fn line_of_sight(&self, x0: i32, y0: i32, x1: i32, y1: i32) -> bool {
+        let (dx, dy) = ((x1 - x0).abs(), (y1 - y0).abs());
+        let (step_x, step_y) = ((x1 - x0).signum(), (y1 - y0).signum());
+        let (mut x, mut y) = (x0, y0);
+        let mut error = dx - dy;
+        let mut remaining = dx + dy;
+        loop {
+            if !self.is_walkable(x, y) {
+                return false;
+            }
+            if remaining <= 0 {
+                return true;
+            }
+            if error > 0 {
+                x += step_x;
+                error -= 2 * dy;
+                remaining -= 1;
+            } else if error < 0 {
+                y += step_y;
+                error += 2 * dx;
+                remaining -= 1;
+            } else {
+                if !self.is_walkable(x + step_x, y) || !self.is_walkable(x, y + step_y) {
+                    return false;
+                }
+                x += step_x;
+                y += step_y;
+                error += 2 * (dx - dy);
+                remaining -= 2;
+            }
+        }
+    }
+
+    /// Casts a ray from `origin` along `direction` through the cells it crosses,
+    /// where each cell spans half a unit around its integer center, and returns
+    /// the first unwalkable or out-of-bounds cell it enters within
+    /// `max_distance`. A ray starting inside such a cell hits it at distance 0.
+    pub fn raycast(&self, origin: Vec2, direction: Vec2, max_distance: f32) -> Option<RaycastHit> {
+        let direction = direction.normalize();
+        let (mut x, mut y) = (origin.x.round() as i32, origin.y.round() as i32);
+        if !self.is_walkable(x, y) {
+            return Some(RaycastHit {
+                position: origin,
+                normal: -direction,
+                distance: 0.0,
+                cell_x: x,
+                cell_y: y,
+            });
+        }
+        if direction == Vec2::ZERO {
+            return None;
+        }
+
+        // Amanatides-Woo traversal: distance along the ray to the next vertical
+        // and horizontal cell boundary, and between consecutive ones.
+        let axis = |position: f32, cell: i32, direction: f32| {
+            if direction == 0.0 {
+                return (0, f32::INFINITY, f32::INFINITY);
+            }
+            let step = direction.signum() as i32;
+            let boundary = cell as f32 + 0.5 * step as f32;
+            (
+                step,
+                (boundary - position) / direction,
+                1.0 / direction.abs(),
+            )
+        };
+        let (step_x, mut next_x, delta_x) = axis(origin.x, x, direction.x);
+        let (step_y, mut next_y, delta_y) = axis(origin.y, y, direction.y);
+        loop {
+            let (distance, normal) = if next_x <= next_y {
+                x += step_x;
+                next_x += delta_x;
+                (next_x - delta_x, Vec2::new(-step_x as f32, 0.0))
+            } else {
+                y += step_y;
+                next_y += delta_y;
+                (next_y - delta_y, Vec2::new(0.0, -step_y as f32))
+            };
+            if distance > max_distance {
+                return None;
+            }
+            if !self.is_walkable(x, y) {
+                return Some(RaycastHit {
+                    position: origin + direction * distance,
+                    normal,
+                    distance,
+                    cell_x: x,
+                    cell_y: y,
+                });
+            }
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl NavMesh {
+    /// Walks the straight segment between two points across the mesh surface, on
+    /// the XZ plane, and returns where it first leaves the walkable mesh: through a
+    /// boundary edge or into a carved obstacle. Returns undefined when the whole
+    /// segment stays on the mesh, and the start itself when it is off the mesh.
+    pub fn raycast(
+        &self,
+        start_x: f32,
+        start_y: f32,
+        start_z: f32,
+        end_x: f32,
+        end_y: f32,
+        end_z: f32,
+    ) -> Option<Vec3> {
+        let (start, end) = ([start_x, start_y, start_z], [end_x, end_y, end_z]);
+        let hit = |point: Point| Some(Vec3::new(point[0], point[1], point[2]));
+        let Some(mut triangle) = self.locate(start).filter(|&t| !self.is_blocked(t)) else {
+            return hit(start);
+        };
+        let mut entered = 0.0;
+        // A straight segment crosses each triangle at most once.
+        for _ in 0..self.triangles.len() {
+            // The segment ends on the mesh once no edge is left to cross.
+            let (exit, edge) = self.segment_exit(triangle, start, end, entered)?;
+            let next = self.links[triangle]
+                .iter()
+                .find(|link| link.edge == edge)
+                .map(|link| link.triangle)
+                .filter(|&next| !self.is_blocked(next));
+            let Some(next) = next else {
+                let point = [
+                    start[0] + (end[0] - start[0]) * exit,
+                    0.0,
+                    start[2] + (end[2] - start[2]) * exit,
+                ];
+                let [a, b, c] = self.triangle(triangle);
+                let height = height_on_triangle(a, b, c, point)
+                    .unwrap_or(start[1] + (end[1] - start[1]) * exit);
+                return hit([point[0], height, point[2]]);
+            };
+            triangle = next;
+            entered = exit;
+        }
+        None
+    }
+
+    /// Whether the straight segment between two points stays on the walkable mesh.
+    pub fn line_of_sight(
+        &self,
+        start_x: f32,
+        start_y: f32,
+        start_z: f32,
+        end_x: f32,
+        end_y: f32,
+        end_z: f32,
+    ) -> bool {
+        self.raycast(start_x, start_y, start_z, end_x, end_y, end_z)
+            .is_none()
+    }
+}
+
+impl NavMesh {
+    /// Where the segment from `start` to `end` leaves `triangle` on XZ, as the
+    /// fraction along the segment and the edge it leaves through, or `None` when
+    /// the segment ends inside. Exits are clamped to no earlier than `entered`.
+    fn segment_exit(
+        &self,
+        triangle: usize,
+        start: Point,
+        end: Point,
+        entered: f32,
+    ) -> Option<(f32, (u32, u32))> {
+        let ids = self.triangles[triangle];
+        let corners = self.triangle(triangle);
+        let side = |p: Point, q: Point, r: Point| {
+            (q[0] - p[0]) * (r[2] - p[2]) - (q[2] - p[2]) * (r[0] - p[0])
+        };
+        let mut exit: Option<(f32, (u32, u32))> = None;
+        for i in 0..3 {
+            let (p, q, r) = (corners[i], corners[(i + 1) % 3], corners[(i + 2) % 3]);
+            // Signed so that the inside of the edge is positive.
+            let inside = side(p, q, r).signum();
+            let (from, to) = (inside * side(p, q, start), inside * side(p, q, end));
+            if to >= from {
+                continue;
+            }
+            let t = (from / (from - to)).max(entered);
+            if exit.is_none_or(|(best, _)| t < best) {
+                exit = Some((t, edge_key(ids[i], ids[(i + 1) % 3])));
+            }
+        }
+        exit.filter(|&(t, _)| t < 1.0)
+    }
+}
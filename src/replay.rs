@@ -0,0 +1,399 @@
+use wasm_bindgen::prelude::*;
+
+use crate::bytes::{Reader, Writer};
+use crate::error::Error;
+use crate::random::Rng;
+use crate::world::{AiWorld, Behavior, Lod};
+
+/// Leading bytes of a recording, then a format version.
+const MAGIC: &[u8; 4] = b"LAIR";
+const VERSION: u32 = 1;
+
+/// A call that changed an `AiWorld` while it was recorded. Ticks and steps
+/// carry a checksum of the world after them, so a replay notices the moment it
+/// stops matching.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Command {
+    Tick {
+        elapsed: f64,
+        checksum: u32,
+    },
+    Step {
+        checksum: u32,
+    },
+    Settings {
+        max_steps: u32,
+        reduced_interval: u32,
+        wander: [f32; 3],
+    },
+    SetRng(Vec<u32>),
+    AddAgent([f32; 4]),
+    RemoveAgent(u32),
+    SetTarget(u32, [f32; 3]),
+    SetBehavior(u32, Behavior),
+    SetLod(u32, Lod),
+    UpdateLods([f32; 4]),
+    WriteLods(Vec<u32>),
+    SetPosition(u32, [f32; 2]),
+    SetTargets(Vec<u32>, Vec<f32>, Vec<u32>),
+    WritePositions(Vec<f32>),
+    WriteVelocities(Vec<f32>),
+    WriteTargets(Vec<f32>),
+}
+
+impl Command {
+    fn write(&self, out: &mut Writer) {
+        match self {
+            Command::Tick { elapsed, checksum } => {
+                out.u8(0);
+                out.f64(*elapsed);
+                out.u32(*checksum);
+            }
+            Command::Step { checksum } => {
+                out.u8(1);
+                out.u32(*checksum);
+            }
+            Command::Settings {
+                max_steps,
+                reduced_interval,
+                wander,
+            } => {
+                out.u8(2);
+                out.u32s(&[*max_steps, *reduced_interval]);
+                out.f32s(wander);
+            }
+            Command::SetRng(state) => {
+                out.u8(3);
+                out.u32s(state);
+            }
+            Command::AddAgent(values) => {
+                out.u8(4);
+                out.f32s(values);
+            }
+            Command::RemoveAgent(handle) => {
+                out.u8(5);
+                out.u32(*handle);
+            }
+            Command::SetTarget(handle, values) => {
+                out.u8(6);
+                out.u32(*handle);
+                out.f32s(values);
+            }
+            Command::SetBehavior(handle, behavior) => {
+                out.u8(7);
+                out.u32(*handle);
+                out.u8(*behavior as u8);
+            }
+            Command::SetLod(handle, lod) => {
+                out.u8(8);
+                out.u32(*handle);
+                out.u8(*lod as u8);
+            }
+            Command::UpdateLods(values) => {
+                out.u8(9);
+                out.f32s(values);
+            }
+            Command::WriteLods(lods) => {
+                out.u8(10);
+                out.u32(lods.len() as u32);
+                lods.iter().for_each(|&lod| out.u8(lod as u8));
+            }
+            Command::SetPosition(handle, values) => {
+                out.u8(11);
+                out.u32(*handle);
+                out.f32s(values);
+            }
+            Command::SetTargets(handles, targets, behaviors) => {
+                out.u8(12);
+                out.u32(handles.len() as u32);
+                out.u32s(handles);
+                out.f32s(targets);
+                behaviors
+                    .iter()
+                    .for_each(|&behavior| out.u8(behavior as u8));
+            }
+            Command::WritePositions(values) => {
+                out.u8(13);
+                out.u32(values.len() as u32);
+                out.f32s(values);
+            }
+            Command::WriteVelocities(values) => {
+                out.u8(14);
+                out.u32(values.len() as u32);
+                out.f32s(values);
+            }
+            Command::WriteTargets(values) => {
+                out.u8(15);
+                out.u32(values.len() as u32);
+                out.f32s(values);
+            }
+        }
+    }
+
+    fn read(input: &mut Reader) -> Result<Command, Error> {
+        let counted = |input: &mut Reader| -> Result<Vec<f32>, Error> {
+            let len = input.u32()? as usize;
+            input.f32s(len)
+        };
+        Ok(match input.u8()? {
+            0 => Command::Tick {
+                elapsed: input.f64()?,
+                checksum: input.u32()?,
+            },
+            1 => Command::Step {
+                checksum: input.u32()?,
+            },
+            2 => Command::Settings {
+                max_steps: input.u32()?,
+                reduced_interval: input.u32()?,
+                wander: [input.f32()?, input.f32()?, input.f32()?],
+            },
+            3 => Command::SetRng(input.u32s(4)?),
+            4 => Command::AddAgent([input.f32()?, input.f32()?, input.f32()?, input.f32()?]),
+            5 => Command::RemoveAgent(input.u32()?),
+            6 => Command::SetTarget(input.u32()?, [input.f32()?, input.f32()?, input.f32()?]),
+            7 => Command::SetBehavior(input.u32()?, behavior(input)?),
+            8 => Command::SetLod(input.u32()?, lod(input)?),
+            9 => Command::UpdateLods([input.f32()?, input.f32()?, input.f32()?, input.f32()?]),
+            10 => {
+                let len = input.u32()?;
+                Command::WriteLods(
+                    (0..len)
+                        .map(|_| lod(input).map(|lod| lod as u32))
+                        .collect::<Result<_, _>>()?,
+                )
+            }
+            11 => Command::SetPosition(input.u32()?, [input.f32()?, input.f32()?]),
+            12 => {
+                let len = input.u32()? as usize;
+                let handles = input.u32s(len)?;
+                let targets = input.f32s(len.checked_mul(2).ok_or_else(|| input.invalid())?)?;
+                let behaviors = (0..len)
+                    .map(|_| behavior(input).map(|behavior| behavior as u32))
+                    .collect::<Result<_, _>>()?;
+                Command::SetTargets(handles, targets, behaviors)
+            }
+            13 => Command::WritePositions(counted(input)?),
+            14 => Command::WriteVelocities(counted(input)?),
+            15 => Command::WriteTargets(counted(input)?),
+            _ => return Err(input.invalid()),
+        })
+    }
+
+    /// Makes the recorded call again, returning the recorded checksum for
+    /// ticks and steps.
+    fn apply(&self, world: &mut AiWorld) -> Result<Option<u32>, Error> {
+        match self {
+            Command::Tick { elapsed, checksum } => {
+                world.tick(*elapsed);
+                return Ok(Some(*checksum));
+            }
+            Command::Step { checksum } => {
+                world.step();
+                return Ok(Some(*checksum));
+            }
+            Command::Settings {
+                max_steps,
+                reduced_interval,
+                wander,
+            } => {
+                world.max_steps = *max_steps;
+                world.reduced_interval = *reduced_interval;
+                [
+                    world.wander_jitter,
+                    world.wander_radius,
+                    world.wander_distance,
+                ] = *wander;
+            }
+            Command::SetRng(state) => {
+                let mut rng = Rng::new(0);
+                rng.set_state(state)?;
+                world.set_rng(&rng);
+            }
+            &Command::AddAgent([x, y, max_speed, max_force]) => {
+                world.add_agent(x, y, max_speed, max_force)?;
+            }
+            &Command::RemoveAgent(handle) => {
+                world.remove_agent(handle);
+            }
+            &Command::SetTarget(handle, [x, y, slow_radius]) => {
+                world.set_target(handle, x, y, slow_radius);
+            }
+            &Command::SetBehavior(handle, behavior) => {
+                world.set_behavior(handle, behavior);
+            }
+            &Command::SetLod(handle, lod) => {
+                world.set_lod(handle, lod);
+            }
+            &Command::UpdateLods([x, y, near, far]) => world.update_lods(x, y, near, far),
+            Command::WriteLods(lods) => world.write_lods(lods)?,
+            &Command::SetPosition(handle, [x, y]) => {
+                world.set_position(handle, x, y);
+            }
+            Command::SetTargets(handles, targets, behaviors) => {
+                world.set_targets(handles, targets, behaviors)?;
+            }
+            Command::WritePositions(values) => world.write_positions(values)?,
+            Command::WriteVelocities(values) => world.write_velocities(values)?,
+            Command::WriteTargets(values) => world.write_targets(values)?,
+        }
+        Ok(None)
+    }
+}
+
+fn behavior(input: &mut Reader) -> Result<Behavior, Error> {
+    Behavior::from_u32(input.u8()? as u32).ok_or_else(|| input.invalid())
+}
+
+fn lod(input: &mut Reader) -> Result<Lod, Error> {
+    Lod::from_u32(input.u8()? as u32).ok_or_else(|| input.invalid())
+}
+
+/// The calls logged since `AiWorld::start_recording`, after the world's state
+/// at that moment.
+pub(crate) struct Recorder {
+    out: Writer,
+    settings: Option<Command>,
+}
+
+impl Recorder {
+    pub(crate) fn new(world: &AiWorld) -> Recorder {
+        let mut out = Writer::new(MAGIC, VERSION);
+        out.bytes(&world.save());
+        Recorder {
+            out,
+            settings: None,
+        }
+    }
+
+    pub(crate) fn push(&mut self, command: &Command) {
+        command.write(&mut self.out);
+    }
+
+    /// Logs the world's public settings when they differ from the last ones
+    /// logged, since the game changes them without a call to hook.
+    pub(crate) fn push_settings(&mut self, world: &AiWorld) {
+        let settings = Command::Settings {
+            max_steps: world.max_steps,
+            reduced_interval: world.reduced_interval,
+            wander: [
+                world.wander_jitter,
+                world.wander_radius,
+                world.wander_distance,
+            ],
+        };
+        if self.settings.as_ref() != Some(&settings) {
+            self.push(&settings);
+            self.settings = Some(settings);
+        }
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.out.len()
+    }
+
+    pub(crate) fn finish(self) -> Vec<u8> {
+        self.out.finish()
+    }
+}
+
+/// The calls left to make in a replay.
+pub(crate) struct Playback {
+    commands: std::vec::IntoIter<Command>,
+    diverged_at: Option<u32>,
+}
+
+#[wasm_bindgen]
+impl AiWorld {
+    /// Starts logging every call that changes the world, after a snapshot of
+    /// it as it is now, so `stop_recording` hands back everything needed to run
+    /// the same frames again, e.g. attached to a player's bug report. Restarts
+    /// the log if one was running.
+    pub fn start_recording(&mut self) {
+        let recorder = Recorder::new(self);
+        self.set_recorder(Some(recorder));
+    }
+
+    /// Ends the log and returns it for `AiWorld::replay`, empty when nothing
+    /// was being recorded.
+    pub fn stop_recording(&mut self) -> Vec<u8> {
+        self.set_recorder(None)
+            .map(Recorder::finish)
+            .unwrap_or_default()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn is_recording(&self) -> bool {
+        self.recorder().is_some()
+    }
+
+    /// Bytes logged so far.
+    #[wasm_bindgen(getter)]
+    pub fn recording_len(&self) -> u32 {
+        self.recorder().map_or(0, |recorder| recorder.len() as u32)
+    }
+
+    /// A world in the state a recording started from, which `replay_frame`
+    /// then takes through the recorded calls.
+    pub fn replay(recording: &[u8]) -> Result<AiWorld, Error> {
+        let what = "an AI world recording";
+        let (mut input, version) = Reader::new(recording, MAGIC, what)?;
+        if version != VERSION {
+            return Err(Error::InvalidInput(
+                "unsupported AI world recording version".into(),
+            ));
+        }
+        let mut world = AiWorld::load(input.bytes()?)?;
+        let mut commands = Vec::new();
+        while !input.is_empty() {
+            commands.push(Command::read(&mut input)?);
+        }
+        input.finish()?;
+        world.set_playback(Some(Playback {
+            commands: commands.into_iter(),
+            diverged_at: None,
+        }));
+        Ok(world)
+    }
+
+    /// Makes the recorded calls up to and including the next `tick` or
+    /// `step`; returns false once the recording is used up.
+    pub fn replay_frame(&mut self) -> Result<bool, Error> {
+        let Some(mut playback) = self.set_playback(None) else {
+            return Ok(false);
+        };
+        let mut replayed = false;
+        let result = loop {
+            let Some(command) = playback.commands.next() else {
+                break Ok(replayed);
+            };
+            replayed = true;
+            match command.apply(self) {
+                Ok(Some(checksum)) => {
+                    if checksum != self.checksum() && playback.diverged_at.is_none() {
+                        playback.diverged_at = Some(self.step_count());
+                    }
+                    break Ok(true);
+                }
+                Ok(None) => {}
+                Err(error) => break Err(error),
+            }
+        };
+        self.set_playback(Some(playback));
+        result
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn is_replaying(&self) -> bool {
+        self.playback()
+            .is_some_and(|playback| playback.commands.len() > 0)
+    }
+
+    /// The step count after the first replayed frame that ended somewhere
+    /// else than it did when recorded, e.g. because the build changed, or
+    /// `None` while the replay matches.
+    #[wasm_bindgen(getter)]
+    pub fn replay_diverged_at(&self) -> Option<u32> {
+        self.playback().and_then(|playback| playback.diverged_at)
+    }
+}
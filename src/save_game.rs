@@ -0,0 +1,169 @@
+use wasm_bindgen::prelude::*;
+
+use crate::behavior_tree::BehaviorTree;
+use crate::blackboard::Blackboard;
+use crate::bytes::{Reader, Writer};
+use crate::crowd::Crowd;
+use crate::error::Error;
+use crate::state_machine::StateMachine;
+use crate::world::AiWorld;
+
+/// Leading bytes of `SaveGame::to_bytes`, then a format version.
+const MAGIC: &[u8; 4] = b"LAIG";
+const VERSION: u32 = 1;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Part {
+    World = 0,
+    Blackboard = 1,
+    Tree = 2,
+    StateMachine = 3,
+    Crowd = 4,
+}
+
+impl Part {
+    fn from_u32(value: u32) -> Option<Part> {
+        Some(match value {
+            0 => Part::World,
+            1 => Part::Blackboard,
+            2 => Part::Tree,
+            3 => Part::StateMachine,
+            4 => Part::Crowd,
+            _ => return None,
+        })
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Part::World => "world",
+            Part::Blackboard => "blackboard",
+            Part::Tree => "behavior tree",
+            Part::StateMachine => "state machine",
+            Part::Crowd => "crowd",
+        }
+    }
+}
+
+/// A whole AI simulation in one save: worlds with the blackboards, behavior
+/// trees, state machines and crowds that drive their agents, each under a
+/// name. Trees and machines call back into the game and crowds walk a grid
+/// or mesh, so of those only the running state is kept, to restore into ones
+/// the game has rebuilt the same way.
+#[wasm_bindgen]
+#[derive(Default)]
+pub struct SaveGame {
+    parts: Vec<(Part, String, Vec<u8>)>,
+}
+
+#[wasm_bindgen]
+impl SaveGame {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> SaveGame {
+        SaveGame::default()
+    }
+
+    /// Reads what `to_bytes` wrote.
+    pub fn from_bytes(bytes: &[u8]) -> Result<SaveGame, Error> {
+        let (mut input, version) = Reader::new(bytes, MAGIC, "a save game")?;
+        if version != VERSION {
+            return Err(Error::InvalidInput("unsupported save game version".into()));
+        }
+        let mut save = SaveGame::new();
+        for _ in 0..input.u32()? {
+            let part = Part::from_u32(input.u32()?).ok_or_else(|| input.invalid())?;
+            let name = input.string()?;
+            let bytes = input.bytes()?.to_vec();
+            save.put(part, name, bytes);
+        }
+        input.finish()?;
+        Ok(save)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Writer::new(MAGIC, VERSION);
+        out.u32(self.parts.len() as u32);
+        for (part, name, bytes) in &self.parts {
+            out.u32(*part as u32);
+            out.string(name);
+            out.bytes(bytes);
+        }
+        out.finish()
+    }
+
+    /// Stores `world` as `AiWorld::save` does, replacing any world saved
+    /// under `name`.
+    pub fn add_world(&mut self, name: &str, world: &AiWorld) {
+        self.put(Part::World, name.to_string(), world.save());
+    }
+
+    pub fn add_blackboard(&mut self, name: &str, blackboard: &Blackboard) {
+        self.put(Part::Blackboard, name.to_string(), blackboard.save());
+    }
+
+    /// Stores what `BehaviorTree::save_state` gives.
+    pub fn add_tree(&mut self, name: &str, tree: &BehaviorTree) {
+        self.put(Part::Tree, name.to_string(), tree.save_state());
+    }
+
+    /// Stores what `StateMachine::save_state` gives.
+    pub fn add_state_machine(&mut self, name: &str, machine: &StateMachine) {
+        self.put(Part::StateMachine, name.to_string(), machine.save_state());
+    }
+
+    /// Stores what `Crowd::save_state` gives.
+    pub fn add_crowd(&mut self, name: &str, crowd: &Crowd) {
+        self.put(Part::Crowd, name.to_string(), crowd.save_state());
+    }
+
+    pub fn load_world(&self, name: &str) -> Result<AiWorld, Error> {
+        AiWorld::load(self.get(Part::World, name)?)
+    }
+
+    pub fn load_blackboard(&self, name: &str) -> Result<Blackboard, Error> {
+        Blackboard::load(self.get(Part::Blackboard, name)?)
+    }
+
+    /// Loads the state saved as `name` into `tree`, built as the saved one was.
+    pub fn restore_tree(&self, name: &str, tree: &mut BehaviorTree) -> Result<(), Error> {
+        tree.load_state(self.get(Part::Tree, name)?)
+    }
+
+    /// Loads the state saved as `name` into `machine`, which has the saved
+    /// one's states.
+    pub fn restore_state_machine(
+        &self,
+        name: &str,
+        machine: &mut StateMachine,
+    ) -> Result<(), Error> {
+        machine.load_state(self.get(Part::StateMachine, name)?)
+    }
+
+    /// Loads the agents saved as `name` into `crowd`, made on the grid or mesh
+    /// the saved one walked.
+    pub fn restore_crowd(&self, name: &str, crowd: &mut Crowd) -> Result<(), Error> {
+        crowd.load_state(self.get(Part::Crowd, name)?)
+    }
+}
+
+impl SaveGame {
+    fn put(&mut self, part: Part, name: String, bytes: Vec<u8>) {
+        match self
+            .parts
+            .iter_mut()
+            .find(|(known, known_name, _)| *known == part && *known_name == name)
+        {
+            Some(entry) => entry.2 = bytes,
+            None => self.parts.push((part, name, bytes)),
+        }
+    }
+
+    fn get(&self, part: Part, name: &str) -> Result<&[u8], Error> {
+        self.parts
+            .iter()
+            .find(|(known, known_name, _)| *known == part && known_name == name)
+            .map(|(_, _, bytes)| bytes.as_slice())
+            .ok_or_else(|| {
+                Error::InvalidInput(format!("no {} named '{}' in the save", part.label(), name))
+            })
+    }
+}
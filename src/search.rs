@@ -0,0 +1,278 @@
+use std::cmp::Ordering;
+use std::sync::atomic::{AtomicU32, Ordering as AtomicOrdering};
+
+use crate::arena::{self, Lease, SearchBuffers};
+
+/// A* expansions across every search so far, wrapping, for `AiStats`.
+static EXPANSIONS: AtomicU32 = AtomicU32::new(0);
+
+pub(crate) fn expansions() -> u32 {
+    EXPANSIONS.load(AtomicOrdering::Relaxed)
+}
+
+/// Entry in an open list, ordered so that `BinaryHeap` pops the lowest cost first.
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) struct OpenNode {
+    pub cost: f32,
+    pub index: usize,
+}
+
+impl Eq for OpenNode {}
+
+impl Ord for OpenNode {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .cost
+            .total_cmp(&self.cost)
+            .then_with(|| other.index.cmp(&self.index))
+    }
+}
+
+impl PartialOrd for OpenNode {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// A* over an implicit graph of `node_count` nodes. `neighbors` pushes `(node, cost)`
+/// pairs for the given node; `heuristic` must not overestimate the remaining cost.
+pub(crate) fn astar<N, H>(
+    node_count: usize,
+    start: usize,
+    goal: usize,
+    mut neighbors: N,
+    heuristic: H,
+) -> Option<Vec<usize>>
+where
+    N: FnMut(usize, &mut Vec<(usize, f32)>),
+    H: Fn(usize) -> f32,
+{
+    let mut search = AStar::new(node_count, start, goal, heuristic(start));
+    match search.step(usize::MAX, &mut neighbors, &heuristic) {
+        Progress::Found(path) => Some(path),
+        _ => None,
+    }
+}
+
+pub(crate) enum Progress {
+    Running,
+    Found(Vec<usize>),
+    Failed,
+}
+
+/// Resumable A* state, advanced a bounded number of expansions at a time. Its
+/// buffers come from the thread's arena and go back to it on drop.
+pub(crate) struct AStar {
+    goal: usize,
+    buffers: Lease,
+}
+
+impl AStar {
+    pub fn new(node_count: usize, start: usize, goal: usize, start_heuristic: f32) -> AStar {
+        let mut search = AStar {
+            goal,
+            buffers: arena::search(node_count),
+        };
+        search.buffers.g[start] = 0.0;
+        search.buffers.open.push(OpenNode {
+            cost: start_heuristic,
+            index: start,
+        });
+        search
+    }
+
+    /// Expands up to `max_expansions` nodes, returning `Running` if the search
+    /// has not finished yet.
+    pub fn step<N, H>(
+        &mut self,
+        max_expansions: usize,
+        neighbors: &mut N,
+        heuristic: &H,
+    ) -> Progress
+    where
+        N: FnMut(usize, &mut Vec<(usize, f32)>),
+        H: Fn(usize) -> f32,
+    {
+        let mut expansions = 0;
+        let progress = self.expand(max_expansions, &mut expansions, neighbors, heuristic);
+        EXPANSIONS.fetch_add(expansions as u32, AtomicOrdering::Relaxed);
+        progress
+    }
+
+    fn expand<N, H>(
+        &mut self,
+        max_expansions: usize,
+        expansions: &mut usize,
+        neighbors: &mut N,
+        heuristic: &H,
+    ) -> Progress
+    where
+        N: FnMut(usize, &mut Vec<(usize, f32)>),
+        H: Fn(usize) -> f32,
+    {
+        let SearchBuffers {
+            g,
+            parent,
+            closed,
+            open,
+            edges,
+        } = &mut *self.buffers;
+        while let Some(OpenNode { index, .. }) = open.pop() {
+            if index == self.goal {
+                return Progress::Found(reconstruct(parent, self.goal));
+            }
+            if closed[index] {
+                continue;
+            }
+            closed[index] = true;
+
+            edges.clear();
+            neighbors(index, edges);
+            for &(next, cost) in edges.iter() {
+                if closed[next] {
+                    continue;
+                }
+                let tentative = g[index] + cost;
+                if tentative < g[next] {
+                    g[next] = tentative;
+                    parent[next] = index;
+                    open.push(OpenNode {
+                        cost: tentative + heuristic(next),
+                        index: next,
+                    });
+                }
+            }
+
+            *expansions += 1;
+            if *expansions >= max_expansions {
+                return Progress::Running;
+            }
+        }
+
+        Progress::Failed
+    }
+}
+
+/// Walks `parent` links back from `goal` and returns the path in start-to-goal order.
+pub(crate) fn reconstruct(parent: &[usize], goal: usize) -> Vec<usize> {
+    let mut path = vec![goal];
+    let mut current = goal;
+    while parent[current] != usize::MAX {
+        current = parent[current];
+        path.push(current);
+    }
+    path.reverse();
+    path
+}
+
+/// Dijkstra from every node in `sources` at once. Returns the cost of reaching each
+/// node from its nearest source, `INFINITY` where unreachable.
+pub(crate) fn dijkstra<N>(node_count: usize, sources: &[usize], neighbors: N) -> Vec<f32>
+where
+    N: FnMut(usize, &mut Vec<(usize, f32)>),
+{
+    shortest_path_tree(node_count, sources, neighbors).0
+}
+
+/// Like `dijkstra`, also returning each node's parent for use with `reconstruct`.
+pub(crate) fn shortest_path_tree<N>(
+    node_count: usize,
+    sources: &[usize],
+    mut neighbors: N,
+) -> (Vec<f32>, Vec<usize>)
+where
+    N: FnMut(usize, &mut Vec<(usize, f32)>),
+{
+    let mut cost = vec![f32::INFINITY; node_count];
+    let mut parent = vec![usize::MAX; node_count];
+    let mut buffers = arena::search(0);
+    let SearchBuffers { open, edges, .. } = &mut *buffers;
+
+    for &source in sources {
+        cost[source] = 0.0;
+        open.push(OpenNode {
+            cost: 0.0,
+            index: source,
+        });
+    }
+
+    while let Some(OpenNode {
+        cost: current,
+        index,
+    }) = open.pop()
+    {
+        if current > cost[index] {
+            continue;
+        }
+        edges.clear();
+        neighbors(index, edges);
+        for &(next, step) in edges.iter() {
+            let tentative = current + step;
+            if tentative < cost[next] {
+                cost[next] = tentative;
+                parent[next] = index;
+                open.push(OpenNode {
+                    cost: tentative,
+                    index: next,
+                });
+            }
+        }
+    }
+
+    (cost, parent)
+}
+
+/// Dijkstra from `start` that stops at the first node `is_goal` accepts, which is
+/// the cheapest of them to reach. Returns that path and its cost.
+pub(crate) fn nearest<N, G>(
+    node_count: usize,
+    start: usize,
+    is_goal: G,
+    mut neighbors: N,
+) -> Option<(Vec<usize>, f32)>
+where
+    N: FnMut(usize, &mut Vec<(usize, f32)>),
+    G: Fn(usize) -> bool,
+{
+    let mut buffers = arena::search(node_count);
+    let SearchBuffers {
+        g: cost,
+        parent,
+        open,
+        edges,
+        ..
+    } = &mut *buffers;
+    cost[start] = 0.0;
+    open.push(OpenNode {
+        cost: 0.0,
+        index: start,
+    });
+
+    while let Some(OpenNode {
+        cost: current,
+        index,
+    }) = open.pop()
+    {
+        if current > cost[index] {
+            continue;
+        }
+        if is_goal(index) {
+            return Some((reconstruct(parent, index), current));
+        }
+        edges.clear();
+        neighbors(index, edges);
+        for &(next, step) in edges.iter() {
+            let tentative = current + step;
+            if tentative < cost[next] {
+                cost[next] = tentative;
+                parent[next] = index;
+                open.push(OpenNode {
+                    cost: tentative,
+                    index: next,
+                });
+            }
+        }
+    }
+
+    None
+}
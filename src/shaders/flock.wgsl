@@ -0,0 +1,87 @@
+// Separation, alignment and cohesion forces of every boid, matching
+// `Flock::update` on the CPU. Boids are binned into a uniform grid of
+// `cell_size` cells no smaller than the neighbor radius, so each boid only
+// scans the 3x3 cells around it.
+
+@group(0) @binding(0) var<storage, read> positions: array<vec2<f32>>;
+@group(0) @binding(1) var<storage, read> velocities: array<vec2<f32>>;
+// Boid indices sorted by cell, and where each cell's run starts in them.
+@group(0) @binding(2) var<storage, read> order: array<u32>;
+@group(0) @binding(3) var<storage, read> cell_start: array<u32>;
+// count, columns, rows, min_x, min_y, cell_size, neighbor_radius,
+// separation_radius, separation_weight, alignment_weight, cohesion_weight,
+// max_speed, max_force
+@group(0) @binding(4) var<storage, read> params: array<f32>;
+@group(0) @binding(5) var<storage, read_write> forces: array<vec2<f32>>;
+
+const EPSILON: f32 = 1.1920929e-7;
+
+fn truncate(v: vec2<f32>, max_length: f32) -> vec2<f32> {
+    let length_squared = dot(v, v);
+    if (length_squared > max_length * max_length) {
+        return v * (max_length / sqrt(length_squared));
+    }
+    return v;
+}
+
+fn steer(direction: vec2<f32>, velocity: vec2<f32>) -> vec2<f32> {
+    if (dot(direction, direction) <= EPSILON) {
+        return vec2<f32>(0.0);
+    }
+    return truncate(normalize(direction) * params[11] - velocity, params[12]);
+}
+
+@compute @workgroup_size(64)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let i = id.x;
+    if (i >= u32(params[0])) {
+        return;
+    }
+    let columns = i32(params[1]);
+    let rows = i32(params[2]);
+    let origin = vec2<f32>(params[3], params[4]);
+    let cell_size = params[5];
+    let radius_squared = params[6] * params[6];
+    let separation_squared = params[7] * params[7];
+
+    let position = positions[i];
+    let velocity = velocities[i];
+    let cell = vec2<i32>(floor((position - origin) / cell_size));
+    var separation = vec2<f32>(0.0);
+    var heading = vec2<f32>(0.0);
+    var center = vec2<f32>(0.0);
+    var neighbors = 0u;
+
+    for (var cy = max(cell.y - 1, 0); cy <= min(cell.y + 1, rows - 1); cy++) {
+        for (var cx = max(cell.x - 1, 0); cx <= min(cell.x + 1, columns - 1); cx++) {
+            let c = u32(cy * columns + cx);
+            for (var k = cell_start[c]; k < cell_start[c + 1u]; k++) {
+                let j = order[k];
+                if (j == i) {
+                    continue;
+                }
+                let other = positions[j];
+                let offset = position - other;
+                let distance_squared = dot(offset, offset);
+                if (distance_squared > radius_squared) {
+                    continue;
+                }
+                if (distance_squared < separation_squared && distance_squared > 0.0) {
+                    separation += offset / distance_squared;
+                }
+                heading += velocities[j];
+                center += other;
+                neighbors++;
+            }
+        }
+    }
+
+    var force = vec2<f32>(0.0);
+    if (neighbors > 0u) {
+        let n = f32(neighbors);
+        force += steer(separation, velocity) * params[8];
+        force += steer(heading / n, velocity) * params[9];
+        force += steer(center / n - position, velocity) * params[10];
+    }
+    forces[i] = truncate(force, params[12]);
+}
@@ -0,0 +1,42 @@
+// One relaxation pass of the flow field's cost integration: every cell takes
+// the cheapest cost any of its up to eight in-neighbors offers. Costs are
+// stored as bit patterns, which order like the non-negative floats they hold,
+// so `atomicMin` lowers them without races. Repeated passes converge to the
+// Dijkstra costs `Grid::generate_flow_field` computes.
+
+@group(0) @binding(0) var<storage, read_write> costs: array<atomic<u32>>;
+// Set to 1 by any pass that lowers a cost.
+@group(0) @binding(1) var<storage, read_write> changed: atomic<u32>;
+// For each cell, eight cells it can be entered from, 0xffffffff for none,
+// and the cost of each of those steps.
+@group(0) @binding(2) var<storage, read> sources: array<u32>;
+@group(0) @binding(3) var<storage, read> steps: array<f32>;
+// cell count
+@group(0) @binding(4) var<storage, read> params: array<u32>;
+
+// Costs at or above this have not been reached.
+const UNREACHED: f32 = 1e30;
+
+@compute @workgroup_size(64)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let cell = id.x;
+    if (cell >= params[0]) {
+        return;
+    }
+    let current = bitcast<f32>(atomicLoad(&costs[cell]));
+    var best = current;
+    for (var k = 0u; k < 8u; k++) {
+        let source = sources[cell * 8u + k];
+        if (source == 0xffffffffu) {
+            continue;
+        }
+        let cost = bitcast<f32>(atomicLoad(&costs[source]));
+        if (cost < UNREACHED) {
+            best = min(best, cost + steps[cell * 8u + k]);
+        }
+    }
+    if (best < current) {
+        atomicMin(&costs[cell], bitcast<u32>(best));
+        atomicStore(&changed, 1u);
+    }
+}
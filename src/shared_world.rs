@@ -0,0 +1,653 @@
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use js_sys::{Array, Atomics, Float32Array, Float64Array, Function, Int32Array, Object, Reflect};
+use js_sys::{SharedArrayBuffer, Uint32Array};
+use wasm_bindgen::prelude::*;
+
+use crate::error::Error;
+use crate::world::{AiWorld, Behavior};
+
+// Header words, read and written with `Atomics`.
+/// Odd while the worker is writing a frame, so readers can tell a torn copy.
+const SEQUENCE: u32 = 0;
+/// Frames published so far; waiters are woken on it.
+const FRAME: u32 = 1;
+const COUNT: u32 = 2;
+const CAPACITY: u32 = 3;
+const STEP_COUNT: u32 = 4;
+/// `f32` bits.
+const ALPHA: u32 = 5;
+/// `f64` bits, high word first.
+const TIME: u32 = 6;
+const HEADER_WORDS: u32 = 8;
+/// Columns after the header by words per agent: keys, handles and behaviors,
+/// then positions, interpolated positions, velocities and headings as pairs.
+const COLUMN_WIDTHS: [u32; 7] = [1, 1, 1, 2, 2, 2, 2];
+
+/// Words per queued command: the opcode, the agent's key and four arguments.
+const COMMAND_WORDS: usize = 6;
+const ADD: f64 = 0.0;
+const REMOVE: f64 = 1.0;
+const TARGET: f64 = 2.0;
+const BEHAVIOR: f64 = 3.0;
+const POSITION: f64 = 4.0;
+
+/// Typed-array views of the regions of a shared world buffer.
+struct Regions {
+    header: Int32Array,
+    keys: Uint32Array,
+    handles: Uint32Array,
+    behaviors: Uint32Array,
+    positions: Float32Array,
+    interpolated: Float32Array,
+    velocities: Float32Array,
+    headings: Float32Array,
+}
+
+impl Regions {
+    fn new(buffer: &SharedArrayBuffer, capacity: u32) -> Regions {
+        let mut offset = HEADER_WORDS * 4;
+        let mut next = |width: u32| {
+            let start = offset;
+            offset += width * capacity * 4;
+            (start, width * capacity)
+        };
+        let [keys, handles, behaviors, positions, interpolated, velocities, headings] =
+            COLUMN_WIDTHS.map(&mut next);
+        let words = |(start, len)| Uint32Array::new_with_byte_offset_and_length(buffer, start, len);
+        let floats =
+            |(start, len)| Float32Array::new_with_byte_offset_and_length(buffer, start, len);
+        Regions {
+            header: Int32Array::new_with_byte_offset_and_length(buffer, 0, HEADER_WORDS),
+            keys: words(keys),
+            handles: words(handles),
+            behaviors: words(behaviors),
+            positions: floats(positions),
+            interpolated: floats(interpolated),
+            velocities: floats(velocities),
+            headings: floats(headings),
+        }
+    }
+
+    fn load(&self, index: u32) -> i32 {
+        Atomics::load(&self.header, index).unwrap_throw()
+    }
+
+    fn store(&self, index: u32, value: i32) {
+        Atomics::store(&self.header, index, value).unwrap_throw();
+    }
+
+    fn add(&self, index: u32, value: i32) {
+        Atomics::add(&self.header, index, value).unwrap_throw();
+    }
+}
+
+/// Most agents an `AiWorld` holds.
+const MAX_CAPACITY: u32 = 1 << 20;
+
+fn check_capacity(capacity: u32) -> Result<(), Error> {
+    if capacity > 0 && capacity <= MAX_CAPACITY {
+        Ok(())
+    } else {
+        Err(Error::InvalidInput(format!(
+            "capacity must be between 1 and {}",
+            MAX_CAPACITY
+        )))
+    }
+}
+
+/// The worker half of the worker mode: an `AiWorld` that takes commands by
+/// key and publishes its agents into a `SharedArrayBuffer` after each tick.
+/// `run_world_worker` drives one from a `WorldWorker`'s messages; build one
+/// directly to run the world from your own worker code.
+///
+/// Agents are addressed by keys the caller picks, such as entity ids, since
+/// the other thread cannot wait for handles. Each frame lists them, in the
+/// order of the bulk arrays, alongside the handles.
+#[wasm_bindgen]
+pub struct SharedWorld {
+    world: AiWorld,
+    regions: Regions,
+    capacity: u32,
+    handles: HashMap<u32, u32>,
+    /// Key of each agent, in the order of the bulk arrays.
+    keys: Vec<u32>,
+    /// Interpolated positions being published.
+    interpolated: Vec<f32>,
+}
+
+#[wasm_bindgen]
+impl SharedWorld {
+    /// A world stepping every `timestep` seconds that publishes up to
+    /// `capacity` agents into `buffer`, which must hold `byte_length(capacity)`
+    /// bytes.
+    #[wasm_bindgen(constructor)]
+    pub fn new(
+        buffer: &SharedArrayBuffer,
+        capacity: u32,
+        timestep: f32,
+    ) -> Result<SharedWorld, Error> {
+        check_capacity(capacity)?;
+        let needed = SharedWorld::byte_length(capacity);
+        if buffer.byte_length() < needed {
+            return Err(Error::InvalidInput(format!(
+                "a buffer for {} agents needs {} bytes, got {}",
+                capacity,
+                needed,
+                buffer.byte_length()
+            )));
+        }
+        let world = AiWorld::new(timestep)?;
+        let regions = Regions::new(buffer, capacity);
+        regions.store(CAPACITY, capacity as i32);
+        Ok(SharedWorld {
+            world,
+            regions,
+            capacity,
+            handles: HashMap::new(),
+            keys: Vec::new(),
+            interpolated: Vec::new(),
+        })
+    }
+
+    /// Bytes a buffer needs to hold `capacity` agents.
+    pub fn byte_length(capacity: u32) -> u32 {
+        (HEADER_WORDS + COLUMN_WIDTHS.iter().sum::<u32>() * capacity) * 4
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn capacity(&self) -> u32 {
+        self.capacity
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn len(&self) -> u32 {
+        self.world.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.world.is_empty()
+    }
+
+    /// The handle of the agent added under `key`.
+    pub fn handle(&self, key: u32) -> Option<u32> {
+        self.handles.get(&key).copied()
+    }
+
+    /// Applies commands queued by a `WorldWorker`. Commands naming unknown keys
+    /// are skipped, as are adds of a key already in use or beyond `capacity`.
+    pub fn apply(&mut self, commands: &[f64]) -> Result<(), Error> {
+        if !commands.len().is_multiple_of(COMMAND_WORDS) {
+            return Err(Error::InvalidInput(format!(
+                "commands length must be a multiple of {}",
+                COMMAND_WORDS
+            )));
+        }
+        for command in commands.chunks_exact(COMMAND_WORDS) {
+            let [op, key, a, b, c, d] = <[f64; COMMAND_WORDS]>::try_from(command).unwrap();
+            let key = key as u32;
+            if op == ADD {
+                if self.handles.contains_key(&key) || self.world.len() >= self.capacity {
+                    continue;
+                }
+                let handle = self
+                    .world
+                    .add_agent(a as f32, b as f32, c as f32, d as f32)?;
+                self.handles.insert(key, handle);
+                self.keys.push(key);
+                continue;
+            }
+            let Some(&handle) = self.handles.get(&key) else {
+                continue;
+            };
+            if op == REMOVE {
+                // The world swap-removes, so the keys follow it.
+                if let Some(index) = self.world.index_of(handle) {
+                    self.keys.swap_remove(index as usize);
+                }
+                self.world.remove_agent(handle);
+                self.handles.remove(&key);
+            } else if op == TARGET {
+                self.world.set_target(handle, a as f32, b as f32, c as f32);
+            } else if op == BEHAVIOR {
+                let behavior = Behavior::from_u32(a as u32)
+                    .ok_or_else(|| Error::InvalidInput(format!("unknown behavior {}", a)))?;
+                self.world.set_behavior(handle, behavior);
+            } else if op == POSITION {
+                self.world.set_position(handle, a as f32, b as f32);
+            } else {
+                return Err(Error::InvalidInput(format!("unknown command {}", op)));
+            }
+        }
+        Ok(())
+    }
+
+    /// Advances the world by `elapsed` seconds; see `AiWorld::tick`.
+    pub fn tick(&mut self, elapsed: f64) -> u32 {
+        self.world.tick(elapsed)
+    }
+
+    /// Copies the agents into the buffer as a new frame and wakes any thread
+    /// waiting on it with `Atomics.wait`.
+    pub fn publish(&mut self) {
+        let regions = &self.regions;
+        let count = self.world.len();
+        regions.add(SEQUENCE, 1);
+        regions.store(COUNT, count as i32);
+        regions.store(STEP_COUNT, self.world.step_count() as i32);
+        regions.store(ALPHA, self.world.alpha().to_bits() as i32);
+        let time = self.world.time().to_bits();
+        regions.store(TIME, (time >> 32) as i32);
+        regions.store(TIME + 1, time as i32);
+        regions.keys.subarray(0, count).copy_from(&self.keys);
+        regions
+            .handles
+            .subarray(0, count)
+            .copy_from(self.world.handle_buffer());
+        regions
+            .behaviors
+            .subarray(0, count)
+            .copy_from(&self.world.behaviors());
+        let pairs = count * 2;
+        let mut interpolated = std::mem::take(&mut self.interpolated);
+        interpolated.resize(pairs as usize, 0.0);
+        self.world
+            .read_interpolated_positions(&mut interpolated)
+            .unwrap_throw();
+        for (column, values) in [
+            (&regions.positions, self.world.position_buffer()),
+            (&regions.interpolated, &interpolated[..]),
+            (&regions.velocities, self.world.velocity_buffer()),
+            (&regions.headings, self.world.heading_buffer()),
+        ] {
+            column.subarray(0, pairs).copy_from(values);
+        }
+        self.interpolated = interpolated;
+        regions.add(SEQUENCE, 1);
+        regions.add(FRAME, 1);
+        Atomics::notify(&regions.header, FRAME).unwrap_throw();
+    }
+}
+
+impl SharedWorld {
+    pub fn world(&self) -> &AiWorld {
+        &self.world
+    }
+
+    /// The world, e.g. to tune `max_steps` or wander. Add and remove agents
+    /// through `apply`, which keeps the keys in step.
+    pub fn world_mut(&mut self) -> &mut AiWorld {
+        &mut self.world
+    }
+}
+
+/// Runs a `SharedWorld` for a `WorldWorker` in the calling worker. Call it
+/// once from the worker script after initializing the module:
+///
+/// ```js
+/// import init, { run_world_worker } from 'lib-ai'
+/// await init()
+/// run_world_worker()
+/// ```
+#[wasm_bindgen]
+pub fn run_world_worker() {
+    let scope = js_sys::global();
+    let mut shared: Option<SharedWorld> = None;
+    let mut failed = false;
+    let listener = Closure::<dyn FnMut(JsValue)>::new(move |event: JsValue| {
+        if failed {
+            return;
+        }
+        let data = Reflect::get(&event, &"data".into()).unwrap_throw();
+        let field = |name: &str| Reflect::get(&data, &name.into()).unwrap_throw();
+        let result = match &mut shared {
+            None => SharedWorld::new(
+                &field("buffer").unchecked_into(),
+                field("capacity").as_f64().unwrap_or(0.0) as u32,
+                field("timestep").as_f64().unwrap_or(0.0) as f32,
+            )
+            .map(|world| shared = Some(world)),
+            Some(world) => {
+                let commands: Float64Array = field("commands").unchecked_into();
+                world.apply(&commands.to_vec()).map(|()| {
+                    world.tick(field("elapsed").as_f64().unwrap_or(0.0));
+                    world.publish();
+                })
+            }
+        };
+        // Thrown here, the error would only reach the worker's console while
+        // the page waits for frames that never come, so it goes back instead.
+        if let Err(error) = result {
+            failed = true;
+            let message = Object::new();
+            Reflect::set(&message, &"error".into(), &error.to_string().into()).unwrap_throw();
+            post(&js_sys::global(), &message, None);
+        }
+    });
+    Reflect::set(&scope, &"onmessage".into(), listener.as_ref()).unwrap_throw();
+    listener.forget();
+    post(&scope, &"ready".into(), None);
+}
+
+fn post(target: &JsValue, message: &JsValue, transfer: Option<&Array>) {
+    let post: Function = Reflect::get(target, &"postMessage".into())
+        .unwrap_throw()
+        .unchecked_into();
+    match transfer {
+        Some(transfer) => post.call2(target, message, transfer),
+        None => post.call1(target, message),
+    }
+    .unwrap_throw();
+}
+
+/// The render-thread half of the worker mode: drives a `SharedWorld` running
+/// in a Web Worker, so stepping never blocks rendering, and reads its agents
+/// back from shared memory.
+///
+/// Commands are queued and sent with the next `tick`, which hands the worker
+/// the elapsed time and returns at once. While the worker is still busy with
+/// the last tick, time and commands carry over to the next one instead of
+/// piling up. `read` copies the latest published frame, retrying if the worker
+/// was writing it at the time. A failure in the worker, such as a command it
+/// cannot apply, stops it and shows up in `error`.
+///
+/// Only the `AiWorld` steering runs in the worker. Pathfinding on a `Grid` or
+/// `NavMesh` and whole `Crowd`s stay on the thread that owns them, which sends
+/// the worker targets along the paths it plans.
+///
+/// The worker script must call `run_world_worker`. `SharedArrayBuffer` needs a
+/// cross-origin isolated page, served with `Cross-Origin-Opener-Policy:
+/// same-origin` and `Cross-Origin-Embedder-Policy: require-corp`.
+#[wasm_bindgen]
+pub struct WorldWorker {
+    worker: Object,
+    buffer: SharedArrayBuffer,
+    regions: Regions,
+    capacity: u32,
+    timestep: f32,
+    ready: Rc<Cell<bool>>,
+    /// Why the worker stopped, once it reports a failure.
+    error: Rc<RefCell<Option<String>>>,
+    started: bool,
+    /// Ticks sent; the worker has caught up when it has published as many.
+    sent: u32,
+    elapsed: f64,
+    commands: Vec<f64>,
+    frame: u32,
+    step_count: u32,
+    alpha: f32,
+    time: f64,
+    keys: Vec<u32>,
+    handles: Vec<u32>,
+    behaviors: Vec<u32>,
+    positions: Vec<f32>,
+    interpolated: Vec<f32>,
+    velocities: Vec<f32>,
+    headings: Vec<f32>,
+    _listener: Closure<dyn FnMut(JsValue)>,
+}
+
+#[wasm_bindgen]
+impl WorldWorker {
+    /// Runs a world stepping every `timestep` seconds with up to `capacity`
+    /// agents in `worker`, a `Worker` whose script calls `run_world_worker`.
+    #[wasm_bindgen(constructor)]
+    pub fn new(worker: Object, capacity: u32, timestep: f32) -> Result<WorldWorker, Error> {
+        if timestep <= 0.0 || !timestep.is_finite() {
+            return Err(Error::InvalidInput("timestep must be positive".into()));
+        }
+        check_capacity(capacity)?;
+        let buffer = SharedArrayBuffer::new(SharedWorld::byte_length(capacity));
+        let ready = Rc::new(Cell::new(false));
+        let error = Rc::new(RefCell::new(None));
+        let listener = {
+            let (ready, error) = (ready.clone(), error.clone());
+            Closure::<dyn FnMut(JsValue)>::new(move |event: JsValue| {
+                let data = Reflect::get(&event, &"data".into()).unwrap_throw();
+                if data.as_string().as_deref() == Some("ready") {
+                    ready.set(true);
+                } else if data.is_object() {
+                    let message = Reflect::get(&data, &"error".into()).unwrap_throw();
+                    if let Some(message) = message.as_string() {
+                        *error.borrow_mut() = Some(message);
+                    }
+                }
+            })
+        };
+        Reflect::set(&worker, &"onmessage".into(), listener.as_ref()).unwrap_throw();
+        Ok(WorldWorker {
+            worker,
+            regions: Regions::new(&buffer, capacity),
+            buffer,
+            capacity,
+            timestep,
+            ready,
+            error,
+            started: false,
+            sent: 0,
+            elapsed: 0.0,
+            commands: Vec::new(),
+            frame: 0,
+            step_count: 0,
+            alpha: 0.0,
+            time: 0.0,
+            keys: Vec::new(),
+            handles: Vec::new(),
+            behaviors: Vec::new(),
+            positions: Vec::new(),
+            interpolated: Vec::new(),
+            velocities: Vec::new(),
+            headings: Vec::new(),
+            _listener: listener,
+        })
+    }
+
+    /// The shared buffer, for reading frames from other threads as well.
+    #[wasm_bindgen(getter)]
+    pub fn buffer(&self) -> SharedArrayBuffer {
+        self.buffer.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn capacity(&self) -> u32 {
+        self.capacity
+    }
+
+    /// Whether the worker has loaded and started taking ticks.
+    #[wasm_bindgen(getter)]
+    pub fn ready(&self) -> bool {
+        self.ready.get()
+    }
+
+    /// Why the worker stopped, when it failed to start the world or to apply
+    /// commands. It takes no more ticks after that.
+    #[wasm_bindgen(getter)]
+    pub fn error(&self) -> Option<String> {
+        self.error.borrow().clone()
+    }
+
+    /// Queues an idle agent under `key`, which later commands use.
+    pub fn add_agent(&mut self, key: u32, x: f32, y: f32, max_speed: f32, max_force: f32) {
+        self.queue(ADD, key, [x, y, max_speed, max_force]);
+    }
+
+    pub fn remove_agent(&mut self, key: u32) {
+        self.queue(REMOVE, key, [0.0; 4]);
+    }
+
+    /// Queues `AiWorld::set_target` for the agent under `key`.
+    pub fn set_target(&mut self, key: u32, x: f32, y: f32, slow_radius: f32) {
+        self.queue(TARGET, key, [x, y, slow_radius, 0.0]);
+    }
+
+    pub fn clear_target(&mut self, key: u32) {
+        self.set_behavior(key, Behavior::Idle);
+    }
+
+    pub fn set_behavior(&mut self, key: u32, behavior: Behavior) {
+        self.queue(BEHAVIOR, key, [behavior as u32 as f32, 0.0, 0.0, 0.0]);
+    }
+
+    pub fn set_position(&mut self, key: u32, x: f32, y: f32) {
+        self.queue(POSITION, key, [x, y, 0.0, 0.0]);
+    }
+
+    /// Sends the worker `elapsed` seconds plus any carried over, with the
+    /// queued commands. Returns false, keeping both for the next call, while
+    /// the worker is loading or still on the last tick, and for good once it
+    /// has failed; see `error`.
+    pub fn tick(&mut self, elapsed: f64) -> bool {
+        self.elapsed += elapsed;
+        if !self.ready.get() || self.error.borrow().is_some() {
+            return false;
+        }
+        if !self.started {
+            let message = Object::new();
+            for (name, value) in [
+                ("buffer", self.buffer.clone().into()),
+                ("capacity", self.capacity.into()),
+                ("timestep", self.timestep.into()),
+            ] {
+                Reflect::set(&message, &name.into(), &value).unwrap_throw();
+            }
+            post(&self.worker, &message, None);
+            self.started = true;
+        }
+        if self.regions.load(FRAME) as u32 != self.sent {
+            return false;
+        }
+        let commands = Float64Array::from(&self.commands[..]);
+        let message = Object::new();
+        Reflect::set(&message, &"commands".into(), &commands).unwrap_throw();
+        Reflect::set(&message, &"elapsed".into(), &self.elapsed.into()).unwrap_throw();
+        post(
+            &self.worker,
+            &message,
+            Some(&Array::of1(&commands.buffer())),
+        );
+        self.commands.clear();
+        self.elapsed = 0.0;
+        self.sent += 1;
+        true
+    }
+
+    /// Copies the latest frame if the worker has published a new one since the
+    /// last call. Returns false, keeping the frame already read, when there is
+    /// nothing new or the worker kept writing over every attempt.
+    pub fn read(&mut self) -> bool {
+        let regions = &self.regions;
+        for _ in 0..4 {
+            let sequence = regions.load(SEQUENCE);
+            let frame = regions.load(FRAME) as u32;
+            if frame == self.frame {
+                return false;
+            }
+            if sequence % 2 != 0 {
+                continue;
+            }
+            let count = (regions.load(COUNT) as u32).min(self.capacity) as usize;
+            let step_count = regions.load(STEP_COUNT) as u32;
+            let alpha = f32::from_bits(regions.load(ALPHA) as u32);
+            let time = f64::from_bits(
+                (regions.load(TIME) as u32 as u64) << 32 | regions.load(TIME + 1) as u32 as u64,
+            );
+            for (column, values) in [
+                (&regions.keys, &mut self.keys),
+                (&regions.handles, &mut self.handles),
+                (&regions.behaviors, &mut self.behaviors),
+            ] {
+                values.resize(count, 0);
+                column.subarray(0, count as u32).copy_to(values);
+            }
+            for (column, values) in [
+                (&regions.positions, &mut self.positions),
+                (&regions.interpolated, &mut self.interpolated),
+                (&regions.velocities, &mut self.velocities),
+                (&regions.headings, &mut self.headings),
+            ] {
+                values.resize(count * 2, 0.0);
+                column.subarray(0, count as u32 * 2).copy_to(values);
+            }
+            if regions.load(SEQUENCE) == sequence {
+                self.frame = frame;
+                self.step_count = step_count;
+                self.alpha = alpha;
+                self.time = time;
+                return true;
+            }
+        }
+        false
+    }
+
+    /// How many frames the worker has published, as of the last `read`.
+    #[wasm_bindgen(getter)]
+    pub fn frame(&self) -> u32 {
+        self.frame
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn len(&self) -> u32 {
+        self.keys.len() as u32
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn step_count(&self) -> u32 {
+        self.step_count
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn alpha(&self) -> f32 {
+        self.alpha
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn time(&self) -> f64 {
+        self.time
+    }
+
+    /// Keys of the agents, in the order of the other arrays.
+    pub fn keys(&self) -> Vec<u32> {
+        self.keys.clone()
+    }
+
+    pub fn handles(&self) -> Vec<u32> {
+        self.handles.clone()
+    }
+
+    /// Each agent's `Behavior` as a number.
+    pub fn behaviors(&self) -> Vec<u32> {
+        self.behaviors.clone()
+    }
+
+    /// Flat `[x, y, ...]` positions after the last fixed step.
+    pub fn positions(&self) -> Vec<f32> {
+        self.positions.clone()
+    }
+
+    /// Flat `[x, y, ...]` positions interpolated when the frame was published.
+    pub fn interpolated_positions(&self) -> Vec<f32> {
+        self.interpolated.clone()
+    }
+
+    pub fn velocities(&self) -> Vec<f32> {
+        self.velocities.clone()
+    }
+
+    pub fn headings(&self) -> Vec<f32> {
+        self.headings.clone()
+    }
+}
+
+impl WorldWorker {
+    fn queue(&mut self, op: f64, key: u32, arguments: [f32; 4]) {
+        self.commands.extend_from_slice(&[op, key as f64]);
+        self.commands
+            .extend(arguments.iter().map(|&argument| argument as f64));
+    }
+}
@@ -0,0 +1,221 @@
+use wasm_bindgen::prelude::*;
+
+use crate::error::Error;
+use crate::grid::Grid;
+
+type Point = [f32; 3];
+
+/// Curve samples per `spacing` used to measure arc length before resampling.
+const SUBDIVISIONS: f32 = 8.0;
+
+#[wasm_bindgen]
+impl Grid {
+    /// Drops every waypoint of a `find_path` result that the previous kept waypoint
+    /// can see past, leaving only the corners. Consecutive waypoints of the result
+    /// have line of sight between cell centers.
+    pub fn smooth_path(&self, path: &[f32]) -> Vec<f32> {
+        let cells: Vec<(i32, i32)> = path
+            .chunks_exact(2)
+            .map(|p| (p[0] as i32, p[1] as i32))
+            .collect();
+        let Some(&first) = cells.first() else {
+            return Vec::new();
+        };
+
+        let mut kept = vec![first];
+        let mut anchor = first;
+        for pair in cells.windows(2) {
+            let (previous, next) = (pair[0], pair[1]);
+            if !self.line_of_sight(anchor.0, anchor.1, next.0, next.1) {
+                kept.push(previous);
+                anchor = previous;
+            }
+        }
+        if cells.len() > 1 {
+            kept.push(cells[cells.len() - 1]);
+        }
+        kept.into_iter()
+            .flat_map(|(x, y)| [x as f32, y as f32])
+            .collect()
+    }
+}
+
+/// Resamples a flat path of `dimensions`-component points (2 or 3) along a
+/// centripetal Catmull-Rom spline through every waypoint, with points `spacing`
+/// apart along the curve. The curve may bulge slightly outside sharp corners.
+#[wasm_bindgen]
+pub fn resample_catmull_rom(
+    path: &[f32],
+    dimensions: u32,
+    spacing: f32,
+) -> Result<Vec<f32>, Error> {
+    let points = parse_path(path, dimensions, spacing)?;
+    Ok(resample(&points, dimensions, spacing, catmull_rom_curve))
+}
+
+/// Like `resample_catmull_rom`, but rounds each interior corner with a quadratic
+/// Bezier from the middle of the incoming segment to the middle of the outgoing
+/// one. The curve cuts inside the corner waypoints instead of passing through
+/// them, so it never swings wide of the original path.
+#[wasm_bindgen]
+pub fn resample_bezier(path: &[f32], dimensions: u32, spacing: f32) -> Result<Vec<f32>, Error> {
+    let points = parse_path(path, dimensions, spacing)?;
+    Ok(resample(&points, dimensions, spacing, bezier_curve))
+}
+
+fn parse_path(path: &[f32], dimensions: u32, spacing: f32) -> Result<Vec<Point>, Error> {
+    if dimensions != 2 && dimensions != 3 {
+        return Err(Error::InvalidInput(format!(
+            "path dimensions must be 2 or 3, got {}",
+            dimensions
+        )));
+    }
+    if !path.len().is_multiple_of(dimensions as usize) {
+        return Err(Error::InvalidInput(format!(
+            "path length must be a multiple of {}",
+            dimensions
+        )));
+    }
+    if spacing > 0.0 && spacing.is_finite() {
+        Ok(path
+            .chunks_exact(dimensions as usize)
+            .map(|p| [p[0], p[1], p.get(2).copied().unwrap_or(0.0)])
+            .collect())
+    } else {
+        Err(Error::InvalidInput(format!(
+            "spacing must be positive, got {}",
+            spacing
+        )))
+    }
+}
+
+/// Builds a dense polyline with `curve`, then walks it emitting a point every
+/// `spacing`, always keeping both endpoints.
+fn resample(
+    points: &[Point],
+    dimensions: u32,
+    spacing: f32,
+    curve: fn(&[Point], f32) -> Vec<Point>,
+) -> Vec<f32> {
+    let dense = if points.len() < 3 {
+        points.to_vec()
+    } else {
+        curve(points, spacing / SUBDIVISIONS)
+    };
+    let Some(&first) = dense.first() else {
+        return Vec::new();
+    };
+
+    let mut samples = vec![first];
+    let mut travelled = 0.0;
+    for pair in dense.windows(2) {
+        let length = distance(pair[0], pair[1]);
+        let mut along = spacing - travelled;
+        while along <= length {
+            samples.push(lerp(pair[0], pair[1], along / length));
+            along += spacing;
+        }
+        travelled = length - (along - spacing);
+    }
+    let last = dense[dense.len() - 1];
+    if distance(samples[samples.len() - 1], last) > spacing * 1e-3 {
+        samples.push(last);
+    }
+
+    samples
+        .into_iter()
+        .flat_map(|p| p.into_iter().take(dimensions as usize))
+        .collect()
+}
+
+fn catmull_rom_curve(points: &[Point], step: f32) -> Vec<Point> {
+    let n = points.len();
+    let control = |i: isize| -> Point {
+        // Reflect the end segments to get phantom control points past each end.
+        if i < 0 {
+            sub(scale(points[0], 2.0), points[1])
+        } else if i as usize >= n {
+            sub(scale(points[n - 1], 2.0), points[n - 2])
+        } else {
+            points[i as usize]
+        }
+    };
+
+    let mut dense = vec![points[0]];
+    for i in 0..n - 1 {
+        let p = [
+            control(i as isize - 1),
+            points[i],
+            points[i + 1],
+            control(i as isize + 2),
+        ];
+        let segments = (distance(p[1], p[2]) / step).ceil().max(1.0) as usize;
+        for k in 1..=segments {
+            dense.push(centripetal(p, k as f32 / segments as f32));
+        }
+    }
+    dense
+}
+
+/// Barry-Goldman evaluation of a centripetal (alpha = 0.5) Catmull-Rom segment
+/// between `p[1]` and `p[2]`.
+fn centripetal(p: [Point; 4], u: f32) -> Point {
+    let knot = |a: Point, b: Point| distance(a, b).sqrt().max(1e-4);
+    let t0 = 0.0;
+    let t1 = t0 + knot(p[0], p[1]);
+    let t2 = t1 + knot(p[1], p[2]);
+    let t3 = t2 + knot(p[2], p[3]);
+    let t = t1 + (t2 - t1) * u;
+
+    let blend = |a: Point, b: Point, ta: f32, tb: f32| lerp(a, b, (t - ta) / (tb - ta));
+    let a1 = blend(p[0], p[1], t0, t1);
+    let a2 = blend(p[1], p[2], t1, t2);
+    let a3 = blend(p[2], p[3], t2, t3);
+    let b1 = blend(a1, a2, t0, t2);
+    let b2 = blend(a2, a3, t1, t3);
+    blend(b1, b2, t1, t2)
+}
+
+fn bezier_curve(points: &[Point], step: f32) -> Vec<Point> {
+    let n = points.len();
+    let mut dense = vec![points[0]];
+    let mut from = points[0];
+    for i in 1..n - 1 {
+        let corner = points[i];
+        let to = if i + 1 == n - 1 {
+            points[n - 1]
+        } else {
+            lerp(corner, points[i + 1], 0.5)
+        };
+        let segments = ((distance(from, corner) + distance(corner, to)) / step)
+            .ceil()
+            .max(1.0) as usize;
+        for k in 1..=segments {
+            let t = k as f32 / segments as f32;
+            dense.push(lerp(lerp(from, corner, t), lerp(corner, to, t), t));
+        }
+        from = to;
+    }
+    dense
+}
+
+fn sub(a: Point, b: Point) -> Point {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn scale(a: Point, s: f32) -> Point {
+    [a[0] * s, a[1] * s, a[2] * s]
+}
+
+fn lerp(a: Point, b: Point, t: f32) -> Point {
+    [
+        a[0] + (b[0] - a[0]) * t,
+        a[1] + (b[1] - a[1]) * t,
+        a[2] + (b[2] - a[2]) * t,
+    ]
+}
+
+fn distance(a: Point, b: Point) -> f32 {
+    let d = sub(a, b);
+    (d[0] * d[0] + d[1] * d[1] + d[2] * d[2]).sqrt()
+}
@@ -0,0 +1,180 @@
+use std::collections::HashMap;
+
+use wasm_bindgen::prelude::*;
+
+type Cell = (i32, i32);
+
+/// Uniform-grid spatial hash for 2D point neighbor queries. Pick a `cell_size`
+/// close to the typical query radius.
+#[wasm_bindgen]
+pub struct SpatialHash {
+    cell_size: f32,
+    cells: HashMap<Cell, Vec<u32>>,
+    entries: HashMap<u32, (f32, f32, Cell)>,
+}
+
+#[wasm_bindgen]
+impl SpatialHash {
+    #[wasm_bindgen(constructor)]
+    pub fn new(cell_size: f32) -> SpatialHash {
+        SpatialHash {
+            cell_size: cell_size.max(f32::EPSILON),
+            cells: HashMap::new(),
+            entries: HashMap::new(),
+        }
+    }
+
+    /// Inserts `id` at a position, replacing any previous entry for it.
+    pub fn insert(&mut self, id: u32, x: f32, y: f32) {
+        self.remove(id);
+        let cell = self.cell(x, y);
+        self.cells.entry(cell).or_default().push(id);
+        self.entries.insert(id, (x, y, cell));
+    }
+
+    /// Moves `id` to a new position, inserting it if it is not present.
+    #[wasm_bindgen(js_name = "move")]
+    pub fn move_to(&mut self, id: u32, x: f32, y: f32) {
+        let cell = self.cell(x, y);
+        match self.entries.get_mut(&id) {
+            Some(entry) if entry.2 == cell => {
+                entry.0 = x;
+                entry.1 = y;
+            }
+            _ => self.insert(id, x, y),
+        }
+    }
+
+    pub fn remove(&mut self, id: u32) -> bool {
+        let Some((_, _, cell)) = self.entries.remove(&id) else {
+            return false;
+        };
+        if let Some(ids) = self.cells.get_mut(&cell) {
+            ids.retain(|&other| other != id);
+            if ids.is_empty() {
+                self.cells.remove(&cell);
+            }
+        }
+        true
+    }
+
+    /// Ids whose position lies within `radius` of `(x, y)`.
+    pub fn query_radius(&self, x: f32, y: f32, radius: f32) -> Vec<u32> {
+        let mut out = Vec::new();
+        self.query_radius_into(x, y, radius, &mut out);
+        out
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn len(&self) -> u32 {
+        self.entries.len() as u32
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.cells.clear();
+        self.entries.clear();
+    }
+}
+
+impl SpatialHash {
+    /// Like `query_radius`, appending into a caller-owned buffer. A radius
+    /// that is not finite matches nothing.
+    pub fn query_radius_into(&self, x: f32, y: f32, radius: f32, out: &mut Vec<u32>) {
+        if !radius.is_finite() {
+            return;
+        }
+        let (min_x, min_y) = self.cell(x - radius, y - radius);
+        let (max_x, max_y) = self.cell(x + radius, y + radius);
+        let radius_squared = radius * radius;
+        let mut visit = |ids: &[u32]| {
+            for &id in ids {
+                let (px, py, _) = self.entries[&id];
+                let (dx, dy) = (px - x, py - y);
+                if dx * dx + dy * dy <= radius_squared {
+                    out.push(id);
+                }
+            }
+        };
+        let spanned = (max_x as i64 - min_x as i64 + 1) * (max_y as i64 - min_y as i64 + 1);
+        if spanned > self.cells.len() as i64 {
+            // Fewer cells are occupied than the radius spans, so look at just
+            // those, in the row-major order the scan below visits them in.
+            let mut cells: Vec<(&Cell, &Vec<u32>)> = self
+                .cells
+                .iter()
+                .filter(|((cx, cy), _)| {
+                    (min_x..=max_x).contains(cx) && (min_y..=max_y).contains(cy)
+                })
+                .collect();
+            cells.sort_unstable_by_key(|&(&(cx, cy), _)| (cy, cx));
+            for (_, ids) in cells {
+                visit(ids);
+            }
+            return;
+        }
+        for cy in min_y..=max_y {
+            for cx in min_x..=max_x {
+                if let Some(ids) = self.cells.get(&(cx, cy)) {
+                    visit(ids);
+                }
+            }
+        }
+    }
+
+    pub fn position(&self, id: u32) -> Option<(f32, f32)> {
+        self.entries.get(&id).map(|&(x, y, _)| (x, y))
+    }
+
+    pub fn set_cell_size(&mut self, cell_size: f32) {
+        let cell_size = cell_size.max(f32::EPSILON);
+        if cell_size == self.cell_size {
+            return;
+        }
+        self.cell_size = cell_size;
+        let entries: Vec<(u32, f32, f32)> = self
+            .entries
+            .iter()
+            .map(|(&id, &(x, y, _))| (id, x, y))
+            .collect();
+        self.clear();
+        for (id, x, y) in entries {
+            self.insert(id, x, y);
+        }
+    }
+
+    fn cell(&self, x: f32, y: f32) -> Cell {
+        (
+            (x / self.cell_size).floor() as i32,
+            (y / self.cell_size).floor() as i32,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn radius_that_is_not_finite_matches_nothing() {
+        let mut hash = SpatialHash::new(1.0);
+        hash.insert(0, 0.0, 0.0);
+        assert!(hash.query_radius(0.0, 0.0, f32::INFINITY).is_empty());
+        assert!(hash.query_radius(0.0, 0.0, f32::NAN).is_empty());
+    }
+
+    #[test]
+    fn huge_radius_scans_only_occupied_cells() {
+        let mut hash = SpatialHash::new(0.01);
+        hash.insert(2, 1.0e6, 0.0);
+        hash.insert(0, -5.0, 3.0);
+        hash.insert(1, 5.0, 3.0);
+        // Spans some 10^17 cells, so scanning the whole box would never end.
+        // In row-major cell order, as a scan of the box would find them.
+        assert_eq!(hash.query_radius(0.0, 0.0, 2.0e6), vec![2, 0, 1]);
+        assert_eq!(hash.query_radius(0.0, 0.0, 10.0), vec![0, 1]);
+    }
+}
@@ -0,0 +1,397 @@
+use wasm_bindgen::prelude::*;
+
+use crate::blackboard::Blackboard;
+use crate::math::Vec2;
+use crate::steering::Agent;
+
+/// Angle from the approach direction of the first pair of flanking positions in
+/// an attack, in radians; each further pair swings out by `FLANK_STEP`.
+const FLANK_ANGLE: f32 = 1.0;
+const FLANK_STEP: f32 = 0.35;
+
+/// Support members hold back this many times `engage_distance` from an attack
+/// target.
+const SUPPORT_RANGE: f32 = 1.5;
+
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Role {
+    /// Leads the squad and takes the front position.
+    Pointman = 0,
+    /// Spreads out to the sides and swings around attack targets.
+    Flanker = 1,
+    /// Trails behind and keeps its distance from attack targets.
+    Support = 2,
+}
+
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Order {
+    /// Stay where the order was given.
+    Hold = 0,
+    Move = 1,
+    Attack = 2,
+}
+
+struct Member {
+    id: u32,
+    agent: Agent,
+    role: Role,
+    /// Where the current order sends this member.
+    goal: Vec2,
+}
+
+/// A group of agents acting on shared orders. `order_move`, `order_attack` and
+/// `order_hold` turn one squad-level order into a position for each member based
+/// on its role, and `update` steers the members there while keeping them apart.
+///
+/// The shared blackboard is written with the current order as `"order"` (a
+/// string) and `"order_target"` (a vec2), next to whatever facts the squad's own
+/// logic stores there.
+#[wasm_bindgen]
+pub struct Squad {
+    members: Vec<Member>,
+    next_id: u32,
+    order: Order,
+    target: Vec2,
+    /// Direction from the squad to the target when the order was given.
+    heading: Vec2,
+    blackboard: Blackboard,
+    /// Distance kept between members, both in their positions and while moving.
+    pub spacing: f32,
+    /// How close the pointman and flankers get to an attack target.
+    pub engage_distance: f32,
+    /// Members slow down within this distance of their position.
+    pub slow_radius: f32,
+}
+
+#[wasm_bindgen]
+impl Squad {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Squad {
+        Squad {
+            members: Vec::new(),
+            next_id: 0,
+            order: Order::Hold,
+            target: Vec2::ZERO,
+            heading: Vec2::new(1.0, 0.0),
+            blackboard: Blackboard::new(),
+            spacing: 2.0,
+            engage_distance: 8.0,
+            slow_radius: 2.0,
+        }
+    }
+
+    /// Adds a copy of `agent`, holding its current position, and returns its id.
+    pub fn add_member(&mut self, agent: &Agent, role: Role) -> u32 {
+        let id = self.next_id;
+        self.next_id = self.next_id.wrapping_add(1);
+        self.members.push(Member {
+            id,
+            agent: agent.clone(),
+            role,
+            goal: agent.position,
+        });
+        self.reissue();
+        id
+    }
+
+    pub fn remove_member(&mut self, id: u32) -> bool {
+        let before = self.members.len();
+        self.members.retain(|member| member.id != id);
+        let removed = self.members.len() != before;
+        if removed {
+            self.reissue();
+        }
+        removed
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn len(&self) -> u32 {
+        self.members.len() as u32
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.members.is_empty()
+    }
+
+    /// Member ids in the order of `positions` and `velocities`.
+    pub fn ids(&self) -> Vec<u32> {
+        self.members.iter().map(|member| member.id).collect()
+    }
+
+    /// A copy of a member's current state.
+    pub fn member(&self, id: u32) -> Option<Agent> {
+        self.find(id).map(|member| member.agent.clone())
+    }
+
+    /// Replaces a member's state, e.g. after teleporting it.
+    pub fn set_member(&mut self, id: u32, agent: &Agent) {
+        if let Some(member) = self.find_mut(id) {
+            member.agent = agent.clone();
+        }
+    }
+
+    pub fn role(&self, id: u32) -> Option<Role> {
+        self.find(id).map(|member| member.role)
+    }
+
+    pub fn set_role(&mut self, id: u32, role: Role) -> bool {
+        let Some(member) = self.find_mut(id) else {
+            return false;
+        };
+        member.role = role;
+        self.reissue();
+        true
+    }
+
+    /// Reassigns every role from the members' positions relative to `x, y`: the
+    /// closest member becomes pointman, the next `flankers` closest become
+    /// flankers, and the rest support.
+    pub fn assign_roles(&mut self, x: f32, y: f32, flankers: u32) {
+        let toward = Vec2::new(x, y);
+        let mut order: Vec<usize> = (0..self.members.len()).collect();
+        order.sort_by(|&a, &b| {
+            let distance = |i: usize| self.members[i].agent.position.distance(toward);
+            distance(a).total_cmp(&distance(b))
+        });
+        for (rank, index) in order.into_iter().enumerate() {
+            self.members[index].role = match rank {
+                0 => Role::Pointman,
+                rank if rank <= flankers as usize => Role::Flanker,
+                _ => Role::Support,
+            };
+        }
+        self.reissue();
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn order(&self) -> Order {
+        self.order
+    }
+
+    /// Where the current move or attack order points; for hold, the squad's center
+    /// when the order was given.
+    #[wasm_bindgen(getter)]
+    pub fn order_target(&self) -> Vec2 {
+        self.target
+    }
+
+    /// Moves to `x, y`: the pointman ahead of the point, flankers spread out to its
+    /// sides and support trailing behind, all facing the direction of travel.
+    pub fn order_move(&mut self, x: f32, y: f32) {
+        self.issue(Order::Move, Vec2::new(x, y));
+    }
+
+    /// Closes in on `x, y`: the pointman straight on and flankers swinging around
+    /// to either side, both at `engage_distance`, with support hanging further
+    /// back.
+    pub fn order_attack(&mut self, x: f32, y: f32) {
+        self.issue(Order::Attack, Vec2::new(x, y));
+    }
+
+    /// Stops every member where it stands.
+    pub fn order_hold(&mut self) {
+        let center = self.center();
+        self.issue(Order::Hold, center);
+    }
+
+    /// Where the current order sends a member.
+    pub fn goal(&self, id: u32) -> Option<Vec2> {
+        self.find(id).map(|member| member.goal)
+    }
+
+    /// Average member position, or the origin for an empty squad.
+    pub fn center(&self) -> Vec2 {
+        if self.members.is_empty() {
+            return Vec2::ZERO;
+        }
+        let sum = self
+            .members
+            .iter()
+            .fold(Vec2::ZERO, |sum, member| sum + member.agent.position);
+        sum / self.members.len() as f32
+    }
+
+    /// Whether every member is within `radius` of its goal.
+    pub fn in_position(&self, radius: f32) -> bool {
+        self.members
+            .iter()
+            .all(|member| member.agent.position.distance(member.goal) <= radius)
+    }
+
+    /// Steers every member toward its goal, pushing away from squad mates closer
+    /// than `spacing`, and integrates them.
+    pub fn update(&mut self, dt: f32) {
+        let positions: Vec<Vec2> = self
+            .members
+            .iter()
+            .map(|member| member.agent.position)
+            .collect();
+        for (i, member) in self.members.iter_mut().enumerate() {
+            let agent = &member.agent;
+            let mut push = Vec2::ZERO;
+            for (j, &other) in positions.iter().enumerate() {
+                let offset = agent.position - other;
+                let distance = offset.length();
+                if j != i && distance > 0.0 && distance < self.spacing {
+                    push += offset.normalize() * (1.0 - distance / self.spacing);
+                }
+            }
+            let separation = if push == Vec2::ZERO {
+                Vec2::ZERO
+            } else {
+                (push.normalize() * agent.max_speed - agent.velocity).truncate(agent.max_force)
+            };
+            let force = agent.arrive(member.goal, self.slow_radius) + separation;
+            member.agent.update(force, dt);
+        }
+    }
+
+    /// Flat `[x0, y0, x1, y1, ...]` member positions, in the order of `ids`.
+    pub fn positions(&self) -> Vec<f32> {
+        self.members
+            .iter()
+            .flat_map(|member| [member.agent.position.x, member.agent.position.y])
+            .collect()
+    }
+
+    /// Flat `[vx0, vy0, vx1, vy1, ...]` member velocities, in the order of `ids`.
+    pub fn velocities(&self) -> Vec<f32> {
+        self.members
+            .iter()
+            .flat_map(|member| [member.agent.velocity.x, member.agent.velocity.y])
+            .collect()
+    }
+
+    pub fn set_fact_f64(&mut self, key: &str, value: f64) {
+        self.blackboard.set_f64(key, value);
+    }
+
+    pub fn get_fact_f64(&self, key: &str) -> Option<f64> {
+        self.blackboard.get_f64(key)
+    }
+
+    pub fn set_fact_bool(&mut self, key: &str, value: bool) {
+        self.blackboard.set_bool(key, value);
+    }
+
+    pub fn get_fact_bool(&self, key: &str) -> Option<bool> {
+        self.blackboard.get_bool(key)
+    }
+
+    pub fn set_fact_string(&mut self, key: &str, value: &str) {
+        self.blackboard.set_string(key, value);
+    }
+
+    pub fn get_fact_string(&self, key: &str) -> Option<String> {
+        self.blackboard.get_string(key)
+    }
+
+    pub fn set_fact_vec2(&mut self, key: &str, x: f32, y: f32) {
+        self.blackboard.set_vec2(key, x, y);
+    }
+
+    pub fn get_fact_vec2(&self, key: &str) -> Option<Vec2> {
+        self.blackboard.get_vec2(key)
+    }
+
+    pub fn remove_fact(&mut self, key: &str) -> bool {
+        self.blackboard.remove(key)
+    }
+}
+
+impl Default for Squad {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Squad {
+    /// The squad's shared blackboard, e.g. for `UtilityBrain::evaluate_blackboard`.
+    pub fn blackboard(&self) -> &Blackboard {
+        &self.blackboard
+    }
+
+    pub fn blackboard_mut(&mut self) -> &mut Blackboard {
+        &mut self.blackboard
+    }
+
+    fn find(&self, id: u32) -> Option<&Member> {
+        self.members.iter().find(|member| member.id == id)
+    }
+
+    fn find_mut(&mut self, id: u32) -> Option<&mut Member> {
+        self.members.iter_mut().find(|member| member.id == id)
+    }
+
+    fn issue(&mut self, order: Order, target: Vec2) {
+        let approach = (target - self.center()).normalize();
+        if approach != Vec2::ZERO {
+            self.heading = approach;
+        }
+        self.order = order;
+        self.target = target;
+        let name = match order {
+            Order::Hold => "hold",
+            Order::Move => "move",
+            Order::Attack => "attack",
+        };
+        self.blackboard.set_string("order", name);
+        self.blackboard.set_vec2("order_target", target.x, target.y);
+        match order {
+            Order::Hold => {
+                for member in &mut self.members {
+                    member.goal = member.agent.position;
+                }
+            }
+            Order::Move | Order::Attack => self.place(),
+        }
+    }
+
+    /// Recomputes goals after the members or their roles change. Holding members
+    /// keep their goals.
+    fn reissue(&mut self) {
+        if self.order != Order::Hold {
+            self.place();
+        }
+    }
+
+    /// Spreads the members' goals around the order target by role.
+    fn place(&mut self) {
+        let forward = self.heading;
+        let side = forward.perp();
+        let (target, spacing, engage) = (self.target, self.spacing, self.engage_distance);
+
+        let mut ranks = [0u32; 3];
+        for member in &mut self.members {
+            let rank = ranks[member.role as usize];
+            ranks[member.role as usize] += 1;
+            // Lanes fan out from the middle: 0, 1, -1, 2, -2, ...
+            let lane = rank.div_ceil(2) as f32 * if rank % 2 == 1 { 1.0 } else { -1.0 };
+            // Flankers skip the middle lane and alternate sides: 1, -1, 2, -2, ...
+            let flank_side = if rank % 2 == 0 { 1.0 } else { -1.0 };
+            let flank_lane = flank_side * (rank / 2 + 1) as f32;
+            member.goal = match (self.order, member.role) {
+                (Order::Move, Role::Pointman) => {
+                    target + forward * spacing + side * (lane * spacing)
+                }
+                (Order::Move, Role::Flanker) => target + side * (flank_lane * spacing),
+                (Order::Move, Role::Support) => {
+                    target - forward * ((rank + 1) as f32 * spacing) + side * (lane * 0.5 * spacing)
+                }
+                (Order::Attack, Role::Pointman) => {
+                    target - forward * engage + side * (lane * spacing)
+                }
+                (Order::Attack, Role::Flanker) => {
+                    let angle = flank_side * (FLANK_ANGLE + (rank / 2) as f32 * FLANK_STEP);
+                    target - forward.rotate(angle) * engage
+                }
+                (Order::Attack, Role::Support) => {
+                    target - forward * (engage * SUPPORT_RANGE) + side * (lane * spacing)
+                }
+                (Order::Hold, _) => member.goal,
+            };
+        }
+    }
+}
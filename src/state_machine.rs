@@ -0,0 +1,496 @@
+use js_sys::Function;
+use wasm_bindgen::prelude::*;
+
+use crate::bytes::{Reader, Writer};
+use crate::error::Error;
+use crate::events::{AiEvent, EventKind};
+
+/// Leading bytes of `StateMachine::save_state`, then a format version.
+const MAGIC: &[u8; 4] = b"LAIF";
+const VERSION: u32 = 1;
+
+pub type Hook = Box<dyn FnMut()>;
+pub type UpdateHook = Box<dyn FnMut(f32)>;
+pub type Guard = Box<dyn FnMut() -> bool>;
+
+/// What a parent state remembers of its sub-states when it is left.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StateHistory {
+    /// Entering the parent always starts at its initial sub-state.
+    None = 0,
+    /// Entering the parent resumes the sub-state it was last in, which then
+    /// enters its own sub-states by its own history.
+    Shallow = 1,
+    /// Entering the parent resumes exactly where it was left, all the way
+    /// down.
+    Deep = 2,
+}
+
+struct State {
+    name: String,
+    on_enter: Option<Hook>,
+    on_exit: Option<Hook>,
+    on_update: Option<UpdateHook>,
+    parent: Option<usize>,
+    /// The sub-state entered when the state itself is the target.
+    initial: Option<usize>,
+    history: StateHistory,
+    /// The sub-state active when the state was last left.
+    last_child: Option<usize>,
+}
+
+enum Trigger {
+    Guard(Guard),
+    Event(String),
+}
+
+struct Transition {
+    from: Option<usize>,
+    to: usize,
+    trigger: Trigger,
+}
+
+/// A finite state machine with named states, which may hold sub-states of
+/// their own, e.g. Combat containing Melee, Ranged and Reload. The machine is
+/// in one innermost state at a time and in every state around it; entering
+/// a parent enters its initial sub-state, or with history the one it was last
+/// in.
+///
+/// Transitions leave every state up to the one shared with the target, and
+/// enter every state down to it. Guarded transitions are checked at the start
+/// of each `update` and events on `send`, both from the innermost active state
+/// outward, so that a sub-state handles what it can and the rest bubbles up
+/// to its parents; transitions from any state are checked with the outermost.
+/// At each level they are checked in registration order, and the first that
+/// applies fires.
+#[wasm_bindgen]
+#[derive(Default)]
+pub struct StateMachine {
+    states: Vec<State>,
+    transitions: Vec<Transition>,
+    /// The innermost active state.
+    current: Option<usize>,
+    time_in_state: f32,
+    events: Vec<AiEvent>,
+}
+
+#[wasm_bindgen]
+impl StateMachine {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> StateMachine {
+        StateMachine::default()
+    }
+
+    /// Registers a state. `on_update` receives the frame's `dt`.
+    pub fn add_state(
+        &mut self,
+        name: &str,
+        on_enter: Option<Function>,
+        on_exit: Option<Function>,
+        on_update: Option<Function>,
+    ) -> Result<(), Error> {
+        self.add_state_with(
+            name,
+            on_enter.map(js_hook),
+            on_exit.map(js_hook),
+            on_update.map(js_update_hook),
+        )
+    }
+
+    /// Registers a sub-state of `parent`. The first sub-state added is the
+    /// initial one.
+    pub fn add_substate(
+        &mut self,
+        parent: &str,
+        name: &str,
+        on_enter: Option<Function>,
+        on_exit: Option<Function>,
+        on_update: Option<Function>,
+    ) -> Result<(), Error> {
+        // Checked first so an unknown parent leaves no orphan state behind.
+        self.find(parent)?;
+        self.add_state_with(
+            name,
+            on_enter.map(js_hook),
+            on_exit.map(js_hook),
+            on_update.map(js_update_hook),
+        )?;
+        self.set_parent(name, parent)
+    }
+
+    /// Picks which sub-state of `parent` it enters first.
+    pub fn set_initial_state(&mut self, parent: &str, child: &str) -> Result<(), Error> {
+        let (parent, child) = (self.find(parent)?, self.find(child)?);
+        if self.states[child].parent != Some(parent) {
+            return Err(Error::InvalidInput(format!(
+                "'{}' is not a sub-state of '{}'",
+                self.states[child].name, self.states[parent].name
+            )));
+        }
+        self.states[parent].initial = Some(child);
+        Ok(())
+    }
+
+    /// Sets what `parent` remembers of its sub-states when left.
+    pub fn set_history(&mut self, parent: &str, history: StateHistory) -> Result<(), Error> {
+        let parent = self.find(parent)?;
+        self.states[parent].history = history;
+        Ok(())
+    }
+
+    /// Adds a transition that fires when `guard` returns true while in `from`.
+    pub fn add_transition(&mut self, from: &str, to: &str, guard: Function) -> Result<(), Error> {
+        self.add_transition_with(Some(from), to, js_guard(guard))
+    }
+
+    /// Adds a transition that can fire from any state while not in `to`.
+    pub fn add_any_transition(&mut self, to: &str, guard: Function) -> Result<(), Error> {
+        self.add_transition_with(None, to, js_guard(guard))
+    }
+
+    /// Adds a transition that fires when `event` is sent while in `from`.
+    pub fn add_event_transition(&mut self, from: &str, event: &str, to: &str) -> Result<(), Error> {
+        let (from, to) = (self.find(from)?, self.find(to)?);
+        self.transitions.push(Transition {
+            from: Some(from),
+            to,
+            trigger: Trigger::Event(event.to_string()),
+        });
+        Ok(())
+    }
+
+    /// Fires the transition for `event` of the innermost active state that
+    /// has one. Returns false when no active state handles it.
+    pub fn send(&mut self, event: &str) -> bool {
+        self.events.clear();
+        for state in self.active() {
+            let next = self.transitions.iter().find_map(|transition| {
+                let handles = transition.from == Some(state)
+                    && matches!(&transition.trigger, Trigger::Event(name) if name == event);
+                handles.then_some(transition.to)
+            });
+            if let Some(next) = next {
+                self.enter(next);
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Leaves the current states (if any) and enters `name`.
+    pub fn transition_to(&mut self, name: &str) -> Result<(), Error> {
+        let index = self.find(name)?;
+        self.enter(index);
+        Ok(())
+    }
+
+    /// Name of the state added `index`th, as `StateChanged` events report it.
+    pub fn state_name(&self, index: u32) -> Option<String> {
+        self.states
+            .get(index as usize)
+            .map(|state| state.name.clone())
+    }
+
+    /// States entered since the last `update` or `send` began, by transitions
+    /// or `transition_to`, as `StateChanged` events with a subject of 0,
+    /// parents before their sub-states.
+    pub fn events(&self) -> Vec<AiEvent> {
+        self.events.clone()
+    }
+
+    /// The innermost active state.
+    pub fn current_state(&self) -> Option<String> {
+        self.current.map(|index| self.states[index].name.clone())
+    }
+
+    /// Whether `name` is the innermost active state or one around it.
+    pub fn is_in_state(&self, name: &str) -> bool {
+        self.find(name)
+            .is_ok_and(|index| self.active().contains(&index))
+    }
+
+    /// Names of the active states, outermost first.
+    pub fn active_states(&self) -> Vec<String> {
+        self.active()
+            .iter()
+            .rev()
+            .map(|&index| self.states[index].name.clone())
+            .collect()
+    }
+
+    /// Seconds since the innermost active state was entered.
+    #[wasm_bindgen(getter)]
+    pub fn time_in_state(&self) -> f32 {
+        self.time_in_state
+    }
+
+    /// Puts the machine back in `name`, `time_in_state` seconds in, without
+    /// running any hooks, e.g. when loading a save with what `current_state`
+    /// and `time_in_state` gave. A parent state resolves to the sub-state it
+    /// would enter.
+    pub fn restore(&mut self, name: &str, time_in_state: f32) -> Result<(), Error> {
+        let mut index = self.find(name)?;
+        let mut deep = false;
+        while let Some(child) = self.entry_child(index, &mut deep) {
+            index = child;
+        }
+        self.current = Some(index);
+        self.time_in_state = time_in_state;
+        Ok(())
+    }
+
+    /// The current state, how long it has been in it and the sub-state each
+    /// parent with history remembers, as bytes for `load_state`, e.g. in a
+    /// `SaveGame`.
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut out = Writer::new(MAGIC, VERSION);
+        let index = |state: Option<usize>| state.map_or(u32::MAX, |state| state as u32);
+        out.u32(index(self.current));
+        out.f32(self.time_in_state);
+        out.u32(self.states.len() as u32);
+        for state in &self.states {
+            out.string(&state.name);
+            out.u32(index(state.last_child));
+        }
+        out.finish()
+    }
+
+    /// Puts the machine back where the one `save_state` came from was,
+    /// without running any hooks. This machine must have the same states,
+    /// which their names are checked against.
+    pub fn load_state(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        let (mut input, version) = Reader::new(bytes, MAGIC, "a state machine save")?;
+        if version != VERSION {
+            return Err(Error::InvalidInput(
+                "unsupported state machine save version".into(),
+            ));
+        }
+        let count = self.states.len();
+        let index = |input: &mut Reader| match input.u32()? {
+            u32::MAX => Ok(None),
+            state if (state as usize) < count => Ok(Some(state as usize)),
+            _ => Err(input.invalid()),
+        };
+        let current = index(&mut input)?;
+        let time_in_state = input.f32()?;
+        let mismatch = || Error::InvalidInput("the save is of a machine with other states".into());
+        if input.u32()? as usize != count {
+            return Err(mismatch());
+        }
+        let mut last_children = Vec::with_capacity(count);
+        for state in &self.states {
+            if input.string()? != state.name {
+                return Err(mismatch());
+            }
+            last_children.push(index(&mut input)?);
+        }
+        input.finish()?;
+
+        self.current = current;
+        self.time_in_state = time_in_state;
+        for (state, last_child) in self.states.iter_mut().zip(last_children) {
+            state.last_child = last_child;
+        }
+        Ok(())
+    }
+
+    /// Fires at most one passing transition, then runs the update hooks of the
+    /// active states, outermost first.
+    pub fn update(&mut self, dt: f32) {
+        self.events.clear();
+        let active = self.active();
+        if let Some(&outermost) = active.last() {
+            let mut next = None;
+            'levels: for &state in &active {
+                for transition in &mut self.transitions {
+                    let applies = match transition.from {
+                        Some(from) => from == state,
+                        None => state == outermost && !active.contains(&transition.to),
+                    };
+                    if let (true, Trigger::Guard(guard)) = (applies, &mut transition.trigger) {
+                        if guard() {
+                            next = Some(transition.to);
+                            break 'levels;
+                        }
+                    }
+                }
+            }
+            if let Some(next) = next {
+                self.enter(next);
+            }
+        }
+
+        if self.current.is_some() {
+            self.time_in_state += dt;
+            for state in self.active().into_iter().rev() {
+                if let Some(on_update) = self.states[state].on_update.as_mut() {
+                    on_update(dt);
+                }
+            }
+        }
+    }
+}
+
+impl StateMachine {
+    pub fn add_state_with(
+        &mut self,
+        name: &str,
+        on_enter: Option<Hook>,
+        on_exit: Option<Hook>,
+        on_update: Option<UpdateHook>,
+    ) -> Result<(), Error> {
+        if self.states.iter().any(|state| state.name == name) {
+            return Err(Error::InvalidInput(format!(
+                "state '{}' already exists",
+                name
+            )));
+        }
+        self.states.push(State {
+            name: name.to_string(),
+            on_enter,
+            on_exit,
+            on_update,
+            parent: None,
+            initial: None,
+            history: StateHistory::None,
+            last_child: None,
+        });
+        Ok(())
+    }
+
+    /// Makes `name`, a state with no parent yet, a sub-state of `parent`.
+    pub fn set_parent(&mut self, name: &str, parent: &str) -> Result<(), Error> {
+        let (child, parent) = (self.find(name)?, self.find(parent)?);
+        if self.states[child].parent.is_some() {
+            return Err(Error::InvalidInput(format!(
+                "state '{}' already has a parent",
+                name
+            )));
+        }
+        if self.ancestors(parent).any(|state| state == child) {
+            return Err(Error::InvalidInput(format!(
+                "state '{}' cannot be inside itself",
+                name
+            )));
+        }
+        self.states[child].parent = Some(parent);
+        self.states[parent].initial.get_or_insert(child);
+        Ok(())
+    }
+
+    /// Adds a transition from `from`, or from any state when `from` is `None`.
+    pub fn add_transition_with(
+        &mut self,
+        from: Option<&str>,
+        to: &str,
+        guard: Guard,
+    ) -> Result<(), Error> {
+        let from = from.map(|name| self.find(name)).transpose()?;
+        let to = self.find(to)?;
+        self.transitions.push(Transition {
+            from,
+            to,
+            trigger: Trigger::Guard(guard),
+        });
+        Ok(())
+    }
+
+    pub(crate) fn recent_events(&self) -> &[AiEvent] {
+        &self.events
+    }
+
+    fn find(&self, name: &str) -> Result<usize, Error> {
+        self.states
+            .iter()
+            .position(|state| state.name == name)
+            .ok_or_else(|| Error::InvalidInput(format!("unknown state '{}'", name)))
+    }
+
+    /// `index` followed by its parents, outward.
+    fn ancestors(&self, index: usize) -> impl Iterator<Item = usize> + '_ {
+        std::iter::successors(Some(index), |&state| self.states[state].parent)
+    }
+
+    /// The active states, innermost first.
+    fn active(&self) -> Vec<usize> {
+        self.current
+            .map(|current| self.ancestors(current).collect())
+            .unwrap_or_default()
+    }
+
+    /// The sub-state that entering `index` goes on into, if it has any.
+    /// `deep` carries a deep history down from the states around it.
+    fn entry_child(&self, index: usize, deep: &mut bool) -> Option<usize> {
+        let state = &self.states[index];
+        *deep |= state.history == StateHistory::Deep;
+        let remembered = if *deep || state.history != StateHistory::None {
+            state.last_child
+        } else {
+            None
+        };
+        remembered.or(state.initial)
+    }
+
+    /// Leaves the active states that do not contain `index`, innermost first,
+    /// then enters those down to it and on into its sub-states.
+    fn enter(&mut self, index: usize) {
+        let mut shared = self.current;
+        while let Some(state) = shared {
+            if state != index && self.ancestors(index).any(|around| around == state) {
+                break;
+            }
+            if let Some(on_exit) = self.states[state].on_exit.as_mut() {
+                on_exit();
+            }
+            let parent = self.states[state].parent;
+            if let Some(parent) = parent {
+                self.states[parent].last_child = Some(state);
+            }
+            shared = parent;
+        }
+
+        let mut entering: Vec<usize> = self
+            .ancestors(index)
+            .take_while(|&state| Some(state) != shared)
+            .collect();
+        entering.reverse();
+        let mut deep = false;
+        let mut innermost = index;
+        while let Some(child) = self.entry_child(innermost, &mut deep) {
+            entering.push(child);
+            innermost = child;
+        }
+
+        self.current = Some(innermost);
+        self.time_in_state = 0.0;
+        for state in entering {
+            self.events
+                .push(AiEvent::new(EventKind::StateChanged, 0, state as u32));
+            if let Some(on_enter) = self.states[state].on_enter.as_mut() {
+                on_enter();
+            }
+        }
+    }
+}
+
+fn js_hook(callback: Function) -> Hook {
+    Box::new(move || {
+        let _ = callback.call0(&JsValue::NULL);
+    })
+}
+
+fn js_update_hook(callback: Function) -> UpdateHook {
+    Box::new(move |dt| {
+        let _ = callback.call1(&JsValue::NULL, &JsValue::from_f64(dt as f64));
+    })
+}
+
+fn js_guard(callback: Function) -> Guard {
+    Box::new(move || {
+        callback
+            .call0(&JsValue::NULL)
+            .map(|value| value.is_truthy())
+            .unwrap_or(false)
+    })
+}
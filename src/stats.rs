@@ -0,0 +1,86 @@
+use wasm_bindgen::prelude::*;
+
+use crate::clock;
+use crate::search;
+
+/// Cost of an AI component's last update, for a performance HUD. Each
+/// component fills the fields it has work for and leaves the rest at zero;
+/// `add` sums them into a frame total.
+///
+/// Timings come from `performance.now()`, which browsers coarsen to between 5
+/// and 100 µs unless the page is cross-origin isolated.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct AiStats {
+    /// Microseconds choosing steering and following paths.
+    pub steering_us: f64,
+    /// Microseconds searching for paths.
+    pub pathfinding_us: f64,
+    /// Microseconds resolving collisions between agents.
+    pub avoidance_us: f64,
+    /// Microseconds computing sight and hearing.
+    pub perception_us: f64,
+    /// Path requests still waiting after the update.
+    pub path_queue_length: u32,
+    /// A* nodes expanded.
+    pub node_expansions: u32,
+    /// Agents, or observers, updated.
+    pub agents: u32,
+    /// Fixed steps run.
+    pub steps: u32,
+}
+
+#[wasm_bindgen]
+impl AiStats {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> AiStats {
+        AiStats::default()
+    }
+
+    /// Both sets of stats summed.
+    pub fn add(&self, other: &AiStats) -> AiStats {
+        AiStats {
+            steering_us: self.steering_us + other.steering_us,
+            pathfinding_us: self.pathfinding_us + other.pathfinding_us,
+            avoidance_us: self.avoidance_us + other.avoidance_us,
+            perception_us: self.perception_us + other.perception_us,
+            path_queue_length: self.path_queue_length + other.path_queue_length,
+            node_expansions: self.node_expansions.wrapping_add(other.node_expansions),
+            agents: self.agents + other.agents,
+            steps: self.steps + other.steps,
+        }
+    }
+
+    /// Microseconds of all the timed work.
+    #[wasm_bindgen(getter)]
+    pub fn total_us(&self) -> f64 {
+        self.steering_us + self.pathfinding_us + self.avoidance_us + self.perception_us
+    }
+}
+
+/// Measures the time and A* expansions from its creation.
+pub(crate) struct Sample {
+    start_ms: f64,
+    expansions: u32,
+}
+
+impl Sample {
+    pub(crate) fn start() -> Sample {
+        Sample {
+            start_ms: clock::now_ms(),
+            expansions: search::expansions(),
+        }
+    }
+
+    pub(crate) fn start_ms(&self) -> f64 {
+        self.start_ms
+    }
+
+    pub(crate) fn elapsed_us(&self) -> f64 {
+        (clock::now_ms() - self.start_ms) * 1000.0
+    }
+
+    pub(crate) fn expansions(&self) -> u32 {
+        search::expansions().wrapping_sub(self.expansions)
+    }
+}
@@ -0,0 +1,198 @@
+use wasm_bindgen::prelude::*;
+
+use crate::math::Vec2;
+use crate::noise::Noise;
+use crate::random::Rng;
+
+/// A point-mass vehicle for Reynolds-style steering. Each behavior returns a
+/// steering force truncated to `max_force`; apply it with `update`.
+#[wasm_bindgen]
+#[derive(Clone)]
+pub struct Agent {
+    pub position: Vec2,
+    pub velocity: Vec2,
+    pub heading: Vec2,
+    pub max_speed: f32,
+    pub max_force: f32,
+    wander_target: Vec2,
+    /// How far `noise_wander` has read along its noise row.
+    wander_time: f32,
+    /// The noise row `noise_wander` reads, so agents sharing a `Noise` wander
+    /// apart.
+    wander_lane: f32,
+    rng: Rng,
+}
+
+#[wasm_bindgen]
+impl Agent {
+    #[wasm_bindgen(constructor)]
+    pub fn new(x: f32, y: f32, max_speed: f32, max_force: f32) -> Agent {
+        let mut agent = Agent {
+            position: Vec2::new(x, y),
+            velocity: Vec2::ZERO,
+            heading: Vec2::new(1.0, 0.0),
+            max_speed,
+            max_force,
+            wander_target: Vec2::new(1.0, 0.0),
+            wander_time: 0.0,
+            wander_lane: 0.0,
+            rng: Rng::default(),
+        };
+        agent.pick_lane();
+        agent
+    }
+
+    /// Steers toward `target` at full speed.
+    pub fn seek(&self, target: Vec2) -> Vec2 {
+        let desired = (target - self.position).normalize() * self.max_speed;
+        (desired - self.velocity).truncate(self.max_force)
+    }
+
+    /// Steers directly away from `target` at full speed.
+    pub fn flee(&self, target: Vec2) -> Vec2 {
+        let desired = (self.position - target).normalize() * self.max_speed;
+        (desired - self.velocity).truncate(self.max_force)
+    }
+
+    /// Like `seek`, but slows down linearly inside `slow_radius` to stop on the target.
+    pub fn arrive(&self, target: Vec2, slow_radius: f32) -> Vec2 {
+        let offset = target - self.position;
+        let distance = offset.length();
+        if distance <= f32::EPSILON {
+            return (-self.velocity).truncate(self.max_force);
+        }
+        let speed = if distance < slow_radius {
+            self.max_speed * distance / slow_radius
+        } else {
+            self.max_speed
+        };
+        let desired = offset * (speed / distance);
+        (desired - self.velocity).truncate(self.max_force)
+    }
+
+    /// Steers toward where a moving target will be: the interception point when
+    /// the agent is fast enough to catch it, otherwise the target's position
+    /// extrapolated by the time needed to cover the current distance.
+    pub fn pursue(&self, target: Vec2, target_velocity: Vec2) -> Vec2 {
+        match intercept(self.position, self.max_speed, target, target_velocity) {
+            Some(aim) => self.seek(aim),
+            None => {
+                self.seek(target + target_velocity * self.time_to_reach(target, target_velocity))
+            }
+        }
+    }
+
+    /// Flees from where a moving target will be, looking ahead by the time the
+    /// two would need to close the current distance.
+    pub fn evade(&self, target: Vec2, target_velocity: Vec2) -> Vec2 {
+        self.flee(target + target_velocity * self.time_to_reach(target, target_velocity))
+    }
+
+    /// Reynolds wander: jitters a target on a circle of `radius` projected `distance`
+    /// ahead of the agent and steers toward it.
+    pub fn wander(&mut self, jitter: f32, radius: f32, distance: f32) -> Vec2 {
+        let displacement = Vec2::new(self.rng.next_signed(), self.rng.next_signed()) * jitter;
+        self.wander_target = (self.wander_target + displacement).normalize() * radius;
+
+        let forward = self.heading;
+        let local = self.wander_target + Vec2::new(distance, 0.0);
+        let world = forward * local.x + forward.perp() * local.y;
+        world.truncate(self.max_force)
+    }
+
+    /// Wander that meanders rather than jitters: the target on the circle of
+    /// `radius` projected `distance` ahead turns smoothly with `noise`, about
+    /// `rate` swings per second, e.g. for grazing animals or idle patrols.
+    pub fn noise_wander(
+        &mut self,
+        noise: &Noise,
+        dt: f32,
+        rate: f32,
+        radius: f32,
+        distance: f32,
+    ) -> Vec2 {
+        self.wander_time += dt * rate;
+        let angle = noise.perlin2(self.wander_time, self.wander_lane) * std::f32::consts::PI;
+        let local = Vec2::new(distance + radius * angle.cos(), radius * angle.sin());
+
+        let forward = self.heading;
+        let world = forward * local.x + forward.perp() * local.y;
+        world.truncate(self.max_force)
+    }
+
+    /// Integrates `force` over `dt`, clamps to `max_speed`, and updates `heading`
+    /// while the agent is moving.
+    pub fn update(&mut self, force: Vec2, dt: f32) {
+        self.velocity =
+            (self.velocity + force.truncate(self.max_force) * dt).truncate(self.max_speed);
+        self.position += self.velocity * dt;
+        if self.velocity.length_squared() > 1e-8 {
+            self.heading = self.velocity.normalize();
+        }
+    }
+
+    pub fn set_seed(&mut self, seed: u64) {
+        self.rng = Rng::new(seed);
+        self.pick_lane();
+    }
+
+    /// Draws wander jitter from a copy of `rng`, e.g. the agent's own stream.
+    pub fn set_rng(&mut self, rng: &Rng) {
+        self.rng = rng.clone();
+        self.pick_lane();
+    }
+}
+
+impl Agent {
+    /// Picks the noise row for `noise_wander` from the generator without
+    /// advancing it, so `wander` draws the same numbers either way.
+    fn pick_lane(&mut self) {
+        self.wander_lane = self.rng.clone().next_f32() * 256.0;
+    }
+
+    /// Time for the agent and target to meet if both head straight for each other
+    /// at their current speeds.
+    fn time_to_reach(&self, target: Vec2, target_velocity: Vec2) -> f32 {
+        let closing = self.max_speed + target_velocity.length();
+        if closing > 0.0 {
+            self.position.distance(target) / closing
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Earliest point where something leaving `position` at `speed` can meet a target
+/// moving at constant `target_velocity`, or undefined when it can never catch up.
+#[wasm_bindgen]
+pub fn intercept_point(
+    position: Vec2,
+    speed: f32,
+    target: Vec2,
+    target_velocity: Vec2,
+) -> Option<Vec2> {
+    intercept(position, speed, target, target_velocity)
+}
+
+fn intercept(position: Vec2, speed: f32, target: Vec2, target_velocity: Vec2) -> Option<Vec2> {
+    // Solve |offset + target_velocity * t| = speed * t for the smallest t >= 0.
+    let offset = target - position;
+    let a = target_velocity.length_squared() - speed * speed;
+    let b = 2.0 * offset.dot(target_velocity);
+    let c = offset.length_squared();
+    let time = if a.abs() < 1e-6 {
+        (b < 0.0).then(|| -c / b)
+    } else {
+        let discriminant = b * b - 4.0 * a * c;
+        if discriminant < 0.0 {
+            None
+        } else {
+            let root = discriminant.sqrt();
+            [(-b - root) / (2.0 * a), (-b + root) / (2.0 * a)]
+                .into_iter()
+                .filter(|&t| t >= 0.0)
+                .reduce(f32::min)
+        }
+    }?;
+    Some(target + target_velocity * time)
+}
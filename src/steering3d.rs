@@ -0,0 +1,593 @@
+#[cfg(target_arch = "wasm32")]
+use js_sys::Float32Array;
+use wasm_bindgen::prelude::*;
+
+use crate::ballistics;
+use crate::debug::{self, DebugGeometry};
+use crate::error::Error;
+use crate::math::Vec3;
+use crate::parallel;
+use crate::random::Rng;
+use crate::spatial_hash::SpatialHash;
+
+/// The world's up axis, `+y` as in three.js.
+const UP: Vec3 = Vec3 {
+    x: 0.0,
+    y: 1.0,
+    z: 0.0,
+};
+
+/// A plane agents are held to, the points `p` with `normal.dot(p) == offset`.
+#[derive(Clone, Copy)]
+struct Plane {
+    normal: Vec3,
+    offset: f32,
+}
+
+impl Plane {
+    fn new(normal: Vec3, point: Vec3) -> Result<Plane, Error> {
+        let normal = normal.normalize();
+        if normal == Vec3::ZERO || !normal.x.is_finite() {
+            return Err(Error::InvalidInput("plane normal must be non-zero".into()));
+        }
+        Ok(Plane {
+            normal,
+            offset: normal.dot(point),
+        })
+    }
+
+    /// `vector` with its part along the normal removed.
+    fn flatten(&self, vector: Vec3) -> Vec3 {
+        reject(vector, self.normal)
+    }
+
+    /// The closest point on the plane to `point`.
+    fn snap(&self, point: Vec3) -> Vec3 {
+        point - self.normal * (self.normal.dot(point) - self.offset)
+    }
+}
+
+/// A point-mass vehicle steering in 3D, e.g. for birds, fish or spaceships,
+/// with the same behaviors as `Agent`. Positions are three.js style with `+y`
+/// up. `set_plane` holds it to a plane instead, e.g. a flat sea surface or a
+/// tilted orbit.
+///
+/// `update` turns by at most `max_turn_rate` radians per second, never climbs
+/// or dives steeper than `max_pitch` from the horizontal and rolls into turns
+/// by up to `max_bank`, so `orientation` gives a banked attitude ready to
+/// render.
+#[wasm_bindgen]
+#[derive(Clone)]
+pub struct Agent3 {
+    pub position: Vec3,
+    pub velocity: Vec3,
+    /// Unit direction of travel, kept while the agent stands still.
+    pub forward: Vec3,
+    /// Unit up of the agent's body, tilted by banking.
+    pub up: Vec3,
+    pub max_speed: f32,
+    pub max_force: f32,
+    /// Radians per second the heading can turn, unlimited by default.
+    pub max_turn_rate: f32,
+    /// Steepest climb or dive from the horizontal, in radians; `PI / 2` or
+    /// more leaves pitch free. Ignored while held to a plane.
+    pub max_pitch: f32,
+    /// Steepest roll into a turn, in radians; 0 keeps the agent level.
+    pub max_bank: f32,
+    /// Acceleration that banking balances against, e.g. 9.81 in meters.
+    pub gravity: f32,
+    plane: Option<Plane>,
+    /// The wander target in the agent's frame: `x` right, `y` up, `z` ahead.
+    wander_target: Vec3,
+    rng: Rng,
+}
+
+#[wasm_bindgen]
+impl Agent3 {
+    #[wasm_bindgen(constructor)]
+    pub fn new(x: f32, y: f32, z: f32, max_speed: f32, max_force: f32) -> Agent3 {
+        Agent3 {
+            position: Vec3::new(x, y, z),
+            velocity: Vec3::ZERO,
+            forward: Vec3::new(0.0, 0.0, 1.0),
+            up: UP,
+            max_speed,
+            max_force,
+            max_turn_rate: f32::INFINITY,
+            max_pitch: std::f32::consts::FRAC_PI_2,
+            max_bank: std::f32::consts::FRAC_PI_4,
+            gravity: 9.81,
+            plane: None,
+            wander_target: Vec3::new(0.0, 0.0, 1.0),
+            rng: Rng::default(),
+        }
+    }
+
+    /// Holds the agent to the plane through `point` with `normal`, moving it
+    /// onto the plane and dropping any velocity off it.
+    pub fn set_plane(&mut self, normal: Vec3, point: Vec3) -> Result<(), Error> {
+        let plane = Plane::new(normal, point)?;
+        self.position = plane.snap(self.position);
+        self.velocity = plane.flatten(self.velocity);
+        let forward = plane.flatten(self.forward).normalize();
+        self.forward = if forward == Vec3::ZERO {
+            any_perpendicular(plane.normal)
+        } else {
+            forward
+        };
+        self.up = level_up(self.forward, plane.normal);
+        self.plane = Some(plane);
+        Ok(())
+    }
+
+    /// Lets the agent move freely in 3D again.
+    pub fn clear_plane(&mut self) {
+        self.plane = None;
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn has_plane(&self) -> bool {
+        self.plane.is_some()
+    }
+
+    /// Steers toward `target` at full speed.
+    pub fn seek(&self, target: Vec3) -> Vec3 {
+        let desired = (target - self.position).normalize() * self.max_speed;
+        self.steer(desired - self.velocity)
+    }
+
+    /// Steers directly away from `target` at full speed.
+    pub fn flee(&self, target: Vec3) -> Vec3 {
+        let desired = (self.position - target).normalize() * self.max_speed;
+        self.steer(desired - self.velocity)
+    }
+
+    /// Like `seek`, but slows down linearly inside `slow_radius` to stop on the target.
+    pub fn arrive(&self, target: Vec3, slow_radius: f32) -> Vec3 {
+        let offset = target - self.position;
+        let distance = offset.length();
+        if distance <= f32::EPSILON {
+            return self.steer(-self.velocity);
+        }
+        let speed = if distance < slow_radius {
+            self.max_speed * distance / slow_radius
+        } else {
+            self.max_speed
+        };
+        self.steer(offset * (speed / distance) - self.velocity)
+    }
+
+    /// Steers toward where a moving target will be, as `Agent::pursue`.
+    pub fn pursue(&self, target: Vec3, target_velocity: Vec3) -> Vec3 {
+        match intercept(self.position, self.max_speed, target, target_velocity) {
+            Some(aim) => self.seek(aim),
+            None => {
+                self.seek(target + target_velocity * self.time_to_reach(target, target_velocity))
+            }
+        }
+    }
+
+    /// Flees from where a moving target will be, as `Agent::evade`.
+    pub fn evade(&self, target: Vec3, target_velocity: Vec3) -> Vec3 {
+        self.flee(target + target_velocity * self.time_to_reach(target, target_velocity))
+    }
+
+    /// Reynolds wander on a sphere: jitters a target on a sphere of `radius`
+    /// projected `distance` ahead of the agent and steers toward it.
+    pub fn wander(&mut self, jitter: f32, radius: f32, distance: f32) -> Vec3 {
+        let displacement = Vec3::new(
+            self.rng.next_signed(),
+            self.rng.next_signed(),
+            self.rng.next_signed(),
+        ) * jitter;
+        self.wander_target = (self.wander_target + displacement).normalize() * radius;
+
+        let local = self.wander_target;
+        let world =
+            self.right() * local.x + self.up * local.y + self.forward * (local.z + distance);
+        self.steer(world)
+    }
+
+    /// Integrates `force` over `dt` within the turn, pitch and plane limits,
+    /// clamps to `max_speed`, and updates `forward` and the banked `up`.
+    pub fn update(&mut self, force: Vec3, dt: f32) {
+        let mut velocity = (self.velocity + self.steer(force) * dt).truncate(self.max_speed);
+        if let Some(plane) = self.plane {
+            velocity = plane.flatten(velocity);
+        }
+        let speed = velocity.length();
+        let reference = self.plane.map_or(UP, |plane| plane.normal);
+        let before = self.forward;
+        if speed > 1e-4 {
+            let mut direction = velocity / speed;
+            if self.plane.is_none() {
+                direction = clamp_pitch(direction, before, self.max_pitch);
+            }
+            let forward = turn_toward(before, direction, self.max_turn_rate * dt, reference);
+            self.forward = forward;
+            self.velocity = forward * speed;
+        } else {
+            self.velocity = velocity;
+        }
+        self.position += self.velocity * dt;
+        if let Some(plane) = self.plane {
+            self.position = plane.snap(self.position);
+        }
+
+        // Bank so lift balances gravity against the turn's centripetal pull.
+        let level = level_up(self.forward, reference);
+        let turn = if dt > 0.0 {
+            (self.forward - before) / dt * speed
+        } else {
+            Vec3::ZERO
+        };
+        let lateral = reject(reject(turn, self.forward), level);
+        let pull = lateral.length();
+        self.up = if pull > 1e-4 && self.max_bank > 0.0 {
+            let bank = pull
+                .atan2(self.gravity.max(f32::EPSILON))
+                .min(self.max_bank);
+            level * bank.cos() + lateral / pull * bank.sin()
+        } else {
+            level
+        };
+    }
+
+    /// The agent's attitude as an `[x, y, z, w]` quaternion turning `+z` onto
+    /// `forward` and `+y` onto `up`, ready for three.js `Quaternion.fromArray`.
+    pub fn orientation(&self) -> Vec<f32> {
+        quaternion(self.right(), self.up, self.forward).to_vec()
+    }
+
+    pub fn set_seed(&mut self, seed: u64) {
+        self.rng = Rng::new(seed);
+    }
+
+    /// Draws wander jitter from a copy of `rng`, e.g. the agent's own stream.
+    pub fn set_rng(&mut self, rng: &Rng) {
+        self.rng = rng.clone();
+    }
+}
+
+impl Agent3 {
+    /// `force` truncated to `max_force` and kept in the plane, if any.
+    fn steer(&self, force: Vec3) -> Vec3 {
+        let force = match self.plane {
+            Some(plane) => plane.flatten(force),
+            None => force,
+        };
+        force.truncate(self.max_force)
+    }
+
+    fn right(&self) -> Vec3 {
+        self.up.cross(self.forward)
+    }
+
+    fn time_to_reach(&self, target: Vec3, target_velocity: Vec3) -> f32 {
+        let closing = self.max_speed + target_velocity.length();
+        if closing > 0.0 {
+            self.position.distance(target) / closing
+        } else {
+            0.0
+        }
+    }
+}
+
+/// A batch of boids flocking in 3D, as `Flock` does in 2D, with positions and
+/// velocities stored as flat `[x0, y0, z0, x1, ...]` buffers. `set_plane`
+/// holds every boid to a plane instead.
+#[wasm_bindgen]
+pub struct Flock3 {
+    positions: Vec<f32>,
+    velocities: Vec<f32>,
+    /// Flocking forces of the update being run, already truncated.
+    accelerations: Vec<[f32; 3]>,
+    /// Boids hashed by `x` and `z`, filtered by true distance.
+    neighbors: SpatialHash,
+    scratch: Vec<u32>,
+    plane: Option<Plane>,
+    pub max_speed: f32,
+    pub max_force: f32,
+    pub neighbor_radius: f32,
+    pub separation_radius: f32,
+    pub separation_weight: f32,
+    pub alignment_weight: f32,
+    pub cohesion_weight: f32,
+}
+
+#[wasm_bindgen]
+impl Flock3 {
+    #[wasm_bindgen(constructor)]
+    pub fn new(max_speed: f32, max_force: f32) -> Flock3 {
+        Flock3 {
+            positions: Vec::new(),
+            velocities: Vec::new(),
+            accelerations: Vec::new(),
+            neighbors: SpatialHash::new(5.0),
+            scratch: Vec::new(),
+            plane: None,
+            max_speed,
+            max_force,
+            neighbor_radius: 5.0,
+            separation_radius: 1.5,
+            separation_weight: 1.5,
+            alignment_weight: 1.0,
+            cohesion_weight: 1.0,
+        }
+    }
+
+    /// Adds a boid and returns its index into the position and velocity buffers.
+    pub fn add_boid(&mut self, position: Vec3, velocity: Vec3) -> u32 {
+        let (position, velocity) = match self.plane {
+            Some(plane) => (plane.snap(position), plane.flatten(velocity)),
+            None => (position, velocity),
+        };
+        self.positions
+            .extend_from_slice(&[position.x, position.y, position.z]);
+        self.velocities
+            .extend_from_slice(&[velocity.x, velocity.y, velocity.z]);
+        self.accelerations.push([0.0; 3]);
+        (self.accelerations.len() - 1) as u32
+    }
+
+    /// Holds every boid to the plane through `point` with `normal`, moving
+    /// them onto it.
+    pub fn set_plane(&mut self, normal: Vec3, point: Vec3) -> Result<(), Error> {
+        let plane = Plane::new(normal, point)?;
+        for index in 0..self.accelerations.len() {
+            let position = plane.snap(self.position(index));
+            let velocity = plane.flatten(self.velocity(index));
+            self.positions[index * 3..index * 3 + 3]
+                .copy_from_slice(&[position.x, position.y, position.z]);
+            self.velocities[index * 3..index * 3 + 3]
+                .copy_from_slice(&[velocity.x, velocity.y, velocity.z]);
+        }
+        self.plane = Some(plane);
+        Ok(())
+    }
+
+    /// Lets the boids move freely in 3D again.
+    pub fn clear_plane(&mut self) {
+        self.plane = None;
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn len(&self) -> u32 {
+        self.accelerations.len() as u32
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.accelerations.is_empty()
+    }
+
+    /// Applies separation, alignment, and cohesion to every boid, then integrates.
+    pub fn update(&mut self, dt: f32) {
+        let count = self.accelerations.len();
+        let separation_radius_squared = self.separation_radius * self.separation_radius;
+        let neighbor_radius_squared = self.neighbor_radius * self.neighbor_radius;
+
+        self.neighbors.clear();
+        self.neighbors.set_cell_size(self.neighbor_radius);
+        for i in 0..count {
+            let position = self.position(i);
+            self.neighbors.insert(i as u32, position.x, position.z);
+        }
+
+        let mut accelerations = std::mem::take(&mut self.accelerations);
+        let mut scratch = std::mem::take(&mut self.scratch);
+        parallel::fill(
+            &mut accelerations,
+            &mut scratch,
+            parallel::AGENT_BATCH,
+            |nearby, i, acceleration| {
+                let position = self.position(i);
+                let velocity = self.velocity(i);
+                let mut separation = Vec3::ZERO;
+                let mut heading = Vec3::ZERO;
+                let mut center = Vec3::ZERO;
+                let mut neighbors = 0;
+
+                nearby.clear();
+                self.neighbors.query_radius_into(
+                    position.x,
+                    position.z,
+                    self.neighbor_radius,
+                    nearby,
+                );
+                for &j in nearby.iter() {
+                    let j = j as usize;
+                    if i == j {
+                        continue;
+                    }
+                    let offset = position - self.position(j);
+                    let distance_squared = offset.length_squared();
+                    if distance_squared > neighbor_radius_squared {
+                        continue;
+                    }
+                    if distance_squared < separation_radius_squared && distance_squared > 0.0 {
+                        separation += offset / distance_squared;
+                    }
+                    heading += self.velocity(j);
+                    center += self.position(j);
+                    neighbors += 1;
+                }
+
+                let mut force = Vec3::ZERO;
+                if neighbors > 0 {
+                    let n = neighbors as f32;
+                    force += self.steer(separation, velocity) * self.separation_weight;
+                    force += self.steer(heading / n, velocity) * self.alignment_weight;
+                    force += self.steer(center / n - position, velocity) * self.cohesion_weight;
+                }
+                if let Some(plane) = self.plane {
+                    force = plane.flatten(force);
+                }
+                let force = force.truncate(self.max_force);
+                *acceleration = [force.x, force.y, force.z];
+            },
+        );
+        self.accelerations = accelerations;
+        self.scratch = scratch;
+
+        for i in 0..count {
+            let [ax, ay, az] = self.accelerations[i];
+            let velocity = (self.velocity(i) + Vec3::new(ax, ay, az) * dt).truncate(self.max_speed);
+            let mut position = self.position(i) + velocity * dt;
+            if let Some(plane) = self.plane {
+                position = plane.snap(position);
+            }
+            self.positions[i * 3..i * 3 + 3].copy_from_slice(&[position.x, position.y, position.z]);
+            self.velocities[i * 3..i * 3 + 3]
+                .copy_from_slice(&[velocity.x, velocity.y, velocity.z]);
+        }
+    }
+
+    /// A view of the flat position buffer in WASM memory. The view is invalidated when
+    /// boids are added or the WASM memory grows, so re-fetch it after either.
+    #[cfg(target_arch = "wasm32")]
+    pub fn positions(&self) -> Float32Array {
+        unsafe { Float32Array::view(&self.positions) }
+    }
+
+    /// A view of the flat velocity buffer, with the same lifetime caveats as `positions`.
+    #[cfg(target_arch = "wasm32")]
+    pub fn velocities(&self) -> Float32Array {
+        unsafe { Float32Array::view(&self.velocities) }
+    }
+
+    /// Appends each boid to `out` as a point with a line for its velocity, in
+    /// 3D rather than at the geometry's elevation.
+    pub fn debug_geometry(&self, out: &mut DebugGeometry) {
+        for i in 0..self.accelerations.len() {
+            let position = self.position(i);
+            let tip = position + self.velocity(i);
+            let from = [position.x, position.y, position.z];
+            out.line_3d(from, [tip.x, tip.y, tip.z], debug::VELOCITY);
+            out.point_3d(from, debug::AGENT);
+        }
+    }
+}
+
+impl Flock3 {
+    /// The flat `[x, y, z, ...]` positions, borrowed rather than viewed, so
+    /// they stay valid for as long as the flock is not changed.
+    pub fn position_buffer(&self) -> &[f32] {
+        &self.positions
+    }
+
+    /// The flat `[x, y, z, ...]` velocities.
+    pub fn velocity_buffer(&self) -> &[f32] {
+        &self.velocities
+    }
+
+    fn position(&self, index: usize) -> Vec3 {
+        let p = &self.positions[index * 3..index * 3 + 3];
+        Vec3::new(p[0], p[1], p[2])
+    }
+
+    fn velocity(&self, index: usize) -> Vec3 {
+        let v = &self.velocities[index * 3..index * 3 + 3];
+        Vec3::new(v[0], v[1], v[2])
+    }
+
+    /// Reynolds steering toward a desired direction at full speed.
+    fn steer(&self, direction: Vec3, velocity: Vec3) -> Vec3 {
+        if direction.length_squared() <= f32::EPSILON {
+            return Vec3::ZERO;
+        }
+        (direction.normalize() * self.max_speed - velocity).truncate(self.max_force)
+    }
+}
+
+/// `vector` with its part along the unit `normal` removed.
+fn reject(vector: Vec3, normal: Vec3) -> Vec3 {
+    vector - normal * vector.dot(normal)
+}
+
+/// Some unit vector perpendicular to the unit `normal`.
+fn any_perpendicular(normal: Vec3) -> Vec3 {
+    let axis = if normal.x.abs() < 0.9 {
+        Vec3::new(1.0, 0.0, 0.0)
+    } else {
+        Vec3::new(0.0, 0.0, 1.0)
+    };
+    reject(axis, normal).normalize()
+}
+
+/// The unrolled up for a body facing `forward`: `reference` made
+/// perpendicular to it.
+fn level_up(forward: Vec3, reference: Vec3) -> Vec3 {
+    let up = reject(reference, forward).normalize();
+    if up == Vec3::ZERO {
+        any_perpendicular(forward)
+    } else {
+        up
+    }
+}
+
+/// Rotates the unit `from` toward the unit `to` by at most `max_angle`
+/// radians, about `reference` when they point opposite ways.
+fn turn_toward(from: Vec3, to: Vec3, max_angle: f32, reference: Vec3) -> Vec3 {
+    let cos = from.dot(to).clamp(-1.0, 1.0);
+    let angle = cos.acos();
+    if angle <= max_angle {
+        return to;
+    }
+    let mut side = (to - from * cos).normalize();
+    if side == Vec3::ZERO {
+        side = reference.cross(from).normalize();
+        if side == Vec3::ZERO {
+            side = any_perpendicular(from);
+        }
+    }
+    (from * max_angle.cos() + side * max_angle.sin()).normalize()
+}
+
+/// `direction` with its climb or dive limited to `max_pitch` from the
+/// horizontal, keeping its compass bearing, or that of `fallback` when it
+/// points straight up or down.
+fn clamp_pitch(direction: Vec3, fallback: Vec3, max_pitch: f32) -> Vec3 {
+    if max_pitch >= std::f32::consts::FRAC_PI_2 {
+        return direction;
+    }
+    let pitch = direction.y.clamp(-1.0, 1.0).asin();
+    if pitch.abs() <= max_pitch {
+        return direction;
+    }
+    let mut bearing = Vec3::new(direction.x, 0.0, direction.z).normalize();
+    if bearing == Vec3::ZERO {
+        bearing = Vec3::new(fallback.x, 0.0, fallback.z).normalize();
+    }
+    if bearing == Vec3::ZERO {
+        bearing = Vec3::new(0.0, 0.0, 1.0);
+    }
+    let limit = max_pitch.max(0.0).copysign(pitch);
+    bearing * limit.cos() + UP * limit.sin()
+}
+
+/// The rotation taking the axes onto the orthonormal `x`, `y` and `z`, as an
+/// `[x, y, z, w]` quaternion.
+fn quaternion(x: Vec3, y: Vec3, z: Vec3) -> [f32; 4] {
+    let trace = x.x + y.y + z.z;
+    let [qx, qy, qz, qw] = if trace > 0.0 {
+        let s = 0.5 / (trace + 1.0).sqrt();
+        [(y.z - z.y) * s, (z.x - x.z) * s, (x.y - y.x) * s, 0.25 / s]
+    } else if x.x > y.y && x.x > z.z {
+        let s = 2.0 * (1.0 + x.x - y.y - z.z).sqrt();
+        [0.25 * s, (y.x + x.y) / s, (z.x + x.z) / s, (y.z - z.y) / s]
+    } else if y.y > z.z {
+        let s = 2.0 * (1.0 + y.y - x.x - z.z).sqrt();
+        [(y.x + x.y) / s, 0.25 * s, (z.y + y.z) / s, (z.x - x.z) / s]
+    } else {
+        let s = 2.0 * (1.0 + z.z - x.x - y.y).sqrt();
+        [(z.x + x.z) / s, (z.y + y.z) / s, 0.25 * s, (x.y - y.x) / s]
+    };
+    let length = (qx * qx + qy * qy + qz * qz + qw * qw).sqrt();
+    [qx / length, qy / length, qz / length, qw / length]
+}
+
+/// The 3D form of `steering::intercept`.
+fn intercept(position: Vec3, speed: f32, target: Vec3, target_velocity: Vec3) -> Option<Vec3> {
+    let time = ballistics::intercept_time(target - position, target_velocity, speed)?;
+    Some(target + target_velocity * time)
+}
@@ -0,0 +1,409 @@
+use wasm_bindgen::prelude::*;
+
+use crate::avoidance::Obstacles;
+use crate::math::Vec2;
+use crate::obstacles::ObstacleSet;
+use crate::spatial_hash::SpatialHash;
+use crate::steering::Agent;
+
+#[derive(Clone)]
+enum Behavior {
+    Seek,
+    Flee {
+        panic_distance: f32,
+    },
+    Arrive {
+        slow_radius: f32,
+    },
+    Pursue,
+    Evade {
+        panic_distance: f32,
+    },
+    Wander {
+        jitter: f32,
+        radius: f32,
+        distance: f32,
+    },
+    AvoidObstacles {
+        obstacles: Obstacles,
+        feeler_length: f32,
+    },
+    AvoidObstacleSet {
+        obstacles: ObstacleSet,
+        feeler_length: f32,
+    },
+    Separation {
+        radius: f32,
+    },
+}
+
+#[derive(Clone)]
+struct Entry {
+    id: u32,
+    behavior: Behavior,
+    weight: f32,
+    priority: i32,
+    enabled: bool,
+}
+
+/// A moving point that behaviors steer toward or away from.
+#[derive(Clone, Copy)]
+struct Mark {
+    position: Vec2,
+    velocity: Vec2,
+}
+
+/// Combines weighted steering behaviors for a batch of agents in one `update` call.
+///
+/// Behaviors are grouped by priority. Each group's weighted sum is added to the
+/// agent's force, highest priority first, until `max_force` is used up, so that
+/// e.g. obstacle avoidance can claim the whole budget before seeking gets any.
+/// Seek, arrive and pursue steer toward each agent's target; flee and evade away
+/// from its threat.
+#[wasm_bindgen]
+pub struct SteeringPipeline {
+    entries: Vec<Entry>,
+    next_id: u32,
+    agents: Vec<Agent>,
+    targets: Vec<Option<Mark>>,
+    threats: Vec<Option<Mark>>,
+    neighbors: SpatialHash,
+    scratch: Vec<u32>,
+}
+
+#[wasm_bindgen]
+impl SteeringPipeline {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> SteeringPipeline {
+        SteeringPipeline {
+            entries: Vec::new(),
+            next_id: 0,
+            agents: Vec::new(),
+            targets: Vec::new(),
+            threats: Vec::new(),
+            neighbors: SpatialHash::new(5.0),
+            scratch: Vec::new(),
+        }
+    }
+
+    pub fn add_seek(&mut self, weight: f32, priority: i32) -> u32 {
+        self.add(Behavior::Seek, weight, priority)
+    }
+
+    /// Flees the threat while it is within `panic_distance`, or always for 0.
+    pub fn add_flee(&mut self, panic_distance: f32, weight: f32, priority: i32) -> u32 {
+        self.add(Behavior::Flee { panic_distance }, weight, priority)
+    }
+
+    pub fn add_arrive(&mut self, slow_radius: f32, weight: f32, priority: i32) -> u32 {
+        self.add(Behavior::Arrive { slow_radius }, weight, priority)
+    }
+
+    pub fn add_pursue(&mut self, weight: f32, priority: i32) -> u32 {
+        self.add(Behavior::Pursue, weight, priority)
+    }
+
+    /// Evades the threat while it is within `panic_distance`, or always for 0.
+    pub fn add_evade(&mut self, panic_distance: f32, weight: f32, priority: i32) -> u32 {
+        self.add(Behavior::Evade { panic_distance }, weight, priority)
+    }
+
+    pub fn add_wander(
+        &mut self,
+        jitter: f32,
+        radius: f32,
+        distance: f32,
+        weight: f32,
+        priority: i32,
+    ) -> u32 {
+        self.add(
+            Behavior::Wander {
+                jitter,
+                radius,
+                distance,
+            },
+            weight,
+            priority,
+        )
+    }
+
+    /// Avoids a snapshot of `obstacles`; add the behavior again after editing them.
+    pub fn add_obstacle_avoidance(
+        &mut self,
+        obstacles: &Obstacles,
+        feeler_length: f32,
+        weight: f32,
+        priority: i32,
+    ) -> u32 {
+        let behavior = Behavior::AvoidObstacles {
+            obstacles: obstacles.clone(),
+            feeler_length,
+        };
+        self.add(behavior, weight, priority)
+    }
+
+    /// Like `add_obstacle_avoidance`, for a snapshot of an `ObstacleSet`.
+    pub fn add_obstacle_set_avoidance(
+        &mut self,
+        obstacles: &ObstacleSet,
+        feeler_length: f32,
+        weight: f32,
+        priority: i32,
+    ) -> u32 {
+        let behavior = Behavior::AvoidObstacleSet {
+            obstacles: obstacles.clone(),
+            feeler_length,
+        };
+        self.add(behavior, weight, priority)
+    }
+
+    /// Pushes away from other agents in this pipeline closer than `radius`.
+    pub fn add_separation(&mut self, radius: f32, weight: f32, priority: i32) -> u32 {
+        self.add(Behavior::Separation { radius }, weight, priority)
+    }
+
+    pub fn remove_behavior(&mut self, id: u32) -> bool {
+        let before = self.entries.len();
+        self.entries.retain(|entry| entry.id != id);
+        self.entries.len() != before
+    }
+
+    pub fn set_weight(&mut self, id: u32, weight: f32) -> bool {
+        self.entry(id).map(|entry| entry.weight = weight).is_some()
+    }
+
+    pub fn set_enabled(&mut self, id: u32, enabled: bool) -> bool {
+        self.entry(id)
+            .map(|entry| entry.enabled = enabled)
+            .is_some()
+    }
+
+    /// Adds a copy of `agent` and returns its index.
+    pub fn add_agent(&mut self, agent: &Agent) -> u32 {
+        self.agents.push(agent.clone());
+        self.targets.push(None);
+        self.threats.push(None);
+        self.agents.len() as u32 - 1
+    }
+
+    /// A copy of an agent's current state.
+    pub fn agent(&self, index: u32) -> Option<Agent> {
+        self.agents.get(index as usize).cloned()
+    }
+
+    /// Replaces an agent's state, e.g. after teleporting it.
+    pub fn set_agent(&mut self, index: u32, agent: &Agent) {
+        if let Some(slot) = self.agents.get_mut(index as usize) {
+            *slot = agent.clone();
+        }
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn len(&self) -> u32 {
+        self.agents.len() as u32
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.agents.is_empty()
+    }
+
+    /// Sets the point an agent seeks, arrives at or pursues.
+    pub fn set_target(&mut self, index: u32, x: f32, y: f32, vx: f32, vy: f32) {
+        if let Some(slot) = self.targets.get_mut(index as usize) {
+            *slot = Some(Mark {
+                position: Vec2::new(x, y),
+                velocity: Vec2::new(vx, vy),
+            });
+        }
+    }
+
+    pub fn clear_target(&mut self, index: u32) {
+        if let Some(slot) = self.targets.get_mut(index as usize) {
+            *slot = None;
+        }
+    }
+
+    /// Sets the point an agent flees or evades.
+    pub fn set_threat(&mut self, index: u32, x: f32, y: f32, vx: f32, vy: f32) {
+        if let Some(slot) = self.threats.get_mut(index as usize) {
+            *slot = Some(Mark {
+                position: Vec2::new(x, y),
+                velocity: Vec2::new(vx, vy),
+            });
+        }
+    }
+
+    pub fn clear_threat(&mut self, index: u32) {
+        if let Some(slot) = self.threats.get_mut(index as usize) {
+            *slot = None;
+        }
+    }
+
+    /// Computes every agent's combined force, then integrates all of them.
+    pub fn update(&mut self, dt: f32) {
+        let separation = self
+            .entries
+            .iter()
+            .filter(|entry| entry.enabled)
+            .filter_map(|entry| match entry.behavior {
+                Behavior::Separation { radius } => Some(radius),
+                _ => None,
+            })
+            .reduce(f32::max);
+        if let Some(radius) = separation {
+            self.neighbors.clear();
+            self.neighbors.set_cell_size(radius);
+            for (i, agent) in self.agents.iter().enumerate() {
+                self.neighbors
+                    .insert(i as u32, agent.position.x, agent.position.y);
+            }
+        }
+
+        let forces: Vec<Vec2> = (0..self.agents.len()).map(|i| self.calculate(i)).collect();
+        for (agent, force) in self.agents.iter_mut().zip(forces) {
+            agent.update(force, dt);
+        }
+    }
+
+    /// Flat `[x0, y0, x1, y1, ...]` agent positions.
+    pub fn positions(&self) -> Vec<f32> {
+        self.agents
+            .iter()
+            .flat_map(|agent| [agent.position.x, agent.position.y])
+            .collect()
+    }
+
+    /// Flat `[vx0, vy0, vx1, vy1, ...]` agent velocities.
+    pub fn velocities(&self) -> Vec<f32> {
+        self.agents
+            .iter()
+            .flat_map(|agent| [agent.velocity.x, agent.velocity.y])
+            .collect()
+    }
+}
+
+impl Default for SteeringPipeline {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SteeringPipeline {
+    fn add(&mut self, behavior: Behavior, weight: f32, priority: i32) -> u32 {
+        let id = self.next_id;
+        self.next_id = self.next_id.wrapping_add(1);
+        self.entries.push(Entry {
+            id,
+            behavior,
+            weight,
+            priority,
+            enabled: true,
+        });
+        // Stable, so behaviors within a group keep their insertion order.
+        self.entries
+            .sort_by_key(|entry| std::cmp::Reverse(entry.priority));
+        id
+    }
+
+    fn entry(&mut self, id: u32) -> Option<&mut Entry> {
+        self.entries.iter_mut().find(|entry| entry.id == id)
+    }
+
+    /// Weighted truncated running sum with prioritization for agent `index`.
+    fn calculate(&mut self, index: usize) -> Vec2 {
+        let max_force = self.agents[index].max_force;
+        let mut total = Vec2::ZERO;
+        let mut remaining = max_force;
+        let mut group = 0;
+        while group < self.entries.len() && remaining > 0.0 {
+            let priority = self.entries[group].priority;
+            let end = self.entries[group..]
+                .iter()
+                .position(|entry| entry.priority != priority)
+                .map_or(self.entries.len(), |offset| group + offset);
+
+            let mut sum = Vec2::ZERO;
+            for entry in group..end {
+                if self.entries[entry].enabled {
+                    sum += self.force(entry, index) * self.entries[entry].weight;
+                }
+            }
+            let sum = sum.truncate(remaining);
+            total += sum;
+            remaining -= sum.length();
+            group = end;
+        }
+        total
+    }
+
+    fn force(&mut self, entry: usize, index: usize) -> Vec2 {
+        match self.entries[entry].behavior {
+            Behavior::Separation { radius } => return self.separation(index, radius),
+            Behavior::Wander {
+                jitter,
+                radius,
+                distance,
+            } => return self.agents[index].wander(jitter, radius, distance),
+            _ => {}
+        }
+
+        let (target, threat) = (self.targets[index], self.threats[index]);
+        let agent = &self.agents[index];
+        let within = |mark: Mark, panic_distance: f32| {
+            panic_distance <= 0.0 || agent.position.distance(mark.position) < panic_distance
+        };
+        match &self.entries[entry].behavior {
+            Behavior::Seek => target.map_or(Vec2::ZERO, |mark| agent.seek(mark.position)),
+            Behavior::Arrive { slow_radius } => {
+                target.map_or(Vec2::ZERO, |mark| agent.arrive(mark.position, *slow_radius))
+            }
+            Behavior::Pursue => target.map_or(Vec2::ZERO, |mark| {
+                agent.pursue(mark.position, mark.velocity)
+            }),
+            Behavior::Flee { panic_distance } => match threat {
+                Some(mark) if within(mark, *panic_distance) => agent.flee(mark.position),
+                _ => Vec2::ZERO,
+            },
+            Behavior::Evade { panic_distance } => match threat {
+                Some(mark) if within(mark, *panic_distance) => {
+                    agent.evade(mark.position, mark.velocity)
+                }
+                _ => Vec2::ZERO,
+            },
+            Behavior::AvoidObstacles {
+                obstacles,
+                feeler_length,
+            } => agent.avoid_obstacles(obstacles, *feeler_length),
+            Behavior::AvoidObstacleSet {
+                obstacles,
+                feeler_length,
+            } => agent.avoid_obstacle_set(obstacles, *feeler_length),
+            Behavior::Separation { .. } | Behavior::Wander { .. } => Vec2::ZERO,
+        }
+    }
+
+    /// Steers away from nearby agents, weighting each by its inverse distance.
+    fn separation(&mut self, index: usize, radius: f32) -> Vec2 {
+        let agent = &self.agents[index];
+        self.scratch.clear();
+        self.neighbors.query_radius_into(
+            agent.position.x,
+            agent.position.y,
+            radius,
+            &mut self.scratch,
+        );
+        let mut push = Vec2::ZERO;
+        for &other in &self.scratch {
+            let offset = agent.position - self.agents[other as usize].position;
+            let distance_squared = offset.length_squared();
+            if other as usize != index && distance_squared > 0.0 {
+                push += offset / distance_squared;
+            }
+        }
+        if push == Vec2::ZERO {
+            return Vec2::ZERO;
+        }
+        let desired = push.normalize() * agent.max_speed;
+        (desired - agent.velocity).truncate(agent.max_force)
+    }
+}
@@ -0,0 +1,129 @@
+use crate::bytes::{Reader, Writer};
+use crate::error::Error;
+use crate::math::Vec2;
+
+/// How long a sidestep lasts, in seconds.
+const SIDESTEP_TIME: f32 = 0.5;
+
+/// What a crowd does about an agent that is stuck, escalating each time it
+/// stays stuck.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Recovery {
+    /// Walk briefly across the path, e.g. to get round an agent coming the
+    /// other way.
+    Sidestep,
+    /// Plan the path again from where the agent stands.
+    Repath,
+    /// Put the agent on its path a little ahead.
+    Teleport,
+}
+
+/// Watches one agent's progress along its path for stalls.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct StuckMonitor {
+    /// Path progress when the current window began.
+    progress: f32,
+    elapsed: f32,
+    attempts: u32,
+    sidestep_left: f32,
+    side: Vec2,
+}
+
+impl StuckMonitor {
+    /// Starts a new window for a newly planned path, keeping count of the
+    /// recoveries tried.
+    pub(crate) fn restart(&mut self) {
+        self.progress = 0.0;
+        self.elapsed = 0.0;
+        self.sidestep_left = 0.0;
+    }
+
+    /// Forgets everything, for an agent that is not moving.
+    pub(crate) fn reset(&mut self) {
+        *self = StuckMonitor::default();
+    }
+
+    pub(crate) fn attempts(&self) -> u32 {
+        self.attempts
+    }
+
+    pub(crate) fn write(&self, out: &mut Writer) {
+        out.f32s(&[self.progress, self.elapsed]);
+        out.u32(self.attempts);
+        out.f32s(&[self.sidestep_left, self.side.x, self.side.y]);
+    }
+
+    pub(crate) fn read(input: &mut Reader) -> Result<StuckMonitor, Error> {
+        Ok(StuckMonitor {
+            progress: input.finite_f32()?,
+            elapsed: input.finite_f32()?,
+            attempts: input.u32()?,
+            sidestep_left: input.finite_f32()?,
+            side: Vec2::new(input.finite_f32()?, input.finite_f32()?),
+        })
+    }
+
+    /// Advances by `dt` with the agent `progress` along its path. When it has
+    /// not gained `distance` in `time` seconds, returns the recovery to try,
+    /// teleports only when `teleport` allows them.
+    pub(crate) fn watch(
+        &mut self,
+        progress: f32,
+        dt: f32,
+        time: f32,
+        distance: f32,
+        teleport: bool,
+    ) -> Option<Recovery> {
+        if time <= 0.0 {
+            return None;
+        }
+        if progress >= self.progress + distance {
+            self.progress = progress;
+            self.elapsed = 0.0;
+            self.attempts = 0;
+            return None;
+        }
+        self.elapsed += dt;
+        if self.elapsed < time {
+            return None;
+        }
+        self.elapsed = 0.0;
+        self.progress = progress;
+        self.attempts += 1;
+        Some(match self.attempts {
+            1 => Recovery::Sidestep,
+            2 => Recovery::Repath,
+            _ if teleport => Recovery::Teleport,
+            attempts if attempts % 2 == 1 => Recovery::Sidestep,
+            _ => Recovery::Repath,
+        })
+    }
+
+    /// Starts a sidestep across `heading`, to the other side from the last.
+    /// `left` picks the side of the first, so that agents meeting head on
+    /// step apart.
+    pub(crate) fn start_sidestep(&mut self, heading: Vec2, left: bool) {
+        let across = Vec2::new(-heading.y, heading.x).normalize();
+        self.side = if self.side == Vec2::ZERO {
+            if left {
+                across
+            } else {
+                -across
+            }
+        } else if self.side.dot(across) > 0.0 {
+            -across
+        } else {
+            across
+        };
+        self.sidestep_left = SIDESTEP_TIME;
+    }
+
+    /// The direction to walk in while a sidestep lasts.
+    pub(crate) fn sidestep(&mut self, dt: f32) -> Option<Vec2> {
+        if self.sidestep_left <= 0.0 {
+            return None;
+        }
+        self.sidestep_left -= dt;
+        Some(self.side)
+    }
+}
@@ -0,0 +1,272 @@
+use wasm_bindgen::prelude::*;
+
+use crate::error::Error;
+use crate::grid::Grid;
+use crate::math::Vec2;
+
+/// Score of a position that a threat cannot see but that has no wall next to it
+/// to duck behind, relative to full cover.
+const CONCEALED: f32 = 0.5;
+
+#[derive(Clone)]
+enum Criterion {
+    /// Discards positions outside `min..=max` distance of `point`.
+    DistanceBand { point: Vec2, min: f32, max: f32 },
+    /// Discards positions the point can not see.
+    RequireLineOfSight { point: Vec2 },
+    /// 1 behind a wall the threat cannot see through, `CONCEALED` when only out of
+    /// its sight.
+    CoverFrom { threat: Vec2, weight: f32 },
+    /// 1 where the point is visible.
+    LineOfSightTo { point: Vec2, weight: f32 },
+    /// 1 at `ideal` distance from the point, falling to 0 at twice or no distance.
+    PreferDistance {
+        point: Vec2,
+        ideal: f32,
+        weight: f32,
+    },
+    /// 1 next to the nearest ally, falling to 0 at `radius` from it.
+    NearAllies {
+        allies: Vec<Vec2>,
+        radius: f32,
+        weight: f32,
+    },
+}
+
+/// Scores candidate positions on a grid against weighted criteria and returns the
+/// best ones, e.g. the closest cover from a threat that still keeps the target in
+/// sight. Requirements discard candidates outright; each scoring criterion adds
+/// its weight times a score in `[0, 1]`, and the total is divided by the sum of
+/// weights.
+///
+/// Candidates are the walkable cells around a point or an explicit list, and
+/// positions are grid cell coordinates.
+#[wasm_bindgen]
+#[derive(Clone, Default)]
+pub struct TacticalQuery {
+    criteria: Vec<Criterion>,
+    candidates: Vec<Vec2>,
+}
+
+#[wasm_bindgen]
+impl TacticalQuery {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> TacticalQuery {
+        TacticalQuery::default()
+    }
+
+    /// Uses every cell within `radius` of `center`, `spacing` cells apart, as a
+    /// candidate; unwalkable cells are skipped when the query runs.
+    pub fn candidates_around(&mut self, center: Vec2, radius: f32, spacing: u32) {
+        let spacing = spacing.max(1) as i32;
+        let reach = radius.max(0.0).floor() as i32;
+        let (cx, cy) = (center.x.round() as i32, center.y.round() as i32);
+        self.candidates.clear();
+        for dy in (-reach..=reach).step_by(spacing as usize) {
+            for dx in (-reach..=reach).step_by(spacing as usize) {
+                let cell = Vec2::new((cx + dx) as f32, (cy + dy) as f32);
+                if cell.distance(center) <= radius {
+                    self.candidates.push(cell);
+                }
+            }
+        }
+    }
+
+    /// Uses flat `[x0, y0, x1, y1, ...]` positions as candidates, such as
+    /// hand-placed cover points.
+    pub fn set_candidates(&mut self, points: &[f32]) -> Result<(), Error> {
+        if !points.len().is_multiple_of(2) {
+            return Err(Error::InvalidInput("candidates must be x, y pairs".into()));
+        }
+        self.candidates = points
+            .chunks_exact(2)
+            .map(|p| Vec2::new(p[0], p[1]))
+            .collect();
+        Ok(())
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn candidate_count(&self) -> u32 {
+        self.candidates.len() as u32
+    }
+
+    /// Keeps only candidates between `min` and `max` away from `point`.
+    pub fn require_distance(&mut self, point: Vec2, min: f32, max: f32) {
+        self.criteria
+            .push(Criterion::DistanceBand { point, min, max });
+    }
+
+    /// Keeps only candidates with a clear line of sight to `point`.
+    pub fn require_line_of_sight(&mut self, point: Vec2) {
+        self.criteria.push(Criterion::RequireLineOfSight { point });
+    }
+
+    /// Prefers candidates hidden from `threat`, most of all those hugging a wall.
+    pub fn prefer_cover_from(&mut self, threat: Vec2, weight: f32) {
+        self.criteria.push(Criterion::CoverFrom { threat, weight });
+    }
+
+    /// Prefers candidates with a line of sight to `point`.
+    pub fn prefer_line_of_sight(&mut self, point: Vec2, weight: f32) {
+        self.criteria
+            .push(Criterion::LineOfSightTo { point, weight });
+    }
+
+    /// Prefers candidates about `ideal` away from `point`, e.g. a weapon's range
+    /// from its target, or 0 to prefer candidates close to the point.
+    pub fn prefer_distance(&mut self, point: Vec2, ideal: f32, weight: f32) {
+        self.criteria.push(Criterion::PreferDistance {
+            point,
+            ideal: ideal.max(0.0),
+            weight,
+        });
+    }
+
+    /// Prefers candidates close to an ally among flat `[x0, y0, ...]` positions,
+    /// up to `radius` away.
+    pub fn prefer_near_allies(&mut self, allies: &[f32], radius: f32, weight: f32) {
+        let allies = allies
+            .chunks_exact(2)
+            .map(|p| Vec2::new(p[0], p[1]))
+            .collect();
+        self.criteria.push(Criterion::NearAllies {
+            allies,
+            radius,
+            weight,
+        });
+    }
+
+    /// Removes every criterion but keeps the candidates.
+    pub fn clear_criteria(&mut self) {
+        self.criteria.clear();
+    }
+
+    /// The best `count` candidates as flat `[x0, y0, score0, x1, y1, score1, ...]`,
+    /// highest score first.
+    pub fn run(&self, grid: &Grid, count: u32) -> Vec<f32> {
+        let total_weight: f32 = self.criteria.iter().map(weight).sum();
+        let mut scored: Vec<(f32, Vec2)> = self
+            .candidates
+            .iter()
+            .filter_map(|&candidate| {
+                let (x, y) = cell(candidate);
+                if !grid.is_walkable(x, y) {
+                    return None;
+                }
+                // Requirements first, so rejected candidates skip the scoring.
+                let (requirements, preferences) = (
+                    self.criteria.iter().filter(|c| is_requirement(c)),
+                    self.criteria.iter().filter(|c| !is_requirement(c)),
+                );
+                let mut sum = 0.0;
+                for criterion in requirements.chain(preferences) {
+                    sum += weight(criterion) * score(criterion, grid, candidate)?;
+                }
+                let score = if total_weight > 0.0 {
+                    sum / total_weight
+                } else {
+                    0.0
+                };
+                Some((score, candidate))
+            })
+            .collect();
+        scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+        scored
+            .into_iter()
+            .take(count as usize)
+            .flat_map(|(score, position)| [position.x, position.y, score])
+            .collect()
+    }
+}
+
+fn cell(position: Vec2) -> (i32, i32) {
+    (position.x.round() as i32, position.y.round() as i32)
+}
+
+fn sees(grid: &Grid, from: Vec2, to: Vec2) -> bool {
+    let ((x0, y0), (x1, y1)) = (cell(from), cell(to));
+    grid.line_of_sight(x0, y0, x1, y1)
+}
+
+fn is_requirement(criterion: &Criterion) -> bool {
+    matches!(
+        criterion,
+        Criterion::DistanceBand { .. } | Criterion::RequireLineOfSight { .. }
+    )
+}
+
+fn weight(criterion: &Criterion) -> f32 {
+    match criterion {
+        Criterion::DistanceBand { .. } | Criterion::RequireLineOfSight { .. } => 0.0,
+        Criterion::CoverFrom { weight, .. }
+        | Criterion::LineOfSightTo { weight, .. }
+        | Criterion::PreferDistance { weight, .. }
+        | Criterion::NearAllies { weight, .. } => *weight,
+    }
+}
+
+/// A criterion's score for `candidate`, or `None` when a requirement rejects it.
+fn score(criterion: &Criterion, grid: &Grid, candidate: Vec2) -> Option<f32> {
+    Some(match criterion {
+        Criterion::DistanceBand { point, min, max } => {
+            let distance = candidate.distance(*point);
+            if distance < *min || distance > *max {
+                return None;
+            }
+            0.0
+        }
+        Criterion::RequireLineOfSight { point } => {
+            if !sees(grid, candidate, *point) {
+                return None;
+            }
+            0.0
+        }
+        Criterion::CoverFrom { threat, .. } => {
+            if sees(grid, *threat, candidate) {
+                0.0
+            } else if wall_toward(grid, candidate, *threat) {
+                1.0
+            } else {
+                CONCEALED
+            }
+        }
+        Criterion::LineOfSightTo { point, .. } => {
+            if sees(grid, candidate, *point) {
+                1.0
+            } else {
+                0.0
+            }
+        }
+        Criterion::PreferDistance { point, ideal, .. } => {
+            let distance = candidate.distance(*point);
+            if *ideal > 0.0 {
+                (1.0 - (distance - ideal).abs() / ideal).max(0.0)
+            } else {
+                1.0 / (1.0 + distance)
+            }
+        }
+        Criterion::NearAllies { allies, radius, .. } => {
+            let nearest = allies
+                .iter()
+                .map(|ally| ally.distance(candidate))
+                .fold(f32::INFINITY, f32::min);
+            if *radius > 0.0 {
+                (1.0 - nearest / radius).max(0.0)
+            } else {
+                0.0
+            }
+        }
+    })
+}
+
+/// Whether an unwalkable cell next to `position` lies on the side facing `threat`.
+fn wall_toward(grid: &Grid, position: Vec2, threat: Vec2) -> bool {
+    let (x, y) = cell(position);
+    let toward = (threat - position).normalize();
+    (-1..=1).any(|dy| {
+        (-1..=1).any(|dx| {
+            let side = Vec2::new(dx as f32, dy as f32);
+            (dx, dy) != (0, 0) && side.dot(toward) > 0.0 && !grid.is_walkable(x + dx, y + dy)
+        })
+    })
+}
@@ -0,0 +1,249 @@
+use std::collections::{HashMap, HashSet};
+
+use wasm_bindgen::prelude::*;
+
+use crate::math::Vec2;
+use crate::perception::Perception;
+
+/// Threat below which an entry is dropped from a threat table.
+const THREAT_FLOOR: f32 = 0.01;
+
+struct Combatant {
+    position: Vec2,
+    /// Fraction of health left, from 1 down to 0 when dead.
+    health: f32,
+    role: u32,
+    team: u32,
+    /// Threat each source has generated against this combatant.
+    threat: HashMap<u32, f32>,
+    /// Combatants this one can currently see.
+    visible: HashSet<u32>,
+}
+
+/// Picks whom each combatant should attack. Every enemy in range gets a
+/// weighted score from how close it is, how hurt it is, how much threat it has
+/// generated against the attacker, whether the attacker can see it, and how
+/// well the attacker's role counters its role; `best_target` takes the
+/// highest. Combatants on the same team never target each other.
+///
+/// Each combatant keeps a threat table of the damage, healing aggro or taunts
+/// others have directed at it, fed by `add_threat`, which halves every
+/// `threat_half_life` seconds so old grudges give way to current attackers.
+#[wasm_bindgen]
+pub struct Targeting {
+    combatants: HashMap<u32, Combatant>,
+    counters: HashMap<(u32, u32), f32>,
+    /// Targets farther than this are never picked.
+    pub max_range: f32,
+    /// Weight of closeness, 1 next to the attacker falling to 0 at `max_range`.
+    pub distance_weight: f32,
+    /// Weight of missing health, so the wounded are finished off.
+    pub health_weight: f32,
+    /// Weight of the target's threat against the attacker, relative to the
+    /// most threatening entry in its table.
+    pub threat_weight: f32,
+    /// Weight of being in the attacker's sight.
+    pub sight_weight: f32,
+    /// Weight of the role counter bonus from `set_counter`.
+    pub role_weight: f32,
+    /// Seconds for threat to fall to half.
+    pub threat_half_life: f32,
+}
+
+impl Default for Targeting {
+    fn default() -> Targeting {
+        Targeting {
+            combatants: HashMap::new(),
+            counters: HashMap::new(),
+            max_range: 30.0,
+            distance_weight: 1.0,
+            health_weight: 0.5,
+            threat_weight: 1.0,
+            sight_weight: 1.0,
+            role_weight: 1.0,
+            threat_half_life: 10.0,
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl Targeting {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Targeting {
+        Targeting::default()
+    }
+
+    /// Adds or moves a combatant, keeping its threat table and sight.
+    /// `health` is the fraction left, from 0 to 1.
+    pub fn set_combatant(&mut self, id: u32, x: f32, y: f32, health: f32, role: u32, team: u32) {
+        let combatant = self.combatants.entry(id).or_insert_with(|| Combatant {
+            position: Vec2::ZERO,
+            health: 1.0,
+            role,
+            team,
+            threat: HashMap::new(),
+            visible: HashSet::new(),
+        });
+        combatant.position = Vec2::new(x, y);
+        combatant.health = health.clamp(0.0, 1.0);
+        combatant.role = role;
+        combatant.team = team;
+    }
+
+    /// Removes a combatant and every threat it generated.
+    pub fn remove_combatant(&mut self, id: u32) -> bool {
+        if self.combatants.remove(&id).is_none() {
+            return false;
+        }
+        for combatant in self.combatants.values_mut() {
+            combatant.threat.remove(&id);
+            combatant.visible.remove(&id);
+        }
+        true
+    }
+
+    /// Sets how much better attackers of role `attacker` fare against targets
+    /// of role `target`, e.g. 1 for archers against casters or -1 against
+    /// cavalry. Pairs left unset count 0.
+    pub fn set_counter(&mut self, attacker: u32, target: u32, bonus: f32) {
+        self.counters.insert((attacker, target), bonus);
+    }
+
+    /// Adds `amount` to the threat `source` has generated against `agent`,
+    /// e.g. the damage it just dealt.
+    pub fn add_threat(&mut self, agent: u32, source: u32, amount: f32) -> bool {
+        if agent == source || !self.combatants.contains_key(&source) {
+            return false;
+        }
+        let Some(combatant) = self.combatants.get_mut(&agent) else {
+            return false;
+        };
+        let threat = combatant.threat.entry(source).or_insert(0.0);
+        *threat = (*threat + amount).max(0.0);
+        true
+    }
+
+    /// The threat `source` has generated against `agent`.
+    pub fn threat(&self, agent: u32, source: u32) -> f32 {
+        self.combatants
+            .get(&agent)
+            .and_then(|combatant| combatant.threat.get(&source))
+            .copied()
+            .unwrap_or(0.0)
+    }
+
+    /// Forgets every threat against `agent`, e.g. when it leaves combat.
+    pub fn clear_threat(&mut self, agent: u32) {
+        if let Some(combatant) = self.combatants.get_mut(&agent) {
+            combatant.threat.clear();
+        }
+    }
+
+    /// Sets whom `agent` can currently see.
+    pub fn set_visible(&mut self, agent: u32, targets: &[u32]) {
+        if let Some(combatant) = self.combatants.get_mut(&agent) {
+            combatant.visible = targets.iter().copied().collect();
+        }
+    }
+
+    /// Sets whom `agent` can see from what observer `agent` saw in the
+    /// perception's last `update`, with perception target ids as combatant
+    /// ids.
+    pub fn observe(&mut self, perception: &Perception, agent: u32) {
+        let visible = perception.visible(agent);
+        self.set_visible(agent, &visible);
+    }
+
+    /// Lets threat decay for `dt` seconds.
+    pub fn update(&mut self, dt: f32) {
+        let factor = if self.threat_half_life > 0.0 {
+            0.5f32.powf(dt.max(0.0) / self.threat_half_life)
+        } else {
+            0.0
+        };
+        for combatant in self.combatants.values_mut() {
+            combatant.threat.retain(|_, threat| {
+                *threat *= factor;
+                *threat >= THREAT_FLOOR
+            });
+        }
+    }
+
+    /// How much `agent` wants to attack `target`, or undefined when it cannot:
+    /// either is unknown, they share a team, the target is dead or out of
+    /// range.
+    pub fn score(&self, agent: u32, target: u32) -> Option<f32> {
+        let attacker = self.combatants.get(&agent)?;
+        let defender = self.combatants.get(&target)?;
+        if agent == target || attacker.team == defender.team || defender.health <= 0.0 {
+            return None;
+        }
+        let distance = attacker.position.distance(defender.position);
+        if distance > self.max_range {
+            return None;
+        }
+        let closeness = if self.max_range > 0.0 {
+            1.0 - distance / self.max_range
+        } else {
+            1.0
+        };
+        let most_threat = attacker.threat.values().copied().fold(0.0, f32::max);
+        let threat = if most_threat > 0.0 {
+            attacker.threat.get(&target).copied().unwrap_or(0.0) / most_threat
+        } else {
+            0.0
+        };
+        let seen = if attacker.visible.contains(&target) {
+            1.0
+        } else {
+            0.0
+        };
+        let counter = self
+            .counters
+            .get(&(attacker.role, defender.role))
+            .copied()
+            .unwrap_or(0.0);
+        Some(
+            self.distance_weight * closeness
+                + self.health_weight * (1.0 - defender.health)
+                + self.threat_weight * threat
+                + self.sight_weight * seen
+                + self.role_weight * counter,
+        )
+    }
+
+    /// The target `agent` should attack, or undefined when there is none.
+    pub fn best_target(&self, agent: u32) -> Option<u32> {
+        self.ranked(agent).first().map(|&(target, _)| target)
+    }
+
+    /// Every target `agent` could attack, best first.
+    pub fn ranked_targets(&self, agent: u32) -> Vec<u32> {
+        self.ranked(agent)
+            .into_iter()
+            .map(|(target, _)| target)
+            .collect()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn len(&self) -> u32 {
+        self.combatants.len() as u32
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.combatants.is_empty()
+    }
+}
+
+impl Targeting {
+    /// Targets with their scores, best first and by id among equals.
+    fn ranked(&self, agent: u32) -> Vec<(u32, f32)> {
+        let mut ranked: Vec<(u32, f32)> = self
+            .combatants
+            .keys()
+            .filter_map(|&target| Some((target, self.score(agent, target)?)))
+            .collect();
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+        ranked
+    }
+}
@@ -0,0 +1,22 @@
+use js_sys::{Float32Array, Promise};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen_futures::{future_to_promise, JsFuture};
+
+/// Runs `compute` after the caller returns and resolves to its path as a
+/// `Float32Array`. Callers move owned copies of their data into `compute`, so the
+/// work can later be handed to a worker without changing the JS API.
+pub(crate) fn spawn_path(compute: impl FnOnce() -> Vec<f32> + 'static) -> Promise {
+    future_to_promise(async move {
+        JsFuture::from(Promise::resolve(&JsValue::UNDEFINED)).await?;
+        Ok(Float32Array::from(&compute()[..]).into())
+    })
+}
+
+/// Like `spawn_path`, for work that resolves to any value, such as an
+/// exported struct.
+pub(crate) fn spawn_value(compute: impl FnOnce() -> JsValue + 'static) -> Promise {
+    future_to_promise(async move {
+        JsFuture::from(Promise::resolve(&JsValue::UNDEFINED)).await?;
+        Ok(compute())
+    })
+}
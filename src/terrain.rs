@@ -0,0 +1,108 @@
+use wasm_bindgen::prelude::*;
+
+use crate::error::Error;
+use crate::grid::{Grid, Traversal};
+
+/// Number of distinct terrain types a cell can have.
+pub(crate) const TERRAIN_TYPES: usize = 256;
+
+pub(crate) fn validate_cost(cost: f32) -> Result<(), Error> {
+    if cost > 0.0 {
+        Ok(())
+    } else {
+        Err(Error::InvalidInput(format!(
+            "terrain cost must be positive, got {}",
+            cost
+        )))
+    }
+}
+
+/// Per-agent multipliers on a grid's terrain costs, so that e.g. a heavy unit can
+/// avoid swamps that a scout cuts straight through. `Infinity` makes a terrain
+/// impassable for the agent.
+#[wasm_bindgen]
+#[derive(Clone)]
+pub struct TerrainCosts {
+    multipliers: Vec<f32>,
+}
+
+#[wasm_bindgen]
+impl TerrainCosts {
+    /// Creates a profile with every multiplier set to 1.
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> TerrainCosts {
+        TerrainCosts {
+            multipliers: vec![1.0; TERRAIN_TYPES],
+        }
+    }
+
+    pub fn set(&mut self, terrain: u8, multiplier: f32) -> Result<(), Error> {
+        validate_cost(multiplier)?;
+        self.multipliers[terrain as usize] = multiplier;
+        Ok(())
+    }
+
+    pub fn get(&self, terrain: u8) -> f32 {
+        self.multipliers[terrain as usize]
+    }
+}
+
+impl Default for TerrainCosts {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[wasm_bindgen]
+impl Grid {
+    /// Assigns a terrain type to a cell. All cells start as type 0.
+    /// Out-of-bounds cells are ignored.
+    pub fn set_terrain(&mut self, x: u32, y: u32, terrain: u8) {
+        if x < self.width() && y < self.height() {
+            let index = self.index(x as i32, y as i32);
+            self.terrain[index] = terrain;
+        }
+    }
+
+    /// Terrain type of a cell, 0 when out of bounds.
+    pub fn terrain(&self, x: i32, y: i32) -> u8 {
+        if self.in_bounds(x, y) {
+            self.terrain[self.index(x, y)]
+        } else {
+            0
+        }
+    }
+
+    /// Sets the cost per unit distance of moving through a terrain type, shared by
+    /// every agent. Defaults to 1; `Infinity` blocks the terrain outright.
+    pub fn set_terrain_cost(&mut self, terrain: u8, cost: f32) -> Result<(), Error> {
+        validate_cost(cost)?;
+        self.terrain_costs[terrain as usize] = cost;
+        Ok(())
+    }
+
+    pub fn terrain_cost(&self, terrain: u8) -> f32 {
+        self.terrain_costs[terrain as usize]
+    }
+
+    /// Like `find_path`, with the terrain costs scaled by an agent's multipliers.
+    pub fn find_path_for(
+        &self,
+        start_x: u32,
+        start_y: u32,
+        end_x: u32,
+        end_y: u32,
+        costs: &TerrainCosts,
+    ) -> Vec<f32> {
+        self.find_weighted_path(
+            start_x,
+            start_y,
+            end_x,
+            end_y,
+            Traversal {
+                costs: Some(costs),
+                ..Traversal::default()
+            },
+        )
+    }
+}
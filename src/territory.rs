@@ -0,0 +1,263 @@
+use std::collections::BTreeMap;
+
+use wasm_bindgen::prelude::*;
+
+use crate::error::Error;
+use crate::grid::Grid;
+use crate::math::Vec2;
+use crate::search;
+
+/// Orthogonal steps that make two cells touch for adjacency.
+const SIDES: [(i32, i32); 4] = [(1, 0), (-1, 0), (0, 1), (0, -1)];
+
+/// Label of unclaimed walkable cells.
+const UNCLAIMED: i32 = -1;
+/// Label of blocked cells.
+const BLOCKED: i32 = -2;
+
+/// A grid split into regions around seeds, as made by `Grid::voronoi` or
+/// `Grid::grow_regions`, e.g. the zones a strategy AI holds, contests or
+/// expands into. Region ids are the seeds' positions in the list given.
+#[wasm_bindgen]
+pub struct Territory {
+    width: u32,
+    labels: Vec<i32>,
+    sizes: Vec<u32>,
+    /// Cell sides shared by each pair of touching regions `(a, b)`, `a < b`.
+    borders: BTreeMap<(u32, u32), u32>,
+}
+
+#[wasm_bindgen]
+impl Territory {
+    #[wasm_bindgen(getter)]
+    pub fn region_count(&self) -> u32 {
+        self.sizes.len() as u32
+    }
+
+    /// The region of each cell, indexed by `y * width + x`; -1 marks walkable
+    /// cells no region claimed and -2 blocked ones.
+    pub fn labels(&self) -> Vec<i32> {
+        self.labels.clone()
+    }
+
+    pub fn region_at(&self, x: u32, y: u32) -> Option<u32> {
+        if x >= self.width {
+            return None;
+        }
+        let label = *self.labels.get((y * self.width + x) as usize)?;
+        (label >= 0).then_some(label as u32)
+    }
+
+    /// The number of cells in each region.
+    pub fn sizes(&self) -> Vec<u32> {
+        self.sizes.clone()
+    }
+
+    /// The cells of `region` as flat `[x0, y0, x1, y1, ...]`.
+    pub fn cells(&self, region: u32) -> Vec<u32> {
+        self.cells_where(|_, label| label == region as i32)
+    }
+
+    /// The mean position of the cells of `region`, or undefined when it has
+    /// none.
+    pub fn centroid(&self, region: u32) -> Option<Vec2> {
+        let cells = self.cells(region);
+        if cells.is_empty() {
+            return None;
+        }
+        let count = (cells.len() / 2) as f32;
+        let (sx, sy) = cells.chunks_exact(2).fold((0.0, 0.0), |(sx, sy), cell| {
+            (sx + cell[0] as f32, sy + cell[1] as f32)
+        });
+        Some(Vec2::new(sx / count, sy / count))
+    }
+
+    /// The regions touching `region`, in id order.
+    pub fn neighbors(&self, region: u32) -> Vec<u32> {
+        let mut neighbors: Vec<u32> = self
+            .borders
+            .keys()
+            .filter_map(|&(a, b)| {
+                if a == region {
+                    Some(b)
+                } else if b == region {
+                    Some(a)
+                } else {
+                    None
+                }
+            })
+            .collect();
+        neighbors.sort_unstable();
+        neighbors
+    }
+
+    /// Every pair of touching regions as flat `[a0, b0, a1, b1, ...]` with
+    /// `a < b`, the edges of the region adjacency graph.
+    pub fn adjacency(&self) -> Vec<u32> {
+        self.borders.keys().flat_map(|&(a, b)| [a, b]).collect()
+    }
+
+    /// How many cell sides regions `a` and `b` share, 0 when they do not
+    /// touch; a short border between large regions marks a chokepoint.
+    pub fn border_length(&self, a: u32, b: u32) -> u32 {
+        self.borders
+            .get(&(a.min(b), a.max(b)))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// The cells of region `a` that touch region `b`, as flat `[x0, y0, ...]`,
+    /// e.g. where to hold the line or push across.
+    pub fn border(&self, a: u32, b: u32) -> Vec<u32> {
+        self.cells_where(|index, label| {
+            label == a as i32 && self.touches(index, |other| other == b as i32)
+        })
+    }
+
+    /// The cells of `region` next to unclaimed walkable cells or another
+    /// region, as flat `[x0, y0, ...]`, e.g. candidates to expand from.
+    pub fn frontier(&self, region: u32) -> Vec<u32> {
+        self.cells_where(|index, label| {
+            label == region as i32 && self.touches(index, |other| other != region as i32)
+        })
+    }
+}
+
+impl Territory {
+    fn new(grid: &Grid, labels: Vec<i32>, regions: usize) -> Territory {
+        let mut territory = Territory {
+            width: grid.width(),
+            labels,
+            sizes: vec![0; regions],
+            borders: BTreeMap::new(),
+        };
+        for (index, &label) in territory.labels.iter().enumerate() {
+            if label < 0 {
+                continue;
+            }
+            territory.sizes[label as usize] += 1;
+            let (x, y) = grid.coords(index);
+            // Each touching pair once, from its left or upper cell.
+            for (dx, dy) in [(1, 0), (0, 1)] {
+                if !grid.in_bounds(x + dx, y + dy) {
+                    continue;
+                }
+                let other = territory.labels[grid.index(x + dx, y + dy)];
+                if other >= 0 && other != label {
+                    let key = (label.min(other) as u32, label.max(other) as u32);
+                    *territory.borders.entry(key).or_insert(0) += 1;
+                }
+            }
+        }
+        territory
+    }
+
+    /// Whether a walkable cell beside `index` has a label `accept` takes.
+    fn touches(&self, index: usize, accept: impl Fn(i32) -> bool) -> bool {
+        let width = self.width as i32;
+        let height = (self.labels.len() / self.width.max(1) as usize) as i32;
+        let (x, y) = (index as i32 % width, index as i32 / width);
+        SIDES.iter().any(|&(dx, dy)| {
+            let (nx, ny) = (x + dx, y + dy);
+            if nx < 0 || ny < 0 || nx >= width || ny >= height {
+                return false;
+            }
+            let other = self.labels[(ny * width + nx) as usize];
+            other != BLOCKED && accept(other)
+        })
+    }
+
+    fn cells_where(&self, keep: impl Fn(usize, i32) -> bool) -> Vec<u32> {
+        let width = self.width.max(1) as usize;
+        self.labels
+            .iter()
+            .enumerate()
+            .filter(|&(index, &label)| keep(index, label))
+            .flat_map(|(index, _)| [(index % width) as u32, (index / width) as u32])
+            .collect()
+    }
+}
+
+#[wasm_bindgen]
+impl Grid {
+    /// Splits the walkable cells by which of `seeds`, flat `[x0, y0, x1, y1,
+    /// ...]` positions, is nearest in a straight line, ignoring walls and
+    /// terrain costs. Ties go to the earlier seed.
+    pub fn voronoi(&self, seeds: &[f32]) -> Result<Territory, Error> {
+        if !seeds.len().is_multiple_of(2) {
+            return Err(Error::InvalidInput(
+                "expected flat [x, y] seed pairs".into(),
+            ));
+        }
+        let seeds: Vec<Vec2> = seeds
+            .chunks_exact(2)
+            .map(|seed| Vec2::new(seed[0], seed[1]))
+            .collect();
+        let labels = (0..self.cell_count())
+            .map(|index| {
+                let (x, y) = self.coords(index);
+                if !self.is_walkable(x, y) {
+                    return BLOCKED;
+                }
+                let cell = Vec2::new(x as f32, y as f32);
+                seeds
+                    .iter()
+                    .enumerate()
+                    .min_by(|a, b| {
+                        (*a.1 - cell)
+                            .length_squared()
+                            .total_cmp(&(*b.1 - cell).length_squared())
+                    })
+                    .map_or(UNCLAIMED, |(region, _)| region as i32)
+            })
+            .collect();
+        Ok(Territory::new(self, labels, seeds.len()))
+    }
+
+    /// Grows a region from each of the cells `seeds`, flat `[x0, y0, x1, y1,
+    /// ...]`, over the walkable grid at once, so each cell joins the seed
+    /// cheapest to walk from, around walls and through terrain costs. Cells
+    /// costing more than `max_cost` to reach from every seed, or unreachable,
+    /// stay unclaimed. Seeds outside the grid or blocked get empty regions.
+    pub fn grow_regions(&self, seeds: &[u32], max_cost: Option<f32>) -> Result<Territory, Error> {
+        let seed_at = self.cells_of(seeds)?;
+        let sources: Vec<usize> = (0..seed_at.len())
+            .filter(|&index| seed_at[index] != u32::MAX)
+            .collect();
+        let (cost, parent) = search::shortest_path_tree(seed_at.len(), &sources, |index, out| {
+            self.neighbors(index, out)
+        });
+        let max_cost = max_cost.unwrap_or(f32::INFINITY);
+        let mut labels: Vec<i32> = (0..seed_at.len())
+            .map(|index| {
+                let (x, y) = self.coords(index);
+                if self.is_walkable(x, y) {
+                    UNCLAIMED
+                } else {
+                    BLOCKED
+                }
+            })
+            .collect();
+        // Each claimed cell takes the region of the seed at the root of its
+        // branch of the search tree, labelling the branch on the way.
+        let mut branch = Vec::new();
+        for (index, &reach) in cost.iter().enumerate() {
+            if !reach.is_finite() || reach > max_cost {
+                continue;
+            }
+            let mut at = index;
+            while labels[at] < 0 && parent[at] != usize::MAX {
+                branch.push(at);
+                at = parent[at];
+            }
+            if labels[at] < 0 {
+                labels[at] = seed_at[at] as i32;
+            }
+            let label = labels[at];
+            for cell in branch.drain(..) {
+                labels[cell] = label;
+            }
+        }
+        Ok(Territory::new(self, labels, seeds.len() / 2))
+    }
+}
@@ -0,0 +1,95 @@
+use wasm_bindgen::prelude::*;
+
+use crate::arena::{self, SearchBuffers};
+use crate::grid::Grid;
+use crate::search::{self, OpenNode};
+
+fn euclidean(dx: i32, dy: i32) -> f32 {
+    ((dx * dx + dy * dy) as f32).sqrt()
+}
+
+#[wasm_bindgen]
+impl Grid {
+    /// Theta*: any-angle search that shortcuts through any cell with line of sight
+    /// to an ancestor, so paths are not limited to 8 directions. Returns only the
+    /// turning points as flat `[x0, y0, x1, y1, ...]` cell coordinates, where
+    /// consecutive points are joined by straight, unobstructed segments between cell
+    /// centers. Falls back to `find_path` once any terrain or obstacle cost differs
+    /// from 1, infinite ones included.
+    pub fn find_path_theta(&self, start_x: u32, start_y: u32, end_x: u32, end_y: u32) -> Vec<f32> {
+        if !self.has_uniform_costs() {
+            return self.find_path(start_x, start_y, end_x, end_y);
+        }
+        let (sx, sy, ex, ey) = (start_x as i32, start_y as i32, end_x as i32, end_y as i32);
+        if !self.is_walkable(sx, sy) || !self.is_walkable(ex, ey) {
+            return Vec::new();
+        }
+        match self.theta_star(sx, sy, ex, ey) {
+            Some(points) => self.to_waypoints(&points),
+            None => Vec::new(),
+        }
+    }
+}
+
+impl Grid {
+    fn theta_star(&self, sx: i32, sy: i32, ex: i32, ey: i32) -> Option<Vec<usize>> {
+        let cell_count = self.cell_count();
+        let start = self.index(sx, sy);
+        let goal = self.index(ex, ey);
+        let mut buffers = arena::search(cell_count);
+        let SearchBuffers {
+            g,
+            parent,
+            closed,
+            open,
+            edges,
+        } = &mut *buffers;
+
+        g[start] = 0.0;
+        open.push(OpenNode {
+            cost: euclidean(ex - sx, ey - sy),
+            index: start,
+        });
+
+        while let Some(OpenNode { index, .. }) = open.pop() {
+            if index == goal {
+                return Some(search::reconstruct(parent, goal));
+            }
+            if closed[index] {
+                continue;
+            }
+            closed[index] = true;
+
+            // Try to connect each neighbor straight to this cell's parent first.
+            let anchor = if parent[index] == usize::MAX {
+                index
+            } else {
+                parent[index]
+            };
+            let (ax, ay) = self.coords(anchor);
+            edges.clear();
+            self.neighbors(index, edges);
+            for &(next, step) in edges.iter() {
+                if closed[next] {
+                    continue;
+                }
+                let (nx, ny) = self.coords(next);
+                let (from, tentative) = if self.line_of_sight(ax, ay, nx, ny) {
+                    (anchor, g[anchor] + euclidean(nx - ax, ny - ay))
+                } else {
+                    (index, g[index] + step)
+                };
+                if tentative < g[next] {
+                    g[next] = tentative;
+                    parent[next] = from;
+                    open.push(OpenNode {
+                        cost: tentative + euclidean(ex - nx, ey - ny),
+                        index: next,
+                    });
+                }
+            }
+        }
+
+        None
+    }
+}
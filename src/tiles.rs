@@ -0,0 +1,217 @@
+use std::ops::Range;
+
+use wasm_bindgen::prelude::*;
+
+use crate::error::Error;
+use crate::navmesh::{edge_key, validate_geometry, Link, NavMesh};
+
+/// Border vertices of neighboring tiles closer than this are treated as the same.
+const STITCH_EPSILON: f32 = 1e-3;
+
+/// Where a loaded tile's geometry lives in the mesh.
+#[derive(Clone)]
+pub(crate) struct Tile {
+    pub triangles: Range<usize>,
+    pub vertices: Range<usize>,
+}
+
+#[wasm_bindgen]
+impl NavMesh {
+    /// Creates an empty mesh that is filled in tile by tile with `add_tile`. Tile
+    /// `(x, z)` covers `[x * tile_size, (x + 1) * tile_size)` on each axis.
+    pub fn tiled(tile_size: f32) -> Result<NavMesh, Error> {
+        if tile_size > 0.0 && tile_size.is_finite() {
+            Ok(NavMesh::empty(tile_size))
+        } else {
+            Err(Error::InvalidInput(format!(
+                "tile size must be positive, got {}",
+                tile_size
+            )))
+        }
+    }
+
+    /// Loads a tile, replacing any tile already at `(x, z)`. Edges along the tile
+    /// border are connected to loaded neighbors whose border edges have matching
+    /// endpoints, so tiles must share vertex positions where they meet. Existing
+    /// obstacles are carved into the new tile.
+    pub fn add_tile(
+        &mut self,
+        x: i32,
+        z: i32,
+        vertices: &[f32],
+        indices: &[u32],
+    ) -> Result<(), Error> {
+        if self.tile_size <= 0.0 {
+            return Err(Error::InvalidInput("mesh was not created as tiled".into()));
+        }
+        validate_geometry(vertices, indices)?;
+        self.remove_tile(x, z);
+
+        let tile = Tile {
+            triangles: self.triangles.len()..self.triangles.len() + indices.len() / 3,
+            vertices: self.vertices.len()..self.vertices.len() + vertices.len() / 3,
+        };
+        self.append(vertices, indices);
+        for neighbor in self.neighbor_tiles(x, z) {
+            self.stitch(tile.triangles.clone(), neighbor.triangles.clone());
+            self.refresh_boundary(neighbor.triangles);
+        }
+        self.refresh_boundary(tile.triangles.clone());
+        self.recarve(tile.triangles.clone());
+        self.tiles.insert((x, z), tile);
+        Ok(())
+    }
+
+    /// Unloads a tile and disconnects it from its neighbors. Returns false when no
+    /// tile is loaded at `(x, z)`.
+    pub fn remove_tile(&mut self, x: i32, z: i32) -> bool {
+        let Some(tile) = self.tiles.remove(&(x, z)) else {
+            return false;
+        };
+        let (triangles, vertices) = (tile.triangles, tile.vertices);
+        let shift_triangle = |index: usize| {
+            if index >= triangles.end {
+                index - triangles.len()
+            } else {
+                index
+            }
+        };
+        let shift_vertex = |index: u32| {
+            if index as usize >= vertices.end {
+                index - vertices.len() as u32
+            } else {
+                index
+            }
+        };
+
+        self.triangles.drain(triangles.clone());
+        self.centroids.drain(triangles.clone());
+        self.links.drain(triangles.clone());
+        self.blockers.drain(triangles.clone());
+        self.areas.drain(triangles.clone());
+        self.flags.drain(triangles.clone());
+        self.vertices.drain(vertices.clone());
+        self.boundary.drain(vertices.clone());
+        for triangle in &mut self.triangles {
+            *triangle = triangle.map(shift_vertex);
+        }
+        for links in &mut self.links {
+            links.retain(|link| !triangles.contains(&link.triangle));
+            for link in links {
+                link.triangle = shift_triangle(link.triangle);
+                link.edge = (shift_vertex(link.edge.0), shift_vertex(link.edge.1));
+            }
+        }
+        for obstacle in self.obstacles.values_mut() {
+            obstacle
+                .triangles
+                .retain(|index| !triangles.contains(index));
+            for index in &mut obstacle.triangles {
+                *index = shift_triangle(*index);
+            }
+        }
+        for other in self.tiles.values_mut() {
+            if other.triangles.start >= triangles.end {
+                other.triangles =
+                    shift_triangle(other.triangles.start)..shift_triangle(other.triangles.end);
+                other.vertices = shift_vertex(other.vertices.start as u32) as usize
+                    ..shift_vertex(other.vertices.end as u32) as usize;
+            }
+        }
+
+        for neighbor in self.neighbor_tiles(x, z) {
+            self.refresh_boundary(neighbor.triangles);
+        }
+        true
+    }
+
+    pub fn has_tile(&self, x: i32, z: i32) -> bool {
+        self.tiles.contains_key(&(x, z))
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn tile_count(&self) -> u32 {
+        self.tiles.len() as u32
+    }
+}
+
+impl NavMesh {
+    /// Triangle ranges worth searching for a point: the tile under it and its
+    /// neighbors, since tile geometry may overhang the tile bounds slightly. `None`
+    /// for untiled meshes.
+    pub(crate) fn tile_candidates(&self, x: f32, z: f32) -> Option<Vec<Range<usize>>> {
+        if self.tile_size <= 0.0 {
+            return None;
+        }
+        let (tx, tz) = (
+            (x / self.tile_size).floor() as i32,
+            (z / self.tile_size).floor() as i32,
+        );
+        let mut ranges: Vec<Range<usize>> = self
+            .neighbor_tiles(tx, tz)
+            .into_iter()
+            .map(|tile| tile.triangles)
+            .collect();
+        ranges.extend(self.tiles.get(&(tx, tz)).map(|tile| tile.triangles.clone()));
+        Some(ranges)
+    }
+
+    fn neighbor_tiles(&self, x: i32, z: i32) -> Vec<Tile> {
+        let mut neighbors = Vec::new();
+        for dz in -1..=1 {
+            for dx in -1..=1 {
+                if dx == 0 && dz == 0 {
+                    continue;
+                }
+                if let Some(tile) = self.tiles.get(&(x + dx, z + dz)) {
+                    neighbors.push(tile.clone());
+                }
+            }
+        }
+        neighbors
+    }
+
+    /// Links open edges of `tile` to open edges of `neighbor` with coincident
+    /// endpoints.
+    fn stitch(&mut self, tile: Range<usize>, neighbor: Range<usize>) {
+        let open = |mesh: &NavMesh, range: Range<usize>| {
+            let mut edges = Vec::new();
+            for index in range {
+                let t = mesh.triangles[index];
+                for k in 0..3 {
+                    let edge = edge_key(t[k], t[(k + 1) % 3]);
+                    if !mesh.links[index].iter().any(|link| link.edge == edge) {
+                        edges.push((index, edge));
+                    }
+                }
+            }
+            edges
+        };
+        let near = |a: u32, b: u32| {
+            let (a, b) = (self.vertices[a as usize], self.vertices[b as usize]);
+            (0..3).all(|k| (a[k] - b[k]).abs() <= STITCH_EPSILON)
+        };
+
+        let mut matches = Vec::new();
+        let theirs = open(self, neighbor);
+        for (index, edge) in open(self, tile) {
+            for &(other, other_edge) in &theirs {
+                let same = (near(edge.0, other_edge.0) && near(edge.1, other_edge.1))
+                    || (near(edge.0, other_edge.1) && near(edge.1, other_edge.0));
+                if same {
+                    matches.push((index, edge, other, other_edge));
+                }
+            }
+        }
+        for (index, edge, other, other_edge) in matches {
+            self.links[index].push(Link {
+                triangle: other,
+                edge,
+            });
+            self.links[other].push(Link {
+                triangle: index,
+                edge: other_edge,
+            });
+        }
+    }
+}
@@ -0,0 +1,330 @@
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+
+use wasm_bindgen::prelude::*;
+
+use crate::error::Error;
+use crate::events::{AiEvent, EventKind};
+use crate::world::AiWorld;
+
+#[derive(Clone, Copy, Debug)]
+struct Scheduled {
+    agent: u32,
+    action: u32,
+    due: f64,
+    /// Seconds between repeats, or 0 for a one-off.
+    interval: f64,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct QueueEntry {
+    due: f64,
+    id: u32,
+}
+
+impl PartialEq for QueueEntry {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for QueueEntry {}
+
+impl PartialOrd for QueueEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for QueueEntry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Reversed so `BinaryHeap` pops the earliest first, and of actions due
+        // together the one scheduled first.
+        other
+            .due
+            .total_cmp(&self.due)
+            .then_with(|| other.id.cmp(&self.id))
+    }
+}
+
+/// Named cooldowns and timers per agent, and actions scheduled to happen
+/// later, on one clock, so behavior trees and state machines ask
+/// `is_ready(agent, "attack")` instead of keeping time in blackboard floats.
+///
+/// The clock moves with `update`, or with `sync` to follow an `AiWorld`'s
+/// fixed steps. Scheduled actions come due as `ActionDue` events with the
+/// agent as `subject` and the action number as `other`, in the order they
+/// fell due; read them from `events` or publish them to an `EventBus`.
+/// Agents are whatever ids the game uses, e.g. `AiWorld` handles.
+#[wasm_bindgen]
+#[derive(Clone, Debug, Default)]
+pub struct Timers {
+    time: f64,
+    names: HashMap<String, u32>,
+    /// When each agent's named cooldown ends.
+    cooldowns: HashMap<(u32, u32), f64>,
+    /// When each agent's named timer started.
+    started: HashMap<(u32, u32), f64>,
+    scheduled: HashMap<u32, Scheduled>,
+    queue: BinaryHeap<QueueEntry>,
+    next_id: u32,
+    events: Vec<AiEvent>,
+}
+
+#[wasm_bindgen]
+impl Timers {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Timers {
+        Timers::default()
+    }
+
+    /// Seconds the clock has run.
+    #[wasm_bindgen(getter)]
+    pub fn time(&self) -> f64 {
+        self.time
+    }
+
+    /// Puts `agent`'s cooldown `name` on for `seconds`, replacing any left.
+    pub fn start_cooldown(&mut self, agent: u32, name: &str, seconds: f32) {
+        let key = (agent, self.intern(name));
+        self.cooldowns
+            .insert(key, self.time + seconds.max(0.0) as f64);
+    }
+
+    /// Whether `agent`'s cooldown `name` has run out, or was never started.
+    pub fn is_ready(&self, agent: u32, name: &str) -> bool {
+        self.cooldown_remaining(agent, name) <= 0.0
+    }
+
+    /// Seconds left on `agent`'s cooldown `name`, or 0 when it is ready.
+    pub fn cooldown_remaining(&self, agent: u32, name: &str) -> f32 {
+        self.key(agent, name)
+            .and_then(|key| self.cooldowns.get(&key))
+            .map_or(0.0, |&end| (end - self.time).max(0.0) as f32)
+    }
+
+    /// Makes `agent`'s cooldown `name` ready at once, returning whether it
+    /// was still running.
+    pub fn clear_cooldown(&mut self, agent: u32, name: &str) -> bool {
+        let time = self.time;
+        self.key(agent, name)
+            .and_then(|key| self.cooldowns.remove(&key))
+            .is_some_and(|end| end > time)
+    }
+
+    /// Starts, or restarts, `agent`'s timer `name` counting up from 0.
+    pub fn start_timer(&mut self, agent: u32, name: &str) {
+        let key = (agent, self.intern(name));
+        self.started.insert(key, self.time);
+    }
+
+    /// Seconds since `agent`'s timer `name` started, or nothing when it is
+    /// not running.
+    pub fn timer_elapsed(&self, agent: u32, name: &str) -> Option<f32> {
+        self.key(agent, name)
+            .and_then(|key| self.started.get(&key))
+            .map(|&start| (self.time - start) as f32)
+    }
+
+    /// Stops `agent`'s timer `name`, returning whether it was running.
+    pub fn stop_timer(&mut self, agent: u32, name: &str) -> bool {
+        self.key(agent, name)
+            .is_some_and(|key| self.started.remove(&key).is_some())
+    }
+
+    /// Schedules action number `action` of `agent` for `delay` seconds from
+    /// now and returns an id for `cancel`.
+    pub fn schedule(&mut self, agent: u32, action: u32, delay: f32) -> u32 {
+        self.push(agent, action, delay, 0.0)
+    }
+
+    /// Schedules action number `action` of `agent` for `delay` seconds from
+    /// now and every `interval` seconds after, until cancelled. Fails when the
+    /// interval is too small to move the clock on from when it is first due.
+    pub fn schedule_repeating(
+        &mut self,
+        agent: u32,
+        action: u32,
+        delay: f32,
+        interval: f32,
+    ) -> Result<u32, Error> {
+        if interval.is_nan() || interval <= 0.0 {
+            return Err(Error::InvalidInput(
+                "a repeating action needs a positive interval".into(),
+            ));
+        }
+        let due = self.time + delay.max(0.0) as f64;
+        if due.is_finite() && due + interval as f64 == due {
+            return Err(Error::InvalidInput(format!(
+                "a repeating interval of {} seconds is too small to count at {} seconds",
+                interval, due
+            )));
+        }
+        Ok(self.push(agent, action, delay, interval as f64))
+    }
+
+    /// Cancels a scheduled action, returning whether it was still pending.
+    pub fn cancel(&mut self, id: u32) -> bool {
+        self.scheduled.remove(&id).is_some()
+    }
+
+    pub fn is_scheduled(&self, id: u32) -> bool {
+        self.scheduled.contains_key(&id)
+    }
+
+    /// Seconds until scheduled action `id` comes due next, or nothing when it
+    /// is not pending.
+    pub fn time_until(&self, id: u32) -> Option<f32> {
+        self.scheduled
+            .get(&id)
+            .map(|scheduled| (scheduled.due - self.time).max(0.0) as f32)
+    }
+
+    /// Drops every cooldown, timer and scheduled action of `agent`, e.g. when
+    /// it dies.
+    pub fn remove_agent(&mut self, agent: u32) {
+        self.cooldowns.retain(|&(owner, _), _| owner != agent);
+        self.started.retain(|&(owner, _), _| owner != agent);
+        self.scheduled
+            .retain(|_, scheduled| scheduled.agent != agent);
+    }
+
+    /// Scheduled actions still pending.
+    #[wasm_bindgen(getter)]
+    pub fn len(&self) -> u32 {
+        self.scheduled.len() as u32
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.scheduled.is_empty()
+    }
+
+    /// Advances the clock by `dt` seconds, raising an `ActionDue` event for
+    /// every action that falls due, repeating ones as often as they did.
+    pub fn update(&mut self, dt: f32) {
+        self.advance_to(self.time + dt.max(0.0) as f64);
+    }
+
+    /// Advances the clock to `world`'s simulated time, so cooldowns and
+    /// schedules run on its fixed steps. Call it after each `tick`.
+    pub fn sync(&mut self, world: &AiWorld) {
+        self.advance_to(world.time());
+    }
+
+    /// Drops every cooldown, timer and scheduled action and restarts the
+    /// clock.
+    pub fn clear(&mut self) {
+        *self = Timers {
+            next_id: self.next_id,
+            ..Timers::default()
+        };
+    }
+
+    /// `ActionDue` events of the last `update` or `sync`.
+    pub fn events(&self) -> Vec<AiEvent> {
+        self.events.clone()
+    }
+}
+
+impl Timers {
+    pub(crate) fn recent_events(&self) -> &[AiEvent] {
+        &self.events
+    }
+
+    fn intern(&mut self, name: &str) -> u32 {
+        if let Some(&id) = self.names.get(name) {
+            return id;
+        }
+        let id = self.names.len() as u32;
+        self.names.insert(name.to_string(), id);
+        id
+    }
+
+    fn key(&self, agent: u32, name: &str) -> Option<(u32, u32)> {
+        self.names.get(name).map(|&id| (agent, id))
+    }
+
+    fn push(&mut self, agent: u32, action: u32, delay: f32, interval: f64) -> u32 {
+        let id = self.next_id;
+        self.next_id = self.next_id.wrapping_add(1);
+        let due = self.time + delay.max(0.0) as f64;
+        self.scheduled.insert(
+            id,
+            Scheduled {
+                agent,
+                action,
+                due,
+                interval,
+            },
+        );
+        self.queue.push(QueueEntry { due, id });
+        id
+    }
+
+    fn advance_to(&mut self, time: f64) {
+        self.events.clear();
+        self.time = self.time.max(time);
+        while let Some(&QueueEntry { due, id }) = self.queue.peek() {
+            if due > self.time {
+                break;
+            }
+            self.queue.pop();
+            // Cancelled since it was queued, or its id reused after wrapping.
+            let Some(scheduled) = self.scheduled.get_mut(&id) else {
+                continue;
+            };
+            if scheduled.due != due {
+                continue;
+            }
+            self.events.push(AiEvent::new(
+                EventKind::ActionDue,
+                scheduled.agent,
+                scheduled.action,
+            ));
+            if scheduled.interval > 0.0 {
+                // Once the clock outgrows the interval's precision, the
+                // action fires once per update instead of spinning in place.
+                let next = scheduled.due + scheduled.interval;
+                scheduled.due = if next > scheduled.due {
+                    next
+                } else {
+                    self.time.next_up()
+                };
+                self.queue.push(QueueEntry {
+                    due: scheduled.due,
+                    id,
+                });
+            } else {
+                self.scheduled.remove(&id);
+            }
+        }
+        let now = self.time;
+        self.cooldowns.retain(|_, end| *end > now);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_an_interval_too_small_for_the_clock() {
+        let mut timers = Timers::new();
+        timers.update(1e17);
+        assert!(timers.schedule_repeating(0, 0, 0.0, 1.0).is_err());
+        assert!(timers.schedule_repeating(0, 0, 0.0, 1e3).is_ok());
+    }
+
+    #[test]
+    fn repeats_once_per_update_once_the_interval_stops_counting() {
+        let mut timers = Timers::new();
+        timers.update(4_503_599_090_499_584.0); // 2^52 - 2^29
+        timers.update(536_870_848.0); // up to 2^52 - 64
+        timers.schedule_repeating(0, 0, 0.0, 0.4).unwrap();
+        // Steps of 0.4 round to 0.5 up to 2^52 and to nothing past it.
+        timers.update(128.0);
+        assert_eq!(timers.events().len(), 129);
+        timers.update(1.0);
+        assert_eq!(timers.events().len(), 1);
+    }
+}
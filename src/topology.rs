@@ -0,0 +1,549 @@
+use std::collections::{BTreeSet, BinaryHeap, VecDeque};
+
+use js_sys::Promise;
+use wasm_bindgen::prelude::*;
+
+use crate::debug::{self, DebugGeometry};
+use crate::grid::Grid;
+use crate::math::Vec2;
+use crate::navmesh::{edge_key, NavMesh, Point};
+use crate::search::OpenNode;
+use crate::task;
+
+/// What part a region plays in the map's layout.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TopologyTag {
+    /// Room to maneuver: within a disc of more than the `narrow` clearance.
+    Open = 0,
+    /// A narrow passage joining two or more open areas, e.g. a door or a
+    /// bridge to hold.
+    Chokepoint = 1,
+    /// A narrow passage with one way in, e.g. for ambushes or a last stand.
+    DeadEnd = 2,
+    /// A narrow passage that leaves and rejoins the same open area, or links
+    /// no open area at all, as in a maze.
+    Corridor = 3,
+}
+
+struct Region {
+    tag: TopologyTag,
+    size: u32,
+    /// The narrowest node of a passage, or the widest of an open area.
+    anchor: usize,
+    clearance: f32,
+    /// Whether closing the region would cut some of its neighbors off from
+    /// each other.
+    cut: bool,
+    neighbors: BTreeSet<u32>,
+}
+
+enum Layout {
+    Grid { width: u32 },
+    Mesh { triangles: Vec<[Point; 3]> },
+}
+
+/// The layout of a grid or navmesh split into tagged regions: open areas, and
+/// the chokepoints, dead ends and corridors between them, as found by
+/// `analyze_topology` from clearance, how far each cell or triangle is from the
+/// nearest wall. Neighboring cells or triangles of the same kind, open or
+/// narrow, form one region.
+///
+/// Nodes are grid cells, indexed by `y * width + x`, or navmesh triangles.
+/// The analysis works on a snapshot, so run it again after the map changes.
+#[wasm_bindgen]
+pub struct Topology {
+    layout: Layout,
+    labels: Vec<i32>,
+    regions: Vec<Region>,
+}
+
+#[wasm_bindgen]
+impl Topology {
+    #[wasm_bindgen(getter)]
+    pub fn region_count(&self) -> u32 {
+        self.regions.len() as u32
+    }
+
+    /// The region of each node; -1 marks blocked ones.
+    pub fn labels(&self) -> Vec<i32> {
+        self.labels.clone()
+    }
+
+    pub fn region_of(&self, node: u32) -> Option<u32> {
+        let label = *self.labels.get(node as usize)?;
+        (label >= 0).then_some(label as u32)
+    }
+
+    pub fn tag(&self, region: u32) -> Option<TopologyTag> {
+        self.regions.get(region as usize).map(|region| region.tag)
+    }
+
+    /// The regions with `tag`, in id order.
+    pub fn regions_tagged(&self, tag: TopologyTag) -> Vec<u32> {
+        (0..self.regions.len() as u32)
+            .filter(|&region| self.regions[region as usize].tag == tag)
+            .collect()
+    }
+
+    /// The number of nodes in `region`.
+    pub fn size(&self, region: u32) -> u32 {
+        self.regions
+            .get(region as usize)
+            .map_or(0, |region| region.size)
+    }
+
+    /// The clearance at the anchor of `region`: how narrow a passage gets, or
+    /// how roomy an open area is at its widest.
+    pub fn clearance(&self, region: u32) -> f32 {
+        self.regions
+            .get(region as usize)
+            .map_or(0.0, |region| region.clearance)
+    }
+
+    /// The narrowest node of a passage, e.g. where to post a guard, or the
+    /// widest node of an open area.
+    pub fn anchor(&self, region: u32) -> Option<u32> {
+        self.regions
+            .get(region as usize)
+            .map(|region| region.anchor as u32)
+    }
+
+    /// Where the anchor of `region` is: `[x, y]` cell coordinates on grids
+    /// and the triangle centroid `[x, y, z]` on navmeshes; empty for unknown
+    /// regions.
+    pub fn anchor_point(&self, region: u32) -> Vec<f32> {
+        let Some(region) = self.regions.get(region as usize) else {
+            return Vec::new();
+        };
+        match &self.layout {
+            Layout::Grid { width } => {
+                let width = *width as usize;
+                vec![
+                    (region.anchor % width) as f32,
+                    (region.anchor / width) as f32,
+                ]
+            }
+            Layout::Mesh { triangles } => centroid(triangles[region.anchor]).to_vec(),
+        }
+    }
+
+    /// Whether closing `region` would split the regions around it apart, so
+    /// that holding it cuts the map in two.
+    pub fn is_cut(&self, region: u32) -> bool {
+        self.regions
+            .get(region as usize)
+            .is_some_and(|region| region.cut)
+    }
+
+    /// The regions touching `region`, in id order.
+    pub fn neighbors(&self, region: u32) -> Vec<u32> {
+        self.regions
+            .get(region as usize)
+            .map_or_else(Vec::new, |region| {
+                region.neighbors.iter().copied().collect()
+            })
+    }
+
+    /// Fills every node with the color of its region's tag.
+    pub fn debug_geometry(&self, out: &mut DebugGeometry) {
+        for (node, &label) in self.labels.iter().enumerate() {
+            if label < 0 {
+                continue;
+            }
+            let color = match self.regions[label as usize].tag {
+                TopologyTag::Open => debug::OPEN,
+                TopologyTag::Chokepoint => debug::CHOKEPOINT,
+                TopologyTag::DeadEnd => debug::DEAD_END,
+                TopologyTag::Corridor => debug::CORRIDOR,
+            };
+            match &self.layout {
+                Layout::Grid { width } => {
+                    let width = *width as usize;
+                    out.cell((node % width) as f32, (node / width) as f32, color);
+                }
+                Layout::Mesh { triangles } => out.triangle_3d(triangles[node], color),
+            }
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl Grid {
+    /// Splits the walkable cells into open areas, those within a disc that
+    /// fits between the walls with more than `narrow` clearance at its center,
+    /// and the narrow passages between them, tagged chokepoints,
+    /// dead ends or corridors. Open areas of fewer than `min_area` cells, such
+    /// as a bulge in a corridor, count as part of the passage around them.
+    pub fn analyze_topology(&self, narrow: f32, min_area: u32) -> Topology {
+        let width = self.width();
+        let count = (width * self.height()) as usize;
+        let walkable: Vec<bool> = (0..count)
+            .map(|index| {
+                let (x, y) = self.coords(index);
+                self.is_walkable(x, y)
+            })
+            .collect();
+        let positions: Vec<Vec2> = (0..count)
+            .map(|index| {
+                let (x, y) = self.coords(index);
+                Vec2::new(x as f32, y as f32)
+            })
+            .collect();
+        let mut edges = Vec::new();
+        let (labels, regions) = analyze(
+            &walkable,
+            self.clearance_cache(),
+            &positions,
+            |index, out| {
+                edges.clear();
+                self.neighbors(index, &mut edges);
+                out.extend(edges.iter().map(|&(next, _)| next));
+            },
+            narrow,
+            min_area,
+        );
+        Topology {
+            layout: Layout::Grid { width },
+            labels,
+            regions,
+        }
+    }
+
+    /// Like `analyze_topology`, but returns a `Promise<Topology>` instead of
+    /// blocking. The analysis runs on a snapshot of the grid taken at call
+    /// time.
+    pub fn analyze_topology_async(&self, narrow: f32, min_area: u32) -> Promise {
+        let grid = self.clone();
+        task::spawn_value(move || grid.analyze_topology(narrow, min_area).into())
+    }
+}
+
+#[wasm_bindgen]
+impl NavMesh {
+    /// Splits the walkable triangles into open areas and narrow passages as
+    /// `Grid::analyze_topology` does, taking a triangle's clearance as the
+    /// distance on the XZ plane from its centroid or edge midpoints to the
+    /// nearest edge of the mesh or of a carved obstacle.
+    pub fn analyze_topology(&self, narrow: f32, min_area: u32) -> Topology {
+        let walkable: Vec<bool> = (0..self.triangles.len())
+            .map(|index| !self.is_blocked(index))
+            .collect();
+        let clearance = self.triangle_clearance(&walkable);
+        let positions: Vec<Vec2> = self.centroids.iter().map(|&point| flat(point)).collect();
+        let (labels, regions) = analyze(
+            &walkable,
+            &clearance,
+            &positions,
+            |index, out| out.extend(self.links[index].iter().map(|link| link.triangle)),
+            narrow,
+            min_area,
+        );
+        Topology {
+            layout: Layout::Mesh {
+                triangles: (0..self.triangles.len())
+                    .map(|index| self.triangle(index))
+                    .collect(),
+            },
+            labels,
+            regions,
+        }
+    }
+
+    /// Like `analyze_topology`, but returns a `Promise<Topology>` instead of
+    /// blocking. The analysis runs on a snapshot of the mesh taken at call
+    /// time.
+    pub fn analyze_topology_async(&self, narrow: f32, min_area: u32) -> Promise {
+        let mesh = self.clone();
+        task::spawn_value(move || mesh.analyze_topology(narrow, min_area).into())
+    }
+}
+
+impl NavMesh {
+    /// For each walkable triangle, the farthest its centroid or an edge
+    /// midpoint lies on XZ from the nearest edge with no walkable triangle on
+    /// its other side, so a corridor one unit wide has clearance 0.5 as on a
+    /// grid.
+    fn triangle_clearance(&self, walkable: &[bool]) -> Vec<f32> {
+        let mut walls = Vec::new();
+        for (index, t) in self.triangles.iter().enumerate() {
+            if !walkable[index] {
+                continue;
+            }
+            for k in 0..3 {
+                let edge = edge_key(t[k], t[(k + 1) % 3]);
+                let open = self.links[index]
+                    .iter()
+                    .any(|link| link.edge == edge && walkable[link.triangle]);
+                if !open {
+                    walls.push((
+                        self.vertices[t[k] as usize],
+                        self.vertices[t[(k + 1) % 3] as usize],
+                    ));
+                }
+            }
+        }
+        (0..self.triangles.len())
+            .map(|index| {
+                if !walkable[index] {
+                    return 0.0;
+                }
+                let [a, b, c] = self.triangle(index).map(flat);
+                let wall_distance = |point: Vec2| {
+                    walls
+                        .iter()
+                        .map(|&(from, to)| distance_to_segment(point, flat(from), flat(to)))
+                        .fold(f32::INFINITY, f32::min)
+                };
+                [
+                    flat(self.centroids[index]),
+                    (a + b) * 0.5,
+                    (b + c) * 0.5,
+                    (c + a) * 0.5,
+                ]
+                .into_iter()
+                .map(wall_distance)
+                .fold(0.0, f32::max)
+            })
+            .collect()
+    }
+}
+
+/// Labels the regions of a map of `walkable.len()` nodes, each with its
+/// `clearance`, its position on the plane, and the walkable nodes `neighbors`
+/// pushes.
+fn analyze(
+    walkable: &[bool],
+    clearance: &[f32],
+    positions: &[Vec2],
+    mut neighbors: impl FnMut(usize, &mut Vec<usize>),
+    narrow: f32,
+    min_area: u32,
+) -> (Vec<i32>, Vec<Region>) {
+    let count = walkable.len();
+    let mut adjacent: Vec<Vec<usize>> = vec![Vec::new(); count];
+    for (index, list) in adjacent.iter_mut().enumerate() {
+        if walkable[index] {
+            neighbors(index, list);
+            list.retain(|&next| walkable[next]);
+        }
+    }
+
+    let mut open = covered(walkable, clearance, positions, &adjacent, narrow);
+    // Open areas too small to count become part of the passage around them.
+    let (labels, sizes) = components(walkable, &adjacent, |index| open[index]);
+    for index in 0..count {
+        if open[index] && sizes[labels[index] as usize] < min_area {
+            open[index] = false;
+        }
+    }
+    let (labels, sizes) = components(walkable, &adjacent, |index| open[index]);
+
+    let mut regions: Vec<Region> = sizes
+        .iter()
+        .map(|&size| Region {
+            tag: TopologyTag::Open,
+            size,
+            anchor: usize::MAX,
+            clearance: 0.0,
+            cut: false,
+            neighbors: BTreeSet::new(),
+        })
+        .collect();
+    for index in (0..count).filter(|&index| labels[index] >= 0) {
+        let label = labels[index];
+        let region = &mut regions[label as usize];
+        let better = match region.anchor {
+            usize::MAX => true,
+            _ if open[index] => clearance[index] > region.clearance,
+            _ => clearance[index] < region.clearance,
+        };
+        if better {
+            region.anchor = index;
+            region.clearance = clearance[index];
+        }
+        for &next in &adjacent[index] {
+            if labels[next] != label {
+                region.neighbors.insert(labels[next] as u32);
+            }
+        }
+    }
+
+    // Neighboring nodes of one kind share a region, so passages only touch
+    // open areas and open areas only passages.
+    for region in 0..regions.len() {
+        if open[regions[region].anchor] {
+            continue;
+        }
+        regions[region].tag = match regions[region].neighbors.len() {
+            0 => TopologyTag::Corridor,
+            1 => {
+                let area = *regions[region].neighbors.first().unwrap() as i32;
+                if mouths(region as i32, area, &labels, &adjacent) > 1 {
+                    TopologyTag::Corridor
+                } else {
+                    TopologyTag::DeadEnd
+                }
+            }
+            _ => TopologyTag::Chokepoint,
+        };
+    }
+    for region in 0..regions.len() {
+        regions[region].cut = is_cut(&regions, region);
+    }
+    (labels, regions)
+}
+
+/// Nodes whose own free disc overlaps a disc wider than `narrow` that fits
+/// in the walkable space, centered on a node with that much clearance, so the
+/// rims and corners along the walls of a room still count as open.
+fn covered(
+    walkable: &[bool],
+    clearance: &[f32],
+    positions: &[Vec2],
+    adjacent: &[Vec<usize>],
+    narrow: f32,
+) -> Vec<bool> {
+    // Spreads from the disc centers, widest first, keeping for each node the
+    // center whose disc reaches farthest past it.
+    let mut reach = vec![f32::NEG_INFINITY; walkable.len()];
+    let mut center = vec![usize::MAX; walkable.len()];
+    let mut queue = BinaryHeap::new();
+    for index in 0..walkable.len() {
+        if walkable[index] && clearance[index] > narrow {
+            reach[index] = clearance[index];
+            center[index] = index;
+            queue.push(OpenNode {
+                cost: -clearance[index],
+                index,
+            });
+        }
+    }
+    while let Some(OpenNode { cost, index }) = queue.pop() {
+        if -cost < reach[index] {
+            continue;
+        }
+        let from = center[index];
+        for &next in &adjacent[index] {
+            let left = clearance[from] - positions[from].distance(positions[next]);
+            if left + clearance[next] > 0.0 && left > reach[next] {
+                reach[next] = left;
+                center[next] = from;
+                queue.push(OpenNode {
+                    cost: -left,
+                    index: next,
+                });
+            }
+        }
+    }
+    reach
+        .iter()
+        .zip(clearance)
+        .map(|(&left, &own)| left + own > 0.0)
+        .collect()
+}
+
+/// Connected components of the walkable nodes, joining neighbors where `kind`
+/// agrees. Returns each node's component, -1 for blocked ones, and the
+/// component sizes.
+fn components(
+    walkable: &[bool],
+    adjacent: &[Vec<usize>],
+    kind: impl Fn(usize) -> bool,
+) -> (Vec<i32>, Vec<u32>) {
+    let mut labels = vec![-1; walkable.len()];
+    let mut sizes = Vec::new();
+    let mut queue = VecDeque::new();
+    for start in 0..walkable.len() {
+        if !walkable[start] || labels[start] >= 0 {
+            continue;
+        }
+        let label = sizes.len() as i32;
+        let mut size = 0;
+        labels[start] = label;
+        queue.push_back(start);
+        while let Some(index) = queue.pop_front() {
+            size += 1;
+            for &next in &adjacent[index] {
+                if labels[next] < 0 && kind(next) == kind(start) {
+                    labels[next] = label;
+                    queue.push_back(next);
+                }
+            }
+        }
+        sizes.push(size);
+    }
+    (labels, sizes)
+}
+
+/// Separate stretches along which passage `region` meets open `area`.
+fn mouths(region: i32, area: i32, labels: &[i32], adjacent: &[Vec<usize>]) -> u32 {
+    let touching = |index: usize| {
+        labels[index] == region && adjacent[index].iter().any(|&next| labels[next] == area)
+    };
+    let mut seen = vec![false; labels.len()];
+    let mut count = 0;
+    let mut queue = VecDeque::new();
+    for start in 0..labels.len() {
+        if seen[start] || !touching(start) {
+            continue;
+        }
+        count += 1;
+        seen[start] = true;
+        queue.push_back(start);
+        while let Some(index) = queue.pop_front() {
+            for &next in &adjacent[index] {
+                if !seen[next] && touching(next) {
+                    seen[next] = true;
+                    queue.push_back(next);
+                }
+            }
+        }
+    }
+    count
+}
+
+/// Whether removing `region` from the region graph leaves some of its
+/// neighbors unable to reach the others.
+fn is_cut(regions: &[Region], region: usize) -> bool {
+    let mut around = regions[region].neighbors.iter().map(|&n| n as usize);
+    let Some(first) = around.next() else {
+        return false;
+    };
+    let mut seen = vec![false; regions.len()];
+    seen[region] = true;
+    seen[first] = true;
+    let mut queue = VecDeque::from([first]);
+    while let Some(current) = queue.pop_front() {
+        for &next in &regions[current].neighbors {
+            if !seen[next as usize] {
+                seen[next as usize] = true;
+                queue.push_back(next as usize);
+            }
+        }
+    }
+    around.any(|neighbor| !seen[neighbor])
+}
+
+fn centroid([a, b, c]: [Point; 3]) -> Point {
+    [
+        (a[0] + b[0] + c[0]) / 3.0,
+        (a[1] + b[1] + c[1]) / 3.0,
+        (a[2] + b[2] + c[2]) / 3.0,
+    ]
+}
+
+fn flat(point: Point) -> Vec2 {
+    Vec2::new(point[0], point[2])
+}
+
+fn distance_to_segment(point: Vec2, a: Vec2, b: Vec2) -> f32 {
+    let ab = b - a;
+    let length = ab.length_squared();
+    let t = if length > 0.0 {
+        ((point - a).dot(ab) / length).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    (a + ab * t).distance(point)
+}
@@ -0,0 +1,649 @@
+use std::collections::{BTreeMap, VecDeque};
+
+use wasm_bindgen::prelude::*;
+
+use crate::error::Error;
+use crate::events::{AiEvent, EventKind};
+use crate::math::Vec2;
+use crate::random::Rng;
+use crate::search;
+
+/// How far short of its stopping point, and how slowly, a vehicle must be
+/// moving to count as arrived.
+const ARRIVE_DISTANCE: f32 = 0.5;
+const ARRIVE_SPEED: f32 = 0.5;
+
+#[derive(Clone, Debug)]
+struct Road {
+    from: usize,
+    to: usize,
+    lanes: u32,
+    lane_width: f32,
+    speed_limit: f32,
+    length: f32,
+    /// Whether vehicles give way at the end of this road.
+    yields: bool,
+}
+
+/// A directed road network for vehicle traffic: junctions placed on the
+/// plane joined by one-way roads with lanes and speed limits. A two-way
+/// street is two roads between the same junctions; lanes are laid out to the
+/// right of the direction of travel, so the two halves do not overlap.
+///
+/// Junctions joining more than two neighbors are intersections, which
+/// `Traffic` lets one approach at a time cross and where roads marked with
+/// `set_yield` give way to the others.
+#[wasm_bindgen]
+#[derive(Clone, Debug, Default)]
+pub struct RoadNetwork {
+    nodes: Vec<Vec2>,
+    roads: Vec<Road>,
+    outgoing: Vec<Vec<usize>>,
+    incoming: Vec<Vec<usize>>,
+    /// Lays lanes out left of the direction of travel instead, for traffic
+    /// that drives on the left.
+    pub drive_on_left: bool,
+}
+
+#[wasm_bindgen]
+impl RoadNetwork {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> RoadNetwork {
+        RoadNetwork::default()
+    }
+
+    /// Adds a junction and returns its id.
+    pub fn add_node(&mut self, x: f32, y: f32) -> u32 {
+        self.nodes.push(Vec2::new(x, y));
+        self.outgoing.push(Vec::new());
+        self.incoming.push(Vec::new());
+        self.nodes.len() as u32 - 1
+    }
+
+    /// Adds a one-way road from junction `from` to `to` with `lanes` lanes
+    /// `lane_width` wide and returns its id. `speed_limit` is in distance
+    /// units per second.
+    pub fn add_road(
+        &mut self,
+        from: u32,
+        to: u32,
+        lanes: u32,
+        lane_width: f32,
+        speed_limit: f32,
+    ) -> Result<u32, Error> {
+        for node in [from, to] {
+            if node as usize >= self.nodes.len() {
+                return Err(Error::InvalidInput(format!("node {} does not exist", node)));
+            }
+        }
+        if from == to {
+            return Err(Error::InvalidInput(
+                "a road needs two different nodes".into(),
+            ));
+        }
+        if lanes == 0 || lane_width.is_nan() || lane_width <= 0.0 {
+            return Err(Error::InvalidInput(format!(
+                "a road needs at least one lane of positive width, got {} of {}",
+                lanes, lane_width
+            )));
+        }
+        if !(speed_limit > 0.0 && speed_limit.is_finite()) {
+            return Err(Error::InvalidInput(format!(
+                "speed limit must be positive and finite, got {}",
+                speed_limit
+            )));
+        }
+        let (from, to) = (from as usize, to as usize);
+        let id = self.roads.len();
+        self.roads.push(Road {
+            from,
+            to,
+            lanes,
+            lane_width,
+            speed_limit,
+            length: self.nodes[from].distance(self.nodes[to]),
+            yields: false,
+        });
+        self.outgoing[from].push(id);
+        self.incoming[to].push(id);
+        Ok(id as u32)
+    }
+
+    /// Makes vehicles at the end of `road` give way to those on the roads
+    /// into the same intersection that do not yield, like a stop or yield
+    /// sign. Returns false for an unknown road.
+    pub fn set_yield(&mut self, road: u32, yields: bool) -> bool {
+        let Some(road) = self.roads.get_mut(road as usize) else {
+            return false;
+        };
+        road.yields = yields;
+        true
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn node_count(&self) -> u32 {
+        self.nodes.len() as u32
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn road_count(&self) -> u32 {
+        self.roads.len() as u32
+    }
+
+    /// Length of `road`, 0 for an unknown one.
+    pub fn road_length(&self, road: u32) -> f32 {
+        self.roads
+            .get(road as usize)
+            .map_or(0.0, |road| road.length)
+    }
+
+    /// Point `distance` along the middle of `lane` of `road`, lanes counting
+    /// outward from the road's centerline.
+    pub fn lane_point(&self, road: u32, lane: u32, distance: f32) -> Vec2 {
+        self.roads
+            .get(road as usize)
+            .map_or(Vec2::ZERO, |road| self.point_on(road, lane, distance))
+    }
+
+    /// The fastest roads from junction `from` to junction `to` at their speed
+    /// limits, in driving order, or an empty array when there is no way.
+    pub fn find_route(&self, from: u32, to: u32) -> Vec<u32> {
+        self.route(from as usize, to as usize)
+            .map_or_else(Vec::new, |roads| {
+                roads.into_iter().map(|road| road as u32).collect()
+            })
+    }
+}
+
+impl RoadNetwork {
+    fn point_on(&self, road: &Road, lane: u32, distance: f32) -> Vec2 {
+        let (start, end) = (self.nodes[road.from], self.nodes[road.to]);
+        let direction = (end - start).normalize();
+        // `perp` turns left; lanes go right of travel unless driving on the left.
+        let side = if self.drive_on_left {
+            direction.perp()
+        } else {
+            -direction.perp()
+        };
+        let offset = (lane.min(road.lanes - 1) as f32 + 0.5) * road.lane_width;
+        start + direction * distance.clamp(0.0, road.length) + side * offset
+    }
+
+    fn direction(&self, road: &Road) -> Vec2 {
+        (self.nodes[road.to] - self.nodes[road.from]).normalize()
+    }
+
+    /// Whether junction `node` has more than two neighbors.
+    fn is_intersection(&self, node: usize) -> bool {
+        let mut neighbors: Vec<usize> = self.outgoing[node]
+            .iter()
+            .map(|&road| self.roads[road].to)
+            .chain(
+                self.incoming[node]
+                    .iter()
+                    .map(|&road| self.roads[road].from),
+            )
+            .collect();
+        neighbors.sort_unstable();
+        neighbors.dedup();
+        neighbors.len() > 2
+    }
+
+    fn route(&self, from: usize, to: usize) -> Option<Vec<usize>> {
+        if from >= self.nodes.len() || to >= self.nodes.len() {
+            return None;
+        }
+        let fastest = self
+            .roads
+            .iter()
+            .map(|road| road.speed_limit)
+            .fold(0.0, f32::max);
+        let nodes = search::astar(
+            self.nodes.len(),
+            from,
+            to,
+            |node, out| {
+                out.extend(self.outgoing[node].iter().map(|&road| {
+                    let road = &self.roads[road];
+                    (road.to, road.length / road.speed_limit)
+                }))
+            },
+            |node| self.nodes[node].distance(self.nodes[to]) / fastest,
+        )?;
+        Some(
+            nodes
+                .windows(2)
+                .filter_map(|pair| self.fastest_road(pair[0], pair[1]))
+                .collect(),
+        )
+    }
+
+    fn fastest_road(&self, from: usize, to: usize) -> Option<usize> {
+        self.outgoing[from]
+            .iter()
+            .copied()
+            .filter(|&road| self.roads[road].to == to)
+            .min_by(|&a, &b| {
+                let time = |road: usize| self.roads[road].length / self.roads[road].speed_limit;
+                time(a).total_cmp(&time(b))
+            })
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Vehicle {
+    road: usize,
+    lane: u32,
+    /// Distance of the vehicle's front along its road.
+    distance: f32,
+    speed: f32,
+    max_speed: f32,
+    length: f32,
+    /// Road the vehicle came from across the junction at the start of its
+    /// road, while it is still clearing that junction.
+    entered_from: Option<usize>,
+    /// Road to take at the end of this one, if any.
+    next: Option<usize>,
+    route: VecDeque<usize>,
+    destination: Option<usize>,
+    /// Whether the vehicle picks roads at random when it has no destination.
+    wandering: bool,
+    /// Whether the vehicle must stop at the end of its road, for the
+    /// intersection ahead or for want of a road on.
+    waiting: bool,
+}
+
+/// Vehicles driving a snapshot of a `RoadNetwork`: each keeps its lane,
+/// follows the vehicle ahead with the intelligent driver model, stops for
+/// intersections other vehicles are crossing or roads it must give way to,
+/// and either drives a route to its destination or wanders at random.
+///
+/// Reaching a destination is reported as a `PathComplete` event with the
+/// vehicle as `subject`, after which the vehicle waits at the end of its last
+/// road for a new destination or `wander`.
+#[wasm_bindgen]
+#[derive(Clone, Debug)]
+pub struct Traffic {
+    network: RoadNetwork,
+    vehicles: BTreeMap<u32, Vehicle>,
+    next_id: u32,
+    /// Acceleration vehicles pull away with.
+    pub acceleration: f32,
+    /// Deceleration vehicles brake with in comfort; they brake harder when
+    /// they must.
+    pub braking: f32,
+    /// Bumper-to-bumper gap vehicles keep when stopped.
+    pub min_gap: f32,
+    /// Seconds of travel vehicles keep between them and the vehicle ahead.
+    pub headway: f32,
+    /// Distance from an intersection within which vehicles on roads without a
+    /// yield sign claim right of way.
+    pub yield_distance: f32,
+    rng: Rng,
+    /// Per-update scratch kept so that updates stop allocating.
+    order: Vec<(usize, u32, f32, u32)>,
+    accelerations: Vec<(u32, f32, bool)>,
+    events: Vec<AiEvent>,
+}
+
+#[wasm_bindgen]
+impl Traffic {
+    /// Traffic on a copy of `network`, picking wandering vehicles' turns with
+    /// random `seed`.
+    #[wasm_bindgen(constructor)]
+    pub fn new(network: &RoadNetwork, seed: u64) -> Traffic {
+        Traffic {
+            network: network.clone(),
+            vehicles: BTreeMap::new(),
+            next_id: 0,
+            acceleration: 2.0,
+            braking: 3.0,
+            min_gap: 2.0,
+            headway: 1.5,
+            yield_distance: 30.0,
+            rng: Rng::new(seed),
+            order: Vec::new(),
+            accelerations: Vec::new(),
+            events: Vec::new(),
+        }
+    }
+
+    /// Adds a wandering vehicle `length` long in `lane` of `road`, its front
+    /// `distance` along it, and returns its id.
+    pub fn add_vehicle(
+        &mut self,
+        road: u32,
+        lane: u32,
+        distance: f32,
+        max_speed: f32,
+        length: f32,
+    ) -> Result<u32, Error> {
+        let Some(info) = self.network.roads.get(road as usize) else {
+            return Err(Error::InvalidInput(format!("road {} does not exist", road)));
+        };
+        if lane >= info.lanes {
+            return Err(Error::InvalidInput(format!(
+                "road {} has {} lanes, got lane {}",
+                road, info.lanes, lane
+            )));
+        }
+        if !(max_speed > 0.0 && length > 0.0) {
+            return Err(Error::InvalidInput(format!(
+                "max speed and length must be positive, got {} and {}",
+                max_speed, length
+            )));
+        }
+        let id = self.next_id;
+        self.next_id = self.next_id.wrapping_add(1);
+        let mut vehicle = Vehicle {
+            road: road as usize,
+            lane,
+            distance: distance.clamp(0.0, info.length),
+            speed: 0.0,
+            max_speed,
+            length,
+            entered_from: None,
+            next: None,
+            route: VecDeque::new(),
+            destination: None,
+            wandering: true,
+            waiting: false,
+        };
+        vehicle.next = self.pick_next(&vehicle);
+        self.vehicles.insert(id, vehicle);
+        Ok(id)
+    }
+
+    pub fn remove_vehicle(&mut self, id: u32) -> bool {
+        self.vehicles.remove(&id).is_some()
+    }
+
+    /// Sends a vehicle along the fastest route to junction `node`. Returns
+    /// false when the vehicle is unknown or no road leads there, leaving it
+    /// as it was.
+    pub fn set_destination(&mut self, id: u32, node: u32) -> bool {
+        let Some(vehicle) = self.vehicles.get(&id) else {
+            return false;
+        };
+        let end = self.network.roads[vehicle.road].to;
+        let Some(route) = self.network.route(end, node as usize) else {
+            return false;
+        };
+        let vehicle = self.vehicles.get_mut(&id).unwrap();
+        vehicle.route = route.into();
+        vehicle.destination = Some(node as usize);
+        vehicle.wandering = false;
+        vehicle.next = vehicle.route.pop_front();
+        true
+    }
+
+    /// Lets a vehicle drive on at random, dropping its destination.
+    pub fn wander(&mut self, id: u32) -> bool {
+        let Some(mut vehicle) = self.vehicles.remove(&id) else {
+            return false;
+        };
+        vehicle.route.clear();
+        vehicle.destination = None;
+        vehicle.wandering = true;
+        vehicle.next = self.pick_next(&vehicle);
+        self.vehicles.insert(id, vehicle);
+        true
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn len(&self) -> u32 {
+        self.vehicles.len() as u32
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.vehicles.is_empty()
+    }
+
+    /// Vehicle ids in the order of the other flat arrays.
+    pub fn ids(&self) -> Vec<u32> {
+        self.vehicles.keys().copied().collect()
+    }
+
+    /// Flat `[x0, y0, x1, y1, ...]` positions of the vehicles' fronts, in
+    /// their lanes.
+    pub fn positions(&self) -> Vec<f32> {
+        self.vehicles
+            .values()
+            .flat_map(|vehicle| {
+                let road = &self.network.roads[vehicle.road];
+                let point = self.network.point_on(road, vehicle.lane, vehicle.distance);
+                [point.x, point.y]
+            })
+            .collect()
+    }
+
+    /// Flat `[x0, y0, ...]` unit directions the vehicles face.
+    pub fn headings(&self) -> Vec<f32> {
+        self.vehicles
+            .values()
+            .flat_map(|vehicle| {
+                let direction = self.network.direction(&self.network.roads[vehicle.road]);
+                [direction.x, direction.y]
+            })
+            .collect()
+    }
+
+    pub fn speeds(&self) -> Vec<f32> {
+        self.vehicles
+            .values()
+            .map(|vehicle| vehicle.speed)
+            .collect()
+    }
+
+    /// Road a vehicle is on, or `u32::MAX` for an unknown vehicle.
+    pub fn vehicle_road(&self, id: u32) -> u32 {
+        self.vehicles
+            .get(&id)
+            .map_or(u32::MAX, |vehicle| vehicle.road as u32)
+    }
+
+    /// Whether a vehicle must stop at the end of its road, held by the rules
+    /// of the intersection ahead or at the end of its route.
+    pub fn is_waiting(&self, id: u32) -> bool {
+        self.vehicles
+            .get(&id)
+            .is_some_and(|vehicle| vehicle.waiting)
+    }
+
+    /// Vehicles that reached their destination during the last `update`, as
+    /// `PathComplete` events.
+    pub fn events(&self) -> Vec<AiEvent> {
+        self.events.clone()
+    }
+
+    /// Advances every vehicle by `dt` seconds.
+    pub fn update(&mut self, dt: f32) {
+        self.events.clear();
+        if dt <= 0.0 {
+            return;
+        }
+        let mut order = std::mem::take(&mut self.order);
+        order.clear();
+        order.extend(
+            self.vehicles
+                .iter()
+                .map(|(&id, vehicle)| (vehicle.road, vehicle.lane, vehicle.distance, id)),
+        );
+        order.sort_by(|a, b| (a.0, a.1).cmp(&(b.0, b.1)).then(a.2.total_cmp(&b.2)));
+
+        let mut accelerations = std::mem::take(&mut self.accelerations);
+        accelerations.clear();
+        for (index, &(road, lane, distance, id)) in order.iter().enumerate() {
+            let vehicle = &self.vehicles[&id];
+            let info = &self.network.roads[road];
+            let remaining = info.length - distance;
+            // The nearest obstacle ahead as (gap, its speed): the next vehicle
+            // in the lane, the first on the road after, or the end of the road
+            // when the vehicle must stop there.
+            let mut ahead = order
+                .get(index + 1)
+                .filter(|next| next.0 == road && next.1 == lane)
+                .map(|next| {
+                    let leader = &self.vehicles[&next.3];
+                    (next.2 - leader.length - distance, leader.speed)
+                });
+            let stop = self.must_stop(id, vehicle);
+            if stop {
+                ahead = Some(ahead.map_or((remaining, 0.0), |(gap, speed)| {
+                    if gap < remaining {
+                        (gap, speed)
+                    } else {
+                        (remaining, 0.0)
+                    }
+                }));
+            } else if ahead.is_none() {
+                ahead = vehicle.next.and_then(|next| {
+                    let lane = lane.min(self.network.roads[next].lanes - 1);
+                    let first = order.partition_point(|entry| (entry.0, entry.1) < (next, lane));
+                    order
+                        .get(first)
+                        .filter(|entry| entry.0 == next && entry.1 == lane)
+                        .map(|entry| {
+                            let leader = &self.vehicles[&entry.3];
+                            (remaining + entry.2 - leader.length, leader.speed)
+                        })
+                });
+            }
+            let desired = vehicle.max_speed.min(info.speed_limit);
+            let acceleration = self.driver_acceleration(vehicle, desired, ahead);
+            accelerations.push((id, acceleration, stop));
+        }
+
+        for &(id, acceleration, stop) in &accelerations {
+            let mut vehicle = self.vehicles.remove(&id).unwrap();
+            vehicle.waiting = stop;
+            vehicle.speed = (vehicle.speed + acceleration * dt).max(0.0);
+            vehicle.distance += vehicle.speed * dt;
+            self.advance(id, &mut vehicle, stop);
+            self.vehicles.insert(id, vehicle);
+        }
+        self.order = order;
+        self.accelerations = accelerations;
+    }
+}
+
+impl Traffic {
+    /// Acceleration of the intelligent driver model toward `desired` speed,
+    /// with the gap to and speed of whatever is `ahead`.
+    fn driver_acceleration(
+        &self,
+        vehicle: &Vehicle,
+        desired: f32,
+        ahead: Option<(f32, f32)>,
+    ) -> f32 {
+        let free = 1.0 - (vehicle.speed / desired.max(f32::EPSILON)).powi(4);
+        let Some((gap, speed)) = ahead else {
+            return self.acceleration * free;
+        };
+        let closing = vehicle.speed - speed;
+        let wanted = self.min_gap
+            + (vehicle.speed * self.headway
+                + vehicle.speed * closing / (2.0 * (self.acceleration * self.braking).sqrt()))
+            .max(0.0);
+        self.acceleration * (free - (wanted / gap.max(0.01)).powi(2))
+    }
+
+    /// Whether `vehicle` must stop at the end of its road: because a vehicle
+    /// from another approach is crossing the intersection there, or because
+    /// its road yields and one on a road that does not is near.
+    fn must_stop(&self, id: u32, vehicle: &Vehicle) -> bool {
+        let road = &self.network.roads[vehicle.road];
+        let remaining = road.length - vehicle.distance;
+        if vehicle.next.is_none() {
+            return true;
+        }
+        if remaining > self.yield_distance || !self.network.is_intersection(road.to) {
+            return false;
+        }
+        let node = road.to;
+        let crossing = self.vehicles.iter().any(|(&other, candidate)| {
+            other != id
+                && self.network.roads[candidate.road].from == node
+                && candidate
+                    .entered_from
+                    .is_some_and(|from| from != vehicle.road)
+        });
+        if crossing {
+            return true;
+        }
+        road.yields
+            && self.vehicles.iter().any(|(&other, candidate)| {
+                let approach = &self.network.roads[candidate.road];
+                other != id
+                    && approach.to == node
+                    && !approach.yields
+                    && candidate.next.is_some()
+                    && approach.length - candidate.distance <= self.yield_distance
+            })
+    }
+
+    /// Moves `vehicle` on to its next road when it has passed the end of its
+    /// current one, or holds it at the end.
+    fn advance(&mut self, id: u32, vehicle: &mut Vehicle, stop: bool) {
+        if vehicle.distance >= vehicle.length + self.min_gap {
+            vehicle.entered_from = None;
+        }
+        let road = &self.network.roads[vehicle.road];
+        // Vehicles ease up to where they must stop, so one creeping the last
+        // stretch of its final road has arrived.
+        if vehicle.next.is_none()
+            && vehicle.destination == Some(road.to)
+            && road.length - vehicle.distance <= self.min_gap + ARRIVE_DISTANCE
+            && vehicle.speed <= ARRIVE_SPEED
+        {
+            vehicle.destination = None;
+            self.events
+                .push(AiEvent::new(EventKind::PathComplete, id, 0));
+        }
+        if vehicle.distance < road.length {
+            return;
+        }
+        let next = match vehicle.next {
+            Some(next) if !stop => next,
+            _ => {
+                vehicle.distance = road.length;
+                vehicle.speed = 0.0;
+                return;
+            }
+        };
+        let overshoot = vehicle.distance - road.length;
+        let road = &self.network.roads[next];
+        vehicle.entered_from = Some(vehicle.road);
+        vehicle.road = next;
+        vehicle.lane = vehicle.lane.min(road.lanes - 1);
+        vehicle.distance = overshoot.min(road.length);
+        vehicle.next = match vehicle.route.pop_front() {
+            Some(next) => Some(next),
+            None => self.pick_next(vehicle),
+        };
+    }
+
+    /// A random road out of the end of `vehicle`'s road for a wandering
+    /// vehicle, avoiding a U-turn while there is another way.
+    fn pick_next(&mut self, vehicle: &Vehicle) -> Option<usize> {
+        if !vehicle.wandering {
+            return None;
+        }
+        let road = &self.network.roads[vehicle.road];
+        let choices = &self.network.outgoing[road.to];
+        let onward: Vec<usize> = choices
+            .iter()
+            .copied()
+            .filter(|&next| self.network.roads[next].to != road.from)
+            .collect();
+        let options = if onward.is_empty() {
+            choices.clone()
+        } else {
+            onward
+        };
+        if options.is_empty() {
+            return None;
+        }
+        Some(options[self.rng.next_index(options.len() as u32) as usize])
+    }
+}
@@ -0,0 +1,309 @@
+use std::collections::HashMap;
+
+use js_sys::Function;
+use wasm_bindgen::prelude::*;
+
+use crate::behavior_tree::{BehaviorTree, BehaviorTreeBuilder, Leaf, Status};
+use crate::error::Error;
+use crate::json::Json;
+use crate::xml::Element;
+
+type Factory = Box<dyn Fn(&[(String, Json)]) -> Leaf>;
+
+struct Entry {
+    factory: Factory,
+    condition: bool,
+}
+
+/// Leaves that tree documents refer to by name. A leaf is built for every node
+/// that names it, from that node's parameters.
+#[wasm_bindgen]
+#[derive(Default)]
+pub struct LeafRegistry {
+    leaves: HashMap<String, Entry>,
+}
+
+#[wasm_bindgen]
+impl LeafRegistry {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> LeafRegistry {
+        LeafRegistry::default()
+    }
+
+    /// Registers an action leaf. Each tick the callback is called with the
+    /// node's parameters as an object and returns a `Status` or a boolean.
+    pub fn register_action(&mut self, name: &str, callback: Function) {
+        self.insert(name, false, js_factory(callback));
+    }
+
+    /// Registers a condition leaf, called like an action and returning a boolean.
+    pub fn register_condition(&mut self, name: &str, callback: Function) {
+        self.insert(name, true, js_factory(callback));
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.leaves.contains_key(name)
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn len(&self) -> u32 {
+        self.leaves.len() as u32
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.leaves.is_empty()
+    }
+}
+
+impl LeafRegistry {
+    /// Registers an action implemented in Rust, made by `factory` from the
+    /// node's parameters, with values other than strings in their JSON form.
+    pub fn register_action_fn(
+        &mut self,
+        name: &str,
+        factory: impl Fn(&HashMap<String, String>) -> Leaf + 'static,
+    ) {
+        self.insert(
+            name,
+            false,
+            Box::new(move |params| factory(&text_params(params))),
+        );
+    }
+
+    /// Registers a condition implemented in Rust.
+    pub fn register_condition_fn(
+        &mut self,
+        name: &str,
+        factory: impl Fn(&HashMap<String, String>) -> Box<dyn FnMut() -> bool> + 'static,
+    ) {
+        self.insert(
+            name,
+            true,
+            Box::new(move |params| {
+                let mut leaf = factory(&text_params(params));
+                Box::new(move || {
+                    if leaf() {
+                        Status::Success
+                    } else {
+                        Status::Failure
+                    }
+                })
+            }),
+        );
+    }
+
+    fn insert(&mut self, name: &str, condition: bool, factory: Factory) {
+        self.leaves
+            .insert(name.to_string(), Entry { factory, condition });
+    }
+}
+
+fn js_factory(callback: Function) -> Factory {
+    Box::new(move |params| {
+        let callback = callback.clone();
+        let params = js_sys::JSON::parse(&Json::Object(params.to_vec()).to_string())
+            .unwrap_or(JsValue::UNDEFINED);
+        Box::new(move || Status::from_js(callback.call1(&JsValue::NULL, &params)))
+    })
+}
+
+fn text_params(params: &[(String, Json)]) -> HashMap<String, String> {
+    params
+        .iter()
+        .map(|(key, value)| {
+            let text = match value {
+                Json::String(text) => text.clone(),
+                other => other.to_string(),
+            };
+            (key.clone(), text)
+        })
+        .collect()
+}
+
+/// A node of a tree document, whichever format it came from.
+struct Spec {
+    kind: String,
+    name: Option<String>,
+    params: Vec<(String, Json)>,
+    children: Vec<Spec>,
+}
+
+impl Spec {
+    fn from_json(json: &Json) -> Result<Spec, Error> {
+        let children = match json.get("children") {
+            Some(_) => json
+                .field("children", Json::as_array)?
+                .iter()
+                .map(Spec::from_json)
+                .collect::<Result<_, _>>()?,
+            None => Vec::new(),
+        };
+        let params = match json.get("params") {
+            Some(Json::Object(params)) => params.clone(),
+            Some(_) => return Err(Error::InvalidInput("'params' must be an object".into())),
+            None => Vec::new(),
+        };
+        Ok(Spec {
+            kind: json.field("type", Json::as_str)?.to_string(),
+            name: match json.get("name") {
+                Some(_) => Some(json.field("name", Json::as_str)?.to_string()),
+                None => None,
+            },
+            params,
+            children,
+        })
+    }
+
+    fn from_xml(element: &Element) -> Result<Spec, Error> {
+        // `<Action ID="Attack"/>` and `<Attack/>` name the same leaf.
+        let generic = matches!(
+            element.name.to_ascii_lowercase().as_str(),
+            "action" | "condition"
+        );
+        let kind = match element.attribute("ID") {
+            Some(id) if generic => id.to_string(),
+            _ if generic => {
+                return Err(Error::InvalidInput(format!(
+                    "<{}> needs an ID attribute",
+                    element.name
+                )))
+            }
+            _ => element.name.clone(),
+        };
+        Ok(Spec {
+            kind,
+            name: element.attribute("name").map(str::to_string),
+            params: element
+                .attributes
+                .iter()
+                .filter(|(key, _)| key != "name" && !(generic && key == "ID"))
+                .map(|(key, value)| (key.clone(), Json::String(value.clone())))
+                .collect(),
+            children: element
+                .children
+                .iter()
+                .map(Spec::from_xml)
+                .collect::<Result<_, _>>()?,
+        })
+    }
+
+    /// A whole-number parameter, written as a number or, from XML, as text.
+    fn count(&self, key: &str, default: u32) -> Result<u32, Error> {
+        let Some((_, value)) = self.params.iter().find(|(name, _)| name == key) else {
+            return Ok(default);
+        };
+        let number = match value {
+            Json::Number(number) => Some(*number),
+            Json::String(text) => text.trim().parse().ok(),
+            _ => None,
+        };
+        match number {
+            Some(number) if number >= 0.0 && number.fract() == 0.0 => Ok(number as u32),
+            _ => Err(Error::InvalidInput(format!(
+                "'{}' of '{}' must be a whole number",
+                key, self.kind
+            ))),
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl BehaviorTree {
+    /// Builds a tree from a JSON document of nodes such as
+    /// `{"type": "selector", "children": [{"type": "sequence", "children":
+    /// [{"type": "see_enemy"}, {"type": "attack", "params": {"damage": 5}}]},
+    /// {"type": "patrol"}]}`. A `type` is `sequence`, `selector` (or
+    /// `fallback`), `parallel` with a `success_threshold` parameter (all
+    /// children by default), `inverter`, `repeater` (or `repeat`) with a
+    /// `count` parameter, `expression` with an `Expression` in an `expr`
+    /// parameter, or the name of a leaf in `leaves`. Nodes may carry a
+    /// `name`, which defaults to their type, and get ids in document order.
+    pub fn from_json(json: &str, leaves: &LeafRegistry) -> Result<BehaviorTree, Error> {
+        build(&Spec::from_json(&Json::parse(json)?)?, leaves)
+    }
+
+    /// Builds a tree from XML as written by BehaviorTree.CPP style editors, e.g.
+    /// `<root main_tree_to_execute="Main"><BehaviorTree ID="Main"><Fallback>
+    /// <Sequence><SeeEnemy/><Attack damage="5"/></Sequence><Patrol/></Fallback>
+    /// </BehaviorTree></root>`. Node types are the ones `from_json` knows, in
+    /// any case; leaves are either elements named after them or `<Action ID=..>`
+    /// and `<Condition ID=..>`, and their other attributes become parameters.
+    pub fn from_xml(xml: &str, leaves: &LeafRegistry) -> Result<BehaviorTree, Error> {
+        let document = Element::parse(xml)?;
+        let mut tree = &document;
+        if tree.name == "root" {
+            let main = tree.attribute("main_tree_to_execute");
+            tree = tree
+                .children
+                .iter()
+                .filter(|child| child.name == "BehaviorTree")
+                .find(|child| main.is_none() || child.attribute("ID") == main)
+                .ok_or_else(|| Error::InvalidInput("no BehaviorTree to execute".into()))?;
+        }
+        if tree.name == "BehaviorTree" {
+            let [root] = tree.children.as_slice() else {
+                return Err(Error::InvalidInput(
+                    "a BehaviorTree must hold exactly one root node".into(),
+                ));
+            };
+            tree = root;
+        }
+        build(&Spec::from_xml(tree)?, leaves)
+    }
+}
+
+fn build(spec: &Spec, leaves: &LeafRegistry) -> Result<BehaviorTree, Error> {
+    let mut builder = BehaviorTreeBuilder::new();
+    add(&mut builder, spec, leaves)?;
+    builder.build()
+}
+
+fn add(builder: &mut BehaviorTreeBuilder, spec: &Spec, leaves: &LeafRegistry) -> Result<(), Error> {
+    let name = spec.name.as_deref().unwrap_or(&spec.kind);
+    match spec.kind.to_ascii_lowercase().as_str() {
+        "sequence" => builder.sequence(name),
+        "selector" | "fallback" => builder.selector(name),
+        "parallel" => builder.parallel(
+            name,
+            spec.count("success_threshold", spec.children.len() as u32)?,
+        ),
+        "inverter" => builder.inverter(name),
+        "repeater" | "repeat" => builder.repeater(name, spec.count("count", 0)?),
+        "expression" | "scriptcondition" => {
+            let source = spec
+                .params
+                .iter()
+                .find(|(key, _)| key == "expr" || key == "code")
+                .and_then(|(_, value)| value.as_str())
+                .ok_or_else(|| {
+                    Error::InvalidInput(format!("expression '{}' needs an 'expr' parameter", name))
+                })?;
+            if !spec.children.is_empty() {
+                return Err(Error::InvalidInput(format!(
+                    "leaf '{}' cannot have children",
+                    name
+                )));
+            }
+            builder.expression(name, source);
+            return Ok(());
+        }
+        _ => {
+            let entry = leaves.leaves.get(&spec.kind).ok_or_else(|| {
+                Error::InvalidInput(format!("no leaf registered as '{}'", spec.kind))
+            })?;
+            if !spec.children.is_empty() {
+                return Err(Error::InvalidInput(format!(
+                    "leaf '{}' cannot have children",
+                    name
+                )));
+            }
+            builder.leaf(name, (entry.factory)(&spec.params), entry.condition);
+            return Ok(());
+        }
+    };
+    for child in &spec.children {
+        add(builder, child, leaves)?;
+    }
+    builder.end();
+    Ok(())
+}
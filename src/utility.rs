@@ -0,0 +1,193 @@
+use js_sys::{Object, Reflect};
+use wasm_bindgen::prelude::*;
+
+use crate::blackboard::Blackboard;
+use crate::curves::Curve;
+use crate::error::Error;
+use crate::expression::{Expression, Inputs, NumberInputs};
+
+enum Input {
+    Key(String),
+    Expression(Box<Expression>),
+}
+
+struct Consideration {
+    input: Input,
+    min: f32,
+    max: f32,
+    curve: Curve,
+}
+
+impl Consideration {
+    fn score(&mut self, inputs: &dyn Inputs, now: f64) -> f32 {
+        let input = match &mut self.input {
+            Input::Key(key) => inputs.number(key).unwrap_or(0.0),
+            Input::Expression(expression) => expression.run(inputs, now),
+        };
+        self.curve.evaluate_in(input as f32, self.min, self.max)
+    }
+}
+
+struct Action {
+    name: String,
+    weight: f32,
+    considerations: Vec<Consideration>,
+}
+
+/// Scores actions by multiplying their considerations' curve outputs, with a
+/// compensation factor so actions with many considerations are not penalized.
+#[wasm_bindgen]
+#[derive(Default)]
+pub struct UtilityBrain {
+    actions: Vec<Action>,
+    scores: Vec<f32>,
+    /// Seconds passed to `advance`, which expression timers measure.
+    clock: f64,
+}
+
+#[wasm_bindgen]
+impl UtilityBrain {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> UtilityBrain {
+        UtilityBrain::default()
+    }
+
+    /// Registers an action and returns its id. An action without considerations
+    /// always scores its `weight`.
+    pub fn add_action(&mut self, name: &str, weight: f32) -> u32 {
+        self.actions.push(Action {
+            name: name.to_string(),
+            weight,
+            considerations: Vec::new(),
+        });
+        self.scores.push(0.0);
+        (self.actions.len() - 1) as u32
+    }
+
+    /// Adds a consideration reading input `key`, normalized from `[min, max]` to
+    /// `[0, 1]` before being fed through `curve`.
+    pub fn add_consideration(
+        &mut self,
+        action: u32,
+        key: &str,
+        min: f32,
+        max: f32,
+        curve: &Curve,
+    ) -> Result<(), Error> {
+        self.push(action, Input::Key(key.to_string()), min, max, curve)
+    }
+
+    /// Adds a consideration scoring an `Expression` such as
+    /// `max(hunger, thirst) * (threat < 2 ? 1 : 0.5)` over the inputs,
+    /// normalized and curved like a key. A blackboard's vector and text
+    /// entries are only readable through `evaluate_blackboard`.
+    pub fn add_expression_consideration(
+        &mut self,
+        action: u32,
+        source: &str,
+        min: f32,
+        max: f32,
+        curve: &Curve,
+    ) -> Result<(), Error> {
+        let expression = Expression::compile(source)?;
+        self.push(
+            action,
+            Input::Expression(Box::new(expression)),
+            min,
+            max,
+            curve,
+        )
+    }
+
+    /// Advances the clock that expression considerations' cooldowns and
+    /// timers measure by `dt` seconds.
+    pub fn advance(&mut self, dt: f32) {
+        self.clock += dt.max(0.0) as f64;
+    }
+
+    /// Scores every action against `inputs`, a plain object of numeric (or boolean)
+    /// values, and returns the best action id. Missing keys read as zero.
+    pub fn evaluate(&mut self, inputs: &Object) -> Option<u32> {
+        self.evaluate_with(|key| {
+            Reflect::get(inputs, &JsValue::from_str(key))
+                .ok()
+                .and_then(|value| {
+                    value
+                        .as_f64()
+                        .or_else(|| value.as_bool().map(|flag| if flag { 1.0 } else { 0.0 }))
+                })
+                .map(|value| value as f32)
+        })
+    }
+
+    /// Like `evaluate`, reading inputs from a blackboard's numeric and boolean
+    /// entries, and its vectors and text in expressions.
+    pub fn evaluate_blackboard(&mut self, blackboard: &Blackboard) -> Option<u32> {
+        self.score(blackboard)
+    }
+
+    /// Scores from the last `evaluate`, indexed by action id.
+    pub fn scores(&self) -> Vec<f32> {
+        self.scores.clone()
+    }
+
+    pub fn action_name(&self, id: u32) -> Option<String> {
+        self.actions
+            .get(id as usize)
+            .map(|action| action.name.clone())
+    }
+}
+
+impl UtilityBrain {
+    /// Scores every action with inputs read through `input` and returns the best one.
+    pub fn evaluate_with(&mut self, input: impl Fn(&str) -> Option<f32>) -> Option<u32> {
+        self.score(&NumberInputs(&input))
+    }
+
+    fn push(
+        &mut self,
+        action: u32,
+        input: Input,
+        min: f32,
+        max: f32,
+        curve: &Curve,
+    ) -> Result<(), Error> {
+        let action = self
+            .actions
+            .get_mut(action as usize)
+            .ok_or_else(|| Error::InvalidInput(format!("unknown action {}", action)))?;
+        action.considerations.push(Consideration {
+            input,
+            min,
+            max,
+            curve: curve.clone(),
+        });
+        Ok(())
+    }
+
+    fn score(&mut self, inputs: &dyn Inputs) -> Option<u32> {
+        let mut best = None;
+        let mut best_score = f32::NEG_INFINITY;
+        for (id, action) in self.actions.iter_mut().enumerate() {
+            let count = action.considerations.len();
+            let mut score = 1.0;
+            for consideration in &mut action.considerations {
+                score *= consideration.score(inputs, self.clock);
+                if score <= 0.0 {
+                    break;
+                }
+            }
+            if count > 0 {
+                let modification = 1.0 - 1.0 / count as f32;
+                score += (1.0 - score) * modification * score;
+            }
+            score *= action.weight;
+            self.scores[id] = score;
+            if score > best_score {
+                best_score = score;
+                best = Some(id as u32);
+            }
+        }
+        best
+    }
+}
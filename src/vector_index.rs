@@ -0,0 +1,453 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+
+use wasm_bindgen::prelude::*;
+
+use crate::error::Error;
+use crate::random::Rng;
+use crate::search::OpenNode;
+
+/// Leading bytes of `VectorIndex::to_bytes`, then a format version.
+const MAGIC: &[u8; 4] = b"LAIV";
+const VERSION: u32 = 2;
+
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Metric {
+    /// `1 - cos` of the angle between vectors, from 0 for the same direction to 2.
+    Cosine = 0,
+    /// Negated dot product, so that larger products rank nearer.
+    Dot = 1,
+    /// Euclidean distance.
+    L2 = 2,
+}
+
+/// A search result: the caller's id and its distance under the index's metric.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Neighbor {
+    pub id: u32,
+    pub distance: f32,
+}
+
+struct Node {
+    id: u32,
+    vector: Vec<f32>,
+    /// Neighbor node indices on each layer the node lives on, from layer 0 up.
+    links: Vec<Vec<u32>>,
+    removed: bool,
+}
+
+/// Approximate nearest neighbor search over fixed-length vectors, such as text
+/// embeddings, using a hierarchical navigable small world (HNSW) graph. Searches
+/// take roughly logarithmic time in the number of vectors, at the cost of
+/// occasionally missing a true neighbor; raise `ef_search` for better recall.
+///
+/// Removed vectors stay in the graph as waypoints until the index is rebuilt, so
+/// they keep searches connected but never appear in results.
+#[wasm_bindgen]
+pub struct VectorIndex {
+    dimensions: usize,
+    metric: Metric,
+    /// Links per node on the upper layers, and twice this on the bottom one.
+    pub max_links: u32,
+    /// Candidates considered while inserting; higher builds a better graph.
+    pub ef_construction: u32,
+    /// Candidates considered while searching; higher finds more true neighbors.
+    pub ef_search: u32,
+    nodes: Vec<Node>,
+    ids: HashMap<u32, usize>,
+    entry: Option<usize>,
+    rng: Rng,
+}
+
+#[wasm_bindgen]
+impl VectorIndex {
+    #[wasm_bindgen(constructor)]
+    pub fn new(dimensions: u32, metric: Metric) -> Result<VectorIndex, Error> {
+        if dimensions == 0 {
+            return Err(Error::InvalidInput("dimensions must be positive".into()));
+        }
+        Ok(VectorIndex {
+            dimensions: dimensions as usize,
+            metric,
+            max_links: 16,
+            ef_construction: 100,
+            ef_search: 50,
+            nodes: Vec::new(),
+            ids: HashMap::new(),
+            entry: None,
+            rng: Rng::default(),
+        })
+    }
+
+    /// Draws the layers of vectors added from now on from a copy of `rng`.
+    pub fn set_rng(&mut self, rng: &Rng) {
+        self.rng = rng.clone();
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn dimensions(&self) -> u32 {
+        self.dimensions as u32
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn metric(&self) -> Metric {
+        self.metric
+    }
+
+    /// Vectors that can be found, not counting removed ones.
+    #[wasm_bindgen(getter)]
+    pub fn len(&self) -> u32 {
+        self.ids.len() as u32
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+
+    pub fn contains(&self, id: u32) -> bool {
+        self.ids.contains_key(&id)
+    }
+
+    /// Adds the vector under the caller's `id`, replacing any vector it had.
+    pub fn add(&mut self, id: u32, vector: &[f32]) -> Result<(), Error> {
+        self.check(vector)?;
+        self.remove(id);
+        let mut vector = vector.to_vec();
+        if self.metric == Metric::Cosine {
+            normalize(&mut vector);
+        }
+
+        let max_links = self.max_links.max(2) as usize;
+        // Each layer up holds about `1 / max_links` of the nodes below it.
+        let scale = 1.0 / (max_links as f32).ln();
+        let level = ((-(1.0 - self.rng.next_f32()).ln() * scale) as usize).min(16);
+        let index = self.nodes.len();
+        self.nodes.push(Node {
+            id,
+            vector,
+            links: vec![Vec::new(); level + 1],
+            removed: false,
+        });
+        self.ids.insert(id, index);
+
+        let Some(entry) = self.entry else {
+            self.entry = Some(index);
+            return Ok(());
+        };
+        let top = self.nodes[entry].links.len() - 1;
+        let query = self.nodes[index].vector.clone();
+        let mut nearest = vec![entry];
+        for layer in (level + 1..=top).rev() {
+            nearest = self.search_layer(&query, &nearest, 1, layer);
+        }
+        for layer in (0..=level.min(top)).rev() {
+            let found = self.search_layer(&query, &nearest, self.ef_construction as usize, layer);
+            let limit = if layer == 0 { max_links * 2 } else { max_links };
+            let neighbors: Vec<usize> = found.iter().copied().take(max_links).collect();
+            self.nodes[index].links[layer] = neighbors.iter().map(|&n| n as u32).collect();
+            for &neighbor in &neighbors {
+                self.nodes[neighbor].links[layer].push(index as u32);
+                if self.nodes[neighbor].links[layer].len() > limit {
+                    self.prune(neighbor, layer, limit);
+                }
+            }
+            nearest = found;
+        }
+        if level > top {
+            self.entry = Some(index);
+        }
+        Ok(())
+    }
+
+    /// Stops returning the vector with `id` from searches.
+    pub fn remove(&mut self, id: u32) -> bool {
+        match self.ids.remove(&id) {
+            Some(index) => {
+                self.nodes[index].removed = true;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// A copy of the stored vector, normalized for the cosine metric.
+    pub fn get(&self, id: u32) -> Option<Vec<f32>> {
+        self.ids
+            .get(&id)
+            .map(|&index| self.nodes[index].vector.clone())
+    }
+
+    /// The `k` stored vectors nearest to `query`, nearest first.
+    pub fn search(&self, query: &[f32], k: u32) -> Result<Vec<Neighbor>, Error> {
+        self.check(query)?;
+        let Some(entry) = self.entry else {
+            return Ok(Vec::new());
+        };
+        let mut query = query.to_vec();
+        if self.metric == Metric::Cosine {
+            normalize(&mut query);
+        }
+        let mut nearest = vec![entry];
+        for layer in (1..self.nodes[entry].links.len()).rev() {
+            nearest = self.search_layer(&query, &nearest, 1, layer);
+        }
+        let ef = (self.ef_search.max(k) as usize).max(1);
+        Ok(self
+            .search_layer(&query, &nearest, ef, 0)
+            .into_iter()
+            .filter(|&index| !self.nodes[index].removed)
+            .take(k as usize)
+            .map(|index| Neighbor {
+                id: self.nodes[index].id,
+                distance: self.distance(&query, &self.nodes[index].vector),
+            })
+            .collect())
+    }
+
+    /// Like `search`, checking every vector: exact, but linear in their number.
+    pub fn search_exact(&self, query: &[f32], k: u32) -> Result<Vec<Neighbor>, Error> {
+        self.check(query)?;
+        let mut query = query.to_vec();
+        if self.metric == Metric::Cosine {
+            normalize(&mut query);
+        }
+        let mut all: Vec<Neighbor> = self
+            .nodes
+            .iter()
+            .filter(|node| !node.removed)
+            .map(|node| Neighbor {
+                id: node.id,
+                distance: self.distance(&query, &node.vector),
+            })
+            .collect();
+        all.sort_by(|a, b| a.distance.total_cmp(&b.distance));
+        all.truncate(k as usize);
+        Ok(all)
+    }
+
+    /// Builds a fresh graph from the vectors that have not been removed.
+    pub fn rebuild(&mut self) {
+        let nodes = std::mem::take(&mut self.nodes);
+        self.ids.clear();
+        self.entry = None;
+        for node in nodes.into_iter().filter(|node| !node.removed) {
+            // Stored vectors already have the right length.
+            let _ = self.add(node.id, &node.vector);
+        }
+    }
+
+    /// The whole index, graph included, as little-endian bytes for `from_bytes`.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        let mut put = |value: u32| bytes.extend_from_slice(&value.to_le_bytes());
+        put(VERSION);
+        put(self.dimensions as u32);
+        put(self.metric as u32);
+        put(self.max_links);
+        put(self.ef_construction);
+        put(self.ef_search);
+        for word in self.rng.state() {
+            put(word);
+        }
+        put(self.entry.map_or(u32::MAX, |entry| entry as u32));
+        put(self.nodes.len() as u32);
+        for node in &self.nodes {
+            put(node.id);
+            put(node.removed as u32);
+            for &value in &node.vector {
+                put(value.to_bits());
+            }
+            put(node.links.len() as u32);
+            for links in &node.links {
+                put(links.len() as u32);
+                for &link in links {
+                    put(link);
+                }
+            }
+        }
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<VectorIndex, Error> {
+        let invalid = || Error::InvalidInput("not a vector index".into());
+        if bytes.get(..4) != Some(&MAGIC[..]) {
+            return Err(invalid());
+        }
+        let mut words = bytes[4..]
+            .chunks_exact(4)
+            .map(|word| u32::from_le_bytes([word[0], word[1], word[2], word[3]]));
+        let mut next = || words.next().ok_or_else(invalid);
+        if next()? != VERSION {
+            return Err(Error::InvalidInput(
+                "unsupported vector index version".into(),
+            ));
+        }
+        let dimensions = next()? as usize;
+        let metric = match next()? {
+            0 => Metric::Cosine,
+            1 => Metric::Dot,
+            2 => Metric::L2,
+            _ => return Err(invalid()),
+        };
+        let (max_links, ef_construction, ef_search) = (next()?, next()?, next()?);
+        let mut rng = Rng::new(0);
+        rng.set_state(&[next()?, next()?, next()?, next()?])?;
+        let entry = next()?;
+        let count = next()? as usize;
+        let mut nodes = Vec::with_capacity(count.min(bytes.len()));
+        let mut ids = HashMap::new();
+        for index in 0..count {
+            let id = next()?;
+            let removed = next()? != 0;
+            let vector = (0..dimensions)
+                .map(|_| next().map(f32::from_bits))
+                .collect::<Result<Vec<_>, _>>()?;
+            let layers = next()? as usize;
+            let mut links = Vec::with_capacity(layers.min(32));
+            for _ in 0..layers {
+                let len = next()? as usize;
+                let layer = (0..len).map(|_| next()).collect::<Result<Vec<_>, _>>()?;
+                links.push(layer);
+            }
+            if links.is_empty() {
+                return Err(invalid());
+            }
+            if !removed {
+                ids.insert(id, index);
+            }
+            nodes.push(Node {
+                id,
+                vector,
+                links,
+                removed,
+            });
+        }
+        let entry = (entry != u32::MAX).then_some(entry as usize);
+        if dimensions == 0 || entry.is_some_and(|entry| entry >= count) {
+            return Err(invalid());
+        }
+        // Searches index straight into linked nodes' layers, so check them all.
+        for node in &nodes {
+            for (layer, links) in node.links.iter().enumerate() {
+                let reaches = |&link: &u32| {
+                    nodes
+                        .get(link as usize)
+                        .is_some_and(|other| other.links.len() > layer)
+                };
+                if !links.iter().all(reaches) {
+                    return Err(invalid());
+                }
+            }
+        }
+        Ok(VectorIndex {
+            dimensions,
+            metric,
+            max_links,
+            ef_construction,
+            ef_search,
+            nodes,
+            ids,
+            entry,
+            rng,
+        })
+    }
+}
+
+impl VectorIndex {
+    fn check(&self, vector: &[f32]) -> Result<(), Error> {
+        if vector.len() == self.dimensions {
+            Ok(())
+        } else {
+            Err(Error::InvalidInput(format!(
+                "expected {} dimensions, got {}",
+                self.dimensions,
+                vector.len()
+            )))
+        }
+    }
+
+    fn distance(&self, a: &[f32], b: &[f32]) -> f32 {
+        match self.metric {
+            Metric::Cosine => 1.0 - dot(a, b),
+            Metric::Dot => -dot(a, b),
+            Metric::L2 => a
+                .iter()
+                .zip(b)
+                .map(|(x, y)| (x - y) * (x - y))
+                .sum::<f32>()
+                .sqrt(),
+        }
+    }
+
+    /// Best-first search of one layer from `entries`, keeping the `ef` nearest
+    /// nodes found, which it returns nearest first. Only nodes that live on
+    /// `layer` are passed in, and links lead only to such nodes.
+    fn search_layer(
+        &self,
+        query: &[f32],
+        entries: &[usize],
+        ef: usize,
+        layer: usize,
+    ) -> Vec<usize> {
+        let mut visited: HashSet<usize> = entries.iter().copied().collect();
+        let mut candidates = BinaryHeap::new();
+        let mut found = BinaryHeap::new();
+        for &index in entries {
+            let cost = self.distance(query, &self.nodes[index].vector);
+            candidates.push(OpenNode { cost, index });
+            found.push(Reverse(OpenNode { cost, index }));
+        }
+        while let Some(OpenNode { cost, index }) = candidates.pop() {
+            if found.len() >= ef && found.peek().is_some_and(|far| cost > far.0.cost) {
+                break;
+            }
+            for &link in &self.nodes[index].links[layer] {
+                let link = link as usize;
+                if !visited.insert(link) {
+                    continue;
+                }
+                let cost = self.distance(query, &self.nodes[link].vector);
+                if found.len() < ef || found.peek().is_some_and(|far| cost < far.0.cost) {
+                    candidates.push(OpenNode { cost, index: link });
+                    found.push(Reverse(OpenNode { cost, index: link }));
+                    if found.len() > ef {
+                        found.pop();
+                    }
+                }
+            }
+        }
+        let mut found: Vec<OpenNode> = found.into_iter().map(|Reverse(node)| node).collect();
+        found.sort_by(|a, b| a.cost.total_cmp(&b.cost));
+        found.into_iter().map(|node| node.index).collect()
+    }
+
+    /// Cuts a node's links on `layer` down to its `limit` nearest.
+    fn prune(&mut self, index: usize, layer: usize, limit: usize) {
+        let origin = &self.nodes[index].vector;
+        let mut links: Vec<(f32, u32)> = self.nodes[index].links[layer]
+            .iter()
+            .map(|&link| {
+                (
+                    self.distance(origin, &self.nodes[link as usize].vector),
+                    link,
+                )
+            })
+            .collect();
+        links.sort_by(|a, b| a.0.total_cmp(&b.0));
+        links.truncate(limit);
+        self.nodes[index].links[layer] = links.into_iter().map(|(_, link)| link).collect();
+    }
+}
+
+fn dot(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+fn normalize(vector: &mut [f32]) {
+    let length = dot(vector, vector).sqrt();
+    if length > 0.0 {
+        vector.iter_mut().for_each(|value| *value /= length);
+    }
+}
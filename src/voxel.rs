@@ -0,0 +1,278 @@
+use wasm_bindgen::prelude::*;
+
+use crate::grid::octile;
+use crate::search;
+
+const SQRT_2: f32 = std::f32::consts::SQRT_2;
+
+/// `(dx, dz)` steps on one level; the first four are the orthogonal ones.
+const DIRECTIONS: [(i32, i32); 8] = [
+    (1, 0),
+    (-1, 0),
+    (0, 1),
+    (0, -1),
+    (1, 1),
+    (1, -1),
+    (-1, 1),
+    (-1, -1),
+];
+
+/// What fills a `VoxelGrid` cell.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VoxelCell {
+    /// Open space, which agents stand in when the cell below holds them up.
+    Empty = 0,
+    /// Ground and walls.
+    Solid = 1,
+    /// Open space agents stand in at any height and climb straight up or down,
+    /// to the next ladder cell or off the top and bottom.
+    Ladder = 2,
+    /// Open space agents stand in at any height, where they step a level up or
+    /// down even when `stairs_only` is set.
+    Stairs = 3,
+}
+
+impl VoxelCell {
+    fn from_u8(value: u8) -> VoxelCell {
+        match value {
+            1 => VoxelCell::Solid,
+            2 => VoxelCell::Ladder,
+            3 => VoxelCell::Stairs,
+            _ => VoxelCell::Empty,
+        }
+    }
+}
+
+/// A box of voxels for games with several floors or terrain to climb, with `y`
+/// pointing up. Agents occupy open cells that rest on something: solid ground,
+/// the bottom of the grid, a ladder or stairs. Each move goes to one of the
+/// eight cells around on the same level, steps up onto a ledge up to
+/// `max_climb` high, drops down up to `max_fall`, or climbs a ladder.
+#[wasm_bindgen]
+#[derive(Clone, Debug)]
+pub struct VoxelGrid {
+    width: u32,
+    height: u32,
+    depth: u32,
+    cells: Vec<u8>,
+    /// Highest ledge, in cells, an agent steps up onto from a neighbor.
+    pub max_climb: u32,
+    /// Deepest drop, in cells, an agent takes off an edge.
+    pub max_fall: u32,
+    /// Only lets agents step up or down where they leave from or arrive on
+    /// `Stairs`, leaving ladders as the other way between levels.
+    pub stairs_only: bool,
+    /// What each level climbed or dropped adds to a move's cost, on top of the
+    /// one per cell, or `sqrt(2)` per diagonal, of moving across.
+    pub vertical_cost: f32,
+}
+
+#[wasm_bindgen]
+impl VoxelGrid {
+    /// A grid `width` cells along x, `height` up y and `depth` along z, all
+    /// empty, with steps up one cell and drops of up to three.
+    #[wasm_bindgen(constructor)]
+    pub fn new(width: u32, height: u32, depth: u32) -> VoxelGrid {
+        VoxelGrid {
+            width,
+            height,
+            depth,
+            cells: vec![VoxelCell::Empty as u8; (width * height * depth) as usize],
+            max_climb: 1,
+            max_fall: 3,
+            stairs_only: false,
+            vertical_cost: 1.0,
+        }
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn depth(&self) -> u32 {
+        self.depth
+    }
+
+    /// Sets one cell. Cells outside the grid are ignored.
+    pub fn set_cell(&mut self, x: i32, y: i32, z: i32, cell: VoxelCell) {
+        if let Some(index) = self.index(x, y, z) {
+            self.cells[index] = cell as u8;
+        }
+    }
+
+    /// Sets every cell of the box between two corners, both included, e.g. a
+    /// floor or a wall. Parts outside the grid are ignored.
+    #[allow(clippy::too_many_arguments)]
+    pub fn fill(&mut self, x0: i32, y0: i32, z0: i32, x1: i32, y1: i32, z1: i32, cell: VoxelCell) {
+        for y in y0.min(y1).max(0)..=y0.max(y1).min(self.height as i32 - 1) {
+            for z in z0.min(z1).max(0)..=z0.max(z1).min(self.depth as i32 - 1) {
+                for x in x0.min(x1).max(0)..=x0.max(x1).min(self.width as i32 - 1) {
+                    self.set_cell(x, y, z, cell);
+                }
+            }
+        }
+    }
+
+    /// The cell's contents; outside the grid counts as solid.
+    pub fn cell(&self, x: i32, y: i32, z: i32) -> VoxelCell {
+        self.index(x, y, z).map_or(VoxelCell::Solid, |index| {
+            VoxelCell::from_u8(self.cells[index])
+        })
+    }
+
+    /// Whether an agent can occupy the cell: open, and resting on something.
+    pub fn is_standable(&self, x: i32, y: i32, z: i32) -> bool {
+        match self
+            .index(x, y, z)
+            .map(|index| VoxelCell::from_u8(self.cells[index]))
+        {
+            Some(VoxelCell::Ladder | VoxelCell::Stairs) => true,
+            Some(VoxelCell::Empty) => y == 0 || self.cell(x, y - 1, z) != VoxelCell::Empty,
+            _ => false,
+        }
+    }
+
+    /// Finds a cheapest path between two standable cells and returns it as
+    /// flat `[x0, y0, z0, x1, y1, z1, ...]` cell coordinates, including both
+    /// ends, or an empty array when there is none. Diagonal moves stay on one
+    /// level and may not cut corners.
+    pub fn find_path(
+        &self,
+        start_x: i32,
+        start_y: i32,
+        start_z: i32,
+        end_x: i32,
+        end_y: i32,
+        end_z: i32,
+    ) -> Vec<f32> {
+        if !self.is_standable(start_x, start_y, start_z) || !self.is_standable(end_x, end_y, end_z)
+        {
+            return Vec::new();
+        }
+        let (Some(start), Some(goal)) = (
+            self.index(start_x, start_y, start_z),
+            self.index(end_x, end_y, end_z),
+        ) else {
+            return Vec::new();
+        };
+        let vertical_cost = self.vertical_cost.max(0.0);
+        let path = search::astar(
+            self.cells.len(),
+            start,
+            goal,
+            |index, out| self.moves(index, out),
+            |index| {
+                let (x, y, z) = self.coords(index);
+                octile(x - end_x, z - end_z) + (y - end_y).unsigned_abs() as f32 * vertical_cost
+            },
+        );
+        path.map(|path| {
+            path.into_iter()
+                .flat_map(|index| {
+                    let (x, y, z) = self.coords(index);
+                    [x as f32, y as f32, z as f32]
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+    }
+}
+
+impl VoxelGrid {
+    fn index(&self, x: i32, y: i32, z: i32) -> Option<usize> {
+        let inside = x >= 0
+            && y >= 0
+            && z >= 0
+            && (x as u32) < self.width
+            && (y as u32) < self.height
+            && (z as u32) < self.depth;
+        inside.then(|| ((y as u32 * self.depth + z as u32) * self.width + x as u32) as usize)
+    }
+
+    fn coords(&self, index: usize) -> (i32, i32, i32) {
+        let index = index as u32;
+        let x = index % self.width;
+        let z = index / self.width % self.depth;
+        let y = index / (self.width * self.depth);
+        (x as i32, y as i32, z as i32)
+    }
+
+    fn is_open(&self, x: i32, y: i32, z: i32) -> bool {
+        self.index(x, y, z)
+            .is_some_and(|index| self.cells[index] != VoxelCell::Solid as u8)
+    }
+
+    fn is_stairs(&self, x: i32, y: i32, z: i32) -> bool {
+        self.cell(x, y, z) == VoxelCell::Stairs
+    }
+
+    /// Pushes the cells one move away with what moving there costs.
+    fn moves(&self, index: usize, out: &mut Vec<(usize, f32)>) {
+        let (x, y, z) = self.coords(index);
+        let vertical_cost = self.vertical_cost.max(0.0);
+        let mut push = |nx: i32, ny: i32, nz: i32, across: f32| {
+            if let Some(next) = self.index(nx, ny, nz) {
+                out.push((
+                    next,
+                    across + (ny - y).unsigned_abs() as f32 * vertical_cost,
+                ));
+            }
+        };
+
+        if self.cell(x, y, z) == VoxelCell::Ladder {
+            for ny in [y + 1, y - 1] {
+                if self.is_open(x, ny, z) && (ny > y || self.is_standable(x, ny, z)) {
+                    push(x, ny, z, 0.0);
+                }
+            }
+        } else if self.cell(x, y - 1, z) == VoxelCell::Ladder {
+            push(x, y - 1, z, 0.0);
+        }
+
+        for (i, &(dx, dz)) in DIRECTIONS.iter().enumerate() {
+            let (nx, nz) = (x + dx, z + dz);
+            if i >= 4 {
+                if self.is_standable(nx, y, nz)
+                    && self.is_open(x + dx, y, z)
+                    && self.is_open(x, y, z + dz)
+                {
+                    push(nx, y, nz, SQRT_2);
+                }
+                continue;
+            }
+            if self.is_standable(nx, y, nz) {
+                push(nx, y, nz, 1.0);
+            } else if self.is_open(nx, y, nz) {
+                // Off an edge: land on the first thing below.
+                let landing = (1..=self.max_fall as i32)
+                    .map(|drop| y - drop)
+                    .take_while(|&ny| self.is_open(nx, ny, nz))
+                    .find(|&ny| self.is_standable(nx, ny, nz));
+                if let Some(ny) = landing {
+                    if !self.stairs_only || self.is_stairs(x, y, z) || self.is_stairs(nx, ny, nz) {
+                        push(nx, ny, nz, 1.0);
+                    }
+                }
+            } else {
+                // Into a wall: climb onto it if there is headroom.
+                let ledge = (1..=self.max_climb as i32)
+                    .map(|rise| y + rise)
+                    .take_while(|&ny| self.is_open(x, ny, z))
+                    .find(|&ny| self.is_standable(nx, ny, nz));
+                if let Some(ny) = ledge {
+                    if !self.stairs_only || self.is_stairs(x, y, z) || self.is_stairs(nx, ny, nz) {
+                        push(nx, ny, nz, 1.0);
+                    }
+                }
+            }
+        }
+    }
+}
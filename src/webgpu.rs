@@ -0,0 +1,384 @@
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use js_sys::{Array, ArrayBuffer, Float32Array, Promise, Uint32Array};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen_futures::{future_to_promise, JsFuture};
+
+use crate::flock::Flock;
+use crate::grid::{Grid, Traversal};
+
+#[wasm_bindgen(inline_js = r#"
+export async function requestGpuDevice() {
+  if (!globalThis.navigator?.gpu) throw new Error('WebGPU is not available');
+  const adapter = await navigator.gpu.requestAdapter();
+  if (!adapter) throw new Error('no WebGPU adapter');
+  return adapter.requestDevice();
+}
+
+export class Kernel {
+  constructor(device, code) {
+    this.device = device;
+    const module = device.createShaderModule({ code });
+    this.pipeline = device.createComputePipeline({
+      layout: 'auto',
+      compute: { module, entryPoint: 'main' },
+    });
+    this.buffers = [];
+    this.group = null;
+  }
+
+  async run(inputs, outputs, workgroups, passes) {
+    const { device } = this;
+    inputs.forEach((data, binding) => {
+      const size = Math.max(16, Math.ceil(data.byteLength / 16) * 16);
+      if (this.buffers[binding]?.size !== size) {
+        this.buffers[binding]?.destroy();
+        this.buffers[binding] = device.createBuffer({
+          size,
+          usage: GPUBufferUsage.STORAGE | GPUBufferUsage.COPY_DST | GPUBufferUsage.COPY_SRC,
+        });
+        this.group = null;
+      }
+      device.queue.writeBuffer(this.buffers[binding], 0, data);
+    });
+    this.group ??= device.createBindGroup({
+      layout: this.pipeline.getBindGroupLayout(0),
+      entries: this.buffers.map((buffer, binding) => ({ binding, resource: { buffer } })),
+    });
+    const encoder = device.createCommandEncoder();
+    for (let pass = 0; pass < passes; pass++) {
+      const compute = encoder.beginComputePass();
+      compute.setPipeline(this.pipeline);
+      compute.setBindGroup(0, this.group);
+      compute.dispatchWorkgroups(workgroups);
+      compute.end();
+    }
+    const staging = outputs.map((binding) => {
+      const source = this.buffers[binding];
+      const buffer = device.createBuffer({
+        size: source.size,
+        usage: GPUBufferUsage.MAP_READ | GPUBufferUsage.COPY_DST,
+      });
+      encoder.copyBufferToBuffer(source, 0, buffer, 0, source.size);
+      return buffer;
+    });
+    device.queue.submit([encoder.finish()]);
+    return Promise.all(staging.map(async (buffer) => {
+      await buffer.mapAsync(GPUMapMode.READ);
+      const copy = buffer.getMappedRange().slice(0);
+      buffer.destroy();
+      return copy;
+    }));
+  }
+
+  destroy() {
+    this.buffers.forEach((buffer) => buffer.destroy());
+  }
+}
+"#)]
+extern "C" {
+    #[wasm_bindgen(catch, js_name = requestGpuDevice)]
+    fn request_device() -> Result<Promise, JsValue>;
+
+    type Kernel;
+
+    #[wasm_bindgen(constructor, catch)]
+    fn new(device: &JsValue, code: &str) -> Result<Kernel, JsValue>;
+
+    /// Uploads `inputs` to bindings 0 and up, runs `passes` dispatches of
+    /// `workgroups` workgroups and resolves to copies of the `outputs`
+    /// bindings as `ArrayBuffer`s.
+    #[wasm_bindgen(method)]
+    fn run(this: &Kernel, inputs: &Array, outputs: &Array, workgroups: u32, passes: u32)
+        -> Promise;
+
+    #[wasm_bindgen(method)]
+    fn destroy(this: &Kernel);
+}
+
+/// Threads per workgroup in both shaders.
+const WORKGROUP_SIZE: u32 = 64;
+
+/// Most workgroups one dispatch may run along a dimension.
+const MAX_WORKGROUPS: u32 = 65535;
+
+/// Flow field costs at or above this have not been reached.
+const UNREACHED: f32 = 1e30;
+
+/// A WebGPU device for the compute backend, from `request_gpu_device`. The
+/// backend talks to the browser's `navigator.gpu` directly, so it runs only
+/// in a browser, and covers two jobs: `Flock::use_gpu` and
+/// `Grid::generate_flow_field_gpu`. `Crowd`, `AiWorld` and the pathfinders
+/// stay on the CPU.
+#[wasm_bindgen]
+#[derive(Clone)]
+pub struct GpuDevice {
+    device: JsValue,
+}
+
+/// Resolves to a `GpuDevice` on the default adapter, rejecting where the
+/// browser has no WebGPU. Needs the `webgpu` feature.
+#[wasm_bindgen]
+pub fn request_gpu_device() -> Promise {
+    future_to_promise(async move {
+        let device = JsFuture::from(request_device()?).await?;
+        Ok(GpuDevice { device }.into())
+    })
+}
+
+fn workgroups(count: usize) -> Result<u32, JsValue> {
+    let groups = (count as u32).div_ceil(WORKGROUP_SIZE).max(1);
+    if groups > MAX_WORKGROUPS {
+        return Err(JsValue::from_str(&format!(
+            "{} items are more than one dispatch can run",
+            count
+        )));
+    }
+    Ok(groups)
+}
+
+/// A flock's compute kernel and its latest forces, kept here rather than in
+/// `Flock` since JS values cannot be shared between threads.
+struct FlockKernel {
+    kernel: Kernel,
+    forces: Rc<RefCell<Option<Vec<f32>>>>,
+    busy: Rc<Cell<bool>>,
+}
+
+thread_local! {
+    static FLOCK_KERNELS: RefCell<HashMap<u32, FlockKernel>> = RefCell::new(HashMap::new());
+    static NEXT_FLOCK_KERNEL: Cell<u32> = const { Cell::new(0) };
+}
+
+/// A `Flock`'s handle on its compute kernel, released on drop.
+pub(crate) struct GpuFlock(u32);
+
+impl GpuFlock {
+    fn new(device: &GpuDevice) -> Result<GpuFlock, JsValue> {
+        let kernel = Kernel::new(&device.device, include_str!("shaders/flock.wgsl"))?;
+        let id = NEXT_FLOCK_KERNEL.with(|next| next.replace(next.get().wrapping_add(1)));
+        FLOCK_KERNELS.with(|kernels| {
+            kernels.borrow_mut().insert(
+                id,
+                FlockKernel {
+                    kernel,
+                    forces: Rc::new(RefCell::new(None)),
+                    busy: Rc::new(Cell::new(false)),
+                },
+            )
+        });
+        Ok(GpuFlock(id))
+    }
+
+    /// Copies the forces of the newest finished dispatch into `out`, `[fx, fy]`
+    /// per boid, and starts the next one from the flock as it is now unless
+    /// one is still running. Boids added since that dispatch keep their old
+    /// forces.
+    pub(crate) fn exchange(&self, flock: &Flock, out: &mut [[f32; 2]]) {
+        FLOCK_KERNELS.with(|kernels| {
+            let kernels = kernels.borrow();
+            let Some(entry) = kernels.get(&self.0) else {
+                return;
+            };
+            if let Some(forces) = entry.forces.borrow_mut().take() {
+                for (out, force) in out.iter_mut().zip(forces.chunks_exact(2)) {
+                    *out = [force[0], force[1]];
+                }
+            }
+            if entry.busy.get() || out.is_empty() {
+                return;
+            }
+            let Ok(groups) = workgroups(out.len()) else {
+                return;
+            };
+            let inputs = flock_inputs(flock);
+            let promise = entry.kernel.run(&inputs, &Array::of1(&5.into()), groups, 1);
+            let (forces, busy) = (entry.forces.clone(), entry.busy.clone());
+            busy.set(true);
+            wasm_bindgen_futures::spawn_local(async move {
+                if let Ok(buffers) = JsFuture::from(promise).await {
+                    let buffer: ArrayBuffer = Array::from(&buffers).get(0).unchecked_into();
+                    *forces.borrow_mut() = Some(Float32Array::new(&buffer).to_vec());
+                }
+                busy.set(false);
+            });
+        });
+    }
+}
+
+impl Drop for GpuFlock {
+    fn drop(&mut self) {
+        let _ = FLOCK_KERNELS.try_with(|kernels| {
+            if let Some(entry) = kernels.borrow_mut().remove(&self.0) {
+                entry.kernel.destroy();
+            }
+        });
+    }
+}
+
+/// The bindings of `flock.wgsl`, with the boids counting-sorted into cells at
+/// least the neighbor radius wide.
+fn flock_inputs(flock: &Flock) -> Array {
+    let positions = flock.position_buffer();
+    let count = positions.len() / 2;
+    let (mut min_x, mut min_y) = (f32::INFINITY, f32::INFINITY);
+    let (mut max_x, mut max_y) = (f32::NEG_INFINITY, f32::NEG_INFINITY);
+    for position in positions.chunks_exact(2) {
+        min_x = min_x.min(position[0]);
+        min_y = min_y.min(position[1]);
+        max_x = max_x.max(position[0]);
+        max_y = max_y.max(position[1]);
+    }
+    // Sparse flocks get wider cells so the cell table stays near the boid count.
+    let mut cell_size = flock.neighbor_radius.max(f32::EPSILON);
+    let (mut columns, mut rows);
+    loop {
+        columns = ((max_x - min_x) / cell_size) as usize + 1;
+        rows = ((max_y - min_y) / cell_size) as usize + 1;
+        if columns * rows <= 4 * count + 1024 {
+            break;
+        }
+        cell_size *= 2.0;
+    }
+    let cell_of = |position: &[f32]| {
+        let cx = (((position[0] - min_x) / cell_size) as usize).min(columns - 1);
+        let cy = (((position[1] - min_y) / cell_size) as usize).min(rows - 1);
+        cy * columns + cx
+    };
+    let mut cell_start = vec![0u32; columns * rows + 1];
+    for position in positions.chunks_exact(2) {
+        cell_start[cell_of(position) + 1] += 1;
+    }
+    for cell in 1..cell_start.len() {
+        cell_start[cell] += cell_start[cell - 1];
+    }
+    let mut next = cell_start.clone();
+    let mut order = vec![0u32; count];
+    for (boid, position) in positions.chunks_exact(2).enumerate() {
+        let slot = &mut next[cell_of(position)];
+        order[*slot as usize] = boid as u32;
+        *slot += 1;
+    }
+    let params = [
+        count as f32,
+        columns as f32,
+        rows as f32,
+        min_x,
+        min_y,
+        cell_size,
+        flock.neighbor_radius,
+        flock.separation_radius,
+        flock.separation_weight,
+        flock.alignment_weight,
+        flock.cohesion_weight,
+        flock.max_speed,
+        flock.max_force,
+    ];
+    let inputs = Array::new();
+    inputs.push(&Float32Array::from(positions));
+    inputs.push(&Float32Array::from(flock.velocity_buffer()));
+    inputs.push(&Uint32Array::from(&order[..]));
+    inputs.push(&Uint32Array::from(&cell_start[..]));
+    inputs.push(&Float32Array::from(&params[..]));
+    inputs.push(&Float32Array::new_with_length(2 * count as u32));
+    inputs
+}
+
+#[wasm_bindgen]
+impl Flock {
+    /// Computes the flocking forces on `device` from the next `update` on,
+    /// for flocks far larger than the CPU keeps up with. The GPU works while
+    /// the frame goes on, so unlike on the CPU each update steers by forces
+    /// from the state of a frame or two before, and boids added since the
+    /// last dispatch get no force until the next one finishes. Needs the
+    /// `webgpu` feature.
+    pub fn use_gpu(&mut self, device: &GpuDevice) -> Result<(), JsValue> {
+        self.gpu = Some(GpuFlock::new(device)?);
+        Ok(())
+    }
+
+    /// Goes back to computing the forces on the CPU.
+    pub fn use_cpu(&mut self) {
+        self.gpu = None;
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn uses_gpu(&self) -> bool {
+        self.gpu.is_some()
+    }
+}
+
+#[wasm_bindgen]
+impl Grid {
+    /// Like `generate_flow_field`, integrating the costs on `device`, but
+    /// asynchronous: returns a `Promise<FlowField>` with the same field. Worth it for large
+    /// grids; the directions are still picked on the CPU. Needs the `webgpu`
+    /// feature.
+    pub fn generate_flow_field_gpu(
+        &self,
+        device: &GpuDevice,
+        target_x: u32,
+        target_y: u32,
+    ) -> Promise {
+        let cell_count = self.cell_count();
+        let mut costs = vec![UNREACHED; cell_count];
+        let mut sources = vec![u32::MAX; cell_count * 8];
+        let mut steps = vec![0.0f32; cell_count * 8];
+        let mut filled = vec![0usize; cell_count];
+        let mut edges = Vec::new();
+        for index in 0..cell_count {
+            edges.clear();
+            self.neighbors(index, &mut edges);
+            for &(next, step) in &edges {
+                let slot = next * 8 + filled[next];
+                sources[slot] = index as u32;
+                steps[slot] = step;
+                filled[next] += 1;
+            }
+        }
+        if self.is_passable(target_x as i32, target_y as i32, Traversal::default()) {
+            costs[self.index(target_x as i32, target_y as i32)] = 0.0;
+        }
+        let grid = self.clone();
+        let device = device.clone();
+        future_to_promise(async move {
+            let kernel = Kernel::new(&device.device, include_str!("shaders/flow_field.wgsl"))?;
+            let groups = workgroups(cell_count)?;
+            let passes = grid.width() + grid.height();
+            let sources = Uint32Array::from(&sources[..]);
+            let steps = Float32Array::from(&steps[..]);
+            let params = Uint32Array::from(&[cell_count as u32][..]);
+            let outputs = Array::of2(&0.into(), &1.into());
+            let result = loop {
+                let inputs = Array::new();
+                inputs.push(&Float32Array::from(&costs[..]));
+                inputs.push(&Uint32Array::new_with_length(1));
+                inputs.push(&sources);
+                inputs.push(&steps);
+                inputs.push(&params);
+                let buffers =
+                    match JsFuture::from(kernel.run(&inputs, &outputs, groups, passes)).await {
+                        Ok(buffers) => Array::from(&buffers),
+                        Err(error) => break Err(error),
+                    };
+                let integrated = Float32Array::new(&buffers.get(0));
+                integrated
+                    .subarray(0, cell_count as u32)
+                    .copy_to(&mut costs);
+                if Uint32Array::new(&buffers.get(1)).get_index(0) == 0 {
+                    break Ok(());
+                }
+            };
+            kernel.destroy();
+            result?;
+            for cost in &mut costs {
+                if *cost >= UNREACHED {
+                    *cost = f32::INFINITY;
+                }
+            }
+            Ok(grid.flow_field(target_x, target_y, costs).into())
+        })
+    }
+}
@@ -0,0 +1,1306 @@
+#[cfg(target_arch = "wasm32")]
+use js_sys::{Float32Array, Uint32Array};
+use wasm_bindgen::prelude::*;
+
+use crate::batch::{self, Limit};
+use crate::bytes::{Reader, Writer};
+use crate::debug::{self, DebugGeometry};
+use crate::error::Error;
+use crate::locomotion::{self, Motion};
+use crate::math::Vec2;
+use crate::random::Rng;
+use crate::replay::{Command, Playback, Recorder};
+use crate::stats::{AiStats, Sample};
+
+/// Low bits of a handle index its slot; the rest hold the slot's generation.
+const SLOT_BITS: u32 = 20;
+const SLOT_MASK: u32 = (1 << SLOT_BITS) - 1;
+const GENERATION_LIMIT: u32 = 1 << (32 - SLOT_BITS);
+
+/// Leading bytes of `AiWorld::save`, then a format version.
+const MAGIC: &[u8; 4] = b"LAIW";
+const VERSION: u32 = 1;
+
+/// Leading bytes of `AiWorld::diff`, then a format version.
+const DELTA_MAGIC: &[u8; 4] = b"LAID";
+const DELTA_VERSION: u32 = 1;
+
+/// Bits of a diff's per-agent field mask past the float arrays of
+/// `agent_floats`.
+const BEHAVIOR_FIELD: u32 = 1 << 10;
+const LOD_FIELD: u32 = 1 << 11;
+const ALL_FIELDS: u32 = (1 << 12) - 1;
+/// Fields the locomotion row is derived from: velocity, heading and max speed.
+const MOTION_FIELDS: u32 = 1 << 2 | 1 << 3 | 1 << 8;
+
+/// Fraction of an agent's `max_force` below which its locomotion counts as
+/// `Steady`.
+const ACCELERATION_THRESHOLD: f32 = 0.1;
+
+/// What an `AiWorld` agent does each step.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Behavior {
+    /// Brakes to a stop and stays put.
+    Idle = 0,
+    /// Heads for the target at full speed.
+    Seek = 1,
+    /// Heads for the target, slowing within its radius to stop on it.
+    Arrive = 2,
+    /// Runs directly away from the target.
+    Flee = 3,
+    /// Meanders with Reynolds wander.
+    Wander = 4,
+}
+
+impl Behavior {
+    pub(crate) fn from_u32(value: u32) -> Option<Behavior> {
+        [
+            Behavior::Idle,
+            Behavior::Seek,
+            Behavior::Arrive,
+            Behavior::Flee,
+            Behavior::Wander,
+        ]
+        .get(value as usize)
+        .copied()
+    }
+
+    /// The behavior called `name` in lowercase, e.g. `"wander"`.
+    pub(crate) fn from_name(name: &str) -> Option<Behavior> {
+        ["idle", "seek", "arrive", "flee", "wander"]
+            .iter()
+            .position(|&known| known == name)
+            .and_then(|value| Behavior::from_u32(value as u32))
+    }
+}
+
+/// How much of each step an `AiWorld` agent gets, so that crowds far from the
+/// camera cost little while nearby agents stay responsive.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Lod {
+    /// Steers every step.
+    Full = 0,
+    /// Steers every `reduced_interval` steps, and in between keeps heading for
+    /// the velocity it last chose.
+    Reduced = 1,
+    /// Stops steering and coasts at its last velocity.
+    Frozen = 2,
+}
+
+impl Lod {
+    pub(crate) fn from_u32(value: u32) -> Option<Lod> {
+        [Lod::Full, Lod::Reduced, Lod::Frozen]
+            .get(value as usize)
+            .copied()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Slot {
+    generation: u32,
+    /// Index into the agent arrays while the slot is live.
+    index: u32,
+    live: bool,
+}
+
+/// Every agent of a simulation in one place, stored as structure-of-arrays and
+/// advanced by a fixed timestep, whatever the frame rate.
+///
+/// Agents are named by handles, which stay valid until the agent is removed and
+/// are then stale for the next 4095 agents that reuse its slot. The bulk arrays (`positions`,
+/// `velocities`, `behaviors`, ...) hold every live agent in the order of
+/// `handles`, so a whole frame syncs in a few calls. Removing an agent moves the
+/// last agent into its place.
+///
+/// Each `tick` adds the frame's elapsed time to an accumulator and runs as many
+/// whole steps as fit, so the same inputs always give the same simulation.
+/// Rendering reads `interpolated_positions`, which blends the last two steps by
+/// the leftover time so motion stays smooth between them.
+///
+/// The `*_view` methods and `write_*` setters move whole buffers across the JS
+/// boundary without per-agent calls, and the views without copying at all.
+///
+/// Each agent also has a `Lod`, set per agent, in bulk, or from the camera with
+/// `update_lods`. Brakes for idle agents apply at every level but `Frozen`.
+#[wasm_bindgen]
+pub struct AiWorld {
+    timestep: f64,
+    /// Most steps one `tick` runs. Time beyond them is dropped, so a long stall
+    /// slows the simulation down instead of freezing the page catching up.
+    pub max_steps: u32,
+    /// Random displacement of the wander target per step.
+    pub wander_jitter: f32,
+    /// Radius of the circle the wander target stays on.
+    pub wander_radius: f32,
+    /// How far ahead of the agent the wander circle sits.
+    pub wander_distance: f32,
+    /// Steps between the steering updates of `Lod::Reduced` agents, which are
+    /// staggered so about as many steer on each step.
+    pub reduced_interval: u32,
+    accumulator: f64,
+    time: f64,
+    steps: u32,
+    slots: Vec<Slot>,
+    free: Vec<u32>,
+    // One entry, or one `[x, y]` pair, per live agent.
+    handles: Vec<u32>,
+    positions: Vec<f32>,
+    previous: Vec<f32>,
+    velocities: Vec<f32>,
+    headings: Vec<f32>,
+    targets: Vec<f32>,
+    wander_targets: Vec<f32>,
+    slow_radii: Vec<f32>,
+    max_speeds: Vec<f32>,
+    max_forces: Vec<f32>,
+    behaviors: Vec<Behavior>,
+    lods: Vec<Lod>,
+    /// Velocity each agent last chose to steer for.
+    desired: Vec<f32>,
+    /// Filled by `interpolated_positions_view`.
+    #[cfg(target_arch = "wasm32")]
+    interpolated: Vec<f32>,
+    /// Steering forces of the step being run.
+    forces: Vec<f32>,
+    /// `locomotion::STRIDE` floats per agent, refreshed by each step.
+    locomotion: Vec<f32>,
+    rng: Rng,
+    stats: AiStats,
+    recorder: Option<Recorder>,
+    playback: Option<Playback>,
+}
+
+#[wasm_bindgen]
+impl AiWorld {
+    /// A world stepping every `timestep` seconds, e.g. `1 / 30`.
+    #[wasm_bindgen(constructor)]
+    pub fn new(timestep: f32) -> Result<AiWorld, Error> {
+        if timestep > 0.0 && timestep.is_finite() {
+            Ok(AiWorld {
+                timestep: timestep as f64,
+                max_steps: 8,
+                wander_jitter: 0.3,
+                wander_radius: 1.0,
+                wander_distance: 2.0,
+                reduced_interval: 4,
+                accumulator: 0.0,
+                time: 0.0,
+                steps: 0,
+                slots: Vec::new(),
+                free: Vec::new(),
+                handles: Vec::new(),
+                positions: Vec::new(),
+                previous: Vec::new(),
+                velocities: Vec::new(),
+                headings: Vec::new(),
+                targets: Vec::new(),
+                wander_targets: Vec::new(),
+                slow_radii: Vec::new(),
+                max_speeds: Vec::new(),
+                max_forces: Vec::new(),
+                behaviors: Vec::new(),
+                lods: Vec::new(),
+                desired: Vec::new(),
+                #[cfg(target_arch = "wasm32")]
+                interpolated: Vec::new(),
+                forces: Vec::new(),
+                locomotion: Vec::new(),
+                rng: Rng::default(),
+                stats: AiStats::default(),
+                recorder: None,
+                playback: None,
+            })
+        } else {
+            Err(Error::InvalidInput("timestep must be positive".into()))
+        }
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn timestep(&self) -> f32 {
+        self.timestep as f32
+    }
+
+    /// Simulated seconds, a whole number of steps.
+    #[wasm_bindgen(getter)]
+    pub fn time(&self) -> f64 {
+        self.time
+    }
+
+    /// Fixed steps run so far.
+    #[wasm_bindgen(getter)]
+    pub fn step_count(&self) -> u32 {
+        self.steps
+    }
+
+    /// How far the accumulated time is into the next step, in `[0, 1)`.
+    #[wasm_bindgen(getter)]
+    pub fn alpha(&self) -> f32 {
+        (self.accumulator / self.timestep) as f32
+    }
+
+    /// Draws wander jitter from a copy of `rng`.
+    pub fn set_rng(&mut self, rng: &Rng) {
+        self.rng = rng.clone();
+        self.record(|_| Command::SetRng(rng.state()));
+    }
+
+    /// Adds an idle agent and returns its handle. A world holds up to about a
+    /// million agents at once.
+    ///
+    /// @deprecated Use `add_agent_with`, which names its arguments.
+    pub fn add_agent(
+        &mut self,
+        x: f32,
+        y: f32,
+        max_speed: f32,
+        max_force: f32,
+    ) -> Result<u32, Error> {
+        let index = self.handles.len() as u32;
+        let slot = match self.free.pop() {
+            Some(slot) => slot,
+            None if self.slots.len() as u32 > SLOT_MASK => {
+                return Err(Error::InvalidInput("the world is full".into()));
+            }
+            None => {
+                self.slots.push(Slot {
+                    generation: 1,
+                    index: 0,
+                    live: false,
+                });
+                self.slots.len() as u32 - 1
+            }
+        };
+        let entry = &mut self.slots[slot as usize];
+        entry.index = index;
+        entry.live = true;
+        let handle = entry.generation << SLOT_BITS | slot;
+
+        self.handles.push(handle);
+        self.positions.extend_from_slice(&[x, y]);
+        self.previous.extend_from_slice(&[x, y]);
+        self.velocities.extend_from_slice(&[0.0, 0.0]);
+        self.headings.extend_from_slice(&[1.0, 0.0]);
+        self.targets.extend_from_slice(&[x, y]);
+        self.wander_targets.extend_from_slice(&[1.0, 0.0]);
+        self.slow_radii.push(0.0);
+        self.max_speeds.push(max_speed);
+        self.max_forces.push(max_force);
+        self.behaviors.push(Behavior::Idle);
+        self.lods.push(Lod::Full);
+        self.desired.extend_from_slice(&[0.0, 0.0]);
+        self.forces.extend_from_slice(&[0.0, 0.0]);
+        self.locomotion
+            .resize((index as usize + 1) * locomotion::STRIDE, 0.0);
+        self.reset_locomotion(index as usize);
+        self.record(|_| Command::AddAgent([x, y, max_speed, max_force]));
+        Ok(handle)
+    }
+
+    /// Removes an agent, making its handle stale.
+    pub fn remove_agent(&mut self, handle: u32) -> bool {
+        let Some(index) = self.index(handle) else {
+            return false;
+        };
+        let slot = (handle & SLOT_MASK) as usize;
+        let entry = &mut self.slots[slot];
+        entry.live = false;
+        entry.generation = (entry.generation + 1) % GENERATION_LIMIT;
+        if entry.generation == 0 {
+            entry.generation = 1;
+        }
+        self.free.push(slot as u32);
+
+        let last = self.handles.len() - 1;
+        self.handles.swap_remove(index);
+        for pairs in [
+            &mut self.positions,
+            &mut self.previous,
+            &mut self.velocities,
+            &mut self.headings,
+            &mut self.targets,
+            &mut self.wander_targets,
+            &mut self.desired,
+            &mut self.forces,
+        ] {
+            pairs.swap(index * 2, last * 2);
+            pairs.swap(index * 2 + 1, last * 2 + 1);
+            pairs.truncate(last * 2);
+        }
+        self.slow_radii.swap_remove(index);
+        self.max_speeds.swap_remove(index);
+        self.max_forces.swap_remove(index);
+        self.behaviors.swap_remove(index);
+        self.lods.swap_remove(index);
+        self.locomotion.copy_within(
+            last * locomotion::STRIDE..(last + 1) * locomotion::STRIDE,
+            index * locomotion::STRIDE,
+        );
+        self.locomotion.truncate(last * locomotion::STRIDE);
+        if index < last {
+            let moved = (self.handles[index] & SLOT_MASK) as usize;
+            self.slots[moved].index = index as u32;
+        }
+        self.record(|_| Command::RemoveAgent(handle));
+        true
+    }
+
+    pub fn contains(&self, handle: u32) -> bool {
+        self.index(handle).is_some()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn len(&self) -> u32 {
+        self.handles.len() as u32
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.handles.is_empty()
+    }
+
+    /// Handles of every agent, in the order of the bulk arrays.
+    pub fn handles(&self) -> Vec<u32> {
+        self.handles.clone()
+    }
+
+    /// Where the agent's values sit in the bulk arrays (times two for the ones
+    /// with pairs), or `None` for a stale handle.
+    pub fn index_of(&self, handle: u32) -> Option<u32> {
+        self.index(handle).map(|index| index as u32)
+    }
+
+    /// Sends an agent toward `(x, y)` with `Arrive`, slowing within
+    /// `slow_radius` to stop there.
+    pub fn set_target(&mut self, handle: u32, x: f32, y: f32, slow_radius: f32) -> bool {
+        match self.index(handle) {
+            Some(index) => {
+                self.targets[index * 2] = x;
+                self.targets[index * 2 + 1] = y;
+                self.slow_radii[index] = slow_radius;
+                self.behaviors[index] = Behavior::Arrive;
+                self.record(|_| Command::SetTarget(handle, [x, y, slow_radius]));
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Lets an agent brake to a stop.
+    pub fn clear_target(&mut self, handle: u32) -> bool {
+        self.set_behavior(handle, Behavior::Idle)
+    }
+
+    /// Switches what an agent does, keeping its target.
+    pub fn set_behavior(&mut self, handle: u32, behavior: Behavior) -> bool {
+        match self.index(handle) {
+            Some(index) => {
+                self.behaviors[index] = behavior;
+                self.record(|_| Command::SetBehavior(handle, behavior));
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn behavior(&self, handle: u32) -> Option<Behavior> {
+        self.index(handle).map(|index| self.behaviors[index])
+    }
+
+    pub fn set_lod(&mut self, handle: u32, lod: Lod) -> bool {
+        match self.index(handle) {
+            Some(index) => {
+                self.lods[index] = lod;
+                self.record(|_| Command::SetLod(handle, lod));
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn lod(&self, handle: u32) -> Option<Lod> {
+        self.index(handle).map(|index| self.lods[index])
+    }
+
+    /// Gives `Lod::Full` to agents within `near` of the camera at `(x, y)`,
+    /// `Lod::Reduced` to those within `far` and `Lod::Frozen` to the rest.
+    pub fn update_lods(&mut self, x: f32, y: f32, near: f32, far: f32) {
+        let camera = Vec2::new(x, y);
+        for (index, lod) in self.lods.iter_mut().enumerate() {
+            let distance_squared = (pair(&self.positions, index) - camera).length_squared();
+            *lod = if distance_squared <= near * near {
+                Lod::Full
+            } else if distance_squared <= far * far {
+                Lod::Reduced
+            } else {
+                Lod::Frozen
+            };
+        }
+        self.record(|_| Command::UpdateLods([x, y, near, far]));
+    }
+
+    /// Each agent's `Lod` as a number.
+    pub fn lods(&self) -> Vec<u32> {
+        self.lods.iter().map(|&lod| lod as u32).collect()
+    }
+
+    /// Overwrites every `Lod` from numbers in bulk order, e.g. from importance
+    /// scores computed in JS.
+    pub fn write_lods(&mut self, lods: &[u32]) -> Result<(), Error> {
+        if lods.len() != self.lods.len() {
+            return Err(Error::InvalidInput(format!(
+                "expected {} levels, got {}",
+                self.lods.len(),
+                lods.len()
+            )));
+        }
+        for (lod, &value) in self.lods.iter_mut().zip(lods) {
+            *lod = Lod::from_u32(value)
+                .ok_or_else(|| Error::InvalidInput(format!("unknown level {}", value)))?;
+        }
+        self.record(|_| Command::WriteLods(lods.to_vec()));
+        Ok(())
+    }
+
+    /// How many agents are at `lod`.
+    pub fn lod_count(&self, lod: Lod) -> u32 {
+        self.lods.iter().filter(|&&other| other == lod).count() as u32
+    }
+
+    /// Moves an agent without interpolating from where it was.
+    pub fn set_position(&mut self, handle: u32, x: f32, y: f32) -> bool {
+        match self.index(handle) {
+            Some(index) => {
+                self.positions[index * 2..index * 2 + 2].copy_from_slice(&[x, y]);
+                self.previous[index * 2..index * 2 + 2].copy_from_slice(&[x, y]);
+                self.record(|_| Command::SetPosition(handle, [x, y]));
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Position after the last fixed step.
+    pub fn position(&self, handle: u32) -> Option<Vec2> {
+        self.index(handle).map(|index| pair(&self.positions, index))
+    }
+
+    pub fn velocity(&self, handle: u32) -> Option<Vec2> {
+        self.index(handle)
+            .map(|index| pair(&self.velocities, index))
+    }
+
+    /// Blend of the position before and after the last step by `alpha`.
+    pub fn interpolated_position(&self, handle: u32) -> Option<Vec2> {
+        let alpha = self.alpha();
+        self.index(handle)
+            .map(|index| pair(&self.previous, index).lerp(pair(&self.positions, index), alpha))
+    }
+
+    /// Flat `[x, y, ...]` positions after the last fixed step.
+    pub fn positions(&self) -> Vec<f32> {
+        self.positions.clone()
+    }
+
+    /// Flat `[x, y, ...]` positions for rendering this frame.
+    pub fn interpolated_positions(&self) -> Vec<f32> {
+        let mut interpolated = vec![0.0; self.positions.len()];
+        self.interpolate_into(&mut interpolated);
+        interpolated
+    }
+
+    /// Flat `[x, y, ...]` velocities.
+    pub fn velocities(&self) -> Vec<f32> {
+        self.velocities.clone()
+    }
+
+    /// Flat `[x, y, ...]` unit headings, which hold while an agent stands still.
+    pub fn headings(&self) -> Vec<f32> {
+        self.headings.clone()
+    }
+
+    /// Flat `[x, y, ...]` targets.
+    pub fn targets(&self) -> Vec<f32> {
+        self.targets.clone()
+    }
+
+    /// Each agent's `Behavior` as a number.
+    pub fn behaviors(&self) -> Vec<u32> {
+        self.behaviors
+            .iter()
+            .map(|&behavior| behavior as u32)
+            .collect()
+    }
+
+    /// Sets the targets and behaviors of the agents in `handles` at once, from
+    /// flat `[x, y, ...]` targets and behavior numbers. Stale handles are
+    /// skipped; returns how many agents were updated.
+    pub fn set_targets(
+        &mut self,
+        handles: &[u32],
+        targets: &[f32],
+        behaviors: &[u32],
+    ) -> Result<u32, Error> {
+        if targets.len() != handles.len() * 2 || behaviors.len() != handles.len() {
+            return Err(Error::InvalidInput(
+                "expected two target values and one behavior per handle".into(),
+            ));
+        }
+        let behaviors = behaviors
+            .iter()
+            .map(|&value| {
+                Behavior::from_u32(value)
+                    .ok_or_else(|| Error::InvalidInput(format!("unknown behavior {}", value)))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut updated = 0;
+        for (i, &handle) in handles.iter().enumerate() {
+            if let Some(index) = self.index(handle) {
+                self.targets[index * 2..index * 2 + 2].copy_from_slice(&targets[i * 2..i * 2 + 2]);
+                self.behaviors[index] = behaviors[i];
+                updated += 1;
+            }
+        }
+        self.record(|_| {
+            Command::SetTargets(
+                handles.to_vec(),
+                targets.to_vec(),
+                behaviors.iter().map(|&behavior| behavior as u32).collect(),
+            )
+        });
+        Ok(updated)
+    }
+
+    /// A view of the flat position buffer in WASM memory. The view is invalidated
+    /// when agents are added or removed or the WASM memory grows, so re-fetch it
+    /// after any of those.
+    #[cfg(target_arch = "wasm32")]
+    pub fn positions_view(&self) -> Float32Array {
+        unsafe { Float32Array::view(&self.positions) }
+    }
+
+    /// Interpolates this frame's positions into a buffer and returns a view of
+    /// it, with the same lifetime caveats as `positions_view`.
+    #[cfg(target_arch = "wasm32")]
+    pub fn interpolated_positions_view(&mut self) -> Float32Array {
+        let mut interpolated = std::mem::take(&mut self.interpolated);
+        interpolated.resize(self.positions.len(), 0.0);
+        self.interpolate_into(&mut interpolated);
+        self.interpolated = interpolated;
+        unsafe { Float32Array::view(&self.interpolated) }
+    }
+
+    /// A view of the flat velocity buffer, with the same lifetime caveats as
+    /// `positions_view`.
+    #[cfg(target_arch = "wasm32")]
+    pub fn velocities_view(&self) -> Float32Array {
+        unsafe { Float32Array::view(&self.velocities) }
+    }
+
+    /// A view of the flat heading buffer, with the same lifetime caveats as
+    /// `positions_view`.
+    #[cfg(target_arch = "wasm32")]
+    pub fn headings_view(&self) -> Float32Array {
+        unsafe { Float32Array::view(&self.headings) }
+    }
+
+    /// A view of the flat target buffer, with the same lifetime caveats as
+    /// `positions_view`.
+    #[cfg(target_arch = "wasm32")]
+    pub fn targets_view(&self) -> Float32Array {
+        unsafe { Float32Array::view(&self.targets) }
+    }
+
+    /// A view of every agent's speed, turn rate, heading and acceleration over
+    /// the last step, `locomotion_stride()` floats per agent in bulk order, with
+    /// the same lifetime caveats as `positions_view`. Turn rates are relative to
+    /// the fastest turn the agent's `max_force` allows at its speed.
+    #[cfg(target_arch = "wasm32")]
+    pub fn locomotion_view(&self) -> Float32Array {
+        unsafe { Float32Array::view(&self.locomotion) }
+    }
+
+    /// A view of the handles in bulk order, with the same lifetime caveats as
+    /// `positions_view`.
+    #[cfg(target_arch = "wasm32")]
+    pub fn handles_view(&self) -> Uint32Array {
+        unsafe { Uint32Array::view(&self.handles) }
+    }
+
+    /// Copies the positions into `out`, e.g. a `Float32Array` kept from frame to
+    /// frame, so reading them allocates nothing.
+    pub fn read_positions(&self, out: &mut [f32]) -> Result<(), Error> {
+        self.check_pairs(out.len())?;
+        out.copy_from_slice(&self.positions);
+        Ok(())
+    }
+
+    /// Like `read_positions`, for this frame's interpolated positions.
+    pub fn read_interpolated_positions(&self, out: &mut [f32]) -> Result<(), Error> {
+        self.check_pairs(out.len())?;
+        self.interpolate_into(out);
+        Ok(())
+    }
+
+    /// Overwrites every position from a flat buffer in bulk order, e.g. after
+    /// physics moved the agents. Like `set_position`, nothing is interpolated.
+    pub fn write_positions(&mut self, positions: &[f32]) -> Result<(), Error> {
+        self.check_pairs(positions.len())?;
+        self.positions.copy_from_slice(positions);
+        self.previous.copy_from_slice(positions);
+        self.record(|_| Command::WritePositions(positions.to_vec()));
+        Ok(())
+    }
+
+    /// Overwrites every velocity from a flat buffer in bulk order.
+    pub fn write_velocities(&mut self, velocities: &[f32]) -> Result<(), Error> {
+        self.check_pairs(velocities.len())?;
+        self.velocities.copy_from_slice(velocities);
+        self.record(|_| Command::WriteVelocities(velocities.to_vec()));
+        Ok(())
+    }
+
+    /// Overwrites every target from a flat buffer in bulk order, keeping each
+    /// agent's behavior.
+    pub fn write_targets(&mut self, targets: &[f32]) -> Result<(), Error> {
+        self.check_pairs(targets.len())?;
+        self.targets.copy_from_slice(targets);
+        self.record(|_| Command::WriteTargets(targets.to_vec()));
+        Ok(())
+    }
+
+    /// The world's settings, clock and random state and every agent with the
+    /// slots behind its handle, as bytes for `load`, e.g. for a server
+    /// snapshot. Loading it gives a world whose agents move on exactly as
+    /// these would. Blackboards, behavior trees, state machines and crowds
+    /// are saved on their own, and a `SaveGame` bundles them with the world.
+    pub fn save(&self) -> Vec<u8> {
+        let mut out = Writer::new(MAGIC, VERSION);
+        out.f64(self.timestep);
+        self.write_clock(&mut out);
+        self.write_handles(&mut out);
+        for (values, _) in self.agent_floats() {
+            out.f32s(values);
+        }
+        out.u32s(&self.behaviors());
+        out.u32s(&self.lods());
+        out.finish()
+    }
+
+    /// Restores a world from `save`.
+    pub fn load(bytes: &[u8]) -> Result<AiWorld, Error> {
+        let (mut input, version) = Reader::new(bytes, MAGIC, "an AI world save")?;
+        if version != VERSION {
+            return Err(Error::InvalidInput(
+                "unsupported AI world save version".into(),
+            ));
+        }
+        let timestep = input.f64()?;
+        if timestep <= 0.0 || !timestep.is_finite() {
+            return Err(input.invalid());
+        }
+        let mut world = AiWorld::new(timestep as f32)?;
+        world.timestep = timestep;
+        world.read_clock(&mut input)?;
+        world.read_handles(&mut input)?;
+        let count = world.handles.len();
+        for (values, width) in world.agent_floats_mut() {
+            *values = input.f32s(count * width)?;
+        }
+        world.behaviors = input
+            .u32s(count)?
+            .into_iter()
+            .map(Behavior::from_u32)
+            .collect::<Option<_>>()
+            .ok_or_else(|| input.invalid())?;
+        world.lods = input
+            .u32s(count)?
+            .into_iter()
+            .map(Lod::from_u32)
+            .collect::<Option<_>>()
+            .ok_or_else(|| input.invalid())?;
+        world.forces = vec![0.0; count * 2];
+        world.locomotion = vec![0.0; count * locomotion::STRIDE];
+        for index in 0..count {
+            world.reset_locomotion(index);
+        }
+
+        let invalid = input.invalid();
+        input.finish()?;
+        if !world.handles_consistent() {
+            return Err(invalid);
+        }
+        Ok(world)
+    }
+
+    /// The changes from the world saved as `base` to this one, as bytes for
+    /// `apply_diff`: the clock and settings, the handle tables when agents
+    /// came or went, and of every agent only the fields that changed. A
+    /// server keeps the last `save` each client acknowledged and sends it the
+    /// diff from there instead of a whole snapshot.
+    pub fn diff(&self, base: &[u8]) -> Result<Vec<u8>, Error> {
+        let base = AiWorld::load(base)?;
+        if base.timestep != self.timestep {
+            return Err(Error::InvalidInput(
+                "cannot diff worlds with different timesteps".into(),
+            ));
+        }
+        let mut out = Writer::new(DELTA_MAGIC, DELTA_VERSION);
+        out.u32(base.checksum());
+        out.u32(self.checksum());
+        self.write_clock(&mut out);
+        let membership =
+            self.slots != base.slots || self.free != base.free || self.handles != base.handles;
+        out.u8(membership as u8);
+        if membership {
+            self.write_handles(&mut out);
+        }
+
+        let floats = self.agent_floats();
+        let base_floats = base.agent_floats();
+        let mut changed = Vec::new();
+        for (index, &handle) in self.handles.iter().enumerate() {
+            let mask = match base.index(handle) {
+                Some(old) => {
+                    let mut mask = 0;
+                    for (field, (&(values, width), &(old_values, _))) in
+                        floats.iter().zip(&base_floats).enumerate()
+                    {
+                        let now = &values[index * width..(index + 1) * width];
+                        let then = &old_values[old * width..(old + 1) * width];
+                        // Compared as bits, so a NaN that stays NaN is unchanged.
+                        if now
+                            .iter()
+                            .map(|v| v.to_bits())
+                            .ne(then.iter().map(|v| v.to_bits()))
+                        {
+                            mask |= 1 << field;
+                        }
+                    }
+                    if self.behaviors[index] != base.behaviors[old] {
+                        mask |= BEHAVIOR_FIELD;
+                    }
+                    if self.lods[index] != base.lods[old] {
+                        mask |= LOD_FIELD;
+                    }
+                    mask
+                }
+                None => ALL_FIELDS,
+            };
+            if mask != 0 {
+                changed.push((index, mask));
+            }
+        }
+        out.u32(changed.len() as u32);
+        for (index, mask) in changed {
+            out.u32s(&[index as u32, mask]);
+            for (field, &(values, width)) in floats.iter().enumerate() {
+                if mask & 1 << field != 0 {
+                    out.f32s(&values[index * width..(index + 1) * width]);
+                }
+            }
+            if mask & BEHAVIOR_FIELD != 0 {
+                out.u32(self.behaviors[index] as u32);
+            }
+            if mask & LOD_FIELD != 0 {
+                out.u32(self.lods[index] as u32);
+            }
+        }
+        Ok(out.finish())
+    }
+
+    /// Brings this world from the state a `diff` was taken from to the state
+    /// it was taken of, e.g. on a client following a server. Fails, changing
+    /// nothing, when the world is not at that base state, e.g. after a lost
+    /// diff, and the client then needs a whole `save` to `load`.
+    pub fn apply_diff(&mut self, delta: &[u8]) -> Result<(), Error> {
+        let (mut input, version) = Reader::new(delta, DELTA_MAGIC, "an AI world diff")?;
+        if version != DELTA_VERSION {
+            return Err(Error::InvalidInput(
+                "unsupported AI world diff version".into(),
+            ));
+        }
+        let (base_checksum, checksum) = (input.u32()?, input.u32()?);
+        if base_checksum != self.checksum() {
+            return Err(Error::InvalidInput(
+                "the world is not at the state the diff was taken from".into(),
+            ));
+        }
+        let mut next = AiWorld::new(self.timestep as f32)?;
+        next.timestep = self.timestep;
+        next.read_clock(&mut input)?;
+        if input.u8()? != 0 {
+            next.read_handles(&mut input)?;
+        } else {
+            next.slots = self.slots.clone();
+            next.free = self.free.clone();
+            next.handles = self.handles.clone();
+        }
+
+        // Every agent starts from its state here, and new ones from nothing.
+        let count = next.handles.len();
+        let old: Vec<Option<usize>> = next
+            .handles
+            .iter()
+            .map(|&handle| self.index(handle))
+            .collect();
+        let copy = |values: &mut Vec<f32>, old_values: &[f32], width: usize| {
+            *values = vec![0.0; count * width];
+            for (index, from) in old.iter().enumerate() {
+                if let Some(from) = *from {
+                    values[index * width..(index + 1) * width]
+                        .copy_from_slice(&old_values[from * width..(from + 1) * width]);
+                }
+            }
+        };
+        for ((values, width), (old_values, _)) in
+            next.agent_floats_mut().into_iter().zip(self.agent_floats())
+        {
+            copy(values, old_values, width);
+        }
+        copy(&mut next.forces, &self.forces, 2);
+        copy(&mut next.locomotion, &self.locomotion, locomotion::STRIDE);
+        next.behaviors = old
+            .iter()
+            .map(|from| from.map_or(Behavior::Idle, |from| self.behaviors[from]))
+            .collect();
+        next.lods = old
+            .iter()
+            .map(|from| from.map_or(Lod::Full, |from| self.lods[from]))
+            .collect();
+
+        let mut listed = vec![false; count];
+        let mut moved = Vec::new();
+        for _ in 0..input.u32()? {
+            let index = input.u32()? as usize;
+            let mask = input.u32()?;
+            if index >= count
+                || mask > ALL_FIELDS
+                || std::mem::replace(&mut listed[index], true)
+                || (old[index].is_none() && mask != ALL_FIELDS)
+            {
+                return Err(input.invalid());
+            }
+            for (field, (values, width)) in next.agent_floats_mut().into_iter().enumerate() {
+                if mask & 1 << field != 0 {
+                    values[index * width..(index + 1) * width].copy_from_slice(&input.f32s(width)?);
+                }
+            }
+            if mask & BEHAVIOR_FIELD != 0 {
+                let value = input.u32()?;
+                next.behaviors[index] = Behavior::from_u32(value).ok_or_else(|| input.invalid())?;
+            }
+            if mask & LOD_FIELD != 0 {
+                let value = input.u32()?;
+                next.lods[index] = Lod::from_u32(value).ok_or_else(|| input.invalid())?;
+            }
+            if mask & MOTION_FIELDS != 0 {
+                moved.push(index);
+            }
+        }
+        for index in moved {
+            next.reset_locomotion(index);
+        }
+
+        let invalid = input.invalid();
+        input.finish()?;
+        let complete = old
+            .iter()
+            .zip(&listed)
+            .all(|(from, &listed)| from.is_some() || listed);
+        if !complete || !next.handles_consistent() || next.checksum() != checksum {
+            return Err(invalid);
+        }
+        next.recorder = self.recorder.take();
+        next.playback = self.playback.take();
+        *self = next;
+        Ok(())
+    }
+
+    /// Advances by a frame of `elapsed` seconds, running every whole step that
+    /// fits in the accumulated time, and returns how many ran.
+    pub fn tick(&mut self, elapsed: f64) -> u32 {
+        self.record_settings();
+        let recorder = self.recorder.take();
+        if elapsed > 0.0 {
+            self.accumulator += elapsed;
+        }
+        let mut steps = 0;
+        let mut stats = AiStats {
+            agents: self.len(),
+            ..AiStats::default()
+        };
+        while self.accumulator >= self.timestep && steps < self.max_steps {
+            self.accumulator -= self.timestep;
+            self.step();
+            stats.steering_us += self.stats.steering_us;
+            steps += 1;
+        }
+        stats.steps = steps;
+        self.stats = stats;
+        if self.accumulator >= self.timestep {
+            self.accumulator %= self.timestep;
+        }
+        self.recorder = recorder;
+        self.record(|world| Command::Tick {
+            elapsed,
+            checksum: world.checksum(),
+        });
+        steps
+    }
+
+    /// Runs one fixed step right away, leaving the accumulator alone, e.g. to
+    /// drive the world from a lockstep network clock.
+    pub fn step(&mut self) {
+        self.record_settings();
+        let sample = Sample::start();
+        let dt = self.timestep as f32;
+        self.previous.copy_from_slice(&self.positions);
+        let interval = self.reduced_interval.max(1);
+        for index in 0..self.handles.len() {
+            let velocity = pair(&self.velocities, index);
+            let steers = match self.lods[index] {
+                Lod::Full => true,
+                Lod::Reduced => (self.handles[index] & SLOT_MASK)
+                    .wrapping_add(self.steps)
+                    .is_multiple_of(interval),
+                Lod::Frozen => {
+                    set_pair(&mut self.forces, index, Vec2::ZERO);
+                    continue;
+                }
+            };
+            if !steers && self.behaviors[index] != Behavior::Idle {
+                let desired = pair(&self.desired, index);
+                set_pair(&mut self.forces, index, desired - velocity);
+                continue;
+            }
+            let position = pair(&self.positions, index);
+            let heading = pair(&self.headings, index);
+            let target = pair(&self.targets, index);
+            let max_speed = self.max_speeds[index];
+            let desired = match self.behaviors[index] {
+                Behavior::Idle => velocity - velocity / dt,
+                Behavior::Seek => (target - position).normalize() * max_speed,
+                Behavior::Arrive => {
+                    let offset = target - position;
+                    let distance = offset.length();
+                    let slow_radius = self.slow_radii[index];
+                    if distance <= f32::EPSILON {
+                        Vec2::ZERO
+                    } else if distance < slow_radius {
+                        offset * (max_speed / slow_radius)
+                    } else {
+                        offset * (max_speed / distance)
+                    }
+                }
+                Behavior::Flee => (position - target).normalize() * max_speed,
+                Behavior::Wander => {
+                    let jitter = Vec2::new(self.rng.next_signed(), self.rng.next_signed());
+                    let wander = (pair(&self.wander_targets, index) + jitter * self.wander_jitter)
+                        .normalize()
+                        * self.wander_radius;
+                    set_pair(&mut self.wander_targets, index, wander);
+                    let local = wander + Vec2::new(self.wander_distance, 0.0);
+                    velocity + heading * local.x + heading.perp() * local.y
+                }
+            };
+            set_pair(&mut self.desired, index, desired);
+            set_pair(&mut self.forces, index, desired - velocity);
+        }
+        batch::integrate(
+            &mut self.positions,
+            &mut self.velocities,
+            &self.forces,
+            Limit::Each(&self.max_forces),
+            Limit::Each(&self.max_speeds),
+            dt,
+        );
+        for index in 0..self.handles.len() {
+            let row = &mut self.locomotion[index * locomotion::STRIDE..];
+            // The last row holds the speed and heading the step started from.
+            let heading = Vec2::new(row[4], row[5]);
+            let after = pair(&self.velocities, index);
+            let max_force = self.max_forces[index];
+            let motion = Motion {
+                before: heading * row[0],
+                after,
+                heading,
+                max_speed: self.max_speeds[index],
+                dt,
+            };
+            // A vehicle turns fastest when its whole force is spent sideways.
+            let max_turn_rate = max_force / after.length().max(f32::EPSILON);
+            motion.write(row, max_turn_rate, max_force * ACCELERATION_THRESHOLD);
+        }
+        batch::normalize_moving(&mut self.headings, &self.velocities);
+        self.steps += 1;
+        self.time = self.steps as f64 * self.timestep;
+        self.stats = AiStats {
+            steering_us: sample.elapsed_us(),
+            agents: self.len(),
+            steps: 1,
+            ..AiStats::default()
+        };
+        self.record(|world| Command::Step {
+            checksum: world.checksum(),
+        });
+    }
+
+    /// What the last `tick`, or a `step` run on its own, cost: `steering_us`,
+    /// `agents` and `steps`.
+    pub fn stats(&self) -> AiStats {
+        self.stats
+    }
+
+    /// Appends each agent to `out` as a point with lines for its heading, its
+    /// velocity and the steering force of the last step, plus one to its
+    /// target while it seeks, arrives or flees.
+    pub fn debug_geometry(&self, out: &mut DebugGeometry) {
+        let pair =
+            |values: &[f32], index: usize| Vec2::new(values[index * 2], values[index * 2 + 1]);
+        for index in 0..self.handles.len() {
+            let position = pair(&self.positions, index);
+            if matches!(
+                self.behaviors[index],
+                Behavior::Seek | Behavior::Arrive | Behavior::Flee
+            ) {
+                out.line(position, pair(&self.targets, index), debug::TARGET);
+            }
+            out.arrow(position, pair(&self.headings, index), debug::HEADING);
+            out.arrow(position, pair(&self.velocities, index), debug::VELOCITY);
+            out.arrow(position, pair(&self.forces, index), debug::FORCE);
+            out.point(position, debug::AGENT);
+        }
+    }
+}
+
+impl AiWorld {
+    /// The handles in bulk order, as `handles_view` shows them.
+    pub fn handle_buffer(&self) -> &[u32] {
+        &self.handles
+    }
+
+    /// The flat `[x, y, ...]` positions behind `positions_view`.
+    pub fn position_buffer(&self) -> &[f32] {
+        &self.positions
+    }
+
+    pub fn velocity_buffer(&self) -> &[f32] {
+        &self.velocities
+    }
+
+    pub fn heading_buffer(&self) -> &[f32] {
+        &self.headings
+    }
+
+    pub fn target_buffer(&self) -> &[f32] {
+        &self.targets
+    }
+
+    /// The locomotion rows behind `locomotion_view`.
+    pub fn locomotion_buffer(&self) -> &[f32] {
+        &self.locomotion
+    }
+
+    /// Logs a call while recording, with `command` built only then.
+    fn record(&mut self, command: impl FnOnce(&AiWorld) -> Command) {
+        if let Some(mut recorder) = self.recorder.take() {
+            recorder.push(&command(self));
+            self.recorder = Some(recorder);
+        }
+    }
+
+    /// Fills the locomotion row of agent `index` from its current velocity
+    /// and heading, as if it had held them.
+    fn reset_locomotion(&mut self, index: usize) {
+        let velocity = pair(&self.velocities, index);
+        let heading = pair(&self.headings, index);
+        Motion::steady(velocity, heading, self.max_speeds[index]).write(
+            &mut self.locomotion[index * locomotion::STRIDE..],
+            0.0,
+            0.0,
+        );
+    }
+
+    fn write_clock(&self, out: &mut Writer) {
+        out.u32(self.max_steps);
+        out.f32s(&[self.wander_jitter, self.wander_radius, self.wander_distance]);
+        out.u32(self.reduced_interval);
+        out.f64(self.accumulator);
+        out.u32(self.steps);
+        out.u32s(&self.rng.state());
+    }
+
+    fn read_clock(&mut self, input: &mut Reader) -> Result<(), Error> {
+        self.max_steps = input.u32()?;
+        [self.wander_jitter, self.wander_radius, self.wander_distance] =
+            [input.f32()?, input.f32()?, input.f32()?];
+        self.reduced_interval = input.u32()?;
+        self.accumulator = input.f64()?;
+        self.steps = input.u32()?;
+        self.time = self.steps as f64 * self.timestep;
+        self.rng.set_state(&input.u32s(4)?)
+    }
+
+    fn write_handles(&self, out: &mut Writer) {
+        out.u32(self.slots.len() as u32);
+        for slot in &self.slots {
+            out.u32s(&[slot.generation, slot.index, slot.live as u32]);
+        }
+        out.u32(self.free.len() as u32);
+        out.u32s(&self.free);
+        out.u32(self.handles.len() as u32);
+        out.u32s(&self.handles);
+    }
+
+    fn read_handles(&mut self, input: &mut Reader) -> Result<(), Error> {
+        let slot_count = input.u32()? as usize;
+        let slots = input.u32s(slot_count.checked_mul(3).ok_or_else(|| input.invalid())?)?;
+        self.slots = slots
+            .chunks_exact(3)
+            .map(|slot| Slot {
+                generation: slot[0],
+                index: slot[1],
+                live: slot[2] != 0,
+            })
+            .collect();
+        let free_count = input.u32()? as usize;
+        self.free = input.u32s(free_count)?;
+        let count = input.u32()? as usize;
+        self.handles = input.u32s(count)?;
+        Ok(())
+    }
+
+    /// Whether read handle tables hold together: handles index straight into
+    /// the slots and the slots into the arrays, so both directions must agree.
+    fn handles_consistent(&self) -> bool {
+        let live = self.slots.iter().filter(|slot| slot.live).count();
+        let handles_match = self.handles.iter().enumerate().all(|(index, &handle)| {
+            self.slots
+                .get((handle & SLOT_MASK) as usize)
+                .is_some_and(|slot| {
+                    slot.live
+                        && slot.generation == handle >> SLOT_BITS
+                        && slot.index as usize == index
+                })
+        });
+        let mut freed = vec![false; self.slots.len()];
+        let free_match = self.free.iter().all(|&slot| {
+            let dead = self.slots.get(slot as usize).is_some_and(|slot| !slot.live);
+            dead && !std::mem::replace(&mut freed[slot as usize], true)
+        });
+        let generations_match = self
+            .slots
+            .iter()
+            .all(|slot| slot.generation > 0 && slot.generation < GENERATION_LIMIT);
+        live == self.handles.len()
+            && handles_match
+            && free_match
+            && generations_match
+            && self.slots.len() <= SLOT_MASK as usize + 1
+    }
+
+    /// The per-agent float arrays of a snapshot, in saved order, with the
+    /// floats each agent has in them.
+    fn agent_floats(&self) -> [(&[f32], usize); 10] {
+        [
+            (&self.positions, 2),
+            (&self.previous, 2),
+            (&self.velocities, 2),
+            (&self.headings, 2),
+            (&self.targets, 2),
+            (&self.wander_targets, 2),
+            (&self.desired, 2),
+            (&self.slow_radii, 1),
+            (&self.max_speeds, 1),
+            (&self.max_forces, 1),
+        ]
+    }
+
+    fn agent_floats_mut(&mut self) -> [(&mut Vec<f32>, usize); 10] {
+        [
+            (&mut self.positions, 2),
+            (&mut self.previous, 2),
+            (&mut self.velocities, 2),
+            (&mut self.headings, 2),
+            (&mut self.targets, 2),
+            (&mut self.wander_targets, 2),
+            (&mut self.desired, 2),
+            (&mut self.slow_radii, 1),
+            (&mut self.max_speeds, 1),
+            (&mut self.max_forces, 1),
+        ]
+    }
+
+    fn record_settings(&mut self) {
+        if let Some(mut recorder) = self.recorder.take() {
+            recorder.push_settings(self);
+            self.recorder = Some(recorder);
+        }
+    }
+
+    pub(crate) fn recorder(&self) -> Option<&Recorder> {
+        self.recorder.as_ref()
+    }
+
+    /// Swaps the recorder, returning the old one.
+    pub(crate) fn set_recorder(&mut self, recorder: Option<Recorder>) -> Option<Recorder> {
+        std::mem::replace(&mut self.recorder, recorder)
+    }
+
+    pub(crate) fn playback(&self) -> Option<&Playback> {
+        self.playback.as_ref()
+    }
+
+    pub(crate) fn set_playback(&mut self, playback: Option<Playback>) -> Option<Playback> {
+        std::mem::replace(&mut self.playback, playback)
+    }
+
+    /// FNV-1a over the handles, the step count and the state steering
+    /// decides, to tell whether two runs went the same way.
+    pub(crate) fn checksum(&self) -> u32 {
+        let words = self.handles.iter().copied().chain([self.steps]).chain(
+            [&self.positions, &self.velocities, &self.desired]
+                .into_iter()
+                .flatten()
+                .map(|value| value.to_bits()),
+        );
+        words.fold(0x811c_9dc5, |hash, word| {
+            word.to_le_bytes().iter().fold(hash, |hash, &byte| {
+                (hash ^ byte as u32).wrapping_mul(0x0100_0193)
+            })
+        })
+    }
+
+    fn interpolate_into(&self, out: &mut [f32]) {
+        batch::lerp(out, &self.previous, &self.positions, self.alpha());
+    }
+
+    fn check_pairs(&self, len: usize) -> Result<(), Error> {
+        if len == self.positions.len() {
+            Ok(())
+        } else {
+            Err(Error::InvalidInput(format!(
+                "expected {} values for {} agents, got {}",
+                self.positions.len(),
+                self.handles.len(),
+                len
+            )))
+        }
+    }
+
+    /// Index of a live agent in the arrays.
+    fn index(&self, handle: u32) -> Option<usize> {
+        let slot = self.slots.get((handle & SLOT_MASK) as usize)?;
+        (slot.live && slot.generation == handle >> SLOT_BITS).then_some(slot.index as usize)
+    }
+}
+
+fn pair(values: &[f32], index: usize) -> Vec2 {
+    Vec2::new(values[index * 2], values[index * 2 + 1])
+}
+
+fn set_pair(values: &mut [f32], index: usize, value: Vec2) {
+    values[index * 2] = value.x;
+    values[index * 2 + 1] = value.y;
+}
@@ -0,0 +1,225 @@
+use crate::error::Error;
+
+/// Deepest nesting of elements the parser accepts, so a hostile document
+/// cannot overflow the stack.
+const MAX_DEPTH: u32 = 128;
+
+/// A parsed XML element. Text, comments, processing instructions and doctypes
+/// are skipped; attributes keep their document order.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Element {
+    pub(crate) name: String,
+    pub(crate) attributes: Vec<(String, String)>,
+    pub(crate) children: Vec<Element>,
+}
+
+impl Element {
+    pub(crate) fn parse(text: &str) -> Result<Element, Error> {
+        let mut parser = Parser {
+            text,
+            bytes: text.as_bytes(),
+            at: 0,
+            depth: 0,
+        };
+        parser.skip_misc()?;
+        let root = parser.element()?;
+        parser.skip_misc()?;
+        if parser.at < parser.bytes.len() {
+            return Err(parser.error("content after the root element"));
+        }
+        Ok(root)
+    }
+
+    pub(crate) fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    bytes: &'a [u8],
+    at: usize,
+    depth: u32,
+}
+
+impl Parser<'_> {
+    fn error(&self, message: &str) -> Error {
+        Error::InvalidInput(format!("invalid XML at byte {}: {}", self.at, message))
+    }
+
+    fn rest(&self) -> &[u8] {
+        &self.bytes[self.at..]
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.rest().first().is_some_and(u8::is_ascii_whitespace) {
+            self.at += 1;
+        }
+    }
+
+    /// Moves past the next `end`, failing with `what` when there is none.
+    fn skip_past(&mut self, end: &str, what: &str) -> Result<(), Error> {
+        match self.text[self.at..].find(end) {
+            Some(offset) => {
+                self.at += offset + end.len();
+                Ok(())
+            }
+            None => Err(self.error(&format!("unterminated {}", what))),
+        }
+    }
+
+    /// Skips whitespace, comments, `<?...?>` and `<!DOCTYPE ...>` between elements.
+    fn skip_misc(&mut self) -> Result<(), Error> {
+        loop {
+            self.skip_whitespace();
+            if self.rest().starts_with(b"<!--") {
+                self.skip_past("-->", "comment")?;
+            } else if self.rest().starts_with(b"<?") {
+                self.skip_past("?>", "processing instruction")?;
+            } else if self.rest().starts_with(b"<!") {
+                self.skip_past(">", "declaration")?;
+            } else {
+                return Ok(());
+            }
+        }
+    }
+
+    fn name(&mut self) -> Result<String, Error> {
+        let start = self.at;
+        while self.rest().first().is_some_and(|&byte| {
+            byte.is_ascii_alphanumeric() || b"_-.:".contains(&byte) || byte >= 0x80
+        }) {
+            self.at += 1;
+        }
+        if self.at == start {
+            return Err(self.error("expected a name"));
+        }
+        Ok(self.text[start..self.at].to_string())
+    }
+
+    fn element(&mut self) -> Result<Element, Error> {
+        if self.rest().first() != Some(&b'<') {
+            return Err(self.error("expected an element"));
+        }
+        self.at += 1;
+        let name = self.name()?;
+        let mut attributes = Vec::new();
+        loop {
+            self.skip_whitespace();
+            match self.rest().first() {
+                Some(b'/') if self.rest().starts_with(b"/>") => {
+                    self.at += 2;
+                    return Ok(Element {
+                        name,
+                        attributes,
+                        children: Vec::new(),
+                    });
+                }
+                Some(b'>') => {
+                    self.at += 1;
+                    break;
+                }
+                Some(_) => {
+                    let key = self.name()?;
+                    self.skip_whitespace();
+                    if self.rest().first() != Some(&b'=') {
+                        return Err(self.error("expected '='"));
+                    }
+                    self.at += 1;
+                    self.skip_whitespace();
+                    attributes.push((key, self.quoted()?));
+                }
+                None => return Err(self.error("unterminated tag")),
+            }
+        }
+        let mut children = Vec::new();
+        loop {
+            // Text between elements carries nothing here.
+            while self.rest().first().is_some_and(|&byte| byte != b'<') {
+                self.at += 1;
+            }
+            if self.rest().starts_with(b"</") {
+                self.at += 2;
+                let closing = self.name()?;
+                if closing != name {
+                    return Err(self.error(&format!("'{}' closed by '{}'", name, closing)));
+                }
+                self.skip_whitespace();
+                if self.rest().first() != Some(&b'>') {
+                    return Err(self.error("expected '>'"));
+                }
+                self.at += 1;
+                return Ok(Element {
+                    name,
+                    attributes,
+                    children,
+                });
+            }
+            if self.rest().starts_with(b"<![CDATA[") {
+                self.skip_past("]]>", "CDATA section")?;
+            } else if self.rest().starts_with(b"<!") || self.rest().starts_with(b"<?") {
+                self.skip_misc()?;
+            } else if self.rest().is_empty() {
+                return Err(self.error(&format!("'{}' is not closed", name)));
+            } else {
+                if self.depth == MAX_DEPTH {
+                    return Err(self.error("nested too deeply"));
+                }
+                self.depth += 1;
+                let child = self.element();
+                self.depth -= 1;
+                children.push(child?);
+            }
+        }
+    }
+
+    fn quoted(&mut self) -> Result<String, Error> {
+        let quote = match self.rest().first() {
+            Some(&quote @ (b'"' | b'\'')) => quote,
+            _ => return Err(self.error("expected a quoted value")),
+        };
+        self.at += 1;
+        let start = self.at;
+        while self.rest().first().is_some_and(|&byte| byte != quote) {
+            self.at += 1;
+        }
+        if self.rest().is_empty() {
+            return Err(self.error("unterminated attribute value"));
+        }
+        let raw = &self.text[start..self.at];
+        self.at += 1;
+        unescape(raw).ok_or_else(|| self.error("invalid entity"))
+    }
+}
+
+/// Replaces the predefined and numeric character references.
+fn unescape(raw: &str) -> Option<String> {
+    let mut text = String::with_capacity(raw.len());
+    let mut rest = raw;
+    while let Some(start) = rest.find('&') {
+        text.push_str(&rest[..start]);
+        let end = rest[start..].find(';')? + start;
+        let entity = &rest[start + 1..end];
+        let c = match entity {
+            "amp" => '&',
+            "lt" => '<',
+            "gt" => '>',
+            "quot" => '"',
+            "apos" => '\'',
+            _ => {
+                let code = match entity.strip_prefix("#x") {
+                    Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                    None => entity.strip_prefix('#')?.parse().ok()?,
+                };
+                char::from_u32(code)?
+            }
+        };
+        text.push(c);
+        rest = &rest[end + 1..];
+    }
+    text.push_str(rest);
+    Some(text)
+}